@@ -34,7 +34,7 @@ fn main() {
         let bitmap = debug_renderer::render_layout_to_bitmap(
             &layout,
             [bitmap_width, bitmap_height],
-            &mut font_system.font_storage.lock(),
+            &font_system.font_storage.read(),
         );
         measurements.push(render_timer.elapsed());
         last_bitmap = Some(bitmap);
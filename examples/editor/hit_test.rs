@@ -0,0 +1,101 @@
+//! Mapping between points and character indices of a left-aligned layout at the origin.
+//!
+//! The caret can sit before any glyph of a line or after its last one. Each of
+//! these positions is a stop with an x coordinate and the character index the
+//! caret would insert at.
+
+use std::ops::Range;
+
+use suzuri::text::TextLayout;
+
+/// A caret position on a line.
+#[derive(Clone, Copy, Debug)]
+pub struct Stop {
+    pub x: f32,
+    pub char_index: usize,
+}
+
+/// Returns the caret stops of line `line_index`, from left to right.
+pub fn line_stops<T>(layout: &TextLayout<T>, line_index: usize) -> Vec<Stop> {
+    let line = &layout.lines[line_index];
+    let mut stops: Vec<Stop> = line
+        .glyphs
+        .iter()
+        .map(|glyph| Stop {
+            x: glyph.x,
+            char_index: glyph.char_index,
+        })
+        .collect();
+
+    let end = match line.glyphs.last() {
+        Some(glyph) => glyph.char_index + 1,
+        // An empty line only holds the caret at the start of its paragraph.
+        None => paragraph_chars(layout, line_index).map_or(0, |chars| chars.start),
+    };
+    stops.push(Stop {
+        x: line.line_width,
+        char_index: end,
+    });
+    stops
+}
+
+/// Returns the character index nearest to `point`.
+pub fn hit_test<T>(layout: &TextLayout<T>, point: [f32; 2]) -> usize {
+    let Some(last) = layout.lines.len().checked_sub(1) else {
+        return 0;
+    };
+    let line_index = layout
+        .lines
+        .iter()
+        .position(|line| point[1] < line.bottom)
+        .unwrap_or(last);
+
+    line_stops(layout, line_index)
+        .into_iter()
+        .min_by(|a, b| (a.x - point[0]).abs().total_cmp(&(b.x - point[0]).abs()))
+        .map_or(0, |stop| stop.char_index)
+}
+
+/// Returns the line index and x coordinate of the caret before `char_index`.
+pub fn caret_position<T>(layout: &TextLayout<T>, char_index: usize) -> Option<(usize, f32)> {
+    // The end of a wrapped line is also the start of the next one; prefer the latter.
+    let mut line_end = None;
+    for line_index in 0..layout.lines.len() {
+        let stops = line_stops(layout, line_index);
+        let (end, glyphs) = stops.split_last()?;
+        if let Some(stop) = glyphs.iter().find(|stop| stop.char_index == char_index) {
+            return Some((line_index, stop.x));
+        }
+        if end.char_index == char_index && line_end.is_none() {
+            line_end = Some((line_index, end.x));
+        }
+    }
+    line_end
+}
+
+/// Returns `[left, right]` spans of each line covered by the characters in `range`.
+pub fn selection_spans<T>(layout: &TextLayout<T>, range: Range<usize>) -> Vec<(usize, [f32; 2])> {
+    let mut spans = Vec::new();
+    if range.is_empty() {
+        return spans;
+    }
+    for line_index in 0..layout.lines.len() {
+        let stops = line_stops(layout, line_index);
+        let selected: Vec<_> = stops
+            .windows(2)
+            .filter(|pair| range.contains(&pair[0].char_index))
+            .collect();
+        if let (Some(first), Some(last)) = (selected.first(), selected.last()) {
+            spans.push((line_index, [first[0].x, last[1].x]));
+        }
+    }
+    spans
+}
+
+fn paragraph_chars<T>(layout: &TextLayout<T>, line_index: usize) -> Option<Range<usize>> {
+    layout
+        .paragraphs
+        .iter()
+        .find(|paragraph| paragraph.lines.contains(&line_index))
+        .map(|paragraph| paragraph.chars.clone())
+}
@@ -0,0 +1,609 @@
+//! A small text editor built on Suzuri, winit and wgpu.
+//!
+//! It exercises the editable buffer with incremental relayout, IME composition,
+//! hit testing, selection rendering and the wgpu renderer together, and shows
+//! per-frame layout and render statistics in the window title.
+//!
+//! Run with `cargo run --example editor --features wgpu`.
+//!
+//! - Type, or compose with an input method; the composition is shown underlined.
+//! - Click and drag (or hold Shift) to select, Ctrl+A selects everything.
+//! - Arrows, Home, End, Backspace, Delete and Enter work as usual.
+
+mod hit_test;
+mod quads;
+
+use std::{
+    convert::Infallible,
+    num::NonZeroUsize,
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use suzuri::{
+    FontSystem,
+    editor::EditBuffer,
+    fontdb::{self, Family, Query},
+    renderer::{AtlasPacking, AtlasPriority, GpuCacheConfig, WgpuRenderPassController},
+    text::{TextLayout, TextLayoutConfig, WrapStyle},
+};
+use winit::{
+    application::ApplicationHandler,
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    event::{ElementState, Ime, MouseButton, WindowEvent},
+    event_loop::{ActiveEventLoop, EventLoop},
+    keyboard::{Key, ModifiersState, NamedKey},
+    window::{Window, WindowId},
+};
+
+use hit_test::{caret_position, hit_test, line_stops, selection_spans};
+use quads::{QuadBatch, QuadRenderer};
+
+const MARGIN: f32 = 24.0;
+const FONT_SIZE: f32 = 20.0;
+
+// Colors are premultiplied.
+const BACKGROUND: wgpu::Color = wgpu::Color {
+    r: 0.08,
+    g: 0.08,
+    b: 0.1,
+    a: 1.0,
+};
+const TEXT_COLOR: [f32; 4] = [0.9, 0.9, 0.88, 1.0];
+const SELECTION_COLOR: [f32; 4] = [0.12, 0.22, 0.4, 0.8];
+const CARET_COLOR: [f32; 4] = [1.0, 0.8, 0.3, 1.0];
+const PREEDIT_UNDERLINE_COLOR: [f32; 4] = [0.9, 0.9, 0.88, 1.0];
+
+const INITIAL_TEXT: &str = "Suzuri editor example\n\
+\n\
+Every paragraph of this buffer keeps its own cached layout, so typing only lays out \
+the paragraph under the caret again; the layout time in the title bar stays flat as \
+the document grows.\n\
+\n\
+Click and drag to select text, or hold Shift while moving the caret. Input methods \
+compose in place: 日本語, 한국어, 中文.\n";
+
+fn main() {
+    let event_loop = EventLoop::new().expect("failed to create event loop");
+    let mut app = App::default();
+    event_loop.run_app(&mut app).expect("event loop failed");
+}
+
+#[derive(Default)]
+struct App {
+    editor: Option<Editor>,
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.editor.is_none() {
+            self.editor = Some(pollster::block_on(Editor::new(event_loop)));
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        let Some(editor) = &mut self.editor else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::RedrawRequested => editor.render(),
+            event => editor.handle_event(event),
+        }
+    }
+}
+
+/// Layout and render figures of the last frame.
+#[derive(Default)]
+struct FrameStats {
+    layout_time: Duration,
+    render_time: Duration,
+    /// Render passes the text renderer opened; each atlas flush starts a new one.
+    text_passes: usize,
+    /// Draw calls of the text, see [`suzuri::renderer::RenderStats`].
+    text_draw_calls: u64,
+}
+
+struct Editor {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    surface_config: wgpu::SurfaceConfiguration,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    quads: QuadRenderer,
+
+    font_system: FontSystem,
+    font_id: fontdb::ID,
+    buffer: EditBuffer<[f32; 4]>,
+
+    /// Byte offset of the caret.
+    caret: usize,
+    /// Byte offset where the selection started; the selection ends at the caret.
+    anchor: Option<usize>,
+    /// Byte range of the IME composition shown in the buffer.
+    preedit: Option<Range<usize>>,
+    modifiers: ModifiersState,
+    cursor: [f32; 2],
+    dragging: bool,
+    stats: FrameStats,
+}
+
+impl Editor {
+    async fn new(event_loop: &ActiveEventLoop) -> Self {
+        let window = Arc::new(
+            event_loop
+                .create_window(
+                    Window::default_attributes()
+                        .with_title("Suzuri editor")
+                        .with_inner_size(LogicalSize::new(900.0, 600.0)),
+                )
+                .expect("failed to create window"),
+        );
+        window.set_ime_allowed(true);
+
+        // 1. Setup wgpu
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let surface = instance
+            .create_surface(window.clone())
+            .expect("failed to create surface");
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: false,
+            })
+            .await
+            .expect("failed to find an appropriate adapter");
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default())
+            .await
+            .expect("failed to create device");
+
+        let size = window.inner_size();
+        let surface_config = surface
+            .get_default_config(&adapter, size.width.max(1), size.height.max(1))
+            .expect("surface is not supported by the adapter");
+        surface.configure(&device, &surface_config);
+
+        // 2. Setup FontSystem and its wgpu renderer
+        let font_system = FontSystem::new();
+        font_system.load_system_fonts();
+        let font_id = font_system
+            .query(&Query {
+                families: &[Family::Monospace, Family::SansSerif],
+                ..Default::default()
+            })
+            .map(|(id, _)| id)
+            .or_else(|| font_system.faces().first().map(|face| face.id))
+            .expect("no system fonts available");
+
+        #[allow(clippy::unwrap_used)]
+        let configs = [
+            GpuCacheConfig {
+                tile_size: NonZeroUsize::new(32).unwrap(),
+                tiles_per_axis: NonZeroUsize::new(16).unwrap(),
+                texture_size: NonZeroUsize::new(512).unwrap(),
+                priority: AtlasPriority::Normal,
+                padding: 1,
+                extrude_edges: false,
+                packing: AtlasPacking::Grid,
+            },
+            GpuCacheConfig {
+                tile_size: NonZeroUsize::new(64).unwrap(),
+                tiles_per_axis: NonZeroUsize::new(8).unwrap(),
+                texture_size: NonZeroUsize::new(512).unwrap(),
+                priority: AtlasPriority::Normal,
+                padding: 1,
+                extrude_edges: false,
+                packing: AtlasPacking::Grid,
+            },
+        ];
+        font_system.wgpu_init(&device, &configs, &[surface_config.format], None);
+        let quads = QuadRenderer::new(&device, surface_config.format);
+
+        // 3. Setup the buffer
+        let font_size = FONT_SIZE * window.scale_factor() as f32;
+        let buffer = EditBuffer::with_text(INITIAL_TEXT, font_id, font_size, TEXT_COLOR);
+
+        Self {
+            window,
+            surface,
+            surface_config,
+            device,
+            queue,
+            quads,
+            font_system,
+            font_id,
+            caret: buffer.len(),
+            buffer,
+            anchor: None,
+            preedit: None,
+            modifiers: ModifiersState::empty(),
+            cursor: [0.0, 0.0],
+            dragging: false,
+            stats: FrameStats::default(),
+        }
+    }
+
+    fn layout_config(&self) -> TextLayoutConfig {
+        let margin = MARGIN * self.window.scale_factor() as f32;
+        TextLayoutConfig {
+            max_width: Some((self.surface_config.width as f32 - 2.0 * margin).max(0.0)),
+            line_height_scale: 1.2,
+            wrap_style: WrapStyle::WordWrap,
+            hex_box_missing_glyphs: true,
+            ..Default::default()
+        }
+    }
+
+    /// Lays out the buffer, reusing the paragraphs that did not change.
+    fn layout(&mut self) -> &TextLayout<[f32; 4]> {
+        let config = self.layout_config();
+        let font_storage = self.font_system.font_storage.read();
+        self.buffer.layout(&config, &font_storage)
+    }
+
+    fn margin(&self) -> f32 {
+        MARGIN * self.window.scale_factor() as f32
+    }
+
+    fn handle_event(&mut self, event: WindowEvent) {
+        match event {
+            WindowEvent::Resized(size) => {
+                self.surface_config.width = size.width.max(1);
+                self.surface_config.height = size.height.max(1);
+                self.surface.configure(&self.device, &self.surface_config);
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.buffer
+                    .set_style(self.font_id, FONT_SIZE * scale_factor as f32, TEXT_COLOR);
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                return;
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = [position.x as f32, position.y as f32];
+                if !self.dragging {
+                    return;
+                }
+                self.caret = self.hit(self.cursor);
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.dragging = state == ElementState::Pressed;
+                if self.dragging {
+                    let at = self.hit(self.cursor);
+                    self.move_caret(at, self.modifiers.shift_key());
+                } else if self.anchor == Some(self.caret) {
+                    self.anchor = None;
+                }
+            }
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                let extend = self.modifiers.shift_key();
+                let command = self.modifiers.control_key() || self.modifiers.super_key();
+                match &event.logical_key {
+                    Key::Named(NamedKey::ArrowLeft) => {
+                        let at = match self.selection() {
+                            Some(range) if !extend => range.start,
+                            _ => self.previous_boundary(self.caret),
+                        };
+                        self.move_caret(at, extend);
+                    }
+                    Key::Named(NamedKey::ArrowRight) => {
+                        let at = match self.selection() {
+                            Some(range) if !extend => range.end,
+                            _ => self.next_boundary(self.caret),
+                        };
+                        self.move_caret(at, extend);
+                    }
+                    Key::Named(NamedKey::ArrowUp) => self.move_vertically(-1, extend),
+                    Key::Named(NamedKey::ArrowDown) => self.move_vertically(1, extend),
+                    Key::Named(NamedKey::Home) => self.move_to_line_edge(false, extend),
+                    Key::Named(NamedKey::End) => self.move_to_line_edge(true, extend),
+                    Key::Named(NamedKey::Backspace) => {
+                        if self.selection().is_none() {
+                            self.anchor = Some(self.previous_boundary(self.caret));
+                        }
+                        self.replace_selection("");
+                    }
+                    Key::Named(NamedKey::Delete) => {
+                        if self.selection().is_none() {
+                            self.anchor = Some(self.next_boundary(self.caret));
+                        }
+                        self.replace_selection("");
+                    }
+                    Key::Named(NamedKey::Enter) => self.replace_selection("\n"),
+                    Key::Character(key) if command => {
+                        if key.eq_ignore_ascii_case("a") {
+                            self.anchor = Some(0);
+                            self.caret = self.buffer.len();
+                        }
+                    }
+                    _ => match event.text {
+                        Some(text) if !command && !text.chars().any(char::is_control) => {
+                            self.replace_selection(&text)
+                        }
+                        Some(text) if text == "\t" => self.replace_selection("\t"),
+                        _ => return,
+                    },
+                }
+            }
+            WindowEvent::Ime(Ime::Preedit(text, cursor)) => self.set_preedit(&text, cursor),
+            WindowEvent::Ime(Ime::Commit(text)) => {
+                self.set_preedit("", None);
+                self.replace_selection(&text);
+            }
+            _ => return,
+        }
+        self.window.request_redraw();
+    }
+
+    /// Byte range of the selection, if it is not empty.
+    fn selection(&self) -> Option<Range<usize>> {
+        self.anchor
+            .filter(|&anchor| anchor != self.caret)
+            .map(|anchor| anchor.min(self.caret)..anchor.max(self.caret))
+    }
+
+    fn move_caret(&mut self, at: usize, extend: bool) {
+        if extend {
+            self.anchor.get_or_insert(self.caret);
+        } else {
+            self.anchor = None;
+        }
+        self.caret = at;
+    }
+
+    fn replace_selection(&mut self, text: &str) {
+        let range = self.selection().unwrap_or(self.caret..self.caret);
+        self.buffer.replace(range.clone(), text);
+        self.caret = range.start + text.len();
+        self.anchor = None;
+    }
+
+    /// Shows the IME composition `text` in place of the previous one.
+    fn set_preedit(&mut self, text: &str, cursor: Option<(usize, usize)>) {
+        let range = match self.preedit.take() {
+            Some(range) => range,
+            None => {
+                self.replace_selection("");
+                self.caret..self.caret
+            }
+        };
+        self.buffer.replace(range.clone(), text);
+        self.caret = range.start + cursor.map_or(text.len(), |(start, _)| start);
+        self.preedit = (!text.is_empty()).then(|| range.start..range.start + text.len());
+    }
+
+    fn previous_boundary(&self, at: usize) -> usize {
+        let text = self.buffer.slice(0..at);
+        at - text.chars().next_back().map_or(0, char::len_utf8)
+    }
+
+    fn next_boundary(&self, at: usize) -> usize {
+        let text = self.buffer.slice(at..self.buffer.len());
+        at + text.chars().next().map_or(0, char::len_utf8)
+    }
+
+    fn char_index(&self, byte: usize) -> usize {
+        self.buffer.slice(0..byte).chars().count()
+    }
+
+    fn byte_offset(&self, char_index: usize) -> usize {
+        self.buffer
+            .text()
+            .char_indices()
+            .nth(char_index)
+            .map_or(self.buffer.len(), |(byte, _)| byte)
+    }
+
+    /// Returns the byte offset nearest to a window position.
+    fn hit(&mut self, position: [f32; 2]) -> usize {
+        let margin = self.margin();
+        let char_index = hit_test(self.layout(), [position[0] - margin, position[1] - margin]);
+        self.byte_offset(char_index)
+    }
+
+    fn move_vertically(&mut self, lines: isize, extend: bool) {
+        let caret = self.char_index(self.caret);
+        let layout = self.layout();
+        let Some((line, x)) = caret_position(layout, caret) else {
+            return;
+        };
+        let Some(target) = line
+            .checked_add_signed(lines)
+            .and_then(|line| layout.lines.get(line))
+        else {
+            return;
+        };
+        let char_index = hit_test(layout, [x, (target.top + target.bottom) / 2.0]);
+        let at = self.byte_offset(char_index);
+        self.move_caret(at, extend);
+    }
+
+    fn move_to_line_edge(&mut self, end: bool, extend: bool) {
+        let caret = self.char_index(self.caret);
+        let layout = self.layout();
+        let Some((line, _)) = caret_position(layout, caret) else {
+            return;
+        };
+        let stops = line_stops(layout, line);
+        let stop = if end { stops.last() } else { stops.first() };
+        if let Some(stop) = stop {
+            let at = self.byte_offset(stop.char_index);
+            self.move_caret(at, extend);
+        }
+    }
+
+    fn render(&mut self) {
+        let margin = self.margin();
+        let caret = self.char_index(self.caret);
+        let selection = self
+            .selection()
+            .map(|range| self.char_index(range.start)..self.char_index(range.end));
+        let preedit = self
+            .preedit
+            .clone()
+            .map(|range| self.char_index(range.start)..self.char_index(range.end));
+
+        let config = self.layout_config();
+        let layout_timer = Instant::now();
+        let layout = self
+            .buffer
+            .layout(&config, &self.font_system.font_storage.read());
+        self.stats.layout_time = layout_timer.elapsed();
+
+        // Selection, composition underline and caret, in window coordinates.
+        let mut batch = QuadBatch::new([
+            self.surface_config.width as f32,
+            self.surface_config.height as f32,
+        ]);
+        for (line, [left, right]) in selection_spans(layout, selection.unwrap_or(0..0)) {
+            let line = &layout.lines[line];
+            batch.push(
+                [margin + left, margin + line.top],
+                [margin + right, margin + line.bottom],
+                SELECTION_COLOR,
+            );
+        }
+        for (line, [left, right]) in selection_spans(layout, preedit.unwrap_or(0..0)) {
+            let line = &layout.lines[line];
+            batch.push(
+                [margin + left, margin + line.bottom - 2.0],
+                [margin + right, margin + line.bottom],
+                PREEDIT_UNDERLINE_COLOR,
+            );
+        }
+        let caret_rect = caret_position(layout, caret).map(|(line, x)| {
+            let line = &layout.lines[line];
+            (
+                [margin + x - 1.0, margin + line.top],
+                [margin + x + 1.0, margin + line.bottom],
+            )
+        });
+        if let Some((min, max)) = caret_rect {
+            batch.push(min, max, CARET_COLOR);
+        }
+
+        // The renderer draws glyphs at their layout coordinates, so draw a copy moved by the margin.
+        let mut text = layout.clone();
+        text.translate(margin, margin);
+        let lines = text.lines.len();
+
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(&self.device, &self.surface_config);
+                self.window.request_redraw();
+                return;
+            }
+            Err(err) => {
+                eprintln!("Failed to acquire frame: {err}");
+                return;
+            }
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Editor Encoder"),
+            });
+
+        let render_timer = Instant::now();
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Editor Background Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(BACKGROUND),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.quads.draw(&self.device, &mut pass, &batch);
+        }
+        let mut target = OverlayPass {
+            encoder: &mut encoder,
+            view: &view,
+            passes: 0,
+        };
+        let Ok(render_stats) =
+            self.font_system
+                .wgpu_render_to(&text, &self.device, &self.queue, &mut target);
+        self.stats.text_passes = target.passes;
+        self.stats.text_draw_calls = render_stats.draw_calls;
+        self.queue.submit(Some(encoder.finish()));
+        self.stats.render_time = render_timer.elapsed();
+        frame.present();
+
+        if let Some((min, max)) = caret_rect {
+            self.window.set_ime_cursor_area(
+                PhysicalPosition::new(min[0], min[1]),
+                PhysicalSize::new(max[0] - min[0], max[1] - min[1]),
+            );
+        }
+        self.window.set_title(&format!(
+            "Suzuri editor - {} paragraphs, {} lines | layout {:.2?}, render {:.2?}, {} text passes, {} draw calls",
+            self.buffer.len_paragraphs(),
+            lines,
+            self.stats.layout_time,
+            self.stats.render_time,
+            self.stats.text_passes,
+            self.stats.text_draw_calls,
+        ));
+    }
+}
+
+/// Draws text over what the frame already holds and counts the passes it takes.
+struct OverlayPass<'a> {
+    encoder: &'a mut wgpu::CommandEncoder,
+    view: &'a wgpu::TextureView,
+    passes: usize,
+}
+
+impl WgpuRenderPassController<Infallible> for OverlayPass<'_> {
+    fn encoder(&mut self) -> Result<&mut wgpu::CommandEncoder, Infallible> {
+        Ok(self.encoder)
+    }
+
+    fn create_pass(&mut self) -> Result<wgpu::RenderPass<'_>, Infallible> {
+        self.passes += 1;
+        Ok(self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Editor Text Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        }))
+    }
+
+    fn format(&self) -> Result<wgpu::TextureFormat, Infallible> {
+        Ok(self.view.texture().format())
+    }
+
+    fn target_size(&self) -> Result<[f32; 2], Infallible> {
+        let size = self.view.texture().size();
+        Ok([size.width as f32, size.height as f32])
+    }
+}
@@ -0,0 +1,126 @@
+//! Solid rectangles for the selection, caret and IME underline.
+//!
+//! Suzuri only draws glyphs, so the editor brings its own minimal pipeline for the
+//! shapes around them.
+
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Rectangles in pixel coordinates, converted to clip space when pushed.
+pub struct QuadBatch {
+    screen_size: [f32; 2],
+    vertices: Vec<Vertex>,
+}
+
+impl QuadBatch {
+    pub fn new(screen_size: [f32; 2]) -> Self {
+        Self {
+            screen_size,
+            vertices: Vec::new(),
+        }
+    }
+
+    /// Adds the rectangle `[x0, y0]..[x1, y1]` with a premultiplied color.
+    pub fn push(&mut self, min: [f32; 2], max: [f32; 2], color: [f32; 4]) {
+        let to_clip = |[x, y]: [f32; 2]| {
+            [
+                x / self.screen_size[0] * 2.0 - 1.0,
+                1.0 - y / self.screen_size[1] * 2.0,
+            ]
+        };
+        let [x0, y0] = to_clip(min);
+        let [x1, y1] = to_clip(max);
+        for position in [[x0, y0], [x1, y0], [x0, y1], [x0, y1], [x1, y0], [x1, y1]] {
+            self.vertices.push(Vertex { position, color });
+        }
+    }
+}
+
+pub struct QuadRenderer {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl QuadRenderer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Editor Quad Shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Editor Quad Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Editor Quad Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        Self { pipeline }
+    }
+
+    /// Records `batch` into `pass`.
+    pub fn draw(&self, device: &wgpu::Device, pass: &mut wgpu::RenderPass<'_>, batch: &QuadBatch) {
+        if batch.vertices.is_empty() {
+            return;
+        }
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Editor Quad Vertices"),
+            contents: bytemuck::cast_slice(&batch.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(0, buffer.slice(..));
+        pass.draw(0..batch.vertices.len() as u32, 0..1);
+    }
+}
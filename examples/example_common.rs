@@ -1,4 +1,6 @@
 #![allow(clippy::unwrap_used)]
+// Each example only uses part of this shared module.
+#![allow(dead_code)]
 
 use std::collections::HashSet;
 
@@ -6,7 +8,10 @@ use fxhash::FxBuildHasher;
 use suzuri::{
     FontSystem,
     fontdb::{self, Family, Query},
-    text::{HorizontalAlign, TextData, TextElement, TextLayoutConfig, VerticalAlign, WrapStyle},
+    text::{
+        FontSizeSnapping, HorizontalAlign, TextData, TextElement, TextLayoutConfig, VerticalAlign,
+        WrapStyle,
+    },
 };
 
 pub const WIDTH: f32 = 1280.0;
@@ -94,11 +99,17 @@ pub fn make_layout_config(max_width: Option<f32>, max_height: Option<f32>) -> Te
         max_height,
         horizontal_align: HorizontalAlign::Left,
         vertical_align: VerticalAlign::Top,
+        line_height: None,
         line_height_scale: 1.3, // Slightly increased for readability
+        paragraph_spacing: 0.0,
         wrap_style: WrapStyle::WordWrap,
         wrap_hard_break: true,
         word_separators,
         linebreak_char,
+        hex_box_missing_glyphs: false,
+        visualize_whitespace: false,
+        subpixel_positions: 0,
+        font_size_snapping: FontSizeSnapping::Exact,
     }
 }
 
@@ -149,18 +160,27 @@ pub fn build_text_data(
     // --- Header ---
     data.append(TextElement {
         font_id: heading_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 64.0,
         content: "NEON CITY DAILY\n".into(),
         user_data: TextColor::NEON_CYAN,
     });
     data.append(TextElement {
         font_id: heading_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 24.0,
         content: "The Pulse of the Metropolis\t--\tWednesday, October 12, 2154\n".into(),
         user_data: TextColor::MUTED_GRAY,
     });
     data.append(TextElement {
         font_id: mono_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 18.0,
         content: "Weather:\tAcid Rain (Heavy)\tVisibility:\t20%\tAir Quality:\tPoor\n\n".into(),
         user_data: TextColor::NEON_GREEN,
@@ -169,12 +189,18 @@ pub fn build_text_data(
     // --- Section 1: Breaking News ---
     data.append(TextElement {
         font_id: heading_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 48.0,
         content: "# TOP STORIES\n".into(),
         user_data: TextColor::WHITE,
     });
     data.append(TextElement {
         font_id: mono_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 20.0,
         content: "---------------------------------------------------------------------\n".into(),
         user_data: TextColor::NEON_PINK,
@@ -183,12 +209,18 @@ pub fn build_text_data(
     // Article 1
     data.append(TextElement {
         font_id: heading_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 32.0,
         content: "> Sky-High Real Estate?\n".into(),
         user_data: TextColor::GOLD,
     });
     data.append(TextElement {
         font_id: body_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 24.0,
         content: "\tLevitating Condos in Sector 7 reach record prices. \"Gravity is a luxury,\" says lead architect \
                   Dr. Xalor. Constructed with aggregated carbon-nanotubes, these homes offer the best view \
@@ -199,12 +231,18 @@ pub fn build_text_data(
     // Article 2
     data.append(TextElement {
         font_id: heading_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 32.0,
         content: "\n> Cyber-Fashion Week Begins\n".into(),
         user_data: TextColor::GOLD,
     });
     data.append(TextElement {
         font_id: body_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 24.0,
         content: "\tDesigners embrace \"Retro-Analog\" aesthetics. Expect to see more mechanical watches \
                    and non-LED fabrics on the runway this season. Critics call it 'impractical', but the \
@@ -214,6 +252,9 @@ pub fn build_text_data(
     // Tags
     data.append(TextElement {
         font_id: mono_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 18.0,
         content: "#Fashion #Retro #AnalogIsTheNewDigital #NoLatency\n".into(),
         user_data: TextColor::NEON_PINK,
@@ -222,12 +263,18 @@ pub fn build_text_data(
     // Article 3 (Warning)
     data.append(TextElement {
         font_id: heading_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 32.0,
         content: "\n> Traffic Advisory: Maglev Line C\n".into(),
         user_data: TextColor::WARNING_RED,
     });
     data.append(TextElement {
         font_id: body_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 24.0,
         content: "\tDelayed due to rogue AI playing chess with the signaling system. \
                   Authorities are negotiating a draw. Expect delays of 20-30 minutes. \
@@ -239,12 +286,18 @@ pub fn build_text_data(
     // --- Section 2: Classifieds ---
     data.append(TextElement {
         font_id: heading_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 48.0,
         content: "\n# CLASSIFIEDS\n".into(),
         user_data: TextColor::WHITE,
     });
     data.append(TextElement {
         font_id: mono_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 20.0,
         content: "---------------------------------------------------------------------\n".into(),
         user_data: TextColor::NEON_PINK,
@@ -253,12 +306,18 @@ pub fn build_text_data(
     // Ad 1
     data.append(TextElement {
         font_id: heading_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 28.0,
         content: "[SELLING]\tVintage 2020 Keyboard\n".into(),
         user_data: TextColor::NEON_GREEN,
     });
     data.append(TextElement {
         font_id: body_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 22.0,
         content: "\tType:\t\tMechanical switches (Blue)\n\tSound:\t\tDistinct clicky sound\n\tCondition:\tPerfect. A relic of the pre-neural-link era.\n\tPrice:\t\t5000 Credits (Firm)\n\tContact:\tUser_882\n"
             .into(),
@@ -268,12 +327,18 @@ pub fn build_text_data(
     // Ad 2
     data.append(TextElement {
         font_id: heading_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 28.0,
         content: "\n[WANTED]\tAndroid Mechanic\n".into(),
         user_data: TextColor::NEON_GREEN,
     });
     data.append(TextElement {
         font_id: body_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 22.0,
         content: "\tSpec:\t\tEmotional sub-routine debugging\n\tIssue:\t\tHousekeeping bot existential crisis\n\tDetails:\tRefuses to vacuum until it understands the meaning of dust.\n"
             .into(),
@@ -283,12 +348,18 @@ pub fn build_text_data(
     // Ad 3
     data.append(TextElement {
         font_id: heading_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 28.0,
         content: "\n[LOST] Cyber-Dog \"Sparky\"\n".into(),
         user_data: TextColor::NEON_GREEN,
     });
     data.append(TextElement {
         font_id: body_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 22.0,
         content: "\tSmall beagle model, chrome finish. Last seen chasing a holographic cat \
                   near the Data District. Answers to binary commands. Reward offered.\n"
@@ -299,18 +370,27 @@ pub fn build_text_data(
     // --- Footer ---
     data.append(TextElement {
         font_id: mono_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 20.0,
         content: "\n=====================================================================\n".into(),
         user_data: TextColor::MUTED_GRAY,
     });
     data.append(TextElement {
         font_id: mono_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 18.0,
         content: "Crypto-Yen:\t145.2 (+2.1%)\tNeural-Net Load:\tStable\tHappy Hacking\n".into(),
         user_data: TextColor::NEON_CYAN,
     });
     data.append(TextElement {
         font_id: mono_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 16.0,
         content: "Thank you for reading via your optical implant.\tBlink twice to refresh.\n"
             .into(),
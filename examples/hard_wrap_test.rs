@@ -26,12 +26,18 @@ fn main() {
     let mut data = TextData::new();
     data.append(TextElement {
         font_id: heading_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 24.0,
         content: "HardWalk:\n".into(),
         user_data: TextColor::NEON_PINK,
     });
     data.append(TextElement {
         font_id: body_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 18.0,
         // formatted as a single long word without spaces
         content:
@@ -41,6 +47,9 @@ fn main() {
     });
     data.append(TextElement {
         font_id: body_font,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 14.0,
         content: "\n(The word above should be broken across multiple lines)".into(),
         user_data: TextColor::MUTED_GRAY,
@@ -7,7 +7,10 @@ use suzuri::{
     FontSystem,
     fontdb::{self, Family, Query},
     renderer::{CpuCacheConfig, debug_renderer},
-    text::{HorizontalAlign, TextData, TextElement, TextLayoutConfig, VerticalAlign, WrapStyle},
+    text::{
+        FontSizeSnapping, HorizontalAlign, TextData, TextElement, TextLayoutConfig, VerticalAlign,
+        WrapStyle,
+    },
 };
 
 fn make_config(max_width: Option<f32>, max_height: Option<f32>) -> TextLayoutConfig {
@@ -27,11 +30,17 @@ fn make_config(max_width: Option<f32>, max_height: Option<f32>) -> TextLayoutCon
         max_height,
         horizontal_align: HorizontalAlign::Left,
         vertical_align: VerticalAlign::Top,
+        line_height: None,
         line_height_scale: 1.0,
+        paragraph_spacing: 0.0,
         wrap_style: WrapStyle::WordWrap,
         wrap_hard_break: true,
         word_separators,
         linebreak_char,
+        hex_box_missing_glyphs: false,
+        visualize_whitespace: false,
+        subpixel_positions: 0,
+        font_size_snapping: FontSizeSnapping::Exact,
     }
 }
 
@@ -82,6 +91,9 @@ fn main() {
 
     data.append(TextElement {
         font_id,
+        font_query: None,
+        line_height: None,
+        paragraph_spacing: None,
         font_size: 24.0,
         content: text_content,
         user_data: (),
@@ -111,7 +123,7 @@ fn main() {
             let bitmap = debug_renderer::render_layout_to_bitmap(
                 &layout,
                 image_size,
-                &mut font_system.font_storage.lock(),
+                &font_system.font_storage.read(),
             );
             // Prevent optimization
             std::hint::black_box(bitmap);
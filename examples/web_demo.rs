@@ -0,0 +1,315 @@
+//! Renders text with wgpu into a window, natively or on a web page.
+//!
+//! Run natively with `cargo run --example web_demo --features wgpu`.
+//!
+//! In the browser, the demo draws into a canvas with WebGPU and loads its font
+//! from `font.ttf` next to the page, as there are no system fonts on the web:
+//!
+//! ```sh
+//! cargo build --example web_demo --features wgpu --target wasm32-unknown-unknown --release
+//! wasm-bindgen --target web --out-dir web target/wasm32-unknown-unknown/release/examples/web_demo.wasm
+//! ```
+//!
+//! Then copy a font to `web/font.ttf` and serve `web/` with an `index.html` containing
+//! `<script type="module">import init from "./web_demo.js"; init();</script>`.
+
+use std::{num::NonZeroUsize, sync::Arc};
+
+use suzuri::{
+    FontSystem, fontdb,
+    renderer::{AtlasPacking, AtlasPriority, GpuCacheConfig},
+    text::{TextData, TextElement, TextLayoutConfig, WrapStyle},
+};
+use winit::{
+    application::ApplicationHandler,
+    dpi::LogicalSize,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy},
+    window::{Window, WindowId},
+};
+
+const MARGIN: f32 = 24.0;
+#[cfg(target_arch = "wasm32")]
+const FONT_URL: &str = "font.ttf";
+
+// Colors are premultiplied.
+const BACKGROUND: wgpu::Color = wgpu::Color {
+    r: 0.08,
+    g: 0.08,
+    b: 0.1,
+    a: 1.0,
+};
+const TEXT_COLOR: [f32; 4] = [0.9, 0.9, 0.88, 1.0];
+const ACCENT_COLOR: [f32; 4] = [1.0, 0.8, 0.3, 1.0];
+
+fn main() {
+    let event_loop = EventLoop::with_user_event()
+        .build()
+        .expect("failed to create event loop");
+    let app = App {
+        proxy: event_loop.create_proxy(),
+        demo: None,
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut app = app;
+        event_loop.run_app(&mut app).expect("event loop failed");
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::EventLoopExtWebSys;
+        event_loop.spawn_app(app);
+    }
+}
+
+struct App {
+    /// Receives the demo once it is set up, which is asynchronous on the web.
+    proxy: EventLoopProxy<Demo>,
+    demo: Option<Demo>,
+}
+
+impl ApplicationHandler<Demo> for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.demo.is_some() {
+            return;
+        }
+        let attributes = Window::default_attributes()
+            .with_title("Suzuri web demo")
+            .with_inner_size(LogicalSize::new(800.0, 600.0));
+        #[cfg(target_arch = "wasm32")]
+        let attributes = {
+            use winit::platform::web::WindowAttributesExtWebSys;
+            attributes.with_append(true)
+        };
+        let window = Arc::new(
+            event_loop
+                .create_window(attributes)
+                .expect("failed to create window"),
+        );
+
+        let proxy = self.proxy.clone();
+        let setup = async move {
+            let demo = Demo::new(window).await;
+            let _ = proxy.send_event(demo);
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        pollster::block_on(setup);
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(setup);
+    }
+
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, demo: Demo) {
+        demo.window.request_redraw();
+        self.demo = Some(demo);
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        let Some(demo) = &mut self.demo else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => {
+                demo.surface_config.width = size.width.max(1);
+                demo.surface_config.height = size.height.max(1);
+                demo.surface.configure(&demo.device, &demo.surface_config);
+                demo.window.request_redraw();
+            }
+            WindowEvent::RedrawRequested => demo.render(),
+            _ => {}
+        }
+    }
+}
+
+struct Demo {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    surface_config: wgpu::SurfaceConfiguration,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    font_system: FontSystem,
+    text: TextData<[f32; 4]>,
+}
+
+impl Demo {
+    async fn new(window: Arc<Window>) -> Self {
+        // 1. Setup wgpu
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let surface = instance
+            .create_surface(window.clone())
+            .expect("failed to create surface");
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: false,
+            })
+            .await
+            .expect("failed to find an appropriate adapter");
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                required_limits: wgpu::Limits::downlevel_webgl2_defaults()
+                    .using_resolution(adapter.limits()),
+                ..Default::default()
+            })
+            .await
+            .expect("failed to create device");
+
+        let size = window.inner_size();
+        let surface_config = surface
+            .get_default_config(&adapter, size.width.max(1), size.height.max(1))
+            .expect("surface is not supported by the adapter");
+        surface.configure(&device, &surface_config);
+
+        // 2. Setup FontSystem and its wgpu renderer
+        let font_system = FontSystem::new();
+        let font_id = load_font(&font_system).await;
+
+        #[allow(clippy::unwrap_used)]
+        let configs = [
+            GpuCacheConfig {
+                tile_size: NonZeroUsize::new(32).unwrap(),
+                tiles_per_axis: NonZeroUsize::new(16).unwrap(),
+                texture_size: NonZeroUsize::new(512).unwrap(),
+                priority: AtlasPriority::Normal,
+                padding: 1,
+                extrude_edges: false,
+                packing: AtlasPacking::Grid,
+            },
+            GpuCacheConfig {
+                tile_size: NonZeroUsize::new(64).unwrap(),
+                tiles_per_axis: NonZeroUsize::new(8).unwrap(),
+                texture_size: NonZeroUsize::new(512).unwrap(),
+                priority: AtlasPriority::Normal,
+                padding: 1,
+                extrude_edges: false,
+                packing: AtlasPacking::Grid,
+            },
+        ];
+        font_system.wgpu_init(&device, &configs, &[surface_config.format], None);
+
+        // 3. Setup the text
+        let scale = window.scale_factor() as f32;
+        let mut text = TextData::new();
+        for (font_size, content, color) in [
+            (40.0, "Suzuri on the web\n", ACCENT_COLOR),
+            (
+                20.0,
+                "This text is laid out by Suzuri and drawn by its wgpu renderer, \
+                 which runs on WebGPU in the browser and on Vulkan, Metal or DirectX \
+                 natively. Resize the window to rewrap the paragraph.\n",
+                TEXT_COLOR,
+            ),
+        ] {
+            text.append(TextElement {
+                font_id,
+                font_query: None,
+                line_height: None,
+                paragraph_spacing: None,
+                font_size: font_size * scale,
+                content: content.into(),
+                user_data: color,
+            });
+        }
+
+        Self {
+            window,
+            surface,
+            surface_config,
+            device,
+            queue,
+            font_system,
+            text,
+        }
+    }
+
+    fn render(&mut self) {
+        let margin = MARGIN * self.window.scale_factor() as f32;
+        let config = TextLayoutConfig {
+            max_width: Some((self.surface_config.width as f32 - 2.0 * margin).max(0.0)),
+            line_height_scale: 1.2,
+            wrap_style: WrapStyle::WordWrap,
+            ..Default::default()
+        };
+        let mut layout = self.font_system.layout_text(&self.text, &config);
+        layout.translate(margin, margin);
+
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(&self.device, &self.surface_config);
+                self.window.request_redraw();
+                return;
+            }
+            Err(err) => {
+                eprintln!("Failed to acquire frame: {err}");
+                return;
+            }
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Web Demo Encoder"),
+            });
+        {
+            let _pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Web Demo Clear Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(BACKGROUND),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+        }
+        self.font_system
+            .wgpu_render(&layout, &self.device, &self.queue, &mut encoder, &view);
+        self.queue.submit(Some(encoder.finish()));
+        frame.present();
+    }
+}
+
+/// Loads the system fonts and picks a sans-serif face.
+#[cfg(not(target_arch = "wasm32"))]
+async fn load_font(font_system: &FontSystem) -> fontdb::ID {
+    font_system.load_system_fonts();
+    font_system
+        .query(&fontdb::Query {
+            families: &[fontdb::Family::SansSerif],
+            ..Default::default()
+        })
+        .map(|(id, _)| id)
+        .expect("no system fonts available")
+}
+
+/// Fetches [`FONT_URL`] and loads it from its bytes.
+#[cfg(target_arch = "wasm32")]
+async fn load_font(font_system: &FontSystem) -> fontdb::ID {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let window = web_sys::window().expect("no browser window");
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(FONT_URL))
+        .await
+        .expect("failed to fetch the font")
+        .dyn_into()
+        .expect("fetch did not return a response");
+    let buffer = JsFuture::from(response.array_buffer().expect("failed to read the font"))
+        .await
+        .expect("failed to read the font");
+    let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+
+    font_system
+        .load_font_binary(bytes)
+        .expect("failed to load the font")[0]
+}
@@ -1,7 +1,10 @@
 use std::num::NonZeroUsize;
 
 use image::{ImageBuffer, Rgba};
-use suzuri::{FontSystem, renderer::GpuCacheConfig};
+use suzuri::{
+    FontSystem,
+    renderer::{AtlasPacking, AtlasPriority, GpuCacheConfig},
+};
 
 mod example_common;
 use example_common::{WIDTH, build_text_data, load_fonts, make_layout_config};
@@ -50,11 +53,19 @@ async fn run() {
             tile_size: NonZeroUsize::new(32).unwrap(),
             tiles_per_axis: NonZeroUsize::new(16).unwrap(),
             texture_size: NonZeroUsize::new(512).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
         },
         GpuCacheConfig {
             tile_size: NonZeroUsize::new(64).unwrap(),
             tiles_per_axis: NonZeroUsize::new(8).unwrap(),
             texture_size: NonZeroUsize::new(512).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
         },
     ];
 
@@ -67,7 +78,7 @@ async fn run() {
     let (heading_font, body_font, mono_font) = load_fonts(&font_system);
     let data = build_text_data(heading_font, body_font, mono_font);
 
-    font_system.wgpu_init(&device, &configs, &[texture_format]);
+    font_system.wgpu_init(&device, &configs, &[texture_format], None);
 
     let layout_timer = std::time::Instant::now();
     let layout = font_system.layout_text(&data, &config);
@@ -138,7 +149,7 @@ async fn run() {
         }
 
         let start = std::time::Instant::now();
-        font_system.wgpu_render(&layout, &device, &mut encoder, &target_view);
+        font_system.wgpu_render(&layout, &device, &queue, &mut encoder, &target_view);
         measurements.push(start.elapsed());
 
         if i == 1 {
@@ -0,0 +1,803 @@
+//! Editable text with incremental relayout.
+//!
+//! [`EditBuffer`] stores its text in a chunked rope and keeps one cached layout
+//! per paragraph. Edits only invalidate the paragraphs they touch, so laying out
+//! the buffer after typing a character re-shapes a single paragraph no matter
+//! how long the document is.
+
+mod rope;
+
+use std::ops::Range;
+
+use crate::{
+    font_storage::FontStorage,
+    text::{
+        HorizontalAlign, ParagraphInfo, TextData, TextElement, TextLayout, TextLayoutConfig,
+        TextLayoutLine, VerticalAlign, WordBox,
+    },
+};
+
+use rope::Rope;
+
+/// A paragraph of the buffer, including its trailing line break.
+#[derive(Clone, Debug)]
+struct Paragraph<T> {
+    bytes: usize,
+    chars: usize,
+    ends_with_break: bool,
+    /// Left- and top-aligned layout of the paragraph, `None` when it needs relayout.
+    layout: Option<TextLayout<T>>,
+}
+
+impl<T> Paragraph<T> {
+    fn dirty(text: &str) -> Self {
+        Self {
+            bytes: text.len(),
+            chars: text.chars().count(),
+            ends_with_break: text.ends_with('\n'),
+            layout: None,
+        }
+    }
+}
+
+/// Paragraphs edited since the layout was composed.
+///
+/// The composed layout is up to date except for the paragraphs after its first
+/// `prefix` and before its last `suffix` ones.
+#[derive(Clone, Copy, Debug)]
+struct Edited {
+    prefix: usize,
+    suffix: usize,
+    /// Change in the length of the text in bytes.
+    bytes: isize,
+}
+
+/// Editable single-style text buffer backed by a rope.
+///
+/// Byte offsets passed to the editing methods must lie on char boundaries, like
+/// with [`String`]. Inserted `\r\n` and `\r` line breaks are stored as `\n`, so
+/// the buffer may end up shorter than the inserted text. [`Self::layout`] returns
+/// the same result as laying out the whole text at once, except that an empty
+/// buffer still has one empty line for the caret.
+#[derive(Clone, Debug)]
+pub struct EditBuffer<T: Clone> {
+    text: Rope,
+    paragraphs: Vec<Paragraph<T>>,
+    font_id: fontdb::ID,
+    font_size: f32,
+    user_data: T,
+    /// Composed layout and the config it was made with.
+    layout: Option<(TextLayoutConfig, TextLayout<T>)>,
+    /// Paragraphs to replace in the composed layout, `None` when it is up to date.
+    edited: Option<Edited>,
+    #[cfg(test)]
+    paragraphs_laid_out: usize,
+    #[cfg(test)]
+    paragraphs_composed: usize,
+}
+
+impl<T: Clone> EditBuffer<T> {
+    /// Creates an empty buffer whose text uses the given style.
+    pub fn new(font_id: fontdb::ID, font_size: f32, user_data: T) -> Self {
+        Self {
+            text: Rope::new(),
+            paragraphs: vec![Paragraph::dirty("")],
+            font_id,
+            font_size,
+            user_data,
+            layout: None,
+            edited: None,
+            #[cfg(test)]
+            paragraphs_laid_out: 0,
+            #[cfg(test)]
+            paragraphs_composed: 0,
+        }
+    }
+
+    /// Creates a buffer containing `text`.
+    pub fn with_text(text: &str, font_id: fontdb::ID, font_size: f32, user_data: T) -> Self {
+        let mut buffer = Self::new(font_id, font_size, user_data);
+        buffer.insert(0, text);
+        buffer
+    }
+
+    /// Length of the text in bytes.
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    /// Returns `true` if the buffer contains no text.
+    pub fn is_empty(&self) -> bool {
+        self.text.len() == 0
+    }
+
+    /// Number of paragraphs, which is one more than the number of line breaks.
+    pub fn len_paragraphs(&self) -> usize {
+        self.paragraphs.len()
+    }
+
+    /// Returns the whole text.
+    pub fn text(&self) -> String {
+        self.text.chunks().collect()
+    }
+
+    /// Returns the text in the byte `range`.
+    pub fn slice(&self, range: Range<usize>) -> String {
+        self.text.slice(range)
+    }
+
+    /// Iterates over the text in storage-sized pieces without copying it.
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.text.chunks()
+    }
+
+    /// Inserts `text` at byte offset `at`.
+    pub fn insert(&mut self, at: usize, text: &str) {
+        self.replace(at..at, text);
+    }
+
+    /// Deletes the text in the byte `range`.
+    pub fn delete(&mut self, range: Range<usize>) {
+        self.replace(range, "");
+    }
+
+    /// Replaces the text in the byte `range` with `text`.
+    ///
+    /// Line breaks in `text` are normalized to `\n`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or does not lie on char boundaries.
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        assert!(
+            range.start <= range.end && range.end <= self.text.len(),
+            "edit range {range:?} out of bounds for a buffer of {} bytes",
+            self.text.len()
+        );
+        // The layout breaks lines at `\r` as well, so `\r\n` would be two breaks.
+        let normalized;
+        let text = if text.contains('\r') {
+            normalized = text.replace("\r\n", "\n").replace('\r', "\n");
+            normalized.as_str()
+        } else {
+            text
+        };
+
+        // Rebuild every paragraph the edit touches from its surrounding text.
+        let (first, first_start) = self.paragraph_at(range.start);
+        let (last, last_start) = self.paragraph_at(range.end);
+        let last_end = last_start + self.paragraphs[last].bytes;
+
+        let mut segment = self.text.slice(first_start..range.start);
+        segment.push_str(text);
+        segment.push_str(&self.text.slice(range.end..last_end));
+
+        self.text.remove(range.clone());
+        self.text.insert(range.start, text);
+
+        let mut paragraphs: Vec<_> = segment
+            .split_inclusive('\n')
+            .map(Paragraph::dirty)
+            .collect();
+        // A trailing line break starts a new paragraph only at the end of the buffer.
+        if last + 1 == self.paragraphs.len() && (segment.is_empty() || segment.ends_with('\n')) {
+            paragraphs.push(Paragraph::dirty(""));
+        }
+        let suffix = self.paragraphs.len() - 1 - last;
+        self.paragraphs.splice(first..=last, paragraphs);
+
+        if self.layout.is_some() {
+            let bytes = text.len() as isize - range.len() as isize;
+            self.edited = Some(match self.edited {
+                Some(edited) => Edited {
+                    prefix: edited.prefix.min(first),
+                    suffix: edited.suffix.min(suffix),
+                    bytes: edited.bytes + bytes,
+                },
+                None => Edited {
+                    prefix: first,
+                    suffix,
+                    bytes,
+                },
+            });
+        }
+    }
+
+    /// Replaces the style of the whole text, invalidating every paragraph.
+    pub fn set_style(&mut self, font_id: fontdb::ID, font_size: f32, user_data: T) {
+        self.font_id = font_id;
+        self.font_size = font_size;
+        self.user_data = user_data;
+        self.invalidate();
+    }
+
+    /// Discards all cached layouts, e.g. after fonts have changed.
+    pub fn invalidate(&mut self) {
+        for paragraph in &mut self.paragraphs {
+            paragraph.layout = None;
+        }
+        self.layout = None;
+        self.edited = None;
+    }
+
+    /// Lays out the buffer, reusing the layouts of paragraphs that were not edited.
+    ///
+    /// Only the edited paragraphs are laid out and composed again; the lines after
+    /// them are moved. Changing `config` between calls lays out every paragraph again.
+    pub fn layout(
+        &mut self,
+        config: &TextLayoutConfig,
+        font_storage: &FontStorage,
+    ) -> &TextLayout<T> {
+        if self
+            .layout
+            .as_ref()
+            .is_some_and(|(cached, _)| cached != config)
+        {
+            self.invalidate();
+        }
+
+        if self.layout.is_none() || self.edited.is_some() {
+            let paragraph_config = TextLayoutConfig {
+                max_height: None,
+                horizontal_align: HorizontalAlign::Left,
+                vertical_align: VerticalAlign::Top,
+                ..config.clone()
+            };
+            let mut start = 0;
+            for index in 0..self.paragraphs.len() {
+                let bytes = self.paragraphs[index].bytes;
+                if self.paragraphs[index].layout.is_none() {
+                    let text = self.text.slice(start..start + bytes);
+                    let layout = self.layout_paragraph(&text, &paragraph_config, font_storage);
+                    self.paragraphs[index].layout = Some(layout);
+                }
+                start += bytes;
+            }
+        }
+
+        let layout = match (self.layout.take(), self.edited.take()) {
+            (Some((_, mut layout)), Some(edited)) => {
+                self.recompose(&mut layout, edited);
+                layout
+            }
+            (Some((_, layout)), None) => layout,
+            (None, _) => self.compose(config),
+        };
+        &self.layout.insert((config.clone(), layout)).1
+    }
+
+    /// Returns the index and start byte of the paragraph containing byte `at`.
+    fn paragraph_at(&self, at: usize) -> (usize, usize) {
+        let mut start = 0;
+        for (index, paragraph) in self.paragraphs.iter().enumerate() {
+            if at < start + paragraph.bytes || index + 1 == self.paragraphs.len() {
+                return (index, start);
+            }
+            start += paragraph.bytes;
+        }
+        (0, 0)
+    }
+
+    /// Lays out one paragraph, keeping only the lines of its first paragraph.
+    fn layout_paragraph(
+        &mut self,
+        text: &str,
+        config: &TextLayoutConfig,
+        font_storage: &FontStorage,
+    ) -> TextLayout<T> {
+        #[cfg(test)]
+        {
+            self.paragraphs_laid_out += 1;
+        }
+
+        // An empty paragraph is laid out as a line break so it gets a line of its own.
+        let content = text.strip_suffix('\n').unwrap_or(text);
+        let content = if content.is_empty() { "\n" } else { content };
+
+        let mut data = TextData::new();
+        data.append(TextElement {
+            font_id: self.font_id,
+            font_query: None,
+            line_height: None,
+            paragraph_spacing: None,
+            font_size: self.font_size,
+            content: content.to_string(),
+            user_data: self.user_data.clone(),
+        });
+        let mut layout = data.layout(config, font_storage);
+
+        let line_count = layout
+            .paragraphs
+            .first()
+            .map_or(layout.lines.len(), |paragraph| paragraph.lines.end);
+        layout.lines.truncate(line_count);
+        layout.words.retain(|word| word.line < line_count);
+        layout.total_height = layout.lines.last().map_or(0.0, |line| line.bottom);
+        layout
+    }
+
+    /// Stacks the paragraph layouts and applies the alignment of `config`.
+    fn compose(&mut self, config: &TextLayoutConfig) -> TextLayout<T> {
+        let mut stack = Stack::new(0, 0.0, 0, 0);
+        for index in 0..self.paragraphs.len() {
+            self.stack_paragraph(&mut stack, index, config);
+        }
+        let Stack {
+            mut lines,
+            mut words,
+            paragraphs,
+            cursor_y,
+            ..
+        } = stack;
+
+        // Alignment, as in the layout engine.
+        let total_height = cursor_y;
+        let total_width = lines.iter().map(|line| line.line_width).fold(0.0, f32::max);
+        let target_width = config.max_width.unwrap_or(total_width);
+        let vertical_offset = vertical_offset(config, total_height);
+
+        for line in &mut lines {
+            let horizontal_offset = horizontal_offset(config, target_width, line.line_width);
+            move_line(line, horizontal_offset, vertical_offset);
+        }
+        for word in &mut words {
+            let horizontal_offset =
+                horizontal_offset(config, target_width, lines[word.line].line_width);
+            word.rect = word
+                .rect
+                .translate(euclid::Vector2D::new(horizontal_offset, vertical_offset));
+        }
+
+        TextLayout {
+            config: config.clone(),
+            total_height,
+            total_width,
+            lines,
+            paragraphs,
+            words,
+            clip_rect: None,
+        }
+    }
+
+    /// Replaces the edited paragraphs of a composed layout, moving the lines
+    /// around them.
+    ///
+    /// Lines before the edit only move when the alignment offsets change, e.g.
+    /// when centered text gets taller.
+    fn recompose(&mut self, layout: &mut TextLayout<T>, edited: Edited) {
+        let config = layout.config.clone();
+        let Edited {
+            prefix,
+            suffix,
+            bytes,
+        } = edited;
+        let old_end = layout.paragraphs.len() - suffix;
+        let new_end = self.paragraphs.len() - suffix;
+        let old_vertical_offset = vertical_offset(&config, layout.total_height);
+        let old_target_width = config.max_width.unwrap_or(layout.total_width);
+
+        // Lines of the edited paragraphs; every paragraph has at least one.
+        let old_lines = match prefix < old_end {
+            true => layout.paragraphs[prefix].lines.start..layout.paragraphs[old_end - 1].lines.end,
+            false => {
+                let start = (layout.paragraphs.get(prefix))
+                    .map_or(layout.lines.len(), |paragraph| paragraph.lines.start);
+                start..start
+            }
+        };
+        let start_y = match old_lines.start {
+            0 => 0.0,
+            start => layout.lines[start - 1].bottom - old_vertical_offset,
+        };
+        let (chars, bytes_before) = self.paragraphs[..prefix]
+            .iter()
+            .fold((0, 0), |(chars, bytes), paragraph| {
+                (chars + paragraph.chars, bytes + paragraph.bytes)
+            });
+
+        let mut stack = Stack::new(old_lines.start, start_y, chars, bytes_before);
+        for index in prefix..new_end {
+            self.stack_paragraph(&mut stack, index, &config);
+        }
+
+        // How far the paragraphs after the edit move, before alignment.
+        let following = layout.lines.get(old_lines.end).map(|line| {
+            let old_top = line.top - old_vertical_offset;
+            let new_top = match old_lines.start + stack.lines.len() {
+                0 => stack.cursor_y,
+                _ => stack.cursor_y + config.paragraph_spacing,
+            };
+            new_top - old_top
+        });
+        let total_height = match following {
+            Some(shift) => layout.total_height + shift,
+            None => stack.cursor_y,
+        };
+        let shift_y = following.unwrap_or(0.0);
+        let shift_lines = stack.lines.len() as isize - old_lines.len() as isize;
+        let shift_chars = stack.char_offset as isize
+            - layout
+                .paragraphs
+                .get(old_end)
+                .map_or(stack.char_offset, |paragraph| paragraph.chars.start)
+                as isize;
+
+        let total_width = (layout.lines[..old_lines.start].iter())
+            .chain(&layout.lines[old_lines.end..])
+            .chain(&stack.lines)
+            .map(|line| line.line_width)
+            .fold(0.0, f32::max);
+        let vertical_offset = vertical_offset(&config, total_height);
+        let target_width = config.max_width.unwrap_or(total_width);
+        let realign = vertical_offset != old_vertical_offset || target_width != old_target_width;
+        // Horizontal movement of an unedited line of the given width.
+        let move_x = |line_width: f32| {
+            horizontal_offset(&config, target_width, line_width)
+                - horizontal_offset(&config, old_target_width, line_width)
+        };
+
+        // Lines.
+        if realign {
+            for line in &mut layout.lines[..old_lines.start] {
+                move_line(
+                    line,
+                    move_x(line.line_width),
+                    vertical_offset - old_vertical_offset,
+                );
+            }
+        }
+        for line in &mut layout.lines[old_lines.end..] {
+            let move_y = shift_y + vertical_offset - old_vertical_offset;
+            move_line(line, move_x(line.line_width), move_y);
+            for glyph in &mut line.glyphs {
+                glyph.char_index = glyph.char_index.wrapping_add_signed(shift_chars);
+            }
+        }
+        for line in &mut stack.lines {
+            move_line(
+                line,
+                horizontal_offset(&config, target_width, line.line_width),
+                vertical_offset,
+            );
+        }
+        let old_words = layout
+            .words
+            .partition_point(|word| word.line < old_lines.start)
+            ..layout
+                .words
+                .partition_point(|word| word.line < old_lines.end);
+        layout
+            .lines
+            .splice(old_lines.start..old_lines.end, stack.lines);
+
+        // Words, positioned by the lines they are on.
+        let lines = &layout.lines;
+        if realign {
+            for word in &mut layout.words[..old_words.start] {
+                let line_width = lines[word.line].line_width;
+                let offset = euclid::Vector2D::new(
+                    move_x(line_width),
+                    vertical_offset - old_vertical_offset,
+                );
+                word.rect = word.rect.translate(offset);
+            }
+        }
+        for word in &mut layout.words[old_words.end..] {
+            word.line = word.line.wrapping_add_signed(shift_lines);
+            word.bytes = word.bytes.start.wrapping_add_signed(bytes)
+                ..word.bytes.end.wrapping_add_signed(bytes);
+            let line = &lines[word.line];
+            word.rect.min.x += move_x(line.line_width);
+            word.rect.min.y = line.top;
+            word.rect.max.x += move_x(line.line_width);
+            word.rect.max.y = line.bottom;
+        }
+        for word in &mut stack.words {
+            let line = &lines[word.line];
+            let horizontal_offset = horizontal_offset(&config, target_width, line.line_width);
+            word.rect.min.x += horizontal_offset;
+            word.rect.min.y = line.top;
+            word.rect.max.x += horizontal_offset;
+            word.rect.max.y = line.bottom;
+        }
+        layout.words.splice(old_words, stack.words);
+
+        // Paragraphs.
+        for paragraph in &mut layout.paragraphs[old_end..] {
+            paragraph.lines = paragraph.lines.start.wrapping_add_signed(shift_lines)
+                ..paragraph.lines.end.wrapping_add_signed(shift_lines);
+            paragraph.chars = paragraph.chars.start.wrapping_add_signed(shift_chars)
+                ..paragraph.chars.end.wrapping_add_signed(shift_chars);
+        }
+        layout.paragraphs.splice(prefix..old_end, stack.paragraphs);
+
+        layout.total_height = total_height;
+        layout.total_width = total_width;
+    }
+
+    /// Appends the layout of paragraph `index` to `stack`, before alignment.
+    fn stack_paragraph(&mut self, stack: &mut Stack<T>, index: usize, config: &TextLayoutConfig) {
+        #[cfg(test)]
+        {
+            self.paragraphs_composed += 1;
+        }
+
+        let paragraph = &self.paragraphs[index];
+        let Some(layout) = &paragraph.layout else {
+            return;
+        };
+        if stack.first_line + stack.lines.len() > 0 {
+            stack.cursor_y += config.paragraph_spacing;
+        }
+        let line_start = stack.lines.len();
+
+        for line in &layout.lines {
+            let shift = stack.cursor_y - line.top;
+            stack.lines.push(TextLayoutLine {
+                line_height: line.line_height,
+                line_width: line.line_width,
+                top: stack.cursor_y,
+                bottom: stack.cursor_y + line.line_height,
+                glyphs: line
+                    .glyphs
+                    .iter()
+                    .map(|glyph| {
+                        let mut glyph = glyph.clone();
+                        glyph.y += shift;
+                        glyph.char_index += stack.char_offset;
+                        glyph
+                    })
+                    .collect(),
+            });
+            stack.cursor_y += line.line_height;
+        }
+
+        for word in &layout.words {
+            let mut word = word.clone();
+            let line = &stack.lines[line_start + word.line];
+            word.rect.min.y = line.top;
+            word.rect.max.y = line.bottom;
+            word.bytes = word.bytes.start + stack.byte_offset..word.bytes.end + stack.byte_offset;
+            word.line += stack.first_line + line_start;
+            stack.words.push(word);
+        }
+
+        let text_chars = paragraph.chars - usize::from(paragraph.ends_with_break);
+        let first_line = stack.first_line;
+        stack.paragraphs.push(ParagraphInfo {
+            lines: first_line + line_start..first_line + stack.lines.len(),
+            chars: stack.char_offset..stack.char_offset + text_chars,
+        });
+
+        stack.char_offset += paragraph.chars;
+        stack.byte_offset += paragraph.bytes;
+    }
+}
+
+/// Paragraph layouts stacked below each other, before alignment.
+struct Stack<T> {
+    lines: Vec<TextLayoutLine<T>>,
+    words: Vec<WordBox>,
+    paragraphs: Vec<ParagraphInfo>,
+    /// Index of the first line of `lines` in the whole layout.
+    first_line: usize,
+    cursor_y: f32,
+    char_offset: usize,
+    byte_offset: usize,
+}
+
+impl<T> Stack<T> {
+    fn new(first_line: usize, cursor_y: f32, char_offset: usize, byte_offset: usize) -> Self {
+        Self {
+            lines: Vec::new(),
+            words: Vec::new(),
+            paragraphs: Vec::new(),
+            first_line,
+            cursor_y,
+            char_offset,
+            byte_offset,
+        }
+    }
+}
+
+/// Vertical offset of text of `total_height`, as in the layout engine.
+fn vertical_offset(config: &TextLayoutConfig, total_height: f32) -> f32 {
+    let target_height = config.max_height.unwrap_or(total_height);
+    match config.vertical_align {
+        VerticalAlign::Top => 0.0,
+        VerticalAlign::Middle => (target_height - total_height) / 2.0,
+        VerticalAlign::Bottom => target_height - total_height,
+    }
+}
+
+/// Horizontal offset of a line of `line_width`, as in the layout engine.
+fn horizontal_offset(config: &TextLayoutConfig, target_width: f32, line_width: f32) -> f32 {
+    match config.horizontal_align {
+        HorizontalAlign::Left => 0.0,
+        HorizontalAlign::Center => (target_width - line_width) / 2.0,
+        HorizontalAlign::Right => target_width - line_width,
+    }
+}
+
+/// Moves a line and its glyphs.
+fn move_line<T>(line: &mut TextLayoutLine<T>, x: f32, y: f32) {
+    if x == 0.0 && y == 0.0 {
+        return;
+    }
+    for glyph in &mut line.glyphs {
+        glyph.x += x;
+        glyph.y += y;
+    }
+    line.top += y;
+    line.bottom += y;
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{test_font_storage, text_element};
+
+    fn full_layout(
+        text: &str,
+        font_id: fontdb::ID,
+        config: &TextLayoutConfig,
+        font_storage: &FontStorage,
+    ) -> TextLayout<()> {
+        let mut data = TextData::new();
+        data.append(text_element(font_id, 16.0, text, ()));
+        data.layout(config, font_storage)
+    }
+
+    fn assert_same_layout(actual: &TextLayout<()>, expected: &TextLayout<()>) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+        assert!(close(actual.total_width, expected.total_width));
+        assert!(close(actual.total_height, expected.total_height));
+        assert_eq!(actual.paragraphs, expected.paragraphs);
+        assert_eq!(actual.lines.len(), expected.lines.len());
+        for (a, e) in actual.lines.iter().zip(&expected.lines) {
+            assert!(close(a.top, e.top) && close(a.bottom, e.bottom));
+            assert_eq!(a.glyphs.len(), e.glyphs.len());
+            for (a, e) in a.glyphs.iter().zip(&e.glyphs) {
+                assert_eq!(a.glyph_id, e.glyph_id);
+                assert_eq!(a.char_index, e.char_index);
+                assert!(close(a.x, e.x) && close(a.y, e.y));
+            }
+        }
+        assert_eq!(actual.words.len(), expected.words.len());
+        for (a, e) in actual.words.iter().zip(&expected.words) {
+            assert_eq!((&a.bytes, a.line), (&e.bytes, e.line));
+            assert!(close(a.rect.min.x, e.rect.min.x) && close(a.rect.max.y, e.rect.max.y));
+        }
+    }
+
+    #[test]
+    fn test_edits_match_full_layout() {
+        let (font_storage, font_id) = test_font_storage();
+        let configs = [
+            TextLayoutConfig {
+                max_width: Some(200.0),
+                horizontal_align: HorizontalAlign::Center,
+                paragraph_spacing: 6.0,
+                ..Default::default()
+            },
+            // Offsets that depend on the size of the whole text.
+            TextLayoutConfig {
+                horizontal_align: HorizontalAlign::Right,
+                vertical_align: VerticalAlign::Middle,
+                max_height: Some(400.0),
+                ..Default::default()
+            },
+        ];
+
+        for config in configs {
+            let mut buffer = EditBuffer::with_text(
+                "first paragraph that wraps across lines\n\nthird",
+                font_id,
+                16.0,
+                (),
+            );
+            let edits: [(Range<usize>, &str); 7] = [
+                (0..5, "1st"),
+                (3..3, "\nsplit"),
+                (10..20, ""),
+                (buffer.len() - 2..buffer.len() - 2, " ünïcode\n"),
+                (0..0, "\n"),
+                (20..20, "a much longer line than any other line of the text"),
+                (4..30, ""),
+            ];
+
+            let mut expected = buffer.text();
+            buffer.layout(&config, &font_storage);
+            for (range, text) in edits {
+                let range = range.start.min(expected.len())..range.end.min(expected.len());
+                buffer.replace(range.clone(), text);
+                expected.replace_range(range, text);
+                assert_eq!(buffer.text(), expected);
+                assert_eq!(buffer.len_paragraphs(), expected.split('\n').count());
+
+                let layout = buffer.layout(&config, &font_storage).clone();
+                assert_same_layout(
+                    &layout,
+                    &full_layout(&expected, font_id, &config, &font_storage),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_breaks_are_normalized() {
+        let (font_storage, font_id) = test_font_storage();
+        let config = TextLayoutConfig::default();
+        let mut buffer = EditBuffer::with_text("a\r\nb\rc", font_id, 16.0, ());
+        assert_eq!(buffer.text(), "a\nb\nc");
+        assert_eq!(buffer.len_paragraphs(), 3);
+
+        // Multiple edits between layouts, including one that removes a paragraph.
+        buffer.layout(&config, &font_storage);
+        buffer.insert(1, "\r\nd");
+        buffer.delete(0..2);
+        assert_eq!(buffer.text(), "d\nb\nc");
+        let layout = buffer.layout(&config, &font_storage).clone();
+        assert_same_layout(
+            &layout,
+            &full_layout("d\nb\nc", font_id, &config, &font_storage),
+        );
+        assert_eq!(layout.lines.len(), 3);
+    }
+
+    #[test]
+    fn test_only_edited_paragraphs_are_laid_out() {
+        let (font_storage, font_id) = test_font_storage();
+        let config = TextLayoutConfig::default();
+        let text = (0..50)
+            .map(|i| format!("paragraph {i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut buffer = EditBuffer::with_text(&text, font_id, 16.0, ());
+
+        buffer.layout(&config, &font_storage);
+        assert_eq!(buffer.paragraphs_laid_out, 50);
+
+        assert_eq!(buffer.paragraphs_composed, 50);
+
+        // Typing inside one paragraph.
+        buffer.insert(3, "x");
+        buffer.layout(&config, &font_storage);
+        assert_eq!(buffer.paragraphs_laid_out, 51);
+        assert_eq!(buffer.paragraphs_composed, 51);
+
+        // Splitting a paragraph lays out and composes both halves.
+        buffer.insert(20, "\n");
+        buffer.layout(&config, &font_storage);
+        assert_eq!(buffer.paragraphs_laid_out, 53);
+        assert_eq!(buffer.paragraphs_composed, 53);
+
+        // Unchanged buffer and config reuse the composed layout.
+        buffer.layout(&config, &font_storage);
+        assert_eq!(buffer.paragraphs_laid_out, 53);
+        assert_eq!(buffer.paragraphs_composed, 53);
+
+        let config = TextLayoutConfig {
+            max_width: Some(50.0),
+            ..config
+        };
+        buffer.layout(&config, &font_storage);
+        assert_eq!(buffer.paragraphs_laid_out, 53 + 51);
+    }
+
+    #[test]
+    fn test_empty_buffer() {
+        let (font_storage, font_id) = test_font_storage();
+        let mut buffer = EditBuffer::new(font_id, 16.0, ());
+        let layout = buffer.layout(&TextLayoutConfig::default(), &font_storage);
+        assert_eq!(layout.lines.len(), 1);
+        assert!(layout.total_height > 0.0);
+
+        buffer.insert(0, "abc");
+        buffer.delete(0..3);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.len_paragraphs(), 1);
+    }
+}
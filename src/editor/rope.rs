@@ -0,0 +1,179 @@
+//! Chunked rope storing the text of an [`super::EditBuffer`].
+
+use std::ops::Range;
+
+/// Chunks are split when they grow beyond this many bytes.
+const MAX_CHUNK: usize = 1024;
+
+/// Text stored as a sequence of bounded chunks, so edits only move the bytes of one chunk.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Rope {
+    chunks: Vec<String>,
+    len: usize,
+}
+
+impl Rope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Length in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.chunks.iter().map(String::as_str)
+    }
+
+    /// Copies the bytes in `range` into a new string.
+    pub fn slice(&self, range: Range<usize>) -> String {
+        assert!(range.start <= range.end && range.end <= self.len);
+
+        let mut out = String::with_capacity(range.len());
+        let mut chunk_start = 0;
+        for chunk in &self.chunks {
+            let chunk_end = chunk_start + chunk.len();
+            if chunk_end > range.start && chunk_start < range.end {
+                let start = range.start.saturating_sub(chunk_start);
+                let end = (range.end - chunk_start).min(chunk.len());
+                out.push_str(&chunk[start..end]);
+            }
+            if chunk_end >= range.end {
+                break;
+            }
+            chunk_start = chunk_end;
+        }
+        out
+    }
+
+    /// Inserts `text` at byte offset `at`, which must lie on a char boundary.
+    pub fn insert(&mut self, at: usize, text: &str) {
+        assert!(at <= self.len);
+        if text.is_empty() {
+            return;
+        }
+
+        // Find the chunk containing `at`, preferring the end of a chunk over the start of the next.
+        let mut chunk_start = 0;
+        let mut index = self.chunks.len();
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            if at <= chunk_start + chunk.len() {
+                index = i;
+                break;
+            }
+            chunk_start += chunk.len();
+        }
+
+        if index == self.chunks.len() {
+            self.chunks.push(String::new());
+        }
+        self.chunks[index].insert_str(at - chunk_start, text);
+        self.len += text.len();
+
+        if self.chunks[index].len() > MAX_CHUNK {
+            let chunk = std::mem::take(&mut self.chunks[index]);
+            let pieces = split_chunk(&chunk);
+            self.chunks.splice(index..=index, pieces);
+        }
+    }
+
+    /// Removes the bytes in `range`, whose ends must lie on char boundaries.
+    pub fn remove(&mut self, range: Range<usize>) {
+        assert!(range.start <= range.end && range.end <= self.len);
+        if range.is_empty() {
+            return;
+        }
+
+        let mut chunk_start = 0;
+        let mut first_touched = None;
+        for (i, chunk) in self.chunks.iter_mut().enumerate() {
+            let chunk_len = chunk.len();
+            let chunk_end = chunk_start + chunk_len;
+            if chunk_end > range.start && chunk_start < range.end {
+                let start = range.start.saturating_sub(chunk_start);
+                let end = (range.end - chunk_start).min(chunk_len);
+                chunk.replace_range(start..end, "");
+                first_touched.get_or_insert(i);
+            }
+            if chunk_end >= range.end {
+                break;
+            }
+            chunk_start = chunk_end;
+        }
+        self.len -= range.len();
+
+        // Merge the edited chunk with its successor while they stay small.
+        if let Some(index) = first_touched {
+            self.chunks.retain(|chunk| !chunk.is_empty());
+            if index + 1 < self.chunks.len()
+                && self.chunks[index].len() + self.chunks[index + 1].len() <= MAX_CHUNK / 2
+            {
+                let next = self.chunks.remove(index + 1);
+                self.chunks[index].push_str(&next);
+            }
+        }
+    }
+}
+
+/// Splits an oversized chunk into pieces of about half the maximum size.
+fn split_chunk(text: &str) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while rest.len() > MAX_CHUNK / 2 {
+        let mut split = MAX_CHUNK / 2;
+        while !rest.is_char_boundary(split) {
+            split += 1;
+        }
+        pieces.push(rest[..split].to_string());
+        rest = &rest[split..];
+    }
+    pieces.push(rest.to_string());
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edits_match_string() {
+        let mut rope = Rope::new();
+        let mut expected = String::new();
+
+        // Deterministic mix of inserts and removals crossing chunk boundaries.
+        let mut seed = 12345u32;
+        let mut next = |bound: usize| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 8) as usize % bound.max(1)
+        };
+        let floor_boundary = |text: &str, mut at: usize| {
+            while !text.is_char_boundary(at) {
+                at -= 1;
+            }
+            at
+        };
+
+        for step in 0..400 {
+            if step % 3 == 2 && !expected.is_empty() {
+                let start = floor_boundary(&expected, next(expected.len()));
+                let end = floor_boundary(&expected, (start + next(300)).min(expected.len()));
+                rope.remove(start..end);
+                expected.replace_range(start..end, "");
+            } else {
+                let at = floor_boundary(&expected, next(expected.len() + 1));
+                let text = "héllo wörld ✓ ".repeat(next(40));
+                rope.insert(at, &text);
+                expected.insert_str(at, &text);
+            }
+
+            assert_eq!(rope.len(), expected.len());
+            assert!(rope.chunks().all(|chunk| chunk.len() <= MAX_CHUNK));
+        }
+
+        assert_eq!(rope.chunks().collect::<String>(), expected);
+        let start = floor_boundary(&expected, expected.len() / 3);
+        let end = floor_boundary(&expected, expected.len() / 2);
+        assert_eq!(rope.slice(start..end), expected[start..end]);
+    }
+}
@@ -0,0 +1,161 @@
+use std::path::PathBuf;
+
+/// Where a font that failed to load came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FontOrigin {
+    /// A font file or directory.
+    Path(PathBuf),
+    /// A key of a [`FontProvider`](crate::font_storage::FontProvider).
+    Key(String),
+    /// The URL of a web font.
+    Url(String),
+}
+
+impl From<PathBuf> for FontOrigin {
+    fn from(path: PathBuf) -> Self {
+        Self::Path(path)
+    }
+}
+
+impl std::fmt::Display for FontOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Key(key) => write!(f, "{key}"),
+            Self::Url(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+/// An error from loading fonts.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading the font, or listing a font directory, failed.
+    Io {
+        /// Where the font was read from.
+        origin: Option<FontOrigin>,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+    /// The data is not a TrueType or OpenType font, or a collection of them.
+    ///
+    /// WOFF and WOFF2 fonts need to be decompressed before loading.
+    UnsupportedFormat {
+        /// Where the data came from, as for [`Error::Io`].
+        origin: Option<FontOrigin>,
+    },
+    /// The data is in a supported format, but none of its faces could be parsed.
+    Parse {
+        /// Where the data came from, as for [`Error::Io`].
+        origin: Option<FontOrigin>,
+        /// Why parsing failed.
+        message: String,
+    },
+}
+
+impl Error {
+    /// Returns where the failed font came from, if known.
+    pub fn origin(&self) -> Option<&FontOrigin> {
+        match self {
+            Self::Io { origin, .. }
+            | Self::UnsupportedFormat { origin }
+            | Self::Parse { origin, .. } => origin.as_ref(),
+        }
+    }
+
+    /// Returns the file or directory the failed font came from, if it came from one.
+    pub fn path(&self) -> Option<&std::path::Path> {
+        match self.origin()? {
+            FontOrigin::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Sets where the font came from, unless it is already known.
+    pub(crate) fn with_origin(mut self, font_origin: impl Into<FontOrigin>) -> Self {
+        match &mut self {
+            Self::Io { origin, .. }
+            | Self::UnsupportedFormat { origin }
+            | Self::Parse { origin, .. } => {
+                if origin.is_none() {
+                    *origin = Some(font_origin.into());
+                }
+            }
+        }
+        self
+    }
+
+    /// Explains why no face could be loaded from `data`.
+    pub(crate) fn diagnose(data: &[u8]) -> Self {
+        match ttf_parser::Face::parse(data, 0) {
+            Err(ttf_parser::FaceParsingError::UnknownMagic) => {
+                Self::UnsupportedFormat { origin: None }
+            }
+            Err(e) => Self::Parse {
+                origin: None,
+                message: e.to_string(),
+            },
+            // fontdb also requires a family name.
+            Ok(_) => Self::Parse {
+                origin: None,
+                message: "the font has no family name".to_string(),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let origin = self
+            .origin()
+            .map(|origin| format!(" {origin}"))
+            .unwrap_or_default();
+        match self {
+            Self::Io { source, .. } => write!(f, "failed to read font{origin}: {source}"),
+            Self::UnsupportedFormat { .. } => write!(f, "unsupported font format{origin}"),
+            Self::Parse { message, .. } => write!(f, "failed to parse font{origin}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self {
+        Self::Io {
+            origin: None,
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnose() {
+        assert!(matches!(
+            Error::diagnose(b"wOF2\0\0\0\0"),
+            Error::UnsupportedFormat { origin: None }
+        ));
+        let error =
+            Error::diagnose(&[0, 1, 0, 0, 0, 0]).with_origin(PathBuf::from("fonts/broken.ttf"));
+        assert!(matches!(error, Error::Parse { .. }));
+        assert_eq!(error.path(), Some(std::path::Path::new("fonts/broken.ttf")));
+        assert!(
+            error
+                .to_string()
+                .starts_with("failed to parse font fonts/broken.ttf: ")
+        );
+    }
+}
@@ -1,16 +1,291 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use parking_lot::RwLock;
+
+use crate::Error;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod db_cache;
+mod matching;
+mod metrics;
+pub(crate) mod provider;
+pub(crate) mod variations;
+
+pub use matching::{MatchReport, Substitution};
+pub use metrics::FaceMetrics;
+pub use provider::FontProvider;
+pub use variations::{NamedInstance, Variation, VariationAxis};
 
 /// Manages font loading and retrieval using `fontdb` and `fontdue`.
 ///
 /// This struct combines a database of available fonts (`fontdb`) with a cache of loaded
 /// font instances (`fontdue`). It allows querying for fonts by family and properties,
 /// and lazily loads the actual font data when requested.
+///
+/// Retrieving fonts and metrics only needs shared access: the lazily filled
+/// caches are guarded by locks of their own, so layout and rendering on several
+/// threads can share the storage behind a read lock.
 pub struct FontStorage {
     /// This is the font set that has been loaded by fontdb.
     font_db: fontdb::Database,
     /// This is the font that has been loaded by fontdue.
     /// Not all fonts in fontdb are necessarily loaded here.
-    loaded_font: HashMap<fontdb::ID, Arc<fontdue::Font>, fxhash::FxBuildHasher>,
+    loaded_font: RwLock<HashMap<fontdb::ID, LoadedFont, fxhash::FxBuildHasher>>,
+    /// User-provided metadata that replaces what a face reports about itself during queries.
+    face_overrides: HashMap<fontdb::ID, FaceOverride, fxhash::FxBuildHasher>,
+    /// Faces consulted, in order, for clusters with emoji presentation.
+    emoji_fallback: Vec<fontdb::ID>,
+    /// Faces consulted, in order, for glyphs missing from a face or family.
+    fallback_chains: HashMap<FallbackTarget, Vec<fontdb::ID>, fxhash::FxBuildHasher>,
+    /// Faces consulted for glyphs missing from faces without a chain of their own.
+    default_fallback: Vec<fontdb::ID>,
+    /// Whether `default_fallback` was detected rather than set, and follows newly registered faces.
+    default_fallback_detected: bool,
+    /// Faces created from variable fonts at fixed coordinates, by instance ID.
+    instances: HashMap<fontdb::ID, Arc<variations::Instance>, fxhash::FxBuildHasher>,
+    /// Settings for faces without settings of their own.
+    default_font_settings: FontSettings,
+    /// Whether queries fail instead of falling back to other families when none
+    /// of the requested families is loaded, see [`FontStorage::set_strict_matching`].
+    strict_matching: bool,
+    /// Family names standing for other families, see [`FontStorage::set_alias`].
+    family_aliases: HashMap<String, String, fxhash::FxBuildHasher>,
+    /// Settings of individual faces.
+    font_settings: HashMap<fontdb::ID, FontSettings, fxhash::FxBuildHasher>,
+    /// Number of calls to [`FontStorage::evict_unused`] so far.
+    frame: u64,
+    /// Memoized line metrics, by face and font size bits.
+    line_metrics:
+        RwLock<HashMap<(fontdb::ID, u32), Option<fontdue::LineMetrics>, fxhash::FxBuildHasher>>,
+    /// Memoized glyph metrics, by face, glyph index and font size bits.
+    glyph_metrics: RwLock<HashMap<(fontdb::ID, u16, u32), fontdue::Metrics, fxhash::FxBuildHasher>>,
+    /// What fallback detection found out about faces, also stored in the
+    /// system font cache.
+    coverage: HashMap<fontdb::ID, FaceCoverage, fxhash::FxBuildHasher>,
+    /// Memoized character maps of faces that are not loaded, by face.
+    char_maps: RwLock<HashMap<fontdb::ID, Arc<CharMap>, fxhash::FxBuildHasher>>,
+}
+
+/// A font loaded by fontdue.
+struct LoadedFont {
+    font: Arc<fontdue::Font>,
+    /// The value of `FontStorage::frame` when the font was last retrieved.
+    used_frame: AtomicU64,
+}
+
+/// Number of memoized metrics above which the metrics caches are emptied.
+///
+/// Keeps animated font sizes from growing the caches without bound.
+const METRICS_CACHE_LIMIT: usize = 1 << 16;
+
+/// Characters of common scripts and symbol blocks, whose coverage picks the
+/// faces of the detected default fallback chain.
+const SCRIPT_SAMPLES: &[char] = &[
+    'A',  // Latin
+    'Ω',  // Greek
+    'Ж',  // Cyrillic
+    'Ա',  // Armenian
+    'א',  // Hebrew
+    'ب',  // Arabic
+    'क',  // Devanagari
+    'ক',  // Bengali
+    'த',  // Tamil
+    'ก',  // Thai
+    'ა',  // Georgian
+    'አ',  // Ethiopic
+    'あ', // Hiragana
+    'ア', // Katakana
+    '漢', // Han
+    '한', // Hangul
+    '→',  // Arrows
+    '∑',  // Mathematical operators
+    '─',  // Box drawing
+    '★',  // Miscellaneous symbols
+];
+
+/// What fallback detection needs to know about a face.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct FaceCoverage {
+    /// Bit `i` is set if the face covers `SCRIPT_SAMPLES[i]`.
+    pub(crate) samples: u32,
+    /// Whether the face has glyph outlines (`glyf`, `CFF ` or `CFF2`), the only
+    /// glyphs `fontdue` rasterizes.
+    pub(crate) outlines: bool,
+}
+
+impl FaceCoverage {
+    /// Reads the coverage of face `id` of `font_db`; unknown or unparsable
+    /// faces cover nothing.
+    pub(crate) fn read(font_db: &fontdb::Database, id: fontdb::ID) -> Self {
+        font_db
+            .with_face_data(id, |data, index| {
+                let Ok(face) = ttf_parser::Face::parse(data, index) else {
+                    return Self::default();
+                };
+                let samples = SCRIPT_SAMPLES
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &ch)| face.glyph_index(ch).is_some_and(|glyph| glyph.0 != 0))
+                    .fold(0, |samples, (sample, _)| samples | 1 << sample);
+                let tables = face.tables();
+                Self {
+                    samples,
+                    outlines: tables.glyf.is_some()
+                        || tables.cff.is_some()
+                        || tables.cff2.is_some(),
+                }
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The characters a face has glyphs for, as sorted, disjoint ranges of code points.
+#[derive(Debug, Default)]
+struct CharMap(Box<[(u32, u32)]>);
+
+impl CharMap {
+    fn new(face: &ttf_parser::Face) -> Self {
+        let mut code_points = Vec::new();
+        if let Some(cmap) = face.tables().cmap {
+            for subtable in cmap.subtables {
+                if subtable.is_unicode() {
+                    subtable.codepoints(|code_point| code_points.push(code_point));
+                }
+            }
+        }
+        code_points.sort_unstable();
+        code_points.dedup();
+
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for code_point in code_points {
+            let covered = char::from_u32(code_point)
+                .and_then(|ch| face.glyph_index(ch))
+                .is_some_and(|glyph| glyph.0 != 0);
+            if !covered {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == code_point => *end = code_point,
+                _ => ranges.push((code_point, code_point)),
+            }
+        }
+        Self(ranges.into_boxed_slice())
+    }
+
+    fn contains(&self, ch: char) -> bool {
+        let code_point = ch as u32;
+        let after = self.0.partition_point(|&(start, _)| start <= code_point);
+        after
+            .checked_sub(1)
+            .is_some_and(|range| self.0[range].1 >= code_point)
+    }
+}
+
+/// The faces loaded by [`FontStorage::load_system_fonts_cached`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CachedLoad {
+    /// The IDs of the loaded faces.
+    pub ids: Vec<fontdb::ID>,
+    /// Whether the faces were read from the cache rather than scanned.
+    pub from_cache: bool,
+}
+
+/// The text a fallback chain applies to, see [`FontStorage::set_fallback_chain`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FallbackTarget {
+    /// Text set in a single face.
+    Face(fontdb::ID),
+    /// Text set in any face of a family.
+    Family(String),
+}
+
+impl From<fontdb::ID> for FallbackTarget {
+    fn from(id: fontdb::ID) -> Self {
+        Self::Face(id)
+    }
+}
+
+impl From<&str> for FallbackTarget {
+    fn from(family: &str) -> Self {
+        Self::Family(family.to_string())
+    }
+}
+
+impl From<String> for FallbackTarget {
+    fn from(family: String) -> Self {
+        Self::Family(family)
+    }
+}
+
+/// Replacement metadata for a single face.
+///
+/// Some fonts ship with incorrect OS/2 or name tables (e.g. a bold face that
+/// claims to be regular), which makes [`FontStorage::query`] pick the wrong
+/// face. An override corrects the classification without editing the font file.
+/// Fields left as `None` keep the value reported by the font.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FaceOverride {
+    /// Family names the face should be matched under, replacing the reported ones.
+    pub families: Option<Vec<String>>,
+    /// Weight the face should be matched as.
+    pub weight: Option<fontdb::Weight>,
+    /// Style the face should be matched as.
+    pub style: Option<fontdb::Style>,
+    /// Stretch the face should be matched as.
+    pub stretch: Option<fontdb::Stretch>,
+}
+
+/// How faces are turned into `fontdue` fonts.
+///
+/// See [`FontStorage::set_default_font_settings`] and [`FontStorage::set_font_settings`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FontSettings {
+    /// The size in pixels fontdue prepares the glyph outlines for.
+    ///
+    /// Glyphs rendered much larger than this may look less accurate; raising it
+    /// costs memory and loading time. Defaults to 40.
+    pub scale: f32,
+    /// Whether fontdue reads the glyph substitutions of the font. Defaults to `true`.
+    pub load_substitutions: bool,
+    /// When loading a font collection, registers only the face at this index.
+    ///
+    /// Only used by the `load_*_with_settings` methods; `None`, the default,
+    /// registers every face.
+    pub collection_index: Option<u32>,
+}
+
+impl Default for FontSettings {
+    fn default() -> Self {
+        Self {
+            scale: 40.0,
+            load_substitutions: true,
+            collection_index: None,
+        }
+    }
+}
+
+/// Criteria for [`FontStorage::faces_matching`], e.g. for font pickers.
+///
+/// Fields left as `None` or `false` keep every face. Face overrides are honored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FaceFilter {
+    /// Keeps faces with a family name containing this text, ignoring case.
+    pub family: Option<String>,
+    /// Keeps only monospaced faces.
+    pub monospaced: bool,
+    /// Keeps faces of this style.
+    pub style: Option<fontdb::Style>,
+    /// Keeps faces with a glyph for every character of this text, e.g. a sample
+    /// of the script the font should support. See [`FontStorage::face_covers`].
+    pub covering: Option<String>,
 }
 
 impl Default for FontStorage {
@@ -24,31 +299,282 @@ impl FontStorage {
     pub fn new() -> Self {
         Self {
             font_db: fontdb::Database::new(),
-            loaded_font: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            loaded_font: RwLock::new(HashMap::with_hasher(fxhash::FxBuildHasher::default())),
+            face_overrides: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            emoji_fallback: Vec::new(),
+            fallback_chains: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            default_fallback: Vec::new(),
+            default_fallback_detected: false,
+            instances: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            default_font_settings: FontSettings::default(),
+            strict_matching: false,
+            family_aliases: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            font_settings: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            frame: 0,
+            line_metrics: RwLock::new(HashMap::with_hasher(fxhash::FxBuildHasher::default())),
+            glyph_metrics: RwLock::new(HashMap::with_hasher(fxhash::FxBuildHasher::default())),
+            coverage: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            char_maps: RwLock::new(HashMap::with_hasher(fxhash::FxBuildHasher::default())),
         }
     }
 }
 
 /// Loading fonts into fontdb and setting up fontdb.
 impl FontStorage {
-    /// Loads a font from binary data.
-    pub fn load_font_binary(&mut self, data: impl Into<Vec<u8>>) {
-        self.font_db.load_font_data(data.into());
+    /// Loads a font from binary data and returns the IDs of its faces.
+    ///
+    /// Fails if the data is not a supported font or none of its faces parse.
+    /// Faces of a collection that fail to parse are skipped with a warning.
+    pub fn load_font_binary(&mut self, data: impl Into<Vec<u8>>) -> Result<Vec<fontdb::ID>, Error> {
+        load_source(
+            &mut self.font_db,
+            fontdb::Source::Binary(Arc::new(data.into())),
+        )
+    }
+
+    /// Loads a font from a file path and returns the IDs of its faces.
+    ///
+    /// Fails if the file cannot be read, as well as for the reasons of
+    /// [`Self::load_font_binary`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_font_file(&mut self, path: PathBuf) -> Result<Vec<fontdb::ID>, Error> {
+        load_source(&mut self.font_db, fontdb::Source::File(path))
     }
 
-    /// Loads a font from a file path.
-    pub fn load_font_file(&mut self, path: PathBuf) -> Result<(), std::io::Error> {
-        self.font_db.load_font_file(path)
+    /// Loads all fonts from a directory, recursively, and returns the IDs of their faces.
+    ///
+    /// Fails only if the directory cannot be read. Files that are not fonts or
+    /// cannot be loaded are skipped with a warning.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_fonts_dir(&mut self, dir: PathBuf) -> Result<Vec<fontdb::ID>, Error> {
+        let loaded = self.face_ids();
+        load_dir(&mut self.font_db, dir)?;
+        Ok(self.new_face_ids(&loaded))
+    }
+
+    /// Loads a font from binary data with `settings` for its faces and returns their IDs.
+    ///
+    /// See [`FontSettings::collection_index`] for loading a single face of a collection.
+    pub fn load_font_binary_with_settings(
+        &mut self,
+        data: impl Into<Vec<u8>>,
+        settings: FontSettings,
+    ) -> Result<Vec<fontdb::ID>, Error> {
+        let mut font_db = fontdb::Database::new();
+        load_source(&mut font_db, fontdb::Source::Binary(Arc::new(data.into())))?;
+        Ok(self.push_faces_with_settings(&font_db, settings))
     }
 
-    /// Loads all fonts from a directory.
-    pub fn load_fonts_dir(&mut self, dir: PathBuf) {
-        self.font_db.load_fonts_dir(dir)
+    /// Loads a font file with `settings` for its faces and returns their IDs.
+    ///
+    /// See [`FontSettings::collection_index`] for loading a single face of a collection.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_font_file_with_settings(
+        &mut self,
+        path: PathBuf,
+        settings: FontSettings,
+    ) -> Result<Vec<fontdb::ID>, Error> {
+        let mut font_db = fontdb::Database::new();
+        load_source(&mut font_db, fontdb::Source::File(path))?;
+        Ok(self.push_faces_with_settings(&font_db, settings))
+    }
+
+    fn push_faces_with_settings(
+        &mut self,
+        font_db: &fontdb::Database,
+        settings: FontSettings,
+    ) -> Vec<fontdb::ID> {
+        let ids: Vec<fontdb::ID> = font_db
+            .faces()
+            .filter(|face| {
+                settings
+                    .collection_index
+                    .is_none_or(|index| face.index == index)
+            })
+            .map(|face| self.font_db.push_face_info(face.clone()))
+            .collect();
+        for &id in &ids {
+            self.font_settings.insert(id, settings);
+        }
+        ids
+    }
+
+    /// Loads a font file and keeps it memory-mapped for as long as its faces are loaded.
+    ///
+    /// Fonts loaded with [`Self::load_font_file`] are mapped briefly whenever their
+    /// data is read. A persistent mapping avoids reopening the file, and coverage
+    /// queries, metrics lookups and variable font instances read the mapped pages
+    /// directly instead of a copy. Faces loaded earlier from the same file share
+    /// the mapping.
+    ///
+    /// The mapping does not keep a face that is laid out or rendered small:
+    /// `fontdue` outlines all of its glyphs into memory of its own when the face
+    /// is first used, see [`Self::font`]. [`Self::evict_unused`] drops those
+    /// outlines again.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped, e.g. by
+    /// another process; see [`fontdb::Database::make_shared_face_data`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub unsafe fn load_font_file_mmap(&mut self, path: PathBuf) -> Result<Vec<fontdb::ID>, Error> {
+        let loaded = self.face_ids();
+        let ids = load_source(&mut self.font_db, fontdb::Source::File(path))?;
+        // SAFETY: upheld by the caller.
+        unsafe { self.share_new_faces(&loaded) };
+        Ok(ids)
+    }
+
+    /// Loads all fonts from a directory and keeps them memory-mapped, see
+    /// [`Self::load_font_file_mmap`].
+    ///
+    /// # Safety
+    ///
+    /// The files must not be modified or truncated while they are mapped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub unsafe fn load_fonts_dir_mmap(&mut self, dir: PathBuf) -> Result<Vec<fontdb::ID>, Error> {
+        let loaded = self.face_ids();
+        load_dir(&mut self.font_db, dir)?;
+        // SAFETY: upheld by the caller.
+        unsafe { self.share_new_faces(&loaded) };
+        Ok(self.new_face_ids(&loaded))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn face_ids(&self) -> std::collections::HashSet<fontdb::ID, fxhash::FxBuildHasher> {
+        self.font_db.faces().map(|face| face.id).collect()
+    }
+
+    /// Returns the IDs of the faces not in `loaded`, in database order.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new_face_ids(
+        &self,
+        loaded: &std::collections::HashSet<fontdb::ID, fxhash::FxBuildHasher>,
+    ) -> Vec<fontdb::ID> {
+        self.font_db
+            .faces()
+            .map(|face| face.id)
+            .filter(|id| !loaded.contains(id))
+            .collect()
+    }
+
+    /// Maps the files of the faces not in `loaded`.
+    ///
+    /// # Safety
+    ///
+    /// The files must not be modified or truncated while they are mapped.
+    #[cfg(not(target_arch = "wasm32"))]
+    unsafe fn share_new_faces(
+        &mut self,
+        loaded: &std::collections::HashSet<fontdb::ID, fxhash::FxBuildHasher>,
+    ) {
+        let new_files: Vec<fontdb::ID> = self
+            .font_db
+            .faces()
+            .filter(|face| {
+                !loaded.contains(&face.id) && matches!(face.source, fontdb::Source::File(_))
+            })
+            .map(|face| face.id)
+            .collect();
+        for id in new_files {
+            // Faces of a collection are shared together; later calls return the same mapping.
+            // SAFETY: upheld by the caller.
+            if unsafe { self.font_db.make_shared_face_data(id) }.is_none() {
+                log::warn!("Failed to map font (id: {:?})", id);
+            }
+        }
     }
 
-    /// Loads the system fonts.
-    pub fn load_system_fonts(&mut self) {
+    /// Loads the system fonts and returns the IDs of their faces.
+    ///
+    /// If no emoji or default fallback chain has been set yet, they are detected
+    /// from the newly loaded fonts (see [`Self::detect_emoji_fallback`] and
+    /// [`Self::detect_fallback_chains`]). Font files that fail to load are skipped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_system_fonts(&mut self) -> Vec<fontdb::ID> {
+        let known: std::collections::HashSet<fontdb::ID> =
+            self.font_db.faces().map(|face| face.id).collect();
         self.font_db.load_system_fonts();
+        let ids = self
+            .font_db
+            .faces()
+            .map(|face| face.id)
+            .filter(|id| !known.contains(id))
+            .collect();
+        self.detect_missing_fallbacks();
+        ids
+    }
+
+    /// Loads the system fonts like [`Self::load_system_fonts`], reusing the scan
+    /// cached at `cache_path` by a previous call when it is still valid.
+    ///
+    /// Scanning the system fonts reads every font file and can take hundreds of
+    /// milliseconds. The cache stores the metadata of the faces, with all of
+    /// their localized family names, and what fallback detection found out
+    /// about them, so neither needs to read the fonts again. It is invalidated
+    /// when any file below the system font directories, or below the
+    /// directories fontconfig added, is added, removed or changes its size or
+    /// modification time. Directories newly added to the fontconfig
+    /// configuration are not noticed; delete the cache file to rescan.
+    ///
+    /// Failing to write the cache is logged and leaves the scanned fonts loaded.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_system_fonts_cached(&mut self, cache_path: &std::path::Path) -> CachedLoad {
+        let cached = match db_cache::read(cache_path) {
+            Ok(cached) => cached,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                log::warn!("Failed to read font cache {}: {}", cache_path.display(), e);
+                None
+            }
+        };
+
+        let Some(faces) = cached else {
+            let mut scanned = fontdb::Database::new();
+            scanned.load_system_fonts();
+            let ids: Vec<fontdb::ID> = scanned
+                .faces()
+                .map(|face| self.font_db.push_face_info(face.clone()))
+                .collect();
+            // Detection fills in the coverage the cache stores along with the faces.
+            self.detect_missing_fallbacks();
+            let faces = ids.iter().filter_map(|id| {
+                let face = self.font_db.face(*id)?;
+                Some((face, self.coverage.get(id).copied()))
+            });
+            if let Err(e) = db_cache::write(cache_path, faces) {
+                log::warn!("Failed to write font cache {}: {}", cache_path.display(), e);
+            }
+            return CachedLoad {
+                ids,
+                from_cache: false,
+            };
+        };
+
+        let mut ids = Vec::with_capacity(faces.len());
+        for (face, coverage) in faces {
+            let id = self.font_db.push_face_info(face);
+            if let Some(coverage) = coverage {
+                self.coverage.insert(id, coverage);
+            }
+            ids.push(id);
+        }
+        self.detect_missing_fallbacks();
+        CachedLoad {
+            ids,
+            from_cache: true,
+        }
+    }
+
+    /// Detects the emoji and default fallback chains if they have not been set yet.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn detect_missing_fallbacks(&mut self) {
+        if self.emoji_fallback.is_empty() {
+            self.detect_emoji_fallback();
+        }
+        if self.default_fallback.is_empty() {
+            self.detect_fallback_chains();
+        }
     }
 
     /// Manually adds a face info.
@@ -56,10 +582,331 @@ impl FontStorage {
         self.font_db.push_face_info(info);
     }
 
+    /// Drops the data cached for the faces loaded from `path`, so that they are read
+    /// again the next time they are used, and returns the IDs of those faces.
+    ///
+    /// Call this after the file changed on disk. Instances of variable fonts from
+    /// the file are reloaded as well and included in the returned IDs. Face IDs
+    /// and the metadata used for queries are kept. Faces whose data is shared
+    /// with the database, such as memory-mapped files, are not affected. Layouts
+    /// and renderer caches that refer to the faces need to be rebuilt, see
+    /// [`FontSystem::reload_font_file`](crate::FontSystem::reload_font_file).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload_font_file(&mut self, path: &std::path::Path) -> Vec<fontdb::ID> {
+        let mut ids: Vec<fontdb::ID> = self
+            .font_db
+            .faces()
+            .filter(|face| matches!(&face.source, fontdb::Source::File(source) if source == path))
+            .map(|face| face.id)
+            .filter(|id| !self.instances.contains_key(id))
+            .collect();
+
+        for &id in &ids {
+            self.loaded_font.get_mut().remove(&id);
+        }
+        let instances: Vec<(fontdb::ID, fontdb::ID, Vec<Variation>)> = self
+            .instances
+            .iter()
+            .filter(|(_, instance)| ids.contains(&instance.base))
+            .map(|(&instance_id, instance)| (instance_id, instance.base, instance.coords.clone()))
+            .collect();
+        // Instances of a font share one copy of the new data.
+        let mut shared = HashMap::with_hasher(fxhash::FxBuildHasher::default());
+        for (instance_id, base, coords) in instances {
+            let data = shared.entry(base).or_insert_with(|| self.face_data(base));
+            if let Some((data, index)) = data.clone() {
+                self.instances.insert(
+                    instance_id,
+                    Arc::new(variations::Instance::new(base, coords, data, index)),
+                );
+            }
+            ids.push(instance_id);
+        }
+        self.forget_metrics(&ids);
+        self.coverage.retain(|id, _| !ids.contains(id));
+        ids
+    }
+
+    /// Registers the faces of font `key` from `provider` and returns their IDs.
+    ///
+    /// The font is read once to parse its faces; its data is read again, and
+    /// kept, when a face is first used. See [`FontProvider`].
+    pub fn load_provided_font(
+        &mut self,
+        provider: &Arc<dyn FontProvider>,
+        key: &str,
+    ) -> Result<Vec<fontdb::ID>, Error> {
+        let mut font_db = fontdb::Database::new();
+        provider::load_into(provider, key, &mut font_db)?;
+        Ok(font_db
+            .faces()
+            .map(|face| self.font_db.push_face_info(face.clone()))
+            .collect())
+    }
+
+    /// Registers the faces of every font listed by [`FontProvider::keys`] and returns their IDs.
+    ///
+    /// Fonts that cannot be read or parsed are skipped with a warning, as with
+    /// [`Self::load_fonts_dir`].
+    pub fn load_provider(&mut self, provider: &Arc<dyn FontProvider>) -> Vec<fontdb::ID> {
+        let mut font_db = fontdb::Database::new();
+        for key in provider.keys() {
+            if let Err(e) = provider::load_into(provider, &key, &mut font_db) {
+                log::warn!("Failed to load provided font {}: {}", key, e);
+            }
+        }
+        font_db
+            .faces()
+            .map(|face| self.font_db.push_face_info(face.clone()))
+            .collect()
+    }
+
+    /// Adds faces parsed elsewhere, e.g. on a loading thread, and returns their IDs.
+    ///
+    /// An empty emoji fallback chain is detected again, and so is a default
+    /// fallback chain that was detected rather than set, so that the new faces
+    /// take part in fallback.
+    pub fn register_faces(
+        &mut self,
+        faces: impl IntoIterator<Item = fontdb::FaceInfo>,
+    ) -> Vec<fontdb::ID> {
+        self.register_faces_with_coverage(faces.into_iter().map(|info| (info, None)))
+    }
+
+    /// Returns `true` if [`Self::register_faces`] would detect fallback chains.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn detects_fallbacks(&self) -> bool {
+        self.emoji_fallback.is_empty()
+            || self.default_fallback_detected
+            || self.default_fallback.is_empty()
+    }
+
+    /// Adds faces like [`Self::register_faces`], along with the coverage
+    /// fallback detection would otherwise read from them.
+    pub(crate) fn register_faces_with_coverage(
+        &mut self,
+        faces: impl IntoIterator<Item = (fontdb::FaceInfo, Option<FaceCoverage>)>,
+    ) -> Vec<fontdb::ID> {
+        let mut ids = Vec::new();
+        for (info, coverage) in faces {
+            let id = self.font_db.push_face_info(info);
+            if let Some(coverage) = coverage {
+                self.coverage.insert(id, coverage);
+            }
+            ids.push(id);
+        }
+        if ids.is_empty() {
+            return ids;
+        }
+
+        if self.emoji_fallback.is_empty() {
+            self.detect_emoji_fallback();
+        }
+        if self.default_fallback_detected || self.default_fallback.is_empty() {
+            self.detect_fallback_chains();
+        }
+        ids
+    }
+
     /// Removes a face by ID.
+    ///
+    /// Removing a variable font also removes the instances created from it.
     pub fn remove_face(&mut self, id: fontdb::ID) {
+        let instances: Vec<fontdb::ID> = self
+            .instances
+            .iter()
+            .filter(|(_, instance)| instance.base == id)
+            .map(|(&instance_id, _)| instance_id)
+            .collect();
+        for instance_id in instances {
+            self.remove_face(instance_id);
+        }
+        self.instances.remove(&id);
         self.font_db.remove_face(id);
-        self.loaded_font.remove(&id);
+        self.loaded_font.get_mut().remove(&id);
+        self.font_settings.remove(&id);
+        self.forget_metrics(&[id]);
+        self.coverage.remove(&id);
+        self.face_overrides.remove(&id);
+        self.emoji_fallback.retain(|&fallback| fallback != id);
+        self.default_fallback.retain(|&fallback| fallback != id);
+        self.fallback_chains.remove(&FallbackTarget::Face(id));
+        for chain in self.fallback_chains.values_mut() {
+            chain.retain(|&fallback| fallback != id);
+        }
+    }
+
+    /// Overrides the metadata used when matching a face in [`Self::query`].
+    ///
+    /// Replaces any override previously set for the same face.
+    pub fn set_face_override(&mut self, id: fontdb::ID, face_override: FaceOverride) {
+        self.face_overrides.insert(id, face_override);
+    }
+
+    /// Removes the override of a face, restoring the metadata reported by the font.
+    pub fn clear_face_override(&mut self, id: fontdb::ID) {
+        self.face_overrides.remove(&id);
+    }
+
+    /// Sets the faces consulted, in order, for clusters with emoji presentation.
+    ///
+    /// This chain is separate from the text font of a run: emoji presentation
+    /// clusters try it first and only fall back to the run's font when no face
+    /// in the chain covers the character.
+    pub fn set_emoji_fallback(&mut self, chain: Vec<fontdb::ID>) {
+        self.emoji_fallback = chain;
+    }
+
+    /// Returns the emoji fallback chain.
+    pub fn emoji_fallback(&self) -> &[fontdb::ID] {
+        &self.emoji_fallback
+    }
+
+    /// Builds the emoji fallback chain from well-known emoji families that are loaded.
+    ///
+    /// Faces with color bitmaps only (e.g. CBDT in Noto Color Emoji, or sbix in
+    /// Apple Color Emoji) are left out, as they have no outlines for `fontdue` to
+    /// rasterize and would render emoji blank. Faces with outlines render as
+    /// coverage masks like any other glyph.
+    pub fn detect_emoji_fallback(&mut self) {
+        const EMOJI_FAMILIES: &[&str] = &[
+            "Noto Color Emoji",
+            "Apple Color Emoji",
+            "Segoe UI Emoji",
+            "Twemoji Mozilla",
+            "EmojiOne Color",
+            "Noto Emoji",
+            "Segoe UI Symbol",
+        ];
+
+        let candidates: Vec<fontdb::ID> = EMOJI_FAMILIES
+            .iter()
+            .filter_map(|&family| {
+                self.query_id(&fontdb::Query {
+                    families: &[fontdb::Family::Name(family)],
+                    ..Default::default()
+                })
+            })
+            .collect();
+        self.emoji_fallback = candidates
+            .into_iter()
+            .filter(|&id| self.coverage(id).outlines)
+            .collect();
+    }
+
+    /// Returns what fallback detection needs to know about face `id`, reading
+    /// the face only the first time.
+    fn coverage(&mut self, id: fontdb::ID) -> FaceCoverage {
+        if let Some(&coverage) = self.coverage.get(&id) {
+            return coverage;
+        }
+        let coverage = FaceCoverage::read(&self.font_db, id);
+        self.coverage.insert(id, coverage);
+        coverage
+    }
+
+    /// Sets the faces consulted, in order, for characters that text in `target` lacks.
+    ///
+    /// `target` is a face ID or a family name. A chain set for a face takes
+    /// precedence over one set for its family, and faces with neither use the
+    /// default chain. An empty chain removes the entry.
+    pub fn set_fallback_chain(
+        &mut self,
+        target: impl Into<FallbackTarget>,
+        chain: Vec<fontdb::ID>,
+    ) {
+        let target = target.into();
+        if chain.is_empty() {
+            self.fallback_chains.remove(&target);
+        } else {
+            self.fallback_chains.insert(target, chain);
+        }
+    }
+
+    /// Sets the faces consulted for faces without a fallback chain of their own.
+    pub fn set_default_fallback(&mut self, chain: Vec<fontdb::ID>) {
+        self.default_fallback = chain;
+        self.default_fallback_detected = false;
+    }
+
+    /// Returns the default fallback chain.
+    pub fn default_fallback(&self) -> &[fontdb::ID] {
+        &self.default_fallback
+    }
+
+    /// Returns the chain consulted for characters missing from face `id`.
+    pub fn fallback_chain(&self, id: fontdb::ID) -> &[fontdb::ID] {
+        if let Some(chain) = self.fallback_chains.get(&FallbackTarget::Face(id)) {
+            return chain;
+        }
+        if let Some(family) = self.primary_family(id)
+            && let Some(chain) = self.fallback_chains.iter().find_map(|(target, chain)| {
+                matches!(target, FallbackTarget::Family(name) if name == family).then_some(chain)
+            })
+        {
+            return chain;
+        }
+        &self.default_fallback
+    }
+
+    /// Builds the default fallback chain from the loaded fonts, one face per script.
+    ///
+    /// For each of a set of common scripts and symbol blocks, the most regular face
+    /// of the first family covering a sample character is added; the sans-serif
+    /// family is tried first. Coverage is read from the fonts' character maps, so
+    /// no font is instantiated, and remembered for later detections.
+    pub fn detect_fallback_chains(&mut self) {
+        // The most regular face of each family, in name order.
+        let mut families: std::collections::BTreeMap<&str, (u32, fontdb::ID)> =
+            std::collections::BTreeMap::new();
+        for face in self.font_db.faces() {
+            let Some((family, _)) = face.families.first() else {
+                continue;
+            };
+            let irregularity = u32::from(face.style != fontdb::Style::Normal) * 1000
+                + u32::from(face.stretch != fontdb::Stretch::Normal) * 1000
+                + u32::from(face.weight.0.abs_diff(fontdb::Weight::NORMAL.0));
+            let best = families
+                .entry(family.as_str())
+                .or_insert((irregularity, face.id));
+            if irregularity < best.0 {
+                *best = (irregularity, face.id);
+            }
+        }
+        let sans_serif = self.font_db.family_name(&fontdb::Family::SansSerif);
+        let mut candidates: Vec<fontdb::ID> = families
+            .get(sans_serif)
+            .map(|&(_, id)| id)
+            .into_iter()
+            .collect();
+        candidates.extend(
+            families
+                .iter()
+                .filter(|&(&family, _)| family != sans_serif)
+                .map(|(_, &(_, id))| id),
+        );
+
+        let coverage: Vec<(fontdb::ID, u32)> = candidates
+            .into_iter()
+            .map(|id| (id, self.coverage(id).samples))
+            .collect();
+        let mut chain = Vec::new();
+        for sample in 0..SCRIPT_SAMPLES.len() {
+            if let Some(&(id, _)) = coverage
+                .iter()
+                .find(|&&(_, samples)| samples & (1 << sample) != 0)
+                && !chain.contains(&id)
+            {
+                chain.push(id);
+            }
+        }
+        self.default_fallback = chain;
+        self.default_fallback_detected = true;
+    }
+
+    /// Returns the override set for a face, if any.
+    pub fn face_override(&self, id: fontdb::ID) -> Option<&FaceOverride> {
+        self.face_overrides.get(&id)
     }
 
     /// Checks if the storage is empty.
@@ -97,52 +944,752 @@ impl FontStorage {
         self.font_db.set_monospace_family(family);
     }
 
-    /// Returns the name of a family.
+    /// Makes queries for the family `alias` match the faces of `family`.
+    ///
+    /// Lets applications and themes refer to semantic names, such as `"ui"` or
+    /// `"code"`, and swap the family behind them in one place. An alias takes
+    /// precedence over a loaded family of the same name. Aliases are resolved
+    /// once, so an alias of an alias matches nothing.
+    pub fn set_alias(&mut self, alias: impl Into<String>, family: impl Into<String>) {
+        self.family_aliases.insert(alias.into(), family.into());
+    }
+
+    /// Removes the alias set with [`Self::set_alias`].
+    pub fn remove_alias(&mut self, alias: &str) {
+        self.family_aliases.remove(alias);
+    }
+
+    /// Returns the family that `alias` stands for, if it is an alias.
+    pub fn alias(&self, alias: &str) -> Option<&str> {
+        self.family_aliases.get(alias).map(String::as_str)
+    }
+
+    /// Returns the name of a family, with aliases resolved.
     pub fn family_name<'a>(&'a self, family: &'a fontdb::Family<'_>) -> &'a str {
-        self.font_db.family_name(family)
+        let name = self.font_db.family_name(family);
+        match family {
+            fontdb::Family::Name(_) => self.alias(name).unwrap_or(name),
+            _ => name,
+        }
     }
 }
 
+/// Loads the faces of `source` into `font_db`, explaining the failure if there are none.
+pub(crate) fn load_source(
+    font_db: &mut fontdb::Database,
+    source: fontdb::Source,
+) -> Result<Vec<fontdb::ID>, Error> {
+    let ids = font_db.load_font_source(source.clone());
+    if !ids.is_empty() {
+        return Ok(ids.to_vec());
+    }
+
+    // Diagnosed from the data only on failure, so successful loads read it once.
+    match source {
+        fontdb::Source::Binary(data) => Err(Error::diagnose((*data).as_ref())),
+        #[cfg(not(target_arch = "wasm32"))]
+        fontdb::Source::File(path) | fontdb::Source::SharedFile(path, _) => {
+            match std::fs::read(&path) {
+                Ok(data) => Err(Error::diagnose(&data).with_origin(path)),
+                Err(source) => Err(Error::Io {
+                    origin: Some(path.into()),
+                    source,
+                }),
+            }
+        }
+    }
+}
+
+/// Loads the fonts in `dir` and its subdirectories into `font_db`.
+///
+/// Fails only if `dir` cannot be read; fontdb skips files that fail to load.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn load_dir(font_db: &mut fontdb::Database, dir: PathBuf) -> Result<(), Error> {
+    if let Err(source) = std::fs::read_dir(&dir) {
+        return Err(Error::Io {
+            origin: Some(dir.into()),
+            source,
+        });
+    }
+    font_db.load_fonts_dir(dir);
+    Ok(())
+}
+
 /// Get `Font`
 impl FontStorage {
     /// Queries for a font matching the description.
     ///
+    /// Face overrides set with [`Self::set_face_override`] take precedence over
+    /// the metadata reported by the fonts.
+    ///
+    /// When none of the requested families is loaded, the generic sans-serif
+    /// family is tried, then the closest face of any family, so a query only
+    /// fails with no fonts loaded. This changed in 0.3.0: earlier versions
+    /// returned `None` for missing families, as strict matching still does. See
+    /// [`Self::set_strict_matching`] to opt out.
+    ///
     /// Returns the ID and the loaded font if found.
-    pub fn query(&mut self, query: &fontdb::Query) -> Option<(fontdb::ID, Arc<fontdue::Font>)> {
-        let id = self.font_db.query(query)?;
+    pub fn query(&self, query: &fontdb::Query) -> Option<(fontdb::ID, Arc<fontdue::Font>)> {
+        let (id, _) = self.query_id_with_fallback(query)?;
         self.font(id).map(|font| (id, font))
     }
 
+    /// Queries for a font like [`Self::query`] and reports how the chosen face
+    /// differs from the query.
+    ///
+    /// When no face has the requested weight, stretch or style, the closest one
+    /// is chosen, e.g. a 700 face for a 600 query, and the report lists the
+    /// substitution. See [`MatchReport::score`] to compare matches. A fallback to
+    /// a generic or default family is reported as [`Substitution::Family`].
+    pub fn query_with_report(
+        &self,
+        query: &fontdb::Query,
+    ) -> Option<(fontdb::ID, Arc<fontdue::Font>, MatchReport)> {
+        let (id, report) = self.query_id_with_fallback(query)?;
+        self.font(id).map(|font| (id, font, report))
+    }
+
+    /// Sets whether [`Self::query`] fails when none of the requested families is
+    /// loaded instead of falling back to the generic sans-serif family and then
+    /// any loaded face.
+    ///
+    /// Off by default. Turn it on to keep the behavior of versions before 0.3.0,
+    /// e.g. to tell missing fonts apart or pick a fallback of your own. Faces
+    /// matching a requested family are chosen the same way either way.
+    pub fn set_strict_matching(&mut self, strict: bool) {
+        self.strict_matching = strict;
+    }
+
+    /// Returns whether queries match the requested families only.
+    pub fn strict_matching(&self) -> bool {
+        self.strict_matching
+    }
+
+    /// Returns the face of `id`'s family that best matches `weight` and `style`.
+    ///
+    /// Used to pick bold and italic variants of a regular face. The stretch of `id`
+    /// is kept, and face overrides are honored as in [`Self::query`].
+    pub fn styled_face(
+        &self,
+        id: fontdb::ID,
+        weight: fontdb::Weight,
+        style: fontdb::Style,
+    ) -> Option<fontdb::ID> {
+        let face = self.font_db.face(id)?;
+        let face_override = self.face_overrides.get(&id);
+        let family = self.primary_family(id)?;
+        let stretch = face_override
+            .and_then(|o| o.stretch)
+            .unwrap_or(face.stretch);
+
+        self.query_id(&fontdb::Query {
+            families: &[fontdb::Family::Name(family)],
+            weight,
+            stretch,
+            style,
+        })
+    }
+
+    /// Returns `true` if `face` belongs to the family `name`, honoring overrides.
+    fn in_family(&self, face: &fontdb::FaceInfo, name: &str) -> bool {
+        match self
+            .face_overrides
+            .get(&face.id)
+            .and_then(|o| o.families.as_ref())
+        {
+            Some(families) => families.iter().any(|family| family == name),
+            None => face.families.iter().any(|family| family.0 == name),
+        }
+    }
+
+    /// Returns the first family name of a face, honoring overrides.
+    fn primary_family(&self, id: fontdb::ID) -> Option<&str> {
+        match self
+            .face_overrides
+            .get(&id)
+            .and_then(|o| o.families.as_ref())
+        {
+            Some(families) => families.first().map(String::as_str),
+            None => Some(self.font_db.face(id)?.families.first()?.0.as_str()),
+        }
+    }
+
+    /// Resolves a query to a face ID without loading the font.
+    ///
+    /// Only the requested families are matched; internal lookups such as text
+    /// queries have fallbacks of their own.
+    pub(crate) fn query_id(&self, query: &fontdb::Query) -> Option<fontdb::ID> {
+        self.query_id_with_report(query).map(|(id, _)| id)
+    }
+
+    /// Like [`Self::query_id_with_report`], falling back through the generic
+    /// sans-serif family and then all faces unless matching is strict.
+    fn query_id_with_fallback(&self, query: &fontdb::Query) -> Option<(fontdb::ID, MatchReport)> {
+        if let Some(result) = self.query_id_with_report(query) {
+            return Some(result);
+        }
+        if self.strict_matching {
+            return None;
+        }
+
+        let requested = query
+            .families
+            .first()
+            .map(|family| self.family_name(family))
+            .unwrap_or_default();
+        let sans_serif = self.font_db.family_name(&fontdb::Family::SansSerif);
+        let mut candidates = self.candidates(|face| self.in_family(face, sans_serif));
+        let mut used = Some(sans_serif);
+        if candidates.is_empty() {
+            // The system default: the closest face of any family.
+            candidates = self.candidates(|_| true);
+            used = None;
+        }
+
+        let index = matching::find_best_match(&candidates, query)?;
+        let candidate = &candidates[index];
+        let used = match used {
+            Some(used) => used,
+            None => self.primary_family(candidate.id)?,
+        };
+        let mut substitutions = vec![Substitution::Family {
+            requested: requested.to_string(),
+            used: used.to_string(),
+        }];
+        substitutions.extend(candidate.substitutions(query));
+        Some((candidate.id, MatchReport { substitutions }))
+    }
+
+    /// Returns the faces accepted by `filter` as match candidates, honoring overrides.
+    fn candidates(&self, filter: impl Fn(&fontdb::FaceInfo) -> bool) -> Vec<matching::Candidate> {
+        self.font_db
+            .faces()
+            .filter_map(|face| {
+                let face_override = self.face_overrides.get(&face.id);
+                filter(face).then(|| matching::Candidate {
+                    id: face.id,
+                    style: face_override.and_then(|o| o.style).unwrap_or(face.style),
+                    weight: face_override.and_then(|o| o.weight).unwrap_or(face.weight),
+                    stretch: face_override
+                        .and_then(|o| o.stretch)
+                        .unwrap_or(face.stretch),
+                })
+            })
+            .collect()
+    }
+
+    fn query_id_with_report(&self, query: &fontdb::Query) -> Option<(fontdb::ID, MatchReport)> {
+        for family in query.families {
+            let name = self.family_name(family);
+            let candidates = self.candidates(|face| self.in_family(face, name));
+
+            if let Some(index) = matching::find_best_match(&candidates, query) {
+                let candidate = &candidates[index];
+                let mut substitutions = Vec::new();
+                let requested = self.family_name(&query.families[0]);
+                if requested != name {
+                    substitutions.push(Substitution::Family {
+                        requested: requested.to_string(),
+                        used: name.to_string(),
+                    });
+                }
+                substitutions.extend(candidate.substitutions(query));
+                return Some((candidate.id, MatchReport { substitutions }));
+            }
+        }
+
+        None
+    }
+
     /// Retrieves a loaded font by ID, loading it if necessary.
-    pub fn font(&mut self, id: fontdb::ID) -> Option<Arc<fontdue::Font>> {
-        use std::collections::hash_map::Entry;
-
-        match self.loaded_font.entry(id) {
-            Entry::Occupied(entry) => Some(Arc::clone(entry.get())),
-            Entry::Vacant(entry) => {
-                let font_result = self.font_db.with_face_data(id, |data, index| {
-                    fontdue::Font::from_bytes(
-                        data,
-                        fontdue::FontSettings {
-                            collection_index: index,
-                            scale: 40.0,
-                            load_substitutions: true,
-                        },
-                    )
-                })?;
-
-                match font_result {
-                    Ok(font) => {
-                        let r: &mut Arc<fontdue::Font> = entry.insert(Arc::new(font));
-                        Some(Arc::clone(r))
-                    }
-                    Err(e) => {
-                        log::error!("Failed to load font (id: {:?}): {}", id, e);
-                        None
-                    }
+    ///
+    /// Faces of font collections are loaded from their own index in the collection.
+    pub fn font(&self, id: fontdb::ID) -> Option<Arc<fontdue::Font>> {
+        // Instances share the font of their variable font; see `variations`.
+        if let Some(instance) = self.instances.get(&id) {
+            return self.font(instance.base);
+        }
+
+        if let Some(loaded) = self.loaded_font.read().get(&id) {
+            loaded.used_frame.store(self.frame, Ordering::Relaxed);
+            return Some(Arc::clone(&loaded.font));
+        }
+
+        // Parsed without holding the lock; if another thread loads the same
+        // font meanwhile, its font is kept and this one dropped.
+        let settings = self
+            .font_settings
+            .get(&id)
+            .unwrap_or(&self.default_font_settings);
+        // Binary and memory-mapped sources are parsed in place, without a copy of
+        // the file, though `fontdue` still copies the outlines of every glyph.
+        let font_result = self.font_db.with_face_data(id, |data, index| {
+            fontdue::Font::from_bytes(
+                data,
+                fontdue::FontSettings {
+                    collection_index: index,
+                    scale: settings.scale,
+                    load_substitutions: settings.load_substitutions,
+                },
+            )
+        })?;
+
+        match font_result {
+            Ok(font) => {
+                let mut loaded_font = self.loaded_font.write();
+                let loaded = loaded_font.entry(id).or_insert_with(|| LoadedFont {
+                    font: Arc::new(font),
+                    used_frame: AtomicU64::new(self.frame),
+                });
+                Some(Arc::clone(&loaded.font))
+            }
+            Err(e) => {
+                log::error!("Failed to load font (id: {:?}): {}", id, e);
+                None
+            }
+        }
+    }
+
+    /// Marks the loaded font of face `id` as used in the current frame, for
+    /// lookups answered without retrieving the font.
+    fn mark_used(&self, id: fontdb::ID) {
+        let id = self.instances.get(&id).map_or(id, |instance| instance.base);
+        if let Some(loaded) = self.loaded_font.read().get(&id) {
+            loaded.used_frame.store(self.frame, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the line metrics of face `id` at `font_size`, memoized.
+    ///
+    /// Like `fontdue::Font::horizontal_line_metrics`; `None` if the face cannot
+    /// be loaded or has no horizontal metrics.
+    pub fn line_metrics(&self, id: fontdb::ID, font_size: f32) -> Option<fontdue::LineMetrics> {
+        let key = (id, font_size.to_bits());
+        if let Some(&line_metrics) = self.line_metrics.read().get(&key) {
+            self.mark_used(id);
+            return line_metrics;
+        }
+        let line_metrics = self.font(id)?.horizontal_line_metrics(font_size);
+        let mut cache = self.line_metrics.write();
+        if cache.len() >= METRICS_CACHE_LIMIT {
+            cache.clear();
+        }
+        cache.insert(key, line_metrics);
+        line_metrics
+    }
+
+    /// Returns the metrics of glyph `glyph_index` of face `id` at `font_size`, memoized.
+    ///
+    /// Like `fontdue::Font::metrics_indexed`, with the outlines of variable font
+    /// instances at their coordinates. `None` if the face cannot be loaded.
+    pub fn glyph_metrics(
+        &self,
+        id: fontdb::ID,
+        glyph_index: u16,
+        font_size: f32,
+    ) -> Option<fontdue::Metrics> {
+        let key = (id, glyph_index, font_size.to_bits());
+        if let Some(&metrics) = self.glyph_metrics.read().get(&key) {
+            self.mark_used(id);
+            return Some(metrics);
+        }
+        let metrics = match self.instances.get(&id) {
+            Some(instance) => instance.metrics(glyph_index, font_size),
+            None => self.font(id)?.metrics_indexed(glyph_index, font_size),
+        };
+        let mut cache = self.glyph_metrics.write();
+        if cache.len() >= METRICS_CACHE_LIMIT {
+            cache.clear();
+        }
+        cache.insert(key, metrics);
+        Some(metrics)
+    }
+
+    /// Rasterizes glyph `glyph_index` of face `id` at `font_size` with its outline
+    /// moved `shift` pixels (less than one) to the right, into a bitmap a column
+    /// wider than [`Self::glyph_metrics`]. `None` if the face cannot be parsed.
+    pub(crate) fn rasterize_shifted(
+        &self,
+        id: fontdb::ID,
+        glyph_index: u16,
+        font_size: f32,
+        shift: f32,
+    ) -> Option<(fontdue::Metrics, Vec<u8>)> {
+        if let Some(instance) = self.instances.get(&id) {
+            return Some(instance.rasterize_shifted(glyph_index, font_size, shift));
+        }
+        let metrics = self.glyph_metrics(id, glyph_index, font_size)?;
+        self.font_db.with_face_data(id, |data, index| {
+            let face = ttf_parser::Face::parse(data, index).ok()?;
+            Some(variations::rasterize_shifted(
+                &face,
+                glyph_index,
+                font_size,
+                metrics,
+                shift,
+            ))
+        })?
+    }
+
+    /// Sets the settings for faces without settings of their own.
+    ///
+    /// Fonts already loaded with the previous settings are dropped and loaded
+    /// again on use; their IDs are returned so that renderer caches can be
+    /// updated, see [`FontSystem::set_default_font_settings`](crate::FontSystem::set_default_font_settings).
+    pub fn set_default_font_settings(&mut self, settings: FontSettings) -> Vec<fontdb::ID> {
+        self.default_font_settings = settings;
+        let font_settings = &self.font_settings;
+        let ids: Vec<fontdb::ID> = self
+            .loaded_font
+            .get_mut()
+            .keys()
+            .filter(|id| !font_settings.contains_key(id))
+            .copied()
+            .collect();
+        self.unload_fonts(&ids)
+    }
+
+    /// Returns the settings for faces without settings of their own.
+    pub fn default_font_settings(&self) -> FontSettings {
+        self.default_font_settings
+    }
+
+    /// Sets the settings of face `id`, or with `None` makes it follow the default.
+    ///
+    /// Instances of a variable font use the settings of the variable font. Like
+    /// [`Self::set_default_font_settings`], returns the ID of the face if its
+    /// loaded font was dropped.
+    pub fn set_font_settings(
+        &mut self,
+        id: fontdb::ID,
+        settings: Option<FontSettings>,
+    ) -> Vec<fontdb::ID> {
+        let id = self.instances.get(&id).map_or(id, |instance| instance.base);
+        match settings {
+            Some(settings) => self.font_settings.insert(id, settings),
+            None => self.font_settings.remove(&id),
+        };
+        self.unload_fonts(&[id])
+    }
+
+    /// Returns the settings used for face `id`.
+    pub fn font_settings(&self, id: fontdb::ID) -> FontSettings {
+        let id = self.instances.get(&id).map_or(id, |instance| instance.base);
+        self.font_settings
+            .get(&id)
+            .copied()
+            .unwrap_or(self.default_font_settings)
+    }
+
+    /// Drops the loaded fonts, metrics and character maps of `ids`, returning
+    /// those that were loaded.
+    fn unload_fonts(&mut self, ids: &[fontdb::ID]) -> Vec<fontdb::ID> {
+        let loaded: Vec<fontdb::ID> = ids
+            .iter()
+            .copied()
+            .filter(|id| self.loaded_font.get_mut().remove(id).is_some())
+            .collect();
+        self.forget_metrics(ids);
+        loaded
+    }
+
+    /// Drops the loaded fonts that were not used during the last
+    /// `max_idle_frames` frames, along with their memoized metrics, and returns
+    /// how many were dropped.
+    ///
+    /// A font is used when it or the metrics of its face are retrieved, which
+    /// the renderers do for every glyph they draw, cached or not.
+    ///
+    /// Call this once per frame, e.g. after rendering; each call ends a frame.
+    /// With `0`, only fonts used since the previous call are kept. Dropped
+    /// fonts are loaded again when they are next used, and layouts holding them
+    /// keep them alive until they are dropped. The database keeps the faces and
+    /// their source data, so fonts loaded from memory still take up memory.
+    pub fn evict_unused(&mut self, max_idle_frames: u64) -> usize {
+        let frame = self.frame;
+        let mut evicted = Vec::new();
+        self.loaded_font.get_mut().retain(|&id, loaded| {
+            let used = frame - *loaded.used_frame.get_mut() <= max_idle_frames;
+            if !used {
+                evicted.push(id);
+            }
+            used
+        });
+        self.frame += 1;
+
+        let count = evicted.len();
+        // Instances share the font of their variable font.
+        let instances: Vec<fontdb::ID> = self
+            .instances
+            .iter()
+            .filter(|(_, instance)| evicted.contains(&instance.base))
+            .map(|(&id, _)| id)
+            .collect();
+        evicted.extend(instances);
+        self.forget_metrics(&evicted);
+        count
+    }
+
+    /// Empties the memoized line and glyph metrics.
+    pub fn clear_metrics_cache(&mut self) {
+        self.line_metrics.get_mut().clear();
+        self.glyph_metrics.get_mut().clear();
+    }
+
+    /// Forgets the memoized metrics and character maps of `ids`.
+    fn forget_metrics(&mut self, ids: &[fontdb::ID]) {
+        self.char_maps.get_mut().retain(|id, _| !ids.contains(id));
+        self.line_metrics
+            .get_mut()
+            .retain(|(id, _), _| !ids.contains(id));
+        self.glyph_metrics
+            .get_mut()
+            .retain(|(id, _, _), _| !ids.contains(id));
+    }
+
+    /// Returns the face-wide metrics of face `id`, such as its x-height and cap height.
+    ///
+    /// Read from the font without loading it; instances of variable fonts report
+    /// the metrics at their coordinates. `None` if the face is unknown or cannot
+    /// be parsed.
+    pub fn face_metrics(&self, id: fontdb::ID) -> Option<FaceMetrics> {
+        let coords = self
+            .instances
+            .get(&id)
+            .map(|instance| instance.coords.as_slice())
+            .unwrap_or_default();
+        self.font_db.with_face_data(id, |data, index| {
+            let mut face = ttf_parser::Face::parse(data, index).ok()?;
+            for coord in coords {
+                face.set_variation(ttf_parser::Tag::from_bytes(&coord.tag), coord.value);
+            }
+            Some(metrics::face_metrics(&face))
+        })?
+    }
+
+    /// Returns the variation axes of face `id`; empty if the face is not variable.
+    pub fn variation_axes(&self, id: fontdb::ID) -> Vec<VariationAxis> {
+        self.font_db
+            .with_face_data(id, |data, index| {
+                ttf_parser::Face::parse(data, index)
+                    .map(|face| variations::axes(&face))
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns a face for the variable font `id` at the coordinates `coords`.
+    ///
+    /// The instance is registered as a face of its own, so its ID can be used
+    /// wherever a face ID is expected, and glyphs of different instances get
+    /// different [`GlyphId`](crate::GlyphId)s and separate cache entries. Weight,
+    /// width and slant coordinates are reflected in the face's properties, so
+    /// queries can match the instance. Coordinates are clamped to the font's
+    /// axes; axes the font lacks are ignored. Asking for the same coordinates
+    /// again returns the same face, and creating an instance from an instance
+    /// starts from its coordinates.
+    ///
+    /// Returns `None` if the face is unknown or not variable.
+    pub fn variation_instance(
+        &mut self,
+        id: fontdb::ID,
+        coords: &[Variation],
+    ) -> Option<fontdb::ID> {
+        let (base, mut requested) = match self.instances.get(&id) {
+            Some(instance) => (instance.base, instance.coords.clone()),
+            None => (id, Vec::new()),
+        };
+        requested.extend_from_slice(coords);
+
+        let axes = self.variation_axes(base);
+        if axes.is_empty() {
+            return None;
+        }
+        let coords = variations::normalize(&axes, &requested);
+        if coords.is_empty() {
+            return Some(base);
+        }
+        if let Some((&instance_id, _)) = self
+            .instances
+            .iter()
+            .find(|(_, instance)| instance.base == base && instance.coords == coords)
+        {
+            return Some(instance_id);
+        }
+
+        let mut info = self.font_db.face(base)?.clone();
+        // Instances of a font share its data, which is read once for file sources.
+        let (data, index) = match self
+            .instances
+            .values()
+            .find(|instance| instance.base == base)
+        {
+            Some(instance) => instance.data(),
+            None => self.face_data(base)?,
+        };
+        for coord in &coords {
+            info.post_script_name.push_str(&format!(
+                "-{}{}",
+                String::from_utf8_lossy(&coord.tag),
+                coord.value
+            ));
+            match &coord.tag {
+                b"wght" => {
+                    info.weight = fontdb::Weight(coord.value.round().clamp(1.0, 1000.0) as u16)
                 }
+                b"wdth" => info.stretch = stretch_for_width(coord.value),
+                b"slnt" if coord.value != 0.0 => info.style = fontdb::Style::Oblique,
+                b"ital" if coord.value >= 0.5 => info.style = fontdb::Style::Italic,
+                _ => {}
             }
         }
+
+        let instance_id = self.font_db.push_face_info(info);
+        self.instances.insert(
+            instance_id,
+            Arc::new(variations::Instance::new(base, coords, data, index)),
+        );
+        Some(instance_id)
+    }
+
+    /// Returns the named instances of the variable font `id`, e.g. "Condensed Bold".
+    ///
+    /// Empty if the face is not variable or lists no instances.
+    pub fn named_instances(&self, id: fontdb::ID) -> Vec<NamedInstance> {
+        self.font_db
+            .with_face_data(id, |data, index| {
+                ttf_parser::Face::parse(data, index)
+                    .map(|face| variations::named_instances(&face))
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns a face for the named instance `name` of the variable font `id`.
+    ///
+    /// `name` is matched against the subfamily and PostScript names of the
+    /// instances, ignoring case. The face is created with
+    /// [`Self::variation_instance`] at the instance's coordinates; the default
+    /// instance resolves to the variable font itself.
+    pub fn named_instance(&mut self, id: fontdb::ID, name: &str) -> Option<fontdb::ID> {
+        let instance = self.named_instances(id).into_iter().find(|instance| {
+            instance.name.eq_ignore_ascii_case(name)
+                || instance
+                    .post_script_name
+                    .as_ref()
+                    .is_some_and(|post_script_name| post_script_name.eq_ignore_ascii_case(name))
+        })?;
+        let base = self.instances.get(&id).map_or(id, |instance| instance.base);
+        self.variation_instance(base, &instance.coords)
+    }
+
+    /// Returns a face for the named instance `name` of the first family of
+    /// `families` with a variable font that has one, see [`Self::named_instance`].
+    pub fn query_named_instance(
+        &mut self,
+        families: &[fontdb::Family],
+        name: &str,
+    ) -> Option<fontdb::ID> {
+        for family in families {
+            let family_name = self.family_name(family).to_string();
+            let faces: Vec<fontdb::ID> = self
+                .font_db
+                .faces()
+                .filter(|face| {
+                    !self.instances.contains_key(&face.id) && self.in_family(face, &family_name)
+                })
+                .map(|face| face.id)
+                .collect();
+            if let Some(id) = faces
+                .into_iter()
+                .find_map(|id| self.named_instance(id, name))
+            {
+                return Some(id);
+            }
+        }
+        None
+    }
+
+    /// Returns the coordinates of a face created by [`Self::variation_instance`].
+    ///
+    /// Empty for other faces.
+    pub fn variations(&self, id: fontdb::ID) -> &[Variation] {
+        self.instances
+            .get(&id)
+            .map_or(&[], |instance| instance.coords.as_slice())
+    }
+
+    /// Returns the variable font instance behind face `id`, if it is one.
+    pub(crate) fn instance(&self, id: fontdb::ID) -> Option<Arc<variations::Instance>> {
+        self.instances.get(&id).cloned()
+    }
+
+    /// Returns the first face in the emoji fallback chain that covers `ch`.
+    pub fn emoji_font_for(&self, ch: char) -> Option<(fontdb::ID, Arc<fontdue::Font>)> {
+        for &id in &self.emoji_fallback {
+            if let Some(font) = self.font(id)
+                && font.has_glyph(ch)
+            {
+                return Some((id, font));
+            }
+        }
+        None
+    }
+
+    /// Returns the first face in the fallback chain of face `id` that covers `ch`.
+    ///
+    /// See [`Self::fallback_chain`]. Only the face that is returned gets loaded.
+    pub fn fallback_font_for(
+        &self,
+        id: fontdb::ID,
+        ch: char,
+    ) -> Option<(fontdb::ID, Arc<fontdue::Font>)> {
+        let fallback = self
+            .fallback_chain(id)
+            .iter()
+            .copied()
+            .find(|&fallback| fallback != id && self.face_covers(fallback, ch))?;
+        Some((fallback, self.font(fallback)?))
+    }
+
+    /// Returns `true` if face `id` has a glyph for `ch`.
+    ///
+    /// Faces that are not loaded yet are checked through their character map
+    /// only, so no font is instantiated. The character map is read once per
+    /// face and kept until the face is removed or reloaded, or its font is
+    /// evicted by [`Self::evict_unused`]. Unknown or
+    /// unparsable faces cover nothing.
+    pub fn face_covers(&self, id: fontdb::ID, ch: char) -> bool {
+        if let Some(loaded) = self.loaded_font.read().get(&id) {
+            return loaded.font.has_glyph(ch);
+        }
+        self.char_map(id)
+            .is_some_and(|char_map| char_map.contains(ch))
+    }
+
+    /// Returns the memoized character map of face `id`, reading it on first use.
+    fn char_map(&self, id: fontdb::ID) -> Option<Arc<CharMap>> {
+        if let Some(char_map) = self.char_maps.read().get(&id) {
+            return Some(char_map.clone());
+        }
+        let char_map = Arc::new(self.font_db.with_face_data(id, |data, index| {
+            ttf_parser::Face::parse(data, index)
+                .map(|face| CharMap::new(&face))
+                .unwrap_or_default()
+        })?);
+        self.char_maps.write().insert(id, char_map.clone());
+        Some(char_map)
+    }
+
+    /// Returns the IDs of all faces that have a glyph for `ch`, see [`Self::face_covers`].
+    ///
+    /// Uses the memoized character maps, so only the first call reads the faces.
+    pub fn fonts_covering(&self, ch: char) -> Vec<fontdb::ID> {
+        self.font_db
+            .faces()
+            .map(|face| face.id)
+            .filter(|&id| self.face_covers(id, ch))
+            .collect()
     }
 
     /// Returns an iterator over all available faces.
@@ -150,13 +1697,954 @@ impl FontStorage {
         self.font_db.faces()
     }
 
+    /// Returns an iterator over the faces that pass `filter`.
+    ///
+    /// The cheap criteria are checked first, so coverage is only looked up for
+    /// faces that pass the others.
+    pub fn faces_matching<'a>(
+        &'a self,
+        filter: &'a FaceFilter,
+    ) -> impl Iterator<Item = &'a fontdb::FaceInfo> {
+        let family = filter.family.as_ref().map(|family| family.to_lowercase());
+        self.font_db.faces().filter(move |face| {
+            let face_override = self.face_overrides.get(&face.id);
+            if let Some(family) = &family {
+                let contains = |name: &str| name.to_lowercase().contains(family.as_str());
+                let in_family = match face_override.and_then(|o| o.families.as_ref()) {
+                    Some(families) => families.iter().any(|name| contains(name)),
+                    None => face.families.iter().any(|(name, _)| contains(name)),
+                };
+                if !in_family {
+                    return false;
+                }
+            }
+            if filter.monospaced && !face.monospaced {
+                return false;
+            }
+            if let Some(style) = filter.style
+                && face_override.and_then(|o| o.style).unwrap_or(face.style) != style
+            {
+                return false;
+            }
+            filter
+                .covering
+                .as_ref()
+                .is_none_or(|text| text.chars().all(|ch| self.face_covers(face.id, ch)))
+        })
+    }
+
     /// Returns face info for an ID.
+    ///
+    /// The info is returned as reported by the font; overrides are not applied.
     pub fn face(&self, id: fontdb::ID) -> Option<&fontdb::FaceInfo> {
         self.font_db.face(id)
     }
 
-    /// Returns the source of a face.
+    /// Returns the source of a face and the face's index within it.
+    ///
+    /// The index is non-zero for all but the first face of a font collection
+    /// (`.ttc`, `.otc`); each face of a collection is registered separately.
     pub fn face_source(&self, id: fontdb::ID) -> Option<(fontdb::Source, u32)> {
         self.font_db.face_source(id)
     }
+
+    /// Returns the data of face `id`, shared with the database when it is in memory
+    /// or memory-mapped and read from its file otherwise.
+    fn face_data(&self, id: fontdb::ID) -> Option<(variations::FontData, u32)> {
+        match self.font_db.face_source(id)? {
+            (fontdb::Source::Binary(data), index) => Some((data, index)),
+            #[cfg(not(target_arch = "wasm32"))]
+            (fontdb::Source::SharedFile(_, data), index) => Some((data, index)),
+            #[cfg(not(target_arch = "wasm32"))]
+            (fontdb::Source::File(_), _) => self.font_db.with_face_data(id, |data, index| {
+                (Arc::new(data.to_vec()) as variations::FontData, index)
+            }),
+        }
+    }
+}
+
+/// Returns the stretch class closest to a `wdth` coordinate in percent.
+fn stretch_for_width(width: f32) -> fontdb::Stretch {
+    use fontdb::Stretch::*;
+
+    [
+        (50.0, UltraCondensed),
+        (62.5, ExtraCondensed),
+        (75.0, Condensed),
+        (87.5, SemiCondensed),
+        (100.0, Normal),
+        (112.5, SemiExpanded),
+        (125.0, Expanded),
+        (150.0, ExtraExpanded),
+        (200.0, UltraExpanded),
+    ]
+    .into_iter()
+    .min_by(|a, b| (a.0 - width).abs().total_cmp(&(b.0 - width).abs()))
+    .map_or(Normal, |(_, stretch)| stretch)
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GlyphId;
+    use crate::test_utils::{
+        SECOND_TEST_FONT, TEST_FONT, TEST_FONT_PATH, test_font_storage, with_weight_axis,
+    };
+
+    /// Returns a storage holding [`TEST_FONT`] and [`SECOND_TEST_FONT`], and the
+    /// IDs of their faces.
+    fn two_fonts() -> (FontStorage, [fontdb::ID; 2]) {
+        let (mut font_storage, first) = test_font_storage();
+        let second = font_storage.load_font_binary(SECOND_TEST_FONT).unwrap()[0];
+        (font_storage, [first, second])
+    }
+
+    fn push_face(font_storage: &mut FontStorage, family: &str) -> fontdb::ID {
+        push_face_data(font_storage, family, Vec::new(), 0)
+    }
+
+    /// Registers face `index` of `data` as a face of `family`.
+    fn push_face_data(
+        font_storage: &mut FontStorage,
+        family: &str,
+        data: Vec<u8>,
+        index: u32,
+    ) -> fontdb::ID {
+        let before: Vec<_> = font_storage.faces().map(|face| face.id).collect();
+        font_storage.push_face_info(fontdb::FaceInfo {
+            id: fontdb::ID::dummy(),
+            source: fontdb::Source::Binary(Arc::new(data)),
+            index,
+            families: vec![(family.to_string(), fontdb::Language::English_UnitedStates)],
+            post_script_name: family.to_string(),
+            style: fontdb::Style::Normal,
+            weight: fontdb::Weight::NORMAL,
+            stretch: fontdb::Stretch::Normal,
+            monospaced: false,
+        });
+        font_storage
+            .faces()
+            .map(|face| face.id)
+            .find(|id| !before.contains(id))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_fallback_chain_precedence() {
+        let mut font_storage = FontStorage::new();
+        let a = push_face(&mut font_storage, "A");
+        let a2 = push_face(&mut font_storage, "A");
+        let b = push_face(&mut font_storage, "B");
+        let c = push_face(&mut font_storage, "C");
+
+        font_storage.set_default_fallback(vec![c]);
+        font_storage.set_fallback_chain("A", vec![b, c]);
+        font_storage.set_fallback_chain(a2, vec![c, b]);
+        assert_eq!(font_storage.fallback_chain(a), [b, c]);
+        assert_eq!(font_storage.fallback_chain(a2), [c, b]);
+        assert_eq!(font_storage.fallback_chain(b), [c]);
+
+        // Families follow overrides.
+        font_storage.set_face_override(
+            b,
+            FaceOverride {
+                families: Some(vec!["A".to_string()]),
+                ..Default::default()
+            },
+        );
+        assert_eq!(font_storage.fallback_chain(b), [b, c]);
+
+        // Removed faces leave every chain; an empty chain clears the entry.
+        font_storage.remove_face(c);
+        assert_eq!(font_storage.fallback_chain(a2), [b]);
+        assert!(font_storage.default_fallback().is_empty());
+        font_storage.set_fallback_chain("A", Vec::new());
+        assert!(font_storage.fallback_chain(a).is_empty());
+    }
+
+    #[test]
+    fn test_emoji_fallback_skips_bitmap_only_faces() {
+        let data = TEST_FONT.to_vec();
+
+        // Renaming the outline table, which keeps the table records sorted,
+        // leaves a face with a character map but nothing to rasterize, like
+        // a color bitmap font.
+        let mut bitmap_only = data.clone();
+        let num_tables = u16::from_be_bytes([data[4], data[5]]) as usize;
+        for record in bitmap_only[12..12 + 16 * num_tables].chunks_exact_mut(16) {
+            if &record[..4] == b"glyf" {
+                record[..4].copy_from_slice(b"glyg");
+            }
+        }
+
+        let mut font_storage = FontStorage::new();
+        push_face_data(&mut font_storage, "Noto Color Emoji", bitmap_only, 0);
+        let outlines = push_face_data(&mut font_storage, "Noto Emoji", data, 0);
+        font_storage.detect_emoji_fallback();
+        assert_eq!(font_storage.emoji_fallback(), [outlines]);
+        assert_eq!(
+            font_storage.emoji_font_for('a').map(|(id, _)| id),
+            Some(outlines)
+        );
+    }
+
+    #[test]
+    fn test_variation_instances() {
+        let mut font_storage = FontStorage::new();
+        let fake = push_face(&mut font_storage, "Fake");
+        assert!(font_storage.variation_axes(fake).is_empty());
+        assert_eq!(
+            font_storage.variation_instance(fake, &[Variation::weight(700.0)]),
+            None
+        );
+
+        let id = font_storage
+            .load_font_binary(with_weight_axis(TEST_FONT).unwrap())
+            .unwrap()[0];
+        let axis = font_storage.variation_axes(id)[0];
+        let faces = font_storage.faces().count();
+
+        let coords = [Variation::new(axis.tag, axis.max_value + 1.0)];
+        let instance = font_storage.variation_instance(id, &coords).unwrap();
+        assert_ne!(instance, id);
+        assert_eq!(
+            font_storage.variations(instance),
+            [Variation::new(axis.tag, axis.max_value)]
+        );
+        assert_eq!(font_storage.variation_instance(id, &coords), Some(instance));
+        assert_eq!(font_storage.faces().count(), faces + 1);
+        assert!(font_storage.font(instance).is_some());
+
+        // The default coordinates need no instance.
+        let default = [Variation::new(axis.tag, axis.default_value)];
+        assert_eq!(font_storage.variation_instance(id, &[]), Some(id));
+        assert_eq!(font_storage.variation_instance(id, &default), Some(id));
+        assert_eq!(
+            font_storage.variation_instance(instance, &default),
+            Some(id)
+        );
+
+        font_storage.remove_face(id);
+        assert!(font_storage.variations(instance).is_empty());
+        assert!(font_storage.face(instance).is_none());
+    }
+
+    #[test]
+    fn test_coverage_matches_loaded_fonts() {
+        let (mut font_storage, id) = test_font_storage();
+        let samples = ['A', 'é', 'Ж', 'א', '∑', '\u{10FFFF}'];
+        let covered: Vec<_> = samples
+            .iter()
+            .map(|&ch| font_storage.face_covers(id, ch))
+            .collect();
+        assert!(font_storage.loaded_font.read().is_empty());
+        assert!(!covered[5]);
+
+        let covering = font_storage.fonts_covering('A');
+        assert_eq!(covering.contains(&id), covered[0]);
+        assert!(font_storage.loaded_font.read().is_empty());
+        assert_eq!(
+            font_storage.char_maps.read().len(),
+            font_storage.faces().count()
+        );
+        assert_eq!(font_storage.fonts_covering('A'), covering);
+
+        let font = font_storage.font(id).unwrap();
+        for (&ch, &covered) in samples.iter().zip(&covered) {
+            assert_eq!(font.has_glyph(ch), covered);
+        }
+
+        // Faces without data cover nothing.
+        let empty = push_face(&mut font_storage, "Empty");
+        assert!(!font_storage.face_covers(empty, 'A'));
+        assert!(!font_storage.fonts_covering('A').contains(&empty));
+    }
+
+    #[test]
+    fn test_char_maps_match_faces() {
+        let (mut font_storage, ids) = two_fonts();
+        for &id in &ids {
+            let expected: Vec<bool> = font_storage
+                .font_db
+                .with_face_data(id, |data, index| {
+                    let face = ttf_parser::Face::parse(data, index).ok();
+                    (0..0x3100)
+                        .chain(0x1F600..0x1F650)
+                        .filter_map(char::from_u32)
+                        .map(|ch| {
+                            face.as_ref()
+                                .and_then(|face| face.glyph_index(ch))
+                                .is_some_and(|glyph| glyph.0 != 0)
+                        })
+                        .collect()
+                })
+                .unwrap();
+            let covered: Vec<bool> = (0..0x3100)
+                .chain(0x1F600..0x1F650)
+                .filter_map(char::from_u32)
+                .map(|ch| font_storage.face_covers(id, ch))
+                .collect();
+            assert_eq!(covered, expected);
+        }
+        assert_eq!(font_storage.char_maps.read().len(), ids.len());
+        assert!(font_storage.loaded_font.read().is_empty());
+
+        // Removed faces forget their character map.
+        font_storage.remove_face(ids[0]);
+        assert!(!font_storage.char_maps.read().contains_key(&ids[0]));
+        assert!(!font_storage.face_covers(ids[0], 'A'));
+    }
+
+    #[test]
+    fn test_face_overrides() {
+        let mut font_storage = FontStorage::new();
+        let regular = push_face(&mut font_storage, "A");
+        let mislabeled = push_face(&mut font_storage, "A");
+        let other = push_face(&mut font_storage, "B");
+        let query = |families, weight, style| fontdb::Query {
+            families,
+            weight: fontdb::Weight(weight),
+            style,
+            ..Default::default()
+        };
+        let family_a = [fontdb::Family::Name("A")];
+
+        // Both faces claim to be regular, so the first one wins.
+        assert_eq!(
+            font_storage.query_id(&query(&family_a, 700, fontdb::Style::Normal)),
+            Some(regular)
+        );
+
+        font_storage.set_face_override(
+            mislabeled,
+            FaceOverride {
+                weight: Some(fontdb::Weight::BOLD),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            font_storage.query_id(&query(&family_a, 700, fontdb::Style::Normal)),
+            Some(mislabeled)
+        );
+        assert_eq!(
+            font_storage.query_id(&query(&family_a, 400, fontdb::Style::Normal)),
+            Some(regular)
+        );
+        assert_eq!(
+            font_storage.styled_face(regular, fontdb::Weight::BOLD, fontdb::Style::Normal),
+            Some(mislabeled)
+        );
+
+        // Overridden families move a face to another family.
+        font_storage.set_face_override(
+            other,
+            FaceOverride {
+                families: Some(vec!["A".to_string()]),
+                style: Some(fontdb::Style::Italic),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            font_storage.query_id(&query(&family_a, 400, fontdb::Style::Italic)),
+            Some(other)
+        );
+        assert_eq!(
+            font_storage.query_id(&query(
+                &[fontdb::Family::Name("B")],
+                400,
+                fontdb::Style::Normal
+            )),
+            None
+        );
+
+        // Clearing or removing restores the reported metadata.
+        font_storage.clear_face_override(mislabeled);
+        assert_eq!(font_storage.face_override(mislabeled), None);
+        assert_eq!(
+            font_storage.query_id(&query(&family_a, 700, fontdb::Style::Normal)),
+            Some(regular)
+        );
+        font_storage.remove_face(other);
+        assert_eq!(font_storage.face_override(other), None);
+    }
+
+    #[test]
+    fn test_query_report() {
+        let mut font_storage = FontStorage::new();
+        let regular = push_face(&mut font_storage, "A");
+        let bold = push_face(&mut font_storage, "A");
+        font_storage.set_face_override(
+            bold,
+            FaceOverride {
+                weight: Some(fontdb::Weight::BOLD),
+                ..Default::default()
+            },
+        );
+        let query = |families, weight| fontdb::Query {
+            families,
+            weight: fontdb::Weight(weight),
+            ..Default::default()
+        };
+
+        let (id, report) = font_storage
+            .query_id_with_report(&query(&[fontdb::Family::Name("A")], 400))
+            .unwrap();
+        assert_eq!(id, regular);
+        assert!(report.is_exact() && report.score() == 0);
+
+        let (id, report) = font_storage
+            .query_id_with_report(&query(&[fontdb::Family::Name("A")], 600))
+            .unwrap();
+        assert_eq!(id, bold);
+        assert_eq!(
+            report.substitutions,
+            [Substitution::Weight {
+                requested: fontdb::Weight::SEMIBOLD,
+                used: fontdb::Weight::BOLD,
+            }]
+        );
+
+        let (id, missing_family) = font_storage
+            .query_id_with_report(&query(
+                &[fontdb::Family::Name("Missing"), fontdb::Family::Name("A")],
+                600,
+            ))
+            .unwrap();
+        assert_eq!(id, bold);
+        assert_eq!(
+            missing_family.substitutions[0],
+            Substitution::Family {
+                requested: "Missing".to_string(),
+                used: "A".to_string(),
+            }
+        );
+        assert!(missing_family.score() > report.score());
+    }
+
+    #[test]
+    fn test_face_metrics() {
+        let (mut font_storage, id) = test_font_storage();
+
+        let metrics = font_storage.face_metrics(id).unwrap();
+        assert!(font_storage.loaded_font.read().is_empty());
+        let font = font_storage.font(id).unwrap();
+        let line_metrics = font.horizontal_line_metrics(16.0).unwrap();
+        let scale = metrics.scale(16.0);
+        assert!((f32::from(metrics.ascender) * scale - line_metrics.ascent).abs() < 0.01);
+        assert!((f32::from(metrics.descender) * scale - line_metrics.descent).abs() < 0.01);
+        assert!(metrics.descender < 0);
+        if let (Some(x_height), Some(cap_height)) = (metrics.x_height, metrics.cap_height) {
+            assert!(0 < x_height && x_height < cap_height && cap_height <= metrics.ascender);
+        }
+        assert!(metrics.average_char_width.is_none_or(|width| width > 0));
+        assert!(
+            metrics
+                .underline_thickness
+                .is_none_or(|thickness| thickness >= 0)
+        );
+        if let (Some(position), Some(x_height)) = (metrics.strikeout_position, metrics.x_height) {
+            assert!(0 < position && position < x_height);
+        }
+
+        let empty = push_face(&mut font_storage, "Empty");
+        assert!(font_storage.face_metrics(empty).is_none());
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let mut font_storage = FontStorage::new();
+        let missing = PathBuf::from("/nonexistent/font.ttf");
+        let error = font_storage.load_font_file(missing.clone()).unwrap_err();
+        assert!(matches!(
+            &error,
+            Error::Io { source, .. } if source.kind() == std::io::ErrorKind::NotFound
+        ));
+        assert_eq!(error.path(), Some(missing.as_path()));
+
+        let error = font_storage
+            .load_fonts_dir(PathBuf::from("/nonexistent"))
+            .unwrap_err();
+        assert!(matches!(error, Error::Io { .. }));
+
+        let error = font_storage
+            .load_font_binary(b"not a font".to_vec())
+            .unwrap_err();
+        assert!(matches!(error, Error::UnsupportedFormat { origin: None }));
+        // A TrueType header without tables.
+        let error = font_storage
+            .load_font_binary(vec![0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0])
+            .unwrap_err();
+        assert!(matches!(error, Error::Parse { .. }));
+        assert!(font_storage.is_empty());
+    }
+
+    #[test]
+    fn test_shared_lookups() {
+        let (font_storage, ids) = two_fonts();
+
+        // Lookups fill the caches through a shared reference, from several threads.
+        let font_storage = &font_storage;
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for &id in &ids {
+                        let font = font_storage.font(id).unwrap();
+                        assert_eq!(
+                            font_storage.line_metrics(id, 16.0),
+                            font.horizontal_line_metrics(16.0)
+                        );
+                    }
+                });
+            }
+        });
+        for &id in &ids {
+            let font = font_storage.font(id).unwrap();
+            assert!(Arc::ptr_eq(&font, &font_storage.font(id).unwrap()));
+        }
+        assert_eq!(font_storage.loaded_font.read().len(), ids.len());
+    }
+
+    #[test]
+    #[ignore = "needs system fonts"]
+    fn test_system_fonts_cache() {
+        let cache = std::env::temp_dir().join(format!("suzuri-fonts-{}.cache", std::process::id()));
+        let mut plain = FontStorage::new();
+        let ids = plain.load_system_fonts();
+        assert_eq!(ids.len(), plain.len());
+
+        let mut scanned = FontStorage::new();
+        let scan = scanned.load_system_fonts_cached(&cache);
+        assert_eq!(scan.ids.len(), ids.len());
+        assert!(!scan.from_cache);
+        assert!(!scan.ids.is_empty(), "no system fonts available");
+
+        let mut cached = FontStorage::new();
+        let load = cached.load_system_fonts_cached(&cache);
+        assert!(load.from_cache);
+        assert_eq!(load.ids.len(), scan.ids.len());
+        assert_eq!(scan.ids.len(), scanned.len());
+        let names = |storage: &FontStorage| {
+            storage
+                .faces()
+                .map(|face| (face.post_script_name.clone(), face.index))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&cached), names(&scanned));
+        assert_eq!(cached.coverage, scanned.coverage);
+        let chain_names = |storage: &FontStorage| {
+            storage
+                .default_fallback()
+                .iter()
+                .map(|&id| storage.face(id).unwrap().post_script_name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(chain_names(&cached), chain_names(&scanned));
+        let id = cached.faces().next().unwrap().id;
+        assert!(cached.font(id).is_some());
+        std::fs::remove_file(&cache).unwrap();
+    }
+
+    #[test]
+    fn test_generic_family_fallback() {
+        let mut font_storage = FontStorage::new();
+        let query = fontdb::Query {
+            families: &[fontdb::Family::Name("Missing")],
+            ..Default::default()
+        };
+        assert!(font_storage.query_id_with_fallback(&query).is_none());
+
+        // Without the sans-serif family, the closest face of any family is used.
+        let serif = push_face(&mut font_storage, "Example Serif");
+        let (id, report) = font_storage.query_id_with_fallback(&query).unwrap();
+        assert_eq!(id, serif);
+        assert_eq!(
+            report.substitutions,
+            [Substitution::Family {
+                requested: "Missing".to_string(),
+                used: "Example Serif".to_string(),
+            }]
+        );
+
+        let sans = push_face(&mut font_storage, "Example Sans");
+        font_storage.set_sans_serif_family("Example Sans");
+        let (id, report) = font_storage.query_id_with_fallback(&query).unwrap();
+        assert_eq!(id, sans);
+        assert_eq!(
+            report.substitutions,
+            [Substitution::Family {
+                requested: "Missing".to_string(),
+                used: "Example Sans".to_string(),
+            }]
+        );
+
+        // Internal lookups and strict matching see the requested families only.
+        assert!(font_storage.query_id(&query).is_none());
+        font_storage.set_strict_matching(true);
+        assert!(font_storage.query_id_with_fallback(&query).is_none());
+    }
+
+    #[test]
+    fn test_family_aliases() {
+        let mut font_storage = FontStorage::new();
+        let sans = push_face(&mut font_storage, "Example Sans");
+        let mono = push_face(&mut font_storage, "Example Mono");
+        font_storage.set_strict_matching(true);
+        let query = fontdb::Query {
+            families: &[fontdb::Family::Name("ui")],
+            ..Default::default()
+        };
+        assert!(font_storage.query_id_with_fallback(&query).is_none());
+
+        font_storage.set_alias("ui", "Example Sans");
+        assert_eq!(font_storage.alias("ui"), Some("Example Sans"));
+        let (id, report) = font_storage.query_id_with_fallback(&query).unwrap();
+        assert_eq!(id, sans);
+        assert!(report.substitutions.is_empty());
+
+        // Swapping the family behind the alias.
+        font_storage.set_alias("ui", "Example Mono");
+        assert_eq!(font_storage.query_id(&query), Some(mono));
+
+        // Aliases are not resolved recursively.
+        font_storage.set_alias("title", "ui");
+        let query = fontdb::Query {
+            families: &[fontdb::Family::Name("title")],
+            ..Default::default()
+        };
+        assert!(font_storage.query_id(&query).is_none());
+
+        font_storage.remove_alias("ui");
+        assert_eq!(font_storage.alias("ui"), None);
+    }
+
+    #[test]
+    fn test_faces_matching() {
+        let mut font_storage = FontStorage::new();
+        let sans = push_face(&mut font_storage, "Example Sans");
+        let mono = push_face(&mut font_storage, "Example Mono");
+        let italic = push_face(&mut font_storage, "Other");
+        font_storage.set_face_override(
+            italic,
+            FaceOverride {
+                families: Some(vec!["Example Serif".to_string()]),
+                style: Some(fontdb::Style::Italic),
+                ..Default::default()
+            },
+        );
+        let matching = |font_storage: &FontStorage, filter: FaceFilter| -> Vec<fontdb::ID> {
+            font_storage
+                .faces_matching(&filter)
+                .map(|face| face.id)
+                .collect()
+        };
+
+        assert_eq!(
+            matching(&font_storage, FaceFilter::default()),
+            [sans, mono, italic]
+        );
+        assert_eq!(
+            matching(
+                &font_storage,
+                FaceFilter {
+                    family: Some("example S".to_string()),
+                    ..Default::default()
+                }
+            ),
+            [sans, italic]
+        );
+        assert_eq!(
+            matching(
+                &font_storage,
+                FaceFilter {
+                    style: Some(fontdb::Style::Italic),
+                    ..Default::default()
+                }
+            ),
+            [italic]
+        );
+        // Faces without data cover nothing and are not reported as monospaced.
+        for filter in [
+            FaceFilter {
+                monospaced: true,
+                ..Default::default()
+            },
+            FaceFilter {
+                covering: Some("A".to_string()),
+                ..Default::default()
+            },
+        ] {
+            assert!(matching(&font_storage, filter).is_empty());
+        }
+        assert_eq!(
+            matching(
+                &font_storage,
+                FaceFilter {
+                    covering: Some(String::new()),
+                    ..Default::default()
+                }
+            )
+            .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_evict_unused() {
+        let (mut font_storage, [a, b]) = two_fonts();
+        let font_a = font_storage.font(a).unwrap();
+        font_storage.font(b).unwrap();
+
+        assert_eq!(font_storage.evict_unused(1), 0);
+        font_storage.font(a).unwrap();
+        assert_eq!(font_storage.evict_unused(1), 0);
+        assert_eq!(font_storage.evict_unused(1), 1);
+        assert!(font_storage.loaded_font.read().contains_key(&a));
+        assert!(!font_storage.loaded_font.read().contains_key(&b));
+        assert_eq!(font_storage.evict_unused(0), 1);
+        assert!(font_storage.loaded_font.read().is_empty());
+
+        // Evicted fonts are loaded again on use; existing handles stay valid.
+        assert!(!Arc::ptr_eq(&font_storage.font(a).unwrap(), &font_a));
+        assert!(font_a.has_glyph('A'));
+    }
+
+    #[cfg(feature = "cpu")]
+    #[test]
+    fn test_evict_unused_keeps_fonts_drawn_from_cache() {
+        use crate::renderer::{CpuCacheConfig, CpuRenderer};
+        use crate::text::{TextData, TextLayoutConfig};
+        use std::num::NonZeroUsize;
+
+        let (mut font_storage, [a, b]) = two_fonts();
+        let layout = TextData::builder(a, 16.0, 0u32)
+            .push_str("Suzuri")
+            .build()
+            .layout(&TextLayoutConfig::default(), &font_storage);
+        let mut renderer = CpuRenderer::new(&[CpuCacheConfig {
+            block_size: NonZeroUsize::new(64 * 64).unwrap(),
+            capacity: NonZeroUsize::new(16).unwrap(),
+        }]);
+        let font_a = font_storage.font(a).unwrap();
+        font_storage.char_map(b).unwrap();
+        font_storage.font(b).unwrap();
+        font_storage.line_metrics(b, 16.0);
+        font_storage.glyph_metrics(b, 0, 16.0).unwrap();
+
+        // Only the first frame rasterizes; later ones draw the cached glyphs and
+        // still keep the font loaded.
+        for frame in 0..3 {
+            renderer.render(&layout, [128, 32], &font_storage, &mut |_, _, _| {});
+            assert_eq!(font_storage.evict_unused(0), usize::from(frame == 1));
+            assert!(Arc::ptr_eq(
+                &font_storage.loaded_font.read()[&a].font,
+                &font_a
+            ));
+        }
+
+        // The metrics and character map of the evicted face went with its font.
+        assert!(!font_storage.loaded_font.read().contains_key(&b));
+        assert!(!font_storage.char_maps.read().contains_key(&b));
+        assert!(
+            !font_storage
+                .line_metrics
+                .read()
+                .keys()
+                .any(|&(id, _)| id == b)
+        );
+        assert!(
+            !font_storage
+                .glyph_metrics
+                .read()
+                .keys()
+                .any(|&(id, _, _)| id == b)
+        );
+    }
+
+    /// Packs font files into a TrueType collection.
+    fn collection(fonts: &[Vec<u8>]) -> Vec<u8> {
+        let header_len = 12 + 4 * fonts.len();
+        let mut data = Vec::new();
+        data.extend_from_slice(b"ttcf");
+        data.extend_from_slice(&[0, 1, 0, 0]);
+        data.extend_from_slice(&(fonts.len() as u32).to_be_bytes());
+        data.resize(header_len, 0);
+        for (i, font) in fonts.iter().enumerate() {
+            data.resize(data.len().next_multiple_of(4), 0);
+            let base = data.len() as u32;
+            data[12 + 4 * i..16 + 4 * i].copy_from_slice(&base.to_be_bytes());
+            let mut font = font.clone();
+            // Table offsets are relative to the start of the collection.
+            let num_tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+            for table in 0..num_tables {
+                let at = 12 + 16 * table + 8;
+                let offset = u32::from_be_bytes(font[at..at + 4].try_into().unwrap());
+                font[at..at + 4].copy_from_slice(&(offset + base).to_be_bytes());
+            }
+            data.extend_from_slice(&font);
+        }
+        data
+    }
+
+    #[test]
+    fn test_font_collection_faces() {
+        let fonts = [TEST_FONT.to_vec(), SECOND_TEST_FONT.to_vec()];
+
+        let mut font_storage = FontStorage::new();
+        let ids = font_storage.load_font_binary(collection(&fonts)).unwrap();
+        let faces: Vec<_> = font_storage.faces().cloned().collect();
+        assert_eq!(faces.len(), 2);
+        assert_eq!(ids, [faces[0].id, faces[1].id]);
+
+        for (index, (face, data)) in faces.iter().zip(&fonts).enumerate() {
+            let (_, source_index) = font_storage.face_source(face.id).unwrap();
+            assert_eq!((face.index, source_index), (index as u32, index as u32));
+
+            let expected = fontdue::Font::from_bytes(
+                data.as_slice(),
+                fontdue::FontSettings {
+                    scale: 40.0,
+                    load_substitutions: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let font = font_storage.font(face.id).unwrap();
+            assert_eq!(font.glyph_count(), expected.glyph_count());
+            let glyph = expected.lookup_glyph_index('A');
+            assert_eq!(font.lookup_glyph_index('A'), glyph);
+            assert_eq!(
+                font.rasterize_indexed(glyph, 16.0),
+                expected.rasterize_indexed(glyph, 16.0)
+            );
+
+            let description = GlyphId::new(face.id, glyph, 16.0).describe(&font_storage);
+            assert!(description.ends_with(&format!("(memory, face {index})")));
+        }
+    }
+
+    #[test]
+    fn test_font_settings() {
+        let (mut font_storage, default_id) = test_font_storage();
+        let path = PathBuf::from(TEST_FONT_PATH);
+        let coarse = FontSettings {
+            scale: 8.0,
+            ..Default::default()
+        };
+        let ids = font_storage
+            .load_font_file_with_settings(path.clone(), coarse)
+            .unwrap();
+        assert_eq!(font_storage.font_settings(ids[0]), coarse);
+        assert_eq!(
+            font_storage.font_settings(default_id),
+            FontSettings::default()
+        );
+
+        let fine = font_storage.font(default_id).unwrap();
+        font_storage.font(ids[0]).unwrap();
+        assert!(Arc::ptr_eq(&font_storage.font(default_id).unwrap(), &fine));
+
+        // Changing the default reloads the fonts that follow it.
+        assert_eq!(font_storage.set_default_font_settings(coarse), [default_id]);
+        assert!(!Arc::ptr_eq(&font_storage.font(default_id).unwrap(), &fine));
+        assert_eq!(font_storage.set_font_settings(ids[0], None), ids[..1]);
+        assert!(font_storage.set_font_settings(ids[0], None).is_empty());
+
+        // A single face of a collection; here, the only face of a font file.
+        let one = font_storage
+            .load_font_file_with_settings(
+                path.clone(),
+                FontSettings {
+                    collection_index: Some(0),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(one.len(), 1);
+        let none = font_storage
+            .load_font_file_with_settings(
+                path,
+                FontSettings {
+                    collection_index: Some(1),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_metrics_memoization() {
+        let (mut font_storage, id) = test_font_storage();
+        let font = font_storage.font(id).unwrap();
+        let glyph = font.lookup_glyph_index('A');
+
+        for _ in 0..2 {
+            assert_eq!(
+                font_storage.line_metrics(id, 16.0),
+                font.horizontal_line_metrics(16.0)
+            );
+            assert_eq!(
+                font_storage.glyph_metrics(id, glyph, 16.0),
+                Some(font.metrics_indexed(glyph, 16.0))
+            );
+        }
+        font_storage.glyph_metrics(id, glyph, 16.5);
+        assert_eq!(font_storage.line_metrics.read().len(), 1);
+        assert_eq!(font_storage.glyph_metrics.read().len(), 2);
+
+        font_storage.remove_face(id);
+        assert!(
+            font_storage.line_metrics.read().is_empty()
+                && font_storage.glyph_metrics.read().is_empty()
+        );
+        assert_eq!(font_storage.line_metrics(id, 16.0), None);
+        assert_eq!(font_storage.glyph_metrics(id, glyph, 16.0), None);
+    }
+
+    #[test]
+    fn test_mmap_loading() {
+        let path = PathBuf::from(TEST_FONT_PATH);
+
+        let mut font_storage = FontStorage::new();
+        // SAFETY: the bundled fonts are not modified while the test runs.
+        unsafe { font_storage.load_font_file_mmap(path.clone()) }.unwrap();
+        let mapped: Vec<_> = font_storage.faces().cloned().collect();
+        assert!(
+            mapped
+                .iter()
+                .all(|face| matches!(&face.source, fontdb::Source::SharedFile(p, _) if *p == path))
+        );
+
+        let mut file_storage = FontStorage::new();
+        file_storage.load_font_file(path.clone()).unwrap();
+        let file_id = file_storage.faces().next().unwrap().id;
+        let from_file = file_storage.font(file_id).unwrap();
+        let from_map = font_storage.font(mapped[0].id).unwrap();
+        let glyph = from_file.lookup_glyph_index('A');
+        assert_eq!(from_map.lookup_glyph_index('A'), glyph);
+        assert_eq!(
+            from_file.rasterize_indexed(glyph, 16.0),
+            from_map.rasterize_indexed(glyph, 16.0)
+        );
+        assert_eq!(
+            font_storage.face_covers(mapped[0].id, 'A'),
+            from_file.has_glyph('A')
+        );
+
+        // Directories map every file.
+        let mut font_storage = FontStorage::new();
+        let ids = unsafe { font_storage.load_fonts_dir_mmap(path.parent().unwrap().to_path_buf()) }
+            .unwrap();
+        assert!(ids.len() >= mapped.len());
+        assert_eq!(ids.len(), font_storage.faces().count());
+        assert!(
+            font_storage
+                .faces()
+                .all(|face| matches!(face.source, fontdb::Source::SharedFile(..)))
+        );
+        assert!(
+            unsafe { font_storage.load_font_file_mmap("/nonexistent/font.ttf".into()) }.is_err()
+        );
+    }
 }
@@ -0,0 +1,457 @@
+//! On-disk cache of the scanned system font database.
+//!
+//! The cache lists the metadata of every face loaded from a file, with all of
+//! its localized family names and the coverage computed for it by fallback
+//! detection. It also lists every file below the directories fonts are loaded
+//! from, with its size and modification time. Adding, removing or replacing a
+//! font file anywhere below those directories, including in new
+//! subdirectories, changes that list, which makes the cache stale.
+//!
+//! The format is a private little-endian binary layout, versioned by [`MAGIC`].
+
+use std::{
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use super::FaceCoverage;
+
+const MAGIC: &[u8; 16] = b"suzuri-fontdb-02";
+
+/// Faces read from the cache, with the coverage cached for each.
+pub(crate) type CachedFaces = Vec<(fontdb::FaceInfo, Option<FaceCoverage>)>;
+
+/// Reads the faces cached at `path`.
+///
+/// Returns `Ok(None)` if the cache is stale or was written by another version.
+pub(crate) fn read(path: &Path) -> io::Result<Option<CachedFaces>> {
+    let data = std::fs::read(path)?;
+    let mut reader = data.as_slice();
+
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Ok(None);
+    }
+
+    let mut roots = Vec::new();
+    for _ in 0..read_u32(&mut reader)? {
+        roots.push(read_path(&mut reader)?);
+    }
+    let mut files = Vec::new();
+    for _ in 0..read_u32(&mut reader)? {
+        files.push(FileStamp {
+            path: read_path(&mut reader)?,
+            size: read_u64(&mut reader)?,
+            modified: read_time(&mut reader)?,
+        });
+    }
+    if stamp_files(&roots) != files {
+        log::info!("Font cache is stale: font files changed");
+        return Ok(None);
+    }
+
+    let count = read_u32(&mut reader)?;
+    let mut faces = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let source = fontdb::Source::File(read_path(&mut reader)?);
+        let index = read_u32(&mut reader)?;
+        let mut families = Vec::new();
+        for _ in 0..read_u32(&mut reader)? {
+            let name = read_string(&mut reader)?;
+            let language = language_from_id(read_u16(&mut reader)?);
+            families.push((name, language));
+        }
+        let post_script_name = read_string(&mut reader)?;
+        let style = match read_u8(&mut reader)? {
+            1 => fontdb::Style::Italic,
+            2 => fontdb::Style::Oblique,
+            _ => fontdb::Style::Normal,
+        };
+        let weight = fontdb::Weight(read_u16(&mut reader)?);
+        let stretch = stretch_from_number(read_u16(&mut reader)?);
+        let monospaced = read_u8(&mut reader)? != 0;
+        let coverage = match read_u8(&mut reader)? {
+            0 => None,
+            _ => Some(FaceCoverage {
+                samples: read_u32(&mut reader)?,
+                outlines: read_u8(&mut reader)? != 0,
+            }),
+        };
+
+        let face = fontdb::FaceInfo {
+            id: fontdb::ID::dummy(),
+            source,
+            index,
+            families,
+            post_script_name,
+            style,
+            weight,
+            stretch,
+            monospaced,
+        };
+        faces.push((face, coverage));
+    }
+    Ok(Some(faces))
+}
+
+/// Writes the faces of `faces` loaded from files, with their coverage if
+/// known, to a cache at `path`.
+///
+/// The file is replaced atomically, so concurrent readers never see a partial cache.
+pub(crate) fn write<'a>(
+    path: &Path,
+    faces: impl Iterator<Item = (&'a fontdb::FaceInfo, Option<FaceCoverage>)>,
+) -> io::Result<()> {
+    let faces: Vec<(&Path, &fontdb::FaceInfo, Option<FaceCoverage>)> = faces
+        .filter_map(|(face, coverage)| match &face.source {
+            fontdb::Source::File(path) => Some((path.as_path(), face, coverage)),
+            _ => None,
+        })
+        .collect();
+    let roots = font_roots(faces.iter().map(|&(path, ..)| path));
+    let files = stamp_files(&roots);
+
+    let mut data = Vec::new();
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&(roots.len() as u32).to_le_bytes());
+    for root in &roots {
+        write_path(&mut data, root)?;
+    }
+    data.extend_from_slice(&(files.len() as u32).to_le_bytes());
+    for file in &files {
+        write_path(&mut data, &file.path)?;
+        data.extend_from_slice(&file.size.to_le_bytes());
+        write_time(&mut data, file.modified)?;
+    }
+
+    let language_ids = language_ids();
+    data.extend_from_slice(&(faces.len() as u32).to_le_bytes());
+    for (path, face, coverage) in faces {
+        write_path(&mut data, path)?;
+        data.extend_from_slice(&face.index.to_le_bytes());
+        data.extend_from_slice(&(face.families.len() as u32).to_le_bytes());
+        for (name, language) in &face.families {
+            write_string(&mut data, name);
+            let id = language_ids.get(*language as usize).copied().unwrap_or(0);
+            data.extend_from_slice(&id.to_le_bytes());
+        }
+        write_string(&mut data, &face.post_script_name);
+        data.push(match face.style {
+            fontdb::Style::Normal => 0,
+            fontdb::Style::Italic => 1,
+            fontdb::Style::Oblique => 2,
+        });
+        data.extend_from_slice(&face.weight.0.to_le_bytes());
+        data.extend_from_slice(&face.stretch.to_number().to_le_bytes());
+        data.push(u8::from(face.monospaced));
+        match coverage {
+            Some(coverage) => {
+                data.push(1);
+                data.extend_from_slice(&coverage.samples.to_le_bytes());
+                data.push(u8::from(coverage.outlines));
+            }
+            None => data.push(0),
+        }
+    }
+
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let mut file = std::fs::File::create(&temp)?;
+    file.write_all(&data)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(temp, path)
+}
+
+/// A file below the font directories, as it was when the cache was written.
+#[derive(Debug, PartialEq)]
+struct FileStamp {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Returns the directories `fontdb` looks for system fonts in, whether they
+/// exist or not, and the outermost directories of `files` outside of them,
+/// such as those configured through fontconfig.
+fn font_roots<'a>(files: impl Iterator<Item = &'a Path>) -> Vec<PathBuf> {
+    let mut roots = system_font_dirs();
+    let mut dirs: Vec<&Path> = files.filter_map(Path::parent).collect();
+    dirs.sort();
+    dirs.dedup();
+    for dir in dirs {
+        if !roots.iter().any(|root| dir.starts_with(root)) {
+            roots.push(dir.to_path_buf());
+        }
+    }
+    roots
+}
+
+/// Directories `fontdb` loads system fonts from on every platform setup.
+fn system_font_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(target_os = "windows") {
+        if let Ok(root) = std::env::var("SYSTEMROOT") {
+            dirs.push(Path::new(&root).join("Fonts"));
+        }
+        if let Ok(home) = std::env::var("USERPROFILE") {
+            let home = Path::new(&home);
+            dirs.push(home.join("AppData\\Local\\Microsoft\\Windows\\Fonts"));
+            dirs.push(home.join("AppData\\Roaming\\Microsoft\\Windows\\Fonts"));
+        }
+    } else if cfg!(target_os = "macos") {
+        dirs.push(PathBuf::from("/Library/Fonts"));
+        dirs.push(PathBuf::from("/System/Library/Fonts"));
+        dirs.push(PathBuf::from("/Network/Library/Fonts"));
+        if let Ok(home) = std::env::var("HOME") {
+            dirs.push(Path::new(&home).join("Library/Fonts"));
+        }
+    } else {
+        dirs.push(PathBuf::from("/usr/share/fonts"));
+        dirs.push(PathBuf::from("/usr/local/share/fonts"));
+        if let Ok(home) = std::env::var("HOME") {
+            let home = Path::new(&home);
+            dirs.push(home.join(".fonts"));
+            dirs.push(home.join(".local/share/fonts"));
+        }
+    }
+    dirs
+}
+
+/// Returns every file below `roots`, following symbolic links, sorted by path.
+fn stamp_files(roots: &[PathBuf]) -> Vec<FileStamp> {
+    let mut files = Vec::new();
+    let mut visited = std::collections::HashSet::new();
+    let mut pending: Vec<PathBuf> = roots.to_vec();
+    while let Some(dir) = pending.pop() {
+        // Links may lead back into a directory that was walked already.
+        let Ok(canonical) = dir.canonicalize() else {
+            continue;
+        };
+        if !visited.insert(canonical) {
+            continue;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = std::fs::metadata(&path) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(path);
+            } else if let Ok(modified) = metadata.modified() {
+                files.push(FileStamp {
+                    path,
+                    size: metadata.len(),
+                    modified,
+                });
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files.dedup_by(|a, b| a.path == b.path);
+    files
+}
+
+/// Returns the language of the Windows language ID `id` of a `name` table record.
+fn language_from_id(id: u16) -> fontdb::Language {
+    ttf_parser::name::Name {
+        platform_id: ttf_parser::PlatformId::Windows,
+        encoding_id: 0,
+        language_id: id,
+        name_id: 0,
+        name: &[],
+    }
+    .language()
+}
+
+/// Returns the Windows language ID of each language, indexed by the language.
+fn language_ids() -> Vec<u16> {
+    let mut ids = Vec::new();
+    for id in 0..=u16::MAX {
+        let language = language_from_id(id);
+        if language == fontdb::Language::Unknown {
+            continue;
+        }
+        let index = language as usize;
+        if ids.len() <= index {
+            ids.resize(index + 1, 0);
+        }
+        ids[index] = id;
+    }
+    ids
+}
+
+fn stretch_from_number(number: u16) -> fontdb::Stretch {
+    match number {
+        1 => fontdb::Stretch::UltraCondensed,
+        2 => fontdb::Stretch::ExtraCondensed,
+        3 => fontdb::Stretch::Condensed,
+        4 => fontdb::Stretch::SemiCondensed,
+        6 => fontdb::Stretch::SemiExpanded,
+        7 => fontdb::Stretch::Expanded,
+        8 => fontdb::Stretch::ExtraExpanded,
+        9 => fontdb::Stretch::UltraExpanded,
+        _ => fontdb::Stretch::Normal,
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u8(reader: &mut &[u8]) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16(reader: &mut &[u8]) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32(reader: &mut &[u8]) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut &[u8]) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_string(reader: &mut &[u8]) -> io::Result<String> {
+    let len = read_u32(reader)? as usize;
+    if reader.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let (bytes, rest) = reader.split_at(len);
+    *reader = rest;
+    String::from_utf8(bytes.to_vec()).map_err(|_| invalid_data("invalid UTF-8 in font cache"))
+}
+
+fn write_string(data: &mut Vec<u8>, string: &str) {
+    data.extend_from_slice(&(string.len() as u32).to_le_bytes());
+    data.extend_from_slice(string.as_bytes());
+}
+
+fn read_path(reader: &mut &[u8]) -> io::Result<PathBuf> {
+    read_string(reader).map(PathBuf::from)
+}
+
+/// Paths are stored as UTF-8; a database with other paths is not cached.
+fn write_path(data: &mut Vec<u8>, path: &Path) -> io::Result<()> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| invalid_data("font path is not valid UTF-8"))?;
+    write_string(data, path);
+    Ok(())
+}
+
+fn read_time(reader: &mut &[u8]) -> io::Result<SystemTime> {
+    let mut secs = [0; 8];
+    reader.read_exact(&mut secs)?;
+    let nanos = read_u32(reader)?;
+    Ok(SystemTime::UNIX_EPOCH + Duration::new(u64::from_le_bytes(secs), nanos))
+}
+
+fn write_time(data: &mut Vec<u8>, time: SystemTime) -> io::Result<()> {
+    let since_epoch = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_err(|_| invalid_data("modification time before 1970"))?;
+    data.extend_from_slice(&since_epoch.as_secs().to_le_bytes());
+    data.extend_from_slice(&since_epoch.subsec_nanos().to_le_bytes());
+    Ok(())
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_staleness() {
+        let dir = std::env::temp_dir().join(format!("suzuri-db-cache-{}", std::process::id()));
+        let fonts = dir.join("fonts");
+        std::fs::create_dir_all(&fonts).unwrap();
+        std::fs::write(fonts.join("Example.ttc"), b"font").unwrap();
+        let cache = dir.join("fonts.cache");
+
+        let mut font_db = fontdb::Database::new();
+        font_db.push_face_info(fontdb::FaceInfo {
+            id: fontdb::ID::dummy(),
+            source: fontdb::Source::File(fonts.join("Example.ttc")),
+            index: 2,
+            families: vec![
+                (
+                    "Example".to_string(),
+                    fontdb::Language::English_UnitedStates,
+                ),
+                ("Exemple".to_string(), fontdb::Language::French_France),
+                ("例".to_string(), fontdb::Language::Japanese_Japan),
+            ],
+            post_script_name: "Example-BoldItalic".to_string(),
+            style: fontdb::Style::Italic,
+            weight: fontdb::Weight::BOLD,
+            stretch: fontdb::Stretch::Condensed,
+            monospaced: true,
+        });
+        // Faces not loaded from files are left out.
+        let binary = fontdb::FaceInfo {
+            source: fontdb::Source::Binary(std::sync::Arc::new(Vec::<u8>::new())),
+            ..font_db.faces().next().unwrap().clone()
+        };
+        font_db.push_face_info(binary);
+        let coverage = FaceCoverage {
+            samples: 0b101,
+            outlines: true,
+        };
+        let write_cache = || write(&cache, font_db.faces().map(|face| (face, Some(coverage))));
+        write_cache().unwrap();
+
+        let faces = read(&cache).unwrap().unwrap();
+        assert_eq!(faces.len(), 1);
+        let ((face, cached_coverage), expected) = (&faces[0], font_db.faces().next().unwrap());
+        assert!(
+            matches!(&face.source, fontdb::Source::File(path) if *path == fonts.join("Example.ttc"))
+        );
+        assert_eq!(face.index, expected.index);
+        assert_eq!(face.families, expected.families);
+        assert_eq!(face.post_script_name, expected.post_script_name);
+        assert_eq!(face.style, expected.style);
+        assert_eq!(face.weight, expected.weight);
+        assert_eq!(face.stretch, expected.stretch);
+        assert!(face.monospaced);
+        assert_eq!(*cached_coverage, Some(coverage));
+
+        // A font added to a new subdirectory makes the cache stale.
+        std::fs::create_dir(fonts.join("new")).unwrap();
+        assert!(read(&cache).unwrap().is_some());
+        std::fs::write(fonts.join("new/New.ttf"), b"font").unwrap();
+        assert!(read(&cache).unwrap().is_none());
+
+        // So does a font overwritten in place, even with the same size.
+        write_cache().unwrap();
+        assert!(read(&cache).unwrap().is_some());
+        let file = std::fs::File::options()
+            .write(true)
+            .open(fonts.join("Example.ttc"))
+            .unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert!(read(&cache).unwrap().is_none());
+
+        std::fs::write(&cache, b"not a cache").unwrap();
+        assert!(read(&cache).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+//! CSS-style face matching.
+//!
+//! This mirrors the algorithm `fontdb::Database::query` uses (CSS Fonts Level 3,
+//! section 5.2, step 4) but operates on plain [`Candidate`] values so the
+//! storage can feed it metadata that differs from what the font file reports.
+
+use fontdb::{Stretch, Style, Weight};
+
+/// The subset of face metadata that takes part in matching.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Candidate {
+    pub id: fontdb::ID,
+    pub style: Style,
+    pub weight: Weight,
+    pub stretch: Stretch,
+}
+
+/// A difference between a query and the face that was chosen for it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Substitution {
+    /// No face of the requested family exists; a later family of the query was used.
+    Family {
+        /// The first family of the query.
+        requested: String,
+        /// The family of the chosen face.
+        used: String,
+    },
+    /// The family has no face of the requested weight.
+    Weight {
+        /// The weight of the query.
+        requested: Weight,
+        /// The weight of the chosen face.
+        used: Weight,
+    },
+    /// The family has no face of the requested stretch.
+    Stretch {
+        /// The stretch of the query.
+        requested: Stretch,
+        /// The stretch of the chosen face.
+        used: Stretch,
+    },
+    /// The family has no face of the requested style.
+    Style {
+        /// The style of the query.
+        requested: Style,
+        /// The style of the chosen face.
+        used: Style,
+    },
+}
+
+/// How the face chosen by [`FontStorage::query_with_report`](crate::FontStorage::query_with_report)
+/// differs from the query.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MatchReport {
+    /// The substitutions made, family first, then stretch, style and weight.
+    pub substitutions: Vec<Substitution>,
+}
+
+impl MatchReport {
+    /// Returns `true` if the chosen face matches the query exactly.
+    pub fn is_exact(&self) -> bool {
+        self.substitutions.is_empty()
+    }
+
+    /// Returns how far the chosen face is from the query; `0` for an exact match.
+    ///
+    /// A family substitution outweighs any difference in stretch, which
+    /// outweighs style, which outweighs weight, following the order in which
+    /// matching narrows the faces down.
+    pub fn score(&self) -> u32 {
+        self.substitutions
+            .iter()
+            .map(|substitution| match substitution {
+                Substitution::Family { .. } => 1 << 24,
+                Substitution::Stretch { requested, used } => {
+                    u32::from(requested.to_number().abs_diff(used.to_number())) << 16
+                }
+                Substitution::Style { requested, used } => {
+                    let rank = |style: &Style| match style {
+                        Style::Normal => 0u32,
+                        Style::Oblique => 1,
+                        Style::Italic => 2,
+                    };
+                    rank(requested).abs_diff(rank(used)) << 12
+                }
+                Substitution::Weight { requested, used } => u32::from(requested.0.abs_diff(used.0)),
+            })
+            .sum()
+    }
+}
+
+impl Candidate {
+    /// Lists how this candidate differs from `query` in stretch, style and weight.
+    pub(crate) fn substitutions(&self, query: &fontdb::Query) -> Vec<Substitution> {
+        let mut substitutions = Vec::new();
+        if self.stretch != query.stretch {
+            substitutions.push(Substitution::Stretch {
+                requested: query.stretch,
+                used: self.stretch,
+            });
+        }
+        if self.style != query.style {
+            substitutions.push(Substitution::Style {
+                requested: query.style,
+                used: self.style,
+            });
+        }
+        if self.weight != query.weight {
+            substitutions.push(Substitution::Weight {
+                requested: query.weight,
+                used: self.weight,
+            });
+        }
+        substitutions
+    }
+}
+
+/// Returns the index of the best candidate for `query`, or `None` when `candidates` is empty.
+pub(crate) fn find_best_match(candidates: &[Candidate], query: &fontdb::Query) -> Option<usize> {
+    let mut matching_set: Vec<usize> = (0..candidates.len()).collect();
+
+    // Step 4a (`font-stretch`).
+    let query_stretch = query.stretch.to_number();
+    let matching_stretch = if matching_set
+        .iter()
+        .any(|&index| candidates[index].stretch == query.stretch)
+    {
+        query.stretch
+    } else {
+        // Narrower values are checked first for condensed queries, wider ones otherwise.
+        let prefer_narrower = query.stretch <= Stretch::Normal;
+        let preferred = matching_set
+            .iter()
+            .filter(|&&index| (candidates[index].stretch < query.stretch) == prefer_narrower)
+            .min_by_key(|&&index| {
+                candidates[index]
+                    .stretch
+                    .to_number()
+                    .abs_diff(query_stretch)
+            });
+
+        let index = match preferred {
+            Some(&index) => index,
+            None => *matching_set.iter().min_by_key(|&&index| {
+                candidates[index]
+                    .stretch
+                    .to_number()
+                    .abs_diff(query_stretch)
+            })?,
+        };
+        candidates[index].stretch
+    };
+    matching_set.retain(|&index| candidates[index].stretch == matching_stretch);
+
+    // Step 4b (`font-style`).
+    let style_preference = match query.style {
+        Style::Italic => [Style::Italic, Style::Oblique, Style::Normal],
+        Style::Oblique => [Style::Oblique, Style::Italic, Style::Normal],
+        Style::Normal => [Style::Normal, Style::Oblique, Style::Italic],
+    };
+    let matching_style = *style_preference.iter().find(|&&style| {
+        matching_set
+            .iter()
+            .any(|&index| candidates[index].style == style)
+    })?;
+    matching_set.retain(|&index| candidates[index].style == matching_style);
+
+    // Step 4c (`font-weight`).
+    //
+    // The spec doesn't say what to do if the weight is between 400 and 500 exclusive,
+    // so 450 is used as the cutoff, like fontdb does.
+    let weight = query.weight.0;
+    let has_weight = |w: u16| {
+        matching_set
+            .iter()
+            .any(|&index| candidates[index].weight.0 == w)
+    };
+
+    let matching_weight = if has_weight(weight) {
+        Weight(weight)
+    } else if (400..450).contains(&weight) && has_weight(500) {
+        Weight::MEDIUM
+    } else if (450..=500).contains(&weight) && has_weight(400) {
+        Weight::NORMAL
+    } else {
+        // Thinner values are checked first for light queries, bolder ones otherwise.
+        let prefer_thinner = weight <= 500;
+        let preferred = matching_set
+            .iter()
+            .filter(|&&index| {
+                let candidate = candidates[index].weight.0;
+                if prefer_thinner {
+                    candidate <= weight
+                } else {
+                    candidate >= weight
+                }
+            })
+            .min_by_key(|&&index| candidates[index].weight.0.abs_diff(weight));
+
+        let index = match preferred {
+            Some(&index) => index,
+            None => *matching_set
+                .iter()
+                .min_by_key(|&&index| candidates[index].weight.0.abs_diff(weight))?,
+        };
+        candidates[index].weight
+    };
+    matching_set.retain(|&index| candidates[index].weight == matching_weight);
+
+    // Step 4d (`font-size`) is ignored.
+    matching_set.into_iter().next()
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::make_id;
+
+    fn candidate(n: u64, weight: u16, style: Style, stretch: Stretch) -> Candidate {
+        Candidate {
+            id: make_id(n),
+            style,
+            weight: Weight(weight),
+            stretch,
+        }
+    }
+
+    fn query(weight: u16, style: Style, stretch: Stretch) -> fontdb::Query<'static> {
+        fontdb::Query {
+            families: &[],
+            weight: Weight(weight),
+            stretch,
+            style,
+        }
+    }
+
+    #[test]
+    fn test_exact_match() {
+        let candidates = [
+            candidate(1, 400, Style::Normal, Stretch::Normal),
+            candidate(2, 700, Style::Normal, Stretch::Normal),
+            candidate(3, 700, Style::Italic, Stretch::Normal),
+        ];
+
+        let index = find_best_match(&candidates, &query(700, Style::Italic, Stretch::Normal));
+        assert_eq!(index, Some(2));
+    }
+
+    #[test]
+    fn test_weight_fallback_direction() {
+        let candidates = [
+            candidate(1, 300, Style::Normal, Stretch::Normal),
+            candidate(2, 700, Style::Normal, Stretch::Normal),
+        ];
+
+        // Light queries prefer thinner faces, bold queries prefer bolder faces.
+        let index = find_best_match(&candidates, &query(400, Style::Normal, Stretch::Normal));
+        assert_eq!(index, Some(0));
+        let index = find_best_match(&candidates, &query(600, Style::Normal, Stretch::Normal));
+        assert_eq!(index, Some(1));
+        let index = find_best_match(&candidates, &query(900, Style::Normal, Stretch::Normal));
+        assert_eq!(index, Some(1));
+    }
+
+    #[test]
+    fn test_stretch_before_weight() {
+        let candidates = [
+            candidate(1, 400, Style::Normal, Stretch::Condensed),
+            candidate(2, 700, Style::Normal, Stretch::Normal),
+        ];
+
+        let index = find_best_match(&candidates, &query(400, Style::Normal, Stretch::Normal));
+        assert_eq!(index, Some(1));
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(
+            find_best_match(&[], &query(400, Style::Normal, Stretch::Normal)),
+            None
+        );
+    }
+}
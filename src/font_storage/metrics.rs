@@ -0,0 +1,85 @@
+//! Face-wide metrics from the `hhea`, `OS/2` and `post` tables.
+
+/// Metrics of a face, in font units; see [`FontStorage::face_metrics`](crate::FontStorage::face_metrics).
+///
+/// Multiply by [`Self::scale`] to convert to pixels at a font size. Metrics
+/// from the `OS/2` and `post` tables are `None` for fonts without them, or with
+/// a table version that predates them. Ascenders are positive and descenders negative,
+/// i.e. y points up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FaceMetrics {
+    /// The size of the em square.
+    pub units_per_em: u16,
+    /// The ascender used for line layout.
+    ///
+    /// The typographic ascender if the font asks for it, the `hhea` ascender otherwise.
+    pub ascender: i16,
+    /// The descender used for line layout, see [`Self::ascender`].
+    pub descender: i16,
+    /// The line gap used for line layout, see [`Self::ascender`].
+    pub line_gap: i16,
+    /// The height of lowercase letters such as "x".
+    pub x_height: Option<i16>,
+    /// The height of flat capital letters such as "H".
+    pub cap_height: Option<i16>,
+    /// The average advance width of the face's glyphs (`xAvgCharWidth`).
+    pub average_char_width: Option<i16>,
+    /// The typographic ascender from the `OS/2` table.
+    pub typo_ascender: Option<i16>,
+    /// The typographic descender from the `OS/2` table.
+    pub typo_descender: Option<i16>,
+    /// The typographic line gap from the `OS/2` table.
+    pub typo_line_gap: Option<i16>,
+    /// The clipping ascent from the `OS/2` table (`usWinAscent`).
+    pub win_ascent: Option<i16>,
+    /// The clipping descent from the `OS/2` table (`usWinDescent`), negated like the
+    /// other descenders.
+    pub win_descent: Option<i16>,
+    /// Whether the font asks for the typographic metrics to be used for line layout.
+    pub use_typo_metrics: bool,
+    /// The top of the underline relative to the baseline, from the `post` table.
+    pub underline_position: Option<i16>,
+    /// The thickness of the underline, from the `post` table.
+    pub underline_thickness: Option<i16>,
+    /// The top of the strikeout stroke relative to the baseline, from the `OS/2` table.
+    pub strikeout_position: Option<i16>,
+    /// The thickness of the strikeout stroke, from the `OS/2` table.
+    pub strikeout_thickness: Option<i16>,
+}
+
+impl FaceMetrics {
+    /// Returns the factor converting font units to pixels at `font_size`.
+    pub fn scale(&self, font_size: f32) -> f32 {
+        font_size / f32::from(self.units_per_em.max(1))
+    }
+}
+
+/// Reads the metrics of `face`, with its variation coordinates applied.
+pub(crate) fn face_metrics(face: &ttf_parser::Face) -> FaceMetrics {
+    let os2 = face.tables().os2;
+    let underline = face.underline_metrics();
+    let strikeout = face.strikeout_metrics();
+    FaceMetrics {
+        units_per_em: face.units_per_em(),
+        ascender: face.ascender(),
+        descender: face.descender(),
+        line_gap: face.line_gap(),
+        x_height: face.x_height(),
+        cap_height: face.capital_height(),
+        average_char_width: face
+            .raw_face()
+            .table(ttf_parser::Tag::from_bytes(b"OS/2"))
+            .and_then(|table| table.get(2..4))
+            .map(|bytes| i16::from_be_bytes([bytes[0], bytes[1]])),
+        typo_ascender: face.typographic_ascender(),
+        typo_descender: face.typographic_descender(),
+        typo_line_gap: face.typographic_line_gap(),
+        win_ascent: os2.map(|os2| os2.windows_ascender()),
+        win_descent: os2.map(|os2| os2.windows_descender()),
+        use_typo_metrics: os2.is_some_and(|os2| os2.use_typographic_metrics()),
+        underline_position: underline.map(|metrics| metrics.position),
+        underline_thickness: underline.map(|metrics| metrics.thickness),
+        strikeout_position: strikeout.map(|metrics| metrics.position),
+        strikeout_thickness: strikeout.map(|metrics| metrics.thickness),
+    }
+}
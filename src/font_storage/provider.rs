@@ -0,0 +1,145 @@
+//! Fonts backed by an application's asset system.
+
+use std::sync::{Arc, OnceLock};
+
+use crate::{Error, error::FontOrigin};
+
+/// A source of font files other than paths and byte buffers, e.g. pak files,
+/// embedded resources or archives.
+///
+/// Fonts are named by keys meaningful to the provider. Registering a font reads
+/// it once to learn its faces; the data is then dropped and read again, and
+/// kept, the first time a face is used. See [`FontStorage::load_provided_font`](crate::FontStorage::load_provided_font).
+pub trait FontProvider: Send + Sync {
+    /// Returns the contents of the font file named `key`.
+    fn read(&self, key: &str) -> Result<Vec<u8>, std::io::Error>;
+
+    /// Returns the keys of the fonts the provider offers.
+    ///
+    /// Used by [`FontStorage::load_provider`](crate::FontStorage::load_provider);
+    /// empty by default.
+    fn keys(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Font data read from a provider on first access.
+struct LazyData {
+    provider: Arc<dyn FontProvider>,
+    key: String,
+    data: OnceLock<Vec<u8>>,
+}
+
+impl AsRef<[u8]> for LazyData {
+    fn as_ref(&self) -> &[u8] {
+        if let Some(data) = self.data.get() {
+            return data;
+        }
+        match self.provider.read(&self.key) {
+            Ok(data) => self.data.get_or_init(|| data),
+            Err(e) => {
+                // Parsing the empty data fails, so the face is treated as unloadable
+                // and reading is retried on the next access.
+                log::error!("Failed to read provided font {}: {}", self.key, e);
+                &[]
+            }
+        }
+    }
+}
+
+/// Parses the font `key` of `provider` into `font_db`, with lazily read data.
+pub(crate) fn load_into(
+    provider: &Arc<dyn FontProvider>,
+    key: &str,
+    font_db: &mut fontdb::Database,
+) -> Result<(), Error> {
+    let data = provider
+        .read(key)
+        .map_err(|e| Error::from(e).with_origin(FontOrigin::Key(key.to_string())))?;
+    let mut parsed = fontdb::Database::new();
+    crate::font_storage::load_source(&mut parsed, fontdb::Source::Binary(Arc::new(data)))
+        .map_err(|e| e.with_origin(FontOrigin::Key(key.to_string())))?;
+
+    let data: Arc<dyn AsRef<[u8]> + Send + Sync> = Arc::new(LazyData {
+        provider: Arc::clone(provider),
+        key: key.to_string(),
+        data: OnceLock::new(),
+    });
+    for face in parsed.faces() {
+        font_db.push_face_info(fontdb::FaceInfo {
+            source: fontdb::Source::Binary(Arc::clone(&data)),
+            ..face.clone()
+        });
+    }
+    Ok(())
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::{FontStorage, test_utils::TEST_FONT_PATH};
+
+    /// Serves files by name and counts the reads.
+    struct Assets {
+        files: HashMap<String, PathBuf>,
+        reads: AtomicUsize,
+    }
+
+    impl FontProvider for Assets {
+        fn read(&self, key: &str) -> Result<Vec<u8>, std::io::Error> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            match self.files.get(key) {
+                Some(path) => std::fs::read(path),
+                None => Err(std::io::ErrorKind::NotFound.into()),
+            }
+        }
+
+        fn keys(&self) -> Vec<String> {
+            self.files.keys().cloned().collect()
+        }
+    }
+
+    #[test]
+    fn test_provided_fonts_are_read_lazily() {
+        let assets = Arc::new(Assets {
+            files: HashMap::from([("fonts/main.ttf".to_string(), PathBuf::from(TEST_FONT_PATH))]),
+            reads: AtomicUsize::new(0),
+        });
+        let provider: Arc<dyn FontProvider> = assets.clone();
+
+        let mut font_storage = FontStorage::new();
+        let ids = font_storage
+            .load_provided_font(&provider, "fonts/main.ttf")
+            .unwrap();
+        assert!(!ids.is_empty());
+        assert_eq!(assets.reads.load(Ordering::SeqCst), 1);
+
+        let font = font_storage.font(ids[0]).unwrap();
+        assert!(font_storage.face_covers(ids[0], 'A') == font.has_glyph('A'));
+        font_storage.evict_unused(0);
+        font_storage.evict_unused(0);
+        font_storage.font(ids[0]).unwrap();
+        assert_eq!(assets.reads.load(Ordering::SeqCst), 2);
+
+        let error = font_storage
+            .load_provided_font(&provider, "fonts/missing.ttf")
+            .unwrap_err();
+        assert!(matches!(
+            &error,
+            Error::Io { source, .. } if source.kind() == std::io::ErrorKind::NotFound
+        ));
+        assert_eq!(
+            error.origin(),
+            Some(&FontOrigin::Key("fonts/missing.ttf".to_string()))
+        );
+        assert_eq!(error.path(), None);
+        assert_eq!(font_storage.load_provider(&provider).len(), ids.len());
+    }
+}
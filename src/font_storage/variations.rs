@@ -0,0 +1,672 @@
+//! Instances of variable fonts.
+//!
+//! fontdue only reads the default instance of a variable font. Faces created by
+//! [`FontStorage::variation_instance`](super::FontStorage::variation_instance)
+//! still use fontdue for character mapping, kerning and line metrics, but their
+//! advances and outlines are read with `ttf-parser` at the chosen coordinates and
+//! rasterized here, with the same metrics conventions as fontdue.
+
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::Mutex;
+
+/// A coordinate on a variation axis of a variable font.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Variation {
+    /// The axis tag, e.g. `*b"wght"`.
+    pub tag: [u8; 4],
+    /// The coordinate in user units, e.g. `700.0` for a bold weight.
+    pub value: f32,
+}
+
+impl Variation {
+    /// Creates a coordinate on the axis `tag`.
+    pub fn new(tag: [u8; 4], value: f32) -> Self {
+        Self { tag, value }
+    }
+
+    /// A coordinate on the weight axis (`wght`), from 1 to 1000.
+    pub fn weight(value: f32) -> Self {
+        Self::new(*b"wght", value)
+    }
+
+    /// A coordinate on the width axis (`wdth`), in percent of the normal width.
+    pub fn width(value: f32) -> Self {
+        Self::new(*b"wdth", value)
+    }
+
+    /// A coordinate on the slant axis (`slnt`), in degrees; negative values lean right.
+    pub fn slant(value: f32) -> Self {
+        Self::new(*b"slnt", value)
+    }
+
+    /// A coordinate on the italic axis (`ital`), from 0 to 1.
+    pub fn italic(value: f32) -> Self {
+        Self::new(*b"ital", value)
+    }
+}
+
+/// A variation axis of a variable font, as listed in its `fvar` table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VariationAxis {
+    /// The axis tag, e.g. `*b"wght"`.
+    pub tag: [u8; 4],
+    /// The smallest coordinate the font supports.
+    pub min_value: f32,
+    /// The coordinate of the default instance.
+    pub default_value: f32,
+    /// The largest coordinate the font supports.
+    pub max_value: f32,
+    /// Whether the font asks applications not to show the axis in user interfaces.
+    pub hidden: bool,
+}
+
+/// Returns the variation axes of a parsed face.
+pub(crate) fn axes(face: &ttf_parser::Face) -> Vec<VariationAxis> {
+    face.variation_axes()
+        .into_iter()
+        .map(|axis| VariationAxis {
+            tag: axis.tag.to_bytes(),
+            min_value: axis.min_value,
+            default_value: axis.def_value,
+            max_value: axis.max_value,
+            hidden: axis.hidden,
+        })
+        .collect()
+}
+
+/// A predefined instance of a variable font, e.g. "Condensed Bold".
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamedInstance {
+    /// The subfamily name of the instance, e.g. `"Condensed Bold"`.
+    pub name: String,
+    /// The PostScript name of the instance, if the font provides one.
+    pub post_script_name: Option<String>,
+    /// The coordinates of the instance on every axis of the font.
+    pub coords: Vec<Variation>,
+}
+
+/// Returns the named instances listed in the `fvar` table of a parsed face.
+///
+/// `ttf-parser` reads the axes of `fvar` but not its instance records, so they
+/// are read here.
+pub(crate) fn named_instances(face: &ttf_parser::Face) -> Vec<NamedInstance> {
+    let Some(fvar) = face.raw_face().table(ttf_parser::Tag::from_bytes(b"fvar")) else {
+        return Vec::new();
+    };
+    let tags: Vec<[u8; 4]> = face
+        .variation_axes()
+        .into_iter()
+        .map(|axis| axis.tag.to_bytes())
+        .collect();
+
+    instance_records(fvar)
+        .into_iter()
+        .filter(|record| record.coords.len() == tags.len())
+        .filter_map(|record| {
+            Some(NamedInstance {
+                name: name(face, record.subfamily_name_id)?,
+                post_script_name: record
+                    .post_script_name_id
+                    .and_then(|name_id| name(face, name_id)),
+                coords: tags
+                    .iter()
+                    .zip(&record.coords)
+                    .map(|(&tag, &value)| Variation::new(tag, value))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// An instance record of an `fvar` table.
+#[derive(Debug, PartialEq)]
+struct InstanceRecord {
+    subfamily_name_id: u16,
+    post_script_name_id: Option<u16>,
+    coords: Vec<f32>,
+}
+
+/// Reads the instance records of an `fvar` table.
+fn instance_records(fvar: &[u8]) -> Vec<InstanceRecord> {
+    let u16_at = |at: usize| Some(u16::from_be_bytes(fvar.get(at..at + 2)?.try_into().ok()?));
+    let fixed_at = |at: usize| {
+        let bits = i32::from_be_bytes(fvar.get(at..at + 4)?.try_into().ok()?);
+        Some(bits as f32 / 65536.0)
+    };
+    let read = || -> Option<Vec<InstanceRecord>> {
+        let axes_offset = usize::from(u16_at(4)?);
+        let axis_count = usize::from(u16_at(8)?);
+        let axis_size = usize::from(u16_at(10)?);
+        let instance_count = usize::from(u16_at(12)?);
+        let instance_size = usize::from(u16_at(14)?);
+        // Subfamily name ID, flags and the coordinates, then an optional PostScript name ID.
+        let has_post_script_name = instance_size >= 4 * axis_count + 6;
+
+        let mut records = Vec::with_capacity(instance_count);
+        for i in 0..instance_count {
+            let start = axes_offset + axis_count * axis_size + i * instance_size;
+            let coords = (0..axis_count)
+                .map(|axis| fixed_at(start + 4 + 4 * axis))
+                .collect::<Option<Vec<f32>>>()?;
+            let post_script_name_id = if has_post_script_name {
+                // 0xFFFF means the instance has no PostScript name.
+                Some(u16_at(start + 4 + 4 * axis_count)?).filter(|&id| id != 0xFFFF)
+            } else {
+                None
+            };
+            records.push(InstanceRecord {
+                subfamily_name_id: u16_at(start)?,
+                post_script_name_id,
+                coords,
+            });
+        }
+        Some(records)
+    };
+    read().unwrap_or_default()
+}
+
+/// Returns the string `name_id` of the `name` table, preferring US English.
+fn name(face: &ttf_parser::Face, name_id: u16) -> Option<String> {
+    let names: Vec<_> = face
+        .names()
+        .into_iter()
+        .filter(|name| name.name_id == name_id)
+        .collect();
+    names
+        .iter()
+        .filter(|name| name.language() == ttf_parser::Language::English_UnitedStates)
+        .chain(&names)
+        .find_map(|name| name.to_string())
+}
+
+/// Clamps `coords` to `axes`, dropping unknown axes, and sorts them by tag.
+///
+/// Later coordinates for the same axis win; axes left at their default are dropped.
+pub(crate) fn normalize(axes: &[VariationAxis], coords: &[Variation]) -> Vec<Variation> {
+    let mut normalized: Vec<Variation> = Vec::new();
+    for coord in coords {
+        let Some(axis) = axes.iter().find(|axis| axis.tag == coord.tag) else {
+            continue;
+        };
+        let value = coord.value.clamp(axis.min_value, axis.max_value);
+        normalized.retain(|c| c.tag != coord.tag);
+        if value != axis.default_value {
+            normalized.push(Variation::new(coord.tag, value));
+        }
+    }
+    normalized.sort_by_key(|coord| coord.tag);
+    normalized
+}
+
+/// Unscaled advance and bounds of a glyph at an instance's coordinates.
+#[derive(Clone, Copy)]
+struct GlyphShape {
+    advance: f32,
+    bounds: Option<ttf_parser::Rect>,
+}
+
+/// Font file contents shared with the database, e.g. a memory-mapped file.
+pub(crate) type FontData = Arc<dyn AsRef<[u8]> + Send + Sync>;
+
+/// A face of a variable font pinned to a set of coordinates.
+pub(crate) struct Instance {
+    /// The face the instance was created from.
+    pub base: fontdb::ID,
+    /// Normalized coordinates, see [`normalize`].
+    pub coords: Vec<Variation>,
+    data: FontData,
+    index: u32,
+    units_per_em: f32,
+    shapes: Mutex<HashMap<u16, GlyphShape, fxhash::FxBuildHasher>>,
+}
+
+impl Instance {
+    pub fn new(base: fontdb::ID, coords: Vec<Variation>, data: FontData, index: u32) -> Self {
+        let units_per_em = ttf_parser::Face::parse((*data).as_ref(), index)
+            .map_or(1000.0, |face| f32::from(face.units_per_em()));
+        Self {
+            base,
+            coords,
+            data,
+            index,
+            units_per_em,
+            shapes: Mutex::new(HashMap::with_hasher(fxhash::FxBuildHasher::default())),
+        }
+    }
+
+    /// Returns the font data of the variable font and the face's index in it.
+    pub fn data(&self) -> (FontData, u32) {
+        (Arc::clone(&self.data), self.index)
+    }
+
+    /// Parses the face and applies the coordinates.
+    fn face(&self) -> Option<ttf_parser::Face<'_>> {
+        let mut face = ttf_parser::Face::parse((*self.data).as_ref(), self.index).ok()?;
+        for coord in &self.coords {
+            face.set_variation(ttf_parser::Tag::from_bytes(&coord.tag), coord.value);
+        }
+        Some(face)
+    }
+
+    fn shape(&self, glyph_index: u16) -> GlyphShape {
+        if let Some(shape) = self.shapes.lock().get(&glyph_index) {
+            return *shape;
+        }
+        let glyph = ttf_parser::GlyphId(glyph_index);
+        let shape = self.face().map_or(
+            GlyphShape {
+                advance: 0.0,
+                bounds: None,
+            },
+            |face| GlyphShape {
+                advance: face.glyph_hor_advance(glyph).map_or(0.0, f32::from),
+                bounds: face.outline_glyph(glyph, &mut NoOutline),
+            },
+        );
+        self.shapes.lock().insert(glyph_index, shape);
+        shape
+    }
+
+    /// Returns the metrics of a glyph, like `fontdue::Font::metrics_indexed`.
+    pub fn metrics(&self, glyph_index: u16, px: f32) -> fontdue::Metrics {
+        let scale = px / self.units_per_em;
+        raster_metrics(self.shape(glyph_index), scale).0
+    }
+
+    /// Rasterizes a glyph, like `fontdue::Font::rasterize_indexed`.
+    pub fn rasterize(&self, glyph_index: u16, px: f32) -> (fontdue::Metrics, Vec<u8>) {
+        if px <= 0.0 {
+            return (fontdue::Metrics::default(), Vec::new());
+        }
+        let scale = px / self.units_per_em;
+        let (metrics, _) = raster_metrics(self.shape(glyph_index), scale);
+        let bitmap = match self.face() {
+            Some(face) => draw_outline(&face, glyph_index, &metrics, scale, 0.0),
+            None => vec![0; metrics.width * metrics.height],
+        };
+        (metrics, bitmap)
+    }
+
+    /// Rasterizes a glyph moved `shift` pixels to the right, see [`rasterize_shifted`].
+    pub fn rasterize_shifted(
+        &self,
+        glyph_index: u16,
+        px: f32,
+        shift: f32,
+    ) -> (fontdue::Metrics, Vec<u8>) {
+        let metrics = self.metrics(glyph_index, px);
+        match self.face() {
+            Some(face) => rasterize_shifted(&face, glyph_index, px, metrics, shift),
+            None => (metrics, vec![0; metrics.width * metrics.height]),
+        }
+    }
+}
+
+/// Rasterizes glyph `glyph_index` of `face` at `px` with its outline moved `shift`
+/// pixels to the right, for glyphs placed at a fractional position.
+///
+/// `metrics` are the glyph's unshifted metrics; the outline is placed as fontdue
+/// places it for their bounds, and the bitmap is a column wider to hold the shift.
+pub(crate) fn rasterize_shifted(
+    face: &ttf_parser::Face<'_>,
+    glyph_index: u16,
+    px: f32,
+    metrics: fontdue::Metrics,
+    shift: f32,
+) -> (fontdue::Metrics, Vec<u8>) {
+    let metrics = fontdue::Metrics {
+        width: metrics.width + usize::from(metrics.width > 0),
+        ..metrics
+    };
+    let scale = px / f32::from(face.units_per_em());
+    let bitmap = draw_outline(face, glyph_index, &metrics, scale, shift);
+    (metrics, bitmap)
+}
+
+/// Draws the outline of glyph `glyph_index`, scaled by `scale` and moved `shift`
+/// pixels to the right, into a bitmap of `metrics`.
+fn draw_outline(
+    face: &ttf_parser::Face<'_>,
+    glyph_index: u16,
+    metrics: &fontdue::Metrics,
+    scale: f32,
+    shift: f32,
+) -> Vec<u8> {
+    let mut bitmap = vec![0; metrics.width * metrics.height];
+    if bitmap.is_empty() || scale <= 0.0 {
+        return bitmap;
+    }
+    let bounds = metrics.bounds;
+    let [offset_x, offset_y] = outline_offset(&bounds);
+    let mut builder = RasterBuilder {
+        rasterizer: ab_glyph_rasterizer::Rasterizer::new(metrics.width, metrics.height),
+        origin: [bounds.xmin / scale, (bounds.ymin + bounds.height) / scale],
+        scale,
+        offset: [offset_x + shift, offset_y],
+        start: ab_glyph_rasterizer::point(0.0, 0.0),
+        last: ab_glyph_rasterizer::point(0.0, 0.0),
+    };
+    face.outline_glyph(ttf_parser::GlyphId(glyph_index), &mut builder);
+    builder.rasterizer.for_each_pixel(|index, coverage| {
+        bitmap[index] = (coverage.min(1.0) * 255.0).round() as u8;
+    });
+    bitmap
+}
+
+/// Returns the sub-pixel offset of an outline with `bounds` within its bitmap, as
+/// fontdue computes it.
+fn outline_offset(bounds: &fontdue::OutlineBounds) -> [f32; 2] {
+    let positive = |value: f32| if value < 0.0 { value + 1.0 } else { value };
+    [
+        positive(bounds.xmin.fract()),
+        positive((1.0 - bounds.height.fract() - bounds.ymin.fract()).fract()),
+    ]
+}
+
+/// Computes fontdue-compatible metrics and the sub-pixel offset of the outline.
+fn raster_metrics(shape: GlyphShape, scale: f32) -> (fontdue::Metrics, [f32; 2]) {
+    let advance_width = shape.advance * scale;
+    let Some(rect) = shape.bounds else {
+        return (
+            fontdue::Metrics {
+                advance_width,
+                ..Default::default()
+            },
+            [0.0, 0.0],
+        );
+    };
+    let bounds = fontdue::OutlineBounds {
+        xmin: f32::from(rect.x_min) * scale,
+        ymin: f32::from(rect.y_min) * scale,
+        width: (f32::from(rect.x_max) - f32::from(rect.x_min)) * scale,
+        height: (f32::from(rect.y_max) - f32::from(rect.y_min)) * scale,
+    };
+    let [offset_x, offset_y] = outline_offset(&bounds);
+    let metrics = fontdue::Metrics {
+        xmin: bounds.xmin.floor() as i32,
+        ymin: bounds.ymin.floor() as i32,
+        width: (bounds.width + offset_x).ceil() as usize,
+        height: (bounds.height + offset_y).ceil() as usize,
+        advance_width,
+        advance_height: 0.0,
+        bounds,
+    };
+    (metrics, [offset_x, offset_y])
+}
+
+/// Discards an outline; used to compute the bounds at the instance's coordinates.
+struct NoOutline;
+
+impl ttf_parser::OutlineBuilder for NoOutline {
+    fn move_to(&mut self, _: f32, _: f32) {}
+    fn line_to(&mut self, _: f32, _: f32) {}
+    fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) {}
+    fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {}
+    fn close(&mut self) {}
+}
+
+/// Draws an outline into a bitmap whose top left corner is the glyph's bounds corner.
+struct RasterBuilder {
+    rasterizer: ab_glyph_rasterizer::Rasterizer,
+    origin: [f32; 2],
+    scale: f32,
+    offset: [f32; 2],
+    start: ab_glyph_rasterizer::Point,
+    last: ab_glyph_rasterizer::Point,
+}
+
+impl RasterBuilder {
+    fn point(&self, x: f32, y: f32) -> ab_glyph_rasterizer::Point {
+        ab_glyph_rasterizer::point(
+            (x - self.origin[0]) * self.scale + self.offset[0],
+            (self.origin[1] - y) * self.scale + self.offset[1],
+        )
+    }
+}
+
+impl ttf_parser::OutlineBuilder for RasterBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.start = self.point(x, y);
+        self.last = self.start;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.rasterizer.draw_line(self.last, p);
+        self.last = p;
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (p1, p) = (self.point(x1, y1), self.point(x, y));
+        self.rasterizer.draw_quad(self.last, p1, p);
+        self.last = p;
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (p1, p2, p) = (self.point(x1, y1), self.point(x2, y2), self.point(x, y));
+        self.rasterizer.draw_cubic(self.last, p1, p2, p);
+        self.last = p;
+    }
+
+    fn close(&mut self) {
+        if self.last != self.start {
+            self.rasterizer.draw_line(self.last, self.start);
+        }
+        self.last = self.start;
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_font_storage;
+
+    #[test]
+    fn test_default_instance_matches_fontdue() {
+        let (font_storage, id) = test_font_storage();
+        let font = font_storage.font(id).unwrap();
+        let (data, index) = font_storage
+            .font_db
+            .with_face_data(id, |data, index| (data.to_vec(), index))
+            .unwrap();
+        let instance = Instance::new(id, Vec::new(), Arc::new(data), index);
+
+        for ch in ['A', 'g', 'é', '@', ' '] {
+            let glyph_index = font.lookup_glyph_index(ch);
+            let (expected, expected_bitmap) = font.rasterize_indexed(glyph_index, 24.0);
+            let (metrics, bitmap) = instance.rasterize(glyph_index, 24.0);
+            assert_eq!(metrics, instance.metrics(glyph_index, 24.0));
+            assert!((metrics.advance_width - expected.advance_width).abs() < 1e-3);
+            assert!(metrics.xmin.abs_diff(expected.xmin) <= 1);
+            assert!(metrics.ymin.abs_diff(expected.ymin) <= 1);
+            assert!(metrics.width.abs_diff(expected.width) <= 1);
+            assert!(metrics.height.abs_diff(expected.height) <= 1);
+            // Both rasterizers cover roughly the same area.
+            let ink = |bitmap: &[u8]| bitmap.iter().map(|&a| f32::from(a)).sum::<f32>();
+            let (ink, expected_ink) = (ink(&bitmap), ink(&expected_bitmap));
+            assert!(
+                (ink - expected_ink).abs() <= expected_ink * 0.05 + 255.0,
+                "{ch}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_shifted_outline_moves_ink() {
+        let (font_storage, id) = test_font_storage();
+        let font = font_storage.font(id).unwrap();
+        let glyph_index = font.lookup_glyph_index('l');
+        let (expected, expected_bitmap) = font.rasterize_indexed(glyph_index, 24.0);
+
+        // Returns the ink and its horizontal center of mass.
+        let ink = |width: usize, bitmap: &[u8]| {
+            let mut sum = 0.0;
+            let mut moment = 0.0;
+            for (index, &coverage) in bitmap.iter().enumerate() {
+                sum += f32::from(coverage);
+                moment += f32::from(coverage) * ((index % width) as f32 + 0.5);
+            }
+            (sum, moment / sum)
+        };
+        let (expected_ink, expected_center) = ink(expected.width, &expected_bitmap);
+
+        for shift in [0.25, 0.5, 0.75] {
+            let (metrics, bitmap) = font_storage
+                .rasterize_shifted(id, glyph_index, 24.0, shift)
+                .unwrap();
+            assert_eq!(metrics.width, expected.width + 1);
+            assert_eq!(metrics.height, expected.height);
+            assert_eq!(bitmap.len(), metrics.width * metrics.height);
+            let (ink, center) = ink(metrics.width, &bitmap);
+            assert!((ink - expected_ink).abs() <= expected_ink * 0.05, "{shift}");
+            assert!((center - expected_center - shift).abs() < 0.1, "{shift}");
+        }
+    }
+
+    #[cfg(feature = "cpu")]
+    #[test]
+    fn test_instances_render_at_their_coordinates() {
+        use std::num::NonZeroUsize;
+
+        use crate::{
+            FontStorage,
+            renderer::{CpuCacheConfig, CpuRenderer},
+            test_utils::{TEST_FONT, with_weight_axis},
+            text::{TextData, TextLayoutConfig},
+        };
+
+        let data = with_weight_axis(TEST_FONT).unwrap();
+        // Instances of fonts read from files share one copy of the file.
+        let path = std::env::temp_dir().join(format!("suzuri-variable-{}.ttf", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let mut font_storage = FontStorage::new();
+        let id = font_storage.load_font_file(path.clone()).unwrap()[0];
+        assert_eq!(
+            font_storage.variation_axes(id),
+            [VariationAxis {
+                tag: *b"wght",
+                min_value: 100.0,
+                default_value: 400.0,
+                max_value: 900.0,
+                hidden: false,
+            }]
+        );
+        let bold = font_storage
+            .variation_instance(id, &[Variation::weight(900.0)])
+            .unwrap();
+        let semi_bold = font_storage
+            .variation_instance(id, &[Variation::weight(650.0)])
+            .unwrap();
+        let (bold_data, _) = font_storage.instances[&bold].data();
+        let (semi_bold_data, _) = font_storage.instances[&semi_bold].data();
+        assert!(Arc::ptr_eq(&bold_data, &semi_bold_data));
+
+        const WIDTH: usize = 160;
+        const HEIGHT: usize = 64;
+        // Returns the ink and its vertical center of mass.
+        let render = |font_id: fontdb::ID| {
+            let layout = TextData::builder(font_id, 40.0, 0u32)
+                .push_str("Suzuri")
+                .build()
+                .layout(&TextLayoutConfig::default(), &font_storage);
+            let mut pixels = vec![0u8; WIDTH * HEIGHT];
+            CpuRenderer::new(&[CpuCacheConfig {
+                block_size: NonZeroUsize::new(64 * 64).unwrap(),
+                capacity: NonZeroUsize::new(16).unwrap(),
+            }])
+            .render(
+                &layout,
+                [WIDTH, HEIGHT],
+                &font_storage,
+                &mut |[x, y], alpha, _| {
+                    let pixel = &mut pixels[y * WIDTH + x];
+                    *pixel = pixel.saturating_add(alpha);
+                },
+            );
+            let mut sum = 0.0;
+            let mut moment = 0.0;
+            for (index, &alpha) in pixels.iter().enumerate() {
+                sum += f32::from(alpha);
+                moment += f32::from(alpha) * ((index / WIDTH) as f32 + 0.5);
+            }
+            (sum, moment / sum)
+        };
+
+        let units_per_em = f32::from(font_storage.face_metrics(id).unwrap().units_per_em);
+        let shift = 100.0 * 40.0 / units_per_em;
+        let (regular_ink, regular_center) = render(id);
+        assert!(regular_ink > 0.0);
+        for (instance, weight) in [(semi_bold, 0.5), (bold, 1.0)] {
+            let (ink, center) = render(instance);
+            assert!((ink - regular_ink).abs() <= regular_ink * 0.05, "{weight}");
+            assert!(
+                (center - regular_center - shift * weight).abs() < 0.25,
+                "{weight}: {regular_center} {center}"
+            );
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_instance_records() {
+        let mut fvar = Vec::new();
+        for value in [1u16, 0, 16, 2, 2, 20, 2, 14] {
+            fvar.extend_from_slice(&value.to_be_bytes());
+        }
+        // Two axes records, whose contents are not read here.
+        fvar.resize(16 + 2 * 20, 0);
+        for (name_id, coords, post_script_name_id) in [
+            (256u16, [700.0f32, 75.0], 0xFFFFu16),
+            (257, [400.0, 100.0], 300),
+        ] {
+            fvar.extend_from_slice(&name_id.to_be_bytes());
+            fvar.extend_from_slice(&0u16.to_be_bytes());
+            for coord in coords {
+                fvar.extend_from_slice(&((coord * 65536.0) as i32).to_be_bytes());
+            }
+            fvar.extend_from_slice(&post_script_name_id.to_be_bytes());
+        }
+
+        assert_eq!(
+            instance_records(&fvar),
+            [
+                InstanceRecord {
+                    subfamily_name_id: 256,
+                    post_script_name_id: None,
+                    coords: vec![700.0, 75.0],
+                },
+                InstanceRecord {
+                    subfamily_name_id: 257,
+                    post_script_name_id: Some(300),
+                    coords: vec![400.0, 100.0],
+                },
+            ]
+        );
+        // Truncated tables yield nothing rather than garbage.
+        assert!(instance_records(&fvar[..fvar.len() - 1]).is_empty());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let axes = [VariationAxis {
+            tag: *b"wght",
+            min_value: 100.0,
+            default_value: 400.0,
+            max_value: 900.0,
+            hidden: false,
+        }];
+        let coords = normalize(
+            &axes,
+            &[
+                Variation::weight(300.0),
+                Variation::width(50.0),
+                Variation::weight(1000.0),
+            ],
+        );
+        assert_eq!(coords, [Variation::weight(900.0)]);
+        let coords = normalize(&axes, &[Variation::weight(300.0), Variation::weight(400.0)]);
+        assert!(coords.is_empty());
+    }
+}
@@ -1,40 +1,84 @@
-use std::{path::PathBuf, sync::Arc};
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+#[cfg(any(feature = "cpu", feature = "gpu", not(target_arch = "wasm32")))]
 use parking_lot::Mutex;
+use parking_lot::RwLock;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod font_load;
+#[cfg(not(target_arch = "wasm32"))]
+mod font_watch;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use font_load::FontLoad;
+#[cfg(not(target_arch = "wasm32"))]
+pub use font_watch::FontWatcher;
 
 use crate::{
-    font_storage::FontStorage,
-    renderer::{
-        CpuRenderer, GpuRenderer,
-        cpu_renderer::CpuCacheConfig,
-        gpu_renderer::{AtlasUpdate, GlyphInstance, GpuCacheConfig, StandaloneGlyph},
+    Error,
+    font_storage::{
+        FaceFilter, FaceMetrics, FaceOverride, FallbackTarget, FontProvider, FontSettings,
+        FontStorage, MatchReport, NamedInstance, Variation, VariationAxis,
     },
-    text::{TextData, TextLayout, TextLayoutConfig},
+    text::{TextData, TextLayout, TextLayoutConfig, TextStyle},
+};
+
+#[cfg(any(feature = "cpu", feature = "gpu"))]
+use crate::renderer::CacheStats;
+#[cfg(feature = "gpu")]
+use crate::renderer::{
+    AtlasMove, AtlasUpdate, GlyphInstance, GpuCacheConfig, GpuRenderer, HostAtlas,
+    HostAtlasRenderer, StandaloneGlyph,
 };
+#[cfg(feature = "cpu")]
+use crate::renderer::{CpuCacheConfig, CpuRenderer};
 
 #[cfg(feature = "wgpu")]
-use crate::renderer::{WgpuRenderPassController, WgpuRenderer};
+use crate::renderer::{
+    BlendSpace, ColorSpace, CustomShader, HdrOutput, LayoutPlacement, LayoutRects, PreparedText,
+    RenderStats, SamplerConfig, TextShadow, WgpuRenderPassController, WgpuRenderer,
+};
 
 /// High-level entry point for the text rendering system.
 ///
-/// This struct coordinates `FontStorage`, `TextLayout`, and various renderers (CPU, GPU, and WGPU if "wgpu" feature is enabled).
+/// This struct coordinates `FontStorage`, `TextLayout`, and the renderers enabled by the
+/// "cpu", "gpu" and "wgpu" features.
 /// It provides a unified interface for loading fonts, laying out text, and rendering it.
 ///
 /// Use `Mutex` to allow shared mutable access, which is common in UI frameworks.
+/// The font storage is behind a `RwLock`: layout, rendering and queries only
+/// take a read lock, so they run concurrently on several threads, while loading
+/// fonts and changing settings take the write lock.
 ///
 /// The fields are public to allow direct access to the underlying storage and renderers when necessary
 /// (e.g. for performance reasons or zero-allocation access).
 pub struct FontSystem {
     /// The underlying font storage.
-    pub font_storage: Mutex<FontStorage>,
+    ///
+    /// Shared with background font loads, see [`Self::load_fonts_dir_async`].
+    pub font_storage: Arc<RwLock<FontStorage>>,
 
+    #[cfg(feature = "cpu")]
     /// The CPU renderer instance (optional).
     pub cpu_renderer: Mutex<Option<Box<CpuRenderer>>>,
+    #[cfg(feature = "gpu")]
     /// The generic GPU renderer instance (optional).
     pub gpu_renderer: Mutex<Option<Box<GpuRenderer>>>,
+    #[cfg(feature = "gpu")]
+    /// The GPU renderer for host-managed atlases (optional).
+    pub host_atlas_renderer: Mutex<Option<Box<HostAtlasRenderer>>>,
     #[cfg(feature = "wgpu")]
     /// The wgpu renderer instance (optional).
     pub wgpu_renderer: Mutex<Option<Box<WgpuRenderer>>>,
+
+    /// Called with the faces registered by each background font load.
+    #[cfg(not(target_arch = "wasm32"))]
+    font_load_hook: Arc<Mutex<Option<font_load::FontLoadHook>>>,
 }
 
 impl Default for FontSystem {
@@ -47,80 +91,355 @@ impl FontSystem {
     /// Creates a new font system with empty renderers and default storage.
     pub fn new() -> Self {
         Self {
-            font_storage: Mutex::new(FontStorage::new()),
+            font_storage: Arc::new(RwLock::new(FontStorage::new())),
+            #[cfg(feature = "cpu")]
             cpu_renderer: Mutex::new(None),
+            #[cfg(feature = "gpu")]
             gpu_renderer: Mutex::new(None),
+            #[cfg(feature = "gpu")]
+            host_atlas_renderer: Mutex::new(None),
             #[cfg(feature = "wgpu")]
             wgpu_renderer: Mutex::new(None),
+            #[cfg(not(target_arch = "wasm32"))]
+            font_load_hook: Arc::new(Mutex::new(None)),
         }
     }
 }
 
 /// font storage initialization
 impl FontSystem {
-    /// Loads the system fonts into the storage.
-    pub fn load_system_fonts(&self) {
-        self.font_storage.lock().load_system_fonts();
+    /// Loads the system fonts into the storage and returns the IDs of their faces.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_system_fonts(&self) -> Vec<fontdb::ID> {
+        self.font_storage.write().load_system_fonts()
+    }
+
+    /// Loads the system fonts, reusing a scan cached at `cache_path`.
+    ///
+    /// See [`FontStorage::load_system_fonts_cached`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_system_fonts_cached(
+        &self,
+        cache_path: &std::path::Path,
+    ) -> crate::font_storage::CachedLoad {
+        self.font_storage
+            .write()
+            .load_system_fonts_cached(cache_path)
+    }
+
+    /// Loads a font from binary data and returns the IDs of its faces.
+    ///
+    /// See [`FontStorage::load_font_binary`].
+    pub fn load_font_binary(&self, data: impl Into<Vec<u8>>) -> Result<Vec<fontdb::ID>, Error> {
+        self.font_storage.write().load_font_binary(data)
+    }
+
+    /// Loads a font from a file path and returns the IDs of its faces.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_font_file(&self, path: PathBuf) -> Result<Vec<fontdb::ID>, Error> {
+        self.font_storage.write().load_font_file(path)
+    }
+
+    /// Loads a font from binary data with `settings` for its faces and returns their IDs.
+    pub fn load_font_binary_with_settings(
+        &self,
+        data: impl Into<Vec<u8>>,
+        settings: FontSettings,
+    ) -> Result<Vec<fontdb::ID>, Error> {
+        self.font_storage
+            .write()
+            .load_font_binary_with_settings(data, settings)
+    }
+
+    /// Loads a font file with `settings` for its faces and returns their IDs.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_font_file_with_settings(
+        &self,
+        path: PathBuf,
+        settings: FontSettings,
+    ) -> Result<Vec<fontdb::ID>, Error> {
+        self.font_storage
+            .write()
+            .load_font_file_with_settings(path, settings)
+    }
+
+    /// Loads all fonts from a directory and returns the IDs of their faces.
+    ///
+    /// See [`FontStorage::load_fonts_dir`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_fonts_dir(&self, dir: PathBuf) -> Result<Vec<fontdb::ID>, Error> {
+        self.font_storage.write().load_fonts_dir(dir)
+    }
+
+    /// Loads a font file and keeps it memory-mapped.
+    ///
+    /// # Safety
+    ///
+    /// See [`FontStorage::load_font_file_mmap`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub unsafe fn load_font_file_mmap(&self, path: PathBuf) -> Result<Vec<fontdb::ID>, Error> {
+        // SAFETY: upheld by the caller.
+        unsafe { self.font_storage.write().load_font_file_mmap(path) }
     }
 
-    /// Loads a font from binary data.
-    pub fn load_font_binary(&self, data: impl Into<Vec<u8>>) {
-        self.font_storage.lock().load_font_binary(data);
+    /// Loads all fonts from a directory and keeps them memory-mapped.
+    ///
+    /// # Safety
+    ///
+    /// See [`FontStorage::load_fonts_dir_mmap`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub unsafe fn load_fonts_dir_mmap(&self, dir: PathBuf) -> Result<Vec<fontdb::ID>, Error> {
+        // SAFETY: upheld by the caller.
+        unsafe { self.font_storage.write().load_fonts_dir_mmap(dir) }
+    }
+
+    /// Loads a font file on a background thread.
+    ///
+    /// The faces become available all at once when the file has been parsed; the
+    /// returned handle resolves to their IDs. See [`FontLoad`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_font_file_async(&self, path: PathBuf) -> FontLoad {
+        self.spawn_font_load(move |font_db| {
+            crate::font_storage::load_source(font_db, fontdb::Source::File(path)).map(drop)
+        })
+    }
+
+    /// Loads all fonts from a directory on a background thread.
+    ///
+    /// Files that cannot be read are skipped, as with [`Self::load_fonts_dir`].
+    /// The faces become available all at once when the directory has been
+    /// parsed; the returned handle resolves to their IDs. See [`FontLoad`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_fonts_dir_async(&self, dir: PathBuf) -> FontLoad {
+        self.spawn_font_load(move |font_db| crate::font_storage::load_dir(font_db, dir))
+    }
+
+    /// Registers the faces of font `key` from `provider`, see [`FontStorage::load_provided_font`].
+    pub fn load_provided_font(
+        &self,
+        provider: &Arc<dyn FontProvider>,
+        key: &str,
+    ) -> Result<Vec<fontdb::ID>, Error> {
+        self.font_storage.write().load_provided_font(provider, key)
+    }
+
+    /// Registers the faces of font `key` from `provider` on a background thread.
+    ///
+    /// Useful for providers that read slowly, e.g. from compressed archives.
+    /// See [`FontLoad`]. Detecting fallback chains for the new faces, as
+    /// background loads do, reads their data right away.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_provided_font_async(
+        &self,
+        provider: &Arc<dyn FontProvider>,
+        key: &str,
+    ) -> FontLoad {
+        let provider = Arc::clone(provider);
+        let key = key.to_string();
+        self.spawn_font_load(move |font_db| {
+            crate::font_storage::provider::load_into(&provider, &key, font_db)
+        })
     }
 
-    /// Loads a font from a file path.
-    pub fn load_font_file(&self, path: PathBuf) -> Result<(), std::io::Error> {
-        self.font_storage.lock().load_font_file(path)
+    /// Runs `load` on a background thread and registers the faces it parsed.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn spawn_font_load(
+        &self,
+        load: impl FnOnce(&mut fontdb::Database) -> Result<(), Error> + Send + 'static,
+    ) -> FontLoad {
+        FontLoad::spawn(
+            Arc::clone(&self.font_storage),
+            Arc::clone(&self.font_load_hook),
+            load,
+        )
+    }
+
+    /// Sets a callback told about the faces registered by each background load.
+    ///
+    /// The callback runs on the loading thread after the faces are registered and
+    /// the storage is unlocked, so it may use the font system, e.g. to relayout
+    /// text or request a redraw. It is not called for loads that add no faces.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_font_load_hook(&self, hook: impl Fn(&[fontdb::ID]) + Send + Sync + 'static) {
+        *self.font_load_hook.lock() = Some(Arc::new(hook));
     }
 
-    /// Loads all fonts from a directory.
-    pub fn load_fonts_dir(&self, dir: PathBuf) {
-        self.font_storage.lock().load_fonts_dir(dir)
+    /// Removes the callback set by [`Self::set_font_load_hook`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn clear_font_load_hook(&self) {
+        *self.font_load_hook.lock() = None;
+    }
+
+    /// Reloads the faces from `path` after the file changed on disk and returns their IDs.
+    ///
+    /// The renderers forget the glyphs cached for those faces. Layouts made with
+    /// the old file must be recomputed, as glyph indices and metrics may differ.
+    /// See [`FontStorage::reload_font_file`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload_font_file(&self, path: &Path) -> Vec<fontdb::ID> {
+        let ids = self.font_storage.write().reload_font_file(path);
+        for &id in &ids {
+            self.forget_font(id);
+        }
+        ids
+    }
+
+    /// Sets the settings for faces without settings of their own.
+    ///
+    /// Fonts loaded with the previous settings are reloaded on use and their
+    /// cached glyphs are dropped from the renderers. Layouts should be recomputed.
+    pub fn set_default_font_settings(&self, settings: FontSettings) {
+        let ids = self
+            .font_storage
+            .write()
+            .set_default_font_settings(settings);
+        for id in ids {
+            self.forget_font(id);
+        }
+    }
+
+    /// Sets the settings of face `id`, or with `None` makes it follow the default.
+    ///
+    /// See [`Self::set_default_font_settings`].
+    pub fn set_font_settings(&self, id: fontdb::ID, settings: Option<FontSettings>) {
+        let ids = self.font_storage.write().set_font_settings(id, settings);
+        for id in ids {
+            self.forget_font(id);
+        }
+    }
+
+    /// Drops the glyphs cached for `font_id` from every initialized renderer.
+    fn forget_font(&self, font_id: fontdb::ID) {
+        #[cfg(feature = "cpu")]
+        if let Some(renderer) = &mut *self.cpu_renderer.lock() {
+            renderer.forget_font(font_id);
+        }
+        #[cfg(feature = "gpu")]
+        if let Some(renderer) = &mut *self.gpu_renderer.lock() {
+            renderer.forget_font(font_id);
+        }
+        #[cfg(feature = "gpu")]
+        if let Some(renderer) = &mut *self.host_atlas_renderer.lock() {
+            renderer.forget_font(font_id);
+        }
+        #[cfg(feature = "wgpu")]
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.forget_font(font_id);
+        }
+        #[cfg(not(any(feature = "cpu", feature = "gpu")))]
+        let _ = font_id;
+    }
+
+    /// Watches the files of the loaded faces and reloads those that change.
+    ///
+    /// A background thread calls [`Self::reload_font_file`] for font files whose
+    /// modification time changed, then `on_reload` with the reloaded IDs so that
+    /// the application can relayout and redraw its text. On Linux and Android
+    /// the thread is notified of changes by the OS and picks up files loaded
+    /// after the watcher started within `interval`. Elsewhere it compares the
+    /// modification times of all font files every `interval`. Watching stops
+    /// when the returned handle is dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn watch_font_files(
+        self: &Arc<Self>,
+        interval: Duration,
+        on_reload: impl Fn(&[fontdb::ID]) + Send + Sync + 'static,
+    ) -> FontWatcher {
+        FontWatcher::spawn(Arc::downgrade(self), interval, Box::new(on_reload))
     }
 
     /// Manually adds a face info.
     pub fn push_face_info(&self, info: fontdb::FaceInfo) {
-        self.font_storage.lock().push_face_info(info);
+        self.font_storage.write().push_face_info(info);
     }
 
     /// Removes a face by ID.
     pub fn remove_face(&self, id: fontdb::ID) {
-        self.font_storage.lock().remove_face(id);
+        self.font_storage.write().remove_face(id);
+    }
+
+    /// Overrides the metadata used when matching a face in queries.
+    pub fn set_face_override(&self, id: fontdb::ID, face_override: FaceOverride) {
+        self.font_storage
+            .write()
+            .set_face_override(id, face_override);
+    }
+
+    /// Removes the override of a face, restoring the metadata reported by the font.
+    pub fn clear_face_override(&self, id: fontdb::ID) {
+        self.font_storage.write().clear_face_override(id);
+    }
+
+    /// Sets the faces consulted, in order, for clusters with emoji presentation.
+    pub fn set_emoji_fallback(&self, chain: Vec<fontdb::ID>) {
+        self.font_storage.write().set_emoji_fallback(chain);
+    }
+
+    /// Returns the emoji fallback chain.
+    pub fn emoji_fallback(&self) -> Vec<fontdb::ID> {
+        self.font_storage.read().emoji_fallback().to_vec()
+    }
+
+    /// Builds the emoji fallback chain from well-known emoji families that are loaded.
+    pub fn detect_emoji_fallback(&self) {
+        self.font_storage.write().detect_emoji_fallback();
+    }
+
+    /// Sets the faces consulted, in order, for characters that text in `target` lacks.
+    ///
+    /// See [`FontStorage::set_fallback_chain`].
+    pub fn set_fallback_chain(&self, target: impl Into<FallbackTarget>, chain: Vec<fontdb::ID>) {
+        self.font_storage.write().set_fallback_chain(target, chain);
+    }
+
+    /// Sets the faces consulted for faces without a fallback chain of their own.
+    pub fn set_default_fallback(&self, chain: Vec<fontdb::ID>) {
+        self.font_storage.write().set_default_fallback(chain);
+    }
+
+    /// Returns the default fallback chain.
+    pub fn default_fallback(&self) -> Vec<fontdb::ID> {
+        self.font_storage.read().default_fallback().to_vec()
+    }
+
+    /// Builds the default fallback chain from the loaded fonts, one face per script.
+    pub fn detect_fallback_chains(&self) {
+        self.font_storage.write().detect_fallback_chains();
     }
 
     /// Checks if the storage is empty.
     pub fn is_empty(&self) -> bool {
-        self.font_storage.lock().is_empty()
+        self.font_storage.read().is_empty()
     }
 
     /// Returns the number of loaded faces.
     pub fn len(&self) -> usize {
-        self.font_storage.lock().len()
+        self.font_storage.read().len()
     }
 
     /// Sets the family name for the "serif" generic family.
     pub fn set_serif_family(&self, family: impl Into<String>) {
-        self.font_storage.lock().set_serif_family(family);
+        self.font_storage.write().set_serif_family(family);
     }
 
     /// Sets the family name for the "sans-serif" generic family.
     pub fn set_sans_serif_family(&self, family: impl Into<String>) {
-        self.font_storage.lock().set_sans_serif_family(family);
+        self.font_storage.write().set_sans_serif_family(family);
     }
 
     /// Sets the family name for the "cursive" generic family.
     pub fn set_cursive_family(&self, family: impl Into<String>) {
-        self.font_storage.lock().set_cursive_family(family);
+        self.font_storage.write().set_cursive_family(family);
     }
 
     /// Sets the family name for the "fantasy" generic family.
     pub fn set_fantasy_family(&self, family: impl Into<String>) {
-        self.font_storage.lock().set_fantasy_family(family);
+        self.font_storage.write().set_fantasy_family(family);
     }
 
     /// Sets the family name for the "monospace" generic family.
     pub fn set_monospace_family(&self, family: impl Into<String>) {
-        self.font_storage.lock().set_monospace_family(family);
+        self.font_storage.write().set_monospace_family(family);
     }
 
     /// Returns the name of a family.
@@ -129,20 +448,52 @@ impl FontSystem {
     /// This method allocates a new `String` to avoid holding a lock on the storage.
     /// If you need zero-allocation access, lock `font_storage` directly.
     pub fn family_name<'a>(&'a self, family: &'a fontdb::Family<'_>) -> String {
-        self.font_storage.lock().family_name(family).to_string()
+        self.font_storage.read().family_name(family).to_string()
     }
 }
 
 /// font querying
 impl FontSystem {
     /// Queries for a font matching the description.
+    ///
+    /// Falls back to the generic sans-serif family and then any loaded face,
+    /// unless strict matching is on; see [`FontStorage::query`].
     pub fn query(&self, query: &fontdb::Query) -> Option<(fontdb::ID, Arc<fontdue::Font>)> {
-        self.font_storage.lock().query(query)
+        self.font_storage.read().query(query)
+    }
+
+    /// Queries for a font and reports how the chosen face differs from the query.
+    ///
+    /// See [`FontStorage::query_with_report`].
+    pub fn query_with_report(
+        &self,
+        query: &fontdb::Query,
+    ) -> Option<(fontdb::ID, Arc<fontdue::Font>, MatchReport)> {
+        self.font_storage.read().query_with_report(query)
+    }
+
+    /// Sets whether queries fail when none of the requested families is loaded.
+    ///
+    /// See [`FontStorage::set_strict_matching`].
+    pub fn set_strict_matching(&self, strict: bool) {
+        self.font_storage.write().set_strict_matching(strict);
+    }
+
+    /// Makes queries for the family `alias` match the faces of `family`.
+    ///
+    /// See [`FontStorage::set_alias`].
+    pub fn set_alias(&self, alias: impl Into<String>, family: impl Into<String>) {
+        self.font_storage.write().set_alias(alias, family);
+    }
+
+    /// Removes an alias set with [`Self::set_alias`].
+    pub fn remove_alias(&self, alias: &str) {
+        self.font_storage.write().remove_alias(alias);
     }
 
     /// Retrieves a loaded font by ID.
     pub fn font(&self, id: fontdb::ID) -> Option<Arc<fontdue::Font>> {
-        self.font_storage.lock().font(id)
+        self.font_storage.read().font(id)
     }
 
     /// Returns a vec over all available faces.
@@ -151,7 +502,42 @@ impl FontSystem {
     /// This method clones all face info to avoid holding a lock on the storage.
     /// If you need to iterate without allocation, lock `font_storage` directly.
     pub fn faces(&self) -> Vec<fontdb::FaceInfo> {
-        self.font_storage.lock().faces().cloned().collect()
+        self.font_storage.read().faces().cloned().collect()
+    }
+
+    /// Drops the loaded fonts unused during the last `max_idle_frames` frames.
+    ///
+    /// Call this once per frame. See [`FontStorage::evict_unused`].
+    pub fn evict_unused_fonts(&self, max_idle_frames: u64) -> usize {
+        self.font_storage.write().evict_unused(max_idle_frames)
+    }
+
+    /// Ends the current frame of every initialized GPU renderer.
+    ///
+    /// See [`GpuRenderer::end_frame`].
+    #[cfg(feature = "gpu")]
+    pub fn end_frame(&self) {
+        if let Some(renderer) = &mut *self.gpu_renderer.lock() {
+            renderer.end_frame();
+        }
+        if let Some(renderer) = &mut *self.host_atlas_renderer.lock() {
+            renderer.end_frame();
+        }
+        #[cfg(feature = "wgpu")]
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.end_frame();
+        }
+    }
+
+    /// Returns the faces that pass `filter`, see [`FontStorage::faces_matching`].
+    ///
+    /// Only the matching face infos are cloned.
+    pub fn faces_matching(&self, filter: &FaceFilter) -> Vec<fontdb::FaceInfo> {
+        self.font_storage
+            .read()
+            .faces_matching(filter)
+            .cloned()
+            .collect()
     }
 
     /// Returns face info for an ID.
@@ -160,12 +546,67 @@ impl FontSystem {
     /// This method clones the face info to avoid holding a lock on the storage.
     /// If you need reference access, lock `font_storage` directly.
     pub fn face(&self, id: fontdb::ID) -> Option<fontdb::FaceInfo> {
-        self.font_storage.lock().face(id).cloned()
+        self.font_storage.read().face(id).cloned()
+    }
+
+    /// Returns the face-wide metrics of face `id`, see [`FontStorage::face_metrics`].
+    pub fn face_metrics(&self, id: fontdb::ID) -> Option<FaceMetrics> {
+        self.font_storage.read().face_metrics(id)
+    }
+
+    /// Returns the variation axes of face `id`; empty if the face is not variable.
+    pub fn variation_axes(&self, id: fontdb::ID) -> Vec<VariationAxis> {
+        self.font_storage.read().variation_axes(id)
+    }
+
+    /// Returns a face for the variable font `id` at the coordinates `coords`.
+    ///
+    /// See [`FontStorage::variation_instance`].
+    pub fn variation_instance(&self, id: fontdb::ID, coords: &[Variation]) -> Option<fontdb::ID> {
+        self.font_storage.write().variation_instance(id, coords)
+    }
+
+    /// Returns the coordinates of a variable font instance; empty for other faces.
+    pub fn variations(&self, id: fontdb::ID) -> Vec<Variation> {
+        self.font_storage.read().variations(id).to_vec()
+    }
+
+    /// Returns the named instances of the variable font `id`, e.g. "Condensed Bold".
+    pub fn named_instances(&self, id: fontdb::ID) -> Vec<NamedInstance> {
+        self.font_storage.read().named_instances(id)
+    }
+
+    /// Returns a face for a named instance of the variable font `id`.
+    ///
+    /// See [`FontStorage::named_instance`].
+    pub fn named_instance(&self, id: fontdb::ID, name: &str) -> Option<fontdb::ID> {
+        self.font_storage.write().named_instance(id, name)
+    }
+
+    /// Returns a face for a named instance of the first of `families` that has one.
+    pub fn query_named_instance(
+        &self,
+        families: &[fontdb::Family],
+        name: &str,
+    ) -> Option<fontdb::ID> {
+        self.font_storage
+            .write()
+            .query_named_instance(families, name)
+    }
+
+    /// Returns `true` if face `id` has a glyph for `ch`, without loading the font.
+    pub fn face_covers(&self, id: fontdb::ID, ch: char) -> bool {
+        self.font_storage.read().face_covers(id, ch)
+    }
+
+    /// Returns the IDs of all faces that have a glyph for `ch`, without loading them.
+    pub fn fonts_covering(&self, ch: char) -> Vec<fontdb::ID> {
+        self.font_storage.read().fonts_covering(ch)
     }
 
     /// Returns the source of a face.
     pub fn face_source(&self, id: fontdb::ID) -> Option<(fontdb::Source, u32)> {
-        self.font_storage.lock().face_source(id)
+        self.font_storage.read().face_source(id)
     }
 }
 
@@ -177,12 +618,23 @@ impl FontSystem {
         text: &TextData<T>,
         config: &TextLayoutConfig,
     ) -> TextLayout<T> {
-        let mut font_storage = self.font_storage.lock();
-        text.layout(config, &mut font_storage)
+        text.layout(config, &self.font_storage.read())
+    }
+
+    /// Performs text layout with the built-in [`TextStyle`], resolving weight and slant to faces.
+    ///
+    /// See [`TextData::layout_styled`].
+    pub fn layout_styled_text(
+        &self,
+        text: &TextData<TextStyle>,
+        config: &TextLayoutConfig,
+    ) -> TextLayout<TextStyle> {
+        text.layout_styled(config, &self.font_storage.read())
     }
 }
 
 /// cpu renderer
+#[cfg(feature = "cpu")]
 impl FontSystem {
     /// Initializes the CPU renderer with the given cache configuration.
     ///
@@ -210,6 +662,16 @@ impl FontSystem {
         }
     }
 
+    /// Returns the CPU renderer's cache counters, or `None` before it is initialized.
+    ///
+    /// See [`CpuRenderer::stats`].
+    pub fn cpu_cache_stats(&self) -> Option<CacheStats> {
+        self.cpu_renderer
+            .lock()
+            .as_ref()
+            .map(|renderer| renderer.stats())
+    }
+
     /// Renders text using the CPU renderer.
     ///
     /// The callback `f` is called for each pixel.
@@ -220,7 +682,7 @@ impl FontSystem {
         f: &mut dyn FnMut([usize; 2], u8, &T),
     ) {
         if let Some(renderer) = &mut *self.cpu_renderer.lock() {
-            renderer.render(layout, image_size, &mut self.font_storage.lock(), f);
+            renderer.render(layout, image_size, &self.font_storage.read(), f);
         } else {
             log::warn!("Render called before cpu renderer initialized.");
         }
@@ -228,6 +690,7 @@ impl FontSystem {
 }
 
 /// gpu renderer
+#[cfg(feature = "gpu")]
 impl FontSystem {
     /// Initializes the generic GPU renderer with the given cache configuration.
     ///
@@ -246,6 +709,30 @@ impl FontSystem {
         }
     }
 
+    /// Lets the generic GPU renderer add atlas pages, up to `bytes` in total, when
+    /// its cache overflows.
+    ///
+    /// See [`GpuRenderer::set_growth_budget`].
+    pub fn gpu_set_growth_budget(&self, bytes: usize) {
+        if let Some(renderer) = &mut *self.gpu_renderer.lock() {
+            renderer.set_growth_budget(bytes);
+        } else {
+            log::warn!("Growth budget set before gpu renderer initialized.");
+        }
+    }
+
+    /// Lets the generic GPU renderer add atlas pages, up to `bytes` in total, for
+    /// glyphs larger than every tile.
+    ///
+    /// See [`GpuRenderer::set_overflow_budget`].
+    pub fn gpu_set_overflow_budget(&self, bytes: usize) {
+        if let Some(renderer) = &mut *self.gpu_renderer.lock() {
+            renderer.set_overflow_budget(bytes);
+        } else {
+            log::warn!("Overflow budget set before gpu renderer initialized.");
+        }
+    }
+
     /// Clears the generic GPU renderer's cache.
     pub fn gpu_cache_clear(&self) {
         if let Some(renderer) = &mut *self.gpu_renderer.lock() {
@@ -255,6 +742,30 @@ impl FontSystem {
         }
     }
 
+    /// Returns the generic GPU renderer's cache counters, or `None` before it is
+    /// initialized.
+    ///
+    /// See [`GpuRenderer::stats`].
+    pub fn gpu_cache_stats(&self) -> Option<CacheStats> {
+        self.gpu_renderer
+            .lock()
+            .as_ref()
+            .map(|renderer| renderer.stats())
+    }
+
+    /// Repacks up to `limit` of the generic GPU renderer's cached glyphs into fewer
+    /// atlas pages and returns how many moved.
+    ///
+    /// See [`GpuRenderer::compact_cache`].
+    pub fn gpu_compact_cache(&self, limit: usize, copy_atlas: impl FnMut(&[AtlasMove])) -> usize {
+        if let Some(renderer) = &mut *self.gpu_renderer.lock() {
+            renderer.compact_cache(limit, copy_atlas)
+        } else {
+            log::warn!("Cache compaction called before gpu renderer initialized.");
+            0
+        }
+    }
+
     /// Renders text using the generic GPU renderer.
     ///
     /// This requires providing callbacks to handle atlas updates and drawing.
@@ -269,7 +780,7 @@ impl FontSystem {
         if let Some(renderer) = &mut *self.gpu_renderer.lock() {
             renderer.render(
                 layout,
-                &mut self.font_storage.lock(),
+                &self.font_storage.read(),
                 update_atlas,
                 draw_instances,
                 draw_standalone,
@@ -293,7 +804,7 @@ impl FontSystem {
         if let Some(renderer) = &mut *self.gpu_renderer.lock() {
             renderer.try_render(
                 layout,
-                &mut self.font_storage.lock(),
+                &self.font_storage.read(),
                 update_atlas,
                 draw_instances,
                 draw_standalone,
@@ -305,6 +816,81 @@ impl FontSystem {
     }
 }
 
+/// host atlas renderer
+#[cfg(feature = "gpu")]
+impl FontSystem {
+    /// Initializes the GPU renderer that allocates glyphs in host-managed atlases.
+    ///
+    /// This will replace any existing host atlas renderer without releasing its regions;
+    /// call `host_atlas_cache_clear` first if the host atlas is kept.
+    pub fn host_atlas_init(&self) {
+        *self.host_atlas_renderer.lock() = Some(Box::new(HostAtlasRenderer::new()));
+    }
+
+    /// Initializes the host atlas renderer if it is not already initialized.
+    pub fn host_atlas_ensure_init(&self) {
+        if self.host_atlas_renderer.lock().is_none() {
+            self.host_atlas_init();
+        }
+    }
+
+    /// Releases every region the host atlas renderer holds in `atlas`.
+    pub fn host_atlas_cache_clear(&self, atlas: &mut impl HostAtlas) {
+        if let Some(renderer) = &mut *self.host_atlas_renderer.lock() {
+            renderer.clear_cache(atlas);
+        } else {
+            log::warn!("Cache clear called before host atlas renderer initialized.");
+        }
+    }
+
+    /// Renders text into a host-managed atlas.
+    ///
+    /// This method is for infallible callbacks. Use `try_host_atlas_render` for fallible callbacks.
+    pub fn host_atlas_render<T: Clone + Copy>(
+        &self,
+        layout: &TextLayout<T>,
+        atlas: &mut impl HostAtlas,
+        draw_instances: impl FnMut(&[GlyphInstance<T>]),
+        draw_standalone: impl FnMut(&StandaloneGlyph<T>),
+    ) {
+        if let Some(renderer) = &mut *self.host_atlas_renderer.lock() {
+            renderer.render(
+                layout,
+                &self.font_storage.read(),
+                atlas,
+                draw_instances,
+                draw_standalone,
+            )
+        } else {
+            log::warn!("Render called before host atlas renderer initialized.");
+        }
+    }
+
+    /// Renders text into a host-managed atlas.
+    ///
+    /// This method allows callbacks to return errors, which will be propagated.
+    pub fn try_host_atlas_render<T: Clone + Copy, E>(
+        &self,
+        layout: &TextLayout<T>,
+        atlas: &mut impl HostAtlas,
+        draw_instances: &mut impl FnMut(&[GlyphInstance<T>]) -> Result<(), E>,
+        draw_standalone: &mut impl FnMut(&StandaloneGlyph<T>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        if let Some(renderer) = &mut *self.host_atlas_renderer.lock() {
+            renderer.try_render(
+                layout,
+                &self.font_storage.read(),
+                atlas,
+                draw_instances,
+                draw_standalone,
+            )
+        } else {
+            log::warn!("Render called before host atlas renderer initialized.");
+            Ok(())
+        }
+    }
+}
+
 /// wgpu renderer
 #[cfg(feature = "wgpu")]
 impl FontSystem {
@@ -312,16 +898,42 @@ impl FontSystem {
     ///
     /// `configs` specifies the atlas configuration.
     /// `formats` specifies the texture formats that will be used for rendering, allowing pipeline pre-compilation.
+    /// `pipeline_cache` lets pipelines compiled in earlier runs load from disk, see
+    /// [`WgpuRenderer::create_pipeline_cache`].
     pub fn wgpu_init(
         &self,
         device: &wgpu::Device,
         configs: &[GpuCacheConfig],
         formats: &[wgpu::TextureFormat],
+        pipeline_cache: Option<&wgpu::PipelineCache>,
     ) {
         // ensures first drop previous resource and then create new one to avoid unnecessary memory usage.
         *self.wgpu_renderer.lock() = None;
 
-        *self.wgpu_renderer.lock() = Some(Box::new(WgpuRenderer::new(device, configs, formats)));
+        *self.wgpu_renderer.lock() = Some(Box::new(WgpuRenderer::new(
+            device,
+            configs,
+            formats,
+            pipeline_cache,
+        )));
+    }
+
+    /// Initializes the WGPU renderer like [`Self::wgpu_init`], with glyphs shaded
+    /// by `shader`.
+    ///
+    /// Keeps the previous renderer if `shader` fails validation; see
+    /// [`WgpuRenderer::with_shader`].
+    pub fn wgpu_init_with_shader(
+        &self,
+        device: &wgpu::Device,
+        configs: &[GpuCacheConfig],
+        formats: &[wgpu::TextureFormat],
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+        shader: &CustomShader,
+    ) -> Result<(), wgpu::Error> {
+        let renderer = WgpuRenderer::with_shader(device, configs, formats, pipeline_cache, shader)?;
+        *self.wgpu_renderer.lock() = Some(Box::new(renderer));
+        Ok(())
     }
 
     /// Initializes the WGPU renderer with the given cache configuration if it is not already initialized.
@@ -330,12 +942,24 @@ impl FontSystem {
         device: &wgpu::Device,
         configs: &[GpuCacheConfig],
         formats: &[wgpu::TextureFormat],
+        pipeline_cache: Option<&wgpu::PipelineCache>,
     ) {
         if self.wgpu_renderer.lock().is_none() {
-            self.wgpu_init(device, configs, formats);
+            self.wgpu_init(device, configs, formats, pipeline_cache);
         }
     }
 
+    /// Returns the contents of the WGPU renderer's pipeline cache, to be saved
+    /// for the next run.
+    ///
+    /// See [`WgpuRenderer::pipeline_cache_data`].
+    pub fn wgpu_pipeline_cache_data(&self) -> Option<Vec<u8>> {
+        self.wgpu_renderer
+            .lock()
+            .as_ref()
+            .and_then(|renderer| renderer.pipeline_cache_data())
+    }
+
     /// Clears the WGPU renderer's cache.
     pub fn wgpu_cache_clear(&self) {
         if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
@@ -345,24 +969,336 @@ impl FontSystem {
         }
     }
 
+    /// Returns the WGPU renderer's cache counters, or `None` before it is initialized.
+    ///
+    /// See [`WgpuRenderer::stats`].
+    pub fn wgpu_cache_stats(&self) -> Option<CacheStats> {
+        self.wgpu_renderer
+            .lock()
+            .as_ref()
+            .map(|renderer| renderer.stats())
+    }
+
+    /// Repacks up to `limit` of the WGPU renderer's cached glyphs into fewer atlas
+    /// pages and returns how many moved.
+    ///
+    /// See [`WgpuRenderer::compact_cache`].
+    pub fn wgpu_compact_cache(&self, limit: usize, encoder: &mut wgpu::CommandEncoder) -> usize {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.compact_cache(limit, encoder)
+        } else {
+            log::warn!("Cache compaction called before wgpu renderer initialized.");
+            0
+        }
+    }
+
+    /// Lets the WGPU renderer add atlas layers, up to `bytes` in total, when its
+    /// cache overflows.
+    ///
+    /// See [`WgpuRenderer::set_growth_budget`].
+    pub fn wgpu_set_growth_budget(&self, device: &wgpu::Device, bytes: usize) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_growth_budget(device, bytes);
+        } else {
+            log::warn!("Growth budget set before wgpu renderer initialized.");
+        }
+    }
+
+    /// Lets the WGPU renderer add atlas layers, up to `bytes` in total, for glyphs
+    /// larger than every tile.
+    ///
+    /// See [`WgpuRenderer::set_overflow_budget`].
+    pub fn wgpu_set_overflow_budget(&self, device: &wgpu::Device, bytes: usize) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_overflow_budget(device, bytes);
+        } else {
+            log::warn!("Overflow budget set before wgpu renderer initialized.");
+        }
+    }
+
+    /// Sets the encoding of the colors passed to the WGPU renderer.
+    ///
+    /// See [`ColorSpace`] for how colors are converted for the render target.
+    pub fn wgpu_set_color_space(&self, color_space: ColorSpace) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_color_space(color_space);
+        } else {
+            log::warn!("Color space set before wgpu renderer initialized.");
+        }
+    }
+
+    /// Sets the space the WGPU renderer blends glyph edges in.
+    ///
+    /// See [`BlendSpace`].
+    pub fn wgpu_set_blend_space(&self, blend_space: BlendSpace) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_blend_space(blend_space);
+        } else {
+            log::warn!("Blend space set before wgpu renderer initialized.");
+        }
+    }
+
+    /// Sets the brightness of text the WGPU renderer draws on HDR targets.
+    ///
+    /// See [`HdrOutput`].
+    pub fn wgpu_set_hdr_output(&self, hdr_output: HdrOutput) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_hdr_output(hdr_output);
+        } else {
+            log::warn!("HDR output set before wgpu renderer initialized.");
+        }
+    }
+
+    /// Sets the drop shadow the WGPU renderer draws behind text, or `None` for
+    /// no shadow.
+    ///
+    /// See [`WgpuRenderer::set_shadow`].
+    pub fn wgpu_set_shadow(&self, shadow: Option<TextShadow>) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_shadow(shadow);
+        } else {
+            log::warn!("Shadow set before wgpu renderer initialized.");
+        }
+    }
+
+    /// Enables or disables LCD subpixel antialiasing in the WGPU renderer and
+    /// returns whether it is enabled.
+    ///
+    /// See [`WgpuRenderer::set_lcd_antialiasing`].
+    pub fn wgpu_set_lcd_antialiasing(&self, device: &wgpu::Device, enabled: bool) -> bool {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_lcd_antialiasing(device, enabled)
+        } else {
+            log::warn!("LCD antialiasing set before wgpu renderer initialized.");
+            false
+        }
+    }
+
+    /// Sets how the WGPU renderer samples glyph textures.
+    ///
+    /// See [`WgpuRenderer::set_sampler`].
+    pub fn wgpu_set_sampler(&self, device: &wgpu::Device, config: SamplerConfig) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_sampler(device, config);
+        } else {
+            log::warn!("Sampler set before wgpu renderer initialized.");
+        }
+    }
+
+    /// Sets whether the WGPU renderer draws prepared text with
+    /// `multi_draw_indirect` and returns whether it is enabled.
+    ///
+    /// See [`WgpuRenderer::set_indirect_draws`].
+    pub fn wgpu_set_indirect_draws(&self, device: &wgpu::Device, enabled: bool) -> bool {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_indirect_draws(device, enabled)
+        } else {
+            log::warn!("Indirect draws set before wgpu renderer initialized.");
+            false
+        }
+    }
+
+    /// Sets whether the WGPU renderer keeps a copy of its atlas in host memory.
+    ///
+    /// See [`WgpuRenderer::set_atlas_host_copy`].
+    pub fn wgpu_set_atlas_host_copy(&self, enabled: bool) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_atlas_host_copy(enabled, &self.font_storage.read());
+        } else {
+            log::warn!("Atlas host copy set before wgpu renderer initialized.");
+        }
+    }
+
+    /// Rebuilds the GPU resources of the WGPU renderer on `device`, e.g. after
+    /// device loss, and returns whether the cached glyphs were kept.
+    ///
+    /// See [`WgpuRenderer::recreate`].
+    pub fn wgpu_recreate(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+    ) -> bool {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.recreate(device, queue, pipeline_cache)
+        } else {
+            log::warn!("Recreate called before wgpu renderer initialized.");
+            false
+        }
+    }
+
+    /// Sets the transform the WGPU renderer applies to rendered text.
+    ///
+    /// See [`WgpuRenderer::set_transform`].
+    pub fn wgpu_set_transform(
+        &self,
+        transform: euclid::Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+    ) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_transform(transform);
+        } else {
+            log::warn!("Transform set before wgpu renderer initialized.");
+        }
+    }
+
+    /// Pushes a clip rectangle onto the WGPU renderer's clip stack.
+    ///
+    /// See [`WgpuRenderer::push_clip_rect`].
+    pub fn wgpu_push_clip_rect(&self, rect: euclid::Box2D<f32, euclid::UnknownUnit>) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.push_clip_rect(rect);
+        } else {
+            log::warn!("Clip rect pushed before wgpu renderer initialized.");
+        }
+    }
+
+    /// Pops the innermost clip rectangle of the WGPU renderer's clip stack.
+    ///
+    /// See [`WgpuRenderer::pop_clip_rect`].
+    pub fn wgpu_pop_clip_rect(&self) -> Option<euclid::Box2D<f32, euclid::UnknownUnit>> {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.pop_clip_rect()
+        } else {
+            log::warn!("Clip rect popped before wgpu renderer initialized.");
+            None
+        }
+    }
+
+    /// Enables or disables rounding glyph origins to whole pixels in the WGPU renderer.
+    ///
+    /// See [`WgpuRenderer::set_pixel_snapping`].
+    pub fn wgpu_set_pixel_snapping(&self, enabled: bool) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_pixel_snapping(enabled);
+        } else {
+            log::warn!("Pixel snapping set before wgpu renderer initialized.");
+        }
+    }
+
+    /// Sets the number of views of the multiview passes the WGPU renderer draws
+    /// in and returns whether multiview is enabled.
+    ///
+    /// See [`WgpuRenderer::set_multiview`].
+    pub fn wgpu_set_multiview(
+        &self,
+        device: &wgpu::Device,
+        views: Option<std::num::NonZeroU32>,
+    ) -> bool {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_multiview(device, views)
+        } else {
+            log::warn!("Multiview set before wgpu renderer initialized.");
+            false
+        }
+    }
+
+    /// Sets the views of a multiview pass the WGPU renderer draws the next
+    /// renders to.
+    ///
+    /// See [`WgpuRenderer::set_view_mask`].
+    pub fn wgpu_set_view_mask(&self, view_mask: u32) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_view_mask(view_mask);
+        } else {
+            log::warn!("View mask set before wgpu renderer initialized.");
+        }
+    }
+
+    /// Sets the depth the WGPU renderer writes for text and the depth-stencil
+    /// state it tests it with.
+    ///
+    /// See [`WgpuRenderer::set_depth`] and [`WgpuRenderer::set_depth_stencil`].
+    pub fn wgpu_set_depth(&self, depth: f32, depth_stencil: Option<wgpu::DepthStencilState>) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.set_depth(depth);
+            renderer.set_depth_stencil(depth_stencil);
+        } else {
+            log::warn!("Depth set before wgpu renderer initialized.");
+        }
+    }
+
     /// Renders text using the WGPU renderer.
+    ///
+    /// See [`WgpuRenderer::render_to`] for how uploads are ordered with the
+    /// commands of `encoder`. `view` is drawn in the format of its texture; for
+    /// views of another format, use [`Self::wgpu_render_with_format`].
     pub fn wgpu_render<T: Into<[f32; 4]> + Copy>(
         &self,
         text_layout: &TextLayout<T>,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         encoder: &mut wgpu::CommandEncoder,
         view: &wgpu::TextureView,
-    ) {
+    ) -> RenderStats {
         if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
             renderer.render(
                 text_layout,
-                &mut self.font_storage.lock(),
+                &self.font_storage.read(),
+                device,
+                queue,
+                encoder,
+                view,
+            )
+        } else {
+            log::warn!("Render called before wgpu renderer initialized.");
+            RenderStats::default()
+        }
+    }
+
+    /// Renders text using the WGPU renderer into a view of `format`.
+    ///
+    /// See [`WgpuRenderer::render_with_format`].
+    pub fn wgpu_render_with_format<T: Into<[f32; 4]> + Copy>(
+        &self,
+        text_layout: &TextLayout<T>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+    ) -> RenderStats {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.render_with_format(
+                text_layout,
+                &self.font_storage.read(),
+                device,
+                queue,
+                encoder,
+                view,
+                format,
+            )
+        } else {
+            log::warn!("Render called before wgpu renderer initialized.");
+            RenderStats::default()
+        }
+    }
+
+    /// Renders text using the WGPU renderer with `transform` instead of the one
+    /// set with [`Self::wgpu_set_transform`].
+    ///
+    /// See [`WgpuRenderer::render_transformed`].
+    pub fn wgpu_render_transformed<T: Into<[f32; 4]> + Copy>(
+        &self,
+        text_layout: &TextLayout<T>,
+        transform: euclid::Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) -> RenderStats {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.render_transformed(
+                text_layout,
+                transform,
+                &self.font_storage.read(),
                 device,
+                queue,
                 encoder,
                 view,
-            );
+            )
         } else {
             log::warn!("Render called before wgpu renderer initialized.");
+            RenderStats::default()
         }
     }
 
@@ -374,20 +1310,184 @@ impl FontSystem {
         &self,
         text_layout: &TextLayout<T>,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         controller: &mut impl WgpuRenderPassController<E>,
-    ) -> Result<(), E> {
+    ) -> Result<RenderStats, E> {
         if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
             renderer.render_to(
                 text_layout,
-                &mut self.font_storage.lock(),
+                &self.font_storage.read(),
                 device,
+                queue,
                 controller,
-            )?;
+            )
+        } else {
+            log::warn!("Render called before wgpu renderer initialized.");
+            Ok(RenderStats::default())
+        }
+    }
 
-            Ok(())
+    /// Renders text along with solid rectangles, such as selection highlights,
+    /// using the WGPU renderer with a custom render pass controller.
+    ///
+    /// See [`WgpuRenderer::render_with_rects_to`].
+    pub fn wgpu_render_with_rects_to<T: Into<[f32; 4]> + Copy, E>(
+        &self,
+        text_layout: &TextLayout<T>,
+        rects: LayoutRects<'_>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+    ) -> Result<RenderStats, E> {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.render_with_rects_to(
+                text_layout,
+                rects,
+                &self.font_storage.read(),
+                device,
+                queue,
+                controller,
+            )
         } else {
             log::warn!("Render called before wgpu renderer initialized.");
-            Ok(())
+            Ok(RenderStats::default())
+        }
+    }
+
+    /// Renders a layout moved and faded by `placement`, using the WGPU renderer
+    /// with a custom render pass controller.
+    ///
+    /// See [`WgpuRenderer::render_placed_to`].
+    pub fn wgpu_render_placed_to<T: Into<[f32; 4]> + Copy, E>(
+        &self,
+        text_layout: &TextLayout<T>,
+        placement: LayoutPlacement,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+    ) -> Result<RenderStats, E> {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.render_placed_to(
+                text_layout,
+                placement,
+                &self.font_storage.read(),
+                device,
+                queue,
+                controller,
+            )
+        } else {
+            log::warn!("Render called before wgpu renderer initialized.");
+            Ok(RenderStats::default())
+        }
+    }
+
+    /// Renders several layouts, each with its placement, using the WGPU renderer
+    /// with a custom render pass controller.
+    ///
+    /// See [`WgpuRenderer::render_all`].
+    pub fn wgpu_render_all<T: Into<[f32; 4]> + Copy, E>(
+        &self,
+        layouts: &[(LayoutPlacement, &TextLayout<T>)],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+    ) -> Result<RenderStats, E> {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.render_all(
+                layouts,
+                &self.font_storage.read(),
+                device,
+                queue,
+                controller,
+            )
+        } else {
+            log::warn!("Render called before wgpu renderer initialized.");
+            Ok(RenderStats::default())
+        }
+    }
+
+    /// Uploads text for the WGPU renderer to draw in a render pass of the caller's.
+    ///
+    /// See [`WgpuRenderer::prepare`]; record the result with [`Self::wgpu_render_in_pass`].
+    pub fn wgpu_prepare<T: Into<[f32; 4]> + Copy>(
+        &self,
+        text_layout: &TextLayout<T>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        format: wgpu::TextureFormat,
+        target_size: [f32; 2],
+    ) -> PreparedText {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.prepare(
+                text_layout,
+                &self.font_storage.read(),
+                device,
+                queue,
+                encoder,
+                format,
+                target_size,
+            )
+        } else {
+            log::warn!("Prepare called before wgpu renderer initialized.");
+            PreparedText::default()
+        }
+    }
+
+    /// Uploads text along with solid rectangles for the WGPU renderer to draw in
+    /// a render pass of the caller's.
+    ///
+    /// See [`WgpuRenderer::prepare_with_rects`].
+    pub fn wgpu_prepare_with_rects<T: Into<[f32; 4]> + Copy>(
+        &self,
+        text_layout: &TextLayout<T>,
+        rects: LayoutRects<'_>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        format: wgpu::TextureFormat,
+        target_size: [f32; 2],
+    ) -> PreparedText {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.prepare_with_rects(
+                text_layout,
+                rects,
+                &self.font_storage.read(),
+                device,
+                queue,
+                encoder,
+                format,
+                target_size,
+            )
+        } else {
+            log::warn!("Prepare called before wgpu renderer initialized.");
+            PreparedText::default()
+        }
+    }
+
+    /// Records text prepared by [`Self::wgpu_prepare`] into `pass`.
+    pub fn wgpu_render_in_pass(&self, prepared: &PreparedText, pass: &mut wgpu::RenderPass<'_>) {
+        if let Some(renderer) = &*self.wgpu_renderer.lock() {
+            renderer.render_in_pass(prepared, pass);
+        } else {
+            log::warn!("Render called before wgpu renderer initialized.");
+        }
+    }
+
+    /// Draws the WGPU renderer's atlas pages into `view`, to diagnose cache configurations.
+    ///
+    /// See [`WgpuRenderer::render_atlas_debug_to`].
+    pub fn wgpu_render_atlas_debug(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        if let Some(renderer) = &mut *self.wgpu_renderer.lock() {
+            renderer.render_atlas_debug(device, queue, encoder, view);
+        } else {
+            log::warn!("Atlas debug render called before wgpu renderer initialized.");
         }
     }
 }
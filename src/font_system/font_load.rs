@@ -0,0 +1,201 @@
+//! Font loading on background threads.
+//!
+//! Reading and parsing a large font directory can take a noticeable time. The
+//! loads here parse into a private database first and only lock the storage to
+//! register the parsed faces, all at once, so layouts never observe a partially
+//! loaded directory and are not blocked while files are read. What fallback
+//! detection needs to know about the new faces is read before the storage is
+//! locked as well.
+
+use std::{
+    any::Any,
+    future::Future,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+    thread::JoinHandle,
+};
+
+use parking_lot::{Mutex, RwLock};
+
+use crate::{
+    Error,
+    font_storage::{FaceCoverage, FontStorage},
+};
+
+/// Callback told about the faces registered by a background load.
+pub(crate) type FontLoadHook = Arc<dyn Fn(&[fontdb::ID]) + Send + Sync>;
+
+type LoadResult = Result<Vec<fontdb::ID>, Error>;
+
+enum State {
+    Running(Option<Waker>),
+    Finished(Option<LoadResult>),
+}
+
+/// A font load running on a background thread.
+///
+/// Returned by [`FontSystem::load_font_file_async`](crate::FontSystem::load_font_file_async)
+/// and [`FontSystem::load_fonts_dir_async`](crate::FontSystem::load_fonts_dir_async).
+/// The load proceeds whether or not the handle is kept. It can be awaited on any
+/// executor, or waited for with [`Self::wait`]; both yield the IDs of the
+/// registered faces. If the load or the
+/// [font load hook](crate::FontSystem::set_font_load_hook) panics, they yield an
+/// error instead; faces registered before the hook panicked stay registered.
+pub struct FontLoad {
+    state: Arc<Mutex<State>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FontLoad {
+    /// Runs `load` on a new thread and registers the faces it parsed.
+    pub(crate) fn spawn(
+        font_storage: Arc<RwLock<FontStorage>>,
+        hook: Arc<Mutex<Option<FontLoadHook>>>,
+        load: impl FnOnce(&mut fontdb::Database) -> Result<(), Error> + Send + 'static,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(State::Running(None)));
+        let thread_state = Arc::clone(&state);
+        let thread = std::thread::spawn(move || {
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                let mut font_db = fontdb::Database::new();
+                load(&mut font_db)?;
+                let detects_fallbacks = font_storage.read().detects_fallbacks();
+                let faces: Vec<(fontdb::FaceInfo, Option<FaceCoverage>)> = font_db
+                    .faces()
+                    .map(|face| {
+                        let coverage =
+                            detects_fallbacks.then(|| FaceCoverage::read(&font_db, face.id));
+                        (face.clone(), coverage)
+                    })
+                    .collect();
+                let ids = font_storage.write().register_faces_with_coverage(faces);
+
+                if !ids.is_empty()
+                    && let Some(hook) = hook.lock().clone()
+                {
+                    hook(&ids);
+                }
+                Ok(ids)
+            }))
+            .unwrap_or_else(|payload| Err(panic_error(payload.as_ref())));
+
+            let previous =
+                std::mem::replace(&mut *thread_state.lock(), State::Finished(Some(result)));
+            if let State::Running(Some(waker)) = previous {
+                waker.wake();
+            }
+        });
+        Self {
+            state,
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns `true` once the faces are registered.
+    pub fn is_finished(&self) -> bool {
+        matches!(*self.state.lock(), State::Finished(_))
+    }
+
+    /// Blocks until the load finishes and returns the IDs of the registered faces.
+    pub fn wait(mut self) -> Result<Vec<fontdb::ID>, Error> {
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            return Err(std::io::Error::other("font loading thread panicked").into());
+        }
+        self.take_result()
+    }
+
+    fn take_result(&self) -> LoadResult {
+        match &mut *self.state.lock() {
+            State::Finished(result) => result.take().unwrap_or_else(|| {
+                Err(std::io::Error::other("font load result already taken").into())
+            }),
+            State::Running(_) => Err(std::io::Error::other("font loading thread panicked").into()),
+        }
+    }
+}
+
+/// Turns the payload of a panic on the loading thread into an error.
+fn panic_error(payload: &(dyn Any + Send)) -> Error {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    std::io::Error::other(format!("font loading thread panicked: {message}")).into()
+}
+
+impl Future for FontLoad {
+    type Output = Result<Vec<fontdb::ID>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        {
+            let mut state = self.state.lock();
+            if let State::Running(waker) = &mut *state
+                && self
+                    .thread
+                    .as_ref()
+                    .is_some_and(|thread| !thread.is_finished())
+            {
+                *waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(self.take_result())
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{FontSystem, test_utils::TEST_FONT_PATH};
+
+    #[test]
+    fn test_load_dir_async() {
+        let path = PathBuf::from(TEST_FONT_PATH);
+        let dir = path.parent().unwrap().to_path_buf();
+
+        let font_system = FontSystem::new();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        font_system.set_font_load_hook(move |ids| sender.send(ids.to_vec()).unwrap());
+
+        let ids = pollster::block_on(font_system.load_fonts_dir_async(dir)).unwrap();
+        assert!(!ids.is_empty());
+        assert_eq!(receiver.recv().unwrap(), ids);
+        assert_eq!(font_system.faces().len(), ids.len());
+        assert!(font_system.face(ids[0]).is_some());
+
+        // A file load is waited for synchronously; its faces are registered again.
+        let load = font_system.load_font_file_async(path);
+        let more = load.wait().unwrap();
+        assert!(!more.is_empty() && more.iter().all(|id| !ids.contains(id)));
+        assert_eq!(receiver.recv().unwrap(), more);
+    }
+
+    #[test]
+    fn test_panicking_hook_fails_the_load() {
+        let path = PathBuf::from(TEST_FONT_PATH);
+
+        let font_system = FontSystem::new();
+        font_system.set_font_load_hook(|_| panic!("hook failed"));
+        let error = pollster::block_on(font_system.load_font_file_async(path.clone()))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("hook failed"), "{error}");
+        assert!(!font_system.faces().is_empty());
+        assert!(font_system.load_font_file_async(path).wait().is_err());
+    }
+
+    #[test]
+    fn test_load_missing_file_async() {
+        let font_system = FontSystem::new();
+        let load = font_system.load_font_file_async("/nonexistent/font.ttf".into());
+        assert!(pollster::block_on(load).is_err());
+        assert!(font_system.faces().is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+//! Reloading font files that change on disk.
+//!
+//! On Linux and Android the watcher is notified of changes through inotify.
+//! Elsewhere, or if inotify is unavailable, it polls the modification times of
+//! the files the loaded faces come from, which needs nothing beyond `std`.
+//! Polling a handful of font files once or twice a second is cheap.
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod inotify;
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{
+        Arc, Weak,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+use crate::FontSystem;
+
+/// Callback told about the faces reloaded from a changed file.
+type ReloadCallback = dyn Fn(&[fontdb::ID]) + Send + Sync;
+
+/// A background thread reloading font files that change on disk.
+///
+/// Returned by [`FontSystem::watch_font_files`]. Watching stops when the handle
+/// is dropped or the font system is dropped.
+pub struct FontWatcher {
+    stop: Arc<AtomicBool>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    wake: Option<inotify::Wake>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FontWatcher {
+    pub(crate) fn spawn(
+        font_system: Weak<FontSystem>,
+        interval: Duration,
+        on_reload: Box<ReloadCallback>,
+    ) -> Self {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let mut notifier = inotify::Notifier::new()
+            .inspect_err(|e| log::warn!("Failed to watch font files, polling instead: {}", e))
+            .ok();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let wake = notifier.as_ref().map(inotify::Notifier::waker);
+        let thread = std::thread::spawn(move || {
+            let mut modified = HashMap::new();
+            // Files that changed according to the notifier, if there is one.
+            let mut changed: Option<HashSet<PathBuf>> = None;
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            if notifier.is_some() {
+                changed = Some(HashSet::new());
+            }
+            loop {
+                let Some(font_system) = font_system.upgrade() else {
+                    return;
+                };
+                let paths = font_files(&font_system);
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                if let Some(notifier) = &mut notifier {
+                    notifier.watch(&paths);
+                }
+                check(
+                    &font_system,
+                    &paths,
+                    changed.as_mut(),
+                    &mut modified,
+                    &*on_reload,
+                );
+                drop(font_system);
+
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                if let (Some(notifier), Some(changed)) = (&mut notifier, &mut changed) {
+                    match notifier.wait(interval, changed) {
+                        inotify::Changes::Files | inotify::Changes::Woken => {}
+                        // Check every file once.
+                        inotify::Changes::Overflow => changed.extend(paths),
+                    }
+                } else {
+                    std::thread::park_timeout(interval);
+                }
+                #[cfg(not(any(target_os = "linux", target_os = "android")))]
+                std::thread::park_timeout(interval);
+                if thread_stop.load(Ordering::Acquire) {
+                    return;
+                }
+            }
+        });
+        Self {
+            stop,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            wake,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for FontWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(wake) = &self.wake {
+            wake.wake();
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Returns the files the faces of `font_system` come from, sorted.
+fn font_files(font_system: &FontSystem) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = font_system
+        .faces()
+        .into_iter()
+        .filter_map(|face| match face.source {
+            fontdb::Source::File(path) => Some(path),
+            _ => None,
+        })
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Reloads the files among `paths` whose modification time changed since they
+/// were last checked.
+///
+/// With `changed`, the files reported by the notifier, only those and files seen
+/// for the first time are checked, and `changed` is emptied. Files seen for the
+/// first time are only recorded.
+fn check(
+    font_system: &FontSystem,
+    paths: &[PathBuf],
+    changed: Option<&mut HashSet<PathBuf>>,
+    modified: &mut HashMap<PathBuf, SystemTime>,
+    on_reload: &ReloadCallback,
+) {
+    modified.retain(|path, _| paths.binary_search(path).is_ok());
+    let candidates: Vec<&PathBuf> = match changed {
+        Some(changed) => {
+            let candidates = paths
+                .iter()
+                .filter(|&path| changed.contains(path) || !modified.contains_key(path))
+                .collect();
+            changed.clear();
+            candidates
+        }
+        None => paths.iter().collect(),
+    };
+
+    for path in candidates {
+        let Ok(time) = std::fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+            // The file is being replaced or was removed; keep the loaded data.
+            continue;
+        };
+        let Some(previous) = modified.insert(path.clone(), time) else {
+            continue;
+        };
+        if previous == time {
+            continue;
+        }
+
+        log::info!("Reloading changed font file {}", path.display());
+        let ids = font_system.reload_font_file(path);
+        if !ids.is_empty() {
+            on_reload(&ids);
+        }
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TEST_FONT_PATH;
+
+    #[test]
+    fn test_reloads_changed_file() {
+        let path = PathBuf::from(TEST_FONT_PATH);
+
+        let dir = std::env::temp_dir().join(format!("suzuri-font-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let copy = dir.join(path.file_name().unwrap());
+        std::fs::copy(&path, &copy).unwrap();
+
+        let font_system = Arc::new(FontSystem::new());
+        font_system.load_font_file(copy.clone()).unwrap();
+        let id = font_system.faces()[0].id;
+        assert!(font_system.font(id).is_some());
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let watcher = font_system.watch_font_files(Duration::from_millis(10), move |ids| {
+            sender.send(ids.to_vec()).unwrap();
+        });
+        // Let the watcher record the current modification time.
+        std::thread::sleep(Duration::from_millis(50));
+
+        let file = std::fs::OpenOptions::new().write(true).open(&copy).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        drop(file);
+
+        let ids = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(ids.contains(&id));
+        assert!(font_system.font(id).is_some());
+
+        // Renaming a new file over the font replaces it too.
+        let new = dir.join("new.ttf");
+        std::fs::copy(&path, &new).unwrap();
+        let file = std::fs::OpenOptions::new().write(true).open(&new).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(120))
+            .unwrap();
+        drop(file);
+        std::fs::rename(&new, &copy).unwrap();
+        let ids = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(ids.contains(&id));
+
+        drop(watcher);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_notified_of_changes_between_polls() {
+        let path = PathBuf::from(TEST_FONT_PATH);
+
+        let dir = std::env::temp_dir().join(format!("suzuri-font-notify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let copy = dir.join(path.file_name().unwrap());
+        std::fs::copy(&path, &copy).unwrap();
+
+        let font_system = Arc::new(FontSystem::new());
+        let id = font_system.load_font_file(copy.clone()).unwrap()[0];
+        let (sender, receiver) = std::sync::mpsc::channel();
+        // Far longer than the test waits, so only a notification reloads the file.
+        let watcher = font_system.watch_font_files(Duration::from_secs(3600), move |ids| {
+            sender.send(ids.to_vec()).unwrap();
+        });
+        std::thread::sleep(Duration::from_millis(50));
+
+        let file = std::fs::OpenOptions::new().write(true).open(&copy).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        drop(file);
+        let ids = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(ids, [id]);
+
+        // Dropping the watcher wakes its thread instead of waiting out the interval.
+        let start = std::time::Instant::now();
+        drop(watcher);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
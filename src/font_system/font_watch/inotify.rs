@@ -0,0 +1,143 @@
+//! Change notifications from inotify.
+//!
+//! The directories of the font files are watched rather than the files, so that
+//! files replaced by renaming a new file over them, as editors and font tools
+//! do, are noticed as well.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    mem::MaybeUninit,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use rustix::{
+    event::{EventfdFlags, PollFd, PollFlags},
+    fd::OwnedFd,
+    fs::inotify,
+    io::Errno,
+};
+
+/// Events that may change a font file: it was written, its attributes (e.g. the
+/// modification time) changed, or another file was moved over it.
+const EVENTS: inotify::WatchFlags = inotify::WatchFlags::CLOSE_WRITE
+    .union(inotify::WatchFlags::ATTRIB)
+    .union(inotify::WatchFlags::MOVED_TO);
+
+/// What [`Notifier::wait`] saw.
+pub(super) enum Changes {
+    /// The listed files may have changed.
+    Files,
+    /// Events were lost, so any file may have changed.
+    Overflow,
+    /// [`Wake::wake`] was called.
+    Woken,
+}
+
+/// Interrupts a [`Notifier::wait`] from another thread.
+#[derive(Clone)]
+pub(super) struct Wake(Arc<OwnedFd>);
+
+impl Wake {
+    pub fn wake(&self) {
+        // Fails only if the counter is about to overflow, when a wake is pending anyway.
+        let _ = rustix::io::write(&*self.0, &1u64.to_ne_bytes());
+    }
+}
+
+/// Watches the directories of a set of files.
+pub(super) struct Notifier {
+    inotify: OwnedFd,
+    wake: Wake,
+    /// Watch descriptors of the watched directories.
+    dirs: HashMap<PathBuf, i32>,
+    buffer: Vec<MaybeUninit<u8>>,
+}
+
+impl Notifier {
+    pub fn new() -> Result<Self, Errno> {
+        let inotify =
+            inotify::init(inotify::CreateFlags::CLOEXEC | inotify::CreateFlags::NONBLOCK)?;
+        let wake = rustix::event::eventfd(0, EventfdFlags::CLOEXEC | EventfdFlags::NONBLOCK)?;
+        Ok(Self {
+            inotify,
+            wake: Wake(Arc::new(wake)),
+            dirs: HashMap::new(),
+            buffer: vec![MaybeUninit::uninit(); 4096],
+        })
+    }
+
+    pub fn waker(&self) -> Wake {
+        self.wake.clone()
+    }
+
+    /// Watches the directories of `paths` and stops watching all others.
+    pub fn watch(&mut self, paths: &[PathBuf]) {
+        let dirs: HashSet<&Path> = paths.iter().filter_map(|path| path.parent()).collect();
+        self.dirs.retain(|dir, &mut wd| {
+            let keep = dirs.contains(dir.as_path());
+            if !keep {
+                let _ = inotify::remove_watch(&self.inotify, wd);
+            }
+            keep
+        });
+        for dir in dirs {
+            if self.dirs.contains_key(dir) {
+                continue;
+            }
+            match inotify::add_watch(&self.inotify, dir, EVENTS) {
+                Ok(wd) => {
+                    self.dirs.insert(dir.to_path_buf(), wd);
+                }
+                Err(e) => log::warn!("Failed to watch font directory {}: {}", dir.display(), e),
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for changes and adds the paths of the changed
+    /// files in the watched directories to `changed`.
+    pub fn wait(&mut self, timeout: Duration, changed: &mut HashSet<PathBuf>) -> Changes {
+        let timespec = rustix::event::Timespec::try_from(timeout).ok();
+        let mut fds = [
+            PollFd::new(&self.inotify, PollFlags::IN),
+            PollFd::new(&*self.wake.0, PollFlags::IN),
+        ];
+        match rustix::event::poll(&mut fds, timespec.as_ref()) {
+            Ok(_) | Err(Errno::INTR) => {}
+            Err(e) => {
+                log::warn!("Failed to wait for font file changes: {}", e);
+                std::thread::sleep(timeout);
+                return Changes::Overflow;
+            }
+        }
+        if !fds[1].revents().is_empty() {
+            let mut counter = [0; 8];
+            let _ = rustix::io::read(&*self.wake.0, &mut counter);
+            return Changes::Woken;
+        }
+
+        let dirs: HashMap<i32, &PathBuf> = self.dirs.iter().map(|(dir, &wd)| (wd, dir)).collect();
+        let mut reader = inotify::Reader::new(&self.inotify, &mut self.buffer);
+        loop {
+            let event = match reader.next() {
+                Ok(event) => event,
+                Err(Errno::WOULDBLOCK | Errno::INTR) => return Changes::Files,
+                Err(e) => {
+                    log::warn!("Failed to read font file changes: {}", e);
+                    return Changes::Overflow;
+                }
+            };
+            if event.events().contains(inotify::ReadFlags::QUEUE_OVERFLOW) {
+                return Changes::Overflow;
+            }
+            if let Some(dir) = dirs.get(&event.wd())
+                && let Some(name) = event.file_name()
+            {
+                changed.insert(dir.join(OsStr::from_bytes(name.to_bytes())));
+            }
+        }
+    }
+}
@@ -5,11 +5,18 @@
 pub const SUB_PIXEL_QUANTIZE: f32 = 256f32;
 
 /// The same glyph is not guaranteed to receive the same `GlyphId` across program runs.
+///
+/// Instances of variable fonts are faces of their own (see
+/// [`FontStorage::variation_instance`](crate::FontStorage::variation_instance)),
+/// so the font ID also identifies the variation coordinates of a glyph.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct GlyphId {
     font_id: fontdb::ID,
     glyph_index: u16,
     font_size: u32, // font size * SUB_PIXEL_QUANTIZE as u32
+    hex_box: Option<char>,
+    subpixel_offset: Option<u8>, // offset * SUB_PIXEL_QUANTIZE as u8
+    lcd_mask: bool,
 }
 
 impl GlyphId {
@@ -21,9 +28,58 @@ impl GlyphId {
             font_id,
             glyph_index,
             font_size: (font_size * SUB_PIXEL_QUANTIZE).round() as u32,
+            hex_box: None,
+            subpixel_offset: None,
+            lcd_mask: false,
+        }
+    }
+
+    /// Creates a `GlyphId` for a procedurally drawn box showing the code point of `ch`.
+    ///
+    /// Used for characters that no font covers; `font_id` is the font of the text run.
+    pub fn hex_box(font_id: fontdb::ID, ch: char, font_size: f32) -> Self {
+        Self {
+            hex_box: Some(ch),
+            ..Self::new(font_id, 0, font_size)
+        }
+    }
+
+    /// Returns the variant of the glyph rasterized `offset` pixels to the right,
+    /// drawn at whole pixels.
+    ///
+    /// `offset` is clamped to `0.0..1.0`. See
+    /// [`TextLayoutConfig::subpixel_positions`](crate::text::TextLayoutConfig::subpixel_positions).
+    pub fn with_subpixel_offset(self, offset: f32) -> Self {
+        let offset = (offset.clamp(0.0, 1.0) * SUB_PIXEL_QUANTIZE).round();
+        Self {
+            subpixel_offset: Some(offset.min(SUB_PIXEL_QUANTIZE - 1.0) as u8),
+            ..self
+        }
+    }
+
+    /// Returns the offset the glyph is rasterized at, if it is drawn at whole pixels.
+    pub fn subpixel_offset(&self) -> Option<f32> {
+        self.subpixel_offset
+            .map(|offset| f32::from(offset) / SUB_PIXEL_QUANTIZE)
+    }
+
+    /// Returns the variant of the glyph rasterized as an LCD subpixel mask: each
+    /// pixel is three bytes wide, holding the coverage of its red, green and
+    /// blue subpixel.
+    ///
+    /// See [`GpuRenderer::set_lcd_antialiasing`](crate::renderer::GpuRenderer::set_lcd_antialiasing).
+    pub fn with_lcd_mask(self) -> Self {
+        Self {
+            lcd_mask: true,
+            ..self
         }
     }
 
+    /// Returns whether the glyph is rasterized as an LCD subpixel mask.
+    pub fn is_lcd_mask(&self) -> bool {
+        self.lcd_mask
+    }
+
     /// Returns the font ID.
     pub fn font_id(&self) -> fontdb::ID {
         self.font_id
@@ -38,4 +94,35 @@ impl GlyphId {
     pub fn font_size(&self) -> f32 {
         self.font_size as f32 / SUB_PIXEL_QUANTIZE
     }
+
+    /// Returns the character drawn as a hex box, if this is not a font glyph.
+    pub fn hex_box_char(&self) -> Option<char> {
+        self.hex_box
+    }
+
+    /// Describes the glyph for debugging output, naming its face, where the face
+    /// comes from and its index within a font collection, e.g.
+    /// `DejaVuSans glyph 36 at 16px (/usr/share/fonts/DejaVuSans.ttf, face 0)`.
+    pub fn describe(&self, font_storage: &crate::FontStorage) -> String {
+        let glyph = match self.hex_box {
+            Some(ch) => format!("hex box U+{:04X}", u32::from(ch)),
+            None => format!("glyph {}", self.glyph_index),
+        };
+        let Some(face) = font_storage.face(self.font_id) else {
+            return format!("{glyph} at {}px (unknown face)", self.font_size());
+        };
+        let source = match &face.source {
+            #[cfg(not(target_arch = "wasm32"))]
+            fontdb::Source::File(path) | fontdb::Source::SharedFile(path, _) => {
+                path.display().to_string()
+            }
+            fontdb::Source::Binary(_) => "memory".to_string(),
+        };
+        format!(
+            "{} {glyph} at {}px ({source}, face {})",
+            face.post_script_name,
+            self.font_size(),
+            face.index
+        )
+    }
 }
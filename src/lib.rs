@@ -2,6 +2,10 @@
 
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+/// Editable text buffers with incremental relayout.
+pub mod editor;
+/// Errors reported by font loading.
+pub mod error;
 /// Font loading and storage management.
 pub mod font_storage;
 /// The main entry point for the library, coordinating layout and rendering.
@@ -12,8 +16,16 @@ pub mod glyph_id;
 pub mod renderer;
 /// Text data structures and layout engine.
 pub mod text;
+/// Fetching and caching fonts delivered over the network.
+#[cfg(feature = "web-fonts")]
+pub mod web_fonts;
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod test_utils;
 
 // common re-exports
+pub use error::Error;
 pub use font_storage::FontStorage;
 pub use font_system::FontSystem;
 pub use glyph_id::GlyphId;
@@ -1,15 +1,40 @@
+/// Counters of the glyph caches.
+mod cache_stats;
 /// CPU software renderer.
+#[cfg(feature = "cpu")]
 pub mod cpu_renderer;
+/// Data-driven text effects interpreted before rendering.
+pub mod effect;
 /// Hardware-agnostic GPU renderer.
+#[cfg(feature = "gpu")]
 pub mod gpu_renderer;
 
+/// Procedural boxes for characters no font covers.
+pub(crate) mod hex_box;
+
+pub use cache_stats::{CacheStats, PageStats};
+#[cfg(feature = "cpu")]
 pub use cpu_renderer::{CpuCacheConfig, CpuRenderer};
-pub use gpu_renderer::{AtlasUpdate, GlyphInstance, GpuCacheConfig, GpuRenderer, StandaloneGlyph};
+pub use effect::{
+    AutoContrast, BackgroundImage, EffectLibrary, FilledRect, TextEffect, Underline,
+    apply_auto_contrast, apply_effects, apply_link_hover, run_backgrounds, text_decorations,
+};
+#[cfg(all(feature = "wgpu", not(target_arch = "wasm32")))]
+pub use effect::{BackgroundTexture, apply_auto_contrast_texture};
+#[cfg(feature = "gpu")]
+pub use gpu_renderer::{
+    AtlasMove, AtlasPacking, AtlasPriority, AtlasUpdate, DamageEffects, GlyphBatch, GlyphInstance,
+    GpuCacheConfig, GpuRenderer, HostAtlas, HostAtlasRegion, HostAtlasRenderer, LayoutDamage,
+    LayoutPlacement, LinearGradient, RenderOutput, StandaloneGlyph,
+};
 
 #[cfg(feature = "wgpu")]
 pub mod wgpu_renderer;
 #[cfg(feature = "wgpu")]
-pub use wgpu_renderer::{SimpleRenderPass, WgpuRenderPassController, WgpuRenderer};
+pub use wgpu_renderer::{
+    BlendSpace, ColorSpace, CustomShader, HdrOutput, LayoutRects, PreparedText, RenderStats,
+    SamplerConfig, SimpleRenderPass, TextShadow, WgpuRenderPassController, WgpuRenderer,
+};
 
 // debug uses
 /// CPU-based debugging renderer.
@@ -20,3 +45,181 @@ pub mod cpu_debug_renderer;
 #[cfg(debug_assertions)]
 #[doc(hidden)]
 pub mod debug_renderer;
+
+use crate::{font_storage::FontStorage, glyph_id::GlyphId};
+
+/// Returns the bitmap metrics of a glyph without rasterizing it.
+pub(crate) fn glyph_metrics(
+    glyph_id: &GlyphId,
+    font_storage: &FontStorage,
+) -> Option<fontdue::Metrics> {
+    let metrics = if let Some(ch) = glyph_id.hex_box_char() {
+        hex_box::metrics(ch, glyph_id.font_size())
+    } else {
+        font_storage.glyph_metrics(
+            glyph_id.font_id(),
+            glyph_id.glyph_index(),
+            glyph_id.font_size(),
+        )?
+    };
+    let metrics = match glyph_id.subpixel_offset() {
+        Some(offset) if offset > 0.0 && metrics.width > 0 => fontdue::Metrics {
+            width: metrics.width + 1,
+            ..metrics
+        },
+        _ => metrics,
+    };
+    Some(match glyph_id.is_lcd_mask() {
+        true => fontdue::Metrics {
+            width: metrics.width * 3,
+            ..metrics
+        },
+        false => metrics,
+    })
+}
+
+/// Rasterizes a glyph into a coverage bitmap, drawing hex boxes procedurally.
+#[cfg_attr(
+    not(any(feature = "cpu", feature = "gpu", debug_assertions)),
+    allow(dead_code)
+)]
+pub(crate) fn rasterize_glyph(
+    glyph_id: &GlyphId,
+    font_storage: &FontStorage,
+) -> Option<(fontdue::Metrics, Vec<u8>)> {
+    if glyph_id.is_lcd_mask() {
+        return rasterize_lcd_mask(glyph_id, font_storage);
+    }
+    let shift = glyph_id.subpixel_offset().filter(|&offset| offset > 0.0);
+    if let Some(ch) = glyph_id.hex_box_char() {
+        let (metrics, bitmap) = hex_box::rasterize(ch, glyph_id.font_size());
+        return Some(match shift {
+            Some(offset) if metrics.width > 0 => shift_right(metrics, &bitmap, offset),
+            _ => (metrics, bitmap),
+        });
+    }
+    // Subpixel variants are rasterized from the shifted outline.
+    if let Some(shift) = shift {
+        return font_storage.rasterize_shifted(
+            glyph_id.font_id(),
+            glyph_id.glyph_index(),
+            glyph_id.font_size(),
+            shift,
+        );
+    }
+    Some(match font_storage.instance(glyph_id.font_id()) {
+        Some(instance) => instance.rasterize(glyph_id.glyph_index(), glyph_id.font_size()),
+        None => {
+            let font = font_storage.font(glyph_id.font_id())?;
+            font.rasterize_indexed(glyph_id.glyph_index(), glyph_id.font_size())
+        }
+    })
+}
+
+/// Rasterizes a glyph into an LCD subpixel mask, three bytes per pixel.
+///
+/// Font glyphs are rasterized at three times the horizontal resolution and
+/// filtered against color fringes. Hex boxes and glyphs of variable font
+/// instances repeat their grayscale coverage for each subpixel. Subpixel
+/// offsets are rounded to whole subpixels.
+fn rasterize_lcd_mask(
+    glyph_id: &GlyphId,
+    font_storage: &FontStorage,
+) -> Option<(fontdue::Metrics, Vec<u8>)> {
+    let (metrics, mask) = match glyph_id.hex_box_char() {
+        None if font_storage.instance(glyph_id.font_id()).is_none() => {
+            let font = font_storage.font(glyph_id.font_id())?;
+            let (metrics, mask) =
+                font.rasterize_indexed_subpixel(glyph_id.glyph_index(), glyph_id.font_size());
+            let mask = lcd_filter(&mask, metrics.width * 3);
+            (metrics, mask)
+        }
+        _ => {
+            let grayscale = match glyph_id.hex_box_char() {
+                Some(ch) => GlyphId::hex_box(glyph_id.font_id(), ch, glyph_id.font_size()),
+                None => GlyphId::new(
+                    glyph_id.font_id(),
+                    glyph_id.glyph_index(),
+                    glyph_id.font_size(),
+                ),
+            };
+            let (metrics, bitmap) = rasterize_glyph(&grayscale, font_storage)?;
+            let mask = bitmap.iter().flat_map(|&coverage| [coverage; 3]).collect();
+            (metrics, mask)
+        }
+    };
+
+    let shift = match glyph_id.subpixel_offset() {
+        Some(offset) if offset > 0.0 && metrics.width > 0 => Some((offset * 3.0).round() as usize),
+        _ => None,
+    };
+    let Some(shift) = shift else {
+        return Some((
+            fontdue::Metrics {
+                width: metrics.width * 3,
+                ..metrics
+            },
+            mask,
+        ));
+    };
+    // Widened by a pixel, like grayscale glyphs with an offset.
+    let (source_width, width) = (metrics.width * 3, (metrics.width + 1) * 3);
+    let mut shifted = vec![0; width * metrics.height];
+    if source_width > 0 {
+        for (row, source) in mask.chunks_exact(source_width).enumerate() {
+            shifted[row * width + shift..][..source_width].copy_from_slice(source);
+        }
+    }
+    Some((fontdue::Metrics { width, ..metrics }, shifted))
+}
+
+/// Spreads each subpixel's coverage over its neighbors with the weights
+/// `[1, 2, 3, 2, 1] / 9`, which reduces color fringes at glyph edges.
+fn lcd_filter(mask: &[u8], row_width: usize) -> Vec<u8> {
+    const WEIGHTS: [u32; 5] = [1, 2, 3, 2, 1];
+    let mut filtered = vec![0; mask.len()];
+    if row_width == 0 {
+        return filtered;
+    }
+    for (source, target) in mask
+        .chunks_exact(row_width)
+        .zip(filtered.chunks_exact_mut(row_width))
+    {
+        for (x, target) in target.iter_mut().enumerate() {
+            let sum: u32 = WEIGHTS
+                .iter()
+                .enumerate()
+                .filter_map(|(tap, weight)| {
+                    let x = (x + tap).checked_sub(2)?;
+                    source.get(x).map(|&coverage| u32::from(coverage) * weight)
+                })
+                .sum();
+            *target = ((sum + 4) / 9) as u8;
+        }
+    }
+    filtered
+}
+
+/// Moves a coverage bitmap `offset` (less than one) pixels to the right, widening
+/// it by a column.
+///
+/// Each pixel takes its share of the coverage of the pixel to its left and itself.
+/// Used for hex boxes, which are drawn on whole pixels and have no outline to shift.
+fn shift_right(
+    metrics: fontdue::Metrics,
+    bitmap: &[u8],
+    offset: f32,
+) -> (fontdue::Metrics, Vec<u8>) {
+    let width = metrics.width + 1;
+    let mut shifted = vec![0; width * metrics.height];
+    for (row, source) in bitmap.chunks_exact(metrics.width).enumerate() {
+        let target = &mut shifted[row * width..(row + 1) * width];
+        let mut left = 0.0;
+        for (pixel, &coverage) in target.iter_mut().zip(source.iter().chain([&0])) {
+            let coverage = f32::from(coverage);
+            *pixel = (left * offset + coverage * (1.0 - offset)).round() as u8;
+            left = coverage;
+        }
+    }
+    (fontdue::Metrics { width, ..metrics }, shifted)
+}
@@ -0,0 +1,68 @@
+/// Counters of a glyph cache, for tuning its configuration.
+///
+/// Counts accumulate from the creation of the renderer or the last call of its
+/// `reset_stats`. A high share of [`Self::misses`] and [`Self::evictions`] means
+/// the glyphs of a frame do not fit into the cache; [`Self::standalone`] counts
+/// glyphs larger than every tile or block, or that found no room in a frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups of glyphs that were already cached.
+    pub hits: u64,
+    /// Lookups of glyphs that were rasterized and inserted into the cache.
+    pub misses: u64,
+    /// Glyphs removed to make room for others.
+    pub evictions: u64,
+    /// Glyphs drawn without being cached.
+    pub standalone: u64,
+    /// Current occupancy of each page, by texture index for GPU caches and by
+    /// block size for CPU caches.
+    pub pages: Vec<PageStats>,
+}
+
+impl CacheStats {
+    /// Returns the share of lookups that were hits, or 0 before any lookup.
+    pub fn hit_rate(&self) -> f32 {
+        let lookups = self.hits + self.misses + self.standalone;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.hits as f32 / lookups as f32
+    }
+
+    /// Returns the share of all pages covered by glyphs, see [`PageStats::occupancy`].
+    pub fn occupancy(&self) -> f32 {
+        let area: usize = self.pages.iter().map(|page| page.area).sum();
+        if area == 0 {
+            return 0.0;
+        }
+        let used_area: usize = self.pages.iter().map(|page| page.used_area).sum();
+        used_area as f32 / area as f32
+    }
+}
+
+/// Occupancy of one page of a glyph cache, see [`CacheStats::pages`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PageStats {
+    /// Slots holding a glyph.
+    pub used: usize,
+    /// Slots of the page.
+    ///
+    /// GPU pages packed with other than [`AtlasPacking::Grid`](crate::renderer::AtlasPacking::Grid)
+    /// add slots as needed; compare their [`Self::occupancy`] instead.
+    pub capacity: usize,
+    /// Texels covered by glyphs, without padding, for GPU caches, and by used
+    /// blocks for CPU caches.
+    pub used_area: usize,
+    /// Texels of the page.
+    pub area: usize,
+}
+
+impl PageStats {
+    /// Returns the share of the page covered by glyphs, or 0 for an empty page.
+    pub fn occupancy(&self) -> f32 {
+        if self.area == 0 {
+            return 0.0;
+        }
+        self.used_area as f32 / self.area as f32
+    }
+}
@@ -40,7 +40,7 @@ impl CpuDebugRenderer {
     pub fn render<T: Clone + Copy + Into<[f32; 4]>>(
         &mut self,
         layout: &TextLayout<T>,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
         target_buffer: &mut [u8],
         target_width: usize,
         target_height: usize,
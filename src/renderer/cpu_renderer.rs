@@ -1,4 +1,5 @@
 use crate::font_storage::FontStorage;
+use crate::renderer::CacheStats;
 use crate::text::{GlyphPosition, TextLayout};
 
 mod glyph_cache;
@@ -84,27 +85,56 @@ impl CpuRenderer {
         self.cache.clear();
     }
 
+    /// Drops the cached glyphs of font `font_id`, e.g. after its file changed.
+    pub fn forget_font(&mut self, font_id: fontdb::ID) {
+        self.cache.remove_font(font_id);
+    }
+
+    /// Returns the cache's counters, see [`CpuCache::stats`].
+    pub fn stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Resets the counters of [`Self::stats`].
+    pub fn reset_stats(&mut self) {
+        self.cache.reset_stats();
+    }
+
     /// Renders the provided [`TextLayout`] by calling the closure for each pixel.
+    ///
+    /// Pixels outside of [`TextLayout::clip_rect`] are skipped.
     pub fn render<T>(
         &mut self,
         layout: &TextLayout<T>,
         image_size: [usize; 2],
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
         f: &mut dyn FnMut([usize; 2], u8, &T),
     ) {
         let width = image_size[0];
         let height = image_size[1];
 
-        if width == 0 || height == 0 {
+        // Pixel bounds `[min_x, min_y, max_x, max_y]` drawn into, covering every
+        // pixel that overlaps the clip.
+        let bounds = match layout.clip_rect {
+            Some(clip_rect) => [
+                (clip_rect.min.x.floor().max(0.0) as usize).min(width),
+                (clip_rect.min.y.floor().max(0.0) as usize).min(height),
+                (clip_rect.max.x.ceil().max(0.0) as usize).min(width),
+                (clip_rect.max.y.ceil().max(0.0) as usize).min(height),
+            ],
+            None => [0, 0, width, height],
+        };
+
+        if bounds[0] >= bounds[2] || bounds[1] >= bounds[3] {
             return;
         }
 
         for line in &layout.lines {
-            if line.bottom <= 0.0 || line.top >= height as f32 {
+            if line.bottom <= bounds[1] as f32 || line.top >= bounds[3] as f32 {
                 continue;
             }
             for glyph in &line.glyphs {
-                self.render_glyph(glyph, font_storage, image_size, f);
+                self.render_glyph(glyph, font_storage, bounds, f);
             }
         }
     }
@@ -112,20 +142,18 @@ impl CpuRenderer {
     fn render_glyph<T>(
         &mut self,
         glyph_pos: &GlyphPosition<T>,
-        font_storage: &mut FontStorage,
-        image_size: [usize; 2],
+        font_storage: &FontStorage,
+        bounds: [usize; 4],
         f: &mut dyn FnMut([usize; 2], u8, &T),
     ) {
         let cached = match self.cache.get(&glyph_pos.glyph_id, font_storage) {
             Some(cached) => cached,
             None => {
-                let Some(font) = font_storage.font(glyph_pos.glyph_id.font_id()) else {
+                let Some((metrics, bitmap)) =
+                    super::rasterize_glyph(&glyph_pos.glyph_id, font_storage)
+                else {
                     return;
                 };
-                let (metrics, bitmap) = font.rasterize_indexed(
-                    glyph_pos.glyph_id.glyph_index(),
-                    glyph_pos.glyph_id.font_size(),
-                );
                 CpuCacheItem {
                     width: metrics.width,
                     height: metrics.height,
@@ -140,8 +168,7 @@ impl CpuRenderer {
 
         let glyph_width = cached.width;
         let glyph_height = cached.height;
-        let origin_x = glyph_pos.x;
-        let origin_y = glyph_pos.y;
+        let [origin_x, origin_y] = glyph_pos.raster_origin();
 
         for row in 0..glyph_height {
             let y = origin_y + row as f32;
@@ -149,7 +176,7 @@ impl CpuRenderer {
                 continue;
             }
             let iy = y.floor() as isize;
-            if iy < 0 || iy as usize >= image_size[1] {
+            if iy < bounds[1] as isize || iy as usize >= bounds[3] {
                 continue;
             }
 
@@ -165,7 +192,7 @@ impl CpuRenderer {
                 }
 
                 let ix = x.floor() as isize;
-                if ix < 0 || ix as usize >= image_size[0] {
+                if ix < bounds[0] as isize || ix as usize >= bounds[2] {
                     continue;
                 }
 
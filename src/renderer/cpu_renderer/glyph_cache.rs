@@ -3,6 +3,7 @@ use std::num::NonZeroUsize;
 
 use crate::font_storage::FontStorage;
 use crate::glyph_id::GlyphId;
+use crate::renderer::{CacheStats, PageStats};
 
 #[derive(Default, Clone, Copy)]
 struct LruNode {
@@ -21,6 +22,9 @@ struct VecAtlas<T: Default + Clone + Copy> {
     lru_map: HashMap<GlyphId, usize, fxhash::FxBuildHasher>,
     lru_empties: Vec<usize>,
     lru_keys: Vec<Option<GlyphId>>,
+
+    /// Glyphs evicted since creation or the last stats reset.
+    evictions: u64,
 }
 
 impl<T: Default + Clone + Copy> VecAtlas<T> {
@@ -38,6 +42,17 @@ impl<T: Default + Clone + Copy> VecAtlas<T> {
             lru_map: HashMap::with_capacity_and_hasher(capacity, fxhash::FxBuildHasher::default()),
             lru_empties: (0..capacity).collect(),
             lru_keys: vec![None; capacity],
+            evictions: 0,
+        }
+    }
+
+    fn stats(&self) -> PageStats {
+        let used = self.capacity - self.lru_empties.len();
+        PageStats {
+            used,
+            capacity: self.capacity,
+            used_area: used * self.block_size,
+            area: self.capacity * self.block_size,
         }
     }
 
@@ -49,6 +64,19 @@ impl<T: Default + Clone + Copy> VecAtlas<T> {
         self.lru_head = None;
         self.lru_tail = None;
     }
+
+    /// Frees the blocks of all glyphs of font `font_id`.
+    fn remove_font(&mut self, font_id: fontdb::ID) {
+        let indices: Vec<usize> = self
+            .lru_map
+            .iter()
+            .filter(|(key, _)| key.font_id() == font_id)
+            .map(|(_, &index)| index)
+            .collect();
+        for index in indices {
+            self.detach(index);
+        }
+    }
 }
 
 impl<T: Default + Clone + Copy> VecAtlas<T> {
@@ -120,6 +148,7 @@ impl<T: Default + Clone + Copy> VecAtlas<T> {
             if let Some(old_key) = self.lru_keys[tail_idx] {
                 self.lru_map.remove(&old_key);
             }
+            self.evictions += 1;
 
             tail_idx
         } else {
@@ -133,6 +162,23 @@ impl<T: Default + Clone + Copy> VecAtlas<T> {
         target_idx
     }
 
+    fn detach(&mut self, index: usize) {
+        let LruNode { newer, older } = self.lru_nodes[index];
+        match newer {
+            Some(newer) => self.lru_nodes[newer].older = older,
+            None => self.lru_head = older,
+        }
+        match older {
+            Some(older) => self.lru_nodes[older].newer = newer,
+            None => self.lru_tail = newer,
+        }
+        if let Some(key) = self.lru_keys[index].take() {
+            self.lru_map.remove(&key);
+        }
+        self.lru_nodes[index] = LruNode::default();
+        self.lru_empties.push(index);
+    }
+
     fn move_to_front(&mut self, key: &GlyphId) {
         // validate
         let Some(&current_index) = self.lru_map.get(key) else {
@@ -214,6 +260,10 @@ pub struct CpuCacheConfig {
 pub struct CpuCache {
     /// must be sorted by block size
     caches: Vec<VecAtlas<u8>>,
+    /// Lookup counters; evictions are counted per block size.
+    hits: u64,
+    misses: u64,
+    standalone: u64,
 }
 
 impl CpuCache {
@@ -230,7 +280,35 @@ impl CpuCache {
             .map(|config| VecAtlas::new(config.capacity, config.block_size))
             .collect();
 
-        Self { caches }
+        Self {
+            caches,
+            hits: 0,
+            misses: 0,
+            standalone: 0,
+        }
+    }
+
+    /// Returns the cache's counters and the occupancy of each block size, smallest first.
+    ///
+    /// Glyphs larger than every block are counted as [`CacheStats::standalone`].
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.caches.iter().map(|cache| cache.evictions).sum(),
+            standalone: self.standalone,
+            pages: self.caches.iter().map(VecAtlas::stats).collect(),
+        }
+    }
+
+    /// Resets the counters of [`Self::stats`].
+    pub fn reset_stats(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+        self.standalone = 0;
+        for cache in &mut self.caches {
+            cache.evictions = 0;
+        }
     }
 
     /// Clears the cache.
@@ -240,28 +318,40 @@ impl CpuCache {
         }
     }
 
+    /// Drops the cached glyphs of font `font_id`, e.g. after its file changed.
+    pub fn remove_font(&mut self, font_id: fontdb::ID) {
+        for cache in &mut self.caches {
+            cache.remove_font(font_id);
+        }
+    }
+
     /// Retrieves a glyph from the cache, or rasterizes and caches it if missing.
     pub fn get(
         &'_ mut self,
         glyph_id: &GlyphId,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
     ) -> Option<CpuCacheItem<'_>> {
-        let glyph_index = glyph_id.glyph_index();
-        let font_size = glyph_id.font_size();
-        let font_id = glyph_id.font_id();
-
-        let font = font_storage.font(font_id)?;
-        let glyph_metrics = font.metrics_indexed(glyph_index, font_size);
+        let glyph_metrics = crate::renderer::glyph_metrics(glyph_id, font_storage)?;
         let glyph_bitmap_size = glyph_metrics.width * glyph_metrics.height;
 
-        let cache = self
+        let Some(cache) = self
             .caches
             .iter_mut()
-            .find(|cache| cache.block_size >= glyph_bitmap_size)?;
+            .find(|cache| cache.block_size >= glyph_bitmap_size)
+        else {
+            self.standalone += 1;
+            return None;
+        };
+        if cache.lru_map.contains_key(glyph_id) {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
 
         let data = cache.get_or_insert_with(glyph_id, || {
-            let bitmap = font.rasterize_indexed(glyph_index, font_size);
-            bitmap.1
+            crate::renderer::rasterize_glyph(glyph_id, font_storage)
+                .map(|(_, bitmap)| bitmap)
+                .unwrap_or_default()
         });
 
         Some(CpuCacheItem {
@@ -277,6 +367,7 @@ impl CpuCache {
 mod tests {
     use super::*;
     use crate::glyph_id::GlyphId;
+    use crate::test_utils::make_id;
     use std::num::NonZeroUsize;
 
     // Helper to create a dummy GlyphId
@@ -358,6 +449,17 @@ mod tests {
 
         assert_eq!(atlas.lru_keys[1], Some(key3));
         assert_eq!(atlas.lru_keys[0], Some(key2));
+
+        assert_eq!(atlas.evictions, 1);
+        assert_eq!(
+            atlas.stats(),
+            PageStats {
+                used: 2,
+                capacity: 2,
+                used_area: 2,
+                area: 2,
+            }
+        );
     }
 
     #[test]
@@ -415,6 +517,34 @@ mod tests {
         assert!(!atlas.lru_map.contains_key(&key1));
     }
 
+    #[test]
+    fn test_vec_atlas_remove_font() {
+        let capacity = NonZeroUsize::new(3).unwrap();
+        let block_size = NonZeroUsize::new(1).unwrap();
+        let mut atlas: VecAtlas<u8> = VecAtlas::new(capacity, block_size);
+
+        let other_font = make_id(1);
+        let key1 = make_key(1);
+        let key2 = GlyphId::new(other_font, 2, 12.0);
+        let key3 = make_key(3);
+        atlas.get_or_insert_with(&key1, || vec![1]);
+        atlas.get_or_insert_with(&key2, || vec![2]);
+        atlas.get_or_insert_with(&key3, || vec![3]);
+
+        atlas.remove_font(key1.font_id());
+        assert_eq!(atlas.lru_map.len(), 1);
+        assert_eq!(atlas.lru_head, atlas.lru_map.get(&key2).copied());
+        assert_eq!(atlas.lru_tail, atlas.lru_head);
+        assert_eq!(atlas.lru_empties.len(), 2);
+
+        // Freed blocks are reused before anything is evicted.
+        atlas.get_or_insert_with(&key1, || vec![4]);
+        atlas.get_or_insert_with(&key3, || vec![5]);
+        assert_eq!(atlas.lru_map.len(), 3);
+        assert_eq!(atlas.get_or_insert_with(&key2, || vec![9]), &[2]);
+        assert_eq!(atlas.get_or_insert_with(&key1, || vec![9]), &[4]);
+    }
+
     #[test]
     fn test_glyph_cache_selection() {
         let config = vec![
@@ -8,7 +8,7 @@ use crate::text::{GlyphPosition, TextLayout};
 pub fn render_layout_to_bitmap<T>(
     layout: &TextLayout<T>,
     image_size: [usize; 2],
-    font_storage: &mut FontStorage,
+    font_storage: &FontStorage,
 ) -> Bitmap {
     let width = image_size[0];
     let height = image_size[1];
@@ -37,19 +37,13 @@ pub fn render_layout_to_bitmap<T>(
 fn render_glyph_into_bitmap<T>(
     bitmap: &mut Bitmap,
     glyph_pos: &GlyphPosition<T>,
-    font_storage: &mut FontStorage,
+    font_storage: &FontStorage,
 ) {
-    let glyph_id = glyph_pos.glyph_id;
-    let font_id = glyph_id.font_id();
-    let glyph_index = glyph_id.glyph_index();
-    let font_size = glyph_id.font_size();
-
-    let Some(font) = font_storage.font(font_id) else {
+    let Some((metrics, coverage)) = super::rasterize_glyph(&glyph_pos.glyph_id, font_storage)
+    else {
         return;
     };
 
-    let (metrics, coverage) = font.rasterize_indexed(glyph_index, font_size);
-
     if metrics.width == 0 || metrics.height == 0 {
         return;
     }
@@ -57,8 +51,7 @@ fn render_glyph_into_bitmap<T>(
     let glyph_width = metrics.width as u32;
     let glyph_height = metrics.height as u32;
 
-    let origin_x = glyph_pos.x;
-    let origin_y = glyph_pos.y;
+    let [origin_x, origin_y] = glyph_pos.raster_origin();
 
     for row in 0..glyph_height {
         let y = origin_y + row as f32;
@@ -0,0 +1,1304 @@
+//! Data-driven text effects.
+//!
+//! A [`TextEffect`] describes how a run of text is styled at render time (outlines,
+//! shadows, gradients and simple animations). Effects are plain data, so with the
+//! `serde` feature an [`EffectLibrary`] can be loaded from a dialogue or theme file
+//! and looked up by tag name (e.g. `"shake"` for `<shake>`).
+//!
+//! [`apply_effects`] interprets the effects of every glyph and returns a new layout
+//! with `[r, g, b, a]` user data that any renderer can draw as-is. [`apply_link_hover`]
+//! does the same for hyperlink hover styling, and [`apply_auto_contrast`] keeps
+//! labels legible over arbitrary background imagery (see `apply_auto_contrast_texture`
+//! for backgrounds rendered with wgpu). [`text_decorations`] turns the
+//! decoration flags of a [`TextStyle`] layout into rectangles to draw, and
+//! [`run_backgrounds`] does the same for per-run background colors.
+
+use std::collections::HashMap;
+
+use euclid::{Box2D, Point2D};
+
+use crate::{
+    font_storage::{FaceMetrics, FontStorage},
+    renderer::glyph_metrics,
+    text::{GlyphPosition, TextLayout, TextLayoutLine, TextStyle},
+};
+
+/// A single effect applied to a run of text.
+///
+/// Colors are `[r, g, b, a]` in the same format as the layout's color user data
+/// (premultiplied for [`super::WgpuRenderer`]).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum TextEffect {
+    /// Draws the glyph in `color` offset by `width` pixels in eight directions behind the text.
+    Outline { width: f32, color: [f32; 4] },
+    /// Draws the glyph in `color` offset by `offset` pixels behind the text.
+    Shadow { offset: [f32; 2], color: [f32; 4] },
+    /// Replaces the color with a vertical gradient from the top to the bottom of the line.
+    Gradient { top: [f32; 4], bottom: [f32; 4] },
+    /// Moves glyphs along a sine wave travelling through the text.
+    Wave {
+        /// Vertical displacement in pixels.
+        amplitude: f32,
+        /// Length of one period in characters.
+        wavelength: f32,
+        /// Periods per second.
+        speed: f32,
+    },
+    /// Jitters glyphs to random offsets.
+    Shake {
+        /// Maximum displacement in pixels.
+        amplitude: f32,
+        /// New offsets per second.
+        speed: f32,
+    },
+    /// Cycles the hue through the text, keeping the original alpha.
+    Rainbow {
+        /// Number of characters per full hue cycle.
+        wavelength: f32,
+        /// Hue cycles per second.
+        speed: f32,
+    },
+}
+
+/// Named effect lists, typically loaded from a data file.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct EffectLibrary {
+    /// Effects by tag name.
+    pub effects: HashMap<String, Vec<TextEffect>>,
+}
+
+impl EffectLibrary {
+    /// Creates an empty library.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `effects` under `name`, replacing any previous entry.
+    pub fn insert(&mut self, name: impl Into<String>, effects: Vec<TextEffect>) {
+        self.effects.insert(name.into(), effects);
+    }
+
+    /// Returns the effects registered under `name`, or an empty slice for unknown names.
+    pub fn get(&self, name: &str) -> &[TextEffect] {
+        self.effects.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+/// Interprets the effects of every glyph in `layout` at `time` seconds.
+///
+/// `effects_for` returns the effects of a glyph's run from its user data. Outline and
+/// shadow copies are emitted before the glyphs of each line so they are drawn behind
+/// the text. Only positions and colors change, so cached glyph bitmaps are reused.
+pub fn apply_effects<'e, T, I>(
+    layout: &TextLayout<T>,
+    time: f32,
+    mut effects_for: impl FnMut(&T) -> I,
+) -> TextLayout<[f32; 4]>
+where
+    T: Into<[f32; 4]> + Copy,
+    I: IntoIterator<Item = &'e TextEffect>,
+{
+    let mut shadows = Vec::new();
+    let mut outlines = Vec::new();
+    let mut lines = Vec::with_capacity(layout.lines.len());
+
+    for line in &layout.lines {
+        let mut behind = Vec::new();
+        let mut front = Vec::new();
+        let mut glyph_outlines = Vec::new();
+
+        for glyph in &line.glyphs {
+            let mut color: [f32; 4] = glyph.user_data.into();
+            let mut offset = [0.0f32; 2];
+            let phase = glyph.char_index as f32;
+
+            for effect in effects_for(&glyph.user_data) {
+                match *effect {
+                    TextEffect::Outline { width, color } => glyph_outlines.push((width, color)),
+                    TextEffect::Shadow {
+                        offset: shadow_offset,
+                        color,
+                    } => shadows.push((shadow_offset, color)),
+                    TextEffect::Gradient { top, bottom } => {
+                        let height = line.bottom - line.top;
+                        let t = if height > 0.0 {
+                            ((glyph.y - line.top) / height).clamp(0.0, 1.0)
+                        } else {
+                            0.0
+                        };
+                        color = std::array::from_fn(|i| top[i] + (bottom[i] - top[i]) * t);
+                    }
+                    TextEffect::Wave {
+                        amplitude,
+                        wavelength,
+                        speed,
+                    } => {
+                        let cycles = phase / wavelength.max(f32::EPSILON) + time * speed;
+                        offset[1] += amplitude * (cycles * std::f32::consts::TAU).sin();
+                    }
+                    TextEffect::Shake { amplitude, speed } => {
+                        let step = (time * speed).floor() as u32;
+                        offset[0] += amplitude * jitter(glyph.char_index as u32, step, 0);
+                        offset[1] += amplitude * jitter(glyph.char_index as u32, step, 1);
+                    }
+                    TextEffect::Rainbow { wavelength, speed } => {
+                        let hue = phase / wavelength.max(f32::EPSILON) + time * speed;
+                        let [r, g, b] = hue_to_rgb(hue);
+                        let alpha = color[3];
+                        color = [r * alpha, g * alpha, b * alpha, alpha];
+                    }
+                }
+            }
+
+            let moved = |dx: f32, dy: f32, user_data: [f32; 4]| GlyphPosition {
+                glyph_id: glyph.glyph_id,
+                x: glyph.x + offset[0] + dx,
+                y: glyph.y + offset[1] + dy,
+                char_index: glyph.char_index,
+                user_data,
+            };
+
+            for ([dx, dy], shadow_color) in shadows.drain(..) {
+                behind.push(moved(dx, dy, shadow_color));
+            }
+            for (width, outline_color) in glyph_outlines.drain(..) {
+                for (dx, dy) in OUTLINE_DIRECTIONS {
+                    outlines.push(moved(dx * width, dy * width, outline_color));
+                }
+            }
+            front.push(moved(0.0, 0.0, color));
+        }
+
+        behind.append(&mut outlines);
+        behind.append(&mut front);
+        lines.push(TextLayoutLine {
+            line_height: line.line_height,
+            line_width: line.line_width,
+            top: line.top,
+            bottom: line.bottom,
+            glyphs: behind,
+        });
+    }
+
+    TextLayout {
+        config: layout.config.clone(),
+        total_height: layout.total_height,
+        total_width: layout.total_width,
+        lines,
+        paragraphs: layout.paragraphs.clone(),
+        words: layout.words.clone(),
+        clip_rect: layout.clip_rect,
+    }
+}
+
+/// A filled rectangle, such as an underline or strikethrough from
+/// [`text_decorations`] or a run background from [`run_backgrounds`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilledRect {
+    /// Screen-space rectangle to fill.
+    pub rect: Box2D<f32, euclid::UnknownUnit>,
+    /// Fill color, `[r, g, b, a]`.
+    pub color: [f32; 4],
+}
+
+/// The underline of a hovered link, see [`apply_link_hover`].
+pub type Underline = FilledRect;
+
+/// Styles the hovered hyperlink of a layout without laying it out again.
+///
+/// `link_of` returns the link id of a glyph's run, or `None` for plain text. Glyphs
+/// whose link equals `hovered` are drawn in `hover_color`; all other glyphs keep the
+/// color of their user data. The returned underlines cover the hovered link, one
+/// segment per line it spans, and should be drawn after the text.
+pub fn apply_link_hover<T, K>(
+    layout: &TextLayout<T>,
+    font_storage: &FontStorage,
+    hovered: Option<&K>,
+    hover_color: [f32; 4],
+    mut link_of: impl FnMut(&T) -> Option<K>,
+) -> (TextLayout<[f32; 4]>, Vec<Underline>)
+where
+    T: Into<[f32; 4]> + Copy,
+    K: PartialEq,
+{
+    let mut underlines = Vec::new();
+    let mut lines = Vec::with_capacity(layout.lines.len());
+    let mut faces = HashMap::new();
+
+    for line in &layout.lines {
+        let mut segment: Option<Box2D<f32, euclid::UnknownUnit>> = None;
+        let mut glyphs = Vec::with_capacity(line.glyphs.len());
+
+        for glyph in &line.glyphs {
+            let is_hovered = hovered.is_some() && link_of(&glyph.user_data).as_ref() == hovered;
+            let metrics = if is_hovered {
+                glyph_metrics(&glyph.glyph_id, font_storage)
+            } else {
+                None
+            };
+
+            match metrics {
+                Some(metrics) => {
+                    let face = face_metrics(&mut faces, glyph, font_storage);
+                    let rect =
+                        decoration_rect(glyph, &metrics, face.as_ref(), DecorationLine::Under);
+                    segment = Some(segment.map_or(rect, |segment| segment.union(&rect)));
+                }
+                None => {
+                    if let Some(rect) = segment.take() {
+                        underlines.push(Underline {
+                            rect,
+                            color: hover_color,
+                        });
+                    }
+                }
+            }
+
+            glyphs.push(GlyphPosition {
+                glyph_id: glyph.glyph_id,
+                x: glyph.x,
+                y: glyph.y,
+                char_index: glyph.char_index,
+                user_data: if is_hovered {
+                    hover_color
+                } else {
+                    glyph.user_data.into()
+                },
+            });
+        }
+
+        if let Some(rect) = segment {
+            underlines.push(Underline {
+                rect,
+                color: hover_color,
+            });
+        }
+
+        lines.push(TextLayoutLine {
+            line_height: line.line_height,
+            line_width: line.line_width,
+            top: line.top,
+            bottom: line.bottom,
+            glyphs,
+        });
+    }
+
+    let layout = TextLayout {
+        config: layout.config.clone(),
+        total_height: layout.total_height,
+        total_width: layout.total_width,
+        lines,
+        paragraphs: layout.paragraphs.clone(),
+        words: layout.words.clone(),
+        clip_rect: layout.clip_rect,
+    };
+    (layout, underlines)
+}
+
+/// Returns the underline and strikethrough rectangles of a [`TextStyle`] layout.
+///
+/// Lines are placed and sized by the underline and strikeout metrics of each
+/// glyph's face, falling back to proportions of the font size for faces without
+/// them. Adjacent glyphs with the same color and line extents share one segment
+/// per line. Lines take the color of their run and should be drawn after the
+/// text, e.g. as [`super::LayoutRects::in_front`] of the wgpu renderer.
+pub fn text_decorations(
+    layout: &TextLayout<TextStyle>,
+    font_storage: &FontStorage,
+) -> Vec<FilledRect> {
+    let mut decorations = Vec::new();
+    let mut faces = HashMap::new();
+
+    for line in &layout.lines {
+        let mut underline = None;
+        let mut strikethrough = None;
+
+        for glyph in &line.glyphs {
+            let style = &glyph.user_data;
+            let (metrics, face) = if style.decoration.is_none() {
+                (None, None)
+            } else {
+                (
+                    glyph_metrics(&glyph.glyph_id, font_storage),
+                    face_metrics(&mut faces, glyph, font_storage),
+                )
+            };
+            let rect = |enabled: bool, kind: DecorationLine| {
+                metrics
+                    .as_ref()
+                    .filter(|_| enabled)
+                    .map(|metrics| decoration_rect(glyph, metrics, face.as_ref(), kind))
+            };
+
+            extend_segment(
+                &mut underline,
+                rect(style.decoration.underline, DecorationLine::Under),
+                style.color,
+                &mut decorations,
+            );
+            extend_segment(
+                &mut strikethrough,
+                rect(style.decoration.strikethrough, DecorationLine::Through),
+                style.color,
+                &mut decorations,
+            );
+        }
+
+        decorations.extend(underline);
+        decorations.extend(strikethrough);
+    }
+
+    decorations
+}
+
+/// Returns the background rectangles of a layout's runs.
+///
+/// `background_of` maps a glyph's user data to the color filled behind it, e.g.
+/// `|style: &TextStyle| style.background` or a selection or search-match color.
+/// Each glyph covers its advance and the full height of its line, and adjacent
+/// glyphs with the same color share one rectangle per line. Rectangles should be
+/// drawn before the text.
+pub fn run_backgrounds<T>(
+    layout: &TextLayout<T>,
+    font_storage: &FontStorage,
+    mut background_of: impl FnMut(&T) -> Option<[f32; 4]>,
+) -> Vec<FilledRect> {
+    let mut backgrounds = Vec::new();
+
+    for line in &layout.lines {
+        let mut segment = None;
+
+        for glyph in &line.glyphs {
+            let color = background_of(&glyph.user_data);
+            let rect = color
+                .and_then(|_| glyph_metrics(&glyph.glyph_id, font_storage))
+                .map(|metrics| {
+                    let origin_x = glyph.x - metrics.xmin as f32;
+                    Box2D::new(
+                        Point2D::new(origin_x, line.top),
+                        Point2D::new(origin_x + metrics.advance_width, line.bottom),
+                    )
+                });
+            extend_segment(
+                &mut segment,
+                rect,
+                color.unwrap_or_default(),
+                &mut backgrounds,
+            );
+        }
+
+        backgrounds.extend(segment);
+    }
+
+    backgrounds
+}
+
+/// Grows the open `segment` by `rect`, or closes it into `out` when `rect` does not continue it.
+fn extend_segment(
+    segment: &mut Option<FilledRect>,
+    rect: Option<Box2D<f32, euclid::UnknownUnit>>,
+    color: [f32; 4],
+    out: &mut Vec<FilledRect>,
+) {
+    match (segment.as_mut(), rect) {
+        (Some(open), Some(rect))
+            if open.color == color
+                && open.rect.min.y == rect.min.y
+                && open.rect.max.y == rect.max.y =>
+        {
+            open.rect = open.rect.union(&rect);
+        }
+        (_, Some(rect)) => out.extend(segment.replace(FilledRect { rect, color })),
+        (_, None) => out.extend(segment.take()),
+    }
+}
+
+/// Background pixels sampled by [`apply_auto_contrast`].
+#[derive(Clone, Copy, Debug)]
+pub struct BackgroundImage<'a> {
+    /// Row-major RGBA8 pixels in sRGB.
+    pub pixels: &'a [u8],
+    /// Width in pixels.
+    pub width: usize,
+    /// Height in pixels.
+    pub height: usize,
+    /// Position of the layout origin in the image, in pixels.
+    pub origin: [f32; 2],
+}
+
+impl BackgroundImage<'_> {
+    /// Samples per axis when averaging the area behind a label.
+    const SAMPLES: [usize; 2] = [16, 8];
+
+    /// Average relative luminance of the image under `rect` (layout coordinates).
+    fn average_luminance(&self, rect: Box2D<f32, euclid::UnknownUnit>) -> Option<f32> {
+        if self.width == 0 || self.height == 0 || self.pixels.len() < self.width * self.height * 4 {
+            return None;
+        }
+
+        let [columns, rows] = Self::SAMPLES;
+        let mut sum = 0.0;
+        let mut count = 0;
+        for row in 0..rows {
+            for column in 0..columns {
+                let x = rect.min.x + rect.width() * (column as f32 + 0.5) / columns as f32;
+                let y = rect.min.y + rect.height() * (row as f32 + 0.5) / rows as f32;
+                let (x, y) = (x + self.origin[0], y + self.origin[1]);
+                if x < 0.0 || y < 0.0 {
+                    continue;
+                }
+                let (x, y) = (x as usize, y as usize);
+                if x >= self.width || y >= self.height {
+                    continue;
+                }
+
+                let i = (y * self.width + x) * 4;
+                let [r, g, b] = [0, 1, 2].map(|c| self.pixels[i + c] as f32 / 255.0);
+                sum += relative_luminance([r, g, b, 1.0]);
+                count += 1;
+            }
+        }
+
+        (count > 0).then(|| sum / count as f32)
+    }
+}
+
+/// A background texture sampled by [`apply_auto_contrast_texture`].
+#[cfg(all(feature = "wgpu", not(target_arch = "wasm32")))]
+#[derive(Clone, Copy, Debug)]
+pub struct BackgroundTexture<'a> {
+    /// The texture the label is drawn over, e.g. a rendered map.
+    pub texture: &'a wgpu::Texture,
+    /// Position of the layout origin in the texture, in texels.
+    pub origin: [f32; 2],
+}
+
+#[cfg(all(feature = "wgpu", not(target_arch = "wasm32")))]
+impl BackgroundTexture<'_> {
+    /// Average relative luminance of the texture under `rect` (layout coordinates).
+    fn average_luminance(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rect: Box2D<f32, euclid::UnknownUnit>,
+    ) -> Option<f32> {
+        let texture = self.texture;
+        let bgra = match texture.format() {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            _ => return None,
+        };
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) || texture.sample_count() != 1 {
+            return None;
+        }
+
+        // The texels under the label, clamped to the texture.
+        let texel = |value: f32, origin: f32, size: u32| (value + origin).clamp(0.0, size as f32);
+        let min_x = texel(rect.min.x, self.origin[0], texture.width()).floor() as u32;
+        let min_y = texel(rect.min.y, self.origin[1], texture.height()).floor() as u32;
+        let max_x = texel(rect.max.x, self.origin[0], texture.width()).ceil() as u32;
+        let max_y = texel(rect.max.y, self.origin[1], texture.height()).ceil() as u32;
+        if min_x >= max_x || min_y >= max_y {
+            return None;
+        }
+        let (width, height) = (max_x - min_x, max_y - min_y);
+
+        let bytes_per_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Suzuri auto contrast readback"),
+            size: u64::from(bytes_per_row * height),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Suzuri auto contrast readback"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: min_x,
+                    y: min_y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit([encoder.finish()]);
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::PollType::wait_indefinitely()).ok()?;
+        receiver.recv().ok()?.ok()?;
+
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks_exact(bytes_per_row as usize)
+        {
+            for texel in row[..(width * 4) as usize].chunks_exact(4) {
+                match bgra {
+                    true => pixels.extend([texel[2], texel[1], texel[0], texel[3]]),
+                    false => pixels.extend_from_slice(texel),
+                }
+            }
+        }
+
+        BackgroundImage {
+            pixels: &pixels,
+            width: width as usize,
+            height: height as usize,
+            origin: [self.origin[0] - min_x as f32, self.origin[1] - min_y as f32],
+        }
+        .average_luminance(rect)
+    }
+}
+
+/// Options of [`apply_auto_contrast`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct AutoContrast {
+    /// Color used on dark backgrounds, `[r, g, b, a]`.
+    pub light: [f32; 4],
+    /// Color used on light backgrounds, `[r, g, b, a]`.
+    pub dark: [f32; 4],
+    /// WCAG contrast ratio (1 to 21) below which a glyph is adjusted.
+    pub min_contrast: f32,
+    /// `None` replaces the color of low-contrast glyphs with `light` or `dark`.
+    /// `Some(width)` keeps their color and draws a halo of `width` pixels behind them instead.
+    pub halo_width: Option<f32>,
+}
+
+impl Default for AutoContrast {
+    fn default() -> Self {
+        Self {
+            light: [1.0, 1.0, 1.0, 1.0],
+            dark: [0.0, 0.0, 0.0, 1.0],
+            min_contrast: 4.5,
+            halo_width: None,
+        }
+    }
+}
+
+impl AutoContrast {
+    /// Returns whichever of `light` and `dark` contrasts more with `luminance`.
+    fn contrasting(&self, luminance: f32) -> [f32; 4] {
+        let light = contrast_ratio(relative_luminance(self.light), luminance);
+        let dark = contrast_ratio(relative_luminance(self.dark), luminance);
+        if light >= dark { self.light } else { self.dark }
+    }
+}
+
+/// Adjusts text colors against the background under the layout.
+///
+/// The background is averaged over the bounds of all glyphs, treating the layout
+/// as a single label. Glyphs whose color has a lower contrast ratio than
+/// [`AutoContrast::min_contrast`] are switched to the light or dark color, or get
+/// a halo in that color. Colors are assumed to be opaque; if the layout lies
+/// outside the image, colors are left unchanged.
+pub fn apply_auto_contrast<T>(
+    layout: &TextLayout<T>,
+    font_storage: &FontStorage,
+    background: &BackgroundImage<'_>,
+    options: &AutoContrast,
+) -> TextLayout<[f32; 4]>
+where
+    T: Into<[f32; 4]> + Copy,
+{
+    let background_luminance =
+        label_bounds(layout, font_storage).and_then(|bounds| background.average_luminance(bounds));
+    adjust_contrast(layout, background_luminance, options)
+}
+
+/// Adjusts text colors against a background texture, like [`apply_auto_contrast`].
+///
+/// Only the texels under the label are copied back from the GPU, and this blocks
+/// until the copy is done, so call it when the background changes rather than
+/// every frame. The texture needs [`wgpu::TextureUsages::COPY_SRC`] and one of the
+/// RGBA8 or BGRA8 formats; otherwise colors are left unchanged.
+#[cfg(all(feature = "wgpu", not(target_arch = "wasm32")))]
+pub fn apply_auto_contrast_texture<T>(
+    layout: &TextLayout<T>,
+    font_storage: &FontStorage,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    background: &BackgroundTexture<'_>,
+    options: &AutoContrast,
+) -> TextLayout<[f32; 4]>
+where
+    T: Into<[f32; 4]> + Copy,
+{
+    let background_luminance = label_bounds(layout, font_storage)
+        .and_then(|bounds| background.average_luminance(device, queue, bounds));
+    adjust_contrast(layout, background_luminance, options)
+}
+
+/// Returns the bounds of all glyph bitmaps of a layout.
+fn label_bounds<T>(
+    layout: &TextLayout<T>,
+    font_storage: &FontStorage,
+) -> Option<Box2D<f32, euclid::UnknownUnit>> {
+    let mut bounds: Option<Box2D<f32, euclid::UnknownUnit>> = None;
+    for glyph in layout.lines.iter().flat_map(|line| &line.glyphs) {
+        if let Some(metrics) = glyph_metrics(&glyph.glyph_id, font_storage) {
+            let rect = Box2D::new(
+                Point2D::new(glyph.x, glyph.y),
+                Point2D::new(
+                    glyph.x + metrics.width as f32,
+                    glyph.y + metrics.height as f32,
+                ),
+            );
+            bounds = Some(bounds.map_or(rect, |bounds| bounds.union(&rect)));
+        }
+    }
+    bounds
+}
+
+/// Applies [`AutoContrast`] against a background of the given luminance, or
+/// only converts the colors when it is unknown.
+fn adjust_contrast<T>(
+    layout: &TextLayout<T>,
+    background_luminance: Option<f32>,
+    options: &AutoContrast,
+) -> TextLayout<[f32; 4]>
+where
+    T: Into<[f32; 4]> + Copy,
+{
+    let mut lines = Vec::with_capacity(layout.lines.len());
+    for line in &layout.lines {
+        let mut halos = Vec::new();
+        let mut glyphs = Vec::with_capacity(line.glyphs.len());
+
+        for glyph in &line.glyphs {
+            let mut color: [f32; 4] = glyph.user_data.into();
+            let moved = |dx: f32, dy: f32, user_data: [f32; 4]| GlyphPosition {
+                glyph_id: glyph.glyph_id,
+                x: glyph.x + dx,
+                y: glyph.y + dy,
+                char_index: glyph.char_index,
+                user_data,
+            };
+
+            if let Some(background_luminance) = background_luminance {
+                let luminance = relative_luminance(color);
+                if contrast_ratio(luminance, background_luminance) < options.min_contrast {
+                    match options.halo_width {
+                        None => color = options.contrasting(background_luminance),
+                        Some(width) => {
+                            let halo_color = options.contrasting(luminance);
+                            for (dx, dy) in OUTLINE_DIRECTIONS {
+                                halos.push(moved(dx * width, dy * width, halo_color));
+                            }
+                        }
+                    }
+                }
+            }
+            glyphs.push(moved(0.0, 0.0, color));
+        }
+
+        halos.append(&mut glyphs);
+        lines.push(TextLayoutLine {
+            line_height: line.line_height,
+            line_width: line.line_width,
+            top: line.top,
+            bottom: line.bottom,
+            glyphs: halos,
+        });
+    }
+
+    TextLayout {
+        config: layout.config.clone(),
+        total_height: layout.total_height,
+        total_width: layout.total_width,
+        lines,
+        paragraphs: layout.paragraphs.clone(),
+        words: layout.words.clone(),
+        clip_rect: layout.clip_rect,
+    }
+}
+
+/// WCAG relative luminance of an sRGB color.
+fn relative_luminance([r, g, b, _]: [f32; 4]) -> f32 {
+    let linear = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+/// WCAG contrast ratio between two relative luminances.
+fn contrast_ratio(a: f32, b: f32) -> f32 {
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Vertical placement of a decoration line.
+#[derive(Clone, Copy)]
+enum DecorationLine {
+    /// Just below the baseline.
+    Under,
+    /// Through the middle of lowercase letters.
+    Through,
+}
+
+/// Returns the metrics of the face of `glyph`, reading each face once per call
+/// of the effect.
+fn face_metrics(
+    faces: &mut HashMap<fontdb::ID, Option<FaceMetrics>>,
+    glyph: &GlyphPosition<impl Sized>,
+    font_storage: &FontStorage,
+) -> Option<FaceMetrics> {
+    let font_id = glyph.glyph_id.font_id();
+    *faces
+        .entry(font_id)
+        .or_insert_with(|| font_storage.face_metrics(font_id))
+}
+
+/// Covers the advance of `glyph` with a decoration line.
+///
+/// The line is placed by the underline or strikeout metrics of `face`, or in
+/// proportion to the font size when it has none.
+fn decoration_rect(
+    glyph: &GlyphPosition<impl Sized>,
+    metrics: &fontdue::Metrics,
+    face: Option<&FaceMetrics>,
+    kind: DecorationLine,
+) -> Box2D<f32, euclid::UnknownUnit> {
+    let font_size = glyph.glyph_id.font_size();
+    let baseline = glyph.y + (metrics.ymin + metrics.height as i32) as f32;
+    let origin_x = glyph.x - metrics.xmin as f32;
+    let face_line = face.and_then(|face| {
+        let (position, thickness) = match kind {
+            DecorationLine::Under => (face.underline_position, face.underline_thickness),
+            DecorationLine::Through => (face.strikeout_position, face.strikeout_thickness),
+        };
+        let scale = face.scale(font_size);
+        position
+            .zip(thickness.filter(|&thickness| thickness > 0))
+            .map(|(position, thickness)| {
+                (
+                    baseline - f32::from(position) * scale,
+                    (f32::from(thickness) * scale).max(1.0),
+                )
+            })
+    });
+    let (top, thickness) = face_line.unwrap_or_else(|| {
+        let thickness = (font_size / 14.0).max(1.0);
+        let top = match kind {
+            DecorationLine::Under => baseline + thickness,
+            DecorationLine::Through => baseline - font_size * 0.3 - thickness / 2.0,
+        };
+        (top, thickness)
+    });
+    Box2D::new(
+        Point2D::new(origin_x, top),
+        Point2D::new(origin_x + metrics.advance_width, top + thickness),
+    )
+}
+
+/// Unit offsets of the eight outline copies.
+const OUTLINE_DIRECTIONS: [(f32, f32); 8] = [
+    (-1.0, 0.0),
+    (1.0, 0.0),
+    (0.0, -1.0),
+    (0.0, 1.0),
+    (
+        -std::f32::consts::FRAC_1_SQRT_2,
+        -std::f32::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        std::f32::consts::FRAC_1_SQRT_2,
+        -std::f32::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        -std::f32::consts::FRAC_1_SQRT_2,
+        std::f32::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        std::f32::consts::FRAC_1_SQRT_2,
+        std::f32::consts::FRAC_1_SQRT_2,
+    ),
+];
+
+/// Deterministic pseudo-random value in `-1.0..=1.0` for a character, time step and axis.
+fn jitter(char_index: u32, step: u32, axis: u32) -> f32 {
+    let mut x = char_index
+        .wrapping_mul(0x9E37_79B9)
+        .wrapping_add(step.wrapping_mul(0x85EB_CA6B))
+        .wrapping_add(axis.wrapping_mul(0xC2B2_AE35));
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7FEB_352D);
+    x ^= x >> 15;
+    (x as f32 / u32::MAX as f32) * 2.0 - 1.0
+}
+
+/// Converts a hue in cycles (wrapping) to a fully saturated RGB color.
+fn hue_to_rgb(hue: f32) -> [f32; 3] {
+    let h = hue.rem_euclid(1.0) * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    match h as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        FontStorage,
+        test_utils::{test_font_storage, text_element},
+        text::{TextData, TextLayoutConfig},
+    };
+
+    #[derive(Clone, Copy)]
+    struct Run {
+        color: [f32; 4],
+        effect: Option<&'static str>,
+    }
+
+    impl From<Run> for [f32; 4] {
+        fn from(run: Run) -> Self {
+            run.color
+        }
+    }
+
+    fn make_layout(runs: &[(&str, Run)]) -> TextLayout<Run> {
+        let (font_storage, font_id) = test_font_storage();
+        make_layout_with(&font_storage, font_id, runs)
+    }
+
+    fn make_layout_with(
+        font_storage: &FontStorage,
+        font_id: fontdb::ID,
+        runs: &[(&str, Run)],
+    ) -> TextLayout<Run> {
+        let mut data = TextData::new();
+        for (content, run) in runs {
+            data.append(text_element(font_id, 16.0, *content, *run));
+        }
+        data.layout(&TextLayoutConfig::default(), font_storage)
+    }
+
+    #[test]
+    fn test_outline_and_shadow_are_drawn_behind() {
+        let mut library = EffectLibrary::new();
+        library.insert(
+            "fancy",
+            vec![
+                TextEffect::Shadow {
+                    offset: [2.0, 2.0],
+                    color: [0.0, 0.0, 0.0, 1.0],
+                },
+                TextEffect::Outline {
+                    width: 1.0,
+                    color: [0.0, 0.0, 1.0, 1.0],
+                },
+            ],
+        );
+        let white = [1.0; 4];
+        let layout = make_layout(&[(
+            "ab",
+            Run {
+                color: white,
+                effect: Some("fancy"),
+            },
+        )]);
+        let glyph_count = layout.len_glyphs();
+
+        let result = apply_effects(&layout, 0.0, |run| library.get(run.effect.unwrap_or("")));
+        let glyphs = &result.lines[0].glyphs;
+
+        assert_eq!(glyphs.len(), glyph_count * 10);
+        assert!(
+            glyphs[..glyph_count]
+                .iter()
+                .all(|g| g.user_data == [0.0, 0.0, 0.0, 1.0])
+        );
+        assert!(
+            glyphs[glyph_count * 9..]
+                .iter()
+                .all(|g| g.user_data == white)
+        );
+        assert_eq!(glyphs[0].x, layout.lines[0].glyphs[0].x + 2.0);
+    }
+
+    #[test]
+    fn test_effects_only_apply_to_their_run() {
+        let library = EffectLibrary {
+            effects: HashMap::from([(
+                "wave".to_string(),
+                vec![TextEffect::Wave {
+                    amplitude: 4.0,
+                    wavelength: 8.0,
+                    speed: 1.0,
+                }],
+            )]),
+        };
+        let plain = Run {
+            color: [1.0; 4],
+            effect: None,
+        };
+        let waving = Run {
+            effect: Some("wave"),
+            ..plain
+        };
+        let layout = make_layout(&[("aa", plain), ("aa", waving)]);
+
+        let result = apply_effects(&layout, 0.0, |run| library.get(run.effect.unwrap_or("")));
+        let before = &layout.lines[0].glyphs;
+        let after = &result.lines[0].glyphs;
+
+        // The third character is a quarter wavelength in, at the crest of the wave.
+        assert_eq!(before.len(), after.len());
+        assert_eq!(after[0].y, before[0].y);
+        assert!((after[2].y - before[2].y - 4.0).abs() < 1e-3);
+        assert_eq!(after[2].x, before[2].x);
+    }
+
+    #[test]
+    fn test_rainbow_keeps_alpha() {
+        let effects = [TextEffect::Rainbow {
+            wavelength: 4.0,
+            speed: 0.0,
+        }];
+        let layout = make_layout(&[(
+            "abcd",
+            Run {
+                color: [0.5, 0.5, 0.5, 0.5],
+                effect: None,
+            },
+        )]);
+
+        let result = apply_effects(&layout, 0.0, |_| &effects);
+        for glyph in &result.lines[0].glyphs {
+            assert_eq!(glyph.user_data[3], 0.5);
+            assert!(glyph.user_data[..3].iter().all(|&c| c <= 0.5));
+        }
+        assert_ne!(
+            result.lines[0].glyphs[0].user_data,
+            result.lines[0].glyphs[1].user_data
+        );
+    }
+
+    #[test]
+    fn test_link_hover() {
+        let (font_storage, font_id) = test_font_storage();
+        let text = Run {
+            color: [1.0; 4],
+            effect: None,
+        };
+        let link = Run {
+            effect: Some("https://example.com"),
+            ..text
+        };
+        let layout = make_layout_with(
+            &font_storage,
+            font_id,
+            &[("see ", text), ("here", link), (" now", text)],
+        );
+        let blue = [0.0, 0.0, 1.0, 1.0];
+
+        let (idle, underlines) =
+            apply_link_hover(&layout, &font_storage, None, blue, |run| run.effect);
+        assert!(underlines.is_empty());
+        assert!(idle.lines[0].glyphs.iter().all(|g| g.user_data == [1.0; 4]));
+
+        let (hovered, underlines) = apply_link_hover(
+            &layout,
+            &font_storage,
+            Some(&"https://example.com"),
+            blue,
+            |run| run.effect,
+        );
+        assert_eq!(underlines.len(), 1);
+        let link_glyphs: Vec<_> = hovered.lines[0]
+            .glyphs
+            .iter()
+            .filter(|g| g.user_data == blue)
+            .collect();
+        assert_eq!(link_glyphs.len(), 4);
+        let rect = underlines[0].rect;
+        assert!(rect.min.x <= link_glyphs[0].x);
+        assert!(rect.max.x >= link_glyphs[3].x);
+        assert!(rect.min.y > link_glyphs[0].y);
+        assert!(rect.height() > 0.0);
+    }
+
+    #[test]
+    fn test_text_decorations() {
+        let (font_storage, font_id) = test_font_storage();
+        let red = [1.0, 0.0, 0.0, 1.0];
+
+        let data = TextData::builder(font_id, 16.0, TextStyle::default())
+            .push_str("plain ")
+            .user_data(TextStyle::default().underline())
+            .push_str("under")
+            .user_data(TextStyle::new(red).underline().strikethrough())
+            .push_str("both")
+            .build();
+        let layout = data.layout_styled(&TextLayoutConfig::default(), &font_storage);
+        let decorations = text_decorations(&layout, &font_storage);
+
+        // The color change splits the underline; the strikethrough covers "both" only.
+        assert_eq!(decorations.len(), 3);
+        let glyphs = &layout.lines[0].glyphs;
+        let under = decorations.iter().find(|d| d.color == [1.0; 4]).unwrap();
+        let red_lines: Vec<_> = decorations.iter().filter(|d| d.color == red).collect();
+        assert_eq!(red_lines.len(), 2);
+        assert!(under.rect.min.x <= glyphs[6].x && under.rect.max.x > glyphs[10].x);
+        assert!(under.rect.max.x <= red_lines[0].rect.min.x + 0.5);
+
+        let (underline, strikethrough) = if red_lines[0].rect.min.y > red_lines[1].rect.min.y {
+            (red_lines[0], red_lines[1])
+        } else {
+            (red_lines[1], red_lines[0])
+        };
+        assert_eq!(underline.rect.min.y, under.rect.min.y);
+        assert_eq!(underline.rect.min.x, strikethrough.rect.min.x);
+        assert!(strikethrough.rect.max.y < underline.rect.min.y - 3.0);
+    }
+
+    #[test]
+    fn test_run_backgrounds() {
+        let (font_storage, font_id) = test_font_storage();
+        let yellow = [1.0, 1.0, 0.0, 1.0];
+        let gray = [0.2, 0.2, 0.2, 1.0];
+
+        let data = TextData::builder(font_id, 16.0, TextStyle::default())
+            .push_str("plain ")
+            .user_data(TextStyle::default().background(yellow))
+            .push_str("match")
+            .user_data(TextStyle::default().background(gray))
+            .push_str(" rest")
+            .build();
+        let layout = data.layout_styled(&TextLayoutConfig::default(), &font_storage);
+        let backgrounds = run_backgrounds(&layout, &font_storage, |style| style.background);
+
+        // One rectangle per colored run, spanning the whole line height.
+        assert_eq!(backgrounds.len(), 2);
+        let line = &layout.lines[0];
+        let glyphs = &line.glyphs;
+        let (highlight, rest) = (&backgrounds[0], &backgrounds[1]);
+        assert_eq!(highlight.color, yellow);
+        assert_eq!(rest.color, gray);
+        assert_eq!(highlight.rect.min.y, line.top);
+        assert_eq!(highlight.rect.max.y, line.bottom);
+        assert!(highlight.rect.min.x <= glyphs[6].x && highlight.rect.max.x > glyphs[10].x);
+        assert!((highlight.rect.max.x - rest.rect.min.x).abs() < 0.5);
+        assert!(run_backgrounds(&layout, &font_storage, |_| None).is_empty());
+    }
+
+    #[test]
+    fn test_auto_contrast() {
+        let (font_storage, font_id) = test_font_storage();
+        let white = Run {
+            color: [1.0; 4],
+            effect: None,
+        };
+        let black = Run {
+            color: [0.0, 0.0, 0.0, 1.0],
+            ..white
+        };
+        let layout = make_layout_with(&font_storage, font_id, &[("ab", white), ("cd", black)]);
+
+        let pixels = vec![240u8; 256 * 64 * 4];
+        let background = BackgroundImage {
+            pixels: &pixels,
+            width: 256,
+            height: 64,
+            origin: [0.0, 0.0],
+        };
+
+        // Light background: white text turns dark, black text is kept.
+        let options = AutoContrast::default();
+        let result = apply_auto_contrast(&layout, &font_storage, &background, &options);
+        let colors: Vec<_> = result.lines[0].glyphs.iter().map(|g| g.user_data).collect();
+        assert_eq!(colors, vec![options.dark; 4]);
+
+        // Halo mode keeps the color and draws a dark halo behind the white glyphs.
+        let options = AutoContrast {
+            halo_width: Some(1.0),
+            ..Default::default()
+        };
+        let result = apply_auto_contrast(&layout, &font_storage, &background, &options);
+        let glyphs = &result.lines[0].glyphs;
+        assert_eq!(glyphs.len(), 2 * 8 + 4);
+        assert!(glyphs[..16].iter().all(|g| g.user_data == options.dark));
+        assert_eq!(glyphs[16].user_data, [1.0; 4]);
+
+        // Outside of the image nothing changes.
+        let background = BackgroundImage {
+            origin: [1000.0, 1000.0],
+            ..background
+        };
+        let result = apply_auto_contrast(&layout, &font_storage, &background, &options);
+        assert_eq!(result.lines[0].glyphs.len(), 4);
+    }
+
+    #[cfg(all(feature = "wgpu", not(target_arch = "wasm32")))]
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_auto_contrast_texture() {
+        let instance = wgpu::Instance::default();
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+                .expect("no wgpu adapter available");
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&Default::default())).unwrap();
+        let (font_storage, font_id) = test_font_storage();
+        let black = Run {
+            color: [0.0, 0.0, 0.0, 1.0],
+            effect: None,
+        };
+        let layout = make_layout_with(&font_storage, font_id, &[("abcd", black)]);
+
+        // Renders a background of a single color.
+        let render = |format, usage, color| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 256,
+                    height: 64,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | usage,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&Default::default());
+            let mut encoder = device.create_command_encoder(&Default::default());
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            queue.submit([encoder.finish()]);
+            texture
+        };
+        let colors = |texture: &wgpu::Texture, origin| {
+            let background = BackgroundTexture { texture, origin };
+            let options = AutoContrast::default();
+            let result = apply_auto_contrast_texture(
+                &layout,
+                &font_storage,
+                &device,
+                &queue,
+                &background,
+                &options,
+            );
+            let colors: Vec<_> = result.lines[0].glyphs.iter().map(|g| g.user_data).collect();
+            colors
+        };
+        let red = wgpu::Color::RED;
+        let blue = wgpu::Color::BLUE;
+        let light = AutoContrast::default().light;
+
+        // Black text is legible on red but not on blue, so a swapped channel
+        // order would pick the wrong color.
+        let copyable = wgpu::TextureUsages::COPY_SRC;
+        for format in [
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureFormat::Bgra8Unorm,
+        ] {
+            let texture = render(format, copyable, red);
+            assert_eq!(colors(&texture, [0.0, 0.0]), [black.color; 4]);
+            let texture = render(format, copyable, blue);
+            assert_eq!(colors(&texture, [0.0, 0.0]), [light; 4]);
+            // Outside of the texture nothing changes.
+            assert_eq!(colors(&texture, [1000.0, 0.0]), [black.color; 4]);
+        }
+
+        // Textures that cannot be copied are not sampled.
+        let texture = render(
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureUsages::empty(),
+            blue,
+        );
+        assert_eq!(colors(&texture, [0.0, 0.0]), [black.color; 4]);
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        let white = relative_luminance([1.0; 4]);
+        let black = relative_luminance([0.0, 0.0, 0.0, 1.0]);
+        assert!((contrast_ratio(white, black) - 21.0).abs() < 1e-3);
+        assert_eq!(contrast_ratio(white, white), 1.0);
+    }
+
+    #[test]
+    fn test_jitter_range() {
+        for char_index in 0..64 {
+            for step in 0..8 {
+                let value = jitter(char_index, step, 0);
+                assert!((-1.0..=1.0).contains(&value));
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_library() {
+        let json = r#"{
+            "shake": [{ "type": "shake", "amplitude": 1.5, "speed": 20.0 }],
+            "spooky": [
+                { "type": "shadow", "offset": [1.0, 1.0], "color": [0.0, 0.0, 0.0, 0.5] },
+                { "type": "gradient", "top": [1.0, 1.0, 1.0, 1.0], "bottom": [0.2, 0.0, 0.4, 1.0] }
+            ]
+        }"#;
+
+        let library: EffectLibrary = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            library.get("shake"),
+            &[TextEffect::Shake {
+                amplitude: 1.5,
+                speed: 20.0
+            }]
+        );
+        assert_eq!(library.get("spooky").len(), 2);
+        assert!(library.get("rainbow").is_empty());
+    }
+}
@@ -1,14 +1,23 @@
-use euclid::{Box2D, Point2D};
+use euclid::{Box2D, Point2D, Transform2D, Vector2D};
 
 use crate::{
     font_storage::FontStorage,
+    glyph_id::GlyphId,
+    renderer::CacheStats,
     text::{GlyphPosition, TextLayout},
 };
 
+mod damage;
+pub use damage::{DamageEffects, LayoutDamage};
 mod glyph_cache;
-pub use glyph_cache::{CacheAtlas, GpuCache, GpuCacheConfig, GpuCacheItem};
+pub use glyph_cache::{AtlasPriority, CacheAtlas, GpuCache, GpuCacheConfig, GpuCacheItem};
+mod host_atlas;
+pub use host_atlas::{HostAtlas, HostAtlasRegion, HostAtlasRenderer};
+mod packing;
+pub use packing::AtlasPacking;
 
 /// Describes an update to a texture in the atlas.
+#[derive(Clone, Debug)]
 pub struct AtlasUpdate {
     /// Index of the texture in the atlas array to update.
     pub texture_index: usize,
@@ -24,19 +33,54 @@ pub struct AtlasUpdate {
     pub pixels: Vec<u8>,
 }
 
+/// Describes a copy of a region of the atlas to another place, e.g. a glyph moved
+/// by [`GpuRenderer::compact_cache`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AtlasMove {
+    /// Index of the texture in the atlas array to copy from.
+    pub src_texture_index: usize,
+    /// X coordinate of the region to copy.
+    pub src_x: usize,
+    /// Y coordinate of the region to copy.
+    pub src_y: usize,
+    /// Index of the texture in the atlas array to copy to.
+    pub dst_texture_index: usize,
+    /// X coordinate of the destination.
+    pub dst_x: usize,
+    /// Y coordinate of the destination.
+    pub dst_y: usize,
+    /// Width of the region.
+    pub width: usize,
+    /// Height of the region.
+    pub height: usize,
+}
+
 /// Describes a glyph instance to be drawn.
+#[derive(Clone, Debug)]
 pub struct GlyphInstance<T> {
     /// Index of the texture in the atlas array.
     pub texture_index: usize,
     /// UV coordinates in the texture atlas.
     pub uv_rect: Box2D<f32, euclid::UnknownUnit>,
-    /// Screen coordinates where the glyph should be drawn.
+    /// Screen coordinates where the glyph should be drawn, before `transform`.
     pub screen_rect: Box2D<f32, euclid::UnknownUnit>,
+    /// Transform mapping `screen_rect` onto the target; see [`GpuRenderer::set_transform`].
+    pub transform: Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+    /// Depth of the glyph; see [`GpuRenderer::set_depth`].
+    pub depth: f32,
+    /// Clip rectangle of the layout the glyph belongs to; see [`TextLayout::clip_rect`].
+    pub clip_rect: Option<Box2D<f32, euclid::UnknownUnit>>,
+    /// Opacity the color of the glyph is multiplied by; see [`LayoutPlacement::opacity`].
+    pub opacity: f32,
+    /// Gradient the glyph is filled with, in the coordinates of `screen_rect`;
+    /// see [`LayoutPlacement::gradient`].
+    pub gradient: Option<LinearGradient>,
     /// User data associated with this glyph.
     pub user_data: T,
 }
 
 /// Describes a standalone large glyph to be drawn separately.
+#[derive(Clone, Debug)]
 pub struct StandaloneGlyph<T> {
     /// Width of the glyph image.
     pub width: usize,
@@ -44,12 +88,147 @@ pub struct StandaloneGlyph<T> {
     pub height: usize,
     /// Bitmap data of the glyph.
     pub pixels: Vec<u8>,
-    /// Screen coordinates where the glyph should be drawn.
+    /// Screen coordinates where the glyph should be drawn, before `transform`.
     pub screen_rect: Box2D<f32, euclid::UnknownUnit>,
+    /// Transform mapping `screen_rect` onto the target; see [`GpuRenderer::set_transform`].
+    pub transform: Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+    /// Depth of the glyph; see [`GpuRenderer::set_depth`].
+    pub depth: f32,
+    /// Clip rectangle of the layout the glyph belongs to; see [`TextLayout::clip_rect`].
+    pub clip_rect: Option<Box2D<f32, euclid::UnknownUnit>>,
+    /// Opacity the color of the glyph is multiplied by; see [`LayoutPlacement::opacity`].
+    pub opacity: f32,
+    /// Gradient the glyph is filled with, in the coordinates of `screen_rect`;
+    /// see [`LayoutPlacement::gradient`].
+    pub gradient: Option<LinearGradient>,
     /// User data associated with this glyph.
     pub user_data: T,
 }
 
+/// Where and how opaque a layout is drawn, so one layout can be drawn at
+/// several places or faded without laying it out again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LayoutPlacement {
+    /// Offset added to the glyph positions, in layout coordinates before the
+    /// transform. The clip of the layout moves along.
+    ///
+    /// Glyphs placed at subpixel positions keep their rasterized variant, so
+    /// this should be whole pixels for such layouts.
+    pub offset: [f32; 2],
+    /// Opacity the colors of the glyphs are multiplied by, from `0.0` to `1.0`.
+    pub opacity: f32,
+    /// Gradient the glyphs are filled with instead of their flat color, in
+    /// layout coordinates before the offset. It moves along with the layout.
+    pub gradient: Option<LinearGradient>,
+    /// Depth of the glyphs, instead of that of the renderer; see
+    /// [`GpuRenderer::set_depth`]. Lets the layouts of one render interleave
+    /// with other depth-tested geometry at different depths.
+    pub depth: Option<f32>,
+}
+
+/// A linear gradient from the color of each glyph to a secondary color, so a
+/// headline can fade across its lines rather than per glyph.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinearGradient {
+    /// Point where the gradient starts, with the color of the glyph.
+    pub start: [f32; 2],
+    /// Point where the gradient ends, with `color`.
+    pub end: [f32; 2],
+    /// Secondary color, premultiplied like the colors of the glyphs.
+    pub color: [f32; 4],
+}
+
+impl LinearGradient {
+    /// Creates a gradient from `start` to `end`, ending in `color`.
+    pub fn new(start: [f32; 2], end: [f32; 2], color: [f32; 4]) -> Self {
+        Self { start, end, color }
+    }
+
+    /// Creates a gradient from the glyph color at `top` to `color` at `bottom`.
+    pub fn vertical(top: f32, bottom: f32, color: [f32; 4]) -> Self {
+        Self::new([0.0, top], [0.0, bottom], color)
+    }
+
+    /// Returns the gradient moved by `offset`.
+    pub fn translate(self, offset: [f32; 2]) -> Self {
+        Self {
+            start: [self.start[0] + offset[0], self.start[1] + offset[1]],
+            end: [self.end[0] + offset[0], self.end[1] + offset[1]],
+            ..self
+        }
+    }
+}
+
+impl Default for LayoutPlacement {
+    fn default() -> Self {
+        Self {
+            offset: [0.0, 0.0],
+            opacity: 1.0,
+            gradient: None,
+            depth: None,
+        }
+    }
+}
+
+impl From<[f32; 2]> for LayoutPlacement {
+    fn from(offset: [f32; 2]) -> Self {
+        Self::at(offset)
+    }
+}
+
+impl LayoutPlacement {
+    /// Places a fully opaque layout at `offset`.
+    pub fn at(offset: [f32; 2]) -> Self {
+        Self {
+            offset,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the opacity the layout is drawn with.
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    /// Sets the gradient the glyphs are filled with.
+    pub fn gradient(mut self, gradient: LinearGradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    /// Sets the depth the glyphs are drawn at.
+    pub fn depth(mut self, depth: f32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+}
+
+/// Output of [`GpuRenderer::render_owned`]: everything needed to draw a layout,
+/// owned so it can be handed to another thread.
+///
+/// Apply all `updates`, then draw the `batches` and the `standalone` glyphs in
+/// any order; the whole layout is drawn from one batch of the cache, so no
+/// update overwrites a glyph an instance refers to.
+#[derive(Clone, Debug)]
+pub struct RenderOutput<T> {
+    /// Atlas uploads, in the order they were produced.
+    pub updates: Vec<AtlasUpdate>,
+    /// Instances in layout order, split into runs that sample one atlas page.
+    pub batches: Vec<GlyphBatch<T>>,
+    /// Glyphs that did not fit into the atlas.
+    pub standalone: Vec<StandaloneGlyph<T>>,
+}
+
+/// Consecutive instances of a [`RenderOutput`] that sample one atlas page.
+#[derive(Clone, Debug)]
+pub struct GlyphBatch<T> {
+    /// Index of the texture in the atlas array all instances sample.
+    pub texture_index: usize,
+    /// Instances in layout order.
+    pub instances: Vec<GlyphInstance<T>>,
+}
+
 /// Generic GPU renderer that manages an atlas and produces draw commands.
 ///
 /// ## Overview
@@ -77,7 +256,7 @@ pub struct StandaloneGlyph<T> {
 /// ```rust,no_run
 /// use suzuri::{
 ///     FontSystem, fontdb,
-///     renderer::{GpuCacheConfig, AtlasUpdate, GlyphInstance, StandaloneGlyph},
+///     renderer::{AtlasPacking, AtlasPriority, GpuCacheConfig, AtlasUpdate, GlyphInstance, StandaloneGlyph},
 ///     text::{TextData, TextElement, TextLayoutConfig}
 /// };
 /// use std::num::NonZeroUsize;
@@ -91,6 +270,10 @@ pub struct StandaloneGlyph<T> {
 ///         texture_size: NonZeroUsize::new(1024).unwrap(),
 ///         tile_size: NonZeroUsize::new(32).unwrap(), // one side length
 ///         tiles_per_axis: NonZeroUsize::new(32).unwrap(),
+///         priority: AtlasPriority::Normal,
+///         padding: 1,
+///         extrude_edges: false,
+///         packing: AtlasPacking::Grid,
 ///     },
 /// ];
 /// font_system.gpu_init(&cache_configs);
@@ -107,7 +290,8 @@ pub struct StandaloneGlyph<T> {
 ///         // Upload 'pixels' to texture 'texture_index' at (x, y)
 ///     },
 ///     |instances: &[GlyphInstance<u32>]| {
-///         // Add instances to a vertex buffer or draw them directly
+///         // All instances sample page 'instances[0].texture_index':
+///         // bind it, then add them to a vertex buffer or draw them directly
 ///     },
 ///     |standalone: &StandaloneGlyph<u32>| {
 ///         // Handle large glyphs separately (e.g. create a temporary texture)
@@ -116,6 +300,24 @@ pub struct StandaloneGlyph<T> {
 /// ```
 pub struct GpuRenderer {
     cache: GpuCache,
+    /// Atlas updates of the current batch. Kept across frames to reuse its allocation.
+    update_atlas_list: Vec<AtlasUpdate>,
+    /// Lookup counters; evictions and occupancy come from the cache.
+    hits: u64,
+    misses: u64,
+    standalone: u64,
+    /// Applied to the screen rectangles of all instances.
+    transform: Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+    /// Depth of all instances.
+    depth: f32,
+    /// Whether glyphs are cached as LCD subpixel masks.
+    lcd: bool,
+    /// Glyphs drawn by the layouts passed to [`Self::track_damage`].
+    damage: damage::DamageTracker,
+    /// Glyphs pinned by [`Self::pin_batch`], released once their token is dropped.
+    pins: Vec<(std::sync::Weak<()>, Vec<GlyphId>)>,
+    /// Whether frames are ended by [`Self::end_frame`] instead of render calls.
+    explicit_frames: bool,
 }
 
 impl GpuRenderer {
@@ -123,21 +325,248 @@ impl GpuRenderer {
     pub fn new(configs: &[GpuCacheConfig]) -> Self {
         Self {
             cache: GpuCache::new(configs),
+            update_atlas_list: Vec::new(),
+            hits: 0,
+            misses: 0,
+            standalone: 0,
+            transform: Transform2D::identity(),
+            depth: 0.0,
+            lcd: false,
+            damage: damage::DamageTracker::default(),
+            pins: Vec::new(),
+            explicit_frames: false,
         }
     }
 
+    /// Sets the transform from layout coordinates to the target, e.g. to pan,
+    /// zoom or rotate rendered text without laying it out again.
+    ///
+    /// Instances keep their untransformed `screen_rect` and carry the transform
+    /// for the backend to apply to the corners of their quads. Clip rectangles
+    /// stay in target coordinates. Takes effect on the next render; to transform
+    /// a single render, use [`Self::render_transformed`].
+    pub fn set_transform(
+        &mut self,
+        transform: Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+    ) {
+        self.transform = transform;
+    }
+
+    /// Returns the transform from layout coordinates to the target.
+    pub fn transform(&self) -> Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit> {
+        self.transform
+    }
+
+    /// Sets the depth given to the instances of the following renders, so text
+    /// can be depth-tested against other geometry. Defaults to `0.0`.
+    ///
+    /// The value is passed through unchanged; the wgpu renderer writes it as the
+    /// normalized device depth, between `0.0` and `1.0`. Layouts placed with a
+    /// [`LayoutPlacement::depth`] use theirs instead.
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth;
+    }
+
+    /// Returns the depth given to instances.
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+
+    /// Sets whether glyphs are cached as LCD subpixel masks for ClearType-style
+    /// antialiasing. Defaults to `false`.
+    ///
+    /// Each pixel of a mask is three horizontally adjacent texels, the coverage
+    /// of its red, green and blue subpixel (see [`GlyphId::with_lcd_mask`]), so
+    /// the `uv_rect` of an instance spans three times as many texels as its
+    /// `screen_rect` spans pixels. The backend samples the three texels of each
+    /// pixel and blends them per channel, e.g. with dual-source blending.
+    /// Standalone glyphs stay grayscale.
+    pub fn set_lcd_antialiasing(&mut self, enabled: bool) {
+        self.lcd = enabled;
+    }
+
+    /// Returns whether glyphs are cached as LCD subpixel masks.
+    pub fn lcd_antialiasing(&self) -> bool {
+        self.lcd
+    }
+
+    /// Returns the cache's counters and the occupancy of each atlas page.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            standalone: self.standalone,
+            ..self.cache.stats()
+        }
+    }
+
+    /// Resets the counters of [`Self::stats`], e.g. after a warm-up phase.
+    pub fn reset_stats(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+        self.standalone = 0;
+        self.cache.reset_stats();
+    }
+
     /// Clears the cache.
     pub fn clear_cache(&mut self) {
         self.cache.clear();
+        self.pins.clear();
+    }
+
+    /// Pins the glyphs of the last render in the atlas until every clone of
+    /// `token` is dropped, see [`GpuCache::pin_batch`].
+    ///
+    /// Lets a backend record the draws of a single-batch render after later
+    /// renders, which neither evict nor move the pinned glyphs.
+    #[cfg(feature = "wgpu")]
+    pub(crate) fn pin_batch(&mut self, token: std::sync::Weak<()>) {
+        let glyphs = self.cache.pin_batch();
+        if !glyphs.is_empty() {
+            self.pins.push((token, glyphs));
+        }
+    }
+
+    /// Releases the pins whose token was dropped.
+    fn release_pins(&mut self) {
+        let cache = &mut self.cache;
+        self.pins.retain(|(token, glyphs)| {
+            let alive = token.strong_count() > 0;
+            if !alive {
+                cache.unpin(glyphs);
+            }
+            alive
+        });
+    }
+
+    /// Lets the cache add atlas pages, up to `bytes` in total, when it overflows.
+    ///
+    /// Instead of evicting glyphs when every page a glyph fits is full, a page
+    /// configured like the smallest of them is added and `update_atlas` receives
+    /// updates for its texture index, which follows the existing ones. Backends
+    /// must then create the texture before uploading; [`Self::page_count`] and
+    /// [`Self::page_config`] describe the pages. See [`GpuCache::set_growth_budget`].
+    pub fn set_growth_budget(&mut self, bytes: usize) {
+        self.cache.set_growth_budget(bytes);
+    }
+
+    /// Lets the cache add overflow pages, up to `bytes` in total, for glyphs larger
+    /// than every tile, so they are cached instead of drawn standalone each frame.
+    ///
+    /// Backends create the textures of overflow pages like those of grown pages,
+    /// see [`Self::set_growth_budget`] and [`GpuCache::set_overflow_budget`].
+    pub fn set_overflow_budget(&mut self, bytes: usize) {
+        self.cache.set_overflow_budget(bytes);
+    }
+
+    /// Returns the number of atlas pages, including pages added by growth.
+    pub fn page_count(&self) -> usize {
+        self.cache.page_count()
+    }
+
+    /// Returns the configuration of the atlas page with texture index `index`.
+    pub fn page_config(&self, index: usize) -> Option<GpuCacheConfig> {
+        self.cache.page_config(index)
+    }
+
+    /// Drops the cached glyphs of font `font_id`, e.g. after its file changed.
+    ///
+    /// Their atlas slots are reused for other glyphs.
+    pub fn forget_font(&mut self, font_id: fontdb::ID) {
+        self.cache.remove_font(font_id);
+    }
+
+    /// Ends the current frame, e.g. once per displayed frame after its last render.
+    ///
+    /// When a new glyph needs room, the glyph drawn the most frames ago is evicted.
+    /// Until this is first called, each render call counts as a frame, so with
+    /// several render calls per displayed frame the glyphs of earlier calls look
+    /// older than they are. Once it is called, frames only end here.
+    pub fn end_frame(&mut self) {
+        self.explicit_frames = true;
+        self.cache.new_frame();
+    }
+
+    /// Repacks up to `limit` cached glyphs into fewer pages and returns how many
+    /// moved.
+    ///
+    /// After heavy eviction, glyphs that spilled into pages with larger tiles are
+    /// moved back to free tiles of the pages sized for them, so the larger tiles
+    /// are available again and fewer glyphs fall back to [`StandaloneGlyph`]s.
+    /// The moved glyphs, with their padding, are passed to `copy_atlas` in a
+    /// single call; the copies must be applied in order, since a later glyph may
+    /// take the place an earlier one left.
+    ///
+    /// Call this between renders, e.g. when the application is idle. A small
+    /// `limit` spreads the work over several calls. See [`GpuCache::compact`].
+    pub fn compact_cache(
+        &mut self,
+        limit: usize,
+        mut copy_atlas: impl FnMut(&[AtlasMove]),
+    ) -> usize {
+        self.release_pins();
+        let moved = self.cache.compact(limit);
+        if moved.is_empty() {
+            return 0;
+        }
+        let moves: Vec<AtlasMove> = moved
+            .iter()
+            .map(|(_, from, to)| {
+                let padding = self
+                    .cache
+                    .page_config(to.texture_index)
+                    .map_or(0, |config| config.padding);
+                AtlasMove {
+                    src_texture_index: from.texture_index,
+                    src_x: from.glyph_box.min.x - padding,
+                    src_y: from.glyph_box.min.y - padding,
+                    dst_texture_index: to.texture_index,
+                    dst_x: to.glyph_box.min.x - padding,
+                    dst_y: to.glyph_box.min.y - padding,
+                    width: from.glyph_box.width() + 2 * padding,
+                    height: from.glyph_box.height() + 2 * padding,
+                }
+            })
+            .collect();
+        copy_atlas(&moves);
+        moves.len()
+    }
+
+    /// Returns updates writing every cached glyph to its place, rasterized
+    /// again, e.g. to fill a copy of the atlas kept by the backend.
+    pub fn cached_updates(&self, font_storage: &FontStorage) -> Vec<AtlasUpdate> {
+        self.rasterized_updates(&self.cache.items(), font_storage)
+    }
+
+    /// Rasterizes `items` into updates of their places.
+    fn rasterized_updates(
+        &self,
+        items: &[(GlyphId, GpuCacheItem)],
+        font_storage: &FontStorage,
+    ) -> Vec<AtlasUpdate> {
+        items
+            .iter()
+            .map(|(glyph_id, item)| {
+                let width = item.glyph_box.width();
+                let height = item.glyph_box.height();
+                let pixels = super::rasterize_glyph(glyph_id, font_storage)
+                    .map_or_else(|| vec![0; width * height], |(_, pixels)| pixels);
+                padded_update(&self.cache, item.texture_index, item.glyph_box, pixels)
+            })
+            .collect()
     }
 
     /// Renders the layout, producing atlas updates and draw calls via callbacks.
     ///
+    /// Instances are drawn in layout order, so overlapping glyphs stack as laid
+    /// out. Each call of `draw_instances` holds a run of consecutive instances
+    /// that sample one atlas page, so a backend binds a page once per run.
+    ///
     /// This method is for infallible callbacks. Use `try_render` for fallible callbacks.
     pub fn render<T: Clone + Copy>(
         &mut self,
         layout: &TextLayout<T>,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
         mut update_atlas: impl FnMut(&[AtlasUpdate]),
         mut draw_instances: impl FnMut(&[GlyphInstance<T>]),
         mut draw_standalone: impl FnMut(&StandaloneGlyph<T>),
@@ -160,32 +589,359 @@ impl GpuRenderer {
         );
     }
 
+    /// Renders the layout like [`Self::render`] with `transform` instead of the
+    /// transform of the renderer, which is left unchanged.
+    pub fn render_transformed<T: Clone + Copy>(
+        &mut self,
+        layout: &TextLayout<T>,
+        transform: Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+        font_storage: &FontStorage,
+        update_atlas: impl FnMut(&[AtlasUpdate]),
+        draw_instances: impl FnMut(&[GlyphInstance<T>]),
+        draw_standalone: impl FnMut(&StandaloneGlyph<T>),
+    ) {
+        let previous = std::mem::replace(&mut self.transform, transform);
+        self.render(
+            layout,
+            font_storage,
+            update_atlas,
+            draw_instances,
+            draw_standalone,
+        );
+        self.transform = previous;
+    }
+
+    /// Renders the layout like [`Self::try_render`] with `transform` instead of
+    /// the transform of the renderer, which is left unchanged.
+    pub fn try_render_transformed<T: Clone + Copy, E>(
+        &mut self,
+        layout: &TextLayout<T>,
+        transform: Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+        font_storage: &FontStorage,
+        update_atlas: &mut impl FnMut(&[AtlasUpdate]) -> Result<(), E>,
+        draw_instances: &mut impl FnMut(&[GlyphInstance<T>]) -> Result<(), E>,
+        draw_standalone: &mut impl FnMut(&StandaloneGlyph<T>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let previous = std::mem::replace(&mut self.transform, transform);
+        let result = self.try_render(
+            layout,
+            font_storage,
+            update_atlas,
+            draw_instances,
+            draw_standalone,
+        );
+        self.transform = previous;
+        result
+    }
+
     /// Renders the layout, producing atlas updates and draw calls via callbacks.
     ///
-    /// This method allows callbacks to return errors, which will be propagated.
+    /// Instances are split into runs as described for [`Self::render`]. This
+    /// method allows callbacks to return errors, which will be propagated.
     pub fn try_render<T: Clone + Copy, E>(
         &mut self,
         layout: &TextLayout<T>,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
         update_atlas: &mut impl FnMut(&[AtlasUpdate]) -> Result<(), E>,
         draw_instances: &mut impl FnMut(&[GlyphInstance<T>]) -> Result<(), E>,
         draw_standalone: &mut impl FnMut(&StandaloneGlyph<T>) -> Result<(), E>,
     ) -> Result<(), E> {
-        let mut update_atlas_list: Vec<AtlasUpdate> = Vec::new();
-        let mut instance_list: Vec<GlyphInstance<T>> = Vec::new();
+        let instance_list: std::cell::RefCell<Vec<GlyphInstance<T>>> = Default::default();
+
+        self.try_render_streaming(
+            layout,
+            font_storage,
+            false,
+            update_atlas,
+            &mut |instance, _| instance_list.borrow_mut().push(instance),
+            &mut || {
+                let mut instance_list = instance_list.borrow_mut();
+                draw_page_runs(&instance_list, draw_instances)?;
+                instance_list.clear();
+                Ok(())
+            },
+            draw_standalone,
+        )?;
+        draw_page_runs(&instance_list.into_inner(), draw_instances)
+    }
+
+    /// Compares the glyphs `layout` draws with those it drew when last tracked
+    /// under `layout_id`, and remembers them for the next call.
+    ///
+    /// Enables partial redraws of mostly static text such as terminals and
+    /// editors: instead of clearing and drawing the whole layout each frame,
+    /// clear and redraw only [`LayoutDamage::bounds`]. Glyphs are compared by
+    /// their place on the target, including the transform and clip, and their
+    /// user data. The first call for an id reports every glyph as added.
+    ///
+    /// Only the glyph bitmaps are covered; use [`Self::track_damage_with`] when
+    /// the backend draws shadows, rectangles or filtered glyphs.
+    pub fn track_damage<T: PartialEq + Copy + Send + 'static>(
+        &mut self,
+        layout_id: u64,
+        layout: &TextLayout<T>,
+        font_storage: &FontStorage,
+    ) -> LayoutDamage {
+        self.track_damage_with(layout_id, layout, DamageEffects::default(), font_storage)
+    }
+
+    /// Like [`Self::track_damage`], with the damage of each glyph and rectangle
+    /// of `effects` covering what the backend draws around it.
+    pub fn track_damage_with<T: PartialEq + Copy + Send + 'static>(
+        &mut self,
+        layout_id: u64,
+        layout: &TextLayout<T>,
+        effects: DamageEffects<'_>,
+        font_storage: &FontStorage,
+    ) -> LayoutDamage {
+        let transform = self.transform;
+        // The target pixels drawing `rect` touches, including its shadow.
+        let cover = |rect: Box2D<f32, euclid::UnknownUnit>| {
+            let rect = match effects.shadow {
+                Some(([dx, dy], blur_radius)) => {
+                    let blur_radius = blur_radius.max(0.0);
+                    rect.union(
+                        &rect
+                            .inflate(blur_radius, blur_radius)
+                            .translate(Vector2D::new(dx, dy)),
+                    )
+                }
+                None => rect,
+            };
+            let rect = transform.outer_transformed_box(&rect).round_out();
+            layout
+                .clip_rect
+                .is_none_or(|clip_rect| clip_rect.intersects(&rect))
+                .then_some(rect)
+        };
+
+        let mut glyphs = Vec::with_capacity(layout.len_glyphs());
+        for glyph in layout.lines.iter().flat_map(|line| &line.glyphs) {
+            let Some(metrics) = super::glyph_metrics(&glyph.glyph_id, font_storage) else {
+                continue;
+            };
+            if metrics.width == 0 || metrics.height == 0 {
+                continue;
+            }
+            let [x, y] = glyph.raster_origin();
+            let bitmap = Box2D::new(
+                Point2D::new(x, y),
+                Point2D::new(x + metrics.width as f32, y + metrics.height as f32),
+            );
+            let Some(rect) = cover(bitmap.inflate(effects.bleed, effects.bleed)) else {
+                continue;
+            };
+            glyphs.push(damage::DrawnGlyph {
+                glyph_id: glyph.glyph_id,
+                rect,
+                user_data: glyph.user_data,
+            });
+        }
+        let rects = effects
+            .rects
+            .iter()
+            .filter_map(|fill| {
+                Some(damage::DrawnRect {
+                    rect: cover(fill.rect)?,
+                    key: (fill.rect, fill.color),
+                })
+            })
+            .collect();
+        self.damage.track(layout_id, glyphs, rects)
+    }
+
+    /// Forgets the glyphs remembered for `layout_id` by [`Self::track_damage`],
+    /// e.g. when the layout is closed.
+    pub fn forget_layout(&mut self, layout_id: u64) {
+        self.damage.forget(layout_id);
+    }
+
+    /// Renders the layout into owned atlas updates and instances batched per page,
+    /// instead of handing them to callbacks.
+    ///
+    /// Suits engines that record commands on another thread. Like the wgpu
+    /// renderer's `prepare`, glyphs of the layout never evict each other, so
+    /// glyphs that do not fit into the atlas at once are returned as standalone
+    /// glyphs.
+    pub fn render_owned<T: Copy>(
+        &mut self,
+        layout: &TextLayout<T>,
+        font_storage: &FontStorage,
+    ) -> RenderOutput<T> {
+        let mut updates = Vec::new();
+        let mut batches: Vec<GlyphBatch<T>> = Vec::new();
+        let mut standalone = Vec::new();
+
+        let _: Result<(), ()> = self.try_render_streaming(
+            layout,
+            font_storage,
+            true,
+            &mut |new_updates| {
+                updates.extend_from_slice(new_updates);
+                Ok(())
+            },
+            &mut |instance, _| match batches
+                .last_mut()
+                .filter(|batch| batch.texture_index == instance.texture_index)
+            {
+                Some(batch) => batch.instances.push(instance),
+                None => batches.push(GlyphBatch {
+                    texture_index: instance.texture_index,
+                    instances: vec![instance],
+                }),
+            },
+            &mut || Ok(()),
+            &mut |glyph| {
+                standalone.push(glyph.clone());
+                Ok(())
+            },
+        );
+
+        RenderOutput {
+            updates,
+            batches,
+            standalone,
+        }
+    }
+
+    /// Core of [`Self::try_render`] that hands out instances one at a time.
+    ///
+    /// `push_instance` queues an instance, along with the texture size of its page,
+    /// and `draw_instances` draws everything queued since its previous call. Because nothing is collected here, a
+    /// layout whose glyphs are all cached is rendered without heap allocations.
+    ///
+    /// The instances of the last batch are left queued, so the caller can queue
+    /// more, such as decoration lines, and draw them all with one more call of
+    /// `draw_instances`.
+    ///
+    /// With `single_batch`, glyphs of earlier draws are never evicted for later
+    /// ones, so the atlas stays valid for all draws of the layout; glyphs that
+    /// do not fit are drawn standalone. Used when every upload is recorded before
+    /// any draw.
+    pub(crate) fn try_render_streaming<T: Copy, E>(
+        &mut self,
+        layout: &TextLayout<T>,
+        font_storage: &FontStorage,
+        single_batch: bool,
+        update_atlas: &mut impl FnMut(&[AtlasUpdate]) -> Result<(), E>,
+        push_instance: &mut impl FnMut(GlyphInstance<T>, usize),
+        draw_instances: &mut impl FnMut() -> Result<(), E>,
+        draw_standalone: &mut impl FnMut(&StandaloneGlyph<T>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.try_render_streaming_all(
+            &[(LayoutPlacement::default(), layout)],
+            font_storage,
+            single_batch,
+            update_atlas,
+            push_instance,
+            draw_instances,
+            draw_standalone,
+        )
+    }
+
+    /// Like [`Self::try_render_streaming`], but renders several layouts, each
+    /// with its placement, as one frame of the cache.
+    ///
+    /// Instances of consecutive layouts share batches. Before a layout
+    /// whose clip differs from that of the previous one, the queued instances
+    /// are drawn, so every batch has a single clip.
+    pub(crate) fn try_render_streaming_all<T: Copy, E>(
+        &mut self,
+        layouts: &[(LayoutPlacement, &TextLayout<T>)],
+        font_storage: &FontStorage,
+        single_batch: bool,
+        update_atlas: &mut impl FnMut(&[AtlasUpdate]) -> Result<(), E>,
+        push_instance: &mut impl FnMut(GlyphInstance<T>, usize),
+        draw_instances: &mut impl FnMut() -> Result<(), E>,
+        draw_standalone: &mut impl FnMut(&StandaloneGlyph<T>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.release_pins();
+        if !self.explicit_frames {
+            self.cache.new_frame();
+        }
+        if single_batch {
+            self.cache.new_batch();
+        }
+        self.update_atlas_list.clear();
+        let mut has_pending_instances = false;
+        let mut previous_clip_rect = None;
+
+        for &(placement, layout) in layouts {
+            let clip_rect = layout.clip_rect.map(|clip_rect| {
+                let offset = Vector2D::from(placement.offset);
+                clip_rect.translate(self.transform.transform_vector(offset))
+            });
+            if clip_rect != previous_clip_rect && has_pending_instances {
+                draw_instances()?;
+                has_pending_instances = false;
+            }
+            previous_clip_rect = clip_rect;
+
+            self.stream_layout(
+                layout,
+                placement,
+                clip_rect,
+                font_storage,
+                single_batch,
+                &mut has_pending_instances,
+                update_atlas,
+                push_instance,
+                draw_instances,
+                draw_standalone,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Streams the glyphs of one layout of [`Self::try_render_streaming_all`],
+    /// and uploads the atlas updates they need before returning.
+    fn stream_layout<T: Copy, E>(
+        &mut self,
+        layout: &TextLayout<T>,
+        placement: LayoutPlacement,
+        clip_rect: Option<Box2D<f32, euclid::UnknownUnit>>,
+        font_storage: &FontStorage,
+        single_batch: bool,
+        has_pending_instances: &mut bool,
+        update_atlas: &mut impl FnMut(&[AtlasUpdate]) -> Result<(), E>,
+        push_instance: &mut impl FnMut(GlyphInstance<T>, usize),
+        draw_instances: &mut impl FnMut() -> Result<(), E>,
+        draw_standalone: &mut impl FnMut(&StandaloneGlyph<T>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut rasterized = rasterize_misses(&self.cache, layout, font_storage, self.lcd);
+        let update_atlas_list = &mut self.update_atlas_list;
+        let gradient = placement
+            .gradient
+            .map(|gradient| gradient.translate(placement.offset));
+        let depth = placement.depth.unwrap_or(self.depth);
 
         for line in &layout.lines {
             'glyph_loop: for glyph in &line.glyphs {
                 let GlyphPosition::<T> {
                     glyph_id,
-                    x,
-                    y,
                     user_data,
+                    ..
                 } = glyph;
-                let Some(font) = font_storage.font(glyph_id.font_id()) else {
+                let [x, y] = glyph.raster_origin();
+                let [x, y] = [x + placement.offset[0], y + placement.offset[1]];
+                // The mask of LCD glyphs is wider than the pixels they cover.
+                let cache_id = match self.lcd {
+                    true => glyph_id.with_lcd_mask(),
+                    false => *glyph_id,
+                };
+                let Some(metrics) = super::glyph_metrics(glyph_id, font_storage) else {
                     continue 'glyph_loop;
                 };
-                let metrics = font.metrics_indexed(glyph_id.glyph_index(), glyph_id.font_size());
+                let screen_rect = Box2D::new(
+                    Point2D::new(x, y),
+                    Point2D::new(x + metrics.width as f32, y + metrics.height as f32),
+                );
+                if let Some(clip_rect) = clip_rect
+                    && !clip_rect.intersects(&self.transform.outer_transformed_box(&screen_rect))
+                {
+                    continue 'glyph_loop;
+                }
 
                 let (
                     GpuCacheItem {
@@ -194,42 +950,49 @@ impl GpuRenderer {
                         glyph_box,
                     },
                     get_or_push_result,
-                ) = match self.cache.get_or_push_and_protect(glyph_id, font_storage) {
+                ) = match self.cache.get_or_push_and_protect(&cache_id, font_storage) {
                     Some(glyph_cache_item) => glyph_cache_item,
                     None => {
                         // upload all new glyph data to atlas
                         if !update_atlas_list.is_empty() {
-                            update_atlas(&update_atlas_list)?;
+                            update_atlas(update_atlas_list)?;
                             update_atlas_list.clear();
                         }
 
                         // draw call
-                        if !instance_list.is_empty() {
-                            draw_instances(&instance_list)?;
-                            instance_list.clear();
+                        if *has_pending_instances {
+                            draw_instances()?;
+                            *has_pending_instances = false;
                         }
 
-                        self.cache.new_batch();
-                        let Some(glyph_cache_item) =
-                            self.cache.get_or_push_and_protect(glyph_id, font_storage)
-                        else {
-                            let (metrics, glyph_data) = font
-                                .rasterize_indexed(glyph_id.glyph_index(), glyph_id.font_size());
+                        let retried = if single_batch {
+                            None
+                        } else {
+                            self.cache.new_batch();
+                            self.cache.get_or_push_and_protect(&cache_id, font_storage)
+                        };
+                        let Some(glyph_cache_item) = retried else {
+                            let Some((metrics, glyph_data)) = rasterized
+                                .remove(glyph_id)
+                                .or_else(|| super::rasterize_glyph(glyph_id, font_storage))
+                            else {
+                                continue 'glyph_loop;
+                            };
 
                             let isolate = StandaloneGlyph {
                                 width: metrics.width,
                                 height: metrics.height,
                                 pixels: glyph_data,
-                                screen_rect: Box2D::new(
-                                    Point2D::new(*x, *y),
-                                    Point2D::new(
-                                        *x + metrics.width as f32,
-                                        *y + metrics.height as f32,
-                                    ),
-                                ),
+                                screen_rect,
+                                transform: self.transform,
+                                depth,
+                                clip_rect,
+                                opacity: placement.opacity,
+                                gradient,
                                 user_data: *user_data,
                             };
 
+                            self.standalone += 1;
                             draw_standalone(&isolate)?;
 
                             continue 'glyph_loop;
@@ -250,44 +1013,650 @@ impl GpuRenderer {
                     ),
                 );
 
-                let screen_rect = Box2D::new(
-                    Point2D::new(*x, *y),
-                    Point2D::new(*x + metrics.width as f32, *y + metrics.height as f32),
-                );
-
                 let glyph_instance = GlyphInstance {
                     texture_index,
                     uv_rect,
                     screen_rect,
+                    transform: self.transform,
+                    depth,
+                    clip_rect,
+                    opacity: placement.opacity,
+                    gradient,
                     user_data: *user_data,
                 };
 
-                instance_list.push(glyph_instance);
+                push_instance(glyph_instance, texture_size);
+                *has_pending_instances = true;
 
+                match get_or_push_result {
+                    glyph_cache::GetOrPushResult::Hit => self.hits += 1,
+                    glyph_cache::GetOrPushResult::NeedToUpload => self.misses += 1,
+                }
                 if let glyph_cache::GetOrPushResult::NeedToUpload = get_or_push_result {
-                    let (_, glyph_data) =
-                        font.rasterize_indexed(glyph_id.glyph_index(), glyph_id.font_size());
+                    let (_, glyph_data) = rasterized
+                        .remove(&cache_id)
+                        .or_else(|| super::rasterize_glyph(&cache_id, font_storage))
+                        .unwrap_or_else(|| {
+                            (metrics, vec![0; glyph_box.width() * glyph_box.height()])
+                        });
 
-                    update_atlas_list.push(AtlasUpdate {
+                    update_atlas_list.push(padded_update(
+                        &self.cache,
                         texture_index,
-                        x: glyph_box.min.x,
-                        y: glyph_box.min.y,
-                        width: glyph_box.width(),
-                        height: glyph_box.height(),
-                        pixels: glyph_data,
-                    });
+                        glyph_box,
+                        glyph_data,
+                    ));
                 }
             }
         }
 
         if !update_atlas_list.is_empty() {
-            update_atlas(&update_atlas_list)?;
+            update_atlas(update_atlas_list)?;
+            update_atlas_list.clear();
         }
 
-        if !instance_list.is_empty() {
-            draw_instances(&instance_list)?;
+        Ok(())
+    }
+}
+
+/// Hands each run of consecutive `instances` that sample one page to one call
+/// of `draw_instances`, keeping the layout order.
+pub(crate) fn draw_page_runs<T, E>(
+    instances: &[GlyphInstance<T>],
+    draw_instances: &mut impl FnMut(&[GlyphInstance<T>]) -> Result<(), E>,
+) -> Result<(), E> {
+    for run in instances.chunk_by(|a, b| a.texture_index == b.texture_index) {
+        draw_instances(run)?;
+    }
+    Ok(())
+}
+
+/// Bitmaps of glyphs rasterized ahead of a render, see [`rasterize_misses`].
+type RasterizedGlyphs =
+    std::collections::HashMap<GlyphId, (fontdue::Metrics, Vec<u8>), fxhash::FxBuildHasher>;
+
+/// Number of uncached glyphs from which a render rasterizes them in parallel.
+#[cfg(feature = "rayon")]
+const PARALLEL_RASTERIZATION_MIN_MISSES: usize = 16;
+
+/// Rasterizes the glyphs of `layout` that are not cached in parallel, so the
+/// frame that first shows many new glyphs does not rasterize them one by one.
+///
+/// Does nothing, and allocates nothing, when fewer than
+/// [`PARALLEL_RASTERIZATION_MIN_MISSES`] glyphs are missing.
+#[cfg(feature = "rayon")]
+fn rasterize_misses<T>(
+    cache: &GpuCache,
+    layout: &TextLayout<T>,
+    font_storage: &FontStorage,
+    lcd: bool,
+) -> RasterizedGlyphs {
+    use rayon::prelude::*;
+
+    let missing = || {
+        layout
+            .lines
+            .iter()
+            .flat_map(|line| &line.glyphs)
+            .map(|glyph| match lcd {
+                true => glyph.glyph_id.with_lcd_mask(),
+                false => glyph.glyph_id,
+            })
+            .filter(|glyph_id| !cache.contains(glyph_id))
+    };
+    if missing().count() < PARALLEL_RASTERIZATION_MIN_MISSES {
+        return RasterizedGlyphs::default();
+    }
+
+    let unique: std::collections::HashSet<GlyphId, fxhash::FxBuildHasher> = missing().collect();
+    unique
+        .into_par_iter()
+        .filter_map(|glyph_id| {
+            super::rasterize_glyph(&glyph_id, font_storage).map(|glyph| (glyph_id, glyph))
+        })
+        .collect()
+}
+
+/// Rasterizes nothing ahead of a render without the `rayon` feature.
+#[cfg(not(feature = "rayon"))]
+fn rasterize_misses<T>(
+    _cache: &GpuCache,
+    _layout: &TextLayout<T>,
+    _font_storage: &FontStorage,
+    _lcd: bool,
+) -> RasterizedGlyphs {
+    RasterizedGlyphs::default()
+}
+
+/// Returns the upload of a glyph's `pixels` into `glyph_box` of a page, covering
+/// the page's padding around the glyph so no stale texels of earlier glyphs of
+/// the tile remain next to it. See [`GpuCacheConfig::padding`].
+fn padded_update(
+    cache: &GpuCache,
+    texture_index: usize,
+    glyph_box: Box2D<usize, euclid::UnknownUnit>,
+    pixels: Vec<u8>,
+) -> AtlasUpdate {
+    let (width, height) = (glyph_box.width(), glyph_box.height());
+    let (padding, extrude_edges) = cache
+        .page_config(texture_index)
+        .map_or((0, false), |config| (config.padding, config.extrude_edges));
+    if padding == 0 {
+        return AtlasUpdate {
+            texture_index,
+            x: glyph_box.min.x,
+            y: glyph_box.min.y,
+            width,
+            height,
+            pixels,
+        };
+    }
+
+    let extrude_edges = extrude_edges && width > 0 && height > 0;
+    let padded_width = width + 2 * padding;
+    let padded_height = height + 2 * padding;
+    let mut padded = vec![0; padded_width * padded_height];
+    for (row, target) in padded.chunks_exact_mut(padded_width).enumerate() {
+        let source_row = if extrude_edges {
+            row.saturating_sub(padding).min(height - 1)
+        } else if (padding..padding + height).contains(&row) {
+            row - padding
+        } else {
+            continue;
+        };
+        let source = &pixels[source_row * width..(source_row + 1) * width];
+        target[padding..padding + width].copy_from_slice(source);
+        if extrude_edges {
+            target[..padding].fill(source[0]);
+            target[padding + width..].fill(source[width - 1]);
         }
+    }
 
-        Ok(())
+    AtlasUpdate {
+        texture_index,
+        x: glyph_box.min.x - padding,
+        y: glyph_box.min.y - padding,
+        width: padded_width,
+        height: padded_height,
+        pixels: padded,
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::renderer::FilledRect;
+    use crate::test_utils::{test_font_storage, text_element};
+    use crate::text::{TextData, TextLayoutConfig};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::num::NonZeroUsize;
+
+    /// Counts allocations made on threads that opted in via `COUNTING`.
+    struct CountingAllocator;
+
+    thread_local! {
+        static COUNTING: Cell<bool> = const { Cell::new(false) };
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            if COUNTING.try_with(Cell::get).unwrap_or(false) {
+                let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            }
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Returns how many heap allocations `f` makes on this thread.
+    pub(crate) fn count_allocations(f: impl FnOnce()) -> usize {
+        ALLOCATIONS.with(|count| count.set(0));
+        COUNTING.with(|counting| counting.set(true));
+        f();
+        COUNTING.with(|counting| counting.set(false));
+        ALLOCATIONS.with(Cell::get)
+    }
+
+    #[test]
+    fn test_cached_layout_renders_without_allocations() {
+        let (font_storage, font_id) = test_font_storage();
+
+        let mut data = TextData::new();
+        data.append(text_element(
+            font_id,
+            16.0,
+            "The quick brown fox jumps over the lazy dog",
+            0u32,
+        ));
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let mut renderer = GpuRenderer::new(&[GpuCacheConfig {
+            texture_size: NonZeroUsize::new(512).unwrap(),
+            tile_size: NonZeroUsize::new(32).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(16).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        }]);
+
+        let placed = [
+            (LayoutPlacement::default(), &layout),
+            (
+                LayoutPlacement {
+                    offset: [0.0, 20.0],
+                    ..Default::default()
+                },
+                &layout,
+            ),
+        ];
+        let render = |renderer: &mut GpuRenderer,
+                      layouts: &[(LayoutPlacement, &TextLayout<u32>)],
+                      uploads: &mut usize,
+                      drawn: &mut usize| {
+            renderer
+                .try_render_streaming_all::<_, ()>(
+                    layouts,
+                    &font_storage,
+                    false,
+                    &mut |updates| {
+                        *uploads += updates.len();
+                        Ok(())
+                    },
+                    &mut |_, _| *drawn += 1,
+                    &mut || Ok(()),
+                    &mut |_| Ok(()),
+                )
+                .unwrap();
+        };
+
+        // The first frame rasterizes and uploads every glyph.
+        let (mut uploads, mut drawn) = (0, 0);
+        render(&mut renderer, &placed[..1], &mut uploads, &mut drawn);
+        assert!(uploads > 0);
+
+        // Both a single layout and several placed ones, as drawn by `render_all`.
+        for layouts in [&placed[..1], &placed] {
+            let (mut uploads, mut drawn) = (0, 0);
+            let allocations =
+                count_allocations(|| render(&mut renderer, layouts, &mut uploads, &mut drawn));
+            assert_eq!(uploads, 0);
+            assert_eq!(drawn, layouts.len() * layout.len_glyphs());
+            assert_eq!(allocations, 0);
+        }
+    }
+
+    #[test]
+    fn test_stats_count_lookups_and_evictions() {
+        let (font_storage, font_id) = test_font_storage();
+        let layout = |content: &str| {
+            let mut data = TextData::new();
+            data.append(text_element(font_id, 16.0, content, 0u32));
+            data.layout(&TextLayoutConfig::default(), &font_storage)
+        };
+
+        // Two pages of one tile each.
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(1).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let mut renderer = GpuRenderer::new(&[config.clone(), config]);
+        let render = |renderer: &mut GpuRenderer, content: &str| {
+            renderer.render(&layout(content), &font_storage, |_| {}, |_| {}, |_| {});
+        };
+
+        // "c" and "d" evict "a" and "b" in new batches.
+        render(&mut renderer, "abcd");
+        let stats = renderer.stats();
+        assert_eq!((stats.hits, stats.misses, stats.standalone), (0, 4, 0));
+        assert_eq!(stats.evictions, 2);
+        for page in &stats.pages {
+            assert_eq!((page.used, page.capacity, page.area), (1, 1, 64 * 64));
+            assert!(page.used_area > 0 && page.occupancy() <= 1.0);
+        }
+
+        render(&mut renderer, "cd");
+        assert_eq!(renderer.stats().hits, 2);
+        assert!((renderer.stats().hit_rate() - 2.0 / 6.0).abs() < 1e-6);
+
+        renderer.reset_stats();
+        let stats = renderer.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (0, 0, 0));
+        assert_eq!(stats.pages.len(), 2);
+    }
+
+    #[test]
+    fn test_clip_rect_and_transform_cull_and_reach_instances() {
+        let (font_storage, font_id) = test_font_storage();
+
+        let mut data = TextData::new();
+        data.append(text_element(font_id, 16.0, "Clipped text", 0u32));
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let mut renderer = GpuRenderer::new(&[GpuCacheConfig {
+            texture_size: NonZeroUsize::new(512).unwrap(),
+            tile_size: NonZeroUsize::new(32).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(16).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        }]);
+        let render = |renderer: &mut GpuRenderer, layout: &TextLayout<u32>| {
+            let (mut uploads, mut instances) = (0, Vec::new());
+            renderer.render(
+                layout,
+                &font_storage,
+                |updates| uploads += updates.len(),
+                |drawn| {
+                    let clip_and_transform =
+                        |instance: &GlyphInstance<u32>| (instance.clip_rect, instance.transform);
+                    instances.extend(drawn.iter().map(clip_and_transform));
+                },
+                |_| {},
+            );
+            (uploads, instances)
+        };
+
+        // A clip beside the text draws and uploads nothing.
+        let outside = Box2D::new(Point2D::new(-100.0, -100.0), Point2D::new(-50.0, -50.0));
+        let (uploads, instances) = render(&mut renderer, &layout.clone().with_clip_rect(outside));
+        assert_eq!((uploads, instances.len()), (0, 0));
+
+        // A clip around the text keeps every glyph and hands the clip on.
+        let (_, unclipped) = render(&mut renderer, &layout);
+        let around = Box2D::new(
+            Point2D::new(-1.0, -1.0),
+            Point2D::new(layout.total_width + 1.0, layout.total_height + 1.0),
+        );
+        let clipped = layout.clone().with_clip_rect(around);
+        let (_, instances) = render(&mut renderer, &clipped);
+        assert!(!instances.is_empty());
+        assert_eq!(instances.len(), unclipped.len());
+        let identity = Transform2D::identity();
+        assert!(
+            instances
+                .iter()
+                .all(|item| *item == (Some(around), identity))
+        );
+
+        // The clip applies to the transformed glyphs, which carry the transform.
+        let scale = Transform2D::scale(0.5, 0.5);
+        renderer.set_transform(scale);
+        let (_, instances) = render(&mut renderer, &clipped);
+        assert_eq!(instances.len(), unclipped.len());
+        assert!(instances.iter().all(|item| item.1 == scale));
+        renderer.set_transform(Transform2D::translation(200.0, 0.0));
+        let (_, instances) = render(&mut renderer, &clipped);
+        assert!(instances.is_empty());
+
+        // A transform passed to a single render does not change the renderer's.
+        let mut transforms = Vec::new();
+        renderer.render_transformed(
+            &clipped,
+            scale,
+            &font_storage,
+            |_| {},
+            |drawn| transforms.extend(drawn.iter().map(|instance| instance.transform)),
+            |_| {},
+        );
+        assert_eq!(transforms.len(), unclipped.len());
+        assert!(transforms.iter().all(|&transform| transform == scale));
+        assert_eq!(renderer.transform(), Transform2D::translation(200.0, 0.0));
+    }
+
+    #[test]
+    fn test_owned_output_batches_instances_per_page() {
+        let (font_storage, font_id) = test_font_storage();
+
+        let mut data = TextData::new();
+        data.append(text_element(font_id, 16.0, "abcab", 0u32));
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        // Two pages of one tile each: "a" and "b" are cached, "c" is not.
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(1).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let mut renderer = GpuRenderer::new(&[config.clone(), config]);
+        let output = renderer.render_owned(&layout, &font_storage);
+
+        // The output can be consumed on another thread.
+        let output = std::thread::spawn(move || output).join().unwrap();
+        assert_eq!(output.updates.len(), 2);
+        assert_eq!(output.standalone.len(), 1);
+        let pages: Vec<_> = output
+            .batches
+            .iter()
+            .map(|batch| {
+                let same_page = batch
+                    .instances
+                    .iter()
+                    .all(|instance| instance.texture_index == batch.texture_index);
+                assert!(same_page);
+                (batch.texture_index, batch.instances.len())
+            })
+            .collect();
+        assert_eq!(pages, [(0, 1), (1, 1), (0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn test_lcd_masks_span_three_texels_per_pixel() {
+        let (font_storage, font_id) = test_font_storage();
+
+        let mut data = TextData::new();
+        data.append(text_element(font_id, 16.0, "ab", 0u32));
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let mut renderer = GpuRenderer::new(&[GpuCacheConfig {
+            texture_size: NonZeroUsize::new(256).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(4).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        }]);
+        let grayscale = renderer.render_owned(&layout, &font_storage);
+        renderer.set_lcd_antialiasing(true);
+        let lcd = renderer.render_owned(&layout, &font_storage);
+
+        // The LCD variants are cached next to the grayscale glyphs.
+        assert_eq!(lcd.updates.len(), grayscale.updates.len());
+        let instances = |output: &RenderOutput<u32>| -> Vec<GlyphInstance<u32>> {
+            output
+                .batches
+                .iter()
+                .flat_map(|batch| batch.instances.clone())
+                .collect()
+        };
+        for (grayscale, lcd) in instances(&grayscale).iter().zip(&instances(&lcd)) {
+            assert_eq!(lcd.screen_rect, grayscale.screen_rect);
+            let texels = |instance: &GlyphInstance<u32>| (instance.uv_rect.width() * 256.0).round();
+            assert_eq!(texels(lcd), texels(grayscale) * 3.0);
+            assert_eq!(texels(lcd), lcd.screen_rect.width().round() * 3.0);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_many_misses_are_rasterized_ahead() {
+        let (font_storage, font_id) = test_font_storage();
+        let layout = |content: &str| {
+            let mut data = TextData::new();
+            data.append(text_element(font_id, 16.0, content, 0u32));
+            data.layout(&TextLayoutConfig::default(), &font_storage)
+        };
+        let mut renderer = GpuRenderer::new(&[GpuCacheConfig {
+            texture_size: NonZeroUsize::new(512).unwrap(),
+            tile_size: NonZeroUsize::new(32).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(16).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        }]);
+
+        // A few misses are left to the render.
+        let short = layout("abc");
+        assert!(rasterize_misses(&renderer.cache, &short, &font_storage, false).is_empty());
+
+        let long = layout("abcdefghijklmnopqrstuvwxyz abcdefghijklmnopqrstuvwxyz");
+        let rasterized = rasterize_misses(&renderer.cache, &long, &font_storage, false);
+        let unique: std::collections::HashSet<_> = long
+            .lines
+            .iter()
+            .flat_map(|line| &line.glyphs)
+            .map(|glyph| glyph.glyph_id)
+            .collect();
+        assert_eq!(rasterized.len(), unique.len());
+        for (glyph_id, (_, pixels)) in &rasterized {
+            let (_, expected) = crate::renderer::rasterize_glyph(glyph_id, &font_storage).unwrap();
+            assert_eq!(*pixels, expected);
+        }
+
+        // Once rendered, nothing is missing.
+        renderer.render(&long, &font_storage, |_| {}, |_| {}, |_| {});
+        assert!(rasterize_misses(&renderer.cache, &long, &font_storage, false).is_empty());
+    }
+
+    #[test]
+    fn test_instances_are_drawn_in_page_runs() {
+        let (font_storage, font_id) = test_font_storage();
+
+        let mut data = TextData::new();
+        data.append(text_element(font_id, 16.0, "abcdefghabcd", 0u32));
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        // Two pages of four tiles each: "a" to "d" fill the first.
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(32).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(2).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let mut renderer = GpuRenderer::new(&[config.clone(), config]);
+        let mut calls = Vec::new();
+        renderer.render(
+            &layout,
+            &font_storage,
+            |_| {},
+            |instances| {
+                let pages: Vec<_> = instances.iter().map(|i| i.texture_index).collect();
+                let x: Vec<_> = instances.iter().map(|i| i.screen_rect.min.x).collect();
+                calls.push((pages, x));
+            },
+            |_| {},
+        );
+
+        // One call per run of a page, in layout order.
+        let pages: Vec<_> = calls.iter().map(|(pages, _)| pages.clone()).collect();
+        assert_eq!(pages, [vec![0; 4], vec![1; 4], vec![0; 4]]);
+        let x: Vec<_> = calls.iter().flat_map(|(_, x)| x.iter().copied()).collect();
+        assert!(x.is_sorted());
+    }
+
+    #[test]
+    fn test_damage_covers_effects() {
+        let (font_storage, font_id) = test_font_storage();
+        let data = TextData::builder(font_id, 16.0, 0u32).push_str("a").build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+        let mut renderer = GpuRenderer::new(&[GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(32).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(2).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        }]);
+
+        let plain = renderer.track_damage(1, &layout, &font_storage).added[0];
+        let underline = FilledRect {
+            rect: Box2D::new(Point2D::new(0.0, 20.0), Point2D::new(10.0, 21.0)),
+            color: [1.0; 4],
+        };
+        let effects = DamageEffects {
+            rects: std::slice::from_ref(&underline),
+            shadow: Some(([4.0, 4.0], 2.0)),
+            bleed: 1.0,
+        };
+        let damage = renderer.track_damage_with(2, &layout, effects, &font_storage);
+        assert_eq!(damage.added.len(), 2);
+
+        // The glyph bleeds one pixel, and its shadow reaches 4 + 2 further.
+        let glyph = damage.added[0];
+        assert_eq!(glyph.min, plain.min - Vector2D::new(1.0, 1.0));
+        assert_eq!(glyph.max, plain.max + Vector2D::new(7.0, 7.0));
+        let underline = damage.added[1];
+        assert_eq!(
+            [
+                underline.min.x,
+                underline.min.y,
+                underline.max.x,
+                underline.max.y
+            ],
+            [0.0, 20.0, 16.0, 27.0]
+        );
+    }
+
+    #[test]
+    fn test_padded_update_clears_or_extrudes_padding() {
+        let update = |extrude_edges| {
+            let cache = GpuCache::new(&[GpuCacheConfig {
+                texture_size: NonZeroUsize::new(64).unwrap(),
+                tile_size: NonZeroUsize::new(16).unwrap(),
+                tiles_per_axis: NonZeroUsize::new(4).unwrap(),
+                priority: AtlasPriority::Normal,
+                padding: 1,
+                extrude_edges,
+                packing: AtlasPacking::Grid,
+            }]);
+            let glyph_box = Box2D::new(Point2D::new(17, 1), Point2D::new(19, 3));
+            padded_update(&cache, 0, glyph_box, vec![1, 2, 3, 4])
+        };
+
+        let cleared = update(false);
+        assert_eq!(
+            (cleared.x, cleared.y, cleared.width, cleared.height),
+            (16, 0, 4, 4)
+        );
+        #[rustfmt::skip]
+        assert_eq!(cleared.pixels, [
+            0, 0, 0, 0,
+            0, 1, 2, 0,
+            0, 3, 4, 0,
+            0, 0, 0, 0,
+        ]);
+
+        #[rustfmt::skip]
+        assert_eq!(update(true).pixels, [
+            1, 1, 2, 2,
+            1, 1, 2, 2,
+            3, 3, 4, 4,
+            3, 3, 4, 4,
+        ]);
     }
 }
@@ -0,0 +1,317 @@
+//! Glyphs that changed on screen between two frames of a layout.
+
+use std::{any::Any, collections::HashMap};
+
+use euclid::{Box2D, UnknownUnit};
+
+use crate::{glyph_id::GlyphId, renderer::FilledRect};
+
+type Rect = Box2D<f32, UnknownUnit>;
+
+/// Glyphs of a layout that changed since it was last tracked, see
+/// [`GpuRenderer::track_damage`](super::GpuRenderer::track_damage).
+///
+/// Rectangles are in target coordinates, after the renderer's transform, and
+/// rounded out to whole pixels. A glyph whose user data changed is reported as
+/// removed and added in place.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LayoutDamage {
+    /// Rectangles of glyphs that were not drawn before.
+    pub added: Vec<Rect>,
+    /// Rectangles of glyphs that are no longer drawn.
+    pub removed: Vec<Rect>,
+    /// Previous and current rectangles of glyphs drawn at another place.
+    pub moved: Vec<(Rect, Rect)>,
+}
+
+impl LayoutDamage {
+    /// Returns whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty()
+    }
+
+    /// Returns every rectangle that has to be redrawn.
+    pub fn rects(&self) -> impl Iterator<Item = Rect> + '_ {
+        self.added
+            .iter()
+            .chain(&self.removed)
+            .copied()
+            .chain(self.moved.iter().flat_map(|&(from, to)| [from, to]))
+    }
+
+    /// Returns the rectangle covering all damage, or `None` if nothing changed.
+    ///
+    /// Redrawing it, e.g. by setting it as the [`TextLayout::clip_rect`](crate::text::TextLayout::clip_rect)
+    /// of a frame drawn over the previous one, updates the text on screen.
+    pub fn bounds(&self) -> Option<Rect> {
+        self.rects().reduce(|bounds, rect| bounds.union(&rect))
+    }
+}
+
+/// What a backend draws around the glyphs of a layout, so that
+/// [`GpuRenderer::track_damage_with`](super::GpuRenderer::track_damage_with)
+/// covers it. All extents are in layout coordinates.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DamageEffects<'a> {
+    /// Rectangles drawn along with the layout, such as
+    /// [`run_backgrounds`](crate::renderer::run_backgrounds) and
+    /// [`text_decorations`](crate::renderer::text_decorations).
+    pub rects: &'a [FilledRect],
+    /// Offset and blur radius of a drop shadow drawn behind the glyphs and rects.
+    pub shadow: Option<([f32; 2], f32)>,
+    /// Distance by which sampling the glyphs bleeds past their bitmaps, e.g.
+    /// one texel with linear filtering.
+    pub bleed: f32,
+}
+
+/// A glyph as drawn in a frame, remembered to find what changed in the next.
+pub(super) struct DrawnGlyph<T> {
+    pub(super) glyph_id: GlyphId,
+    pub(super) rect: Rect,
+    pub(super) user_data: T,
+}
+
+/// A rectangle filled in a frame, remembered like [`DrawnGlyph`]s.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct DrawnRect {
+    /// The area the fill covers on the target, including its shadow.
+    pub(super) rect: Rect,
+    /// The filled rectangle and its color, which identify it across frames.
+    pub(super) key: (Rect, [f32; 4]),
+}
+
+/// What a tracked layout drew last.
+struct TrackedLayout {
+    /// `Vec<DrawnGlyph<T>>` of the user data type of the last call.
+    glyphs: Box<dyn Any + Send>,
+    /// The rectangles of `glyphs`, reported as removed when the layout is
+    /// tracked with another user data type.
+    glyph_rects: Vec<Rect>,
+    rects: Vec<DrawnRect>,
+}
+
+/// What each tracked layout drew last, by layout id.
+///
+/// The user data type of the renderer's callers is not known to the renderer,
+/// so the glyphs are stored type-erased; when a layout is tracked with another
+/// type than before, all its previous glyphs are removed and the current ones
+/// added.
+#[derive(Default)]
+pub(super) struct DamageTracker {
+    layouts: HashMap<u64, TrackedLayout, fxhash::FxBuildHasher>,
+}
+
+impl DamageTracker {
+    /// Remembers `glyphs` and `rects` for `layout_id` and returns how they
+    /// differ from those remembered before.
+    pub(super) fn track<T: PartialEq + Send + 'static>(
+        &mut self,
+        layout_id: u64,
+        glyphs: Vec<DrawnGlyph<T>>,
+        rects: Vec<DrawnRect>,
+    ) -> LayoutDamage {
+        let damage = match self.layouts.remove(&layout_id) {
+            Some(previous) => {
+                let mut damage = match previous.glyphs.downcast::<Vec<DrawnGlyph<T>>>() {
+                    Ok(previous_glyphs) => diff(&previous_glyphs, &glyphs),
+                    Err(_) => LayoutDamage {
+                        removed: previous.glyph_rects,
+                        ..diff(&[], &glyphs)
+                    },
+                };
+                diff_rects(&previous.rects, &rects, &mut damage);
+                damage
+            }
+            None => {
+                let mut damage = diff(&[], &glyphs);
+                diff_rects(&[], &rects, &mut damage);
+                damage
+            }
+        };
+        self.layouts.insert(
+            layout_id,
+            TrackedLayout {
+                glyph_rects: glyphs.iter().map(|glyph| glyph.rect).collect(),
+                glyphs: Box::new(glyphs),
+                rects,
+            },
+        );
+        damage
+    }
+
+    /// Drops the glyphs remembered for `layout_id`.
+    pub(super) fn forget(&mut self, layout_id: u64) {
+        self.layouts.remove(&layout_id);
+    }
+}
+
+/// Matches the glyphs of two frames: first those drawn unchanged at the same
+/// place, then equal glyphs at other places, in layout order.
+fn diff<T: PartialEq>(previous: &[DrawnGlyph<T>], current: &[DrawnGlyph<T>]) -> LayoutDamage {
+    let place = |glyph: &DrawnGlyph<T>| {
+        let Rect { min, max } = glyph.rect;
+        (
+            glyph.glyph_id,
+            [min.x, min.y, max.x, max.y].map(f32::to_bits),
+        )
+    };
+
+    let mut at_place: HashMap<_, Vec<usize>, fxhash::FxBuildHasher> = HashMap::default();
+    for (index, glyph) in previous.iter().enumerate() {
+        at_place.entry(place(glyph)).or_default().push(index);
+    }
+    let mut matched = vec![false; previous.len()];
+    let mut changed = Vec::new();
+    for glyph in current {
+        let unchanged = at_place.get_mut(&place(glyph)).and_then(|candidates| {
+            let position = candidates
+                .iter()
+                .position(|&index| previous[index].user_data == glyph.user_data)?;
+            Some(candidates.remove(position))
+        });
+        match unchanged {
+            Some(index) => matched[index] = true,
+            None => changed.push(glyph),
+        }
+    }
+
+    let mut by_glyph: HashMap<GlyphId, Vec<usize>, fxhash::FxBuildHasher> = HashMap::default();
+    for (index, glyph) in previous.iter().enumerate() {
+        if !matched[index] {
+            by_glyph.entry(glyph.glyph_id).or_default().push(index);
+        }
+    }
+    let mut damage = LayoutDamage::default();
+    for glyph in changed {
+        let moved = by_glyph.get_mut(&glyph.glyph_id).and_then(|candidates| {
+            let position = candidates
+                .iter()
+                .position(|&index| previous[index].user_data == glyph.user_data)?;
+            Some(candidates.remove(position))
+        });
+        match moved {
+            Some(index) => {
+                matched[index] = true;
+                damage.moved.push((previous[index].rect, glyph.rect));
+            }
+            None => damage.added.push(glyph.rect),
+        }
+    }
+    damage.removed = previous
+        .iter()
+        .zip(&matched)
+        .filter(|(_, matched)| !**matched)
+        .map(|(glyph, _)| glyph.rect)
+        .collect();
+    damage
+}
+
+/// Adds the rectangles filled in only one of two frames to `damage`.
+fn diff_rects(previous: &[DrawnRect], current: &[DrawnRect], damage: &mut LayoutDamage) {
+    let key = |rect: &DrawnRect| {
+        let (Rect { min, max }, color) = rect.key;
+        (
+            [min.x, min.y, max.x, max.y].map(f32::to_bits),
+            color.map(f32::to_bits),
+        )
+    };
+
+    let mut unmatched: HashMap<_, Vec<usize>, fxhash::FxBuildHasher> = HashMap::default();
+    for (index, rect) in previous.iter().enumerate() {
+        unmatched.entry(key(rect)).or_default().push(index);
+    }
+    for rect in current {
+        if unmatched.get_mut(&key(rect)).and_then(Vec::pop).is_none() {
+            damage.added.push(rect.rect);
+        }
+    }
+    let mut removed: Vec<usize> = unmatched.into_values().flatten().collect();
+    removed.sort_unstable();
+    damage
+        .removed
+        .extend(removed.into_iter().map(|index| previous[index].rect));
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use euclid::Point2D;
+
+    fn glyph(ch: char, x: f32, color: u32) -> DrawnGlyph<u32> {
+        DrawnGlyph {
+            glyph_id: GlyphId::hex_box(fontdb::ID::dummy(), ch, 8.0),
+            rect: Rect::new(Point2D::new(x, 0.0), Point2D::new(x + 8.0, 10.0)),
+            user_data: color,
+        }
+    }
+
+    #[test]
+    fn test_diff_reports_added_removed_and_moved_glyphs() {
+        let rect = |x: f32| glyph('a', x, 0).rect;
+        let mut tracker = DamageTracker::default();
+
+        // Everything is new at first, and nothing changes when drawn again.
+        let frame = || vec![glyph('a', 0.0, 0), glyph('b', 8.0, 0), glyph('c', 16.0, 0)];
+        assert_eq!(tracker.track(1, frame(), Vec::new()).added.len(), 3);
+        assert!(tracker.track(1, frame(), Vec::new()).is_empty());
+
+        // "b" recolored, "c" moved right, "d" typed in its place, ...
+        let damage = tracker.track(
+            1,
+            vec![
+                glyph('a', 0.0, 0),
+                glyph('b', 8.0, 1),
+                glyph('d', 16.0, 0),
+                glyph('c', 24.0, 0),
+            ],
+            Vec::new(),
+        );
+        assert_eq!(damage.added, [rect(8.0), rect(16.0)]);
+        assert_eq!(damage.removed, [rect(8.0)]);
+        assert_eq!(damage.moved, [(rect(16.0), rect(24.0))]);
+        let bounds = damage.bounds().unwrap();
+        assert_eq!((bounds.min.x, bounds.max.x), (8.0, 32.0));
+
+        // ... and "a" deleted. Other layouts start over.
+        let damage = tracker.track(1, vec![glyph('b', 8.0, 1), glyph('d', 16.0, 0)], Vec::new());
+        assert_eq!(damage.removed, [rect(0.0), rect(24.0)]);
+        assert!(damage.added.is_empty() && damage.moved.is_empty());
+        assert_eq!(tracker.track(2, frame(), Vec::new()).added.len(), 3);
+        let recolored = vec![DrawnGlyph {
+            glyph_id: GlyphId::hex_box(fontdb::ID::dummy(), 'b', 8.0),
+            rect: rect(8.0),
+            user_data: [0.0f32; 4],
+        }];
+
+        // A change of the user data type replaces every glyph.
+        let damage = tracker.track(1, recolored, Vec::new());
+        assert_eq!(damage.added, [rect(8.0)]);
+        assert_eq!(damage.removed, [rect(8.0), rect(16.0)]);
+        tracker.forget(2);
+        assert_eq!(tracker.track(2, frame(), Vec::new()).added.len(), 3);
+    }
+
+    #[test]
+    fn test_diff_reports_changed_rects() {
+        let fill = |x: f32, alpha: f32| {
+            let rect = Rect::new(Point2D::new(x, 10.0), Point2D::new(x + 8.0, 11.0));
+            DrawnRect {
+                rect: rect.inflate(1.0, 1.0),
+                key: (rect, [0.0, 0.0, 0.0, alpha]),
+            }
+        };
+        let mut tracker = DamageTracker::default();
+        let glyphs = || vec![glyph('a', 0.0, 0)];
+
+        let damage = tracker.track(1, glyphs(), vec![fill(0.0, 1.0)]);
+        assert_eq!(damage.added, [glyph('a', 0.0, 0).rect, fill(0.0, 1.0).rect]);
+        assert!(tracker.track(1, glyphs(), vec![fill(0.0, 1.0)]).is_empty());
+
+        // An underline recolored and another one added.
+        let damage = tracker.track(1, glyphs(), vec![fill(0.0, 0.5), fill(8.0, 1.0)]);
+        assert_eq!(damage.added, [fill(0.0, 0.5).rect, fill(8.0, 1.0).rect]);
+        assert_eq!(damage.removed, [fill(0.0, 1.0).rect]);
+        assert!(damage.moved.is_empty());
+    }
+}
@@ -1,11 +1,15 @@
 use euclid::{Box2D, Point2D, UnknownUnit};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::num::NonZeroUsize;
 
 use crate::font_storage::FontStorage;
 use crate::glyph_id::GlyphId;
+use crate::renderer::{CacheStats, PageStats};
 
-const ATLAS_MARGIN: usize = 2;
+use super::packing::{AtlasPacking, Packer};
+
+/// Tiles along each axis of an overflow page.
+const OVERFLOW_TILES_PER_AXIS: usize = 2;
 
 /// protect `push_front`, `move_to_front` and `attach_to_head` from incorrect usage.
 mod cache_state {
@@ -17,6 +21,16 @@ mod cache_state {
         newer: Option<usize>,
         older: Option<usize>,
         last_used_batch_id: usize,
+        last_used_frame: u64,
+        /// Prepared draws sampling the glyph; pinned glyphs are never evicted.
+        pins: u32,
+    }
+
+    /// The nodes last drawn in one frame, which are adjacent in the LRU list.
+    #[derive(Clone, Copy)]
+    struct FrameRun {
+        newest: usize,
+        len: usize,
     }
 
     pub struct CacheState {
@@ -27,8 +41,14 @@ mod cache_state {
         lru_tail: Option<usize>,
         lru_map: HashMap<GlyphId, usize, fxhash::FxBuildHasher>,
         lru_empties: Vec<usize>,
+        /// Runs of the LRU list by frame, to insert glyphs by frame without a scan.
+        frame_runs: BTreeMap<u64, FrameRun>,
 
         current_batch_id: usize,
+        current_frame: u64,
+
+        /// Glyphs evicted since creation or the last `reset_evictions`.
+        evictions: u64,
     }
 
     impl CacheState {
@@ -44,18 +64,230 @@ mod cache_state {
                     fxhash::FxBuildHasher::default(),
                 ),
                 lru_empties: (0..capacity).collect(),
+                frame_runs: BTreeMap::new(),
                 current_batch_id: 0,
+                current_frame: 0,
+                evictions: 0,
             }
         }
 
+        /// Returns an empty state of the same capacity, in the same frame.
+        pub fn empty_like(&self) -> Self {
+            let capacity = NonZeroUsize::new(self.capacity).expect("non-zero");
+            Self::new_in_frame(capacity, self.current_frame)
+        }
+
+        /// Returns an empty state starting in `frame`.
+        pub fn new_in_frame(capacity: NonZeroUsize, frame: u64) -> Self {
+            let mut state = Self::new(capacity);
+            state.current_frame = frame;
+            state
+        }
+
+        pub fn current_frame(&self) -> u64 {
+            self.current_frame
+        }
+
+        pub fn has_free_slot(&self) -> bool {
+            !self.lru_empties.is_empty()
+        }
+
+        pub fn evictions(&self) -> u64 {
+            self.evictions
+        }
+
+        pub fn reset_evictions(&mut self) {
+            self.evictions = 0;
+        }
+
+        /// Returns the frame the least recently used glyph was last drawn in, if
+        /// the cache is full and that glyph may be evicted.
+        pub fn evictable_frame(&self) -> Option<u64> {
+            if !self.lru_empties.is_empty() {
+                return None;
+            }
+            self.unprotected_tail_frame()
+        }
+
+        /// Returns the frame the least recently used unpinned glyph was last drawn
+        /// in, if that glyph may be evicted.
+        pub fn unprotected_tail_frame(&self) -> Option<u64> {
+            let index = self.eviction_candidate()?;
+            Some(self.lru_nodes[index].last_used_frame)
+        }
+
+        /// Evicts the least recently used unpinned glyph if it may be evicted, and
+        /// returns its freed slot.
+        pub fn evict_unprotected_tail(&mut self) -> Option<usize> {
+            let index = self.eviction_candidate()?;
+            self.detach(index);
+            self.evictions += 1;
+            Some(index)
+        }
+
+        /// Returns the slot of the least recently used unpinned glyph, if it was
+        /// not used in the current batch.
+        fn eviction_candidate(&self) -> Option<usize> {
+            let mut index = self.lru_tail;
+            while let Some(i) = index {
+                let node = &self.lru_nodes[i];
+                if node.pins == 0 {
+                    return (node.last_used_batch_id != self.current_batch_id).then_some(i);
+                }
+                index = node.newer;
+            }
+            None
+        }
+
+        /// Pins the glyphs used in the current batch and returns them.
+        pub fn pin_batch(&mut self) -> Vec<GlyphId> {
+            let mut pinned = Vec::new();
+            // Glyphs used in the current batch were moved to the front.
+            let mut index = self.lru_head;
+            while let Some(i) = index {
+                let node = &mut self.lru_nodes[i];
+                if node.last_used_batch_id != self.current_batch_id {
+                    break;
+                }
+                if let Some(glyph_id) = node.glyph_id {
+                    node.pins += 1;
+                    pinned.push(glyph_id);
+                }
+                index = node.older;
+            }
+            pinned
+        }
+
+        /// Releases one pin of `glyph_id` and returns whether it is cached.
+        pub fn unpin(&mut self, glyph_id: &GlyphId) -> bool {
+            let Some(&index) = self.lru_map.get(glyph_id) else {
+                return false;
+            };
+            let node = &mut self.lru_nodes[index];
+            node.pins = node.pins.saturating_sub(1);
+            true
+        }
+
+        /// Returns whether `glyph_id` is cached and pinned.
+        pub fn is_pinned(&self, glyph_id: &GlyphId) -> bool {
+            self.lru_map
+                .get(glyph_id)
+                .is_some_and(|&index| self.lru_nodes[index].pins > 0)
+        }
+
+        pub fn capacity(&self) -> usize {
+            self.capacity
+        }
+
+        /// Adds a free slot, with the index of the current capacity.
+        pub fn add_slot(&mut self) {
+            self.lru_nodes.push(LruNode::default());
+            self.lru_empties.push(self.capacity);
+            self.capacity += 1;
+        }
+
+        /// Returns the occupied slots.
+        pub fn slots(&self) -> impl Iterator<Item = usize> + '_ {
+            self.lru_map.values().copied()
+        }
+
+        /// Returns the cached glyphs with their slots.
+        pub fn glyphs(&self) -> impl Iterator<Item = (GlyphId, usize)> + '_ {
+            self.lru_map
+                .iter()
+                .map(|(&glyph_id, &index)| (glyph_id, index))
+        }
+
         pub fn clear(&mut self) {
             self.lru_map.clear();
             self.lru_empties.clear();
             self.lru_empties.extend(0..self.capacity);
+            self.lru_nodes.fill(LruNode::default());
             self.lru_head = None;
             self.lru_tail = None;
+            self.frame_runs.clear();
             self.current_batch_id = 0;
         }
+
+        /// Returns the cached glyphs with their slots and the frames they were last
+        /// drawn in, most recently used first.
+        pub fn entries(&self) -> Vec<(GlyphId, usize, u64)> {
+            let mut entries = Vec::with_capacity(self.lru_map.len());
+            let mut index = self.lru_head;
+            while let Some(i) = index {
+                let node = &self.lru_nodes[i];
+                if let Some(glyph_id) = node.glyph_id {
+                    entries.push((glyph_id, i, node.last_used_frame));
+                }
+                index = node.older;
+            }
+            entries
+        }
+
+        /// Returns whether `glyph_id` is cached, without marking it used.
+        pub fn contains(&self, glyph_id: &GlyphId) -> bool {
+            self.lru_map.contains_key(glyph_id)
+        }
+
+        /// Frees the slot of `glyph_id`, if it is cached, and returns it.
+        pub fn remove(&mut self, glyph_id: &GlyphId) -> Option<usize> {
+            let index = *self.lru_map.get(glyph_id)?;
+            self.detach(index);
+            Some(index)
+        }
+
+        /// Puts `glyph_id` into a free slot, ordered as last drawn in `frame`, and
+        /// returns the slot. Returns `None` if no slot is free.
+        ///
+        /// The glyph is not protected in the current batch.
+        pub fn insert_with_frame(&mut self, glyph_id: GlyphId, frame: u64) -> Option<usize> {
+            let index = self.lru_empties.pop()?;
+
+            // The newest node drawn no later than `frame` becomes the next older one.
+            let older = self
+                .frame_runs
+                .range(..=frame)
+                .next_back()
+                .map(|(_, run)| run.newest);
+            let newer = match older {
+                Some(i) => self.lru_nodes[i].newer,
+                None => self.lru_tail,
+            };
+
+            self.lru_nodes[index] = LruNode {
+                glyph_id: Some(glyph_id),
+                newer,
+                older,
+                last_used_batch_id: self.current_batch_id.wrapping_sub(1),
+                last_used_frame: frame,
+                pins: 0,
+            };
+            match newer {
+                Some(newer) => self.lru_nodes[newer].older = Some(index),
+                None => self.lru_head = Some(index),
+            }
+            match older {
+                Some(older) => self.lru_nodes[older].newer = Some(index),
+                None => self.lru_tail = Some(index),
+            }
+            self.lru_map.insert(glyph_id, index);
+            self.link_run(index);
+            Some(index)
+        }
+
+        /// Frees the slots of all glyphs of font `font_id` and returns them.
+        pub fn remove_font(&mut self, font_id: fontdb::ID) -> Vec<usize> {
+            let indices: Vec<usize> = self
+                .lru_map
+                .iter()
+                .filter(|(glyph_id, _)| glyph_id.font_id() == font_id)
+                .map(|(_, &index)| index)
+                .collect();
+            for &index in &indices {
+                self.detach(index);
+            }
+            indices
+        }
     }
 
     impl CacheState {
@@ -63,6 +295,10 @@ mod cache_state {
             self.current_batch_id = self.current_batch_id.wrapping_add(1);
         }
 
+        pub fn new_frame(&mut self) {
+            self.current_frame += 1;
+        }
+
         pub fn get_or_push_and_protect(
             &mut self,
             glyph_id: &GlyphId,
@@ -70,98 +306,30 @@ mod cache_state {
             match self.lru_map.entry(*glyph_id) {
                 std::collections::hash_map::Entry::Occupied(entry) => {
                     let &index = entry.get();
-                    let node = &mut self.lru_nodes[index];
-                    node.last_used_batch_id = self.current_batch_id;
-                    self.move_node_to_front(index);
+                    self.touch(index);
                     return Some((index, GetOrPushResult::Hit));
                 }
-                std::collections::hash_map::Entry::Vacant(entry) => {
-                    if !self.lru_empties.is_empty() {
-                        let target_idx = self.lru_empties.pop().expect("checked before");
-
-                        // --- add head ---
-                        // set node
-                        self.lru_nodes[target_idx].newer = None;
-                        self.lru_nodes[target_idx].older = self.lru_head;
-                        self.lru_nodes[target_idx].glyph_id = Some(*glyph_id);
-                        self.lru_nodes[target_idx].last_used_batch_id = self.current_batch_id;
-                        entry.insert(target_idx);
-
-                        // update old head
-                        if let Some(old_head_idx) = self.lru_head {
-                            self.lru_nodes[old_head_idx].newer = Some(target_idx);
-                        }
-
-                        // update new head and tail
-                        self.lru_head = Some(target_idx);
-                        if self.lru_tail.is_none() {
-                            self.lru_tail = Some(target_idx);
-                        }
-
+                std::collections::hash_map::Entry::Vacant(_) => {
+                    if let Some(target_idx) = self.lru_empties.pop() {
+                        self.attach_to_head(target_idx, *glyph_id);
                         return Some((target_idx, GetOrPushResult::NeedToUpload));
                     }
                 }
             }
 
-            // Eviction case
-            let tail_idx = self
-                .lru_tail
-                .expect("tail must be set when all slots are used");
-
-            let tail_node = &mut self.lru_nodes[tail_idx];
-            if tail_node.last_used_batch_id == self.current_batch_id {
-                // tail is protected
-                return None;
-            }
-
-            // --- remove tail ---
-            if let Some(second_tail) = self.lru_nodes[tail_idx].newer {
-                self.lru_nodes[second_tail].older = None;
-                self.lru_tail = Some(second_tail);
-            } else {
-                // tail == head (capacity 1)
-                self.lru_head = None;
-                self.lru_tail = None;
-            }
-
-            // remove from map
-            if let Some(old_key) = self.lru_nodes[tail_idx].glyph_id {
-                self.lru_map.remove(&old_key);
-            }
-
-            let target_idx = tail_idx;
+            // Eviction case: `None` when every glyph is protected or pinned.
+            let target_idx = self.evict_unprotected_tail()?;
+            self.lru_empties.pop();
 
             // --- add head ---
-            // set node
-            self.lru_nodes[target_idx].newer = None;
-            self.lru_nodes[target_idx].older = self.lru_head;
-            self.lru_nodes[target_idx].glyph_id = Some(*glyph_id);
-            self.lru_nodes[target_idx].last_used_batch_id = self.current_batch_id;
-            self.lru_map.insert(*glyph_id, target_idx);
-
-            // update old head
-            if let Some(old_head_idx) = self.lru_head {
-                self.lru_nodes[old_head_idx].newer = Some(target_idx);
-            }
-
-            // update new head and tail
-            self.lru_head = Some(target_idx);
-            if self.lru_tail.is_none() {
-                self.lru_tail = Some(target_idx);
-            }
+            self.attach_to_head(target_idx, *glyph_id);
 
             Some((target_idx, GetOrPushResult::NeedToUpload))
         }
 
         pub fn get_and_protect_entry(&mut self, glyph_id: &GlyphId) -> Option<usize> {
             if let Some(&idx) = self.lru_map.get(glyph_id) {
-                // update last used frame
-                let node = &mut self.lru_nodes[idx];
-                node.last_used_batch_id = self.current_batch_id;
-
-                // move to front
-                self.move_node_to_front(idx);
-
+                self.touch(idx);
                 Some(idx)
             } else {
                 None
@@ -169,22 +337,18 @@ mod cache_state {
         }
 
         pub fn push_and_evicting_unprotected(&mut self, glyph_id: &GlyphId) -> Option<usize> {
-            if let Some(tail_idx) = self.lru_tail {
-                let tail_node = &mut self.lru_nodes[tail_idx];
-                if tail_node.last_used_batch_id == self.current_batch_id {
-                    // tail is protected
-                    return None;
-                }
-                // if tail is not protected, able to use push_front.
+            if self.lru_empties.is_empty()
+                && self.lru_tail.is_some()
+                && self.eviction_candidate().is_none()
+            {
+                // every glyph is protected or pinned
+                return None;
             }
-            // there is no tail. means there is no entry in cache
+            // if some glyph is evictable, able to use push_front.
+            // there is a free slot or no entry in cache.
             // able to use push_front.
 
-            let allocated_idx = self.push_front(*glyph_id);
-            let allocated_node = &mut self.lru_nodes[allocated_idx];
-            allocated_node.last_used_batch_id = self.current_batch_id;
-
-            Some(allocated_idx)
+            Some(self.push_front(*glyph_id))
         }
     }
 
@@ -196,27 +360,13 @@ mod cache_state {
             }
 
             let target_idx = if self.lru_empties.is_empty() {
-                // all slots are used, evict tail
-                let tail_idx = self
-                    .lru_tail
-                    .expect("tail must be set when all slots are used");
-
-                // --- remove tail ---
-                if let Some(second_tail) = self.lru_nodes[tail_idx].newer {
-                    self.lru_nodes[second_tail].older = None;
-                    self.lru_tail = Some(second_tail);
-                } else {
-                    // tail == head (capacity 1)
-                    self.lru_head = None;
-                    self.lru_tail = None;
-                }
-
-                // remove from map
-                if let Some(old_key) = self.lru_nodes[tail_idx].glyph_id {
-                    self.lru_map.remove(&old_key);
-                }
-
-                tail_idx
+                // all slots are used, evict the least recently used unpinned glyph
+                let index = self
+                    .eviction_candidate()
+                    .expect("an evictable glyph must exist when all slots are used");
+                self.detach(index);
+                self.evictions += 1;
+                self.lru_empties.pop().expect("detached a slot")
             } else {
                 // use empty slot
                 self.lru_empties.pop().expect("checked before")
@@ -228,6 +378,77 @@ mod cache_state {
             target_idx
         }
 
+        fn detach(&mut self, index: usize) {
+            if self.lru_nodes[index].glyph_id.is_some() {
+                self.unlink_run(index);
+            }
+            let LruNode {
+                glyph_id,
+                newer,
+                older,
+                ..
+            } = self.lru_nodes[index];
+            match newer {
+                Some(newer) => self.lru_nodes[newer].older = older,
+                None => self.lru_head = older,
+            }
+            match older {
+                Some(older) => self.lru_nodes[older].newer = newer,
+                None => self.lru_tail = newer,
+            }
+            if let Some(glyph_id) = glyph_id {
+                self.lru_map.remove(&glyph_id);
+            }
+            self.lru_nodes[index] = LruNode::default();
+            self.lru_empties.push(index);
+        }
+
+        /// Marks the node at `index` used in the current batch and frame.
+        fn touch(&mut self, index: usize) {
+            self.unlink_run(index);
+            self.move_node_to_front(index);
+            let node = &mut self.lru_nodes[index];
+            node.last_used_batch_id = self.current_batch_id;
+            node.last_used_frame = self.current_frame;
+            self.link_run(index);
+        }
+
+        /// Counts the node at `index` as the newest of the run of its frame.
+        fn link_run(&mut self, index: usize) {
+            let run = self
+                .frame_runs
+                .entry(self.lru_nodes[index].last_used_frame)
+                .or_insert(FrameRun {
+                    newest: index,
+                    len: 0,
+                });
+            run.newest = index;
+            run.len += 1;
+        }
+
+        /// Removes the node at `index` from the run of its frame, before it leaves
+        /// its place in the list.
+        fn unlink_run(&mut self, index: usize) {
+            let LruNode {
+                last_used_frame,
+                older,
+                ..
+            } = self.lru_nodes[index];
+            let std::collections::btree_map::Entry::Occupied(mut entry) =
+                self.frame_runs.entry(last_used_frame)
+            else {
+                return;
+            };
+            let run = entry.get_mut();
+            run.len -= 1;
+            if run.len == 0 {
+                entry.remove();
+            } else if run.newest == index {
+                // The run is adjacent, so the next older node belongs to it.
+                run.newest = older.expect("the run has older nodes");
+            }
+        }
+
         fn move_node_to_front(&mut self, current_index: usize) {
             let older_idx = self.lru_nodes[current_index].older;
             let newer_idx = self.lru_nodes[current_index].newer;
@@ -293,6 +514,11 @@ mod cache_state {
             if self.lru_tail.is_none() {
                 self.lru_tail = Some(node_idx);
             }
+
+            let node = &mut self.lru_nodes[node_idx];
+            node.last_used_batch_id = self.current_batch_id;
+            node.last_used_frame = self.current_frame;
+            self.link_run(node_idx);
         }
     }
 }
@@ -303,11 +529,73 @@ pub struct GpuCacheConfig {
     /// Size of each tile in pixels.
     ///
     /// This specifies the length of one side of the square tile (width or height).
+    /// Pages packed with other than [`AtlasPacking::Grid`] have no tiles; they take
+    /// glyphs that would fit a tile of this size.
     pub tile_size: NonZeroUsize,
     /// Number of tiles along one axis of the texture.
+    ///
+    /// Packed pages hold as many glyphs as fit, whatever this number is.
     pub tiles_per_axis: NonZeroUsize,
     /// Size of the texture in pixels.
     pub texture_size: NonZeroUsize,
+    /// Residency class of the texture's glyphs.
+    ///
+    /// Only the [`GpuCacheStrategy::Fallback`] strategy chooses between pages, so
+    /// the priority has no effect with [`GpuCacheStrategy::Fixed`].
+    pub priority: AtlasPriority,
+    /// Texels kept around each glyph inside its tile.
+    ///
+    /// Linear filtering at fractional positions samples the texels next to a
+    /// glyph's edges, so without padding it picks up neighboring glyphs. One texel
+    /// suffices for unscaled text; scaled or transformed text needs more. A glyph
+    /// fits a tile if its larger side is at most `tile_size - 2 * padding`.
+    pub padding: usize,
+    /// Whether the padding repeats the glyph's edge texels instead of being cleared.
+    ///
+    /// Cleared padding makes glyph edges fade out under filtering, which suits
+    /// glyphs with transparent borders. Repeating the edges keeps glyphs that reach
+    /// their bitmap's border, such as emoji or box-drawing characters, opaque up to
+    /// their edges.
+    pub extrude_edges: bool,
+    /// How glyphs are placed on the texture.
+    ///
+    /// Compare the [`PageStats::occupancy`] of the strategies for a workload to
+    /// pick one; packed pages evict as many least recently used glyphs as it takes
+    /// to fit a new one.
+    pub packing: AtlasPacking,
+}
+
+impl GpuCacheConfig {
+    /// Returns the largest glyph side that fits a tile, see [`Self::padding`].
+    pub fn glyph_capacity(&self) -> usize {
+        self.tile_size.get().saturating_sub(2 * self.padding)
+    }
+}
+
+/// Residency class of an atlas page, ordered from first to last evicted.
+///
+/// When every page that fits a new glyph is full, the least recently used glyph of
+/// a [`Self::Scratch`] page is evicted first, then of a [`Self::Normal`] page, and
+/// only then of a [`Self::High`] page. Giving the core glyph set of a HUD its own
+/// high-priority page keeps it resident while body text churns through the others.
+/// [`GpuCache::compact`] only moves glyphs between pages of the same class.
+///
+/// If every page that fits a glyph has the same class, the class changes nothing
+/// and pages are used as without priorities.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AtlasPriority {
+    /// Transient content; evicted before any other page.
+    Scratch,
+    /// Regular content.
+    #[default]
+    Normal,
+    /// Critical content; evicted only when no other suitable page can make room.
+    High,
+}
+
+impl AtlasPriority {
+    /// All classes in eviction order.
+    const EVICTION_ORDER: [Self; 3] = [Self::Scratch, Self::Normal, Self::High];
 }
 
 /// Manages a single texture atlas for caching glyphs.
@@ -316,7 +604,15 @@ pub struct CacheAtlas {
     tile_size: usize,
     tiles_per_axis: usize,
     texture_size: usize,
-
+    priority: AtlasPriority,
+    padding: usize,
+    extrude_edges: bool,
+    packing: AtlasPacking,
+
+    /// Free space of packed pages; `None` for grid pages.
+    packer: Option<Packer>,
+    /// Glyph boxes by slot.
+    glyph_boxes: Vec<Box2D<usize, UnknownUnit>>,
     cache_state: cache_state::CacheState,
 }
 
@@ -340,12 +636,89 @@ impl CacheAtlas {
             tile_size: config.tile_size.get(),
             tiles_per_axis: config.tiles_per_axis.get(),
             texture_size: config.texture_size.get(),
+            priority: config.priority,
+            padding: config.padding,
+            extrude_edges: config.extrude_edges,
+            packing: config.packing,
+            packer: Packer::new(config.packing, config.texture_size.get()),
+            glyph_boxes: vec![Box2D::zero(); cache_capacity.get()],
             cache_state: cache_state::CacheState::new(cache_capacity),
         }
     }
 
+    /// Creates an empty page starting in `frame`, see [`Self::new`].
+    fn new_in_frame(config: &GpuCacheConfig, frame: u64) -> Self {
+        let mut page = Self::new(config);
+        let capacity = NonZeroUsize::new(page.tiles_per_axis.pow(2)).expect("non-zero");
+        page.cache_state = cache_state::CacheState::new_in_frame(capacity, frame);
+        page
+    }
+
+    /// Returns an empty page with the same configuration, in the same frame.
+    fn empty_like(&self) -> Self {
+        Self {
+            tile_size: self.tile_size,
+            tiles_per_axis: self.tiles_per_axis,
+            texture_size: self.texture_size,
+            priority: self.priority,
+            padding: self.padding,
+            extrude_edges: self.extrude_edges,
+            packing: self.packing,
+            packer: Packer::new(self.packing, self.texture_size),
+            glyph_boxes: vec![Box2D::zero(); self.cache_state.capacity()],
+            cache_state: self.cache_state.empty_like(),
+        }
+    }
+
+    fn config(&self) -> GpuCacheConfig {
+        GpuCacheConfig {
+            tile_size: NonZeroUsize::new(self.tile_size).expect("non-zero"),
+            tiles_per_axis: NonZeroUsize::new(self.tiles_per_axis).expect("non-zero"),
+            texture_size: NonZeroUsize::new(self.texture_size).expect("non-zero"),
+            priority: self.priority,
+            padding: self.padding,
+            extrude_edges: self.extrude_edges,
+            packing: self.packing,
+        }
+    }
+
+    /// Returns the largest glyph side that fits a tile.
+    fn glyph_capacity(&self) -> usize {
+        self.tile_size.saturating_sub(2 * self.padding)
+    }
+
+    /// Returns the occupancy of the page, counting the texels of glyphs without
+    /// their padding.
+    fn stats(&self) -> PageStats {
+        PageStats {
+            used: self.cache_state.slots().count(),
+            capacity: self.cache_state.capacity(),
+            used_area: self
+                .cache_state
+                .slots()
+                .map(|i| self.glyph_boxes[i].area())
+                .sum(),
+            area: self.texture_size.pow(2),
+        }
+    }
+
     fn clear(&mut self) {
         self.cache_state.clear();
+        if let Some(packer) = &mut self.packer {
+            packer.clear();
+        }
+    }
+
+    fn remove(&mut self, glyph_id: &GlyphId) {
+        if let Some(index) = self.cache_state.remove(glyph_id) {
+            self.release(index);
+        }
+    }
+
+    fn remove_font(&mut self, font_id: fontdb::ID) {
+        for index in self.cache_state.remove_font(font_id) {
+            self.release(index);
+        }
     }
 }
 
@@ -354,28 +727,143 @@ impl CacheAtlas {
         self.cache_state.new_batch();
     }
 
+    fn new_frame(&mut self) {
+        self.cache_state.new_frame();
+    }
+
+    /// Returns whether a glyph of `size` can be added without evicting others.
+    fn has_room(&self, size: [usize; 2]) -> bool {
+        match &self.packer {
+            Some(packer) => {
+                let [width, height] = self.padded(size);
+                packer.can_allocate(width, height)
+            }
+            None => self.cache_state.has_free_slot(),
+        }
+    }
+
+    /// Returns the frame the least recently used glyph was last drawn in, if the
+    /// page has no room and that glyph may be evicted.
+    fn evictable_frame(&self) -> Option<u64> {
+        match self.packer {
+            Some(_) => self.cache_state.unprotected_tail_frame(),
+            None => self.cache_state.evictable_frame(),
+        }
+    }
+
     fn get_or_push_and_protect(
         &mut self,
         glyph_id: &GlyphId,
+        size: [usize; 2],
     ) -> Option<([usize; 2], GetOrPushResult)> {
+        if self.packer.is_some() {
+            if let Some(position) = self.get_and_protect_entry(glyph_id) {
+                return Some((position, GetOrPushResult::Hit));
+            }
+            let position = self.get_and_push_with_evicting_unprotected(glyph_id, size)?;
+            return Some((position, GetOrPushResult::NeedToUpload));
+        }
+
         let (index, result) = self.cache_state.get_or_push_and_protect(glyph_id)?;
-        let x = (index % self.tiles_per_axis) * self.tile_size;
-        let y = (index / self.tiles_per_axis) * self.tile_size;
-        Some(([x, y], result))
+        if let GetOrPushResult::NeedToUpload = result {
+            self.place_in_tile(index, size);
+        }
+        Some((self.glyph_boxes[index].min.to_array(), result))
     }
 
     fn get_and_protect_entry(&mut self, glyph_id: &GlyphId) -> Option<[usize; 2]> {
         let index = self.cache_state.get_and_protect_entry(glyph_id)?;
-        let x = (index % self.tiles_per_axis) * self.tile_size;
-        let y = (index / self.tiles_per_axis) * self.tile_size;
-        Some([x, y])
+        Some(self.glyph_boxes[index].min.to_array())
     }
 
-    fn get_and_push_with_evicting_unprotected(&mut self, glyph_id: &GlyphId) -> Option<[usize; 2]> {
-        let index = self.cache_state.push_and_evicting_unprotected(glyph_id)?;
-        let x = (index % self.tiles_per_axis) * self.tile_size;
-        let y = (index / self.tiles_per_axis) * self.tile_size;
-        Some([x, y])
+    fn get_and_push_with_evicting_unprotected(
+        &mut self,
+        glyph_id: &GlyphId,
+        size: [usize; 2],
+    ) -> Option<[usize; 2]> {
+        if self.packer.is_none() {
+            let index = self.cache_state.push_and_evicting_unprotected(glyph_id)?;
+            return Some(self.place_in_tile(index, size));
+        }
+
+        let glyph_box = self.allocate(size, true)?;
+        self.reserve_slot();
+        let index = self
+            .cache_state
+            .push_and_evicting_unprotected(glyph_id)
+            .expect("reserved a free slot");
+        self.glyph_boxes[index] = glyph_box;
+        Some(glyph_box.min.to_array())
+    }
+
+    fn insert_with_frame(
+        &mut self,
+        glyph_id: GlyphId,
+        frame: u64,
+        size: [usize; 2],
+    ) -> Option<[usize; 2]> {
+        if self.packer.is_none() {
+            let index = self.cache_state.insert_with_frame(glyph_id, frame)?;
+            return Some(self.place_in_tile(index, size));
+        }
+
+        let glyph_box = self.allocate(size, false)?;
+        self.reserve_slot();
+        let index = self
+            .cache_state
+            .insert_with_frame(glyph_id, frame)
+            .expect("reserved a free slot");
+        self.glyph_boxes[index] = glyph_box;
+        Some(glyph_box.min.to_array())
+    }
+
+    /// Records a glyph of `size` in tile `index`, inside its padding, and returns
+    /// the glyph's position.
+    fn place_in_tile(&mut self, index: usize, [width, height]: [usize; 2]) -> [usize; 2] {
+        let x = (index % self.tiles_per_axis) * self.tile_size + self.padding;
+        let y = (index / self.tiles_per_axis) * self.tile_size + self.padding;
+        self.glyph_boxes[index] =
+            Box2D::new(Point2D::new(x, y), Point2D::new(x + width, y + height));
+        [x, y]
+    }
+
+    /// Places a glyph of `size` on a packed page and returns its box without the
+    /// padding. If `evict`, evicts the least recently used unprotected glyphs until
+    /// the glyph fits.
+    fn allocate(&mut self, size: [usize; 2], evict: bool) -> Option<Box2D<usize, UnknownUnit>> {
+        let [width, height] = self.padded(size);
+        let padding = self.padding;
+        let packer = self.packer.as_mut()?;
+        loop {
+            if let Some(rect) = packer.allocate(width, height) {
+                let min = rect.min + euclid::vec2(padding, padding);
+                return Some(Box2D::new(min, min + euclid::vec2(size[0], size[1])));
+            }
+            if !evict {
+                return None;
+            }
+            let index = self.cache_state.evict_unprotected_tail()?;
+            packer.deallocate(self.glyph_boxes[index].inflate(padding, padding));
+        }
+    }
+
+    /// Frees the space of the glyph just removed from slot `index` of a packed page.
+    fn release(&mut self, index: usize) {
+        if let Some(packer) = &mut self.packer {
+            packer.deallocate(self.glyph_boxes[index].inflate(self.padding, self.padding));
+        }
+    }
+
+    /// Makes sure a packed page has a free slot for a glyph that fits its space.
+    fn reserve_slot(&mut self) {
+        if !self.cache_state.has_free_slot() {
+            self.cache_state.add_slot();
+            self.glyph_boxes.push(Box2D::zero());
+        }
+    }
+
+    fn padded(&self, [width, height]: [usize; 2]) -> [usize; 2] {
+        [width + 2 * self.padding, height + 2 * self.padding]
     }
 }
 
@@ -424,15 +912,15 @@ pub enum GpuCacheStrategy {
 }
 
 pub struct FixedGpuCache {
-    /// must be sorted by tile size
+    /// must be sorted by glyph capacity
     caches: Vec<CacheAtlas>,
 }
 
 impl FixedGpuCache {
     fn new(configs: &[GpuCacheConfig]) -> Self {
-        // sort by tile size
+        // sort by glyph capacity
         let mut configs = configs.to_vec();
-        configs.sort_by_key(|config| config.tile_size.get());
+        configs.sort_by_key(GpuCacheConfig::glyph_capacity);
 
         Self {
             caches: configs.iter().map(CacheAtlas::new).collect(),
@@ -445,35 +933,43 @@ impl FixedGpuCache {
         }
     }
 
+    fn remove_font(&mut self, font_id: fontdb::ID) {
+        for cache in &mut self.caches {
+            cache.remove_font(font_id);
+        }
+    }
+
     fn new_batch(&mut self) {
         for cache in &mut self.caches {
             cache.new_batch();
         }
     }
 
+    fn new_frame(&mut self) {
+        for cache in &mut self.caches {
+            cache.new_frame();
+        }
+    }
+
     fn get_or_push_and_protect(
         &mut self,
         glyph_id: &GlyphId,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
     ) -> Option<(GpuCacheItem, GetOrPushResult)> {
-        let glyph_index = glyph_id.glyph_index();
-        let font_size = glyph_id.font_size();
-        let font_id = glyph_id.font_id();
-
-        let font = font_storage.font(font_id)?;
-        let glyph_metrics = font.metrics_indexed(glyph_index, font_size);
-        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height) + ATLAS_MARGIN;
+        let glyph_metrics = crate::renderer::glyph_metrics(glyph_id, font_storage)?;
+        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height);
 
         let cache_index = self
             .caches
             .iter()
-            .position(|cache| glyph_bitmap_size <= cache.tile_size)?;
+            .position(|cache| glyph_bitmap_size <= cache.glyph_capacity())?;
 
         let cache = &mut self.caches[cache_index];
         let texture_index = cache_index;
         let texture_size = cache.texture_size;
 
-        let ([x_min, y_min], result) = cache.get_or_push_and_protect(glyph_id)?;
+        let ([x_min, y_min], result) =
+            cache.get_or_push_and_protect(glyph_id, [glyph_metrics.width, glyph_metrics.height])?;
         let x_max = x_min + glyph_metrics.width;
         let y_max = y_min + glyph_metrics.height;
         let glyph_box = Box2D::new(Point2D::new(x_min, y_min), Point2D::new(x_max, y_max));
@@ -491,20 +987,15 @@ impl FixedGpuCache {
     fn get_and_protect_entry(
         &mut self,
         glyph_id: &GlyphId,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
     ) -> Option<GpuCacheItem> {
-        let glyph_index = glyph_id.glyph_index();
-        let font_size = glyph_id.font_size();
-        let font_id = glyph_id.font_id();
-
-        let font = font_storage.font(font_id)?;
-        let glyph_metrics = font.metrics_indexed(glyph_index, font_size);
-        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height) + ATLAS_MARGIN;
+        let glyph_metrics = crate::renderer::glyph_metrics(glyph_id, font_storage)?;
+        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height);
 
         let cache_index = self
             .caches
             .iter()
-            .position(|cache| glyph_bitmap_size <= cache.tile_size)?;
+            .position(|cache| glyph_bitmap_size <= cache.glyph_capacity())?;
 
         let cache = &mut self.caches[cache_index];
         let texture_index = cache_index;
@@ -525,25 +1016,23 @@ impl FixedGpuCache {
     fn push_and_evicting_unprotected(
         &mut self,
         glyph_id: &GlyphId,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
     ) -> Option<GpuCacheItem> {
-        let glyph_index = glyph_id.glyph_index();
-        let font_size = glyph_id.font_size();
-        let font_id = glyph_id.font_id();
-
-        let font = font_storage.font(font_id)?;
-        let glyph_metrics = font.metrics_indexed(glyph_index, font_size);
-        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height) + ATLAS_MARGIN;
+        let glyph_metrics = crate::renderer::glyph_metrics(glyph_id, font_storage)?;
+        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height);
 
         let cache_index = self
             .caches
             .iter()
-            .position(|cache| glyph_bitmap_size <= cache.tile_size)?;
+            .position(|cache| glyph_bitmap_size <= cache.glyph_capacity())?;
 
         let cache = &mut self.caches[cache_index];
         let texture_index = cache_index;
         let texture_size = cache.texture_size;
-        let [x_min, y_min] = cache.get_and_push_with_evicting_unprotected(glyph_id)?;
+        let [x_min, y_min] = cache.get_and_push_with_evicting_unprotected(
+            glyph_id,
+            [glyph_metrics.width, glyph_metrics.height],
+        )?;
         let x_max = x_min + glyph_metrics.width;
         let y_max = y_min + glyph_metrics.height;
 
@@ -558,18 +1047,27 @@ impl FixedGpuCache {
 }
 
 pub struct FallbackGpuCache {
-    /// must be sorted by tile size
+    /// Pages in creation order; the index of a page is its texture index.
     caches: Vec<CacheAtlas>,
+    /// Page indices sorted by glyph capacity.
+    order: Vec<usize>,
+    /// Bytes left for pages added when every page a glyph fits is full.
+    growth_budget: usize,
+    /// Bytes left for overflow pages, added for glyphs larger than every tile.
+    overflow_budget: usize,
 }
 
 impl FallbackGpuCache {
     fn new(configs: &[GpuCacheConfig]) -> Self {
-        // sort by tile size
+        // sort by glyph capacity
         let mut configs = configs.to_vec();
-        configs.sort_by_key(|config| config.tile_size.get());
+        configs.sort_by_key(GpuCacheConfig::glyph_capacity);
 
         Self {
             caches: configs.iter().map(CacheAtlas::new).collect(),
+            order: (0..configs.len()).collect(),
+            growth_budget: 0,
+            overflow_budget: 0,
         }
     }
 
@@ -579,32 +1077,77 @@ impl FallbackGpuCache {
         }
     }
 
+    fn remove_font(&mut self, font_id: fontdb::ID) {
+        for cache in &mut self.caches {
+            cache.remove_font(font_id);
+        }
+    }
+
     fn new_batch(&mut self) {
         for cache in &mut self.caches {
             cache.new_batch();
         }
     }
 
+    fn new_frame(&mut self) {
+        for cache in &mut self.caches {
+            cache.new_frame();
+        }
+    }
+
+    /// Returns the position in `order` of the smallest page fitting a glyph.
+    fn first_suitable(&self, glyph_bitmap_size: usize) -> Option<usize> {
+        self.order
+            .iter()
+            .position(|&i| glyph_bitmap_size <= self.caches[i].glyph_capacity())
+    }
+
+    /// Like [`Self::first_suitable`], but adds an overflow page when no page fits
+    /// the glyph and the overflow budget allows.
+    ///
+    /// Overflow pages have tiles of the padded glyph's size rounded up to a power of
+    /// two, so glyphs of similar sizes share them, and are evicted first. They pad
+    /// their glyphs like the page with the largest tiles.
+    fn first_suitable_or_overflow(&mut self, glyph_bitmap_size: usize) -> Option<usize> {
+        if let Some(position) = self.first_suitable(glyph_bitmap_size) {
+            return Some(position);
+        }
+
+        let largest = &self.caches[*self.order.last()?];
+        let (padding, extrude_edges) = (largest.padding, largest.extrude_edges);
+        let tile_size = glyph_bitmap_size
+            .checked_add(padding.checked_mul(2)?)?
+            .checked_next_power_of_two()?;
+        let texture_size = tile_size.checked_mul(OVERFLOW_TILES_PER_AXIS)?;
+        let bytes = texture_size.checked_pow(2)?;
+        self.overflow_budget = self.overflow_budget.checked_sub(bytes)?;
+
+        let config = GpuCacheConfig {
+            tile_size: NonZeroUsize::new(tile_size)?,
+            tiles_per_axis: NonZeroUsize::new(OVERFLOW_TILES_PER_AXIS)?,
+            texture_size: NonZeroUsize::new(texture_size)?,
+            priority: AtlasPriority::Scratch,
+            padding,
+            extrude_edges,
+            packing: AtlasPacking::Grid,
+        };
+        let frame = self.caches[0].cache_state.current_frame();
+        let index = self.add_page(CacheAtlas::new_in_frame(&config, frame));
+        self.order.iter().position(|&i| i == index)
+    }
+
     fn get_or_push_and_protect(
         &mut self,
         glyph_id: &GlyphId,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
     ) -> Option<(GpuCacheItem, GetOrPushResult)> {
-        let glyph_index = glyph_id.glyph_index();
-        let font_size = glyph_id.font_size();
-        let font_id = glyph_id.font_id();
-
-        let font = font_storage.font(font_id)?;
-        let glyph_metrics = font.metrics_indexed(glyph_index, font_size);
-        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height) + ATLAS_MARGIN;
+        let glyph_metrics = crate::renderer::glyph_metrics(glyph_id, font_storage)?;
+        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height);
 
-        let start_index = self
-            .caches
-            .iter()
-            .position(|cache| glyph_bitmap_size <= cache.tile_size)?;
+        let start = self.first_suitable_or_overflow(glyph_bitmap_size)?;
 
         // Phase 1: Try to find existing entry in any suitable cache
-        for i in start_index..self.caches.len() {
+        for &i in &self.order[start..] {
             if let Some([x_min, y_min]) = self.caches[i].get_and_protect_entry(glyph_id) {
                 let cache = &self.caches[i];
                 let texture_index = i;
@@ -624,58 +1167,37 @@ impl FallbackGpuCache {
             }
         }
 
-        // Phase 2: Try to push to any suitable cache
-        for i in start_index..self.caches.len() {
-            // We use push_and_evicting_unprotected here because we want to try to insert.
-            // If it fails (returns None), it means the cache is full of protected items.
-            // Note: get_or_push_and_protect on CacheAtlas does both get and push, but we already did get in Phase 1.
-            // However, CacheAtlas::get_or_push_and_protect is more efficient if we were only checking one cache.
-            // But here we are iterating.
-            // Actually, we can use push_and_evicting_unprotected directly.
-
-            if let Some([x_min, y_min]) =
-                self.caches[i].get_and_push_with_evicting_unprotected(glyph_id)
-            {
-                let cache = &self.caches[i];
-                let texture_index = i;
-                let texture_size = cache.texture_size;
-                let x_max = x_min + glyph_metrics.width;
-                let y_max = y_min + glyph_metrics.height;
-                let glyph_box = Box2D::new(Point2D::new(x_min, y_min), Point2D::new(x_max, y_max));
-
-                return Some((
-                    GpuCacheItem {
-                        texture_index,
-                        texture_size,
-                        glyph_box,
-                    },
-                    GetOrPushResult::NeedToUpload,
-                ));
-            }
-        }
+        // Phase 2: Push to a suitable cache, evicting by page priority if all are full
+        let (i, [x_min, y_min]) =
+            self.push_by_priority(start, glyph_id, [glyph_metrics.width, glyph_metrics.height])?;
+        let cache = &self.caches[i];
+        let texture_index = i;
+        let texture_size = cache.texture_size;
+        let x_max = x_min + glyph_metrics.width;
+        let y_max = y_min + glyph_metrics.height;
+        let glyph_box = Box2D::new(Point2D::new(x_min, y_min), Point2D::new(x_max, y_max));
 
-        None
+        Some((
+            GpuCacheItem {
+                texture_index,
+                texture_size,
+                glyph_box,
+            },
+            GetOrPushResult::NeedToUpload,
+        ))
     }
 
     fn get_and_protect_entry(
         &mut self,
         glyph_id: &GlyphId,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
     ) -> Option<GpuCacheItem> {
-        let glyph_index = glyph_id.glyph_index();
-        let font_size = glyph_id.font_size();
-        let font_id = glyph_id.font_id();
+        let glyph_metrics = crate::renderer::glyph_metrics(glyph_id, font_storage)?;
+        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height);
 
-        let font = font_storage.font(font_id)?;
-        let glyph_metrics = font.metrics_indexed(glyph_index, font_size);
-        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height) + ATLAS_MARGIN;
+        let start = self.first_suitable(glyph_bitmap_size)?;
 
-        let start_index = self
-            .caches
-            .iter()
-            .position(|cache| glyph_bitmap_size <= cache.tile_size)?;
-
-        for i in start_index..self.caches.len() {
+        for &i in &self.order[start..] {
             if let Some([x_min, y_min]) = self.caches[i].get_and_protect_entry(glyph_id) {
                 let cache = &self.caches[i];
                 let texture_index = i;
@@ -698,42 +1220,149 @@ impl FallbackGpuCache {
     fn push_and_evicting_unprotected(
         &mut self,
         glyph_id: &GlyphId,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
     ) -> Option<GpuCacheItem> {
-        let glyph_index = glyph_id.glyph_index();
-        let font_size = glyph_id.font_size();
-        let font_id = glyph_id.font_id();
+        let glyph_metrics = crate::renderer::glyph_metrics(glyph_id, font_storage)?;
+        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height);
 
-        let font = font_storage.font(font_id)?;
-        let glyph_metrics = font.metrics_indexed(glyph_index, font_size);
-        let glyph_bitmap_size = glyph_metrics.width.max(glyph_metrics.height) + ATLAS_MARGIN;
+        let start = self.first_suitable_or_overflow(glyph_bitmap_size)?;
 
-        let start_index = self
-            .caches
-            .iter()
-            .position(|cache| glyph_bitmap_size <= cache.tile_size)?;
+        let (i, [x_min, y_min]) =
+            self.push_by_priority(start, glyph_id, [glyph_metrics.width, glyph_metrics.height])?;
+        let cache = &self.caches[i];
+        let texture_index = i;
+        let texture_size = cache.texture_size;
+        let x_max = x_min + glyph_metrics.width;
+        let y_max = y_min + glyph_metrics.height;
+        let glyph_box = Box2D::new(Point2D::new(x_min, y_min), Point2D::new(x_max, y_max));
 
-        for i in start_index..self.caches.len() {
-            if let Some([x_min, y_min]) =
-                self.caches[i].get_and_push_with_evicting_unprotected(glyph_id)
-            {
-                let cache = &self.caches[i];
-                let texture_index = i;
-                let texture_size = cache.texture_size;
-                let x_max = x_min + glyph_metrics.width;
-                let y_max = y_min + glyph_metrics.height;
-                let glyph_box = Box2D::new(Point2D::new(x_min, y_min), Point2D::new(x_max, y_max));
+        Some(GpuCacheItem {
+            texture_index,
+            texture_size,
+            glyph_box,
+        })
+    }
 
-                return Some(GpuCacheItem {
-                    texture_index,
-                    texture_size,
+    /// Moves up to `limit` glyphs into free tiles of pages before theirs in glyph
+    /// capacity order, i.e. pages fitting smaller or equal glyphs, and returns the
+    /// moved glyphs with their old and new places.
+    ///
+    /// Glyphs only move between pages of the same priority, so high-priority
+    /// pages neither lose their glyphs nor fill up with other ones, and between
+    /// pages with the same padding, so the padded texels can be copied as they
+    /// are. Pinned glyphs stay where prepared draws sample them.
+    fn compact(&mut self, limit: usize) -> Vec<(GlyphId, GpuCacheItem, GpuCacheItem)> {
+        let mut moved = Vec::new();
+        for position in 1..self.order.len() {
+            let source = self.order[position];
+            // Most recently used first, so the glyphs most likely to be drawn again
+            // get the free tiles.
+            for (glyph_id, slot, frame) in self.caches[source].cache_state.entries() {
+                if moved.len() == limit {
+                    return moved;
+                }
+                let page = &self.caches[source];
+                if page.cache_state.is_pinned(&glyph_id) {
+                    continue;
+                }
+                let glyph_box = page.glyph_boxes[slot];
+                let size = [glyph_box.width(), glyph_box.height()];
+                let Some(target) = self.order[..position].iter().copied().find(|&i| {
+                    let cache = &self.caches[i];
+                    cache.priority == page.priority
+                        && cache.padding == page.padding
+                        && cache.extrude_edges == page.extrude_edges
+                        && size[0].max(size[1]) <= cache.glyph_capacity()
+                        && cache.has_room(size)
+                }) else {
+                    continue;
+                };
+                let from = GpuCacheItem {
+                    texture_index: source,
+                    texture_size: page.texture_size,
                     glyph_box,
-                });
+                };
+
+                self.caches[source].remove(&glyph_id);
+                let [x_min, y_min] = self.caches[target]
+                    .insert_with_frame(glyph_id, frame, size)
+                    .expect("checked free slot");
+                let to = GpuCacheItem {
+                    texture_index: target,
+                    texture_size: self.caches[target].texture_size,
+                    glyph_box: Box2D::new(
+                        Point2D::new(x_min, y_min),
+                        Point2D::new(x_min + size[0], y_min + size[1]),
+                    ),
+                };
+                moved.push((glyph_id, from, to));
+            }
+        }
+        moved
+    }
+
+    /// Pushes into the smallest suitable cache with room for the glyph. When every
+    /// suitable cache is full, adds a page like the smallest suitable one if the growth
+    /// budget allows, and otherwise evicts from scratch pages first, then normal
+    /// and high ones.
+    ///
+    /// Within a priority class, the glyph drawn the most frames ago is evicted,
+    /// whichever page it is on, so glyphs in use survive pressure on other sizes.
+    ///
+    /// `start` is the position in `order` of the smallest suitable page, and `size`
+    /// the glyph's size. Returns the cache index and the glyph position.
+    fn push_by_priority(
+        &mut self,
+        start: usize,
+        glyph_id: &GlyphId,
+        size: [usize; 2],
+    ) -> Option<(usize, [usize; 2])> {
+        let free = self.order[start..]
+            .iter()
+            .copied()
+            .find(|&i| self.caches[i].has_room(size));
+        if let Some(i) = free.or_else(|| self.grow(self.order[start])) {
+            return self.caches[i]
+                .get_and_push_with_evicting_unprotected(glyph_id, size)
+                .map(|position| (i, position));
+        }
+
+        for priority in AtlasPriority::EVICTION_ORDER {
+            let oldest = self.order[start..]
+                .iter()
+                .copied()
+                .filter(|&i| self.caches[i].priority == priority)
+                .filter_map(|i| Some((self.caches[i].evictable_frame()?, i)))
+                .min();
+            if let Some((_, i)) = oldest {
+                return self.caches[i]
+                    .get_and_push_with_evicting_unprotected(glyph_id, size)
+                    .map(|position| (i, position));
             }
         }
 
         None
     }
+
+    /// Adds an empty page configured like page `template` if the growth budget
+    /// allows, and returns its index.
+    fn grow(&mut self, template: usize) -> Option<usize> {
+        let bytes = self.caches[template].texture_size.checked_pow(2)?;
+        self.growth_budget = self.growth_budget.checked_sub(bytes)?;
+        let page = self.caches[template].empty_like();
+        Some(self.add_page(page))
+    }
+
+    /// Appends `page`, keeping `order` sorted by glyph capacity, and returns its index.
+    fn add_page(&mut self, page: CacheAtlas) -> usize {
+        let index = self.caches.len();
+        let position = self
+            .order
+            .partition_point(|&i| self.caches[i].glyph_capacity() <= page.glyph_capacity());
+        self.caches.push(page);
+        self.order.insert(position, index);
+        index
+    }
 }
 
 /// Manages the GPU glyph cache, using one of the available strategies.
@@ -767,6 +1396,152 @@ impl GpuCache {
         }
     }
 
+    /// Frees the slots of all glyphs of font `font_id`.
+    pub fn remove_font(&mut self, font_id: fontdb::ID) {
+        match self {
+            Self::Fixed(c) => c.remove_font(font_id),
+            Self::Fallback(c) => c.remove_font(font_id),
+        }
+    }
+
+    /// Sets how many bytes of pages may be added when the cache overflows.
+    ///
+    /// When every page a new glyph fits is full, the fallback strategy adds an
+    /// empty page configured like the smallest of them instead of evicting, as
+    /// long as its `texture_size²` bytes fit into the budget. Added pages are
+    /// kept until the cache is dropped, and their texture indices follow the
+    /// existing ones. The fixed strategy never adds pages. Defaults to 0.
+    pub fn set_growth_budget(&mut self, bytes: usize) {
+        match self {
+            Self::Fixed(_) => {}
+            Self::Fallback(c) => c.growth_budget = bytes,
+        }
+    }
+
+    /// Returns the bytes left for adding pages, see [`Self::set_growth_budget`].
+    pub fn growth_budget(&self) -> usize {
+        match self {
+            Self::Fixed(_) => 0,
+            Self::Fallback(c) => c.growth_budget,
+        }
+    }
+
+    /// Sets how many bytes of overflow pages may be added for oversized glyphs.
+    ///
+    /// A glyph larger than every tile is drawn standalone each frame. With an
+    /// overflow budget, the fallback strategy instead adds a scratch page of 2×2
+    /// tiles sized to the glyph, rounded up to a power of two, as long as its
+    /// `texture_size²` bytes fit into the budget. Later glyphs of similar sizes
+    /// share the page. Like grown pages, overflow pages are kept until the cache
+    /// is dropped. The fixed strategy never adds pages. Defaults to 0.
+    pub fn set_overflow_budget(&mut self, bytes: usize) {
+        match self {
+            Self::Fixed(_) => {}
+            Self::Fallback(c) => c.overflow_budget = bytes,
+        }
+    }
+
+    /// Returns the bytes left for overflow pages, see [`Self::set_overflow_budget`].
+    pub fn overflow_budget(&self) -> usize {
+        match self {
+            Self::Fixed(_) => 0,
+            Self::Fallback(c) => c.overflow_budget,
+        }
+    }
+
+    /// Returns the number of pages, including added ones.
+    pub fn page_count(&self) -> usize {
+        self.pages().len()
+    }
+
+    /// Returns the configuration of the page with texture index `index`.
+    pub fn page_config(&self, index: usize) -> Option<GpuCacheConfig> {
+        self.pages().get(index).map(CacheAtlas::config)
+    }
+
+    /// Returns the evictions and the occupancy of each page, by texture index.
+    ///
+    /// Lookups are counted by the renderer using the cache; their counters are zero.
+    pub fn stats(&self) -> CacheStats {
+        let pages = self.pages();
+        CacheStats {
+            evictions: pages.iter().map(|page| page.cache_state.evictions()).sum(),
+            pages: pages.iter().map(CacheAtlas::stats).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Resets the eviction counters of all pages.
+    pub fn reset_stats(&mut self) {
+        for page in self.pages_mut() {
+            page.cache_state.reset_evictions();
+        }
+    }
+
+    fn pages(&self) -> &[CacheAtlas] {
+        match self {
+            Self::Fixed(c) => &c.caches,
+            Self::Fallback(c) => &c.caches,
+        }
+    }
+
+    fn pages_mut(&mut self) -> &mut [CacheAtlas] {
+        match self {
+            Self::Fixed(c) => &mut c.caches,
+            Self::Fallback(c) => &mut c.caches,
+        }
+    }
+
+    /// Pins the glyphs used in the current batch and returns them.
+    ///
+    /// Pinned glyphs are neither evicted nor moved by [`Self::compact`], so
+    /// draws sampling them stay valid while later batches fill the atlas. Every
+    /// pin is released by passing the returned glyphs to [`Self::unpin`].
+    /// Clearing the cache or removing a font drops their glyphs regardless.
+    pub fn pin_batch(&mut self) -> Vec<GlyphId> {
+        self.pages_mut()
+            .iter_mut()
+            .flat_map(|page| page.cache_state.pin_batch())
+            .collect()
+    }
+
+    /// Releases the pins taken by [`Self::pin_batch`] on `glyphs`.
+    pub fn unpin(&mut self, glyphs: &[GlyphId]) {
+        let pages = self.pages_mut();
+        for glyph_id in glyphs {
+            for page in pages.iter_mut() {
+                if page.cache_state.unpin(glyph_id) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Returns every cached glyph with its place, without marking it used.
+    pub fn items(&self) -> Vec<(GlyphId, GpuCacheItem)> {
+        self.pages()
+            .iter()
+            .enumerate()
+            .flat_map(|(texture_index, page)| {
+                page.cache_state.glyphs().map(move |(glyph_id, index)| {
+                    let item = GpuCacheItem {
+                        texture_index,
+                        texture_size: page.texture_size,
+                        glyph_box: page.glyph_boxes[index],
+                    };
+                    (glyph_id, item)
+                })
+            })
+            .collect()
+    }
+
+    /// Returns whether `glyph_id` is cached on any page, without marking it used.
+    pub fn contains(&self, glyph_id: &GlyphId) -> bool {
+        self.pages()
+            .iter()
+            .any(|page| page.cache_state.contains(glyph_id))
+    }
+
     /// Marks start of a new batch.
     pub fn new_batch(&mut self) {
         match self {
@@ -775,11 +1550,37 @@ impl GpuCache {
         }
     }
 
+    /// Marks start of a new frame.
+    ///
+    /// Glyphs remember the frame they were last drawn in; when the pages a new
+    /// glyph fits are full, the one drawn longest ago is evicted.
+    pub fn new_frame(&mut self) {
+        match self {
+            Self::Fixed(c) => c.new_frame(),
+            Self::Fallback(c) => c.new_frame(),
+        }
+    }
+
+    /// Moves up to `limit` cached glyphs into free tiles of pages with smaller or
+    /// equal tiles and returns the moved glyphs with their old and new places,
+    /// whose texels must be copied in order.
+    ///
+    /// After heavy eviction, glyphs that spilled into larger pages return to the
+    /// pages sized for them, freeing the larger tiles for glyphs that need them.
+    /// Glyphs only move between pages of the same priority and padding. The fixed
+    /// strategy places each glyph on a single page and never moves glyphs.
+    pub fn compact(&mut self, limit: usize) -> Vec<(GlyphId, GpuCacheItem, GpuCacheItem)> {
+        match self {
+            Self::Fixed(_) => Vec::new(),
+            Self::Fallback(c) => c.compact(limit),
+        }
+    }
+
     /// Gets existing or adds new glyph, marking it used.
     pub fn get_or_push_and_protect(
         &mut self,
         glyph_id: &GlyphId,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
     ) -> Option<(GpuCacheItem, GetOrPushResult)> {
         match self {
             Self::Fixed(c) => c.get_or_push_and_protect(glyph_id, font_storage),
@@ -791,7 +1592,7 @@ impl GpuCache {
     pub fn get_and_protect_entry(
         &mut self,
         glyph_id: &GlyphId,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
     ) -> Option<GpuCacheItem> {
         match self {
             Self::Fixed(c) => c.get_and_protect_entry(glyph_id, font_storage),
@@ -803,7 +1604,7 @@ impl GpuCache {
     pub fn push_and_evicting_unprotected(
         &mut self,
         glyph_id: &GlyphId,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
     ) -> Option<GpuCacheItem> {
         match self {
             Self::Fixed(c) => c.push_and_evicting_unprotected(glyph_id, font_storage),
@@ -811,3 +1612,448 @@ impl GpuCache {
         }
     }
 }
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::make_id;
+
+    /// A procedural glyph, so no font has to be loaded.
+    fn glyph(ch: char) -> GlyphId {
+        GlyphId::hex_box(make_id(0), ch, 8.0)
+    }
+
+    /// A page holding a single tile.
+    fn page(priority: AtlasPriority) -> GpuCacheConfig {
+        GpuCacheConfig {
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(1).unwrap(),
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            priority,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        }
+    }
+
+    /// Pushes `ch` in a new batch and returns the page it was placed on.
+    fn push(cache: &mut GpuCache, font_storage: &FontStorage, ch: char) -> usize {
+        cache.new_batch();
+        let (item, _) = cache
+            .get_or_push_and_protect(&glyph(ch), font_storage)
+            .unwrap();
+        item.texture_index
+    }
+
+    #[test]
+    fn test_eviction_prefers_scratch_pages() {
+        let font_storage = FontStorage::new();
+        let mut cache = GpuCache::new(&[page(AtlasPriority::High), page(AtlasPriority::Scratch)]);
+
+        // Free tiles are filled regardless of priority.
+        assert_eq!(push(&mut cache, &font_storage, 'a'), 0);
+        assert_eq!(push(&mut cache, &font_storage, 'b'), 1);
+
+        // Once full, the scratch page is evicted even though its glyph is newer.
+        assert_eq!(push(&mut cache, &font_storage, 'c'), 1);
+        assert_eq!(push(&mut cache, &font_storage, 'd'), 1);
+        cache.new_batch();
+        let item = cache.get_and_protect_entry(&glyph('a'), &font_storage);
+        assert_eq!(item.map(|item| item.texture_index), Some(0));
+    }
+
+    #[test]
+    fn test_high_priority_page_evicted_last() {
+        let font_storage = FontStorage::new();
+        let mut cache = GpuCache::new(&[page(AtlasPriority::High), page(AtlasPriority::Normal)]);
+        push(&mut cache, &font_storage, 'a');
+        push(&mut cache, &font_storage, 'b');
+
+        // Within one batch the normal page's glyph is protected, so the high page gives way.
+        cache.new_batch();
+        let (item, _) = cache
+            .get_or_push_and_protect(&glyph('c'), &font_storage)
+            .unwrap();
+        assert_eq!(item.texture_index, 1);
+        let (item, _) = cache
+            .get_or_push_and_protect(&glyph('d'), &font_storage)
+            .unwrap();
+        assert_eq!(item.texture_index, 0);
+
+        // Every page now holds a glyph of the current batch.
+        assert!(
+            cache
+                .get_or_push_and_protect(&glyph('e'), &font_storage)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_push_uses_free_slots_while_tail_is_protected() {
+        let font_storage = FontStorage::new();
+        for strategy in [GpuCacheStrategy::Fixed, GpuCacheStrategy::Fallback] {
+            let four_tiles = GpuCacheConfig {
+                tiles_per_axis: NonZeroUsize::new(2).unwrap(),
+                texture_size: NonZeroUsize::new(128).unwrap(),
+                ..page(AtlasPriority::Normal)
+            };
+            let mut cache = GpuCache::new_with_strategy(&[four_tiles], strategy);
+            cache.new_batch();
+            let a = cache
+                .push_and_evicting_unprotected(&glyph('a'), &font_storage)
+                .unwrap();
+            // 'a' is protected in this batch, but three tiles are still free.
+            let b = cache
+                .push_and_evicting_unprotected(&glyph('b'), &font_storage)
+                .unwrap();
+            assert_ne!(a.glyph_box.min, b.glyph_box.min);
+        }
+    }
+
+    #[test]
+    fn test_pinned_glyphs_are_not_evicted_until_unpinned() {
+        let font_storage = FontStorage::new();
+        let four_tiles = GpuCacheConfig {
+            tiles_per_axis: NonZeroUsize::new(2).unwrap(),
+            texture_size: NonZeroUsize::new(128).unwrap(),
+            ..page(AtlasPriority::Normal)
+        };
+        let mut cache = GpuCache::new(&[four_tiles]);
+        push(&mut cache, &font_storage, 'a');
+        let pinned = cache.pin_batch();
+        assert_eq!(pinned, [glyph('a')]);
+        for ch in ['b', 'c', 'd'] {
+            push(&mut cache, &font_storage, ch);
+        }
+
+        // 'a' is the least recently used glyph, but pinned.
+        push(&mut cache, &font_storage, 'e');
+        assert!(cache.contains(&glyph('a')));
+        assert!(!cache.contains(&glyph('b')));
+
+        cache.unpin(&pinned);
+        push(&mut cache, &font_storage, 'f');
+        assert!(!cache.contains(&glyph('a')));
+    }
+
+    #[test]
+    fn test_eviction_follows_frames_across_pages() {
+        let font_storage = FontStorage::new();
+        let small = GpuCacheConfig {
+            tile_size: NonZeroUsize::new(32).unwrap(),
+            texture_size: NonZeroUsize::new(32).unwrap(),
+            ..page(AtlasPriority::Normal)
+        };
+        let mut cache = GpuCache::new(&[small, page(AtlasPriority::Normal)]);
+        assert_eq!(push(&mut cache, &font_storage, 'a'), 0);
+        assert_eq!(push(&mut cache, &font_storage, 'b'), 1);
+
+        // 'a' stays in use while 'b' is not drawn for a frame.
+        cache.new_frame();
+        assert_eq!(push(&mut cache, &font_storage, 'a'), 0);
+        cache.new_frame();
+
+        // The stale glyph on the larger page is evicted rather than the hot one.
+        assert_eq!(push(&mut cache, &font_storage, 'c'), 1);
+        cache.new_batch();
+        assert!(
+            cache
+                .get_and_protect_entry(&glyph('a'), &font_storage)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_compact_moves_glyphs_to_smaller_pages() {
+        let font_storage = FontStorage::new();
+        let small = GpuCacheConfig {
+            tile_size: NonZeroUsize::new(32).unwrap(),
+            texture_size: NonZeroUsize::new(32).unwrap(),
+            ..page(AtlasPriority::Normal)
+        };
+        let mut cache = GpuCache::new(&[
+            small,
+            page(AtlasPriority::Normal),
+            page(AtlasPriority::High),
+        ]);
+        let other = GlyphId::hex_box(make_id(1), 'a', 8.0);
+        cache.new_batch();
+        let (item, _) = cache
+            .get_or_push_and_protect(&other, &font_storage)
+            .unwrap();
+        assert_eq!(item.texture_index, 0);
+        // 'b' and 'c' spill into the larger pages.
+        assert_eq!(push(&mut cache, &font_storage, 'b'), 1);
+        assert_eq!(push(&mut cache, &font_storage, 'c'), 2);
+
+        // Once the small page is free, the normal glyph moves back; the high page keeps its glyph.
+        cache.remove_font(make_id(1));
+        let moved = cache.compact(usize::MAX);
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].0, glyph('b'));
+        assert_eq!(moved[0].1.texture_index, 1);
+        assert_eq!(moved[0].2.texture_index, 0);
+        assert_eq!(moved[0].1.glyph_box.size(), moved[0].2.glyph_box.size());
+        cache.new_batch();
+        let item = cache.get_and_protect_entry(&glyph('b'), &font_storage);
+        assert_eq!(item.map(|item| item.texture_index), Some(0));
+        let item = cache.get_and_protect_entry(&glyph('c'), &font_storage);
+        assert_eq!(item.map(|item| item.texture_index), Some(2));
+        assert!(cache.compact(usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_compact_moves_up_to_limit_and_keeps_lru_order() {
+        let font_storage = FontStorage::new();
+        let small = GpuCacheConfig {
+            tiles_per_axis: NonZeroUsize::new(2).unwrap(),
+            texture_size: NonZeroUsize::new(128).unwrap(),
+            ..page(AtlasPriority::Normal)
+        };
+        let large = GpuCacheConfig {
+            tile_size: NonZeroUsize::new(128).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(2).unwrap(),
+            texture_size: NonZeroUsize::new(256).unwrap(),
+            ..page(AtlasPriority::Normal)
+        };
+        let mut cache = GpuCache::new(&[small, large]);
+        let other = |ch| GlyphId::hex_box(make_id(1), ch, 8.0);
+
+        // The small page is full within the batch, so 'e' and 'f' spill.
+        cache.new_batch();
+        let pages: Vec<usize> = [other('a'), other('b'), glyph('c'), glyph('d')]
+            .iter()
+            .chain(&[glyph('e'), glyph('f')])
+            .map(|glyph_id| {
+                let (item, _) = cache
+                    .get_or_push_and_protect(glyph_id, &font_storage)
+                    .unwrap();
+                item.texture_index
+            })
+            .collect();
+        assert_eq!(pages, [0, 0, 0, 0, 1, 1]);
+        cache.new_frame();
+        push(&mut cache, &font_storage, 'f');
+        cache.new_frame();
+        push(&mut cache, &font_storage, 'c');
+        cache.remove_font(make_id(1));
+
+        // The most recently drawn spilled glyph moves first.
+        let moved = cache.compact(1);
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].0, glyph('f'));
+        let moved = cache.compact(usize::MAX);
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].0, glyph('e'));
+
+        // The moved glyphs keep the frames they were last drawn in.
+        let order: Vec<GlyphId> = cache.pages()[0]
+            .cache_state
+            .entries()
+            .into_iter()
+            .map(|(glyph_id, _, _)| glyph_id)
+            .collect();
+        assert_eq!(order, [glyph('c'), glyph('f'), glyph('e'), glyph('d')]);
+    }
+
+    #[test]
+    fn test_growth_adds_pages_within_budget() {
+        let font_storage = FontStorage::new();
+        let small = GpuCacheConfig {
+            tile_size: NonZeroUsize::new(32).unwrap(),
+            texture_size: NonZeroUsize::new(32).unwrap(),
+            ..page(AtlasPriority::Normal)
+        };
+        let mut cache = GpuCache::new(&[small, page(AtlasPriority::Normal)]);
+        cache.set_growth_budget(32 * 32);
+
+        assert_eq!(push(&mut cache, &font_storage, 'a'), 0);
+        assert_eq!(push(&mut cache, &font_storage, 'b'), 1);
+        // Both pages are full, so a page like the smallest suitable one is added.
+        assert_eq!(push(&mut cache, &font_storage, 'c'), 2);
+        assert_eq!(cache.page_count(), 3);
+        assert_eq!(cache.page_config(2).unwrap().tile_size.get(), 32);
+        assert_eq!(cache.growth_budget(), 0);
+
+        // With the budget spent, the least recently drawn glyph is evicted.
+        cache.new_frame();
+        cache.new_batch();
+        for ch in ['b', 'c'] {
+            assert!(
+                cache
+                    .get_and_protect_entry(&glyph(ch), &font_storage)
+                    .is_some()
+            );
+        }
+        assert_eq!(push(&mut cache, &font_storage, 'd'), 0);
+        assert_eq!(cache.page_count(), 3);
+    }
+
+    #[test]
+    fn test_overflow_pages_cache_oversized_glyphs() {
+        let font_storage = FontStorage::new();
+        let large = |ch| GlyphId::hex_box(make_id(0), ch, 128.0);
+        let metrics = crate::renderer::glyph_metrics(&large('a'), &font_storage).unwrap();
+        let tile_size = (metrics.width.max(metrics.height) + 2).next_power_of_two();
+        assert!(tile_size > 64);
+
+        let mut cache = GpuCache::new(&[page(AtlasPriority::Normal)]);
+        cache.new_batch();
+        // Without a budget, glyphs larger than every tile are not cached.
+        assert!(
+            cache
+                .get_or_push_and_protect(&large('a'), &font_storage)
+                .is_none()
+        );
+
+        cache.set_overflow_budget((2 * tile_size).pow(2));
+        let mut push_large = |ch| {
+            cache.new_batch();
+            let (item, _) = cache
+                .get_or_push_and_protect(&large(ch), &font_storage)
+                .unwrap();
+            item.texture_index
+        };
+        for ch in ['a', 'b', 'c', 'd'] {
+            assert_eq!(push_large(ch), 1);
+        }
+        // The page is full and the budget spent, so a glyph is evicted.
+        assert_eq!(push_large('e'), 1);
+        assert_eq!(cache.page_count(), 2);
+        assert_eq!(cache.overflow_budget(), 0);
+        let config = cache.page_config(1).unwrap();
+        assert_eq!(config.tile_size.get(), tile_size);
+        assert_eq!(config.texture_size.get(), 2 * tile_size);
+        assert_eq!(config.priority, AtlasPriority::Scratch);
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn test_padding_offsets_glyphs_and_limits_capacity() {
+        let font_storage = FontStorage::new();
+        let metrics = crate::renderer::glyph_metrics(&glyph('a'), &font_storage).unwrap();
+        let size = metrics.width.max(metrics.height);
+        let padded = |padding| GpuCacheConfig {
+            tile_size: NonZeroUsize::new(size + 6).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(2).unwrap(),
+            texture_size: NonZeroUsize::new(2 * (size + 6)).unwrap(),
+            padding,
+            ..page(AtlasPriority::Normal)
+        };
+        assert_eq!(padded(3).glyph_capacity(), size);
+
+        let mut cache = GpuCache::new(&[padded(3)]);
+        cache.new_batch();
+        let (a, _) = cache
+            .get_or_push_and_protect(&glyph('a'), &font_storage)
+            .unwrap();
+        let (b, _) = cache
+            .get_or_push_and_protect(&glyph('b'), &font_storage)
+            .unwrap();
+        for item in [a, b] {
+            let min = item.glyph_box.min;
+            assert_eq!((min.x % (size + 6), min.y % (size + 6)), (3, 3));
+        }
+
+        // The glyph no longer fits once the padding leaves less room than its size.
+        let mut cache = GpuCache::new(&[padded(4)]);
+        cache.new_batch();
+        assert!(
+            cache
+                .get_or_push_and_protect(&glyph('a'), &font_storage)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_packed_pages_fit_more_glyphs_and_evict_to_fit() {
+        let font_storage = FontStorage::new();
+        let glyphs = ('a'..='z').chain('A'..='Z').map(glyph);
+        let mut occupancy = Vec::new();
+        for packing in [
+            AtlasPacking::Grid,
+            AtlasPacking::Shelf,
+            AtlasPacking::Skyline,
+            AtlasPacking::Guillotine,
+        ] {
+            let config = GpuCacheConfig {
+                tile_size: NonZeroUsize::new(32).unwrap(),
+                tiles_per_axis: NonZeroUsize::new(2).unwrap(),
+                packing,
+                ..page(AtlasPriority::Normal)
+            };
+            let mut cache = GpuCache::new(&[config]);
+            cache.new_batch();
+            let mut boxes = Vec::new();
+            for glyph_id in glyphs.clone() {
+                let Some((item, _)) = cache.get_or_push_and_protect(&glyph_id, &font_storage)
+                else {
+                    break;
+                };
+                assert!(
+                    boxes
+                        .iter()
+                        .all(|other: &Box2D<_, _>| !other.intersects(&item.glyph_box))
+                );
+                boxes.push(item.glyph_box);
+            }
+            let stats = cache.stats();
+            assert_eq!(stats.pages[0].used, boxes.len());
+            occupancy.push(stats.occupancy());
+
+            // A larger glyph evicts unprotected glyphs until it fits.
+            cache.new_batch();
+            let large = GlyphId::hex_box(make_id(0), 'a', 20.0);
+            let (item, _) = cache
+                .get_or_push_and_protect(&large, &font_storage)
+                .unwrap();
+            assert!(cache.stats().evictions >= 1);
+            for glyph_id in glyphs.clone() {
+                if let Some(other) = cache.get_and_protect_entry(&glyph_id, &font_storage) {
+                    assert!(!other.glyph_box.intersects(&item.glyph_box));
+                }
+            }
+        }
+        assert!(
+            occupancy[1..]
+                .iter()
+                .all(|&packed| packed > 2.0 * occupancy[0])
+        );
+    }
+
+    #[test]
+    fn test_remove_font_frees_slots() {
+        let font_storage = FontStorage::new();
+        let other = GlyphId::hex_box(make_id(1), 'b', 8.0);
+        for strategy in [GpuCacheStrategy::Fixed, GpuCacheStrategy::Fallback] {
+            let four_tiles = GpuCacheConfig {
+                tiles_per_axis: NonZeroUsize::new(2).unwrap(),
+                texture_size: NonZeroUsize::new(128).unwrap(),
+                ..page(AtlasPriority::Normal)
+            };
+            let mut cache = GpuCache::new_with_strategy(&[four_tiles], strategy);
+            cache.new_batch();
+            let (a, _) = cache
+                .get_or_push_and_protect(&glyph('a'), &font_storage)
+                .unwrap();
+            cache
+                .get_or_push_and_protect(&other, &font_storage)
+                .unwrap();
+
+            // The slot of the removed font is free even within the same batch.
+            cache.remove_font(make_id(0));
+            assert!(
+                cache
+                    .get_and_protect_entry(&glyph('a'), &font_storage)
+                    .is_none()
+            );
+            let (c, result) = cache
+                .get_or_push_and_protect(&glyph('c'), &font_storage)
+                .unwrap();
+            assert_eq!(c.glyph_box.min, a.glyph_box.min);
+            assert!(matches!(result, GetOrPushResult::NeedToUpload));
+            assert!(cache.get_and_protect_entry(&other, &font_storage).is_some());
+        }
+    }
+}
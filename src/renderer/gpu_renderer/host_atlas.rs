@@ -0,0 +1,583 @@
+use std::collections::{BTreeMap, HashMap};
+
+use euclid::{Box2D, Point2D, Transform2D};
+
+use crate::{
+    font_storage::FontStorage,
+    glyph_id::GlyphId,
+    renderer::{glyph_metrics, rasterize_glyph},
+    text::{GlyphPosition, TextLayout},
+};
+
+use super::{GlyphInstance, StandaloneGlyph, draw_page_runs};
+
+/// A region of a texture atlas owned by the host engine.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HostAtlasRegion {
+    /// Host-defined texture identifier, passed through to [`GlyphInstance::texture_index`].
+    pub texture_index: usize,
+    /// UV coordinates of the region in the host texture.
+    pub uv_rect: Box2D<f32, euclid::UnknownUnit>,
+    /// Host-defined handle of the allocation, e.g. an allocator id.
+    pub allocation_id: u64,
+}
+
+/// Texture atlas allocator implemented by the host engine.
+///
+/// Suzuri only decides which glyphs need a region and when a region is no
+/// longer referenced; packing and texture ownership stay with the host.
+pub trait HostAtlas {
+    /// Reserves a `width` x `height` region, or returns `None` if the atlas is full.
+    fn allocate(&mut self, width: usize, height: usize) -> Option<HostAtlasRegion>;
+
+    /// Writes a glyph bitmap (row-major, one byte of coverage per pixel) into `region`.
+    fn upload(&mut self, region: &HostAtlasRegion, width: usize, height: usize, pixels: &[u8]);
+
+    /// Releases a region previously returned by [`Self::allocate`].
+    fn deallocate(&mut self, region: &HostAtlasRegion);
+}
+
+struct HostAtlasEntry {
+    region: HostAtlasRegion,
+    last_used_frame: u64,
+    /// Key of the glyph in `HostAtlasRenderer::by_use`.
+    use_order: u64,
+}
+
+/// GPU renderer that places glyphs into texture atlases managed by the host engine.
+///
+/// Works like [`super::GpuRenderer`], but instead of owning fixed atlas
+/// textures it asks a [`HostAtlas`] for regions and only produces glyph
+/// instances referencing them. When the host atlas is full, regions not used
+/// in the current frame are released one at a time, least recently used
+/// first, until the new glyph fits; glyphs that still do not fit are drawn as
+/// [`StandaloneGlyph`]s. Each render call is a frame until [`Self::end_frame`]
+/// is called.
+pub struct HostAtlasRenderer {
+    entries: HashMap<GlyphId, HostAtlasEntry, fxhash::FxBuildHasher>,
+    /// Cached glyphs by the order of their first use in a frame, oldest first.
+    by_use: BTreeMap<u64, GlyphId>,
+    next_use_order: u64,
+    /// Regions of forgotten glyphs, returned to the host atlas on the next render.
+    released: Vec<HostAtlasRegion>,
+    frame: u64,
+    /// Whether frames are ended by [`Self::end_frame`] instead of render calls.
+    explicit_frames: bool,
+    transform: Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+    depth: f32,
+}
+
+impl Default for HostAtlasRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostAtlasRenderer {
+    /// Creates a renderer with no cached regions.
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            by_use: BTreeMap::new(),
+            next_use_order: 0,
+            released: Vec::new(),
+            frame: 0,
+            explicit_frames: false,
+            transform: Transform2D::identity(),
+            depth: 0.0,
+        }
+    }
+
+    /// Sets the transform passed on with every instance; see
+    /// [`GpuRenderer::set_transform`](super::GpuRenderer::set_transform).
+    pub fn set_transform(
+        &mut self,
+        transform: Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+    ) {
+        self.transform = transform;
+    }
+
+    /// Returns the transform passed on with every instance.
+    pub fn transform(&self) -> Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit> {
+        self.transform
+    }
+
+    /// Sets the depth passed on with every instance; see
+    /// [`GpuRenderer::set_depth`](super::GpuRenderer::set_depth).
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth;
+    }
+
+    /// Returns the depth passed on with every instance.
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+
+    /// Ends the current frame; see [`GpuRenderer::end_frame`](super::GpuRenderer::end_frame).
+    ///
+    /// Regions used in the current frame are never released for new glyphs, so
+    /// glyphs of every render call of a frame stay valid until it ends.
+    pub fn end_frame(&mut self) {
+        self.explicit_frames = true;
+        self.frame += 1;
+    }
+
+    /// Releases every cached region back to the host atlas.
+    pub fn clear_cache(&mut self, atlas: &mut impl HostAtlas) {
+        self.by_use.clear();
+        for (_, entry) in self.entries.drain() {
+            atlas.deallocate(&entry.region);
+        }
+        for region in self.released.drain(..) {
+            atlas.deallocate(&region);
+        }
+    }
+
+    /// Drops the cached glyphs of font `font_id`, e.g. after its file changed.
+    ///
+    /// Their regions are released to the host atlas at the start of the next render.
+    pub fn forget_font(&mut self, font_id: fontdb::ID) {
+        let released = &mut self.released;
+        let by_use = &mut self.by_use;
+        self.entries.retain(|glyph_id, entry| {
+            let keep = glyph_id.font_id() != font_id;
+            if !keep {
+                released.push(entry.region);
+                by_use.remove(&entry.use_order);
+            }
+            keep
+        });
+    }
+
+    /// Renders the layout, allocating host atlas regions for new glyphs.
+    ///
+    /// Each call of `draw_instances` holds the instances of one host texture, in
+    /// layout order, as for [`GpuRenderer::render`](super::GpuRenderer::render).
+    ///
+    /// This method is for infallible callbacks. Use `try_render` for fallible callbacks.
+    pub fn render<T: Clone + Copy>(
+        &mut self,
+        layout: &TextLayout<T>,
+        font_storage: &FontStorage,
+        atlas: &mut impl HostAtlas,
+        mut draw_instances: impl FnMut(&[GlyphInstance<T>]),
+        mut draw_standalone: impl FnMut(&StandaloneGlyph<T>),
+    ) {
+        let _: Result<(), ()> = self.try_render(
+            layout,
+            font_storage,
+            atlas,
+            &mut |i| {
+                draw_instances(i);
+                Ok(())
+            },
+            &mut |s| {
+                draw_standalone(s);
+                Ok(())
+            },
+        );
+    }
+
+    /// Renders the layout, allocating host atlas regions for new glyphs.
+    ///
+    /// This method allows callbacks to return errors, which will be propagated.
+    pub fn try_render<T: Clone + Copy, E>(
+        &mut self,
+        layout: &TextLayout<T>,
+        font_storage: &FontStorage,
+        atlas: &mut impl HostAtlas,
+        draw_instances: &mut impl FnMut(&[GlyphInstance<T>]) -> Result<(), E>,
+        draw_standalone: &mut impl FnMut(&StandaloneGlyph<T>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        if !self.explicit_frames {
+            self.frame += 1;
+        }
+        for region in self.released.drain(..) {
+            atlas.deallocate(&region);
+        }
+        let mut instance_list: Vec<GlyphInstance<T>> = Vec::new();
+
+        for line in &layout.lines {
+            for glyph in &line.glyphs {
+                let GlyphPosition::<T> {
+                    glyph_id,
+                    user_data,
+                    ..
+                } = glyph;
+                let [x, y] = glyph.raster_origin();
+                let Some(metrics) = glyph_metrics(glyph_id, font_storage) else {
+                    continue;
+                };
+                if metrics.width == 0 || metrics.height == 0 {
+                    continue;
+                }
+
+                let screen_rect = Box2D::new(
+                    Point2D::new(x, y),
+                    Point2D::new(x + metrics.width as f32, y + metrics.height as f32),
+                );
+                if let Some(clip_rect) = layout.clip_rect
+                    && !clip_rect.intersects(&self.transform.outer_transformed_box(&screen_rect))
+                {
+                    continue;
+                }
+
+                let region = match self.entries.get_mut(glyph_id) {
+                    Some(entry) => {
+                        // Glyphs used in the current frame are never released, so
+                        // their order among each other does not matter.
+                        if entry.last_used_frame != self.frame {
+                            entry.last_used_frame = self.frame;
+                            self.by_use.remove(&entry.use_order);
+                            entry.use_order = self.next_use_order;
+                            self.by_use.insert(entry.use_order, *glyph_id);
+                            self.next_use_order += 1;
+                        }
+                        entry.region
+                    }
+                    None => {
+                        let Some((metrics, pixels)) = rasterize_glyph(glyph_id, font_storage)
+                        else {
+                            continue;
+                        };
+
+                        let Some(region) = self.allocate(atlas, metrics.width, metrics.height)
+                        else {
+                            if !instance_list.is_empty() {
+                                draw_page_runs(&instance_list, draw_instances)?;
+                                instance_list.clear();
+                            }
+
+                            draw_standalone(&StandaloneGlyph {
+                                width: metrics.width,
+                                height: metrics.height,
+                                pixels,
+                                screen_rect,
+                                transform: self.transform,
+                                depth: self.depth,
+                                clip_rect: layout.clip_rect,
+                                opacity: 1.0,
+                                gradient: None,
+                                user_data: *user_data,
+                            })?;
+                            continue;
+                        };
+
+                        atlas.upload(&region, metrics.width, metrics.height, &pixels);
+                        self.entries.insert(
+                            *glyph_id,
+                            HostAtlasEntry {
+                                region,
+                                last_used_frame: self.frame,
+                                use_order: self.next_use_order,
+                            },
+                        );
+                        self.by_use.insert(self.next_use_order, *glyph_id);
+                        self.next_use_order += 1;
+                        region
+                    }
+                };
+
+                instance_list.push(GlyphInstance {
+                    texture_index: region.texture_index,
+                    uv_rect: region.uv_rect,
+                    screen_rect,
+                    transform: self.transform,
+                    depth: self.depth,
+                    clip_rect: layout.clip_rect,
+                    opacity: 1.0,
+                    gradient: None,
+                    user_data: *user_data,
+                });
+            }
+        }
+
+        if !instance_list.is_empty() {
+            draw_page_runs(&instance_list, draw_instances)?;
+        }
+
+        Ok(())
+    }
+
+    /// Allocates a region, releasing the least recently used regions not used in
+    /// the current frame until the atlas has room.
+    fn allocate(
+        &mut self,
+        atlas: &mut impl HostAtlas,
+        width: usize,
+        height: usize,
+    ) -> Option<HostAtlasRegion> {
+        if let Some(region) = atlas.allocate(width, height) {
+            return Some(region);
+        }
+
+        // Glyphs are ordered by use, so the first one of the current frame ends
+        // the candidates.
+        while let Some(entry) = self.by_use.first_entry() {
+            let glyph_id = *entry.get();
+            if self
+                .entries
+                .get(&glyph_id)
+                .is_some_and(|entry| entry.last_used_frame == self.frame)
+            {
+                return None;
+            }
+            entry.remove();
+            if let Some(entry) = self.entries.remove(&glyph_id) {
+                atlas.deallocate(&entry.region);
+            }
+            if let Some(region) = atlas.allocate(width, height) {
+                return Some(region);
+            }
+        }
+        None
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::test_font_storage,
+        text::{TextData, TextLayoutConfig},
+    };
+
+    /// A host atlas of `slots` equal regions.
+    struct SlotAtlas {
+        free: Vec<u64>,
+        deallocated: Vec<u64>,
+    }
+
+    impl SlotAtlas {
+        fn new(slots: u64) -> Self {
+            Self {
+                free: (0..slots).rev().collect(),
+                deallocated: Vec::new(),
+            }
+        }
+    }
+
+    impl HostAtlas for SlotAtlas {
+        fn allocate(&mut self, _width: usize, _height: usize) -> Option<HostAtlasRegion> {
+            let allocation_id = self.free.pop()?;
+            Some(HostAtlasRegion {
+                texture_index: 0,
+                uv_rect: Box2D::zero(),
+                allocation_id,
+            })
+        }
+
+        fn upload(&mut self, _: &HostAtlasRegion, _: usize, _: usize, _: &[u8]) {}
+
+        fn deallocate(&mut self, region: &HostAtlasRegion) {
+            self.free.push(region.allocation_id);
+            self.deallocated.push(region.allocation_id);
+        }
+    }
+
+    /// A host atlas of one texture, whose regions are packed into rows.
+    #[cfg(feature = "cpu")]
+    struct PixelAtlas {
+        pixels: Vec<u8>,
+        cursor: [usize; 2],
+        row_height: usize,
+    }
+
+    #[cfg(feature = "cpu")]
+    impl PixelAtlas {
+        const SIZE: usize = 256;
+
+        fn new() -> Self {
+            Self {
+                pixels: vec![0; Self::SIZE * Self::SIZE],
+                cursor: [0, 0],
+                row_height: 0,
+            }
+        }
+
+        /// Returns the coverage of the texel at `uv` offset by `offset` texels.
+        fn sample(&self, uv: Point2D<f32, euclid::UnknownUnit>, offset: [usize; 2]) -> u8 {
+            let x = (uv.x * Self::SIZE as f32).round() as usize + offset[0];
+            let y = (uv.y * Self::SIZE as f32).round() as usize + offset[1];
+            self.pixels[y * Self::SIZE + x]
+        }
+    }
+
+    #[cfg(feature = "cpu")]
+    impl HostAtlas for PixelAtlas {
+        fn allocate(&mut self, width: usize, height: usize) -> Option<HostAtlasRegion> {
+            if self.cursor[0] + width > Self::SIZE {
+                self.cursor = [0, self.cursor[1] + self.row_height];
+                self.row_height = 0;
+            }
+            if width > Self::SIZE || self.cursor[1] + height > Self::SIZE {
+                return None;
+            }
+            let [x, y] = self.cursor;
+            self.cursor[0] += width;
+            self.row_height = self.row_height.max(height);
+            let size = Self::SIZE as f32;
+            Some(HostAtlasRegion {
+                texture_index: 0,
+                uv_rect: Box2D::new(
+                    Point2D::new(x as f32 / size, y as f32 / size),
+                    Point2D::new((x + width) as f32 / size, (y + height) as f32 / size),
+                ),
+                allocation_id: (y * Self::SIZE + x) as u64,
+            })
+        }
+
+        fn upload(&mut self, region: &HostAtlasRegion, width: usize, _: usize, pixels: &[u8]) {
+            let x = (region.uv_rect.min.x * Self::SIZE as f32).round() as usize;
+            let y = (region.uv_rect.min.y * Self::SIZE as f32).round() as usize;
+            for (row, source) in pixels.chunks_exact(width).enumerate() {
+                let start = (y + row) * Self::SIZE + x;
+                self.pixels[start..start + width].copy_from_slice(source);
+            }
+        }
+
+        fn deallocate(&mut self, _: &HostAtlasRegion) {}
+    }
+
+    /// Renders `text` and returns how many glyphs were drawn standalone.
+    fn render(
+        renderer: &mut HostAtlasRenderer,
+        atlas: &mut SlotAtlas,
+        font_storage: &FontStorage,
+        font_id: fontdb::ID,
+        text: &str,
+    ) -> usize {
+        let layout = TextData::builder(font_id, 16.0, 0u32)
+            .push_str(text)
+            .build()
+            .layout(&TextLayoutConfig::default(), font_storage);
+        let mut standalone = 0;
+        renderer.render(&layout, font_storage, atlas, |_| {}, |_| standalone += 1);
+        standalone
+    }
+
+    #[cfg(feature = "cpu")]
+    #[test]
+    fn test_instances_sample_uploaded_glyphs() {
+        use crate::renderer::{CpuCacheConfig, CpuRenderer};
+        use std::num::NonZeroUsize;
+
+        let (font_storage, font_id) = test_font_storage();
+        let layout = TextData::builder(font_id, 24.0, 0u32)
+            .push_str("Suzuri")
+            .build()
+            .layout(&TextLayoutConfig::default(), &font_storage);
+        const WIDTH: usize = 128;
+        const HEIGHT: usize = 48;
+
+        // Draws the instances as a host engine would, sampling the host texture.
+        let mut renderer = HostAtlasRenderer::new();
+        let mut atlas = PixelAtlas::new();
+        let mut instances = Vec::new();
+        let mut standalone = 0;
+        renderer.render(
+            &layout,
+            &font_storage,
+            &mut atlas,
+            |batch| instances.extend_from_slice(batch),
+            |_| standalone += 1,
+        );
+        assert_eq!(standalone, 0);
+        let mut drawn = vec![0u8; WIDTH * HEIGHT];
+        for instance in &instances {
+            let rect = instance.screen_rect;
+            for row in 0..rect.height().round() as usize {
+                for col in 0..rect.width().round() as usize {
+                    let x = (rect.min.x + col as f32).floor() as usize;
+                    let y = (rect.min.y + row as f32).floor() as usize;
+                    let pixel = &mut drawn[y * WIDTH + x];
+                    *pixel = pixel.saturating_add(atlas.sample(instance.uv_rect.min, [col, row]));
+                }
+            }
+        }
+
+        let mut expected = vec![0u8; WIDTH * HEIGHT];
+        let mut cpu_renderer = CpuRenderer::new(&[CpuCacheConfig {
+            block_size: NonZeroUsize::new(64 * 64).unwrap(),
+            capacity: NonZeroUsize::new(16).unwrap(),
+        }]);
+        cpu_renderer.render(
+            &layout,
+            [WIDTH, HEIGHT],
+            &font_storage,
+            &mut |[x, y], alpha, _| {
+                let pixel = &mut expected[y * WIDTH + x];
+                *pixel = pixel.saturating_add(alpha);
+            },
+        );
+
+        assert!(expected.iter().any(|&alpha| alpha > 0));
+        assert_eq!(drawn, expected);
+    }
+
+    #[test]
+    fn test_end_frame_keeps_regions_of_earlier_render_calls() {
+        let (font_storage, font_id) = test_font_storage();
+
+        // By default each render call is a frame, so 'a' gives way to 'b'.
+        let mut renderer = HostAtlasRenderer::new();
+        let mut atlas = SlotAtlas::new(1);
+        assert_eq!(
+            render(&mut renderer, &mut atlas, &font_storage, font_id, "a"),
+            0
+        );
+        assert_eq!(
+            render(&mut renderer, &mut atlas, &font_storage, font_id, "b"),
+            0
+        );
+        assert_eq!(atlas.deallocated, [0]);
+
+        // With explicit frames, 'a' stays valid for the rest of its frame.
+        let mut renderer = HostAtlasRenderer::new();
+        let mut atlas = SlotAtlas::new(1);
+        renderer.end_frame();
+        assert_eq!(
+            render(&mut renderer, &mut atlas, &font_storage, font_id, "a"),
+            0
+        );
+        assert_eq!(
+            render(&mut renderer, &mut atlas, &font_storage, font_id, "b"),
+            1
+        );
+        assert!(atlas.deallocated.is_empty());
+        renderer.end_frame();
+        assert_eq!(
+            render(&mut renderer, &mut atlas, &font_storage, font_id, "b"),
+            0
+        );
+        assert_eq!(atlas.deallocated, [0]);
+    }
+
+    #[test]
+    fn test_full_atlas_releases_least_recently_used_regions() {
+        let (font_storage, font_id) = test_font_storage();
+
+        let mut renderer = HostAtlasRenderer::new();
+        let mut atlas = SlotAtlas::new(3);
+        assert_eq!(
+            render(&mut renderer, &mut atlas, &font_storage, font_id, "abc"),
+            0
+        );
+        // 'a' is drawn again, so 'b' is the least recently used glyph.
+        assert_eq!(
+            render(&mut renderer, &mut atlas, &font_storage, font_id, "a"),
+            0
+        );
+        assert_eq!(
+            render(&mut renderer, &mut atlas, &font_storage, font_id, "d"),
+            0
+        );
+        assert_eq!(atlas.deallocated, [1]);
+        // A frame using every cached glyph leaves no room for another.
+        assert_eq!(
+            render(&mut renderer, &mut atlas, &font_storage, font_id, "acdb"),
+            1
+        );
+    }
+}
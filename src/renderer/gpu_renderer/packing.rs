@@ -0,0 +1,425 @@
+//! Placement of glyphs of varying sizes on atlas pages.
+
+use euclid::{Box2D, Point2D, UnknownUnit};
+
+type Rect = Box2D<usize, UnknownUnit>;
+
+/// How glyphs are placed on an atlas page, see [`GpuCacheConfig::packing`](super::GpuCacheConfig::packing).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AtlasPacking {
+    /// Square tiles of the page's tile size, one glyph each.
+    ///
+    /// Placing and evicting glyphs is cheapest, but glyphs much smaller than the
+    /// tiles, such as punctuation next to capitals, leave most of their tile empty.
+    #[default]
+    Grid,
+    /// Rows as high as the first glyph placed in them, filled left to right.
+    ///
+    /// Glyphs go into the row closest to their height, so text of one size packs
+    /// tightly, while mixed sizes open more rows.
+    Shelf,
+    /// Each glyph at the lowest point of the skyline, the top edge of the glyphs
+    /// placed so far.
+    ///
+    /// Packs mixed heights tighter than [`Self::Shelf`]. Space freed below the
+    /// skyline is reused for glyphs that fit it.
+    Skyline,
+    /// Free rectangles, each split in two when a glyph is placed in it.
+    ///
+    /// Adapts best to mixed sizes and merges freed space back, at the highest cost
+    /// per placement.
+    Guillotine,
+}
+
+/// Free space of a page packed with other than [`AtlasPacking::Grid`].
+///
+/// Rectangles are allocated and deallocated one by one; once every rectangle is
+/// deallocated, the page is empty again regardless of fragmentation.
+pub(crate) struct Packer {
+    size: usize,
+    allocated: usize,
+    kind: PackerKind,
+}
+
+enum PackerKind {
+    Shelf(Vec<Shelf>),
+    Skyline {
+        segments: Vec<Segment>,
+        /// Freed space below the skyline.
+        holes: FreeRects,
+    },
+    Guillotine(FreeRects),
+}
+
+struct Shelf {
+    y: usize,
+    height: usize,
+    /// Free spans as `(x, width)`, sorted by `x`.
+    free: Vec<(usize, usize)>,
+}
+
+/// A horizontal piece of the skyline.
+struct Segment {
+    x: usize,
+    y: usize,
+    width: usize,
+}
+
+impl Packer {
+    /// Returns the packer of an empty square page of `size` texels per side, or
+    /// `None` for [`AtlasPacking::Grid`].
+    pub fn new(packing: AtlasPacking, size: usize) -> Option<Self> {
+        let kind = match packing {
+            AtlasPacking::Grid => return None,
+            AtlasPacking::Shelf => PackerKind::Shelf(Vec::new()),
+            AtlasPacking::Skyline => PackerKind::Skyline {
+                segments: Vec::new(),
+                holes: FreeRects::default(),
+            },
+            AtlasPacking::Guillotine => PackerKind::Guillotine(FreeRects::default()),
+        };
+        let mut packer = Self {
+            size,
+            allocated: 0,
+            kind,
+        };
+        packer.clear();
+        Some(packer)
+    }
+
+    /// Frees the whole page.
+    pub fn clear(&mut self) {
+        self.allocated = 0;
+        let size = self.size;
+        match &mut self.kind {
+            PackerKind::Shelf(shelves) => shelves.clear(),
+            PackerKind::Skyline { segments, holes } => {
+                segments.clear();
+                segments.push(Segment {
+                    x: 0,
+                    y: 0,
+                    width: size,
+                });
+                holes.0.clear();
+            }
+            PackerKind::Guillotine(free) => {
+                free.0.clear();
+                free.0
+                    .push(Rect::new(Point2D::zero(), Point2D::new(size, size)));
+            }
+        }
+    }
+
+    /// Returns whether a `width` by `height` rectangle fits the free space.
+    pub fn can_allocate(&self, width: usize, height: usize) -> bool {
+        match &self.kind {
+            PackerKind::Shelf(shelves) => shelf_find(shelves, self.size, width, height).is_some(),
+            PackerKind::Skyline { segments, holes } => {
+                holes.find(width, height).is_some()
+                    || skyline_find(segments, self.size, width, height).is_some()
+            }
+            PackerKind::Guillotine(free) => free.find(width, height).is_some(),
+        }
+    }
+
+    /// Places a `width` by `height` rectangle, or returns `None` if it does not fit
+    /// the free space.
+    pub fn allocate(&mut self, width: usize, height: usize) -> Option<Rect> {
+        let size = self.size;
+        let rect = match &mut self.kind {
+            PackerKind::Shelf(shelves) => {
+                let (index, span) = shelf_find(shelves, size, width, height)?;
+                if index == shelves.len() {
+                    let y = shelves.last().map_or(0, |shelf| shelf.y + shelf.height);
+                    shelves.push(Shelf {
+                        y,
+                        height,
+                        free: vec![(0, size)],
+                    });
+                }
+                let shelf = &mut shelves[index];
+                let (x, free_width) = &mut shelf.free[span];
+                let origin = Point2D::new(*x, shelf.y);
+                *x += width;
+                *free_width -= width;
+                if *free_width == 0 {
+                    shelf.free.remove(span);
+                }
+                Rect::new(origin, origin + euclid::vec2(width, height))
+            }
+            PackerKind::Skyline { segments, holes } => {
+                if let Some(index) = holes.find(width, height) {
+                    holes.take(index, width, height)
+                } else {
+                    let (index, x, y) = skyline_find(segments, size, width, height)?;
+                    skyline_place(segments, index, x, y + height, width);
+                    Rect::new(Point2D::new(x, y), Point2D::new(x + width, y + height))
+                }
+            }
+            PackerKind::Guillotine(free) => {
+                let index = free.find(width, height)?;
+                free.take(index, width, height)
+            }
+        };
+        self.allocated += 1;
+        Some(rect)
+    }
+
+    /// Frees `rect`, which must have been returned by [`Self::allocate`].
+    pub fn deallocate(&mut self, rect: Rect) {
+        self.allocated = self.allocated.saturating_sub(1);
+        if self.allocated == 0 {
+            self.clear();
+            return;
+        }
+        match &mut self.kind {
+            PackerKind::Shelf(shelves) => {
+                let Some(shelf) = shelves.iter_mut().find(|shelf| shelf.y == rect.min.y) else {
+                    return;
+                };
+                let position = shelf.free.partition_point(|&(x, _)| x < rect.min.x);
+                shelf.free.insert(position, (rect.min.x, rect.width()));
+                // Merge with the following span, then with the preceding one.
+                for i in [position, position.saturating_sub(1)] {
+                    if let [(x, width), (next_x, next_width), ..] = shelf.free[i..]
+                        && x + width == next_x
+                    {
+                        shelf.free[i] = (x, width + next_width);
+                        shelf.free.remove(i + 1);
+                    }
+                }
+                // Drop empty shelves at the top, so their space can take other heights.
+                while shelves
+                    .last()
+                    .is_some_and(|shelf| shelf.free == [(0, self.size)])
+                {
+                    shelves.pop();
+                }
+            }
+            PackerKind::Skyline { segments, holes } => {
+                let on_top = segments
+                    .iter()
+                    .filter(|segment| {
+                        segment.x < rect.max.x && rect.min.x < segment.x + segment.width
+                    })
+                    .all(|segment| segment.y == rect.max.y);
+                if on_top {
+                    // Nothing lies above the rectangle, so the skyline drops back.
+                    let index = skyline_split(segments, rect.min.x);
+                    skyline_place(segments, index, rect.min.x, rect.min.y, rect.width());
+                } else {
+                    holes.insert(rect);
+                }
+            }
+            PackerKind::Guillotine(free) => free.insert(rect),
+        }
+    }
+}
+
+/// Returns the shelf and the free span to place a rectangle in, where a shelf
+/// index of `shelves.len()` stands for a new shelf on top.
+///
+/// Prefers the shelf wasting the least height, unless it wastes more than half
+/// the rectangle's height and a new shelf fits.
+fn shelf_find(
+    shelves: &[Shelf],
+    size: usize,
+    width: usize,
+    height: usize,
+) -> Option<(usize, usize)> {
+    let best = shelves
+        .iter()
+        .enumerate()
+        .filter(|(_, shelf)| shelf.height >= height)
+        .filter_map(|(i, shelf)| {
+            let span = shelf.free.iter().position(|&(_, free)| free >= width)?;
+            Some((shelf.height - height, i, span))
+        })
+        .min();
+    let top = shelves.last().map_or(0, |shelf| shelf.y + shelf.height);
+    let new_shelf = (width <= size && top + height <= size).then_some((shelves.len(), 0));
+    match best {
+        Some((waste, _, _)) if waste > height / 2 && new_shelf.is_some() => new_shelf,
+        Some((_, i, span)) => Some((i, span)),
+        None => new_shelf,
+    }
+}
+
+/// Returns the segment index, position and skyline height to place a rectangle
+/// at, lowest first, then leftmost.
+fn skyline_find(
+    segments: &[Segment],
+    size: usize,
+    width: usize,
+    height: usize,
+) -> Option<(usize, usize, usize)> {
+    let mut best: Option<(usize, usize, usize)> = None;
+    for (i, segment) in segments.iter().enumerate() {
+        let x = segment.x;
+        if x + width > size {
+            break;
+        }
+        let y = segments[i..]
+            .iter()
+            .take_while(|segment| segment.x < x + width)
+            .map(|segment| segment.y)
+            .max()
+            .unwrap_or(0);
+        if y + height <= size && best.is_none_or(|(_, _, best_y)| y < best_y) {
+            best = Some((i, x, y));
+        }
+    }
+    best
+}
+
+/// Sets the skyline to `y` over `width` texels from `x`, which must be the start
+/// of segment `index`.
+fn skyline_place(segments: &mut Vec<Segment>, index: usize, x: usize, y: usize, width: usize) {
+    let end = x + width;
+    while let Some(segment) = segments.get_mut(index) {
+        if segment.x >= end {
+            break;
+        }
+        let segment_end = segment.x + segment.width;
+        if segment_end <= end {
+            segments.remove(index);
+        } else {
+            segment.width = segment_end - end;
+            segment.x = end;
+            break;
+        }
+    }
+    segments.insert(index, Segment { x, y, width });
+
+    // Merge neighbors of equal height.
+    let mut i = index.saturating_sub(1);
+    while i + 1 < segments.len() && i <= index + 1 {
+        if segments[i].y == segments[i + 1].y {
+            segments[i].width += segments[i + 1].width;
+            segments.remove(i + 1);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Splits the segment containing `x` so one starts at `x`, and returns its index.
+fn skyline_split(segments: &mut Vec<Segment>, x: usize) -> usize {
+    let index = segments.partition_point(|segment| segment.x + segment.width <= x);
+    let segment = &mut segments[index];
+    if segment.x == x {
+        return index;
+    }
+    let right = Segment {
+        x,
+        y: segment.y,
+        width: segment.x + segment.width - x,
+    };
+    segment.width = x - segment.x;
+    segments.insert(index + 1, right);
+    index + 1
+}
+
+/// Free rectangles that do not overlap.
+#[derive(Default)]
+struct FreeRects(Vec<Rect>);
+
+impl FreeRects {
+    /// Returns the free rectangle leaving the shortest side unused.
+    fn find(&self, width: usize, height: usize) -> Option<usize> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, rect)| rect.width() >= width && rect.height() >= height)
+            .min_by_key(|(_, rect)| {
+                let short_side = (rect.width() - width).min(rect.height() - height);
+                (short_side, rect.area())
+            })
+            .map(|(i, _)| i)
+    }
+
+    /// Places a rectangle at the corner of free rectangle `index`, splitting the
+    /// rest along the shorter leftover axis.
+    fn take(&mut self, index: usize, width: usize, height: usize) -> Rect {
+        let free = self.0.swap_remove(index);
+        let split = Point2D::new(free.min.x + width, free.min.y + height);
+        let (right, bottom) = if free.max.x - split.x < free.max.y - split.y {
+            (
+                Rect::new(
+                    Point2D::new(split.x, free.min.y),
+                    Point2D::new(free.max.x, split.y),
+                ),
+                Rect::new(Point2D::new(free.min.x, split.y), free.max),
+            )
+        } else {
+            (
+                Rect::new(Point2D::new(split.x, free.min.y), free.max),
+                Rect::new(
+                    Point2D::new(free.min.x, split.y),
+                    Point2D::new(split.x, free.max.y),
+                ),
+            )
+        };
+        self.0
+            .extend([right, bottom].into_iter().filter(|rect| !rect.is_empty()));
+        Rect::new(free.min, split)
+    }
+
+    /// Adds `rect`, merged with free rectangles sharing a whole edge with it.
+    fn insert(&mut self, mut rect: Rect) {
+        while let Some(i) = self.0.iter().position(|other| {
+            let same_rows = other.min.y == rect.min.y && other.max.y == rect.max.y;
+            let same_columns = other.min.x == rect.min.x && other.max.x == rect.max.x;
+            (same_rows && (other.max.x == rect.min.x || rect.max.x == other.min.x))
+                || (same_columns && (other.max.y == rect.min.y || rect.max.y == other.min.y))
+        }) {
+            rect = rect.union(&self.0.swap_remove(i));
+        }
+        self.0.push(rect);
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packers_place_disjoint_rects_and_reuse_freed_space() {
+        for packing in [
+            AtlasPacking::Shelf,
+            AtlasPacking::Skyline,
+            AtlasPacking::Guillotine,
+        ] {
+            let mut packer = Packer::new(packing, 64).unwrap();
+            let sizes = (0..).map(|i| [4 + i * 7 % 13, 4 + i * 5 % 11]);
+            let mut rects: Vec<Rect> = Vec::new();
+            for [width, height] in sizes {
+                let Some(rect) = packer.allocate(width, height) else {
+                    assert!(!packer.can_allocate(width, height));
+                    break;
+                };
+                assert_eq!(rect.size().to_array(), [width, height]);
+                assert!(rect.max.x <= 64 && rect.max.y <= 64);
+                assert!(rects.iter().all(|other| !other.intersects(&rect)));
+                rects.push(rect);
+            }
+            // Mixed sizes cover more than half of the page.
+            let area: usize = rects.iter().map(Rect::area).sum();
+            assert!(area * 2 > 64 * 64, "{packing:?} covered {area} texels");
+
+            // Freed space is reused, and an emptied page takes a full-size rectangle.
+            let freed = rects.swap_remove(rects.len() / 2);
+            packer.deallocate(freed);
+            let rect = packer.allocate(freed.width(), freed.height()).unwrap();
+            assert!(rects.iter().all(|other| !other.intersects(&rect)));
+            rects.push(rect);
+            assert!(!packer.can_allocate(64, 64));
+            for rect in rects {
+                packer.deallocate(rect);
+            }
+            assert!(packer.allocate(64, 64).is_some());
+        }
+        assert!(Packer::new(AtlasPacking::Grid, 64).is_none());
+    }
+}
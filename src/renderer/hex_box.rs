@@ -0,0 +1,156 @@
+//! Procedural "Last Resort" glyphs for characters no font covers.
+//!
+//! A hex box is an outlined rectangle with the code point printed inside it
+//! in a tiny built-in 3x5 pixel font: two rows of two hex digits for the BMP,
+//! two rows of three digits for the supplementary planes.
+
+/// 3x5 bitmaps for the hex digits `0`-`F`, row-major from the top-left, MSB first.
+const DIGITS: [u16; 16] = [
+    0b111_101_101_101_111, // 0
+    0b010_110_010_010_111, // 1
+    0b111_001_111_100_111, // 2
+    0b111_001_111_001_111, // 3
+    0b101_101_111_001_001, // 4
+    0b111_100_111_001_111, // 5
+    0b111_100_111_101_111, // 6
+    0b111_001_001_001_001, // 7
+    0b111_101_111_101_111, // 8
+    0b111_101_111_001_111, // 9
+    0b010_101_111_101_101, // A
+    0b110_101_110_101_110, // B
+    0b011_100_100_100_011, // C
+    0b110_101_101_101_110, // D
+    0b111_100_111_100_111, // E
+    0b111_100_111_100_100, // F
+];
+
+const DIGIT_WIDTH: usize = 3;
+const DIGIT_HEIGHT: usize = 5;
+/// Border plus padding on each side of the digit grid, in cells.
+const INSET: usize = 2;
+
+/// Pixel size of one cell of the digit font at `font_size`.
+fn cell_size(font_size: f32) -> usize {
+    (font_size / 20.0).round().max(1.0) as usize
+}
+
+/// Hex digits of `ch`, padded to 4 or 6 digits.
+fn hex_digits(ch: char) -> Vec<usize> {
+    let cp = ch as u32;
+    let count = if cp <= 0xFFFF { 4 } else { 6 };
+    (0..count)
+        .rev()
+        .map(|i| ((cp >> (i * 4)) & 0xF) as usize)
+        .collect()
+}
+
+/// Box size in cells for a code point with `digit_count` hex digits.
+fn box_cells(digit_count: usize) -> [usize; 2] {
+    let columns = digit_count / 2;
+    let width = columns * DIGIT_WIDTH + (columns - 1) + INSET * 2;
+    let height = DIGIT_HEIGHT * 2 + 1 + INSET * 2;
+    [width, height]
+}
+
+/// Returns the metrics of the hex box for `ch`.
+///
+/// The box sits on the baseline with one cell of side bearing on both sides.
+pub(crate) fn metrics(ch: char, font_size: f32) -> fontdue::Metrics {
+    let cell = cell_size(font_size);
+    let [width, height] = box_cells(hex_digits(ch).len());
+    let [width, height] = [width * cell, height * cell];
+
+    fontdue::Metrics {
+        xmin: cell as i32,
+        ymin: 0,
+        width,
+        height,
+        advance_width: (width + cell * 2) as f32,
+        advance_height: 0.0,
+        bounds: fontdue::OutlineBounds {
+            xmin: cell as f32,
+            ymin: 0.0,
+            width: width as f32,
+            height: height as f32,
+        },
+    }
+}
+
+/// Rasterizes the hex box for `ch` into a coverage bitmap.
+pub(crate) fn rasterize(ch: char, font_size: f32) -> (fontdue::Metrics, Vec<u8>) {
+    let metrics = metrics(ch, font_size);
+    let cell = cell_size(font_size);
+    let digits = hex_digits(ch);
+    let columns = digits.len() / 2;
+    let [cells_w, cells_h] = box_cells(digits.len());
+
+    let mut bitmap = vec![0u8; metrics.width * metrics.height];
+    let mut fill_cell = |cx: usize, cy: usize| {
+        for y in cy * cell..(cy + 1) * cell {
+            let row = &mut bitmap[y * metrics.width..(y + 1) * metrics.width];
+            row[cx * cell..(cx + 1) * cell].fill(255);
+        }
+    };
+
+    // Outline.
+    for cx in 0..cells_w {
+        fill_cell(cx, 0);
+        fill_cell(cx, cells_h - 1);
+    }
+    for cy in 1..cells_h - 1 {
+        fill_cell(0, cy);
+        fill_cell(cells_w - 1, cy);
+    }
+
+    // Digits, first half on the top row.
+    for (i, &digit) in digits.iter().enumerate() {
+        let origin_x = INSET + (i % columns) * (DIGIT_WIDTH + 1);
+        let origin_y = INSET + (i / columns) * (DIGIT_HEIGHT + 1);
+        let glyph = DIGITS[digit];
+        for y in 0..DIGIT_HEIGHT {
+            for x in 0..DIGIT_WIDTH {
+                let bit = DIGIT_WIDTH * DIGIT_HEIGHT - 1 - (y * DIGIT_WIDTH + x);
+                if glyph >> bit & 1 == 1 {
+                    fill_cell(origin_x + x, origin_y + y);
+                }
+            }
+        }
+    }
+
+    (metrics, bitmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitmap_matches_metrics() {
+        for ch in ['\u{E000}', '\u{10FFFD}'] {
+            for font_size in [8.0, 16.0, 48.0] {
+                let (metrics, bitmap) = rasterize(ch, font_size);
+                assert_eq!(metrics, super::metrics(ch, font_size));
+                assert_eq!(bitmap.len(), metrics.width * metrics.height);
+                assert!(metrics.advance_width > metrics.width as f32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_supplementary_planes_are_wider() {
+        let bmp = metrics('\u{E000}', 16.0);
+        let supplementary = metrics('\u{F0000}', 16.0);
+        assert!(supplementary.width > bmp.width);
+        assert_eq!(supplementary.height, bmp.height);
+    }
+
+    #[test]
+    fn test_digit_pixels() {
+        // U+0000 renders four zeros; the centre of each zero's loop stays empty.
+        let (metrics, bitmap) = rasterize('\u{0}', 16.0);
+        let pixel = |x: usize, y: usize| bitmap[y * metrics.width + x];
+        assert_eq!(pixel(0, 0), 255);
+        assert_eq!(pixel(INSET, INSET), 255);
+        assert_eq!(pixel(INSET + 1, INSET + 1), 0);
+    }
+}
@@ -1,27 +1,153 @@
 use super::gpu_renderer::{
-    AtlasUpdate, GlyphInstance, GpuCacheConfig, GpuRenderer, StandaloneGlyph,
+    AtlasMove, AtlasUpdate, DamageEffects, GlyphInstance, GpuCacheConfig, GpuRenderer,
+    LayoutDamage, LayoutPlacement, LinearGradient, StandaloneGlyph,
 };
+use super::{CacheStats, FilledRect};
 use crate::font_storage::FontStorage;
 use crate::text::TextLayout;
 use bytemuck::{Pod, Zeroable};
 use std::collections::HashMap;
-use wgpu::util::DeviceExt;
+use std::num::NonZeroU32;
+
+mod upload_ring;
+use upload_ring::{FrameLease, UploadRing};
 
 /// Initial capacity for the instance buffer.
 /// Chosen to balance memory usage and typical text rendering workloads
 /// (average paragraph with ~250-500 glyphs, with headroom for multiple draw calls).
 const INITIAL_INSTANCE_CAPACITY: usize = 1024;
 
+/// Initial capacity of the staging buffer glyph pixels are copied from, in bytes.
+/// Holds 64 glyphs of up to 16 rows, each padded to 256 bytes.
+const INITIAL_STAGING_CAPACITY: u64 = 256 * 1024;
+
+/// Initial number of globals the globals buffer holds, one per render.
+const INITIAL_GLOBALS_CAPACITY: u64 = 16;
+
+/// Initial number of draw arguments the indirect buffer holds.
+const INITIAL_INDIRECT_CAPACITY: u64 = 256;
+
+/// Placement of frames in the instance ring buffer, in bytes.
+///
+/// A frame first draws the instances of the previous frame where they match, so
+/// an unchanged frame writes nothing. From the first instance that differs, it
+/// writes into fresh space of the ring, which is never space a render that may
+/// still be executed reads.
+#[derive(Clone, Copy, Debug, Default)]
+struct InstanceRing {
+    /// Start of the instances of the previous frame.
+    last_start: u64,
+    /// Length of the instances of the previous frame.
+    last_len: u64,
+    /// Upper bound of the length of the current frame.
+    frame_len: u64,
+    /// Start of the current frame's fresh space, once it differs from the previous one.
+    fresh_start: Option<u64>,
+    /// Length of the current frame's fresh space.
+    fresh_len: u64,
+    /// Length of the instances of the current frame so far.
+    used: u64,
+}
+
+/// Pixels of the atlas kept in host memory, to restore it on another device;
+/// see [`WgpuRenderer::set_atlas_host_copy`].
+#[derive(Clone, Debug)]
+struct AtlasCopy {
+    /// Size of the atlas texture.
+    size: wgpu::Extent3d,
+    /// Texels of each layer, row by row.
+    layers: Vec<Vec<u8>>,
+}
+
+impl AtlasCopy {
+    fn new(size: wgpu::Extent3d) -> Self {
+        let layer_len = size.width as usize * size.height as usize;
+        Self {
+            size,
+            layers: vec![vec![0; layer_len]; size.depth_or_array_layers as usize],
+        }
+    }
+
+    /// Grows the layers to `size`, keeping their texels in place.
+    fn resize(&mut self, size: wgpu::Extent3d) {
+        let mut resized = Self::new(size);
+        let width = self.size.width as usize;
+        for (layer, old) in resized.layers.iter_mut().zip(&self.layers) {
+            for (row, texels) in old.chunks_exact(width).enumerate() {
+                let start = row * size.width as usize;
+                layer[start..start + width].copy_from_slice(texels);
+            }
+        }
+        *self = resized;
+    }
+
+    /// Copies the region of `atlas_move` to its destination on another layer.
+    fn copy(&mut self, atlas_move: &AtlasMove) {
+        let atlas_width = self.size.width as usize;
+        let width = atlas_move.width;
+        let Ok([src_layer, dst_layer]) = self
+            .layers
+            .get_disjoint_mut([atlas_move.src_texture_index, atlas_move.dst_texture_index])
+        else {
+            return;
+        };
+        for row in 0..atlas_move.height {
+            let src = (atlas_move.src_y + row) * atlas_width + atlas_move.src_x;
+            let dst = (atlas_move.dst_y + row) * atlas_width + atlas_move.dst_x;
+            let (Some(texels), Some(target)) = (
+                src_layer.get(src..src + width),
+                dst_layer.get_mut(dst..dst + width),
+            ) else {
+                return;
+            };
+            target.copy_from_slice(texels);
+        }
+    }
+
+    /// Writes the pixels of `update`, missing ones as zero.
+    fn write(&mut self, update: &AtlasUpdate) {
+        let Some(layer) = self.layers.get_mut(update.texture_index) else {
+            return;
+        };
+        let atlas_width = self.size.width as usize;
+        for row in 0..update.height {
+            let start = (update.y + row) * atlas_width + update.x;
+            let Some(dst) = layer.get_mut(start..start + update.width) else {
+                return;
+            };
+            match update
+                .pixels
+                .get(row * update.width..(row + 1) * update.width)
+            {
+                Some(pixels) => dst.copy_from_slice(pixels),
+                None => dst.fill(0),
+            }
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 struct InstanceData {
     screen_rect: [f32; 4], // x, y, w, h
-    uv_rect: [f32; 4],     // u, v, w, h
+    uv_rect: [f32; 4],     // u, v, w, h; in texels for the atlas
     color: [f32; 4],
     layer: u32,
-    _padding: [u32; 3],
+    /// Normalized device depth of the quad.
+    depth: f32,
+    /// Views of a multiview pass the quad is drawn to, one bit each.
+    view_mask: u32,
+    _padding: u32,
+    /// Color at the end of the gradient; equal to `color` without one.
+    secondary_color: [f32; 4],
+    /// Start and end point of the gradient, in the coordinates of `screen_rect`.
+    /// Both are zero without one.
+    gradient: [f32; 4],
 }
 
+/// Layer of instances that fill their rectangle instead of sampling the atlas.
+const SOLID_LAYER: u32 = u32::MAX;
+
 impl InstanceData {
     /// Returns the vertex buffer layout for instance data.
     ///
@@ -51,8 +177,55 @@ impl InstanceData {
             shader_location: 3,
             format: wgpu::VertexFormat::Uint32,
         },
+        // depth
+        wgpu::VertexAttribute {
+            offset: 52,
+            shader_location: 4,
+            format: wgpu::VertexFormat::Float32,
+        },
+        // secondary_color
+        wgpu::VertexAttribute {
+            offset: 64,
+            shader_location: 5,
+            format: wgpu::VertexFormat::Float32x4,
+        },
+        // gradient
+        wgpu::VertexAttribute {
+            offset: 80,
+            shader_location: 6,
+            format: wgpu::VertexFormat::Float32x4,
+        },
+        // view_mask
+        wgpu::VertexAttribute {
+            offset: 56,
+            shader_location: 7,
+            format: wgpu::VertexFormat::Uint32,
+        },
     ];
 
+    /// Returns the color, secondary color and gradient line of a glyph.
+    fn fill(
+        color: [f32; 4],
+        opacity: f32,
+        gradient: Option<LinearGradient>,
+    ) -> ([f32; 4], [f32; 4], [f32; 4]) {
+        // Colors are premultiplied, so fading scales every channel.
+        let color = color.map(|channel| channel * opacity);
+        match gradient {
+            Some(gradient) => (
+                color,
+                gradient.color.map(|channel| channel * opacity),
+                [
+                    gradient.start[0],
+                    gradient.start[1],
+                    gradient.end[0],
+                    gradient.end[1],
+                ],
+            ),
+            None => (color, color, [0.0; 4]),
+        }
+    }
+
     fn vertex_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<InstanceData>() as wgpu::BufferAddress,
@@ -63,10 +236,295 @@ impl InstanceData {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable)]
 struct Globals {
+    /// Rows `[m11, m21, m31, _]` and `[m12, m22, m32, _]` of the transform from
+    /// layout coordinates to the target, applied to the corners of each quad.
+    transform: [[f32; 4]; 2],
     screen_size: [f32; 2],
-    _padding: [f32; 2],
+    /// One of the `COLOR_TRANSFORM_*` constants.
+    color_transform: u32,
+    /// One of the `COVERAGE_CORRECTION_*` constants.
+    coverage_correction: u32,
+    /// See [`TextShadow`]; only read by the shadow pipelines.
+    shadow_color: [f32; 4],
+    shadow_offset: [f32; 2],
+    shadow_blur: f32,
+    /// Whether quad origins are rounded to whole pixels; see [`WgpuRenderer::set_pixel_snapping`].
+    pixel_snap: u32,
+    /// Factor linear colors are scaled by; see [`HdrOutput`].
+    output_scale: f32,
+    _padding: [u32; 3],
+    /// Rows of the transform from pixels of the target into the coordinates of
+    /// the clip rectangle applied by the shaders; see [`WgpuRenderer::push_clip_rect`].
+    clip_transform: [[f32; 4]; 2],
+    /// Minimum and maximum of that clip rectangle, unbounded without one.
+    clip_rect: [f32; 4],
+}
+
+impl Globals {
+    fn new(
+        screen_size: [f32; 2],
+        color_transform: u32,
+        coverage_correction: u32,
+        transform: euclid::Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+        shadow: Option<TextShadow>,
+        pixel_snap: bool,
+        output_scale: f32,
+        shader_clip: Option<ClipEntry>,
+    ) -> Self {
+        let shadow = shadow.unwrap_or(TextShadow {
+            offset: [0.0; 2],
+            blur_radius: 0.0,
+            color: [0.0; 4],
+        });
+        let shader_clip = shader_clip.and_then(|clip| Some((clip.transform.inverse()?, clip.rect)));
+        let (clip_transform, clip_rect) = match shader_clip {
+            Some((inverse, rect)) => (
+                [
+                    [inverse.m11, inverse.m21, inverse.m31, 0.0],
+                    [inverse.m12, inverse.m22, inverse.m32, 0.0],
+                ],
+                [rect.min.x, rect.min.y, rect.max.x, rect.max.y],
+            ),
+            None => (
+                [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0]],
+                [f32::MIN, f32::MIN, f32::MAX, f32::MAX],
+            ),
+        };
+        Self {
+            transform: [
+                [transform.m11, transform.m21, transform.m31, 0.0],
+                [transform.m12, transform.m22, transform.m32, 0.0],
+            ],
+            screen_size,
+            color_transform,
+            coverage_correction,
+            shadow_color: shadow.color,
+            shadow_offset: shadow.offset,
+            shadow_blur: shadow.blur_radius.max(0.0),
+            pixel_snap: pixel_snap.into(),
+            output_scale,
+            _padding: [0; 3],
+            clip_transform,
+            clip_rect,
+        }
+    }
+}
+
+/// A drop shadow drawn behind text; see [`WgpuRenderer::set_shadow`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextShadow {
+    /// Offset of the shadow from the text, in layout coordinates, so it moves
+    /// and scales with [`WgpuRenderer::set_transform`].
+    pub offset: [f32; 2],
+    /// Distance in layout coordinates over which the edges of the shadow fade
+    /// out. `0.0` draws a hard shadow.
+    pub blur_radius: f32,
+    /// Color of the shadow, premultiplied like the colors of the text. It is
+    /// scaled by the alpha of each glyph's color, so fading text fades its shadow.
+    pub color: [f32; 4],
+}
+
+/// Solid rectangles drawn along with a layout, in layout coordinates.
+///
+/// Rectangles take the transform, depth and clip of the layout's glyphs and the
+/// same color conversion, so they line up with the text at any zoom. With a
+/// depth-stencil state, compare with `LessEqual` so text passes over its
+/// backgrounds. See [`WgpuRenderer::render_with_rects_to`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LayoutRects<'a> {
+    /// Rectangles filled before the text and its shadows, such as selection
+    /// highlights, search matches or [`super::run_backgrounds`].
+    pub behind: &'a [FilledRect],
+    /// Rectangles filled over the text, such as [`super::text_decorations`].
+    ///
+    /// They are drawn in the last batch of glyphs, with the same pipeline, and
+    /// cast shadows like the glyphs do.
+    pub in_front: &'a [FilledRect],
+}
+
+/// How glyph textures are sampled; see [`WgpuRenderer::set_sampler`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SamplerConfig {
+    /// Filter used when glyphs are drawn larger or smaller than rasterized, or
+    /// off the pixel grid. [`wgpu::FilterMode::Nearest`] keeps pixel-art fonts
+    /// and text at whole-pixel zoom factors crisp.
+    pub filter: wgpu::FilterMode,
+    /// Maximum anisotropy, from 1 to 16, for text drawn at an angle by a
+    /// perspective transform. Only applies to linear filtering.
+    pub anisotropy_clamp: u16,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self {
+            filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 1,
+        }
+    }
+}
+
+impl SamplerConfig {
+    /// Returns the descriptor of the sampler, with the anisotropy clamped to
+    /// what wgpu accepts for the filter.
+    fn descriptor(&self) -> wgpu::SamplerDescriptor<'static> {
+        let anisotropy_clamp = match self.filter {
+            wgpu::FilterMode::Linear => self.anisotropy_clamp.clamp(1, 16),
+            wgpu::FilterMode::Nearest => 1,
+        };
+        wgpu::SamplerDescriptor {
+            label: Some("WgpuRenderer Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: self.filter,
+            min_filter: self.filter,
+            // Anisotropic filtering needs every filter to be linear.
+            mipmap_filter: self.filter,
+            anisotropy_clamp,
+            ..Default::default()
+        }
+    }
+}
+
+/// WGSL customizing how glyphs are shaded; see [`WgpuRenderer::with_shader`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CustomShader {
+    /// Defines `fn fragment_hook(in: VertexOutput, color: vec4<f32>) -> vec4<f32>`,
+    /// called for every fragment of a glyph with its premultiplied color, in the
+    /// encoding of the target, before coverage is applied.
+    ///
+    /// The snippet is appended to the built-in shaders of atlas glyphs,
+    /// standalone glyphs and LCD subpixel masks. It can read `globals` and the
+    /// `tex_coords`, `color`, `secondary_color`, `gradient` and `layout_pos`
+    /// of `VertexOutput`. Shadows are drawn without it.
+    FragmentHook(String),
+    /// Replaces the shaders of atlas and standalone glyphs.
+    ///
+    /// Each needs `vs_main` and `fs_main` entry points taking the instance
+    /// attributes of the built-in shaders and the bindings of group 0: the
+    /// globals uniform, a filtering sampler, and a `texture_2d_array<f32>` of
+    /// atlas pages or a `texture_2d<f32>` holding the standalone glyph. Shadows
+    /// and LCD subpixel masks keep the built-in shaders.
+    Replace {
+        /// Shader of glyphs drawn from the atlas.
+        atlas: String,
+        /// Shader of glyphs too large for the atlas, drawn on their own.
+        standalone: String,
+    },
+}
+
+/// Instance colors are written unchanged.
+const COLOR_TRANSFORM_NONE: u32 = 0;
+/// Instance colors are decoded from sRGB to linear.
+const COLOR_TRANSFORM_SRGB_TO_LINEAR: u32 = 1;
+/// Instance colors are encoded from linear to sRGB.
+const COLOR_TRANSFORM_LINEAR_TO_SRGB: u32 = 2;
+
+/// Encoding of the instance colors passed to [`WgpuRenderer`].
+///
+/// Colors are converted on the GPU to match the render target: `*Srgb` texture
+/// formats expect linear shader output (the hardware encodes it), and so do
+/// floating-point HDR formats such as `Rgba16Float`, which store linear values.
+/// All other formats, including `Rgb10a2Unorm`, store the shader output as-is.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Colors are already encoded for the render target and are written unchanged.
+    #[default]
+    Target,
+    /// Colors are sRGB-encoded, e.g. taken from a color picker or CSS.
+    Srgb,
+    /// Colors are linear.
+    Linear,
+}
+
+impl ColorSpace {
+    /// Returns the shader color transform for a target format.
+    fn transform(self, format: wgpu::TextureFormat) -> u32 {
+        match (self, linear_target(format)) {
+            (ColorSpace::Srgb, true) => COLOR_TRANSFORM_SRGB_TO_LINEAR,
+            (ColorSpace::Linear, false) => COLOR_TRANSFORM_LINEAR_TO_SRGB,
+            _ => COLOR_TRANSFORM_NONE,
+        }
+    }
+}
+
+/// Glyph coverage is blended unchanged.
+const COVERAGE_CORRECTION_NONE: u32 = 0;
+/// Glyph coverage is corrected to approximate blending in linear space.
+const COVERAGE_CORRECTION_LINEAR: u32 = 1;
+
+/// Space in which [`WgpuRenderer`] blends glyph edges with the target.
+///
+/// Blending happens in the encoding the target stores: `*Srgb` and
+/// floating-point formats blend in linear space, all other formats in
+/// sRGB-encoded space, where light text on a
+/// dark background looks too thin and dark text on a light one too bold.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlendSpace {
+    /// Glyph coverage is blended as the target blends.
+    #[default]
+    Target,
+    /// Glyph coverage is blended as in linear space. On targets that do not
+    /// store linear values, the shaders correct the coverage by the luminance of the text
+    /// color, exact for light text on black and dark text on white.
+    Linear,
+}
+
+impl BlendSpace {
+    /// Returns the shader coverage correction for a target format.
+    fn correction(self, format: wgpu::TextureFormat) -> u32 {
+        match (self, linear_target(format)) {
+            (BlendSpace::Linear, false) => COVERAGE_CORRECTION_LINEAR,
+            _ => COVERAGE_CORRECTION_NONE,
+        }
+    }
+}
+
+/// Returns whether `format` stores linear values: `*Srgb` formats, which the
+/// hardware encodes, and floating-point ones.
+fn linear_target(format: wgpu::TextureFormat) -> bool {
+    format.is_srgb() || float_target(format)
+}
+
+/// Returns whether `format` is a floating-point color format, as used by HDR
+/// swapchains.
+fn float_target(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format,
+        wgpu::TextureFormat::Rgba16Float
+            | wgpu::TextureFormat::Rgba32Float
+            | wgpu::TextureFormat::Rg11b10Ufloat
+    )
+}
+
+/// Luminance of `1.0` in scRGB, in nits.
+const SCRGB_NITS: f32 = 80.0;
+
+/// Brightness of the colors written to floating-point HDR targets, such as
+/// `Rgba16Float` swapchains. Other targets are written as by [`ColorSpace`].
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub enum HdrOutput {
+    /// Linear colors are written unchanged.
+    #[default]
+    Unscaled,
+    /// The target is an extended linear scRGB surface, where `1.0` is 80 nits.
+    /// Linear colors are scaled so `1.0` shows at `white_nits`, e.g. the SDR
+    /// white level of the display; colors above `1.0` get brighter still.
+    ScRgb {
+        /// Luminance of white text, in nits.
+        white_nits: f32,
+    },
+}
+
+impl HdrOutput {
+    /// Returns the factor linear colors are scaled by on a target of `format`.
+    fn scale(self, format: wgpu::TextureFormat) -> f32 {
+        match self {
+            HdrOutput::ScRgb { white_nits } if float_target(format) => white_nits / SCRGB_NITS,
+            _ => 1.0,
+        }
+    }
 }
 
 /// A text renderer using `wgpu` for hardware-accelerated rendering.
@@ -94,16 +552,14 @@ struct Globals {
 /// ```rust,no_run
 /// use suzuri::{
 ///     FontSystem, fontdb,
-///     renderer::GpuCacheConfig,
+///     renderer::{AtlasPacking, AtlasPriority, GpuCacheConfig},
 ///     text::{TextData, TextElement, TextLayoutConfig}
 /// };
 /// use std::num::NonZeroUsize;
 ///
 /// // Assume standard wgpu setup (device, queue, etc.)
-/// # async fn example() {
-/// # let (device, queue): (wgpu::Device, wgpu::Queue) = todo!();
+/// # fn example(device: wgpu::Device, queue: wgpu::Queue, view: wgpu::TextureView) {
 /// # let texture_format = wgpu::TextureFormat::Bgra8Unorm;
-/// # let view: wgpu::TextureView = todo!();
 /// # let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
 ///
 /// let font_system = FontSystem::new();
@@ -115,10 +571,14 @@ struct Globals {
 ///         texture_size: NonZeroUsize::new(1024).unwrap(),
 ///         tile_size: NonZeroUsize::new(32).unwrap(), // one side length
 ///         tiles_per_axis: NonZeroUsize::new(32).unwrap(),
+///         priority: AtlasPriority::Normal,
+///         padding: 1,
+///         extrude_edges: false,
+///         packing: AtlasPacking::Grid,
 ///     },
 /// ];
 /// // Pre-compile pipeline for the target format
-/// font_system.wgpu_init(&device, &cache_configs, &[texture_format]);
+/// font_system.wgpu_init(&device, &cache_configs, &[texture_format], None);
 ///
 /// // 2. Layout Text
 /// let mut data: TextData<[f32; 4]> = TextData::new();
@@ -129,9 +589,11 @@ struct Globals {
 /// font_system.wgpu_render(
 ///     &layout,
 ///     &device,
+///     &queue,
 ///     &mut encoder,
 ///     &view
 /// );
+/// queue.submit([encoder.finish()]);
 /// # }
 /// ```
 ///
@@ -144,6 +606,13 @@ struct Globals {
 ///   - Example: 50% transparent white should be `[0.5, 0.5, 0.5, 0.5]`, NOT `[1.0, 1.0, 1.0, 0.5]`.
 /// - **Compositing**: The renderer performs standard usage of the alpha masking from the font atlas.
 ///   It applies the mask to the input color. The pipeline is configured with `PREMULTIPLIED_ALPHA_BLENDING`.
+/// - **Color Space**: By default colors are written unchanged, so they must already be in the
+///   encoding of the render target. Use [`Self::set_color_space`] to declare them as sRGB or
+///   linear instead; the shaders then convert them for the target format.
+/// - **Blending**: Glyph edges blend in the space of the target, which makes light text
+///   look thin on targets that are not `*Srgb`. [`Self::set_blend_space`] can correct that.
+/// - **HDR**: `Rgba16Float` and `Rgb10a2Unorm` targets are supported like any other.
+///   [`Self::set_hdr_output`] scales text to the white level of scRGB swapchains.
 ///
 /// # Performance Optimizations
 ///
@@ -156,28 +625,81 @@ struct Globals {
 /// ```rust,no_run
 /// # use suzuri::{FontSystem, renderer::GpuCacheConfig};
 /// # use std::num::NonZeroUsize;
-/// # let (device, queue): (wgpu::Device, wgpu::Queue) = todo!();
-/// # let cache_configs = [];
+/// # fn example(device: wgpu::Device, cache_configs: &[GpuCacheConfig]) {
 /// let font_system = FontSystem::new();
 /// font_system.wgpu_init(
 ///     &device,
-///     &cache_configs,
-///     &[wgpu::TextureFormat::Bgra8Unorm, wgpu::TextureFormat::Rgba8Unorm] // Pre-compile these
+///     cache_configs,
+///     // Pre-compile these, including the format of an HDR swapchain.
+///     &[wgpu::TextureFormat::Bgra8Unorm, wgpu::TextureFormat::Rgba16Float],
+///     None,
 /// );
+/// # }
+/// ```
+///
+/// Compiling them can still take a while on the first run. On devices with
+/// [`wgpu::Features::PIPELINE_CACHE`], pass a [`wgpu::PipelineCache`] to reuse the
+/// compiled pipelines of earlier runs, and save its data before exiting:
+/// ```rust,no_run
+/// # use suzuri::{FontSystem, renderer::{GpuCacheConfig, WgpuRenderer}};
+/// # fn example(
+/// #     adapter: wgpu::Adapter,
+/// #     device: wgpu::Device,
+/// #     cache_configs: &[GpuCacheConfig],
+/// #     formats: &[wgpu::TextureFormat],
+/// # ) {
+/// let path = wgpu::util::pipeline_cache_key(&adapter.get_info())
+///     .map(|key| std::env::temp_dir().join(key));
+/// let data = path.as_ref().and_then(|path| std::fs::read(path).ok());
+/// // SAFETY: The data was saved by `pipeline_cache_data` for the same adapter.
+/// let cache = unsafe { WgpuRenderer::create_pipeline_cache(&device, data.as_deref()) };
+///
+/// let font_system = FontSystem::new();
+/// font_system.wgpu_init(&device, cache_configs, formats, cache.as_ref());
+///
+/// // ... render ...
+///
+/// if let (Some(path), Some(data)) = (path, font_system.wgpu_pipeline_cache_data()) {
+///     std::fs::write(path, data).ok();
+/// }
+/// # }
 /// ```
 ///
+/// ## Steady-State Frames
+/// Rendering layouts whose glyphs are all cached, with [`WgpuRenderer::render`],
+/// [`WgpuRenderer::render_to`] or [`WgpuRenderer::render_all`], writes nothing
+/// to the queue and creates no buffers. Preparing the draws makes no heap
+/// allocation: instances are written into reused vectors, globals or instance
+/// data that already match what was uploaded before are not written again, and
+/// the tokens keeping uploaded space from being reused are recycled once the
+/// GPU is done with earlier frames. Recording the passes still allocates in
+/// `wgpu`, including the callback each pass registers to release the frame's
+/// space, but how much does not depend on the text. Glyphs drawn standalone
+/// (too large for the atlas) are re-uploaded every frame.
+///
 /// # Important Notes
 /// - **Atlas Management**: The renderer manages an internal texture atlas array.
 ///   It automatically handles updates and uploads. Ensure `configs` passed to `new`
-///   are sufficient for your text usage preventing frequent cache trashing (fallback strategy handles overflow but can be slower).
-/// - **Command Encoder**: The `render` method takes a mutable `CommandEncoder`. It will record
-///   copy commands (for atlas/uniform updates) and a render pass.
+///   are sufficient for your text usage preventing frequent cache trashing (fallback strategy handles overflow but can be slower),
+///   or let the atlas grow on overflow with [`WgpuRenderer::set_growth_budget`].
+/// - **Uploads**: Uniforms, instances and glyph pixels are written with `Queue::write_*`
+///   into space no recorded command reads, and glyph pixels are copied into the atlas by
+///   commands of the `CommandEncoder` passed to `render`. Several renders can therefore
+///   be recorded before one submission, see [`WgpuRenderer::render_to`].
 /// - **Thread Safety**: `WgpuRenderer` employs internal mutability (`RefCell`) for resource
 ///   management, so it is **not** `Sync`. Even though `wgpu` resources are thread-safe,
 ///   this renderer is designed to be used from a single thread (usually the main render thread).
 pub struct WgpuRenderer {
     pub gpu_renderer: GpuRenderer,
     resources: WgpuResources,
+    color_space: ColorSpace,
+    blend_space: BlendSpace,
+    hdr_output: HdrOutput,
+    shadow: Option<TextShadow>,
+    pixel_snap: bool,
+    indirect_draws: bool,
+    /// Clip rectangles of [`Self::push_clip_rect`], outermost first.
+    clip_stack: Vec<ClipEntry>,
 }
 
 /// Resources used by the renderer, including pipelines, buffers, and textures.
@@ -190,25 +712,87 @@ struct WgpuResources {
     pipelines: std::cell::RefCell<HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>>,
     /// Cache of pipelines for standalone large glyphs.
     standalone_pipelines: std::cell::RefCell<HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>>,
+    /// Cache of dual-source blending pipelines for LCD subpixel masks.
+    subpixel_pipelines: std::cell::RefCell<HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>>,
+    /// Caches of pipelines drawing the shadows of atlas and standalone glyphs.
+    shadow_pipelines: std::cell::RefCell<HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>>,
+    standalone_shadow_pipelines:
+        std::cell::RefCell<HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>>,
+    /// Depth-stencil state of all pipelines; see [`WgpuRenderer::set_depth_stencil`].
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    /// Cache all pipelines are compiled with; see [`WgpuRenderer::new`].
+    pipeline_cache: Option<wgpu::PipelineCache>,
 
     pipeline_layout: wgpu::PipelineLayout,
     standalone_pipeline_layout: wgpu::PipelineLayout,
     shader: wgpu::ShaderModule,
     standalone_shader: wgpu::ShaderModule,
+    /// Shader drawing LCD subpixel masks, created once the device is known to
+    /// support dual-source blending; see [`WgpuRenderer::set_lcd_antialiasing`].
+    subpixel_shader: Option<wgpu::ShaderModule>,
+    /// Shader the renderer was created with, to recreate the shaders on
+    /// another device.
+    custom_shader: Option<CustomShader>,
+    /// Shaders of atlas and standalone glyphs replacing `shader` and
+    /// `standalone_shader`, which still draw shadows; see [`CustomShader::Replace`].
+    replacement_shaders: Option<(wgpu::ShaderModule, wgpu::ShaderModule)>,
+    /// Views of the multiview passes all pipelines draw into; see
+    /// [`WgpuRenderer::set_multiview`].
+    multiview: Option<NonZeroU32>,
+    /// `shader` and `standalone_shader` with multiview vertex stages, created
+    /// once multiview is enabled.
+    multiview_shaders: Option<(wgpu::ShaderModule, wgpu::ShaderModule)>,
+    /// Views the instances of the next renders are drawn to; see
+    /// [`WgpuRenderer::set_view_mask`].
+    view_mask: u32,
 
     /// The texture atlas array used for caching small glyphs.
-    atlas_texture: wgpu::Texture,
+    ///
+    /// Recreated with more layers when the cache adds pages.
+    atlas_texture: std::cell::RefCell<wgpu::Texture>,
+    /// Copy of the atlas pixels in host memory, if enabled; see
+    /// [`WgpuRenderer::set_atlas_host_copy`].
+    atlas_copy: std::cell::RefCell<Option<AtlasCopy>>,
     sampler: wgpu::Sampler,
+    /// Settings `sampler` was created with.
+    sampler_config: SamplerConfig,
+    /// Counters of the current frame, reset by `begin_frame`.
+    frame_stats: std::cell::Cell<RenderStats>,
 
-    /// Shared instance buffer for drawing glyph quads. Resizes automatically.
-    instance_buffer: std::cell::RefCell<wgpu::Buffer>,
+    /// Shared instance buffer for drawing glyph quads, used as a ring of frames.
+    ///
+    /// Replaced by a larger one when a frame does not fit; draws recorded
+    /// earlier keep the old buffer, and their instances, alive.
+    instance_buffer: std::cell::RefCell<UploadRing>,
+    /// Lease of the space the current frame reads, held by its passes.
+    frame: std::cell::RefCell<FrameLease>,
+    /// Leases of earlier frames, reused once their passes are done.
+    spare_frames: std::cell::RefCell<Vec<FrameLease>>,
+    /// Pixels copied into textures by commands of the encoder, used as a ring
+    /// like `instance_buffer`.
+    staging_buffer: std::cell::RefCell<UploadRing>,
+    /// Arguments of indirect draws, used as a ring like `instance_buffer`.
+    /// Created on first use, as indirect buffers need indirect execution.
+    indirect_buffer: std::cell::RefCell<Option<UploadRing>>,
+    /// Where the previous and current frame are placed in `instance_buffer`.
+    instance_ring: std::cell::Cell<InstanceRing>,
+    /// Scissor rectangle of the draws of the current frame, `[x, y, width, height]`.
+    frame_scissor: std::cell::Cell<[u32; 4]>,
+    /// Whether the atlas draws of the current frame sample LCD subpixel masks.
+    frame_subpixel: std::cell::Cell<bool>,
+    /// Whether the draws of the current frame are preceded by shadows.
+    frame_shadow: std::cell::Cell<bool>,
 
-    _bind_group_layout: wgpu::BindGroupLayout,
+    bind_group_layout: wgpu::BindGroupLayout,
     standalone_bind_group_layout: wgpu::BindGroupLayout,
 
-    /// Uniform buffer for global data (screen size, etc.).
-    globals_buffer: wgpu::Buffer,
-    globals_bind_group: wgpu::BindGroup,
+    /// Uniform buffer for global data (screen size, etc.), used as a ring like
+    /// `instance_buffer` with the globals of each render at their own offset.
+    globals_buffer: std::cell::RefCell<UploadRing>,
+    /// Offset of the globals of the current frame in `globals_buffer`.
+    globals_offset: std::cell::Cell<u32>,
+    /// Binds the globals, sampler and atlas. Recreated along with the atlas.
+    globals_bind_group: std::cell::RefCell<wgpu::BindGroup>,
 
     /// Resources for drawing a single large glyph that doesn't fit in the atlas.
     standalone_resources: std::cell::RefCell<Option<StandaloneResources>>,
@@ -220,6 +804,18 @@ struct WgpuResources {
     /// **Staging Vector for Pixel Padding**
     /// Reused across frames to avoid allocations when padding texture data to 256-byte alignment.
     pixel_staging: std::cell::RefCell<Vec<u8>>,
+
+    /// **Mirror of the Instance Buffer**
+    /// Instance data as of the last write, indexed by instance slot. Draws whose
+    /// instances already match are not uploaded again. Cleared when the buffer is recreated.
+    instance_shadow: std::cell::RefCell<Vec<InstanceData>>,
+    /// Globals last written to the globals buffer, at the region it allocated last.
+    uploaded_globals: std::cell::Cell<Option<Globals>>,
+
+    #[cfg(test)]
+    queue_writes: std::cell::Cell<usize>,
+    #[cfg(test)]
+    staging_buffers_created: std::cell::Cell<usize>,
 }
 
 /// Resources required for rendering a standalone large glyph.
@@ -234,9 +830,26 @@ const SHADER: &str = include_str!("wgpu_renderer/wgpu_renderer_shader.wgsl");
 
 const STANDALONE_SHADER: &str = include_str!("wgpu_renderer/wgpu_renderer_standalone.wgsl");
 
+/// Shadow stages, appended to [`SHADER`] and [`STANDALONE_SHADER`].
+const SHADOW_SHADER: &str = include_str!("wgpu_renderer/wgpu_renderer_shadow.wgsl");
+
+/// Fragment stage for LCD subpixel masks, appended to [`SHADER`].
+const SUBPIXEL_FRAGMENT: &str = include_str!("wgpu_renderer/wgpu_renderer_subpixel.wgsl");
+
+/// Fragment hook appended to the glyph shaders unless replaced by
+/// [`CustomShader::FragmentHook`].
+const FRAGMENT_HOOK: &str = include_str!("wgpu_renderer/wgpu_renderer_fragment_hook.wgsl");
+
+/// Vertex stages for multiview passes, appended after [`SHADOW_SHADER`].
+const MULTIVIEW_VERTEX: &str = include_str!("wgpu_renderer/wgpu_renderer_multiview.wgsl");
+
 impl WgpuRenderer {
     /// Requires at least one `GpuCacheConfig`.
     ///
+    /// Pipelines for `formats` are compiled up front, the others on first use.
+    /// All of them are compiled with `pipeline_cache`, if given, so pipelines
+    /// compiled in earlier runs load from it; see [`Self::create_pipeline_cache`].
+    ///
     /// # Panics
     ///
     /// Panics if `configs` is empty.
@@ -244,6 +857,53 @@ impl WgpuRenderer {
         device: &wgpu::Device,
         configs: &[GpuCacheConfig],
         formats: &[wgpu::TextureFormat],
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        Self::create(device, configs, formats, pipeline_cache, None)
+    }
+
+    /// Like [`Self::new`], with glyphs shaded by `shader`.
+    ///
+    /// The shaders and the pipelines for `formats`, or for
+    /// [`wgpu::TextureFormat::Rgba8Unorm`] without any, are validated against
+    /// the bindings and instance attributes of the renderer, and the first
+    /// validation error is returned. On the web, where errors are reported
+    /// asynchronously, they reach the uncaptured error handler of the device
+    /// instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `configs` is empty.
+    pub fn with_shader(
+        device: &wgpu::Device,
+        configs: &[GpuCacheConfig],
+        formats: &[wgpu::TextureFormat],
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+        shader: &CustomShader,
+    ) -> Result<Self, wgpu::Error> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let renderer = Self::create(device, configs, formats, pipeline_cache, Some(shader));
+        if formats.is_empty() {
+            let format = wgpu::TextureFormat::Rgba8Unorm;
+            renderer.resources.get_pipeline(device, format);
+            renderer.resources.get_standalone_pipeline(device, format);
+        }
+
+        // Native backends have reported their errors by the time the scope is popped.
+        let mut error = std::pin::pin!(device.pop_error_scope());
+        let mut context = std::task::Context::from_waker(std::task::Waker::noop());
+        match error.as_mut().poll(&mut context) {
+            std::task::Poll::Ready(Some(error)) => Err(error),
+            _ => Ok(renderer),
+        }
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        configs: &[GpuCacheConfig],
+        formats: &[wgpu::TextureFormat],
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+        custom_shader: Option<&CustomShader>,
     ) -> Self {
         if configs.is_empty() {
             log::error!("At least one GPU cache config is required");
@@ -265,192 +925,576 @@ impl WgpuRenderer {
             .expect("Checked above") as u32;
         let layers = configs.len() as u32;
 
-        let atlas_texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Glyph Atlas Array"),
-            size: wgpu::Extent3d {
+        let resources = WgpuResources::new(
+            device,
+            wgpu::Extent3d {
                 width: max_width,
                 height: max_height,
                 depth_or_array_layers: layers,
             },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::R8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
-        });
+            pipeline_cache,
+            custom_shader,
+        );
 
-        let atlas_view = atlas_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        for &format in formats {
+            resources.get_pipeline(device, format);
+            resources.get_standalone_pipeline(device, format);
+        }
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
+        Self {
+            gpu_renderer,
+            resources,
+            color_space: ColorSpace::default(),
+            blend_space: BlendSpace::default(),
+            hdr_output: HdrOutput::default(),
+            shadow: None,
+            pixel_snap: false,
+            indirect_draws: false,
+            clip_stack: Vec::new(),
+        }
+    }
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("WgpuRenderer Bind Group Layout"),
-            entries: &[
-                // Globals
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-                // Sampler
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-                // Texture Array
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2Array,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-            ],
-        });
+    /// Creates a pipeline cache for [`Self::new`], filled with `data` saved by
+    /// [`Self::pipeline_cache_data`] in an earlier run. Returns `None` without
+    /// [`wgpu::Features::PIPELINE_CACHE`] on `device`.
+    ///
+    /// Data the device cannot use, e.g. after a driver update, is ignored and
+    /// the cache starts out empty. Store the data of each adapter under its
+    /// [`wgpu::util::pipeline_cache_key`].
+    ///
+    /// # Safety
+    ///
+    /// `data` must have been returned by [`Self::pipeline_cache_data`] or
+    /// [`wgpu::PipelineCache::get_data`]; see [`wgpu::Device::create_pipeline_cache`].
+    pub unsafe fn create_pipeline_cache(
+        device: &wgpu::Device,
+        data: Option<&[u8]>,
+    ) -> Option<wgpu::PipelineCache> {
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return None;
+        }
+        // SAFETY: Guaranteed by the caller.
+        Some(unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("WgpuRenderer Pipeline Cache"),
+                data,
+                fallback: true,
+            })
+        })
+    }
 
-        // Standalone layout (Texture 2D instead of Array)
-        let standalone_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("WgpuRenderer Standalone Bind Group Layout"),
-                entries: &[
-                    // Globals
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                    // Sampler
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                    // Texture 2D
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                ],
-            });
+    /// Returns the contents of the pipeline cache passed to [`Self::new`], to be
+    /// saved and passed to [`Self::create_pipeline_cache`] in the next run.
+    ///
+    /// Returns `None` without a cache, or if the backend does not support
+    /// reading it.
+    pub fn pipeline_cache_data(&self) -> Option<Vec<u8>> {
+        self.resources.pipeline_cache.as_ref()?.get_data()
+    }
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("WgpuRenderer Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
+    /// Clears the renderer's cache, freeing GPU memory.
+    pub fn clear_cache(&mut self) {
+        self.gpu_renderer.clear_cache();
+    }
 
-        let standalone_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("WgpuRenderer Standalone Pipeline Layout"),
-                bind_group_layouts: &[&standalone_bind_group_layout],
-                push_constant_ranges: &[],
-            });
+    /// Lets the glyph cache add atlas layers, up to `bytes` in total, when it
+    /// overflows instead of evicting glyphs.
+    ///
+    /// The atlas texture array is recreated with the new layers, copying the
+    /// cached glyphs, during the render that needs them. Every layer is as large
+    /// as the largest configured page, while the budget counts the size of the
+    /// page a layer holds, so the texture may use more memory than the budget.
+    /// The budget is capped so the layers stay within the device's
+    /// `max_texture_array_layers`. See [`GpuRenderer::set_growth_budget`].
+    pub fn set_growth_budget(&mut self, device: &wgpu::Device, bytes: usize) {
+        let free_layers = (device.limits().max_texture_array_layers as usize)
+            .saturating_sub(self.gpu_renderer.page_count());
+        let min_page_bytes = (0..self.gpu_renderer.page_count())
+            .filter_map(|index| self.gpu_renderer.page_config(index))
+            .map(|config| config.texture_size.get().saturating_pow(2))
+            .min()
+            .unwrap_or(0);
+        self.gpu_renderer
+            .set_growth_budget(bytes.min(free_layers.saturating_mul(min_page_bytes)));
+    }
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("WgpuRenderer Shader"),
-            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
-        });
+    /// Lets the glyph cache add atlas layers, up to `bytes` in total, for glyphs
+    /// larger than every tile.
+    ///
+    /// When an overflow page is larger than the layers, every layer of the atlas
+    /// texture array grows to its size, so large budgets cost more memory than
+    /// they count. The budget is capped so a page stays within the device's
+    /// `max_texture_dimension_2d`. See [`GpuRenderer::set_overflow_budget`].
+    pub fn set_overflow_budget(&mut self, device: &wgpu::Device, bytes: usize) {
+        let max_size = device.limits().max_texture_dimension_2d as usize;
+        self.gpu_renderer
+            .set_overflow_budget(bytes.min(max_size.saturating_pow(2)));
+    }
 
-        let standalone_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("WgpuRenderer Standalone Shader"),
-            source: wgpu::ShaderSource::Wgsl(STANDALONE_SHADER.into()),
-        });
+    /// Ends the current frame, see [`GpuRenderer::end_frame`].
+    pub fn end_frame(&mut self) {
+        self.gpu_renderer.end_frame();
+    }
 
-        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Instance Buffer"),
-            size: (INITIAL_INSTANCE_CAPACITY * std::mem::size_of::<InstanceData>()) as u64,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+    /// Drops the cached glyphs of font `font_id`, e.g. after its file changed.
+    pub fn forget_font(&mut self, font_id: fontdb::ID) {
+        self.gpu_renderer.forget_font(font_id);
+    }
 
-        let globals_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Globals Buffer"),
-            size: std::mem::size_of::<Globals>() as u64,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+    /// Returns the glyph cache's counters, see [`GpuRenderer::stats`].
+    pub fn stats(&self) -> CacheStats {
+        self.gpu_renderer.stats()
+    }
 
-        let globals_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Globals Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: globals_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&atlas_view),
-                },
-            ],
-        });
+    /// Resets the counters of [`Self::stats`].
+    pub fn reset_stats(&mut self) {
+        self.gpu_renderer.reset_stats();
+    }
 
-        let resources = WgpuResources {
-            pipelines: std::cell::RefCell::new(HashMap::new()),
-            standalone_pipelines: std::cell::RefCell::new(HashMap::new()),
-            pipeline_layout,
-            standalone_pipeline_layout,
-            shader,
-            standalone_shader,
-            atlas_texture,
-            sampler,
-            instance_buffer: std::cell::RefCell::new(instance_buffer),
-            _bind_group_layout: bind_group_layout,
-            standalone_bind_group_layout,
-            globals_buffer,
-            globals_bind_group,
-            standalone_resources: std::cell::RefCell::new(None),
-            instance_data_staging: std::cell::RefCell::new(Vec::new()),
-            pixel_staging: std::cell::RefCell::new(Vec::new()),
+    /// Repacks up to `limit` cached glyphs into fewer atlas pages, recording
+    /// texture-to-texture copies of their texels in `encoder`, and returns how
+    /// many glyphs moved.
+    ///
+    /// See [`GpuRenderer::compact_cache`].
+    pub fn compact_cache(&mut self, limit: usize, encoder: &mut wgpu::CommandEncoder) -> usize {
+        let resources = &self.resources;
+        self.gpu_renderer
+            .compact_cache(limit, |moves| resources.move_atlas(encoder, moves))
+    }
+
+    /// Compares what `layout` and `rects` draw with what they drew when last
+    /// tracked under `layout_id`, and remembers it for the next call.
+    ///
+    /// The damage covers the shadow, the linear filtering of glyphs and the
+    /// rectangles as the renderer currently draws them. See
+    /// [`GpuRenderer::track_damage`].
+    pub fn track_damage<T: PartialEq + Copy + Send + 'static>(
+        &mut self,
+        layout_id: u64,
+        layout: &TextLayout<T>,
+        rects: LayoutRects<'_>,
+        font_storage: &FontStorage,
+    ) -> LayoutDamage {
+        let fills: Vec<FilledRect> = rects.behind.iter().chain(rects.in_front).cloned().collect();
+        let effects = DamageEffects {
+            rects: &fills,
+            shadow: self
+                .shadow
+                .map(|shadow| (shadow.offset, shadow.blur_radius)),
+            bleed: match self.resources.sampler_config.filter {
+                wgpu::FilterMode::Linear => 1.0,
+                wgpu::FilterMode::Nearest => 0.0,
+            },
         };
+        self.gpu_renderer
+            .track_damage_with(layout_id, layout, effects, font_storage)
+    }
 
-        for &format in formats {
-            resources.get_pipeline(device, format);
-            resources.get_standalone_pipeline(device, format);
+    /// Forgets what was remembered for `layout_id` by [`Self::track_damage`].
+    pub fn forget_layout(&mut self, layout_id: u64) {
+        self.gpu_renderer.forget_layout(layout_id);
+    }
+
+    /// Sets the encoding of the instance colors. Takes effect on the next render.
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    /// Returns the encoding of the instance colors.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Sets the space glyph edges are blended in. Takes effect on the next render.
+    pub fn set_blend_space(&mut self, blend_space: BlendSpace) {
+        self.blend_space = blend_space;
+    }
+
+    /// Returns the space glyph edges are blended in.
+    pub fn blend_space(&self) -> BlendSpace {
+        self.blend_space
+    }
+
+    /// Sets the brightness of text on floating-point HDR targets. Takes effect
+    /// on the next render.
+    pub fn set_hdr_output(&mut self, hdr_output: HdrOutput) {
+        self.hdr_output = hdr_output;
+    }
+
+    /// Returns the brightness of text on floating-point HDR targets.
+    pub fn hdr_output(&self) -> HdrOutput {
+        self.hdr_output
+    }
+
+    /// Sets the drop shadow drawn behind the glyphs of the next renders, or
+    /// `None`, the default, for no shadow.
+    ///
+    /// Every draw of instances is preceded by a draw of the same instances with
+    /// the shadow pipeline, which offsets and blurs their coverage, so no second
+    /// render or layout is needed. [`Self::render_in_pass`] draws the shadows
+    /// of all glyphs before any glyph; [`Self::render_to`] does so per draw
+    /// call, so with a cache too small for the layout, a shadow may cover glyphs
+    /// drawn before it. Blurring takes 25 texture samples per pixel of shadow.
+    pub fn set_shadow(&mut self, shadow: Option<TextShadow>) {
+        self.shadow = shadow;
+    }
+
+    /// Returns the drop shadow drawn behind text.
+    pub fn shadow(&self) -> Option<TextShadow> {
+        self.shadow
+    }
+
+    /// Sets the transform from layout coordinates to the target, applied in the
+    /// vertex shader. Takes effect on the next render.
+    ///
+    /// Pans, zooms or rotates rendered text without laying it out again: the
+    /// transform is part of the globals, so a scrolled frame of cached text only
+    /// rewrites those. Glyphs are rasterized at their laid out size, so large
+    /// zoom factors blur them. To transform a single render, use
+    /// [`Self::render_transformed`] or [`Self::render_transformed_to`].
+    /// See [`GpuRenderer::set_transform`].
+    pub fn set_transform(
+        &mut self,
+        transform: euclid::Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+    ) {
+        self.gpu_renderer.set_transform(transform);
+    }
+
+    /// Returns the transform from layout coordinates to the target.
+    pub fn transform(&self) -> euclid::Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit> {
+        self.gpu_renderer.transform()
+    }
+
+    /// Pushes a clip rectangle in layout coordinates onto the clip stack, e.g.
+    /// for a nested scroll area. The next renders draw only inside every
+    /// rectangle on the stack and the clip of each layout, until it is popped.
+    ///
+    /// The rectangle is placed on the target by the current
+    /// [transform](Self::set_transform). While it stays axis-aligned there, it
+    /// clips with the scissor rectangle of each batch. Otherwise the shaders
+    /// clip to it, and to one rectangle per render at most: rotated rectangles
+    /// around the innermost one clip to their bounds unless pushed under the
+    /// same transform. Each render binds its shader clip with its own globals,
+    /// so renders recorded before one submission keep their clips, and glyphs
+    /// of [`CustomShader::Replace`] shaders only clip to the scissor rectangle.
+    pub fn push_clip_rect(&mut self, rect: euclid::Box2D<f32, euclid::UnknownUnit>) {
+        self.clip_stack.push(ClipEntry {
+            rect,
+            transform: self.gpu_renderer.transform(),
+        });
+    }
+
+    /// Pops the innermost clip rectangle of [`Self::push_clip_rect`] and
+    /// returns it, or `None` when the stack is empty.
+    pub fn pop_clip_rect(&mut self) -> Option<euclid::Box2D<f32, euclid::UnknownUnit>> {
+        self.clip_stack.pop().map(|entry| entry.rect)
+    }
+
+    /// Returns the number of clip rectangles on the clip stack.
+    pub fn clip_depth(&self) -> usize {
+        self.clip_stack.len()
+    }
+
+    /// Enables or disables rounding the origin of each glyph to whole pixels of
+    /// the target in the vertex shader. Defaults to disabled.
+    ///
+    /// Glyph bitmaps are drawn at the resolution they were rasterized at, so
+    /// under a fractional scale, such as a 1.25 DPI factor in the transform,
+    /// their texels straddle pixels and blur. Snapping keeps them crisp without
+    /// rounding the layout coordinates; glyphs move by less than half a pixel.
+    pub fn set_pixel_snapping(&mut self, enabled: bool) {
+        self.pixel_snap = enabled;
+    }
+
+    /// Returns whether glyph origins are rounded to whole pixels of the target.
+    pub fn pixel_snapping(&self) -> bool {
+        self.pixel_snap
+    }
+
+    /// Records the draws of [`PreparedText`] with `multi_draw_indirect` from
+    /// arguments written by [`Self::prepare`] into a buffer kept across frames,
+    /// and returns whether it is enabled. Defaults to disabled.
+    ///
+    /// Consecutive draws with the same pipeline, bindings and clip are issued
+    /// by one call, which saves encoding time when many small batches are
+    /// prepared at once, e.g. by [`Self::render_all`] with a controller that
+    /// records copies before passes. Only prepared text benefits: renders that
+    /// record copies between their passes draw each batch directly. Without
+    /// [`wgpu::Features::INDIRECT_FIRST_INSTANCE`] on `device`, draws stay
+    /// direct and this returns `false`. The adapter also needs
+    /// [`wgpu::DownlevelFlags::INDIRECT_EXECUTION`], which every adapter with
+    /// the feature has on native backends.
+    pub fn set_indirect_draws(&mut self, device: &wgpu::Device, enabled: bool) -> bool {
+        self.indirect_draws = enabled
+            && device
+                .features()
+                .contains(wgpu::Features::INDIRECT_FIRST_INSTANCE);
+        self.indirect_draws
+    }
+
+    /// Returns whether prepared text is drawn with `multi_draw_indirect`.
+    pub fn indirect_draws(&self) -> bool {
+        self.indirect_draws
+    }
+
+    /// Sets the number of views of the multiview passes the text is drawn in,
+    /// e.g. two for the eyes of a stereo headset, and returns whether multiview
+    /// is enabled. `None`, the default, draws into ordinary passes.
+    ///
+    /// Targets must then be array views of that many layers, all drawn by each
+    /// draw call; instances go to the views of [`Self::set_view_mask`]. Without
+    /// [`wgpu::Features::MULTIVIEW`] on `device`, pipelines stay single-view and
+    /// this returns `false`. LCD subpixel masks and glyphs drawn by
+    /// [`CustomShader::Replace`] shaders go to every view. When the views
+    /// change, the pipelines are recreated on the next render.
+    pub fn set_multiview(&mut self, device: &wgpu::Device, views: Option<NonZeroU32>) -> bool {
+        let views = views.filter(|_| device.features().contains(wgpu::Features::MULTIVIEW));
+        if views.is_some() && self.resources.multiview_shaders.is_none() {
+            let hook = self.resources.fragment_hook();
+            let module = |label, source| {
+                device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(label),
+                    source: wgpu::ShaderSource::Wgsl(
+                        format!("{source}\n{SHADOW_SHADER}\n{hook}\n{MULTIVIEW_VERTEX}").into(),
+                    ),
+                })
+            };
+            self.resources.multiview_shaders = Some((
+                module("WgpuRenderer Multiview Shader", SHADER),
+                module(
+                    "WgpuRenderer Multiview Standalone Shader",
+                    STANDALONE_SHADER,
+                ),
+            ));
+        }
+        if self.resources.multiview != views {
+            self.resources.multiview = views;
+            self.resources.clear_pipelines();
         }
+        views.is_some()
+    }
 
-        Self {
-            gpu_renderer,
-            resources,
+    /// Returns the number of views of the multiview passes the text is drawn in.
+    pub fn multiview(&self) -> Option<NonZeroU32> {
+        self.resources.multiview
+    }
+
+    /// Sets the views of a multiview pass the glyphs and rectangles of the next
+    /// renders are drawn to, bit `i` standing for view `i`. Defaults to all
+    /// views, e.g. for HUD text seen by both eyes. See [`Self::set_multiview`].
+    pub fn set_view_mask(&mut self, view_mask: u32) {
+        self.resources.view_mask = view_mask;
+    }
+
+    /// Returns the views the glyphs of the next renders are drawn to.
+    pub fn view_mask(&self) -> u32 {
+        self.resources.view_mask
+    }
+
+    /// Sets the depth written for the glyphs of the next renders, between `0.0`
+    /// and `1.0`. See [`GpuRenderer::set_depth`] and [`Self::set_depth_stencil`].
+    pub fn set_depth(&mut self, depth: f32) {
+        self.gpu_renderer.set_depth(depth);
+    }
+
+    /// Returns the depth written for glyphs.
+    pub fn depth(&self) -> f32 {
+        self.gpu_renderer.depth()
+    }
+
+    /// Sets the depth-stencil state of the text pipelines, so text is depth
+    /// tested against the rest of the scene. `None`, the default, draws without
+    /// a depth attachment.
+    ///
+    /// Every pass the text is drawn in must then have a matching depth-stencil
+    /// attachment, so draw with [`Self::render_to`], e.g. with
+    /// [`SimpleRenderPass::with_depth_stencil_attachment`], or
+    /// [`Self::render_in_pass`]; the passes of [`Self::render`] have none. When the state changes, the
+    /// pipelines are recreated on the next render.
+    pub fn set_depth_stencil(&mut self, depth_stencil: Option<wgpu::DepthStencilState>) {
+        if self.resources.depth_stencil == depth_stencil {
+            return;
         }
+        self.resources.depth_stencil = depth_stencil;
+        self.resources.clear_pipelines();
     }
 
-    /// Clears the renderer's cache, freeing GPU memory.
-    pub fn clear_cache(&mut self) {
-        self.gpu_renderer.clear_cache();
+    /// Returns the depth-stencil state of the text pipelines.
+    pub fn depth_stencil(&self) -> Option<&wgpu::DepthStencilState> {
+        self.resources.depth_stencil.as_ref()
+    }
+
+    /// Enables or disables ClearType-style LCD subpixel antialiasing and returns
+    /// whether it is enabled. Defaults to disabled.
+    ///
+    /// Glyphs are cached as subpixel masks for a horizontal RGB stripe layout and
+    /// drawn with dual-source blending, so each color channel of the target is
+    /// blended with the coverage of its own subpixel. Without
+    /// [`wgpu::Features::DUAL_SOURCE_BLENDING`] on `device`, text stays grayscale
+    /// and this returns `false`.
+    ///
+    /// Intended for opaque targets whose pixels map one to one onto the screen;
+    /// rotated or scaled text shows color fringes. Toggling it caches every glyph
+    /// again. See [`GpuRenderer::set_lcd_antialiasing`].
+    pub fn set_lcd_antialiasing(&mut self, device: &wgpu::Device, enabled: bool) -> bool {
+        let enabled = enabled
+            && device
+                .features()
+                .contains(wgpu::Features::DUAL_SOURCE_BLENDING);
+        if enabled && self.resources.subpixel_shader.is_none() {
+            self.resources.subpixel_shader = Some(
+                device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("WgpuRenderer Subpixel Shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        format!(
+                            "enable dual_source_blending;\n{SHADER}\n{SUBPIXEL_FRAGMENT}\n{}",
+                            self.resources.fragment_hook()
+                        )
+                        .into(),
+                    ),
+                }),
+            );
+        }
+        self.gpu_renderer.set_lcd_antialiasing(enabled);
+        enabled
+    }
+
+    /// Returns whether LCD subpixel antialiasing is enabled.
+    pub fn lcd_antialiasing(&self) -> bool {
+        self.gpu_renderer.lcd_antialiasing() && self.resources.subpixel_shader.is_some()
+    }
+
+    /// Sets how glyph textures are sampled. Defaults to linear filtering
+    /// without anisotropy.
+    ///
+    /// Takes effect on the next render; draws prepared before keep the previous
+    /// sampler. Nearest filtering also applies to the blur of shadows.
+    pub fn set_sampler(&mut self, device: &wgpu::Device, config: SamplerConfig) {
+        if self.resources.sampler_config == config {
+            return;
+        }
+        let resources = &mut self.resources;
+        resources.sampler = device.create_sampler(&config.descriptor());
+        resources.sampler_config = config;
+        *resources.globals_bind_group.borrow_mut() = WgpuResources::create_globals_bind_group(
+            device,
+            &resources.bind_group_layout,
+            resources.globals_buffer.borrow().buffer(),
+            &resources.sampler,
+            &resources.atlas_texture.borrow(),
+        );
+        // The shared standalone texture is bound along with the sampler.
+        *resources.standalone_resources.borrow_mut() = None;
+    }
+
+    /// Returns how glyph textures are sampled.
+    pub fn sampler(&self) -> SamplerConfig {
+        self.resources.sampler_config
+    }
+
+    /// Keeps a copy of the atlas pixels in host memory, so [`Self::recreate`]
+    /// restores the cached glyphs without rasterizing them again. Defaults to
+    /// enabled.
+    ///
+    /// The copy takes as much memory as the atlas texture; disable it to save
+    /// that memory when the cache may be rebuilt after a device change. Enabling
+    /// it fills the copy with the glyphs already cached, rasterized again from
+    /// `font_storage`.
+    pub fn set_atlas_host_copy(&mut self, enabled: bool, font_storage: &FontStorage) {
+        if enabled == self.atlas_host_copy() {
+            return;
+        }
+        let copy = enabled.then(|| {
+            let mut copy = AtlasCopy::new(self.resources.atlas_texture.get_mut().size());
+            for update in self.gpu_renderer.cached_updates(font_storage) {
+                copy.write(&update);
+            }
+            copy
+        });
+        *self.resources.atlas_copy.get_mut() = copy;
+    }
+
+    /// Returns whether a copy of the atlas pixels is kept in host memory.
+    pub fn atlas_host_copy(&self) -> bool {
+        self.resources.atlas_copy.borrow().is_some()
+    }
+
+    /// Rebuilds all GPU resources on `device`, e.g. after the previous device
+    /// was lost or to move the text to another adapter, and returns whether the
+    /// cached glyphs were kept.
+    ///
+    /// With [`Self::set_atlas_host_copy`] enabled, the default, the atlas is
+    /// restored from the copy through `queue`, so no glyph is rasterized again;
+    /// otherwise the cache is cleared. The atlas is clamped to the texture
+    /// limits of `device`, and cleared as well when it had to shrink. The
+    /// settings carry over, except that LCD antialiasing and multiview turn off
+    /// when `device` lacks their features. Pipelines are compiled on first use,
+    /// with `pipeline_cache` if given. Text prepared on the previous device
+    /// must be prepared again.
+    pub fn recreate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+    ) -> bool {
+        let lcd_antialiasing = self.lcd_antialiasing();
+        let atlas_copy = self.resources.atlas_copy.get_mut().take();
+        let atlas_size = self.resources.atlas_texture.get_mut().size();
+        let fitting_size = WgpuResources::fit_atlas_size(device, atlas_size);
+        let resources = WgpuResources::new(
+            device,
+            fitting_size,
+            pipeline_cache,
+            self.resources.custom_shader.as_ref(),
+        );
+        let old = std::mem::replace(&mut self.resources, resources);
+        self.resources.depth_stencil = old.depth_stencil;
+        self.resources.view_mask = old.view_mask;
+        self.set_sampler(device, old.sampler_config);
+        self.set_lcd_antialiasing(device, lcd_antialiasing);
+        self.set_multiview(device, old.multiview);
+        self.set_indirect_draws(device, self.indirect_draws);
+
+        if atlas_copy.is_none() {
+            *self.resources.atlas_copy.get_mut() = None;
+        }
+        if fitting_size != atlas_size {
+            log::warn!(
+                "The glyph atlas exceeds the texture limits of the new device and was shrunk from {atlas_size:?} to {fitting_size:?}."
+            );
+        }
+        let Some(copy) = atlas_copy.filter(|_| fitting_size == atlas_size) else {
+            self.gpu_renderer.clear_cache();
+            return false;
+        };
+        let atlas_texture = self.resources.atlas_texture.borrow();
+        for (z, layer) in copy.layers.iter().enumerate() {
+            self.resources.write_pixels(
+                queue,
+                &atlas_texture,
+                wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: z as u32,
+                },
+                copy.size.width,
+                copy.size.height,
+                layer,
+            );
+        }
+        drop(atlas_texture);
+        *self.resources.atlas_copy.get_mut() = Some(copy);
+        true
+    }
+
+    /// Returns whether the next frame draws LCD subpixel masks. Falls back to
+    /// grayscale when they were enabled on [`Self::gpu_renderer`] directly,
+    /// without the subpixel shader of [`Self::set_lcd_antialiasing`].
+    fn subpixel_frame(&mut self) -> bool {
+        if self.resources.subpixel_shader.is_none() {
+            self.gpu_renderer.set_lcd_antialiasing(false);
+        }
+        self.gpu_renderer.lcd_antialiasing()
     }
 }
 
@@ -468,11 +1512,37 @@ pub trait WgpuRenderPassController<E = ()> {
     /// Note: The lifetime is tied to the controller to enforce correct usage scope.
     fn create_pass(&mut self) -> Result<wgpu::RenderPass<'_>, E>;
 
-    /// Returns the target texture format for pipeline selection.
+    /// Creates the last pass of a render, e.g. to discard or resolve the target
+    /// only once everything is drawn. Defaults to [`Self::create_pass`].
+    ///
+    /// A render whose last draw is recorded in an earlier pass ends with an
+    /// empty last pass; a render drawing nothing creates no pass at all.
+    fn create_last_pass(&mut self) -> Result<wgpu::RenderPass<'_>, E> {
+        self.create_pass()
+    }
+
+    /// Returns the format of the target view, which pipelines are selected and
+    /// cached by and colors are converted for.
+    ///
+    /// This is the format of the view, not of its texture: an sRGB view of a
+    /// non-sRGB swapchain texture needs the sRGB format.
     fn format(&self) -> Result<wgpu::TextureFormat, E>;
 
     /// Returns the target screen size in pixels.
     fn target_size(&self) -> Result<[f32; 2], E>;
+
+    /// Returns whether every copy of a render has to be recorded before its
+    /// first pass is created.
+    ///
+    /// By default, atlas copies are recorded between the passes of a render, so
+    /// glyphs of drawn batches can be evicted for later ones. When this returns
+    /// `true`, the render is drawn like [`WgpuRenderer::prepare`]: all copies are
+    /// recorded into [`Self::encoder`] first, then everything is drawn in one
+    /// pass, and glyphs that do not fit into the atlas at once are drawn
+    /// standalone. Suits render graphs that schedule uploads as a step of their own.
+    fn copies_before_passes(&self) -> bool {
+        false
+    }
 }
 
 impl<T: WgpuRenderPassController<E> + ?Sized, E> WgpuRenderPassController<E> for &mut T {
@@ -484,6 +1554,10 @@ impl<T: WgpuRenderPassController<E> + ?Sized, E> WgpuRenderPassController<E> for
         (**self).create_pass()
     }
 
+    fn create_last_pass(&mut self) -> Result<wgpu::RenderPass<'_>, E> {
+        (**self).create_last_pass()
+    }
+
     fn format(&self) -> Result<wgpu::TextureFormat, E> {
         (**self).format()
     }
@@ -491,17 +1565,35 @@ impl<T: WgpuRenderPassController<E> + ?Sized, E> WgpuRenderPassController<E> for
     fn target_size(&self) -> Result<[f32; 2], E> {
         (**self).target_size()
     }
+
+    fn copies_before_passes(&self) -> bool {
+        (**self).copies_before_passes()
+    }
 }
 
 /// A simple implementation of `WgpuRenderPassController` that renders to a given view.
 ///
-/// It clears the screen on the first draw call and loads on subsequent calls.
-/// This matches the typical behavior for rendering text overlay.
+/// The first pass applies the configured load operation, clearing the screen by
+/// default, and subsequent passes load what the previous ones drew. The
+/// configured store operation applies to the last pass of each render.
+///
+/// ```no_run
+/// # use suzuri::renderer::SimpleRenderPass;
+/// # fn f(encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+/// // Draw text over what is already in `view`.
+/// let pass = SimpleRenderPass::new(encoder, view).with_load_op(wgpu::LoadOp::Load);
+/// # }
+/// ```
 pub struct SimpleRenderPass<'a> {
     encoder: &'a mut wgpu::CommandEncoder,
     view: &'a wgpu::TextureView,
     first_call: bool,
-    clear_color: wgpu::Color,
+    load: wgpu::LoadOp<wgpu::Color>,
+    store: wgpu::StoreOp,
+    depth_stencil_attachment: Option<wgpu::RenderPassDepthStencilAttachment<'a>>,
+    copies_before_passes: bool,
+    /// Format of `view`, if it differs from that of its texture.
+    format: Option<wgpu::TextureFormat>,
 }
 
 impl<'a> SimpleRenderPass<'a> {
@@ -513,130 +1605,454 @@ impl<'a> SimpleRenderPass<'a> {
             encoder,
             view,
             first_call: true,
-            clear_color: wgpu::Color::BLACK,
+            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            store: wgpu::StoreOp::Store,
+            depth_stencil_attachment: None,
+            copies_before_passes: false,
+            format: None,
         }
     }
 
     /// Sets the clear color used on the first pass.
-    pub fn with_clear_color(mut self, color: wgpu::Color) -> Self {
-        self.clear_color = color;
+    pub fn with_clear_color(self, color: wgpu::Color) -> Self {
+        self.with_load_op(wgpu::LoadOp::Clear(color))
+    }
+
+    /// Sets the load operation of the first pass. [`wgpu::LoadOp::Load`] draws
+    /// the text over the contents of the view.
+    pub fn with_load_op(mut self, load: wgpu::LoadOp<wgpu::Color>) -> Self {
+        self.load = load;
         self
     }
-}
 
-impl<'a> WgpuRenderPassController<()> for SimpleRenderPass<'a> {
-    fn encoder(&mut self) -> Result<&mut wgpu::CommandEncoder, ()> {
-        Ok(self.encoder)
+    /// Sets the store operation of the last pass of each render,
+    /// [`wgpu::StoreOp::Store`] by default. The passes before store, so the
+    /// following ones can load what they drew.
+    pub fn with_store_op(mut self, store: wgpu::StoreOp) -> Self {
+        self.store = store;
+        self
     }
 
-    fn create_pass(&mut self) -> Result<wgpu::RenderPass<'_>, ()> {
-        let load = if self.first_call {
-            self.first_call = false;
-            wgpu::LoadOp::Clear(self.clear_color)
-        } else {
-            wgpu::LoadOp::Load
+    /// Sets the depth-stencil attachment of the passes, needed when the renderer
+    /// has a [depth-stencil state](WgpuRenderer::set_depth_stencil). Its load
+    /// operations apply to the first pass and its store operations to the last
+    /// pass of each render; the passes in between load and store.
+    pub fn with_depth_stencil_attachment(
+        mut self,
+        attachment: wgpu::RenderPassDepthStencilAttachment<'a>,
+    ) -> Self {
+        self.depth_stencil_attachment = Some(attachment);
+        self
+    }
+
+    /// Records every copy of a render before its first pass; see
+    /// [`WgpuRenderPassController::copies_before_passes`].
+    pub fn with_copies_before_passes(mut self, copies_before_passes: bool) -> Self {
+        self.copies_before_passes = copies_before_passes;
+        self
+    }
+
+    /// Sets the format of the view, for views created with another format than
+    /// their texture, e.g. an sRGB view of a non-sRGB swapchain texture. Defaults
+    /// to the format of the texture, as views cannot tell their own.
+    pub fn with_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Returns the operations of the color attachment and the depth-stencil
+    /// attachment of the next pass, the last one of a render if `last`.
+    fn next_operations(
+        &mut self,
+        last: bool,
+    ) -> (
+        wgpu::Operations<wgpu::Color>,
+        Option<wgpu::RenderPassDepthStencilAttachment<'a>>,
+    ) {
+        let first_call = std::mem::replace(&mut self.first_call, false);
+        let mut depth_stencil_attachment = self.depth_stencil_attachment.clone();
+        if let Some(attachment) = &mut depth_stencil_attachment {
+            if let Some(ops) = &mut attachment.depth_ops {
+                if !first_call {
+                    ops.load = wgpu::LoadOp::Load;
+                }
+                if !last {
+                    ops.store = wgpu::StoreOp::Store;
+                }
+            }
+            if let Some(ops) = &mut attachment.stencil_ops {
+                if !first_call {
+                    ops.load = wgpu::LoadOp::Load;
+                }
+                if !last {
+                    ops.store = wgpu::StoreOp::Store;
+                }
+            }
+        }
+        let ops = wgpu::Operations {
+            load: if first_call {
+                self.load
+            } else {
+                wgpu::LoadOp::Load
+            },
+            store: if last {
+                self.store
+            } else {
+                wgpu::StoreOp::Store
+            },
         };
+        (ops, depth_stencil_attachment)
+    }
 
-        Ok(self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+    fn begin_pass(&mut self, last: bool) -> wgpu::RenderPass<'_> {
+        let (ops, depth_stencil_attachment) = self.next_operations(last);
+        self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("WgpuRenderer Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                 view: self.view,
                 resolve_target: None,
-                ops: wgpu::Operations {
-                    load,
-                    store: wgpu::StoreOp::Store,
-                },
+                ops,
                 depth_slice: None,
             })],
-            depth_stencil_attachment: None,
+            depth_stencil_attachment,
             timestamp_writes: None,
             occlusion_query_set: None,
-        }))
+        })
+    }
+}
+
+impl<'a> WgpuRenderPassController<()> for SimpleRenderPass<'a> {
+    fn encoder(&mut self) -> Result<&mut wgpu::CommandEncoder, ()> {
+        Ok(self.encoder)
+    }
+
+    fn create_pass(&mut self) -> Result<wgpu::RenderPass<'_>, ()> {
+        Ok(self.begin_pass(false))
+    }
+
+    fn create_last_pass(&mut self) -> Result<wgpu::RenderPass<'_>, ()> {
+        Ok(self.begin_pass(true))
     }
 
     fn format(&self) -> Result<wgpu::TextureFormat, ()> {
-        Ok(self.view.texture().format())
+        Ok(self.format.unwrap_or_else(|| self.view.texture().format()))
     }
 
     fn target_size(&self) -> Result<[f32; 2], ()> {
         let size = self.view.texture().size();
         Ok([size.width as f32, size.height as f32])
     }
+
+    fn copies_before_passes(&self) -> bool {
+        self.copies_before_passes
+    }
 }
 
 impl WgpuRenderer {
+    /// Renders the layout into `view`, clearing it to black first.
+    ///
+    /// To keep the contents of `view` or choose the pass operations, render with
+    /// [`Self::render_to`] and a configured [`SimpleRenderPass`]; to draw into a
+    /// render pass of the caller's, use [`Self::prepare`] and [`Self::render_in_pass`].
+    ///
+    /// `view` is drawn in the format of its texture, as views cannot tell their
+    /// own. For views of another format, use [`Self::render_with_format`].
+    ///
+    /// Returns the counters of this render.
     pub fn render<T: Into<[f32; 4]> + Copy>(
         &mut self,
         text_layout: &TextLayout<T>,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         encoder: &mut wgpu::CommandEncoder,
         view: &wgpu::TextureView,
-    ) {
+    ) -> RenderStats {
         let mut ctx = SimpleRenderPass::new(encoder, view);
 
-        self.render_to(text_layout, font_storage, device, &mut ctx)
+        self.render_to(text_layout, font_storage, device, queue, &mut ctx)
             .expect("`SimpleRenderPass` never fails.")
     }
 
-    /// Renders the layout using a custom render pass controller.
+    /// Renders the layout like [`Self::render`] into a view of `format`, e.g.
+    /// an sRGB view of a non-sRGB swapchain texture.
     ///
-    /// This method allows for more flexible rendering scenarios where the render pass
-    /// creation or management is handled externally via the `WgpuRenderPassController` trait.
-    pub fn render_to<T: Into<[f32; 4]> + Copy, E>(
+    /// Pipelines are selected and colors converted for `format` instead of the
+    /// format of the view's texture; see [`SimpleRenderPass::with_format`].
+    pub fn render_with_format<T: Into<[f32; 4]> + Copy>(
         &mut self,
         text_layout: &TextLayout<T>,
-        font_storage: &mut FontStorage,
+        font_storage: &FontStorage,
         device: &wgpu::Device,
-        controller: &mut impl WgpuRenderPassController<E>,
-    ) -> Result<(), E> {
-        // Reset offset at the beginning of the frame
-        let current_offset = std::cell::Cell::new(0);
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+    ) -> RenderStats {
+        let mut ctx = SimpleRenderPass::new(encoder, view).with_format(format);
 
-        // Update globals
-        let globals = Globals {
-            screen_size: controller.target_size()?,
-            _padding: [0.0; 2],
-        };
-        let globals_staging_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Globals Staging Buffer"),
-            contents: bytemuck::bytes_of(&globals),
-            usage: wgpu::BufferUsages::COPY_SRC,
-        });
-        controller.encoder()?.copy_buffer_to_buffer(
-            &globals_staging_buffer,
-            0,
-            &self.resources.globals_buffer,
-            0,
-            std::mem::size_of::<Globals>() as u64,
-        );
+        self.render_to(text_layout, font_storage, device, queue, &mut ctx)
+            .expect("`SimpleRenderPass` never fails.")
+    }
 
-        // Create a thread-local-like cell for the controller to share it with closures below
-        let ctx_cell = std::cell::RefCell::new(controller);
+    /// Renders the layout like [`Self::render`] with `transform` instead of the
+    /// [transform](Self::set_transform) of the renderer, which is left unchanged.
+    ///
+    /// Clip rectangles on the stack keep the transform they were pushed under.
+    pub fn render_transformed<T: Into<[f32; 4]> + Copy>(
+        &mut self,
+        text_layout: &TextLayout<T>,
+        transform: euclid::Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+        font_storage: &FontStorage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) -> RenderStats {
+        let mut ctx = SimpleRenderPass::new(encoder, view);
 
-        // Delegate to GpuRenderer to calculate layout and cache glyphs
-        self.gpu_renderer.try_render(
+        self.render_transformed_to(
             text_layout,
+            transform,
             font_storage,
+            device,
+            queue,
+            &mut ctx,
+        )
+        .expect("`SimpleRenderPass` never fails.")
+    }
+
+    /// Renders the layout like [`Self::render_to`] with `transform` instead of
+    /// the transform of the renderer, which is left unchanged.
+    pub fn render_transformed_to<T: Into<[f32; 4]> + Copy, E>(
+        &mut self,
+        text_layout: &TextLayout<T>,
+        transform: euclid::Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+        font_storage: &FontStorage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+    ) -> Result<RenderStats, E> {
+        let previous = self.transform();
+        self.set_transform(transform);
+        let result = self.render_to(text_layout, font_storage, device, queue, controller);
+        self.set_transform(previous);
+        result
+    }
+
+    /// Renders the layout using a custom render pass controller.
+    ///
+    /// This method allows for more flexible rendering scenarios where the render pass
+    /// creation or management is handled externally via the `WgpuRenderPassController` trait.
+    ///
+    /// Globals and instances are written through `queue` into ring buffers,
+    /// where they are kept until the GPU is done with the passes of the render,
+    /// and glyph pixels are copied into the atlas by commands of the controller's
+    /// encoder. Renders with other globals (target, transform or color space), or
+    /// evicting glyphs of earlier ones, can therefore be recorded before one
+    /// submission without affecting each other.
+    ///
+    /// Atlas copies are recorded between the passes, unless the controller asks
+    /// for [copies before passes](WgpuRenderPassController::copies_before_passes).
+    ///
+    /// Returns the counters of this render, to profile it and to spot cache
+    /// configurations the text does not fit into.
+    pub fn render_to<T: Into<[f32; 4]> + Copy, E>(
+        &mut self,
+        text_layout: &TextLayout<T>,
+        font_storage: &FontStorage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+    ) -> Result<RenderStats, E> {
+        self.render_with_rects_to(
+            text_layout,
+            LayoutRects::default(),
+            font_storage,
+            device,
+            queue,
+            controller,
+        )
+    }
+
+    /// Renders the layout like [`Self::render_to`], along with solid rectangles.
+    ///
+    /// The rectangles behind the text are drawn in a pass of their own before
+    /// the first glyph, those in front of it after the last.
+    pub fn render_with_rects_to<T: Into<[f32; 4]> + Copy, E>(
+        &mut self,
+        text_layout: &TextLayout<T>,
+        rects: LayoutRects<'_>,
+        font_storage: &FontStorage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+    ) -> Result<RenderStats, E> {
+        self.render_layouts_to(
+            &[(LayoutPlacement::default(), text_layout)],
+            rects,
+            font_storage,
+            device,
+            queue,
+            controller,
+        )
+    }
+
+    /// Renders the layout like [`Self::render_to`], moved, faded and filled by `placement`.
+    ///
+    /// Offset, opacity and gradient are applied to the instances of this render,
+    /// so one layout can be drawn at several places within a frame.
+    pub fn render_placed_to<T: Into<[f32; 4]> + Copy, E>(
+        &mut self,
+        text_layout: &TextLayout<T>,
+        placement: LayoutPlacement,
+        font_storage: &FontStorage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+    ) -> Result<RenderStats, E> {
+        self.render_layouts_to(
+            &[(placement, text_layout)],
+            LayoutRects::default(),
+            font_storage,
+            device,
+            queue,
+            controller,
+        )
+    }
+
+    /// Renders several layouts, each with its placement, with one set of passes.
+    ///
+    /// Glyphs of all layouts are merged into shared instance batches, so a frame
+    /// of many small labels takes a few draw calls instead of a few per label.
+    /// Consecutive layouts with the same clip share batches; keep layouts with
+    /// a clip next to each other to make the most of it.
+    pub fn render_all<T: Into<[f32; 4]> + Copy, E>(
+        &mut self,
+        layouts: &[(LayoutPlacement, &TextLayout<T>)],
+        font_storage: &FontStorage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+    ) -> Result<RenderStats, E> {
+        self.render_layouts_to(
+            layouts,
+            LayoutRects::default(),
+            font_storage,
+            device,
+            queue,
+            controller,
+        )
+    }
+
+    /// Shared body of [`Self::render_with_rects_to`] and [`Self::render_all`].
+    fn render_layouts_to<T: Into<[f32; 4]> + Copy, E>(
+        &mut self,
+        layouts: &[(LayoutPlacement, &TextLayout<T>)],
+        rects: LayoutRects<'_>,
+        font_storage: &FontStorage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+    ) -> Result<RenderStats, E> {
+        if controller.copies_before_passes() {
+            let format = controller.format()?;
+            let target_size = controller.target_size()?;
+            let prepared = self.prepare_layouts(
+                layouts,
+                rects,
+                font_storage,
+                device,
+                queue,
+                controller.encoder()?,
+                format,
+                target_size,
+            );
+            if prepared.draw_count() > 0 {
+                self.render_in_pass(&prepared, &mut controller.create_last_pass()?);
+            }
+            return Ok(prepared.stats);
+        }
+
+        // Reset offset at the beginning of the frame
+        let current_offset = std::cell::Cell::new(0);
+
+        let format = controller.format()?;
+        let (stack_clip, shader_clip) = resolve_clips(&self.clip_stack);
+        let globals = Globals::new(
+            controller.target_size()?,
+            self.color_space.transform(format),
+            self.blend_space.correction(format),
+            self.gpu_renderer.transform(),
+            self.shadow,
+            self.pixel_snap,
+            self.hdr_output.scale(format),
+            shader_clip,
+        );
+        // Create a thread-local-like cell for the controller to share it with closures below
+        let ctx_cell = std::cell::RefCell::new(controller);
+
+        let subpixel = self.subpixel_frame();
+        let glyphs: usize = layouts.iter().map(|(_, layout)| layout.len_glyphs()).sum();
+        let first_clip_rect = layouts.first().and_then(|(_, layout)| layout.clip_rect);
+        self.resources.begin_frame(
+            glyphs + rects.behind.len() + rects.in_front.len(),
+            scissor_rect(
+                intersect_clip_rects(first_clip_rect, stack_clip),
+                globals.screen_size,
+            ),
+            subpixel,
+            self.shadow.is_some(),
+        );
+        self.resources.upload_globals(device, queue, globals);
+        self.resources.draw_rects(
+            device,
+            queue,
+            &mut *ctx_cell.borrow_mut(),
+            &current_offset,
+            rects.behind,
+            self.gpu_renderer.depth(),
+        )?;
+
+        // Delegate to GpuRenderer to calculate layout and cache glyphs
+        self.gpu_renderer.try_render_streaming_all(
+            layouts,
+            font_storage,
+            false,
             // Callback: Update Texture Atlas
             &mut |updates: &[AtlasUpdate]| -> Result<(), E> {
                 let mut ctx = ctx_cell.borrow_mut();
-                self.resources.update_atlas(device, ctx.encoder()?, updates);
+                self.resources
+                    .update_atlas(device, queue, ctx.encoder()?, updates);
                 Ok(())
             },
-            // Callback: Draw standard glyphs (batched)
-            &mut |instances: &[GlyphInstance<T>]| -> Result<(), E> {
+            // Callback: Queue a standard glyph
+            &mut |instance: GlyphInstance<T>, texture_size| {
+                // Batches never mix clips, so the clip of any instance is that of its batch.
+                self.resources.frame_scissor.set(scissor_rect(
+                    intersect_clip_rects(instance.clip_rect, stack_clip),
+                    globals.screen_size,
+                ));
+                self.resources.push_instance(&instance, texture_size);
+            },
+            // Callback: Draw queued standard glyphs (batched)
+            &mut || -> Result<(), E> {
                 self.resources.draw_instances(
                     device,
+                    queue,
                     &mut *ctx_cell.borrow_mut(),
                     &current_offset,
-                    instances,
                 )
             },
             // Callback: Draw standalone glyph (large)
             &mut |standalone: &StandaloneGlyph<T>| -> Result<(), E> {
+                self.resources.frame_scissor.set(scissor_rect(
+                    intersect_clip_rects(standalone.clip_rect, stack_clip),
+                    globals.screen_size,
+                ));
                 self.resources.draw_standalone(
                     device,
+                    queue,
                     &mut *ctx_cell.borrow_mut(),
                     &current_offset,
                     standalone,
@@ -644,488 +2060,4894 @@ impl WgpuRenderer {
             },
         )?;
 
-        Ok(())
+        // The last batch of glyphs is still queued; draw the rectangles in front with it.
+        let depth = self.gpu_renderer.depth();
+        for rect in rects.in_front {
+            self.resources.push_rect(rect, depth);
+        }
+        self.resources.draw_last_instances(
+            device,
+            queue,
+            &mut *ctx_cell.borrow_mut(),
+            &current_offset,
+        )?;
+        Ok(self.resources.frame_stats.get())
     }
-}
 
-impl WgpuResources {
-    fn get_pipeline(
-        &self,
+    /// Uploads everything needed to draw the layout and returns the draws, to be
+    /// recorded into a render pass of the caller's with [`Self::render_in_pass`].
+    /// Globals and instances are written through `queue`, and copies into the
+    /// atlas are recorded in `encoder`.
+    ///
+    /// `format` and `target_size` describe the attachment of that pass. Unlike
+    /// [`Self::render_to`], the whole layout is drawn from one batch of the cache,
+    /// so glyphs that do not fit into the atlas at once are drawn standalone.
+    ///
+    /// The prepared text stays valid while it is alive, whatever is rendered or
+    /// prepared in the meantime: its instances get space of their own, and its
+    /// glyphs are pinned in the atlas, so later renders neither evict nor move
+    /// them, until it is dropped and the passes it was recorded into are done.
+    /// Pinned glyphs take atlas space from other text, so keep only as much
+    /// prepared text as you draw. [`Self::clear_cache`] and
+    /// [`Self::forget_font`] still drop pinned glyphs. The encoder has to be
+    /// submitted before the pass the text is recorded into, or be the encoder of
+    /// that pass.
+    pub fn prepare<T: Into<[f32; 4]> + Copy>(
+        &mut self,
+        text_layout: &TextLayout<T>,
+        font_storage: &FontStorage,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
         format: wgpu::TextureFormat,
-    ) -> wgpu::RenderPipeline {
-        // Optimistic check
-        if let Some(pipeline) = self.pipelines.borrow().get(&format) {
-            return pipeline.clone();
-        }
-
-        // Create new pipeline
-        let instance_buffer_layout = InstanceData::vertex_buffer_layout();
-
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("WgpuRenderer Pipeline"),
-            layout: Some(&self.pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &self.shader,
-                entry_point: Some("vs_main"),
-                buffers: std::slice::from_ref(&instance_buffer_layout),
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &self.shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format,
-                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            cache: None,
-        });
+        target_size: [f32; 2],
+    ) -> PreparedText {
+        self.prepare_with_rects(
+            text_layout,
+            LayoutRects::default(),
+            font_storage,
+            device,
+            queue,
+            encoder,
+            format,
+            target_size,
+        )
+    }
 
-        self.pipelines.borrow_mut().insert(format, pipeline.clone());
-        pipeline
+    /// Prepares the layout like [`Self::prepare`], along with solid rectangles.
+    pub fn prepare_with_rects<T: Into<[f32; 4]> + Copy>(
+        &mut self,
+        text_layout: &TextLayout<T>,
+        rects: LayoutRects<'_>,
+        font_storage: &FontStorage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        format: wgpu::TextureFormat,
+        target_size: [f32; 2],
+    ) -> PreparedText {
+        let prepared = self.prepare_layouts(
+            &[(LayoutPlacement::default(), text_layout)],
+            rects,
+            font_storage,
+            device,
+            queue,
+            encoder,
+            format,
+            target_size,
+        );
+        self.gpu_renderer.pin_batch(prepared.frame.downgrade());
+        // The prepared text keeps its lease, which pins its glyphs for as long
+        // as it lives, so the next render must not reuse it.
+        *self.resources.frame.borrow_mut() = FrameLease::new();
+        prepared
     }
 
-    fn get_standalone_pipeline(
-        &self,
+    /// Shared body of [`Self::prepare_with_rects`] and renders whose controller
+    /// records copies before passes.
+    fn prepare_layouts<T: Into<[f32; 4]> + Copy>(
+        &mut self,
+        layouts: &[(LayoutPlacement, &TextLayout<T>)],
+        rects: LayoutRects<'_>,
+        font_storage: &FontStorage,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
         format: wgpu::TextureFormat,
-    ) -> wgpu::RenderPipeline {
-        if let Some(pipeline) = self.standalone_pipelines.borrow().get(&format) {
-            return pipeline.clone();
-        }
+        target_size: [f32; 2],
+    ) -> PreparedText {
+        let current_offset = std::cell::Cell::new(0);
 
-        let instance_buffer_layout = InstanceData::vertex_buffer_layout();
+        let (stack_clip, shader_clip) = resolve_clips(&self.clip_stack);
+        let globals = Globals::new(
+            target_size,
+            self.color_space.transform(format),
+            self.blend_space.correction(format),
+            self.gpu_renderer.transform(),
+            self.shadow,
+            self.pixel_snap,
+            self.hdr_output.scale(format),
+            shader_clip,
+        );
+        let encoder_cell = std::cell::RefCell::new(encoder);
+        let draws = std::cell::RefCell::new(Vec::new());
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("WgpuRenderer Standalone Pipeline"),
-            layout: Some(&self.standalone_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &self.standalone_shader,
-                entry_point: Some("vs_main"),
-                buffers: std::slice::from_ref(&instance_buffer_layout),
-                compilation_options: Default::default(),
+        let subpixel = self.subpixel_frame();
+        let glyphs: usize = layouts.iter().map(|(_, layout)| layout.len_glyphs()).sum();
+        let first_clip_rect = layouts.first().and_then(|(_, layout)| layout.clip_rect);
+        self.resources.begin_frame(
+            glyphs + rects.behind.len() + rects.in_front.len(),
+            scissor_rect(
+                intersect_clip_rects(first_clip_rect, stack_clip),
+                target_size,
+            ),
+            subpixel,
+            self.shadow.is_some(),
+        );
+        self.resources.upload_globals(device, queue, globals);
+        let behind = self.resources.prepare_rects(
+            device,
+            queue,
+            format,
+            &current_offset,
+            rects.behind,
+            self.gpu_renderer.depth(),
+        );
+
+        let _: Result<(), ()> = self.gpu_renderer.try_render_streaming_all(
+            layouts,
+            font_storage,
+            true,
+            &mut |updates: &[AtlasUpdate]| {
+                self.resources
+                    .update_atlas(device, queue, &mut encoder_cell.borrow_mut(), updates);
+                Ok(())
             },
-            fragment: Some(wgpu::FragmentState {
-                module: &self.standalone_shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
+            &mut |instance: GlyphInstance<T>, texture_size| {
+                // Batches never mix clips, so the clip of any instance is that of its batch.
+                self.resources.frame_scissor.set(scissor_rect(
+                    intersect_clip_rects(instance.clip_rect, stack_clip),
+                    target_size,
+                ));
+                self.resources.push_instance(&instance, texture_size);
+            },
+            &mut || {
+                let draw = self
+                    .resources
+                    .prepare_instances(device, queue, format, &current_offset);
+                draws.borrow_mut().extend(draw);
+                Ok(())
+            },
+            &mut |standalone: &StandaloneGlyph<T>| {
+                self.resources.frame_scissor.set(scissor_rect(
+                    intersect_clip_rects(standalone.clip_rect, stack_clip),
+                    target_size,
+                ));
+                let draw = self.resources.prepare_standalone(
+                    device,
+                    queue,
+                    &mut encoder_cell.borrow_mut(),
                     format,
-                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
+                    &current_offset,
+                    standalone,
+                    false,
+                );
+                draws.borrow_mut().push(draw);
+                Ok(())
             },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            cache: None,
-        });
+        );
 
-        self.standalone_pipelines
-            .borrow_mut()
-            .insert(format, pipeline.clone());
-        pipeline
+        let depth = self.gpu_renderer.depth();
+        for rect in rects.in_front {
+            self.resources.push_rect(rect, depth);
+        }
+        let mut draws = draws.into_inner();
+        draws.extend(
+            self.resources
+                .prepare_instances(device, queue, format, &current_offset),
+        );
+
+        let indirect = (self.indirect_draws && !draws.is_empty())
+            .then(|| self.resources.upload_indirect_args(device, queue, &draws));
+        PreparedText {
+            behind,
+            draws,
+            indirect,
+            stats: self.resources.frame_stats.get(),
+            frame: self.resources.frame.borrow().clone(),
+        }
     }
 
-    /// Ensures the instance buffer has enough capacity to hold `needed_bytes`.
+    /// Records text returned by [`Self::prepare`] into `pass`, after the
+    /// rectangles behind it and the shadows of all of its glyphs.
     ///
-    /// If the buffer is too small, it creates a new one with at least double the current capacity
-    /// (geometric growth) to minimize the frequency of re-allocations.
-    fn ensure_instance_buffer_capacity(
-        &self,
+    /// Sets the scissor rectangle of `pass` to the clip of the layout, or to the
+    /// whole target when it has none, and leaves it set.
+    pub fn render_in_pass(&self, prepared: &PreparedText, pass: &mut wgpu::RenderPass<'_>) {
+        prepared.frame.hold_by_pass(pass);
+        if let Some(behind) = &prepared.behind {
+            behind.record(pass);
+        }
+        let Some((indirect, offset)) = &prepared.indirect else {
+            for draw in &prepared.draws {
+                draw.record_shadow(pass);
+            }
+            for draw in &prepared.draws {
+                draw.record(pass);
+            }
+            return;
+        };
+
+        // Runs of draws sharing their state, with the index of their first draw.
+        let runs = || {
+            prepared
+                .draws
+                .chunk_by(PreparedDraw::shares_state)
+                .scan(0, |first, run| {
+                    let start = *first;
+                    *first += run.len();
+                    Some((start, run))
+                })
+        };
+        for (first, run) in runs() {
+            if let Some(pipeline) = &run[0].shadow_pipeline {
+                run[0].record_indirect(pipeline, pass, indirect, *offset, first..first + run.len());
+            }
+        }
+        for (first, run) in runs() {
+            let draws = first..first + run.len();
+            run[0].record_indirect(&run[0].pipeline, pass, indirect, *offset, draws);
+        }
+    }
+
+    /// Draws the atlas pages themselves into `view`, to diagnose cache configurations.
+    ///
+    /// See [`Self::render_atlas_debug_to`].
+    pub fn render_atlas_debug(
+        &mut self,
         device: &wgpu::Device,
-        needed_bytes: u64,
-        instance_buffer: &mut wgpu::Buffer,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
     ) {
-        let current_capacity = instance_buffer.size();
-        if needed_bytes > current_capacity {
-            let new_capacity = needed_bytes.max(current_capacity * 2);
-            let new_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some("Instance Buffer"),
-                size: new_capacity,
-                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
-            *instance_buffer = new_buffer;
-        }
+        let mut ctx = SimpleRenderPass::new(encoder, view);
+
+        self.render_atlas_debug_to(device, queue, &mut ctx)
+            .expect("`SimpleRenderPass` never fails.")
     }
 
-    /// Ensures that standalone resources (texture, bind group) are sufficient for the needed dimensions.
+    /// Draws the atlas pages themselves using a custom render pass controller.
     ///
-    /// # Power-of-Two Sizing
-    /// To avoid recreating the texture every time the glyph size changes slightly, the texture dimensions
-    /// are rounded up to the next power of two (e.g., 100x100 -> 128x128). This significantly stabilizes
-    /// GPU resource churn for variable-sized large glyphs.
-    fn ensure_standalone_resources(
-        &self,
+    /// The pages are laid out in a grid in texture index order, scaled to fit the
+    /// target. Each is framed by a gray border, and a bar along its bottom edge
+    /// shows its [`PageStats::occupancy`](crate::renderer::PageStats::occupancy),
+    /// green when mostly free and red when full. The transform, depth and clip of
+    /// text rendering do not apply, but the pipelines are shared, so the passes
+    /// need a depth attachment when [`Self::set_depth_stencil`] is set.
+    pub fn render_atlas_debug_to<E>(
+        &mut self,
         device: &wgpu::Device,
-        needed_width: u32,
-        needed_height: u32,
-    ) -> std::cell::RefMut<'_, Option<StandaloneResources>> {
-        let mut resources_ref = self.standalone_resources.borrow_mut();
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+    ) -> Result<(), E> {
+        /// Space between pages.
+        const GAP: f32 = 4.0;
+        /// Width of page borders.
+        const BORDER: f32 = 1.0;
+        /// Height of occupancy bars.
+        const BAR: f32 = 4.0;
 
-        let recreate = if let Some(res) = resources_ref.as_ref() {
-            res.size.width < needed_width || res.size.height < needed_height
-        } else {
-            true
+        let target_size = controller.target_size()?;
+        let identity = euclid::Transform2D::identity();
+        let globals = Globals::new(
+            target_size,
+            self.color_space.transform(controller.format()?),
+            COVERAGE_CORRECTION_NONE,
+            identity,
+            None,
+            false,
+            1.0,
+            None,
+        );
+
+        let pages = self.gpu_renderer.stats().pages;
+        let columns = (pages.len() as f32).sqrt().ceil().max(1.0);
+        let rows = (pages.len() as f32 / columns).ceil().max(1.0);
+        let cell = (target_size[0] / columns).min(target_size[1] / rows);
+        let side = (cell - GAP).max(0.0);
+        let rect = |x: f32, y: f32, width: f32, height: f32| {
+            euclid::Box2D::new(
+                euclid::Point2D::new(x, y),
+                euclid::Point2D::new(x + width, y + height),
+            )
+        };
+
+        // One atlas quad per page, plus four borders and a bar drawn as solid
+        // standalone quads.
+        self.resources.begin_frame(
+            pages.len() * 6,
+            scissor_rect(None, target_size),
+            false,
+            false,
+        );
+        self.resources.upload_globals(device, queue, globals);
+        let current_offset = std::cell::Cell::new(0);
+        let mut solids = Vec::with_capacity(pages.len() * 5);
+        for (index, page) in pages.iter().enumerate() {
+            let x = (index as f32 % columns) * cell;
+            let y = (index as f32 / columns).floor() * cell;
+            let texture_size = self
+                .gpu_renderer
+                .page_config(index)
+                .map_or(1, |config| config.texture_size.get());
+            self.resources.push_instance(
+                &GlyphInstance {
+                    texture_index: index,
+                    uv_rect: rect(0.0, 0.0, 1.0, 1.0),
+                    screen_rect: rect(x, y, side, side),
+                    transform: identity,
+                    depth: 0.0,
+                    clip_rect: None,
+                    opacity: 1.0,
+                    gradient: None,
+                    user_data: [1.0f32; 4],
+                },
+                texture_size,
+            );
+
+            let gray = [0.5, 0.5, 0.5, 1.0];
+            solids.push((rect(x, y, side, BORDER), gray));
+            solids.push((rect(x, y + side - BORDER, side, BORDER), gray));
+            solids.push((rect(x, y, BORDER, side), gray));
+            solids.push((rect(x + side - BORDER, y, BORDER, side), gray));
+            let occupancy = page.occupancy().clamp(0.0, 1.0);
+            solids.push((
+                rect(x, y + side - BAR, side * occupancy, BAR),
+                [occupancy, 1.0 - occupancy, 0.0, 1.0],
+            ));
+        }
+
+        self.resources
+            .draw_instances(device, queue, controller, &current_offset)?;
+        let format = controller.format()?;
+        // Solids get 1x1 textures of their own, so no texels of earlier
+        // standalone glyphs are filtered into them.
+        let mut draws = Vec::with_capacity(solids.len());
+        for (screen_rect, color) in solids {
+            let solid = StandaloneGlyph {
+                width: 1,
+                height: 1,
+                pixels: vec![255],
+                screen_rect,
+                transform: identity,
+                depth: 0.0,
+                clip_rect: None,
+                opacity: 1.0,
+                gradient: None,
+                user_data: color,
+            };
+            draws.push(self.resources.prepare_standalone(
+                device,
+                queue,
+                controller.encoder()?,
+                format,
+                &current_offset,
+                &solid,
+                false,
+            ));
+        }
+        let mut pass = self.resources.create_last_pass(controller)?;
+        for draw in &draws {
+            draw.record(&mut pass);
+        }
+        Ok(())
+    }
+}
+
+/// Counters of one render, for profiling and for spotting cache configurations
+/// the text does not fit into; see [`WgpuRenderer::render_to`].
+///
+/// Unlike [`CacheStats`], which accumulate, these cover a single render. Many
+/// standalone glyphs or reallocations in every frame mean the atlas is too
+/// small for the text of a frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    /// Glyph and rectangle instances drawn, not counting their shadows.
+    pub instances: u64,
+    /// Draw calls recorded, including those drawing shadows.
+    pub draw_calls: u64,
+    /// Bytes of glyph masks uploaded to the atlas.
+    pub atlas_bytes_uploaded: u64,
+    /// Glyphs drawn on their own, too large for the atlas or finding no room in it.
+    pub standalone: u64,
+    /// Times the instance buffer, the atlas or the texture of standalone glyphs
+    /// was replaced by a larger one.
+    pub buffer_reallocations: u64,
+}
+
+/// Text uploaded by [`WgpuRenderer::prepare`], ready to be recorded into a render pass.
+#[derive(Default)]
+pub struct PreparedText {
+    /// Rectangles drawn before the glyphs and their shadows.
+    behind: Option<PreparedDraw>,
+    draws: Vec<PreparedDraw>,
+    /// Arguments of `draws`, one after another, when they are drawn indirectly;
+    /// see [`WgpuRenderer::set_indirect_draws`].
+    indirect: Option<(wgpu::Buffer, u64)>,
+    /// Counters of the prepare, with the draw calls recording the text issues.
+    stats: RenderStats,
+    /// Keeps the instances and glyphs of the draws from being overwritten; held
+    /// by the passes the text is recorded into.
+    frame: FrameLease,
+}
+
+impl PreparedText {
+    /// Returns the number of draw calls recording the text issues.
+    pub fn draw_count(&self) -> usize {
+        self.draws.len() + usize::from(self.behind.is_some())
+    }
+
+    /// Returns the counters of the prepare; see [`RenderStats`].
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+}
+
+/// Returns the scissor rectangle, `[x, y, width, height]` in whole pixels, that
+/// covers `clip_rect` within a target of `target_size`, or the whole target
+/// without a clip.
+fn scissor_rect(
+    clip_rect: Option<euclid::Box2D<f32, euclid::UnknownUnit>>,
+    target_size: [f32; 2],
+) -> [u32; 4] {
+    let [target_width, target_height] = target_size.map(|size| size.max(0.0) as u32);
+    let Some(clip_rect) = clip_rect else {
+        return [0, 0, target_width, target_height];
+    };
+    let clamp_x = |x: f32| (x.max(0.0) as u32).min(target_width);
+    let clamp_y = |y: f32| (y.max(0.0) as u32).min(target_height);
+    let (min_x, max_x) = (
+        clamp_x(clip_rect.min.x.floor()),
+        clamp_x(clip_rect.max.x.ceil()),
+    );
+    let (min_y, max_y) = (
+        clamp_y(clip_rect.min.y.floor()),
+        clamp_y(clip_rect.max.y.ceil()),
+    );
+    [
+        min_x,
+        min_y,
+        max_x.saturating_sub(min_x),
+        max_y.saturating_sub(min_y),
+    ]
+}
+
+/// Returns the intersection of two optional clip rectangles, or the one that is
+/// given.
+fn intersect_clip_rects(
+    a: Option<euclid::Box2D<f32, euclid::UnknownUnit>>,
+    b: Option<euclid::Box2D<f32, euclid::UnknownUnit>>,
+) -> Option<euclid::Box2D<f32, euclid::UnknownUnit>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.intersection_unchecked(&b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Clip rectangle of [`WgpuRenderer::push_clip_rect`], in layout coordinates,
+/// with the transform to the target it was pushed under.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ClipEntry {
+    rect: euclid::Box2D<f32, euclid::UnknownUnit>,
+    transform: euclid::Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+}
+
+impl ClipEntry {
+    /// Returns whether the rectangle stays axis-aligned on the target.
+    fn axis_aligned(&self) -> bool {
+        self.transform.m12 == 0.0 && self.transform.m21 == 0.0
+    }
+
+    /// Returns the bounds of the rectangle on the target.
+    fn target_bounds(&self) -> euclid::Box2D<f32, euclid::UnknownUnit> {
+        self.transform.outer_transformed_box(&self.rect)
+    }
+}
+
+/// Splits a clip stack into the rectangle the scissor clips to, in pixels of
+/// the target, and the one the shaders clip to.
+///
+/// Axis-aligned rectangles go to the scissor. Of the others, the innermost is
+/// clipped in the shaders, intersected with those around it pushed under the
+/// same transform; the rest clip to their bounds.
+fn resolve_clips(
+    stack: &[ClipEntry],
+) -> (
+    Option<euclid::Box2D<f32, euclid::UnknownUnit>>,
+    Option<ClipEntry>,
+) {
+    let mut scissor = None;
+    let mut shader: Option<ClipEntry> = None;
+    for entry in stack {
+        let bounds = if entry.axis_aligned() {
+            Some(entry.target_bounds())
+        } else if let Some(outer) = shader.filter(|outer| outer.transform == entry.transform) {
+            shader = Some(ClipEntry {
+                rect: outer.rect.intersection_unchecked(&entry.rect),
+                ..outer
+            });
+            None
+        } else {
+            shader.replace(*entry).map(|outer| outer.target_bounds())
+        };
+        scissor = intersect_clip_rects(scissor, bounds);
+    }
+    (scissor, shader)
+}
+
+/// One instanced draw call, with the resources it binds.
+struct PreparedDraw {
+    pipeline: wgpu::RenderPipeline,
+    /// Pipeline drawing the shadows of the same instances first, if any.
+    shadow_pipeline: Option<wgpu::RenderPipeline>,
+    bind_group: wgpu::BindGroup,
+    /// Offset of the globals of the draw's render in the bound globals buffer.
+    globals_offset: u32,
+    instance_buffer: wgpu::Buffer,
+    range: std::ops::Range<u64>,
+    count: u32,
+    /// `[x, y, width, height]` of the target the draw is clipped to.
+    scissor: [u32; 4],
+}
+
+impl PreparedDraw {
+    fn record(&self, rpass: &mut wgpu::RenderPass<'_>) {
+        self.record_with(&self.pipeline, rpass);
+    }
+
+    /// Records the shadows of the instances, if the draw has any.
+    fn record_shadow(&self, rpass: &mut wgpu::RenderPass<'_>) {
+        if let Some(pipeline) = &self.shadow_pipeline {
+            self.record_with(pipeline, rpass);
+        }
+    }
+
+    fn record_with(&self, pipeline: &wgpu::RenderPipeline, rpass: &mut wgpu::RenderPass<'_>) {
+        self.bind(pipeline, rpass);
+        rpass.set_vertex_buffer(0, self.instance_buffer.slice(self.range.clone()));
+        rpass.draw(0..4, 0..self.count);
+    }
+
+    /// Records the `draws` of the arguments at `offset` of `indirect`, which
+    /// share the state of this draw, with one call.
+    fn record_indirect(
+        &self,
+        pipeline: &wgpu::RenderPipeline,
+        rpass: &mut wgpu::RenderPass<'_>,
+        indirect: &wgpu::Buffer,
+        offset: u64,
+        draws: std::ops::Range<usize>,
+    ) {
+        self.bind(pipeline, rpass);
+        rpass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        let args_size = std::mem::size_of::<wgpu::util::DrawIndirectArgs>() as u64;
+        rpass.multi_draw_indirect(
+            indirect,
+            offset + draws.start as u64 * args_size,
+            draws.len() as u32,
+        );
+    }
+
+    fn bind(&self, pipeline: &wgpu::RenderPipeline, rpass: &mut wgpu::RenderPass<'_>) {
+        let [x, y, width, height] = self.scissor;
+        rpass.set_scissor_rect(x, y, width, height);
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[self.globals_offset]);
+    }
+
+    /// Returns whether `other` can be drawn by the same indirect call as this draw.
+    fn shares_state(&self, other: &Self) -> bool {
+        self.pipeline == other.pipeline
+            && self.shadow_pipeline == other.shadow_pipeline
+            && self.bind_group == other.bind_group
+            && self.globals_offset == other.globals_offset
+            && self.instance_buffer == other.instance_buffer
+            && self.scissor == other.scissor
+    }
+}
+
+impl WgpuResources {
+    /// Creates the resources of a renderer on `device`, with an empty atlas of
+    /// `atlas_size` and no pipelines yet.
+    fn new(
+        device: &wgpu::Device,
+        atlas_size: wgpu::Extent3d,
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+        custom_shader: Option<&CustomShader>,
+    ) -> Self {
+        let atlas_texture = Self::create_atlas_texture(device, atlas_size);
+
+        let sampler = device.create_sampler(&SamplerConfig::default().descriptor());
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("WgpuRenderer Bind Group Layout"),
+            entries: &[
+                // Globals
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: Self::globals_size(),
+                    },
+                    count: None,
+                },
+                // Sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // Texture Array
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        // Standalone layout (Texture 2D instead of Array)
+        let standalone_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("WgpuRenderer Standalone Bind Group Layout"),
+                entries: &[
+                    // Globals
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: Self::globals_size(),
+                        },
+                        count: None,
+                    },
+                    // Sampler
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    // Texture 2D
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WgpuRenderer Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let standalone_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("WgpuRenderer Standalone Pipeline Layout"),
+                bind_group_layouts: &[&standalone_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let fragment_hook = Self::fragment_hook_of(custom_shader);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("WgpuRenderer Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                format!("{SHADER}\n{SHADOW_SHADER}\n{fragment_hook}").into(),
+            ),
+        });
+
+        let standalone_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("WgpuRenderer Standalone Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                format!("{STANDALONE_SHADER}\n{SHADOW_SHADER}\n{fragment_hook}").into(),
+            ),
+        });
+
+        let replacement_shaders = match custom_shader {
+            Some(CustomShader::Replace { atlas, standalone }) => Some((
+                device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("WgpuRenderer Replacement Shader"),
+                    source: wgpu::ShaderSource::Wgsl(atlas.as_str().into()),
+                }),
+                device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("WgpuRenderer Replacement Standalone Shader"),
+                    source: wgpu::ShaderSource::Wgsl(standalone.as_str().into()),
+                }),
+            )),
+            _ => None,
+        };
+
+        let instance_size = std::mem::size_of::<InstanceData>() as u64;
+        let instance_buffer = UploadRing::new(
+            device,
+            "Instance Buffer",
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            instance_size,
+            INITIAL_INSTANCE_CAPACITY as u64 * instance_size,
+        );
+        let staging_buffer = UploadRing::new(
+            device,
+            "Staging Buffer",
+            wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            INITIAL_STAGING_CAPACITY,
+        );
+
+        let globals_alignment = u64::from(device.limits().min_uniform_buffer_offset_alignment);
+        let globals_buffer = UploadRing::new(
+            device,
+            "Globals Buffer",
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            globals_alignment,
+            INITIAL_GLOBALS_CAPACITY
+                * (std::mem::size_of::<Globals>() as u64).next_multiple_of(globals_alignment),
+        );
+
+        let globals_bind_group = Self::create_globals_bind_group(
+            device,
+            &bind_group_layout,
+            globals_buffer.buffer(),
+            &sampler,
+            &atlas_texture,
+        );
+
+        Self {
+            pipelines: std::cell::RefCell::new(HashMap::new()),
+            standalone_pipelines: std::cell::RefCell::new(HashMap::new()),
+            subpixel_pipelines: std::cell::RefCell::new(HashMap::new()),
+            shadow_pipelines: std::cell::RefCell::new(HashMap::new()),
+            standalone_shadow_pipelines: std::cell::RefCell::new(HashMap::new()),
+            depth_stencil: None,
+            pipeline_cache: pipeline_cache.cloned(),
+            pipeline_layout,
+            standalone_pipeline_layout,
+            shader,
+            standalone_shader,
+            subpixel_shader: None,
+            custom_shader: custom_shader.cloned(),
+            replacement_shaders,
+            multiview: None,
+            multiview_shaders: None,
+            view_mask: u32::MAX,
+            atlas_texture: std::cell::RefCell::new(atlas_texture),
+            atlas_copy: std::cell::RefCell::new(Some(AtlasCopy::new(atlas_size))),
+            sampler,
+            sampler_config: SamplerConfig::default(),
+            frame_stats: std::cell::Cell::new(RenderStats::default()),
+            instance_buffer: std::cell::RefCell::new(instance_buffer),
+            frame: std::cell::RefCell::new(FrameLease::new()),
+            spare_frames: std::cell::RefCell::new(Vec::new()),
+            staging_buffer: std::cell::RefCell::new(staging_buffer),
+            indirect_buffer: std::cell::RefCell::new(None),
+            instance_ring: std::cell::Cell::new(InstanceRing::default()),
+            frame_scissor: std::cell::Cell::new([0; 4]),
+            frame_subpixel: std::cell::Cell::new(false),
+            frame_shadow: std::cell::Cell::new(false),
+            bind_group_layout,
+            standalone_bind_group_layout,
+            globals_buffer: std::cell::RefCell::new(globals_buffer),
+            globals_offset: std::cell::Cell::new(0),
+            globals_bind_group: std::cell::RefCell::new(globals_bind_group),
+            standalone_resources: std::cell::RefCell::new(None),
+            instance_data_staging: std::cell::RefCell::new(Vec::new()),
+            pixel_staging: std::cell::RefCell::new(Vec::new()),
+            instance_shadow: std::cell::RefCell::new(Vec::new()),
+            uploaded_globals: std::cell::Cell::new(None),
+            #[cfg(test)]
+            queue_writes: std::cell::Cell::new(0),
+            #[cfg(test)]
+            staging_buffers_created: std::cell::Cell::new(0),
+        }
+    }
+
+    /// Returns the WGSL defining `fragment_hook` for `custom_shader`.
+    fn fragment_hook_of(custom_shader: Option<&CustomShader>) -> &str {
+        match custom_shader {
+            Some(CustomShader::FragmentHook(hook)) => hook,
+            _ => FRAGMENT_HOOK,
+        }
+    }
+
+    /// Returns the WGSL defining `fragment_hook`, appended to the built-in glyph shaders.
+    fn fragment_hook(&self) -> &str {
+        Self::fragment_hook_of(self.custom_shader.as_ref())
+    }
+
+    /// Drops all pipelines, to be recreated with the current settings on use.
+    fn clear_pipelines(&mut self) {
+        self.pipelines.get_mut().clear();
+        self.standalone_pipelines.get_mut().clear();
+        self.subpixel_pipelines.get_mut().clear();
+        self.shadow_pipelines.get_mut().clear();
+        self.standalone_shadow_pipelines.get_mut().clear();
+    }
+
+    /// Returns the multiview shaders of atlas and standalone glyphs, if
+    /// multiview is enabled.
+    fn multiview_shaders(&self) -> Option<&(wgpu::ShaderModule, wgpu::ShaderModule)> {
+        self.multiview.and(self.multiview_shaders.as_ref())
+    }
+
+    /// Writes the arguments of `draws`, one after another, which address their
+    /// instances from the start of their instance buffer, into the indirect
+    /// ring, and returns the buffer and offset of the first.
+    fn upload_indirect_args(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        draws: &[PreparedDraw],
+    ) -> (wgpu::Buffer, u64) {
+        let instance_size = std::mem::size_of::<InstanceData>() as u64;
+        let args_size = std::mem::size_of::<wgpu::util::DrawIndirectArgs>() as u64;
+        let mut contents = Vec::with_capacity(draws.len() * args_size as usize);
+        for draw in draws {
+            let args = wgpu::util::DrawIndirectArgs {
+                vertex_count: 4,
+                instance_count: draw.count,
+                first_vertex: 0,
+                first_instance: (draw.range.start / instance_size) as u32,
+            };
+            contents.extend_from_slice(args.as_bytes());
+        }
+
+        let mut indirect_buffer = self.indirect_buffer.borrow_mut();
+        let indirect_buffer = indirect_buffer.get_or_insert_with(|| {
+            UploadRing::new(
+                device,
+                "Indirect Draw Buffer",
+                wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+                wgpu::COPY_BUFFER_ALIGNMENT,
+                INITIAL_INDIRECT_CAPACITY * args_size,
+            )
+        });
+        let (start, grown) =
+            indirect_buffer.allocate(device, contents.len() as u64, &self.frame.borrow());
+        if grown {
+            self.update_stats(|stats| stats.buffer_reallocations += 1);
+        }
+        self.count_queue_write();
+        queue.write_buffer(indirect_buffer.buffer(), start, &contents);
+        (indirect_buffer.buffer().clone(), start)
+    }
+
+    /// Updates the counters of the current frame.
+    fn update_stats(&self, update: impl FnOnce(&mut RenderStats)) {
+        let mut stats = self.frame_stats.get();
+        update(&mut stats);
+        self.frame_stats.set(stats);
+    }
+
+    /// Counts the instances and draw calls of `draw`, including its shadows.
+    fn count_draw(&self, draw: &PreparedDraw) {
+        self.update_stats(|stats| {
+            stats.instances += u64::from(draw.count);
+            stats.draw_calls += 1 + u64::from(draw.shadow_pipeline.is_some());
+        });
+    }
+
+    /// Returns the size of the globals bound by each draw.
+    fn globals_size() -> Option<wgpu::BufferSize> {
+        wgpu::BufferSize::new(std::mem::size_of::<Globals>() as u64)
+    }
+
+    /// Uploads the globals of the current frame, which its draws bind at their
+    /// own offset of the globals buffer. The globals of the previous frame are
+    /// bound again when they hold the same values.
+    fn upload_globals(&self, device: &wgpu::Device, queue: &wgpu::Queue, globals: Globals) {
+        let frame = self.frame.borrow();
+        let mut globals_buffer = self.globals_buffer.borrow_mut();
+        if self.uploaded_globals.get() == Some(globals)
+            && let Some(last) = globals_buffer.lease_last(&frame)
+        {
+            self.globals_offset.set(last.start as u32);
+            return;
+        }
+
+        let size = std::mem::size_of::<Globals>() as u64;
+        let (start, grown) = globals_buffer.allocate(device, size, &frame);
+        if grown {
+            self.update_stats(|stats| stats.buffer_reallocations += 1);
+            *self.globals_bind_group.borrow_mut() = Self::create_globals_bind_group(
+                device,
+                &self.bind_group_layout,
+                globals_buffer.buffer(),
+                &self.sampler,
+                &self.atlas_texture.borrow(),
+            );
+            // The shared standalone texture is bound along with the globals.
+            *self.standalone_resources.borrow_mut() = None;
+        }
+        self.count_queue_write();
+        queue.write_buffer(globals_buffer.buffer(), start, bytemuck::bytes_of(&globals));
+        self.uploaded_globals.set(Some(globals));
+        self.globals_offset.set(start as u32);
+    }
+
+    /// Counts a write through the queue, for tests.
+    fn count_queue_write(&self) {
+        #[cfg(test)]
+        self.queue_writes.set(self.queue_writes.get() + 1);
+    }
+
+    /// Writes `width` x `height` tightly packed `pixels` into `texture` at `origin`
+    /// through the queue.
+    fn write_pixels(
+        &self,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        origin: wgpu::Origin3d,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) {
+        self.count_queue_write();
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin,
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Creates a pipeline drawing instanced glyph quads into `format` targets.
+    fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        layout: &wgpu::PipelineLayout,
+        module: &wgpu::ShaderModule,
+        [vertex_entry, fragment_entry]: [&str; 2],
+        format: wgpu::TextureFormat,
+        blend: wgpu::BlendState,
+    ) -> wgpu::RenderPipeline {
+        let instance_buffer_layout = InstanceData::vertex_buffer_layout();
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: Some(vertex_entry),
+                buffers: std::slice::from_ref(&instance_buffer_layout),
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: Some(fragment_entry),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: self.depth_stencil.clone(),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: self.multiview,
+            cache: self.pipeline_cache.as_ref(),
+        })
+    }
+
+    fn get_pipeline(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        // Optimistic check
+        if let Some(pipeline) = self.pipelines.borrow().get(&format) {
+            return pipeline.clone();
+        }
+
+        // Create new pipeline
+        let (shader, vertex_entry) = match (&self.replacement_shaders, self.multiview_shaders()) {
+            (Some((shader, _)), _) => (shader, "vs_main"),
+            (None, Some((shader, _))) => (shader, "vs_multiview"),
+            (None, None) => (&self.shader, "vs_main"),
+        };
+        let pipeline = self.create_pipeline(
+            device,
+            "WgpuRenderer Pipeline",
+            &self.pipeline_layout,
+            shader,
+            [vertex_entry, "fs_main"],
+            format,
+            wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+        );
+
+        self.pipelines.borrow_mut().insert(format, pipeline.clone());
+        pipeline
+    }
+
+    /// Returns the dual-source blending pipeline drawing LCD subpixel masks, or
+    /// `None` without the subpixel shader.
+    fn get_subpixel_pipeline(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> Option<wgpu::RenderPipeline> {
+        let shader = self.subpixel_shader.as_ref()?;
+        if let Some(pipeline) = self.subpixel_pipelines.borrow().get(&format) {
+            return Some(pipeline.clone());
+        }
+
+        // Each color channel of the target is covered by its own subpixel.
+        let blend = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::OneMinusSrc1,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::OneMinusSrc1Alpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+        let pipeline = self.create_pipeline(
+            device,
+            "WgpuRenderer Subpixel Pipeline",
+            &self.pipeline_layout,
+            shader,
+            ["vs_main", "fs_subpixel"],
+            format,
+            blend,
+        );
+
+        self.subpixel_pipelines
+            .borrow_mut()
+            .insert(format, pipeline.clone());
+        Some(pipeline)
+    }
+
+    fn get_standalone_pipeline(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        if let Some(pipeline) = self.standalone_pipelines.borrow().get(&format) {
+            return pipeline.clone();
+        }
+
+        let (shader, vertex_entry) = match (&self.replacement_shaders, self.multiview_shaders()) {
+            (Some((_, shader)), _) => (shader, "vs_main"),
+            (None, Some((_, shader))) => (shader, "vs_multiview"),
+            (None, None) => (&self.standalone_shader, "vs_main"),
+        };
+        let pipeline = self.create_pipeline(
+            device,
+            "WgpuRenderer Standalone Pipeline",
+            &self.standalone_pipeline_layout,
+            shader,
+            [vertex_entry, "fs_main"],
+            format,
+            wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+        );
+
+        self.standalone_pipelines
+            .borrow_mut()
+            .insert(format, pipeline.clone());
+        pipeline
+    }
+
+    /// Returns the pipeline drawing the shadows of atlas glyphs, or of
+    /// standalone glyphs with `standalone`; see [`WgpuRenderer::set_shadow`].
+    fn get_shadow_pipeline(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        standalone: bool,
+    ) -> wgpu::RenderPipeline {
+        let (pipelines, layout, shader) = match standalone {
+            true => (
+                &self.standalone_shadow_pipelines,
+                &self.standalone_pipeline_layout,
+                &self.standalone_shader,
+            ),
+            false => (&self.shadow_pipelines, &self.pipeline_layout, &self.shader),
+        };
+        if let Some(pipeline) = pipelines.borrow().get(&format) {
+            return pipeline.clone();
+        }
+
+        let (shader, vertex_entry) = match (self.multiview_shaders(), standalone) {
+            (Some((shader, _)), false) | (Some((_, shader)), true) => {
+                (shader, "vs_shadow_multiview")
+            }
+            (None, _) => (shader, "vs_shadow"),
+        };
+        let pipeline = self.create_pipeline(
+            device,
+            "WgpuRenderer Shadow Pipeline",
+            layout,
+            shader,
+            [vertex_entry, "fs_shadow"],
+            format,
+            wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+        );
+
+        pipelines.borrow_mut().insert(format, pipeline.clone());
+        pipeline
+    }
+
+    /// Writes `contents` into the staging ring, to be copied from by commands
+    /// recorded in `encoder`, and returns the buffer and offset they are at.
+    ///
+    /// The space stays leased by the current frame until the commands of
+    /// `encoder` are done.
+    fn stage(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &wgpu::CommandEncoder,
+        contents: &[u8],
+    ) -> (wgpu::Buffer, u64) {
+        let frame = self.frame.borrow();
+        let mut staging_buffer = self.staging_buffer.borrow_mut();
+        let (start, grown) = staging_buffer.allocate(device, contents.len() as u64, &frame);
+        if grown {
+            #[cfg(test)]
+            self.staging_buffers_created
+                .set(self.staging_buffers_created.get() + 1);
+        }
+        frame.hold_by_encoder(encoder);
+
+        self.count_queue_write();
+        queue.write_buffer(staging_buffer.buffer(), start, contents);
+        (staging_buffer.buffer().clone(), start)
+    }
+
+    /// Starts a frame drawing at most `instances` instances, clipped to `scissor`,
+    /// with atlas draws sampling LCD subpixel masks when `subpixel` is set, and
+    /// all draws preceded by shadows when `shadow` is set.
+    ///
+    /// Offsets into the instance buffer passed within the frame are relative to
+    /// the frame; see [`InstanceRing`].
+    fn begin_frame(&self, instances: usize, scissor: [u32; 4], subpixel: bool, shadow: bool) {
+        self.instance_data_staging.borrow_mut().clear();
+        self.frame_scissor.set(scissor);
+        self.frame_subpixel.set(subpixel);
+        self.frame_shadow.set(shadow);
+        self.frame_stats.set(RenderStats::default());
+        let mut spare = self.spare_frames.borrow_mut();
+        FrameLease::next(&mut self.frame.borrow_mut(), &mut spare);
+        self.instance_buffer.borrow_mut().release_finished(&spare);
+        self.staging_buffer.borrow_mut().release_finished(&spare);
+        self.globals_buffer.borrow_mut().release_finished(&spare);
+        if let Some(indirect_buffer) = self.indirect_buffer.borrow_mut().as_mut() {
+            indirect_buffer.release_finished(&spare);
+        }
+        drop(spare);
+
+        let mut ring = self.instance_ring.get();
+        if let Some(fresh_start) = ring.fresh_start {
+            ring.last_start = fresh_start;
+            ring.last_len = ring.used;
+        }
+        ring.frame_len = (instances * std::mem::size_of::<InstanceData>()) as u64;
+        ring.fresh_start = None;
+        ring.fresh_len = 0;
+        ring.used = 0;
+        self.instance_ring.set(ring);
+    }
+
+    /// Creates a pass of `controller` that keeps the space the current frame
+    /// reads from being reused until its commands are done.
+    fn create_pass<'a, E>(
+        &self,
+        controller: &'a mut impl WgpuRenderPassController<E>,
+    ) -> Result<wgpu::RenderPass<'a>, E> {
+        let pass = controller.create_pass()?;
+        self.frame.borrow().hold_by_pass(&pass);
+        Ok(pass)
+    }
+
+    /// Like [`Self::create_pass`], but creates the last pass of a render.
+    fn create_last_pass<'a, E>(
+        &self,
+        controller: &'a mut impl WgpuRenderPassController<E>,
+    ) -> Result<wgpu::RenderPass<'a>, E> {
+        let pass = controller.create_last_pass()?;
+        self.frame.borrow().hold_by_pass(&pass);
+        Ok(pass)
+    }
+
+    /// Uploads `instances` at byte `offset` of the current frame and returns the
+    /// buffer and byte offset to draw them from.
+    ///
+    /// While the frame matches the previous one, its instances are drawn from
+    /// there. Otherwise they are written into the frame's fresh space, where
+    /// the write is skipped when the buffer already holds the same data. A
+    /// frame outgrowing its fresh space moves to a larger one.
+    fn upload_instances(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        offset: u64,
+        instances: &[InstanceData],
+    ) -> (wgpu::Buffer, u64) {
+        let instance_size = std::mem::size_of::<InstanceData>() as u64;
+        let bytes: &[u8] = bytemuck::cast_slice(instances);
+        let end = offset + bytes.len() as u64;
+        let holds = |shadow: &[InstanceData], start: u64| {
+            let first_slot = (start / instance_size) as usize;
+            shadow
+                .get(first_slot..first_slot + instances.len())
+                .is_some_and(|uploaded| bytemuck::cast_slice::<InstanceData, u8>(uploaded) == bytes)
+        };
+
+        let mut ring = self.instance_ring.get();
+        ring.used = ring.used.max(end);
+        let mut shadow = self.instance_shadow.borrow_mut();
+        let fresh_start = match ring.fresh_start {
+            Some(fresh_start) if end <= ring.fresh_len => fresh_start,
+            Some(fresh_start) => {
+                self.place_fresh_frame(device, queue, &mut ring, &mut shadow, fresh_start, offset)
+            }
+            None if end <= ring.last_len
+                && holds(&shadow, ring.last_start + offset)
+                && self
+                    .instance_buffer
+                    .borrow_mut()
+                    .lease_last(&self.frame.borrow())
+                    .is_some() =>
+            {
+                self.instance_ring.set(ring);
+                let instance_buffer = self.instance_buffer.borrow().buffer().clone();
+                return (instance_buffer, ring.last_start + offset);
+            }
+            None => {
+                let last_start = ring.last_start;
+                self.place_fresh_frame(device, queue, &mut ring, &mut shadow, last_start, offset)
+            }
+        };
+        self.instance_ring.set(ring);
+
+        let instance_buffer = self.instance_buffer.borrow().buffer().clone();
+        let start = fresh_start + offset;
+        if !holds(&shadow, start) {
+            self.count_queue_write();
+            queue.write_buffer(&instance_buffer, start, bytes);
+
+            let first_slot = (start / instance_size) as usize;
+            let slots = first_slot..first_slot + instances.len();
+            if shadow.len() < slots.end {
+                shadow.resize(slots.end, InstanceData::zeroed());
+            }
+            shadow[slots].copy_from_slice(instances);
+        }
+        (instance_buffer, start)
+    }
+
+    /// Places fresh space for the current frame, whose instances up to byte
+    /// `prefix` are the ones at `source`, and returns its start.
+    ///
+    /// That is the previous frame when the current one stopped matching it, or
+    /// the frame's own fresh space when it outgrew it. The space is allocated
+    /// from the ring, which replaces the buffer by a larger one when nothing
+    /// fits. The prefix is moved along, so the next frame finds all of this one
+    /// in one place.
+    fn place_fresh_frame(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        ring: &mut InstanceRing,
+        shadow: &mut Vec<InstanceData>,
+        source: u64,
+        prefix: u64,
+    ) -> u64 {
+        let instance_size = std::mem::size_of::<InstanceData>() as u64;
+        let prefix_slots = |start: u64| {
+            let first_slot = (start / instance_size) as usize;
+            first_slot..first_slot + (prefix / instance_size) as usize
+        };
+
+        let mut instance_buffer = self.instance_buffer.borrow_mut();
+        // A frame outgrowing its space is likely to keep growing.
+        let needed = match ring.fresh_start {
+            Some(_) => ring.used.max(ring.fresh_len * 2),
+            None => ring.frame_len.max(ring.used),
+        };
+        let (fresh_start, grown) = instance_buffer.allocate(device, needed, &self.frame.borrow());
+
+        let source = prefix_slots(source);
+        let target = prefix_slots(fresh_start);
+        if shadow.len() < target.end.max(source.end) {
+            shadow.resize(target.end.max(source.end), InstanceData::zeroed());
+        }
+        shadow.copy_within(source, target.start);
+        if grown {
+            self.update_stats(|stats| stats.buffer_reallocations += 1);
+            // Only the moved prefix is written to the new buffer.
+            shadow.truncate(target.end);
+        }
+        if !target.is_empty() {
+            self.count_queue_write();
+            queue.write_buffer(
+                instance_buffer.buffer(),
+                fresh_start,
+                bytemuck::cast_slice(&shadow[target]),
+            );
+        }
+
+        ring.fresh_start = Some(fresh_start);
+        ring.fresh_len = needed;
+        fresh_start
+    }
+
+    /// Ensures that standalone resources (texture, bind group) are sufficient for the needed dimensions.
+    ///
+    /// # Power-of-Two Sizing
+    /// To avoid recreating the texture every time the glyph size changes slightly, the texture dimensions
+    /// are rounded up to the next power of two (e.g., 100x100 -> 128x128). This significantly stabilizes
+    /// GPU resource churn for variable-sized large glyphs.
+    fn ensure_standalone_resources(
+        &self,
+        device: &wgpu::Device,
+        needed_width: u32,
+        needed_height: u32,
+    ) -> std::cell::RefMut<'_, Option<StandaloneResources>> {
+        let mut resources_ref = self.standalone_resources.borrow_mut();
+
+        let recreate = if let Some(res) = resources_ref.as_ref() {
+            res.size.width < needed_width || res.size.height < needed_height
+        } else {
+            true
+        };
+
+        if recreate {
+            let current_size = resources_ref
+                .as_ref()
+                .map(|r| r.size)
+                .unwrap_or(wgpu::Extent3d {
+                    width: 0,
+                    height: 0,
+                    depth_or_array_layers: 1,
+                });
+            let new_width = current_size.width.max(needed_width);
+            let new_height = current_size.height.max(needed_height);
+
+            let size = wgpu::Extent3d {
+                width: new_width.next_power_of_two(),
+                height: new_height.next_power_of_two(),
+                depth_or_array_layers: 1,
+            };
+
+            if resources_ref.is_some() {
+                self.update_stats(|stats| stats.buffer_reallocations += 1);
+            }
+            *resources_ref = Some(self.create_standalone_resources(device, size));
+        }
+
+        resources_ref
+    }
+
+    /// Creates a standalone glyph texture of `size` and its bind group.
+    fn create_standalone_resources(
+        &self,
+        device: &wgpu::Device,
+        size: wgpu::Extent3d,
+    ) -> StandaloneResources {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Standalone Glyph Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Standalone Bind Group"),
+            layout: &self.standalone_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: Self::globals_binding(self.globals_buffer.borrow().buffer()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+            ],
+        });
+
+        StandaloneResources {
+            texture,
+            bind_group,
+            size,
+        }
+    }
+
+    /// Returns the bytes per row of a copy of `width` one-byte texels, rounded up
+    /// to the 256 bytes WGPU requires.
+    fn padded_bytes_per_row(width: u32) -> u32 {
+        // Align to 256 bytes: (val + 255) & !255 checks the next multiple of 256.
+        (width + 255) & !255
+    }
+
+    /// Prepares pixel data for texture upload, handling WGPU's alignment requirements.
+    ///
+    /// WGPU (and underlying APIs like Vulkan/DirectX) requires that the "bytes per row" in a copy command
+    /// be a multiple of **256 bytes**. If the image width doesn't match this alignment, we must
+    /// copy the data into a new buffer with padding bytes added to the end of each row.
+    ///
+    /// - `pixel_staging`: A reusable vector to avoid allocation when padding is needed.
+    fn prepare_padded_data<'a>(
+        pixel_staging: &'a mut Vec<u8>,
+        pixels: &'a [u8],
+        width: u32,
+        height: u32,
+    ) -> (std::borrow::Cow<'a, [u8]>, u32) {
+        let bytes_per_row = width;
+        let padded_bytes_per_row = Self::padded_bytes_per_row(width);
+        let padding = padded_bytes_per_row - bytes_per_row;
+
+        let data = if padding == 0 {
+            // No padding needed, use original data directly (zero-copy).
+            std::borrow::Cow::Borrowed(pixels)
+        } else {
+            // Padding needed, reuse staging buffer.
+            pixel_staging.clear();
+            pixel_staging.reserve((padded_bytes_per_row * height) as usize);
+
+            for row in 0..height {
+                let src_start = (row * width) as usize;
+                let src_end = src_start + width as usize;
+                if src_end <= pixels.len() {
+                    pixel_staging.extend_from_slice(&pixels[src_start..src_end]);
+                    // Append zeros for alignment
+                    pixel_staging.extend(std::iter::repeat_n(0, padding as usize));
+                }
+            }
+            std::borrow::Cow::Borrowed(pixel_staging.as_slice())
+        };
+
+        (data, padded_bytes_per_row)
+    }
+
+    fn create_atlas_texture(device: &wgpu::Device, size: wgpu::Extent3d) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph Atlas Array"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            // COPY_SRC to carry the glyphs over when the array grows.
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// Binds one set of globals of `globals_buffer`, at the offset of each draw.
+    fn globals_binding(globals_buffer: &wgpu::Buffer) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: globals_buffer,
+            offset: 0,
+            size: Self::globals_size(),
+        })
+    }
+
+    fn create_globals_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        globals_buffer: &wgpu::Buffer,
+        sampler: &wgpu::Sampler,
+        atlas_texture: &wgpu::Texture,
+    ) -> wgpu::BindGroup {
+        let atlas_view = atlas_texture.create_view(&wgpu::TextureViewDescriptor {
+            // A single-layer texture would otherwise default to a `D2` view.
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Globals Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: Self::globals_binding(globals_buffer),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&atlas_view),
+                },
+            ],
+        })
+    }
+
+    /// Recreates the atlas with at least `layers` layers of at least `extent` texels
+    /// on each side, copying the existing ones, and rebinds it. Draws recorded
+    /// before keep sampling the old atlas.
+    fn ensure_atlas_size(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        layers: u32,
+        extent: u32,
+    ) {
+        let mut atlas_texture = self.atlas_texture.borrow_mut();
+        let size = atlas_texture.size();
+        let new_size = Self::fit_atlas_size(
+            device,
+            wgpu::Extent3d {
+                width: size.width.max(extent.next_power_of_two()),
+                height: size.height.max(extent.next_power_of_two()),
+                depth_or_array_layers: size.depth_or_array_layers.max(layers),
+            },
+        );
+        if new_size == size {
+            return;
+        }
+        self.update_stats(|stats| stats.buffer_reallocations += 1);
+
+        let texture = Self::create_atlas_texture(device, new_size);
+        encoder.copy_texture_to_texture(
+            atlas_texture.as_image_copy(),
+            texture.as_image_copy(),
+            size,
+        );
+        if let Some(copy) = &mut *self.atlas_copy.borrow_mut() {
+            copy.resize(texture.size());
+        }
+        *self.globals_bind_group.borrow_mut() = Self::create_globals_bind_group(
+            device,
+            &self.bind_group_layout,
+            self.globals_buffer.borrow().buffer(),
+            &self.sampler,
+            &texture,
+        );
+        *atlas_texture = texture;
+    }
+
+    /// Returns `size` clamped to the texture limits of `device`.
+    fn fit_atlas_size(device: &wgpu::Device, size: wgpu::Extent3d) -> wgpu::Extent3d {
+        let limits = device.limits();
+        wgpu::Extent3d {
+            width: size.width.min(limits.max_texture_dimension_2d),
+            height: size.height.min(limits.max_texture_dimension_2d),
+            depth_or_array_layers: size
+                .depth_or_array_layers
+                .min(limits.max_texture_array_layers),
+        }
+    }
+
+    /// Copies regions of the atlas to other places, in order. Moves outside the
+    /// atlas are skipped.
+    fn move_atlas(&self, encoder: &mut wgpu::CommandEncoder, moves: &[AtlasMove]) {
+        if let Some(copy) = &mut *self.atlas_copy.borrow_mut() {
+            for atlas_move in moves {
+                copy.copy(atlas_move);
+            }
+        }
+        let atlas_texture = self.atlas_texture.borrow();
+        let atlas_size = atlas_texture.size();
+        let fits = |texture_index: usize, x: usize, y: usize, atlas_move: &AtlasMove| {
+            texture_index < atlas_size.depth_or_array_layers as usize
+                && x + atlas_move.width <= atlas_size.width as usize
+                && y + atlas_move.height <= atlas_size.height as usize
+        };
+        for atlas_move in moves {
+            let AtlasMove {
+                src_texture_index,
+                src_x,
+                src_y,
+                dst_texture_index,
+                dst_x,
+                dst_y,
+                width,
+                height,
+            } = *atlas_move;
+            if width == 0
+                || height == 0
+                || !fits(src_texture_index, src_x, src_y, atlas_move)
+                || !fits(dst_texture_index, dst_x, dst_y, atlas_move)
+            {
+                continue;
+            }
+            encoder.copy_texture_to_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &atlas_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: src_x as u32,
+                        y: src_y as u32,
+                        z: src_texture_index as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::TexelCopyTextureInfo {
+                    texture: &atlas_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: dst_x as u32,
+                        y: dst_y as u32,
+                        z: dst_texture_index as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width: width as u32,
+                    height: height as u32,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    /// Uploads glyphs to the atlas, growing it first if they need more layers or
+    /// a larger extent. Glyphs outside an atlas at the texture limits of the
+    /// device are skipped.
+    ///
+    /// The copies are recorded in `encoder`, so commands recorded before still
+    /// see the atlas they were recorded with, even when a later render evicts
+    /// their glyphs before the encoder is submitted.
+    fn update_atlas(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        updates: &[AtlasUpdate],
+    ) {
+        // Pages added by cache growth get their layers before the first upload, and
+        // overflow pages larger than the layers grow all of them.
+        if let Some(layers) = updates.iter().map(|update| update.texture_index + 1).max() {
+            let extent = updates
+                .iter()
+                .map(|update| (update.x + update.width).max(update.y + update.height))
+                .max()
+                .unwrap_or(0);
+            self.ensure_atlas_size(device, encoder, layers as u32, extent as u32);
+        }
+        let bytes: usize = updates
+            .iter()
+            .map(|update| update.width * update.height)
+            .sum();
+        self.update_stats(|stats| stats.atlas_bytes_uploaded += bytes as u64);
+        if let Some(copy) = &mut *self.atlas_copy.borrow_mut() {
+            for update in updates {
+                copy.write(update);
+            }
+        }
+        let atlas_texture = self.atlas_texture.borrow();
+        let mut pixel_staging = self.pixel_staging.borrow_mut();
+        let atlas_size = atlas_texture.size();
+        let fits = |update: &AtlasUpdate| {
+            update.texture_index < atlas_size.depth_or_array_layers as usize
+                && update.x + update.width <= atlas_size.width as usize
+                && update.y + update.height <= atlas_size.height as usize
+        };
+
+        // Gather all updates into one staging buffer. Rows are padded to 256 bytes,
+        // so every update also starts at a multiple of 256.
+        pixel_staging.clear();
+        for update in updates.iter().filter(|update| fits(update)) {
+            let width = update.width;
+            let padding = Self::padded_bytes_per_row(width as u32) as usize - width;
+            for row in 0..update.height {
+                match update.pixels.get(row * width..(row + 1) * width) {
+                    Some(pixels) => pixel_staging.extend_from_slice(pixels),
+                    None => pixel_staging.extend(std::iter::repeat_n(0, width)),
+                }
+                pixel_staging.extend(std::iter::repeat_n(0, padding));
+            }
+        }
+        if pixel_staging.is_empty() {
+            return;
+        }
+        let (staging_buffer, mut offset) = self.stage(device, queue, encoder, &pixel_staging);
+
+        for update in updates.iter().filter(|update| fits(update)) {
+            let width = update.width as u32;
+            let height = update.height as u32;
+
+            if width == 0 || height == 0 {
+                continue;
+            }
+
+            let padded_bytes_per_row = Self::padded_bytes_per_row(width);
+            let region_offset = offset;
+            offset += u64::from(padded_bytes_per_row) * u64::from(height);
+
+            encoder.copy_buffer_to_texture(
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &staging_buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: region_offset,
+                        bytes_per_row: Some(padded_bytes_per_row),
+                        rows_per_image: Some(height),
+                    },
+                },
+                wgpu::TexelCopyTextureInfo {
+                    texture: &atlas_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: update.x as u32,
+                        y: update.y as u32,
+                        z: update.texture_index as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    /// Converts a glyph instance and queues it for the next `draw_instances` call.
+    ///
+    /// UVs are converted to texels of the page, `texture_size` wide, so they stay
+    /// valid when the atlas layers grow.
+    fn push_instance<T: Into<[f32; 4]> + Copy>(
+        &self,
+        inst: &GlyphInstance<T>,
+        texture_size: usize,
+    ) {
+        let uv_rect = inst.uv_rect.scale(texture_size as f32, texture_size as f32);
+        let (color, secondary_color, gradient) =
+            InstanceData::fill(inst.user_data.into(), inst.opacity, inst.gradient);
+        self.instance_data_staging.borrow_mut().push(InstanceData {
+            screen_rect: [
+                inst.screen_rect.min.x,
+                inst.screen_rect.min.y,
+                inst.screen_rect.width(),
+                inst.screen_rect.height(),
+            ],
+            uv_rect: [
+                uv_rect.min.x,
+                uv_rect.min.y,
+                uv_rect.width(),
+                uv_rect.height(),
+            ],
+            color,
+            layer: inst.texture_index as u32,
+            depth: inst.depth,
+            view_mask: self.view_mask,
+            _padding: 0,
+            secondary_color,
+            gradient,
+        });
+    }
+
+    /// Draws solid `rects` at `depth` in a new pass, if there are any.
+    fn draw_rects<E>(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+        current_offset: &std::cell::Cell<u64>,
+        rects: &[FilledRect],
+        depth: f32,
+    ) -> Result<(), E> {
+        let format = controller.format()?;
+        if let Some(draw) = self.prepare_rects(device, queue, format, current_offset, rects, depth)
+        {
+            draw.record(&mut self.create_pass(controller)?);
+        }
+        Ok(())
+    }
+
+    /// Uploads solid `rects` at `depth` and returns their draw, if there are any.
+    ///
+    /// The rectangles sample no glyphs, so they are drawn with the grayscale
+    /// pipeline, cast no shadow and leave the atlas free for queue writes.
+    fn prepare_rects(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        current_offset: &std::cell::Cell<u64>,
+        rects: &[FilledRect],
+        depth: f32,
+    ) -> Option<PreparedDraw> {
+        for rect in rects {
+            self.push_rect(rect, depth);
+        }
+
+        let subpixel = self.frame_subpixel.replace(false);
+        let shadow = self.frame_shadow.replace(false);
+        let draw = self.prepare_instances(device, queue, format, current_offset);
+        self.frame_subpixel.set(subpixel);
+        self.frame_shadow.set(shadow);
+        draw
+    }
+
+    /// Queues a solid rectangle at `depth`, to be drawn like the instances of `push_instance`.
+    fn push_rect(&self, rect: &FilledRect, depth: f32) {
+        let [width, height] = [rect.rect.width(), rect.rect.height()];
+        self.instance_data_staging.borrow_mut().push(InstanceData {
+            screen_rect: [rect.rect.min.x, rect.rect.min.y, width, height],
+            // One texel per layout unit, so shadows blur solids like glyphs.
+            uv_rect: [0.0, 0.0, width, height],
+            color: rect.color,
+            layer: SOLID_LAYER,
+            depth,
+            view_mask: self.view_mask,
+            _padding: 0,
+            secondary_color: rect.color,
+            gradient: [0.0; 4],
+        });
+    }
+
+    /// Draws the instances queued by `push_instance` in a new pass and empties the queue.
+    fn draw_instances<E>(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+        current_offset: &std::cell::Cell<u64>,
+    ) -> Result<(), E> {
+        let format = controller.format()?;
+        let Some(draw) = self.prepare_instances(device, queue, format, current_offset) else {
+            return Ok(());
+        };
+        let mut pass = self.create_pass(controller)?;
+        draw.record_shadow(&mut pass);
+        draw.record(&mut pass);
+        Ok(())
+    }
+
+    /// Draws the instances still queued at the end of a render in its last
+    /// pass, which is left empty when the earlier passes drew everything.
+    fn draw_last_instances<E>(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+        current_offset: &std::cell::Cell<u64>,
+    ) -> Result<(), E> {
+        let format = controller.format()?;
+        let draw = self.prepare_instances(device, queue, format, current_offset);
+        if draw.is_none() && self.frame_stats.get().draw_calls == 0 {
+            return Ok(());
+        }
+        let mut pass = self.create_last_pass(controller)?;
+        if let Some(draw) = draw {
+            draw.record_shadow(&mut pass);
+            draw.record(&mut pass);
+        }
+        Ok(())
+    }
+
+    /// Uploads the instances queued by `push_instance`, empties the queue and
+    /// returns the draw of the instances, if any were queued.
+    fn prepare_instances(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        current_offset: &std::cell::Cell<u64>,
+    ) -> Option<PreparedDraw> {
+        let mut instance_data = self.instance_data_staging.borrow_mut();
+        if instance_data.is_empty() {
+            return None;
+        }
+
+        let instance_size = std::mem::size_of::<InstanceData>() as u64;
+        let offset = current_offset.get();
+        let byte_len = instance_data.len() as u64 * instance_size;
+
+        let (instance_buffer, start) = self.upload_instances(device, queue, offset, &instance_data);
+
+        // Use cached pipeline or create new one based on format
+        let pipeline = match self.frame_subpixel.get() {
+            true => self.get_subpixel_pipeline(device, format),
+            false => None,
+        };
+        let draw = PreparedDraw {
+            pipeline: pipeline.unwrap_or_else(|| self.get_pipeline(device, format)),
+            shadow_pipeline: self
+                .frame_shadow
+                .get()
+                .then(|| self.get_shadow_pipeline(device, format, false)),
+            bind_group: self.globals_bind_group.borrow().clone(),
+            globals_offset: self.globals_offset.get(),
+            instance_buffer,
+            range: start..start + byte_len,
+            count: instance_data.len() as u32,
+            scissor: self.frame_scissor.get(),
+        };
+
+        instance_data.clear();
+        current_offset.set(offset + byte_len);
+        self.count_draw(&draw);
+        Some(draw)
+    }
+
+    fn draw_standalone<T: Into<[f32; 4]> + Copy, E>(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controller: &mut impl WgpuRenderPassController<E>,
+        current_offset: &std::cell::Cell<u64>,
+        standalone: &StandaloneGlyph<T>,
+    ) -> Result<(), E> {
+        let format = controller.format()?;
+        let draw = self.prepare_standalone(
+            device,
+            queue,
+            controller.encoder()?,
+            format,
+            current_offset,
+            standalone,
+            true,
+        );
+        let mut pass = self.create_pass(controller)?;
+        draw.record_shadow(&mut pass);
+        draw.record(&mut pass);
+        Ok(())
+    }
+
+    /// Uploads a standalone glyph and returns its draw.
+    ///
+    /// With `shared_texture`, the glyph goes into the reused standalone texture
+    /// through a copy recorded in `encoder`, so draws recorded before keep the
+    /// glyph they sampled. Otherwise it gets a texture of its own, written
+    /// through the queue and kept alive by the draw.
+    fn prepare_standalone<T: Into<[f32; 4]> + Copy>(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        format: wgpu::TextureFormat,
+        current_offset: &std::cell::Cell<u64>,
+        standalone: &StandaloneGlyph<T>,
+        shared_texture: bool,
+    ) -> PreparedDraw {
+        let needed_width = standalone.width as u32;
+        let needed_height = standalone.height as u32;
+
+        let resources = if shared_texture {
+            let resources_ref =
+                self.ensure_standalone_resources(device, needed_width, needed_height);
+            let resources = resources_ref
+                .as_ref()
+                .expect("Logic bug: resources_ref should be initialized.");
+            StandaloneResources {
+                texture: resources.texture.clone(),
+                bind_group: resources.bind_group.clone(),
+                size: resources.size,
+            }
+        } else {
+            self.create_standalone_resources(
+                device,
+                wgpu::Extent3d {
+                    width: needed_width.max(1),
+                    height: needed_height.max(1),
+                    depth_or_array_layers: 1,
+                },
+            )
+        };
+
+        let width = standalone.width as u32;
+        let height = standalone.height as u32;
+
+        if shared_texture && width > 0 && height > 0 {
+            // Prepare data with 256-byte alignment for copy_buffer_to_texture
+            let mut pixel_staging = self.pixel_staging.borrow_mut();
+            let (data, padded_bytes_per_row) =
+                Self::prepare_padded_data(&mut pixel_staging, &standalone.pixels, width, height);
+
+            let (staging_buffer, offset) = self.stage(device, queue, encoder, &data);
+
+            encoder.copy_buffer_to_texture(
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &staging_buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset,
+                        bytes_per_row: Some(padded_bytes_per_row),
+                        rows_per_image: Some(height),
+                    },
+                },
+                wgpu::TexelCopyTextureInfo {
+                    texture: &resources.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        } else if width > 0 && height > 0 {
+            self.write_pixels(
+                queue,
+                &resources.texture,
+                wgpu::Origin3d::ZERO,
+                width,
+                height,
+                &standalone.pixels,
+            );
+        }
+
+        // UV calculation
+        let u_max = standalone.width as f32 / resources.size.width as f32;
+        let v_max = standalone.height as f32 / resources.size.height as f32;
+
+        // Instance data for standalone
+        let (color, secondary_color, gradient) = InstanceData::fill(
+            standalone.user_data.into(),
+            standalone.opacity,
+            standalone.gradient,
+        );
+        let instance_data = InstanceData {
+            screen_rect: [
+                standalone.screen_rect.min.x,
+                standalone.screen_rect.min.y,
+                standalone.screen_rect.width(),
+                standalone.screen_rect.height(),
+            ],
+            uv_rect: [0.0, 0.0, u_max, v_max],
+            color,
+            layer: 0,
+            depth: standalone.depth,
+            view_mask: self.view_mask,
+            _padding: 0,
+            secondary_color,
+            gradient,
+        };
+
+        // Use the shared instance buffer for standalone glyphs too
+        let instance_size = std::mem::size_of::<InstanceData>() as u64;
+        let offset = current_offset.get();
+        let (instance_buffer, start) =
+            self.upload_instances(device, queue, offset, std::slice::from_ref(&instance_data));
+
+        current_offset.set(offset + instance_size);
+        let draw = PreparedDraw {
+            pipeline: self.get_standalone_pipeline(device, format),
+            shadow_pipeline: self
+                .frame_shadow
+                .get()
+                .then(|| self.get_shadow_pipeline(device, format, true)),
+            bind_group: resources.bind_group,
+            globals_offset: self.globals_offset.get(),
+            instance_buffer,
+            range: start..start + instance_size,
+            count: 1,
+            scissor: self.frame_scissor.get(),
+        };
+        self.update_stats(|stats| stats.standalone += 1);
+        self.count_draw(&draw);
+        draw
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::gpu_renderer::tests::count_allocations;
+    use crate::renderer::{AtlasPacking, AtlasPriority, text_decorations};
+    use crate::test_utils::{load_test_font, test_font_storage, text_element};
+    use crate::text::{TextData, TextLayoutConfig, TextStyle};
+    use std::num::NonZeroUsize;
+
+    /// Returns a device of the default adapter.
+    ///
+    /// The tests that need one are ignored, as not every machine has an
+    /// adapter; run them with `cargo test -- --include-ignored`.
+    fn create_device() -> (wgpu::Device, wgpu::Queue) {
+        create_device_with_features(wgpu::Features::empty()).unwrap()
+    }
+
+    /// Returns a device with `features`, or `None` if the adapter lacks them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no adapter.
+    fn create_device_with_features(
+        features: wgpu::Features,
+    ) -> Option<(wgpu::Device, wgpu::Queue)> {
+        let adapter = request_adapter();
+        if !adapter.features().contains(features) {
+            return None;
+        }
+        Some(
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+                required_features: features,
+                required_limits: adapter.limits(),
+                ..Default::default()
+            }))
+            .unwrap(),
+        )
+    }
+
+    /// Returns the default adapter, panicking if there is none.
+    fn request_adapter() -> wgpu::Adapter {
+        let instance = wgpu::Instance::default();
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .expect("no wgpu adapter available")
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_cached_layout_creates_no_staging_buffers() {
+        let (device, queue) = create_device();
+
+        let (font_storage, font_id) = test_font_storage();
+
+        let mut data = TextData::new();
+        data.append(text_element(
+            font_id,
+            16.0,
+            "The quick brown fox jumps over the lazy dog",
+            [1.0f32, 1.0, 1.0, 1.0],
+        ));
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 512,
+                height: 64,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut renderer = WgpuRenderer::new(
+            &device,
+            &[GpuCacheConfig {
+                texture_size: NonZeroUsize::new(512).unwrap(),
+                tile_size: NonZeroUsize::new(32).unwrap(),
+                tiles_per_axis: NonZeroUsize::new(16).unwrap(),
+                priority: AtlasPriority::Normal,
+                padding: 1,
+                extrude_edges: false,
+                packing: AtlasPacking::Grid,
+            }],
+            &[format],
+            None,
+        );
+
+        let render_frame = |renderer: &mut WgpuRenderer| {
+            let before = uploads(renderer);
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            renderer.render(&layout, &font_storage, &device, &queue, &mut encoder, &view);
+            queue.submit([encoder.finish()]);
+            let after = uploads(renderer);
+            [after[0] - before[0], after[1] - before[1]]
+        };
+
+        // The first frame writes its globals, the pixels of all glyphs into the
+        // staging buffer and its instances, once each.
+        assert_eq!(render_frame(&mut renderer), [3, 0]);
+        assert_eq!(render_frame(&mut renderer), [0, 0]);
+        assert_eq!(render_frame(&mut renderer), [0, 0]);
+
+        // Scrolling and zooming only rewrites the globals.
+        renderer.set_transform(
+            euclid::Transform2D::scale(1.5, 1.5).then_translate(euclid::vec2(0.0, -20.0)),
+        );
+        assert_eq!(render_frame(&mut renderer), [1, 0]);
+        assert_eq!(render_frame(&mut renderer), [0, 0]);
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_cached_layouts_render_without_allocations() {
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let layout = |content: &str| {
+            TextData::builder(font_id, 16.0, [1.0f32, 1.0, 1.0, 1.0])
+                .push_str(content)
+                .build()
+                .layout(&TextLayoutConfig::default(), &font_storage)
+        };
+        let empty = layout("");
+        let short = layout("fox");
+        let long = layout("The quick brown fox jumps over the lazy dog");
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, 512, 64);
+        let mut renderer = test_renderer(&device, format);
+
+        // Returns the allocations of rendering `layouts`, with `render` for a
+        // single layout and `render_all` otherwise.
+        let frame = |renderer: &mut WgpuRenderer, layouts: &[&TextLayout<[f32; 4]>]| {
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            let allocations = count_allocations(|| match layouts {
+                [layout] => {
+                    renderer.render(
+                        layout,
+                        &font_storage,
+                        &device,
+                        &queue,
+                        &mut encoder,
+                        &target.view,
+                    );
+                }
+                _ => {
+                    let placed: [_; 2] = std::array::from_fn(|index| {
+                        let placement = LayoutPlacement {
+                            offset: [0.0, 20.0 * index as f32],
+                            ..Default::default()
+                        };
+                        (placement, layouts[index])
+                    });
+                    let mut pass = SimpleRenderPass::new(&mut encoder, &target.view);
+                    renderer
+                        .render_all(&placed, &font_storage, &device, &queue, &mut pass)
+                        .unwrap();
+                }
+            });
+            queue.submit([encoder.finish()]);
+            device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+            allocations
+        };
+
+        // Caches every glyph.
+        frame(&mut renderer, &[&long]);
+
+        // Nothing of an empty layout reaches wgpu, so nothing is allocated.
+        frame(&mut renderer, &[&empty]);
+        assert_eq!(frame(&mut renderer, &[&empty]), 0);
+
+        // What wgpu allocates to record the pass does not depend on the text.
+        let mut steady = Vec::new();
+        for layouts in [&[&short][..], &[&long], &[&short, &long]] {
+            frame(&mut renderer, layouts);
+            steady.push(frame(&mut renderer, layouts));
+            steady.push(frame(&mut renderer, layouts));
+        }
+        assert!(steady.iter().all(|&allocations| allocations == steady[0]));
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_cached_layouts_prepare_frames_without_allocations() {
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let layout = TextData::builder(font_id, 16.0, [1.0f32, 1.0, 1.0, 1.0])
+            .push_str("The quick brown fox jumps over the lazy dog")
+            .build()
+            .layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, 512, 64);
+        let mut renderer = test_renderer(&device, format);
+        let render = |renderer: &mut WgpuRenderer| {
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            renderer.render(
+                &layout,
+                &font_storage,
+                &device,
+                &queue,
+                &mut encoder,
+                &target.view,
+            );
+            queue.submit([encoder.finish()]);
+            device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        };
+
+        // What `render_to` does in Suzuri for a frame, without recording the
+        // passes: queues the instance of every glyph and uploads the globals and
+        // the instances.
+        let prepare = |renderer: &mut WgpuRenderer| {
+            let (stack_clip, shader_clip) = resolve_clips(&renderer.clip_stack);
+            let globals = Globals::new(
+                [512.0, 64.0],
+                renderer.color_space.transform(format),
+                renderer.blend_space.correction(format),
+                renderer.gpu_renderer.transform(),
+                renderer.shadow,
+                renderer.pixel_snap,
+                renderer.hdr_output.scale(format),
+                shader_clip,
+            );
+            let resources = &renderer.resources;
+            let current_offset = std::cell::Cell::new(0);
+            resources.begin_frame(
+                layout.len_glyphs(),
+                scissor_rect(
+                    intersect_clip_rects(layout.clip_rect, stack_clip),
+                    globals.screen_size,
+                ),
+                false,
+                false,
+            );
+            resources.upload_globals(&device, &queue, globals);
+            let mut draws = 0;
+            renderer
+                .gpu_renderer
+                .try_render_streaming_all::<_, ()>(
+                    &[(LayoutPlacement::default(), &layout)],
+                    &font_storage,
+                    false,
+                    &mut |updates| {
+                        assert!(updates.is_empty());
+                        Ok(())
+                    },
+                    &mut |instance, texture_size| resources.push_instance(&instance, texture_size),
+                    &mut || {
+                        draws += resources
+                            .prepare_instances(&device, &queue, format, &current_offset)
+                            .is_some() as usize;
+                        Ok(())
+                    },
+                    &mut |_| Ok(()),
+                )
+                .unwrap();
+            draws += resources
+                .prepare_instances(&device, &queue, format, &current_offset)
+                .is_some() as usize;
+            assert_eq!(draws, 1);
+        };
+
+        // Caches every glyph and uploads the globals and the instances.
+        render(&mut renderer);
+        render(&mut renderer);
+
+        let before = uploads(&renderer);
+        for _ in 0..3 {
+            assert_eq!(count_allocations(|| prepare(&mut renderer)), 0);
+            render(&mut renderer);
+        }
+        assert_eq!(uploads(&renderer), before);
+    }
+
+    /// Returns the writes through the queue and the times the staging ring grew so far.
+    fn uploads(renderer: &WgpuRenderer) -> [usize; 2] {
+        [
+            renderer.resources.queue_writes.get(),
+            renderer.resources.staging_buffers_created.get(),
+        ]
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_atlas_grows_when_cache_overflows() {
+        let (device, queue) = create_device();
+
+        let (font_storage, font_id) = test_font_storage();
+
+        let mut data = TextData::new();
+        data.append(text_element(font_id, 16.0, "abcd", [1.0f32, 1.0, 1.0, 1.0]));
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 128,
+                height: 64,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // One tile per page, so the glyphs of a single frame overflow the two pages.
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(1).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let mut renderer = WgpuRenderer::new(&device, &[config.clone(), config], &[format], None);
+        renderer.set_growth_budget(&device, 2 * 64 * 64);
+
+        let render_frame = |renderer: &mut WgpuRenderer| {
+            let before = uploads(renderer);
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            renderer.render(&layout, &font_storage, &device, &queue, &mut encoder, &view);
+            queue.submit([encoder.finish()]);
+            let after = uploads(renderer);
+            [after[0] - before[0], after[1] - before[1]]
+        };
+
+        render_frame(&mut renderer);
+        assert_eq!(renderer.gpu_renderer.page_count(), 4);
+        assert_eq!(
+            renderer
+                .resources
+                .atlas_texture
+                .borrow()
+                .depth_or_array_layers(),
+            4
+        );
+        // All glyphs stay cached, so the next frame uploads nothing.
+        assert_eq!(render_frame(&mut renderer), [0, 0]);
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_oversized_glyph_is_cached_in_overflow_page() {
+        let (device, queue) = create_device();
+
+        let (font_storage, font_id) = test_font_storage();
+
+        let mut data = TextData::new();
+        data.append(text_element(font_id, 200.0, "c", [1.0f32, 1.0, 1.0, 1.0]));
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 256,
+                height: 256,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(1).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let mut renderer = WgpuRenderer::new(&device, &[config.clone(), config], &[format], None);
+        renderer.set_overflow_budget(&device, 1 << 20);
+
+        let render_frame = |renderer: &mut WgpuRenderer| {
+            let before = uploads(renderer);
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            renderer.render(&layout, &font_storage, &device, &queue, &mut encoder, &view);
+            queue.submit([encoder.finish()]);
+            let after = uploads(renderer);
+            [after[0] - before[0], after[1] - before[1]]
+        };
+
+        render_frame(&mut renderer);
+        assert_eq!(renderer.stats().standalone, 0);
+        assert_eq!(renderer.gpu_renderer.page_count(), 3);
+        let page_size = renderer
+            .gpu_renderer
+            .page_config(2)
+            .unwrap()
+            .texture_size
+            .get();
+        let atlas_size = renderer.resources.atlas_texture.borrow().size();
+        assert!(atlas_size.width as usize >= page_size);
+        assert_eq!(atlas_size.depth_or_array_layers, 3);
+        // The glyph stays cached, so the next frame uploads nothing.
+        assert_eq!(render_frame(&mut renderer), [0, 0]);
+        assert_eq!(renderer.stats().hits, 1);
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_instance_ring_keeps_renders_apart() {
+        let (device, queue) = create_device();
+
+        let (font_storage, font_id) = test_font_storage();
+        let layout = |content: &str| {
+            let mut data = TextData::new();
+            data.append(text_element(
+                font_id,
+                16.0,
+                content,
+                [1.0f32, 1.0, 1.0, 1.0],
+            ));
+            data.layout(&TextLayoutConfig::default(), &font_storage)
+        };
+        let (first, second) = (layout("abcd"), layout("wxyz"));
+
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(256).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(4).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let mut renderer = WgpuRenderer::new(&device, &[config.clone(), config], &[format], None);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let mut prepare = |renderer: &mut WgpuRenderer, layout, rects| {
+            renderer.prepare_with_rects(
+                layout,
+                rects,
+                &font_storage,
+                &device,
+                &queue,
+                &mut encoder,
+                format,
+                [128.0, 64.0],
+            )
+        };
+        let disjoint = |a: &std::ops::Range<u64>, b: &std::ops::Range<u64>| {
+            a.end <= b.start || b.end <= a.start
+        };
+        let no_rects = LayoutRects::default();
+
+        // Renders recorded before one submission each get instances of their own.
+        let a = prepare(&mut renderer, &first, no_rects);
+        let b = prepare(&mut renderer, &second, no_rects);
+        let c = prepare(&mut renderer, &first, no_rects);
+        let [a, b, c] = [a, b, c].map(|prepared| {
+            assert_eq!(prepared.draw_count(), 1);
+            prepared.draws[0].range.clone()
+        });
+        assert!(disjoint(&a, &b) && disjoint(&b, &c) && disjoint(&a, &c));
+
+        // An unchanged frame draws the instances of the previous one.
+        let before = uploads(&renderer);
+        let d = prepare(&mut renderer, &first, no_rects);
+        assert_eq!(d.draws[0].range, c);
+        assert_eq!(uploads(&renderer), before);
+
+        // A frame that changes after matching draws moves them along, so the
+        // next frame finds it in one place.
+        let background = FilledRect {
+            rect: euclid::Box2D::new(
+                euclid::Point2D::new(0.0, 0.0),
+                euclid::Point2D::new(8.0, 8.0),
+            ),
+            color: [1.0; 4],
+        };
+        let rects = LayoutRects {
+            behind: std::slice::from_ref(&background),
+            in_front: &[],
+        };
+        prepare(&mut renderer, &first, rects);
+        let changed = prepare(&mut renderer, &second, rects);
+        let before = uploads(&renderer);
+        let unchanged = prepare(&mut renderer, &second, rects);
+        assert_eq!(uploads(&renderer), before);
+        let (rect, glyphs) = (
+            &unchanged.behind.as_ref().unwrap().range,
+            &unchanged.draws[0].range,
+        );
+        assert_eq!(rect.end, glyphs.start);
+        assert_eq!(glyphs, &changed.draws[0].range);
+
+        // A frame finding no free space grows the ring; earlier draws keep the
+        // previous buffer.
+        let buffer = d.draws[0].instance_buffer.clone();
+        let large = layout(&"abcd".repeat(300));
+        let grown = prepare(&mut renderer, &large, no_rects);
+        assert_ne!(grown.draws[0].instance_buffer, buffer);
+        assert_eq!(d.draws[0].instance_buffer, buffer);
+        queue.submit([encoder.finish()]);
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_renders_sharing_an_encoder_keep_their_instances() {
+        const SIZE: u32 = 64;
+
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, SIZE, SIZE);
+
+        // Together, the renders draw more instances than the ring initially
+        // holds, each into a band of its own, before one submission.
+        for (renders, glyphs) in [(4, 300), (16, 80)] {
+            let band = SIZE / renders;
+            let data = TextData::builder(font_id, band as f32 / 2.0, [1.0f32; 4])
+                .push_str(&"█".repeat(glyphs))
+                .build();
+            let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+            let mut renderer = test_renderer(&device, format);
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            for index in 0..renders {
+                let load = match index {
+                    0 => wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    _ => wgpu::LoadOp::Load,
+                };
+                let placement = LayoutPlacement::at([0.0, (index * band) as f32]);
+                let mut pass = SimpleRenderPass::new(&mut encoder, &target.view).with_load_op(load);
+                renderer
+                    .render_placed_to(
+                        &layout,
+                        placement,
+                        &font_storage,
+                        &device,
+                        &queue,
+                        &mut pass,
+                    )
+                    .unwrap();
+            }
+            let pixels = target.read(&device, &queue, encoder);
+            for index in 0..renders {
+                let band_pixels =
+                    &pixels[(index * band * SIZE) as usize..][..(band * SIZE) as usize];
+                assert!(
+                    band_pixels.iter().any(|&[red, ..]| red > 0),
+                    "band {index} of {renders} is empty"
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_renders_sharing_an_encoder_keep_their_globals_and_glyphs() {
+        const SIZE: u32 = 64;
+
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let layout = |content: &str| {
+            TextData::builder(font_id, 32.0, [1.0f32; 4])
+                .push_str(content)
+                .build()
+                .layout(&TextLayoutConfig::default(), &font_storage)
+        };
+        let (block, other) = (layout("█"), layout("ab"));
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, SIZE, SIZE);
+        // One glyph per page, so the second render evicts the block.
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(1).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let left_half = |pixels: &[[u8; 4]]| -> Vec<u8> {
+            pixels
+                .chunks_exact(SIZE as usize)
+                .flat_map(|row| row[..SIZE as usize / 2].iter().map(|&[red, ..]| red))
+                .collect()
+        };
+
+        let mut renderer = WgpuRenderer::new(&device, &[config.clone(), config], &[format], None);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        renderer.render(
+            &block,
+            &font_storage,
+            &device,
+            &queue,
+            &mut encoder,
+            &target.view,
+        );
+        let expected = left_half(&target.read(&device, &queue, encoder));
+        assert!(expected.iter().any(|&red| red > 0));
+
+        // The second render moves its text to the right half and evicts the
+        // block, before either is submitted.
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        renderer.render(
+            &block,
+            &font_storage,
+            &device,
+            &queue,
+            &mut encoder,
+            &target.view,
+        );
+        renderer.set_transform(euclid::Transform2D::translation(SIZE as f32 / 2.0, 0.0));
+        let mut pass =
+            SimpleRenderPass::new(&mut encoder, &target.view).with_load_op(wgpu::LoadOp::Load);
+        renderer
+            .render_to(&other, &font_storage, &device, &queue, &mut pass)
+            .unwrap();
+        assert!(renderer.stats().evictions > 0);
+        assert_eq!(left_half(&target.read(&device, &queue, encoder)), expected);
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_prepared_text_keeps_its_glyphs_while_alive() {
+        const SIZE: u32 = 64;
+
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let layout = |content: &str| {
+            TextData::builder(font_id, 32.0, [1.0f32; 4])
+                .push_str(content)
+                .build()
+                .layout(&TextLayoutConfig::default(), &font_storage)
+        };
+        let (block, other) = (layout("█"), layout("ab"));
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, SIZE, SIZE);
+        // One glyph per page, so the render after the prepare fills the atlas.
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(1).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let left_half = |pixels: &[[u8; 4]]| -> Vec<u8> {
+            pixels
+                .chunks_exact(SIZE as usize)
+                .flat_map(|row| row[..SIZE as usize / 2].iter().map(|&[red, ..]| red))
+                .collect()
+        };
+
+        let mut renderer = WgpuRenderer::new(&device, &[config.clone(), config], &[format], None);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        renderer.render(
+            &block,
+            &font_storage,
+            &device,
+            &queue,
+            &mut encoder,
+            &target.view,
+        );
+        let expected = left_half(&target.read(&device, &queue, encoder));
+        assert!(expected.iter().any(|&red| red > 0));
+
+        // A render of other text on the right half, recorded between the prepare
+        // and the pass drawing the prepared block, needs both pages.
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let prepared = renderer.prepare(
+            &block,
+            &font_storage,
+            &device,
+            &queue,
+            &mut encoder,
+            format,
+            [SIZE as f32; 2],
+        );
+        renderer.set_transform(euclid::Transform2D::translation(SIZE as f32 / 2.0, 0.0));
+        renderer.render(
+            &other,
+            &font_storage,
+            &device,
+            &queue,
+            &mut encoder,
+            &target.view,
+        );
+        assert!(renderer.stats().evictions > 0);
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            renderer.render_in_pass(&prepared, &mut pass);
+        }
+        assert_eq!(left_half(&target.read(&device, &queue, encoder)), expected);
+
+        // Once dropped and drawn, its glyph makes way for others again: both
+        // glyphs of a later prepare are cached instead of one being standalone.
+        drop(prepared);
+        renderer.reset_stats();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        renderer.prepare(
+            &layout("cd"),
+            &font_storage,
+            &device,
+            &queue,
+            &mut encoder,
+            format,
+            [SIZE as f32; 2],
+        );
+        assert_eq!(renderer.stats().standalone, 0);
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_prepared_text_renders_in_callers_pass() {
+        let (device, queue) = create_device();
+
+        let (font_storage, font_id) = test_font_storage();
+
+        let mut data = TextData::new();
+        data.append(text_element(font_id, 16.0, "abcd", [1.0f32, 1.0, 1.0, 1.0]));
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 128,
+                height: 64,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // One tile per page: two glyphs are cached, the others drawn standalone.
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(1).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let mut renderer = WgpuRenderer::new(&device, &[config.clone(), config], &[format], None);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let prepared = renderer.prepare(
+            &layout,
+            &font_storage,
+            &device,
+            &queue,
+            &mut encoder,
+            format,
+            [128.0, 64.0],
+        );
+        assert_eq!(prepared.draw_count(), 3);
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            renderer.render_in_pass(&prepared, &mut pass);
+        }
+        queue.submit([encoder.finish()]);
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+
+        // A standalone draw followed by more instances than the initial capacity:
+        // the buffer is sized up front instead of being replaced after the first draw.
+        let mut data = TextData::new();
+        for (font_size, content) in [
+            (200.0, "c".to_string()),
+            (16.0, "ab".repeat(INITIAL_INSTANCE_CAPACITY)),
+        ] {
+            data.append(text_element(
+                font_id,
+                font_size,
+                content,
+                [1.0f32, 1.0, 1.0, 1.0],
+            ));
+        }
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+        let before = uploads(&renderer);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let prepared = renderer.prepare(
+            &layout,
+            &font_storage,
+            &device,
+            &queue,
+            &mut encoder,
+            format,
+            [128.0, 64.0],
+        );
+        queue.submit([encoder.finish()]);
+        assert_eq!(prepared.draw_count(), 2);
+        let buffer = &prepared.draws[0].instance_buffer;
+        assert!(
+            prepared
+                .draws
+                .iter()
+                .all(|draw| &draw.instance_buffer == buffer)
+        );
+        // The standalone glyph's pixels and instance, and the other instances,
+        // all written through the queue.
+        let after = uploads(&renderer);
+        assert_eq!([after[0] - before[0], after[1] - before[1]], [3, 0]);
+    }
+
+    /// Renders a full block in `color` and returns the brightest red value of the target.
+    fn render_block(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        color_space: ColorSpace,
+        color: [f32; 4],
+        transform: euclid::Transform2D<f32, euclid::UnknownUnit, euclid::UnknownUnit>,
+    ) -> u8 {
+        let red = render_text(device, queue, format, "█", color, |renderer| {
+            renderer.set_color_space(color_space);
+            renderer.set_transform(transform);
+        });
+        red.into_iter().max().unwrap()
+    }
+
+    /// Renders `content` in `color` over black and returns the red values of the
+    /// target.
+    fn render_text(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        content: &str,
+        color: [f32; 4],
+        configure: impl FnOnce(&mut WgpuRenderer),
+    ) -> Vec<u8> {
+        let pixels = render_text_rgba(device, queue, format, content, color, configure);
+        pixels.into_iter().map(|[red, ..]| red).collect()
+    }
+
+    /// Like [`render_text`], but returns the whole pixels of the target.
+    fn render_text_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        content: &str,
+        color: [f32; 4],
+        configure: impl FnOnce(&mut WgpuRenderer),
+    ) -> Vec<[u8; 4]> {
+        render_text_with_rects(
+            device,
+            queue,
+            format,
+            content,
+            color,
+            LayoutRects::default(),
+            configure,
+        )
+    }
+
+    /// Like [`render_text_rgba`], but draws `rects` along with the text.
+    fn render_text_with_rects(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        content: &str,
+        color: [f32; 4],
+        rects: LayoutRects<'_>,
+        configure: impl FnOnce(&mut WgpuRenderer),
+    ) -> Vec<[u8; 4]> {
+        const SIZE: u32 = 64;
+
+        let (font_storage, font_id) = test_font_storage();
+
+        let data = TextData::builder(font_id, 32.0, color)
+            .push_str(content)
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let target = Target::new(device, format, SIZE, SIZE);
+        let mut renderer = test_renderer(device, format);
+        configure(&mut renderer);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        renderer
+            .render_with_rects_to(
+                &layout,
+                rects,
+                &font_storage,
+                device,
+                queue,
+                &mut SimpleRenderPass::new(&mut encoder, &target.view),
+            )
+            .unwrap();
+        target.read(device, queue, encoder)
+    }
+
+    /// Creates a renderer for `format` with a small atlas of two layers, as the
+    /// GL backend cannot sample a single-layer texture as an array.
+    fn test_renderer(device: &wgpu::Device, format: wgpu::TextureFormat) -> WgpuRenderer {
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(256).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(4).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        WgpuRenderer::new(device, &[config.clone(), config], &[format], None)
+    }
+
+    /// A render target of a four-byte format, read back through a buffer.
+    struct Target {
+        texture: wgpu::Texture,
+        view: wgpu::TextureView,
+        readback: wgpu::Buffer,
+    }
+
+    impl Target {
+        fn new(
+            device: &wgpu::Device,
+            format: wgpu::TextureFormat,
+            width: u32,
+            height: u32,
+        ) -> Self {
+            Self::with_view_format(device, format, format, width, height)
+        }
+
+        /// Creates a target of `format` whose view is of `view_format`.
+        fn with_view_format(
+            device: &wgpu::Device,
+            format: wgpu::TextureFormat,
+            view_format: wgpu::TextureFormat,
+            width: u32,
+            height: u32,
+        ) -> Self {
+            let view_formats = [view_format];
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: if view_format == format {
+                    &[]
+                } else {
+                    &view_formats
+                },
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                format: Some(view_format),
+                ..Default::default()
+            });
+            let readback = device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: (width * height * 4) as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            Self {
+                texture,
+                view,
+                readback,
+            }
+        }
+
+        /// Copies the target out after the commands of `encoder`, submits them
+        /// and returns the pixels row by row.
+        fn read(
+            &self,
+            device: &wgpu::Device,
+            queue: &wgpu::Queue,
+            mut encoder: wgpu::CommandEncoder,
+        ) -> Vec<[u8; 4]> {
+            encoder.copy_texture_to_buffer(
+                self.texture.as_image_copy(),
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &self.readback,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(self.texture.width() * 4),
+                        rows_per_image: None,
+                    },
+                },
+                self.texture.size(),
+            );
+            queue.submit([encoder.finish()]);
+
+            let slice = self.readback.slice(..);
+            slice.map_async(wgpu::MapMode::Read, |_| {});
+            device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+            let pixels = slice
+                .get_mapped_range()
+                .chunks_exact(4)
+                .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+                .collect();
+            self.readback.unmap();
+            pixels
+        }
+    }
+
+    #[test]
+    fn test_scissor_rect_covers_clip_within_target() {
+        let clip = |min: [f32; 2], max: [f32; 2]| Some(euclid::Box2D::new(min.into(), max.into()));
+        let target = [128.0, 64.0];
+        assert_eq!(scissor_rect(None, target), [0, 0, 128, 64]);
+        assert_eq!(
+            scissor_rect(clip([10.5, 4.2], [20.1, 30.0]), target),
+            [10, 4, 11, 26]
+        );
+        assert_eq!(
+            scissor_rect(clip([-10.0, 40.0], [200.0, 100.0]), target),
+            [0, 40, 128, 24]
+        );
+        assert_eq!(
+            scissor_rect(clip([300.0, 10.0], [400.0, 20.0]), target),
+            [128, 10, 0, 10]
+        );
+    }
+
+    #[test]
+    fn test_resolve_clips_splits_scissor_and_shader_clips() {
+        let rect = |min: [f32; 2], max: [f32; 2]| euclid::Box2D::new(min.into(), max.into());
+        let entry = |rect, transform| ClipEntry { rect, transform };
+        let identity = euclid::Transform2D::identity();
+        let rotation = euclid::Transform2D::rotation(euclid::Angle::degrees(30.0));
+        let other_rotation = euclid::Transform2D::rotation(euclid::Angle::degrees(60.0));
+        let outer = rect([0.0, 0.0], [50.0, 50.0]);
+        let inner = rect([10.0, 10.0], [100.0, 100.0]);
+        let both = rect([10.0, 10.0], [50.0, 50.0]);
+
+        assert_eq!(resolve_clips(&[]), (None, None));
+        // Axis-aligned rectangles intersect into the scissor.
+        assert_eq!(
+            resolve_clips(&[entry(outer, identity), entry(inner, identity)]),
+            (Some(both), None)
+        );
+        // Rotated ones under the same transform intersect in the shaders.
+        assert_eq!(
+            resolve_clips(&[entry(outer, rotation), entry(inner, rotation)]),
+            (None, Some(entry(both, rotation)))
+        );
+        // Under another transform, the outer one clips to its bounds.
+        assert_eq!(
+            resolve_clips(&[entry(outer, rotation), entry(inner, other_rotation)]),
+            (
+                Some(rotation.outer_transformed_box(&outer)),
+                Some(entry(inner, other_rotation))
+            )
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_clip_stack_clips_rendered_text() {
+        let (device, queue) = create_device();
+        let rect = |min: [f32; 2], max: [f32; 2]| euclid::Box2D::new(min.into(), max.into());
+        let render = |configure: &dyn Fn(&mut WgpuRenderer)| {
+            render_text_rgba(
+                &device,
+                &queue,
+                wgpu::TextureFormat::Rgba8Unorm,
+                "██",
+                [1.0; 4],
+                |renderer| configure(renderer),
+            )
+        };
+        // Returns the coordinates of the lit pixels of a 64x64 target.
+        let lit = |pixels: Vec<[u8; 4]>| -> Vec<(usize, usize)> {
+            (pixels.iter().enumerate())
+                .filter(|(_, pixel)| pixel[0] > 0)
+                .map(|(index, _)| (index % 64, index / 64))
+                .collect()
+        };
+        let unclipped = render(&|_| {});
+        let unclipped = lit(unclipped);
+        assert!(unclipped.iter().any(|&(x, y)| x >= 20 && y >= 10));
+
+        // Nested axis-aligned clips intersect through the scissor rectangle.
+        let clipped = lit(render(&|renderer| {
+            renderer.push_clip_rect(rect([0.0, 0.0], [20.0, 64.0]));
+            renderer.push_clip_rect(rect([0.0, 0.0], [64.0, 10.0]));
+            assert_eq!(renderer.clip_depth(), 2);
+        }));
+        assert!(!clipped.is_empty());
+        assert!(clipped.iter().all(|&(x, y)| x < 20 && y < 10));
+
+        // Popped clips no longer apply.
+        let popped = lit(render(&|renderer| {
+            let clip = rect([0.0, 0.0], [20.0, 64.0]);
+            renderer.push_clip_rect(clip);
+            assert_eq!(renderer.pop_clip_rect(), Some(clip));
+            assert_eq!(renderer.pop_clip_rect(), None);
+        }));
+        assert_eq!(popped, unclipped);
+
+        // Under a quarter turn, layout x becomes target y, clipped in the shaders.
+        let quarter_turn = euclid::Transform2D::rotation(euclid::Angle::degrees(90.0))
+            .then_translate(euclid::vec2(64.0, 0.0));
+        let rotated = lit(render(&|renderer| renderer.set_transform(quarter_turn)));
+        assert!(rotated.iter().any(|&(_, y)| y >= 17));
+        let clipped = lit(render(&|renderer| {
+            renderer.set_transform(quarter_turn);
+            renderer.push_clip_rect(rect([0.0, 0.0], [16.0, 64.0]));
+        }));
+        assert!(!clipped.is_empty());
+        assert!(clipped.iter().all(|&(_, y)| y < 17));
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_renders_sharing_an_encoder_keep_their_shader_clips() {
+        const SIZE: u32 = 64;
+
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let layout = TextData::builder(font_id, 32.0, [1.0f32; 4])
+            .push_str("██")
+            .build()
+            .layout(&TextLayoutConfig::default(), &font_storage);
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, SIZE, SIZE);
+        let mut renderer = test_renderer(&device, format);
+
+        // Rotated, so both clips are applied in the shaders: the first render
+        // keeps the left of the text, the second the right.
+        let transform = euclid::Transform2D::rotation(euclid::Angle::degrees(30.0))
+            .then_translate(euclid::vec2(16.0, 0.0));
+        renderer.set_transform(transform);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        for (index, [min_x, max_x]) in [[0.0, 10.0], [20.0, 64.0]].into_iter().enumerate() {
+            renderer.pop_clip_rect();
+            renderer.push_clip_rect(euclid::Box2D::new(
+                euclid::Point2D::new(min_x, 0.0),
+                euclid::Point2D::new(max_x, 64.0),
+            ));
+            let load = match index {
+                0 => wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                _ => wgpu::LoadOp::Load,
+            };
+            let mut pass = SimpleRenderPass::new(&mut encoder, &target.view).with_load_op(load);
+            renderer
+                .render_to(&layout, &font_storage, &device, &queue, &mut pass)
+                .unwrap();
+        }
+        let pixels = target.read(&device, &queue, encoder);
+
+        // Layout x of the centers of lit pixels.
+        let inverse = transform.inverse().unwrap();
+        let lit_x: Vec<f32> = (pixels.iter().enumerate())
+            .filter(|(_, pixel)| pixel[0] > 0)
+            .map(|(index, _)| {
+                let center = euclid::Point2D::new(
+                    (index as u32 % SIZE) as f32 + 0.5,
+                    (index as u32 / SIZE) as f32 + 0.5,
+                );
+                inverse.transform_point(center).x
+            })
+            .collect();
+        assert!(lit_x.iter().any(|&x| x < 9.0));
+        assert!(lit_x.iter().any(|&x| x > 21.0));
+        assert!(!lit_x.iter().any(|x| (11.0..=19.0).contains(x)));
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_transform_moves_rendered_text() {
+        let (device, queue) = create_device();
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let white = [1.0; 4];
+        let render = |transform| {
+            render_block(
+                &device,
+                &queue,
+                format,
+                ColorSpace::Target,
+                white,
+                transform,
+            )
+        };
+        let drawn = render(euclid::Transform2D::identity());
+        assert!(drawn > 0);
+
+        // Moved out of the target, and moved off the origin, which the block
+        // overhangs slightly, then rotated a half turn about it.
+        assert_eq!(render(euclid::Transform2D::translation(-200.0, 0.0)), 0);
+        assert_eq!(
+            render(
+                euclid::Transform2D::translation(4.0, 4.0)
+                    .then_rotate(euclid::Angle::radians(std::f32::consts::PI))
+            ),
+            0
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_render_transformed_keeps_renderer_transform() {
+        const SIZE: u32 = 64;
+
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let layout = TextData::builder(font_id, 32.0, [1.0f32; 4])
+            .push_str("█")
+            .build()
+            .layout(&TextLayoutConfig::default(), &font_storage);
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, SIZE, SIZE);
+        let mut renderer = test_renderer(&device, format);
+        let mut render = |transform| {
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            match transform {
+                Some(transform) => renderer.render_transformed(
+                    &layout,
+                    transform,
+                    &font_storage,
+                    &device,
+                    &queue,
+                    &mut encoder,
+                    &target.view,
+                ),
+                None => renderer.render(
+                    &layout,
+                    &font_storage,
+                    &device,
+                    &queue,
+                    &mut encoder,
+                    &target.view,
+                ),
+            };
+            let lit = (target.read(&device, &queue, encoder).iter())
+                .filter(|pixel| pixel[0] > 0)
+                .count();
+            (lit, renderer.transform())
+        };
+
+        let identity = euclid::Transform2D::identity();
+        let (drawn, _) = render(None);
+        assert!(drawn > 0);
+        assert_eq!(
+            render(Some(euclid::Transform2D::translation(-200.0, 0.0))),
+            (0, identity)
+        );
+        assert_eq!(render(None), (drawn, identity));
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_load_op_keeps_or_clears_target() {
+        const SIZE: u32 = 64;
+
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let layout = TextData::builder(font_id, 16.0, [1.0, 0.0, 0.0, 1.0])
+            .push_str("█")
+            .build()
+            .layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, SIZE, SIZE);
+        let mut renderer = test_renderer(&device, format);
+
+        // Draws the text over a blue target and returns the number of red and blue pixels.
+        let mut render = |load: Option<wgpu::LoadOp<wgpu::Color>>| {
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            SimpleRenderPass::new(&mut encoder, &target.view)
+                .with_clear_color(wgpu::Color::BLUE)
+                .create_pass()
+                .unwrap();
+            let mut pass = SimpleRenderPass::new(&mut encoder, &target.view);
+            if let Some(load) = load {
+                pass = pass.with_load_op(load);
+            }
+            renderer
+                .render_to(&layout, &font_storage, &device, &queue, &mut pass)
+                .unwrap();
+            let pixels = target.read(&device, &queue, encoder);
+            let count =
+                |channel: usize| pixels.iter().filter(|pixel| pixel[channel] == 255).count();
+            (count(0), count(2))
+        };
+
+        let (red, blue) = render(Some(wgpu::LoadOp::Load));
+        assert!(red > 0 && blue > 0);
+        let (red, blue) = render(Some(wgpu::LoadOp::Clear(wgpu::Color::GREEN)));
+        assert!(red > 0 && blue == 0);
+        // Cleared to black by default.
+        assert_eq!(render(None).1, 0);
+
+        // The store operation applies to the last pass only, so the passes of a
+        // render keep what the earlier ones drew.
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let mut pass =
+            SimpleRenderPass::new(&mut encoder, &target.view).with_store_op(wgpu::StoreOp::Discard);
+        let (first, _) = pass.next_operations(false);
+        let (middle, _) = pass.next_operations(false);
+        let (last, _) = pass.next_operations(true);
+        assert_eq!(first.load, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+        assert_eq!(middle.load, wgpu::LoadOp::Load);
+        assert_eq!(
+            [first.store, middle.store, last.store],
+            [
+                wgpu::StoreOp::Store,
+                wgpu::StoreOp::Store,
+                wgpu::StoreOp::Discard
+            ]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_depth_test_hides_text_behind_scene() {
+        const SIZE: u32 = 64;
+
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+
+        let data = TextData::builder(font_id, 32.0, [1.0f32; 4])
+            .push_str("█")
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, SIZE, SIZE);
+        let depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: target.texture.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth_view = depth.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut renderer = test_renderer(&device, format);
+        renderer.set_depth_stencil(Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }));
+        renderer.set_depth(0.5);
+
+        // Draws the text over a scene whose depth buffer is cleared to `scene_depth`.
+        let mut render = |scene_depth: f32| {
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            let prepared = renderer.prepare(
+                &layout,
+                &font_storage,
+                &device,
+                &queue,
+                &mut encoder,
+                format,
+                [SIZE as f32; 2],
+            );
+            {
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &depth_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(scene_depth),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                renderer.render_in_pass(&prepared, &mut pass);
+            }
+            target
+                .read(&device, &queue, encoder)
+                .into_iter()
+                .map(|pixel| pixel[0])
+                .max()
+        };
+
+        // In front of the far plane, behind geometry at depth 0.25.
+        assert!(render(1.0).is_some_and(|max| max > 0));
+        assert_eq!(render(0.25), Some(0));
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_layouts_of_one_render_are_drawn_at_their_depths() {
+        const SIZE: u32 = 64;
+
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let block = |color: [f32; 4]| {
+            TextData::builder(font_id, 32.0, color)
+                .push_str("█")
+                .build()
+                .layout(&TextLayoutConfig::default(), &font_storage)
+        };
+        let (red, green) = (block([1.0, 0.0, 0.0, 1.0]), block([0.0, 1.0, 0.0, 1.0]));
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, SIZE, SIZE);
+        let depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: target.texture.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth_view = depth.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut renderer = test_renderer(&device, format);
+        renderer.set_depth_stencil(Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }));
+        renderer.set_depth(0.5);
+
+        // The green block is drawn after the red one, but in front of it.
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let mut pass = SimpleRenderPass::new(&mut encoder, &target.view)
+            .with_depth_stencil_attachment(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            });
+        renderer
+            .render_all(
+                &[
+                    (LayoutPlacement::default().depth(0.75), &red),
+                    (LayoutPlacement::default().depth(0.25), &green),
+                ],
+                &font_storage,
+                &device,
+                &queue,
+                &mut pass,
+            )
+            .unwrap();
+        let pixels = target.read(&device, &queue, encoder);
+        let lit: Vec<_> = pixels
+            .iter()
+            .filter(|&&[r, g, ..]| r > 0 || g > 0)
+            .collect();
+        assert!(!lit.is_empty());
+        // Red only shows through the antialiased edges of the green block.
+        assert!(lit.iter().all(|&&[r, g, ..]| g > r));
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_atlas_debug_draws_pages_with_borders() {
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+
+        let mut data = TextData::new();
+        data.append(text_element(font_id, 24.0, "ab", [1.0f32; 4]));
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let width = 128;
+        let target = Target::new(&device, format, width, 64);
+
+        // Two pages of one tile each, both holding a glyph.
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(1).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let mut renderer = WgpuRenderer::new(&device, &[config.clone(), config], &[format], None);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        renderer.render(
+            &layout,
+            &font_storage,
+            &device,
+            &queue,
+            &mut encoder,
+            &target.view,
+        );
+        renderer.render_atlas_debug(&device, &queue, &mut encoder, &target.view);
+        let pixels = target.read(&device, &queue, encoder);
+        let pixel = |x: u32, y: u32| {
+            let [r, g, b, _] = pixels[(y * width + x) as usize];
+            [r, g, b]
+        };
+
+        // Pages are 60 pixels wide cells of 64, framed in gray, with the
+        // glyphs inside and a partly filled occupancy bar at the bottom.
+        for (x, y) in [(0, 0), (59, 30), (64, 0), (123, 30)] {
+            assert!(pixel(x, y).iter().all(|&c| c.abs_diff(128) <= 2));
+        }
+        for page_x in [0, 64] {
+            let glyph = (1..55).any(|y| (1..59).any(|x| pixel(page_x + x, y)[0] > 200));
+            assert!(glyph);
+            let bar = pixel(page_x + 1, 57);
+            assert!(bar[1] > 0 && bar[2] == 0);
+        }
+        assert_eq!(pixel(62, 30), [0, 0, 0]);
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_color_space_conversion() {
+        let (device, queue) = create_device();
+        let gray = [0.5, 0.5, 0.5, 1.0];
+        let render = |format, color_space| {
+            let identity = euclid::Transform2D::identity();
+            render_block(&device, &queue, format, color_space, gray, identity)
+        };
+        let unchanged = render(wgpu::TextureFormat::Rgba8Unorm, ColorSpace::Target);
+        let near = |value: u8, expected: u8| value.abs_diff(expected) <= 2;
+
+        // 0.5 is written as-is, or as sRGB-encoded linear 0.5 (~188).
+        assert!(near(unchanged, 128));
+        assert!(near(
+            render(wgpu::TextureFormat::Rgba8Unorm, ColorSpace::Srgb),
+            128
+        ));
+        assert!(near(
+            render(wgpu::TextureFormat::Rgba8Unorm, ColorSpace::Linear),
+            188
+        ));
+        assert!(near(
+            render(wgpu::TextureFormat::Rgba8UnormSrgb, ColorSpace::Target),
+            188
+        ));
+        assert!(near(
+            render(wgpu::TextureFormat::Rgba8UnormSrgb, ColorSpace::Linear),
+            188
+        ));
+        assert!(near(
+            render(wgpu::TextureFormat::Rgba8UnormSrgb, ColorSpace::Srgb),
+            128
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_linear_blend_space_brightens_edges_of_light_text() {
+        let (device, queue) = create_device();
+        let render = |format, blend_space| {
+            render_text(&device, &queue, format, "ao", [1.0; 4], |renderer| {
+                renderer.set_blend_space(blend_space)
+            })
+        };
+        let target = render(wgpu::TextureFormat::Rgba8Unorm, BlendSpace::Target);
+
+        // White on black: covered pixels stay, partially covered ones brighten.
+        let linear = render(wgpu::TextureFormat::Rgba8Unorm, BlendSpace::Linear);
+        let mut brightened = 0;
+        for (&target, &linear) in target.iter().zip(&linear) {
+            assert!(linear >= target);
+            assert!(target < 255 || linear == 255);
+            brightened += usize::from(linear > target);
+        }
+        assert!(brightened > 0);
+
+        // `*Srgb` targets already blend in linear space.
+        assert_eq!(
+            render(wgpu::TextureFormat::Rgba8UnormSrgb, BlendSpace::Target),
+            render(wgpu::TextureFormat::Rgba8UnormSrgb, BlendSpace::Linear)
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_shadow_is_drawn_offset_and_blurred_behind_text() {
+        let (device, queue) = create_device();
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        // White text over a blue shadow; returns the shadow pixels not covered by
+        // text and how many of them are partially covered.
+        let render = |shadow| {
+            let pixels = render_text_rgba(&device, &queue, format, "█", [1.0; 4], |renderer| {
+                renderer.set_shadow(shadow)
+            });
+            let shadow: Vec<u8> = pixels
+                .iter()
+                .filter(|&&[r, _, b, _]| b > r)
+                .map(|&[_, _, b, _]| b)
+                .collect();
+            let partial = shadow.iter().filter(|&&b| b < 250).count();
+            (shadow.len(), partial)
+        };
+        let none = render(None);
+        assert_eq!(none, (0, 0));
+
+        let shadow = |blur_radius| TextShadow {
+            offset: [6.0, 6.0],
+            blur_radius,
+            color: [0.0, 0.0, 1.0, 1.0],
+        };
+        let (hard, _) = render(Some(shadow(0.0)));
+        assert!(hard > 0);
+        let (blurred, partial) = render(Some(shadow(4.0)));
+        assert!(blurred > hard);
+        assert!(partial > 0);
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_pixel_snapping_draws_texels_onto_whole_pixels() {
+        let (device, queue) = create_device();
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        // Returns the sorted values of a glyph drawn moved by `offset` pixels.
+        let render = |snap, offset| {
+            let mut red = render_text(&device, &queue, format, "a", [1.0; 4], |renderer| {
+                renderer.set_pixel_snapping(snap);
+                assert_eq!(renderer.pixel_snapping(), snap);
+                renderer.set_transform(euclid::Transform2D::translation(offset, offset));
+            });
+            red.sort_unstable();
+            red
+        };
+        let snapped = render(true, 0.0);
+        assert!(snapped.iter().any(|&value| value > 0));
+
+        // Snapped glyphs copy their texels whatever the fractional offset,
+        // while others are resampled.
+        assert_eq!(render(true, 0.3), snapped);
+        assert_ne!(render(false, 0.3), render(false, 0.0));
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_nearest_sampler_keeps_magnified_glyphs_crisp() {
+        let (device, queue) = create_device();
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        // Draws a glyph at four times its size and returns the distinct values
+        // of the target.
+        let render = |config| {
+            let red = render_text(&device, &queue, format, "a", [1.0; 4], |renderer| {
+                renderer.set_sampler(&device, config);
+                assert_eq!(renderer.sampler(), config);
+                renderer.set_transform(euclid::Transform2D::scale(4.0, 4.0));
+            });
+            let mut values: Vec<u8> = red.into_iter().collect();
+            values.sort_unstable();
+            values.dedup();
+            values.len()
+        };
+        let linear = render(SamplerConfig::default());
+        let nearest = render(SamplerConfig {
+            filter: wgpu::FilterMode::Nearest,
+            ..SamplerConfig::default()
+        });
+        // Magnified texels are copied, not blended with their neighbours.
+        assert!(nearest > 1);
+        assert!(nearest < linear);
+
+        // Anisotropy is ignored with nearest filtering instead of failing validation.
+        let anisotropic = render(SamplerConfig {
+            filter: wgpu::FilterMode::Nearest,
+            anisotropy_clamp: 16,
+        });
+        assert_eq!(anisotropic, nearest);
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_custom_shader_hooks_fragments_and_reports_invalid_shaders() {
+        let (device, queue) = create_device();
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(256).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(4).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let configs = [config.clone(), config];
+
+        // A hook swapping red and green draws red text green.
+        let hook = CustomShader::FragmentHook(
+            "fn fragment_hook(in: VertexOutput, color: vec4<f32>) -> vec4<f32> {
+                return color.grba;
+            }"
+            .to_string(),
+        );
+        let pixels = render_text_rgba(
+            &device,
+            &queue,
+            format,
+            "█",
+            [1.0, 0.0, 0.0, 1.0],
+            |renderer| {
+                *renderer =
+                    WgpuRenderer::with_shader(&device, &configs, &[format], None, &hook).unwrap();
+            },
+        );
+        assert!(pixels.iter().any(|pixel| pixel[1] == 255));
+        assert!(pixels.iter().all(|pixel| pixel[0] == 0));
+
+        // Shaders that do not compile, or do not match the bindings, are reported.
+        let broken = CustomShader::FragmentHook("fn fragment_hook(".to_string());
+        assert!(WgpuRenderer::with_shader(&device, &configs, &[], None, &broken).is_err());
+        let mismatched = CustomShader::Replace {
+            atlas: format!("{SHADER}\n{FRAGMENT_HOOK}")
+                .replace("texture_2d_array<f32>", "texture_2d<f32>")
+                .replace(", i32(layer), 0.0)", ", 0.0)"),
+            standalone: format!("{STANDALONE_SHADER}\n{FRAGMENT_HOOK}"),
+        };
+        assert!(WgpuRenderer::with_shader(&device, &configs, &[], None, &mismatched).is_err());
+        let built_in = CustomShader::Replace {
+            atlas: format!("{SHADER}\n{FRAGMENT_HOOK}"),
+            standalone: format!("{STANDALONE_SHADER}\n{FRAGMENT_HOOK}"),
+        };
+        assert!(WgpuRenderer::with_shader(&device, &configs, &[], None, &built_in).is_ok());
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_rects_behind_are_drawn_under_shadow_and_text() {
+        let (device, queue) = create_device();
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let background = FilledRect {
+            rect: euclid::Box2D::new(
+                euclid::Point2D::new(0.0, 0.0),
+                euclid::Point2D::new(64.0, 64.0),
+            ),
+            color: [0.0, 1.0, 0.0, 1.0],
+        };
+        let pixels = render_text_with_rects(
+            &device,
+            &queue,
+            format,
+            "█",
+            [1.0; 4],
+            LayoutRects {
+                behind: &[background],
+                in_front: &[],
+            },
+            |renderer| {
+                renderer.set_shadow(Some(TextShadow {
+                    offset: [6.0, 6.0],
+                    blur_radius: 0.0,
+                    color: [0.0, 0.0, 1.0, 1.0],
+                }))
+            },
+        );
+
+        // The whole target is covered, by the background or the text and its
+        // shadow drawn over it.
+        assert!(pixels.iter().all(|&[r, g, b, _]| r > 0 || g > 0 || b > 0));
+        assert!(pixels.contains(&[255, 255, 255, 255]));
+        assert!(pixels.contains(&[0, 0, 255, 255]));
+        assert!(pixels.contains(&[0, 255, 0, 255]));
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_rects_in_front_share_the_last_batch() {
+        let (device, queue) = create_device();
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let cover = FilledRect {
+            rect: euclid::Box2D::new(
+                euclid::Point2D::new(0.0, 0.0),
+                euclid::Point2D::new(64.0, 64.0),
+            ),
+            color: [1.0, 0.0, 0.0, 1.0],
+        };
+        let rects = LayoutRects {
+            behind: &[],
+            in_front: std::slice::from_ref(&cover),
+        };
+        let pixels = render_text_with_rects(&device, &queue, format, "█", [1.0; 4], rects, |_| {});
+        // The rectangle is drawn over the white text.
+        assert!(pixels.iter().all(|&pixel| pixel == [255, 0, 0, 255]));
+
+        let (font_storage, font_id) = test_font_storage();
+        let data = TextData::builder(font_id, 16.0, TextStyle::default().underline())
+            .push_str("under")
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+        let decorations = text_decorations(&layout, &font_storage);
+        assert_eq!(decorations.len(), 1);
+
+        let mut renderer = test_renderer(&device, format);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let mut prepare = |rects| {
+            renderer
+                .prepare_with_rects(
+                    &layout,
+                    rects,
+                    &font_storage,
+                    &device,
+                    &queue,
+                    &mut encoder,
+                    format,
+                    [128.0, 64.0],
+                )
+                .draw_count()
+        };
+        assert_eq!(prepare(LayoutRects::default()), 1);
+        let in_front = LayoutRects {
+            behind: &[],
+            in_front: &decorations,
+        };
+        assert_eq!(prepare(in_front), 1);
+        let both = LayoutRects {
+            behind: &decorations,
+            in_front: &decorations,
+        };
+        assert_eq!(prepare(both), 2);
+    }
+
+    /// Counts the passes created through a [`SimpleRenderPass`].
+    struct CountingPass<'a> {
+        inner: SimpleRenderPass<'a>,
+        passes: usize,
+    }
+
+    impl WgpuRenderPassController for CountingPass<'_> {
+        fn encoder(&mut self) -> Result<&mut wgpu::CommandEncoder, ()> {
+            self.inner.encoder()
+        }
+
+        fn create_pass(&mut self) -> Result<wgpu::RenderPass<'_>, ()> {
+            self.passes += 1;
+            self.inner.create_pass()
+        }
+
+        fn format(&self) -> Result<wgpu::TextureFormat, ()> {
+            self.inner.format()
+        }
+
+        fn target_size(&self) -> Result<[f32; 2], ()> {
+            self.inner.target_size()
+        }
+
+        fn copies_before_passes(&self) -> bool {
+            self.inner.copies_before_passes()
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_copies_before_passes_draw_in_one_pass() {
+        const WIDTH: u32 = 128;
+        const HEIGHT: u32 = 32;
+
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let data = TextData::builder(font_id, 16.0, [1.0f32; 4])
+            .push_str("abcdefghijklm")
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, WIDTH, HEIGHT);
+        // Eight slots for thirteen glyphs.
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(32).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(2).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+
+        // Returns the passes created and the red values of the target.
+        let render = |copies_before_passes: bool| {
+            let mut renderer =
+                WgpuRenderer::new(&device, &[config.clone(), config.clone()], &[format], None);
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            let mut controller = CountingPass {
+                inner: SimpleRenderPass::new(&mut encoder, &target.view)
+                    .with_copies_before_passes(copies_before_passes),
+                passes: 0,
+            };
+            renderer
+                .render_to(&layout, &font_storage, &device, &queue, &mut controller)
+                .unwrap();
+            let passes = controller.passes;
+            let red: Vec<u8> = target
+                .read(&device, &queue, encoder)
+                .into_iter()
+                .map(|p| p[0])
+                .collect();
+            (passes, red)
+        };
+
+        // Evicting glyphs for later batches takes a pass per batch.
+        let (interleaved_passes, interleaved) = render(false);
+        assert!(interleaved_passes > 1);
+        assert!(interleaved.iter().any(|&value| value > 0));
+
+        // With all copies first, glyphs that do not fit are drawn standalone
+        // in the same pass. Their textures clamp at the edges instead of being
+        // padded, so edges differ slightly; every glyph is drawn in its place.
+        let (passes, red) = render(true);
+        assert_eq!(passes, 1);
+        let inked_columns = |red: &[u8]| -> Vec<bool> {
+            (0..WIDTH)
+                .map(|x| (0..HEIGHT).any(|y| red[(y * WIDTH + x) as usize] > 0))
+                .collect()
+        };
+        assert_eq!(inked_columns(&red), inked_columns(&interleaved));
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_multiview_draws_instances_to_their_views() {
+        const SIZE: u32 = 64;
+
+        let Some((device, queue)) = create_device_with_features(wgpu::Features::MULTIVIEW) else {
+            // No adapter with multiview available.
+            return;
+        };
+        let (font_storage, font_id) = test_font_storage();
+        let data = TextData::builder(font_id, 32.0, [1.0f32; 4])
+            .push_str("█")
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: SIZE,
+                height: SIZE,
+                depth_or_array_layers: 2,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (SIZE * SIZE * 4 * 2) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut renderer = test_renderer(&device, format);
+        assert!(renderer.set_multiview(&device, NonZeroU32::new(2)));
+        renderer.set_shadow(Some(TextShadow {
+            offset: [1.0, 1.0],
+            blur_radius: 0.0,
+            color: [0.0, 0.0, 0.0, 0.5],
+        }));
+
+        // Returns whether each view holds any ink.
+        let mut render = |view_mask| {
+            renderer.set_view_mask(view_mask);
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            renderer
+                .render_to(
+                    &layout,
+                    &font_storage,
+                    &device,
+                    &queue,
+                    &mut SimpleRenderPass::new(&mut encoder, &view),
+                )
+                .unwrap();
+            encoder.copy_texture_to_buffer(
+                target.as_image_copy(),
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &readback,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(SIZE * 4),
+                        rows_per_image: Some(SIZE),
+                    },
+                },
+                target.size(),
+            );
+            queue.submit([encoder.finish()]);
+
+            let slice = readback.slice(..);
+            slice.map_async(wgpu::MapMode::Read, |_| {});
+            device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+            let inked: Vec<bool> = slice
+                .get_mapped_range()
+                .chunks_exact((SIZE * SIZE * 4) as usize)
+                .map(|layer| layer.chunks_exact(4).any(|pixel| pixel[0] > 0))
+                .collect();
+            readback.unmap();
+            inked
         };
 
-        if recreate {
-            let current_size = resources_ref
-                .as_ref()
-                .map(|r| r.size)
-                .unwrap_or(wgpu::Extent3d {
-                    width: 0,
-                    height: 0,
-                    depth_or_array_layers: 1,
-                });
-            let new_width = current_size.width.max(needed_width);
-            let new_height = current_size.height.max(needed_height);
+        assert_eq!(render(u32::MAX), [true, true]);
+        assert_eq!(render(0b01), [true, false]);
+        assert_eq!(render(0b10), [false, true]);
 
-            let size = wgpu::Extent3d {
-                width: new_width.next_power_of_two(),
-                height: new_height.next_power_of_two(),
+        renderer.set_multiview(&device, None);
+        assert_eq!(renderer.multiview(), None);
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_render_stats_count_one_render() {
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let data = TextData::builder(font_id, 32.0, [1.0f32; 4])
+            .push_str("██")
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 64,
+                height: 64,
                 depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let renderer_with_tiles = |tile_size: usize| {
+            let config = GpuCacheConfig {
+                texture_size: NonZeroUsize::new(256).unwrap(),
+                tile_size: NonZeroUsize::new(tile_size).unwrap(),
+                tiles_per_axis: NonZeroUsize::new(256 / tile_size).unwrap(),
+                priority: AtlasPriority::Normal,
+                padding: 1,
+                extrude_edges: false,
+                packing: AtlasPacking::Grid,
             };
+            WgpuRenderer::new(&device, &[config.clone(), config], &[format], None)
+        };
+        let render = |renderer: &mut WgpuRenderer| {
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            let stats = renderer
+                .render_to(
+                    &layout,
+                    &font_storage,
+                    &device,
+                    &queue,
+                    &mut SimpleRenderPass::new(&mut encoder, &view),
+                )
+                .unwrap();
+            queue.submit([encoder.finish()]);
+            stats
+        };
 
-            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("Standalone Glyph Texture"),
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::R8Unorm,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
+        // The first render uploads the glyph, the next one draws it from the atlas.
+        let mut renderer = renderer_with_tiles(64);
+        let first = render(&mut renderer);
+        assert_eq!(first.instances, 2);
+        assert_eq!(first.draw_calls, 1);
+        assert!(first.atlas_bytes_uploaded > 0);
+        assert_eq!(first.standalone, 0);
+        let second = render(&mut renderer);
+        assert_eq!(
+            second,
+            RenderStats {
+                atlas_bytes_uploaded: 0,
+                buffer_reallocations: 0,
+                ..first
+            }
+        );
 
-            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Shadows take a draw call of their own.
+        renderer.set_shadow(Some(TextShadow {
+            offset: [1.0, 1.0],
+            blur_radius: 0.0,
+            color: [0.0, 0.0, 0.0, 1.0],
+        }));
+        assert_eq!(render(&mut renderer).draw_calls, 2);
 
-            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Standalone Bind Group"),
-                layout: &self.standalone_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: self.globals_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&self.sampler),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::TextureView(&view),
-                    },
-                ],
-            });
+        // Glyphs larger than the tiles are drawn on their own.
+        let mut renderer = renderer_with_tiles(16);
+        let stats = render(&mut renderer);
+        assert_eq!(stats.standalone, 2);
+        assert_eq!(stats.instances, 2);
+        assert_eq!(stats.draw_calls, 2);
+        assert_eq!(stats.atlas_bytes_uploaded, 0);
+    }
 
-            *resources_ref = Some(StandaloneResources {
-                texture,
-                bind_group,
-                size,
-            });
-        }
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_recreate_restores_atlas_from_host_copy() {
+        const SIZE: u32 = 64;
 
-        resources_ref
+        let (device, queue) = create_device();
+        let (new_device, new_queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let data = TextData::builder(font_id, 32.0, [1.0f32; 4])
+            .push_str("██")
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let render = |renderer: &mut WgpuRenderer, device: &wgpu::Device, queue: &wgpu::Queue| {
+            let target = Target::new(device, format, SIZE, SIZE);
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            let stats = renderer
+                .render_to(
+                    &layout,
+                    &font_storage,
+                    device,
+                    queue,
+                    &mut SimpleRenderPass::new(&mut encoder, &target.view),
+                )
+                .unwrap();
+            (stats, target.read(device, queue, encoder))
+        };
+
+        let mut renderer = test_renderer(&device, format);
+        assert!(renderer.atlas_host_copy());
+        let (stats, before) = render(&mut renderer, &device, &queue);
+        assert!(stats.atlas_bytes_uploaded > 0);
+        assert!(before.iter().any(|pixel| pixel[0] == 255));
+
+        // The cached glyph moves to the new device without being uploaded again.
+        assert!(renderer.recreate(&new_device, &new_queue, None));
+        let (stats, after) = render(&mut renderer, &new_device, &new_queue);
+        assert_eq!(stats.atlas_bytes_uploaded, 0);
+        assert_eq!(after, before);
+
+        // A copy enabled again is filled from the glyphs already cached.
+        renderer.set_atlas_host_copy(false, &font_storage);
+        renderer.set_atlas_host_copy(true, &font_storage);
+        let (stats, _) = render(&mut renderer, &new_device, &new_queue);
+        assert_eq!(stats.atlas_bytes_uploaded, 0);
+        assert!(renderer.recreate(&device, &queue, None));
+        let (stats, after) = render(&mut renderer, &device, &queue);
+        assert_eq!(stats.atlas_bytes_uploaded, 0);
+        assert_eq!(after, before);
+
+        // Without the copy, the glyph is cached again.
+        renderer.set_atlas_host_copy(false, &font_storage);
+        assert!(!renderer.recreate(&device, &queue, None));
+        let (stats, after) = render(&mut renderer, &device, &queue);
+        assert!(stats.atlas_bytes_uploaded > 0);
+        assert_eq!(after, before);
     }
 
-    /// Prepares pixel data for texture upload, handling WGPU's alignment requirements.
-    ///
-    /// WGPU (and underlying APIs like Vulkan/DirectX) requires that the "bytes per row" in a copy command
-    /// be a multiple of **256 bytes**. If the image width doesn't match this alignment, we must
-    /// copy the data into a new buffer with padding bytes added to the end of each row.
-    ///
-    /// - `pixel_staging`: A reusable vector to avoid allocation when padding is needed.
-    fn prepare_padded_data<'a>(
-        pixel_staging: &'a mut Vec<u8>,
-        pixels: &'a [u8],
-        width: u32,
-        height: u32,
-    ) -> (std::borrow::Cow<'a, [u8]>, u32) {
-        let bytes_per_row = width;
-        // Align to 256 bytes: (val + 255) & !255 checks the next multiple of 256.
-        let padded_bytes_per_row = (bytes_per_row + 255) & !255;
-        let padding = padded_bytes_per_row - bytes_per_row;
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_compaction_copies_glyphs_between_layers() {
+        const SIZE: u32 = 64;
 
-        let data = if padding == 0 {
-            // No padding needed, use original data directly (zero-copy).
-            std::borrow::Cow::Borrowed(pixels)
-        } else {
-            // Padding needed, reuse staging buffer.
-            pixel_staging.clear();
-            pixel_staging.reserve((padded_bytes_per_row * height) as usize);
+        let (device, queue) = create_device();
+        let (mut font_storage, font_id) = test_font_storage();
+        // A second copy of the font, whose glyph fills the small page and is dropped.
+        let copy_id = load_test_font(&mut font_storage);
 
-            for row in 0..height {
-                let src_start = (row * width) as usize;
-                let src_end = src_start + width as usize;
-                if src_end <= pixels.len() {
-                    pixel_staging.extend_from_slice(&pixels[src_start..src_end]);
-                    // Append zeros for alignment
-                    pixel_staging.extend(std::iter::repeat_n(0, padding as usize));
-                }
-            }
-            std::borrow::Cow::Borrowed(pixel_staging.as_slice())
+        let small = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(1).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let large = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(256).unwrap(),
+            tile_size: NonZeroUsize::new(128).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(2).unwrap(),
+            ..small.clone()
+        };
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let mut renderer = WgpuRenderer::new(&device, &[small, large], &[format], None);
+        let render = |renderer: &mut WgpuRenderer, data: &TextData<[f32; 4]>| {
+            let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+            let target = Target::new(&device, format, SIZE, SIZE);
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            let stats = renderer
+                .render_to(
+                    &layout,
+                    &font_storage,
+                    &device,
+                    &queue,
+                    &mut SimpleRenderPass::new(&mut encoder, &target.view),
+                )
+                .unwrap();
+            (stats, target.read(&device, &queue, encoder))
         };
 
-        (data, padded_bytes_per_row)
+        // A glyph of the copy takes the small page, so the block spills to the large one.
+        let both = TextData::builder(copy_id, 32.0, [1.0f32; 4])
+            .push_str("A")
+            .font(font_id)
+            .push_str("█")
+            .build();
+        render(&mut renderer, &both);
+        let single = TextData::builder(font_id, 32.0, [1.0f32; 4])
+            .push_str("█")
+            .build();
+        let (_, before) = render(&mut renderer, &single);
+        assert!(before.iter().any(|pixel| pixel[0] == 255));
+
+        // The glyph moves to the freed small page by a copy, without an upload.
+        renderer.forget_font(copy_id);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        assert_eq!(renderer.compact_cache(usize::MAX, &mut encoder), 1);
+        queue.submit([encoder.finish()]);
+        let (stats, after) = render(&mut renderer, &single);
+        assert_eq!(stats.atlas_bytes_uploaded, 0);
+        assert_eq!(after, before);
     }
 
-    fn update_atlas(
-        &self,
-        device: &wgpu::Device,
-        encoder: &mut wgpu::CommandEncoder,
-        updates: &[AtlasUpdate],
-    ) {
-        let mut pixel_staging = self.pixel_staging.borrow_mut();
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_indirect_draws_match_direct_draws() {
+        const SIZE: u32 = 64;
 
-        for update in updates {
-            let width = update.width as u32;
-            let height = update.height as u32;
+        let (device, _) = create_device();
+        if !device
+            .features()
+            .contains(wgpu::Features::INDIRECT_FIRST_INSTANCE)
+        {
+            let mut renderer = test_renderer(&device, wgpu::TextureFormat::Rgba8Unorm);
+            assert!(!renderer.set_indirect_draws(&device, true));
+            assert!(!renderer.indirect_draws());
+        }
 
-            if width == 0 || height == 0 {
-                continue;
-            }
+        let Some((device, queue)) =
+            create_device_with_features(wgpu::Features::INDIRECT_FIRST_INSTANCE)
+        else {
+            // No adapter with indirect first instance available.
+            return;
+        };
+        let (font_storage, font_id) = test_font_storage();
+        let data = TextData::builder(font_id, 16.0, [1.0f32, 0.5, 0.0, 1.0])
+            .push_str("█ █")
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+        let layouts = [
+            (LayoutPlacement::at([0.0, 0.0]), &layout),
+            (LayoutPlacement::at([8.0, 24.0]), &layout),
+        ];
 
-            let (data, padded_bytes_per_row) =
-                Self::prepare_padded_data(&mut pixel_staging, &update.pixels, width, height);
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let render = |indirect: bool| {
+            let mut renderer = test_renderer(&device, format);
+            assert_eq!(renderer.set_indirect_draws(&device, indirect), indirect);
+            renderer.set_shadow(Some(TextShadow {
+                offset: [2.0, 2.0],
+                blur_radius: 0.0,
+                color: [0.0, 0.0, 1.0, 1.0],
+            }));
 
-            let staging_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Atlas Staging Buffer"),
-                contents: &data,
-                usage: wgpu::BufferUsages::COPY_SRC,
-            });
+            let target = Target::new(&device, format, SIZE, SIZE);
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            renderer
+                .render_all(
+                    &layouts,
+                    &font_storage,
+                    &device,
+                    &queue,
+                    &mut SimpleRenderPass::new(&mut encoder, &target.view)
+                        .with_copies_before_passes(true),
+                )
+                .unwrap();
+            target.read(&device, &queue, encoder)
+        };
 
-            encoder.copy_buffer_to_texture(
+        let direct = render(false);
+        // Both glyphs and their shadows are drawn.
+        assert!(direct.iter().any(|pixel| pixel[0] == 255));
+        assert!(direct.iter().any(|pixel| pixel[2] == 255));
+        assert_eq!(render(true), direct);
+    }
+
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_hdr_targets_write_linear_scaled_colors() {
+        const SIZE: u32 = 64;
+
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        // Mid gray in sRGB, about 0.214 in linear.
+        let data = TextData::builder(font_id, 32.0, [0.5f32, 0.5, 0.5, 1.0])
+            .push_str("█")
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+
+        let formats = [
+            wgpu::TextureFormat::Rgba16Float,
+            wgpu::TextureFormat::Rgb10a2Unorm,
+        ];
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(256).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(4).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
+        };
+        let mut renderer = WgpuRenderer::new(&device, &[config.clone(), config], &formats, None);
+        assert!(
+            formats
+                .iter()
+                .all(|format| renderer.resources.pipelines.borrow().contains_key(format))
+        );
+        renderer.set_color_space(ColorSpace::Srgb);
+
+        // Returns the largest red channel of the target.
+        let render = |renderer: &mut WgpuRenderer, format: wgpu::TextureFormat| {
+            let bytes_per_pixel = format.block_copy_size(None).unwrap();
+            let target = device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: SIZE,
+                    height: SIZE,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+            let readback = device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: u64::from(SIZE * SIZE * bytes_per_pixel),
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            renderer
+                .render_to(
+                    &layout,
+                    &font_storage,
+                    &device,
+                    &queue,
+                    &mut SimpleRenderPass::new(&mut encoder, &view),
+                )
+                .unwrap();
+            encoder.copy_texture_to_buffer(
+                target.as_image_copy(),
                 wgpu::TexelCopyBufferInfo {
-                    buffer: &staging_buffer,
+                    buffer: &readback,
                     layout: wgpu::TexelCopyBufferLayout {
                         offset: 0,
-                        bytes_per_row: Some(padded_bytes_per_row),
-                        rows_per_image: Some(height),
-                    },
-                },
-                wgpu::TexelCopyTextureInfo {
-                    texture: &self.atlas_texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d {
-                        x: update.x as u32,
-                        y: update.y as u32,
-                        z: update.texture_index as u32,
+                        bytes_per_row: Some(SIZE * bytes_per_pixel),
+                        rows_per_image: None,
                     },
-                    aspect: wgpu::TextureAspect::All,
-                },
-                wgpu::Extent3d {
-                    width,
-                    height,
-                    depth_or_array_layers: 1,
                 },
+                target.size(),
             );
-        }
+            queue.submit([encoder.finish()]);
+            let slice = readback.slice(..);
+            slice.map_async(wgpu::MapMode::Read, |_| {});
+            device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+            let pixels = slice.get_mapped_range();
+            pixels
+                .chunks_exact(bytes_per_pixel as usize)
+                .map(|pixel| match format {
+                    wgpu::TextureFormat::Rgba16Float => {
+                        // Positive normal half floats are enough here.
+                        let bits = u16::from_le_bytes([pixel[0], pixel[1]]);
+                        let exponent = i32::from((bits >> 10) & 0x1f);
+                        let mantissa = f32::from(bits & 0x3ff) / 1024.0;
+                        match exponent {
+                            0 => 0.0,
+                            _ => (1.0 + mantissa) * 2f32.powi(exponent - 15),
+                        }
+                    }
+                    _ => {
+                        let packed = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                        (packed & 0x3ff) as f32 / 1023.0
+                    }
+                })
+                .fold(0.0, f32::max)
+        };
+
+        // Float targets get linear colors, other targets keep sRGB ones.
+        let linear = render(&mut renderer, wgpu::TextureFormat::Rgba16Float);
+        assert!((linear - 0.214).abs() < 0.01, "{linear}");
+        let encoded = render(&mut renderer, wgpu::TextureFormat::Rgb10a2Unorm);
+        assert!((encoded - 0.5).abs() < 0.01, "{encoded}");
+
+        // scRGB output scales linear colors to the white level, only on float targets.
+        renderer.set_hdr_output(HdrOutput::ScRgb { white_nits: 240.0 });
+        let scaled = render(&mut renderer, wgpu::TextureFormat::Rgba16Float);
+        assert!((scaled - 3.0 * linear).abs() < 0.01, "{scaled}");
+        assert_eq!(
+            render(&mut renderer, wgpu::TextureFormat::Rgb10a2Unorm),
+            encoded
+        );
     }
 
-    fn draw_instances<T: Into<[f32; 4]> + Copy, E>(
-        &self,
-        device: &wgpu::Device,
-        controller: &mut impl WgpuRenderPassController<E>,
-        current_offset: &std::cell::Cell<u64>,
-        instances: &[GlyphInstance<T>],
-    ) -> Result<(), E> {
-        if instances.is_empty() {
-            return Ok(());
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_view_format_selects_pipeline() {
+        const SIZE: u32 = 64;
+
+        let adapter = request_adapter();
+        if !adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::VIEW_FORMATS)
+        {
+            // No adapter with view formats available.
+            return;
         }
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).unwrap();
+        let (font_storage, font_id) = test_font_storage();
+        let data = TextData::builder(font_id, 32.0, [0.5f32, 0.5, 0.5, 1.0])
+            .push_str("█")
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
 
-        let mut instance_buffer = self.instance_buffer.borrow_mut();
+        // An sRGB view of a non-sRGB texture, as swapchains often provide.
+        let target = Target::with_view_format(
+            &device,
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            SIZE,
+            SIZE,
+        );
 
-        let mut instance_data = self.instance_data_staging.borrow_mut();
-        instance_data.clear();
-        instance_data.extend(instances.iter().map(|inst| InstanceData {
-            screen_rect: [
-                inst.screen_rect.min.x,
-                inst.screen_rect.min.y,
-                inst.screen_rect.width(),
-                inst.screen_rect.height(),
-            ],
-            uv_rect: [
-                inst.uv_rect.min.x,
-                inst.uv_rect.min.y,
-                inst.uv_rect.width(),
-                inst.uv_rect.height(),
-            ],
-            color: inst.user_data.into(),
-            layer: inst.texture_index as u32,
-            _padding: [0; 3],
-        }));
+        let mut renderer = test_renderer(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+        renderer.set_color_space(ColorSpace::Srgb);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let pass = SimpleRenderPass::new(&mut encoder, &target.view)
+            .with_format(wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(pass.format(), Ok(wgpu::TextureFormat::Rgba8UnormSrgb));
+        renderer.render_with_format(
+            &layout,
+            &font_storage,
+            &device,
+            &queue,
+            &mut encoder,
+            &target.view,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        );
+        let pixels = target.read(&device, &queue, encoder);
+        let pipelines = renderer.resources.pipelines.borrow();
+        assert!(pipelines.contains_key(&wgpu::TextureFormat::Rgba8UnormSrgb));
+        assert!(!pipelines.contains_key(&wgpu::TextureFormat::Rgba8Unorm));
 
-        let instance_size = std::mem::size_of::<InstanceData>() as u64;
-        let needed_bytes = current_offset.get() + instance_data.len() as u64 * instance_size;
+        // The text is written linear and encoded by the view, back to its sRGB value.
+        let brightest = pixels.iter().map(|pixel| pixel[0]).max();
+        assert!(
+            brightest.is_some_and(|red| red.abs_diff(128) <= 2),
+            "{brightest:?}"
+        );
+    }
 
-        self.ensure_instance_buffer_capacity(device, needed_bytes, &mut instance_buffer);
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_render_all_merges_layouts_into_shared_passes() {
+        const WIDTH: u32 = 128;
+        const HEIGHT: u32 = 64;
 
-        let offset = current_offset.get();
-        let bytes = bytemuck::cast_slice(&instance_data);
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let data = TextData::builder(font_id, 32.0, [1.0f32; 4])
+            .push_str("█")
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
+        let mut clipped = layout.clone();
+        clipped.clip_rect = Some(euclid::Box2D::new(
+            euclid::Point2D::new(0.0, 0.0),
+            euclid::Point2D::new(8.0, HEIGHT as f32),
+        ));
 
-        let staging_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Instance Staging Buffer"),
-            contents: bytes,
-            usage: wgpu::BufferUsages::COPY_SRC,
-        });
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, WIDTH, HEIGHT);
+        let mut renderer = test_renderer(&device, format);
 
-        controller.encoder()?.copy_buffer_to_buffer(
-            &staging_buffer,
-            0,
-            &instance_buffer,
-            offset,
-            bytes.len() as u64,
-        );
+        // Returns the passes created and the red values of the target.
+        let mut render = |layouts: &[(LayoutPlacement, &TextLayout<[f32; 4]>)]| {
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            let mut controller = CountingPass {
+                inner: SimpleRenderPass::new(&mut encoder, &target.view),
+                passes: 0,
+            };
+            renderer
+                .render_all(layouts, &font_storage, &device, &queue, &mut controller)
+                .unwrap();
+            let passes = controller.passes;
+            let red: Vec<u8> = target
+                .read(&device, &queue, encoder)
+                .into_iter()
+                .map(|p| p[0])
+                .collect();
+            (passes, red)
+        };
+        let column = |red: &[u8], x: u32| -> Vec<u8> {
+            (0..HEIGHT).map(|y| red[(y * WIDTH + x) as usize]).collect()
+        };
 
-        let format = controller.format()?;
-        let mut rpass = controller.create_pass()?;
+        // Both copies are drawn by one pass, the second one moved by its offset.
+        // They start off the left edges, as the block overhangs its origin.
+        let left = LayoutPlacement::at([4.0, 0.0]);
+        let right = LayoutPlacement::at([68.0, 0.0]);
+        let (passes, red) = render(&[(left, &layout), (right, &layout)]);
+        assert_eq!(passes, 1);
+        assert!(red.iter().any(|&value| value > 0));
+        for x in 0..64 {
+            assert_eq!(column(&red, x), column(&red, x + 64));
+        }
 
-        // Use cached pipeline or create new one based on format
-        let pipeline = self.get_pipeline(device, format);
-        rpass.set_pipeline(&pipeline);
-        rpass.set_bind_group(0, &self.globals_bind_group, &[]);
-        rpass.set_vertex_buffer(
-            0,
-            instance_buffer.slice(offset..offset + bytes.len() as u64),
-        );
-        rpass.draw(0..4, 0..instance_data.len() as u32);
+        // The clip moves with its layout, and a different clip starts a new batch.
+        let (passes, red) = render(&[(left, &layout), (right, &clipped)]);
+        assert_eq!(passes, 2);
+        for x in 76..WIDTH {
+            assert!(column(&red, x).iter().all(|&value| value == 0));
+        }
+        for x in 68..76 {
+            assert_eq!(column(&red, x), column(&red, x - 64));
+        }
 
-        current_offset.set(offset + bytes.len() as u64);
-        Ok(())
+        // A faded copy is drawn at half the brightness of the opaque one.
+        let faded = right.opacity(0.5);
+        let (passes, red) = render(&[(left, &layout), (faded, &layout)]);
+        assert_eq!(passes, 1);
+        for x in 0..64 {
+            for (opaque, faded) in column(&red, x).into_iter().zip(column(&red, x + 64)) {
+                assert!((opaque as i32 / 2 - faded as i32).abs() <= 1);
+            }
+        }
     }
 
-    fn draw_standalone<T: Into<[f32; 4]> + Copy, E>(
-        &self,
-        device: &wgpu::Device,
-        controller: &mut impl WgpuRenderPassController<E>,
-        current_offset: &std::cell::Cell<u64>,
-        standalone: &StandaloneGlyph<T>,
-    ) -> Result<(), E> {
-        let needed_width = standalone.width as u32;
-        let needed_height = standalone.height as u32;
-
-        let resources_ref = self.ensure_standalone_resources(device, needed_width, needed_height);
-        let resources = resources_ref
-            .as_ref()
-            .expect("Logic bug: resources_ref should be initialized.");
-
-        // Prepare data with 256-byte alignment for copy_buffer_to_texture
-        let width = standalone.width as u32;
-        let height = standalone.height as u32;
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_gradient_fills_glyphs_along_its_line() {
+        const SIZE: u32 = 64;
 
-        let mut pixel_staging = self.pixel_staging.borrow_mut();
-        let (data, padded_bytes_per_row) =
-            Self::prepare_padded_data(&mut pixel_staging, &standalone.pixels, width, height);
+        let (device, queue) = create_device();
+        let (font_storage, font_id) = test_font_storage();
+        let data = TextData::builder(font_id, 32.0, [1.0f32, 0.0, 0.0, 1.0])
+            .push_str("█")
+            .build();
+        let layout = data.layout(&TextLayoutConfig::default(), &font_storage);
 
-        let staging_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Standalone Staging Buffer"),
-            contents: &data,
-            usage: wgpu::BufferUsages::COPY_SRC,
-        });
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let target = Target::new(&device, format, SIZE, SIZE);
+        let mut renderer = test_renderer(&device, format);
 
-        controller.encoder()?.copy_buffer_to_texture(
-            wgpu::TexelCopyBufferInfo {
-                buffer: &staging_buffer,
-                layout: wgpu::TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(padded_bytes_per_row),
-                    rows_per_image: Some(height),
-                },
-            },
-            wgpu::TexelCopyTextureInfo {
-                texture: &resources.texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-        );
+        // From red at the top of the target to blue at its bottom.
+        let placement = LayoutPlacement::default().gradient(LinearGradient::vertical(
+            0.0,
+            SIZE as f32,
+            [0.0, 0.0, 1.0, 1.0],
+        ));
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        renderer
+            .render_placed_to(
+                &layout,
+                placement,
+                &font_storage,
+                &device,
+                &queue,
+                &mut SimpleRenderPass::new(&mut encoder, &target.view),
+            )
+            .unwrap();
+        let pixels = target.read(&device, &queue, encoder);
 
-        // UV calculation
-        let u_max = standalone.width as f32 / resources.size.width as f32;
-        let v_max = standalone.height as f32 / resources.size.height as f32;
+        // Fully covered pixels of the glyph, from top to bottom. The target is
+        // cleared to black, so red and blue add up to the coverage.
+        let covered = |pixel: [u8; 4]| (pixel[0] as i32 + pixel[2] as i32 - 255).abs() <= 2;
+        let x = (0..SIZE)
+            .max_by_key(|&x| {
+                (0..SIZE)
+                    .filter(|&y| covered(pixels[(y * SIZE + x) as usize]))
+                    .count()
+            })
+            .unwrap();
+        let column: Vec<[u8; 4]> = (0..SIZE)
+            .map(|y| pixels[(y * SIZE + x) as usize])
+            .filter(|&pixel| covered(pixel))
+            .collect();
+        assert!(column.len() >= 8);
+        let (top, bottom) = (column[0], column[column.len() - 1]);
+        assert!(top[0] > bottom[0] + 64);
+        assert!(bottom[2] > top[2] + 64);
+        for pair in column.windows(2) {
+            assert!(pair[1][0] <= pair[0][0]);
+            assert!(pair[1][2] >= pair[0][2]);
+        }
+    }
 
-        // Instance data for standalone
-        let instance_data = InstanceData {
-            screen_rect: [
-                standalone.screen_rect.min.x,
-                standalone.screen_rect.min.y,
-                standalone.screen_rect.width(),
-                standalone.screen_rect.height(),
-            ],
-            uv_rect: [0.0, 0.0, u_max, v_max],
-            color: standalone.user_data.into(),
-            layer: 0,
-            _padding: [0; 3],
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_pipeline_cache_needs_feature() {
+        let (device, _queue) = create_device();
+        // SAFETY: No data is passed.
+        assert!(unsafe { WgpuRenderer::create_pipeline_cache(&device, None) }.is_none());
+        let config = GpuCacheConfig {
+            texture_size: NonZeroUsize::new(64).unwrap(),
+            tile_size: NonZeroUsize::new(64).unwrap(),
+            tiles_per_axis: NonZeroUsize::new(1).unwrap(),
+            priority: AtlasPriority::Normal,
+            padding: 1,
+            extrude_edges: false,
+            packing: AtlasPacking::Grid,
         };
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let renderer =
+            WgpuRenderer::new(&device, &[config.clone(), config.clone()], &[format], None);
+        assert!(renderer.pipeline_cache_data().is_none());
 
-        // Use the shared instance buffer for standalone glyphs too
-        let instance_size = std::mem::size_of::<InstanceData>() as u64;
-        let mut instance_buffer = self.instance_buffer.borrow_mut();
-        let needed_bytes = current_offset.get() + instance_size;
-
-        self.ensure_instance_buffer_capacity(device, needed_bytes, &mut instance_buffer);
+        let Some((device, _queue)) = create_device_with_features(wgpu::Features::PIPELINE_CACHE)
+        else {
+            // No adapter with pipeline caches available.
+            return;
+        };
+        // SAFETY: No data is passed.
+        let cache = unsafe { WgpuRenderer::create_pipeline_cache(&device, None) }.unwrap();
+        let renderer =
+            WgpuRenderer::new(&device, &[config.clone(), config], &[format], Some(&cache));
+        let data = renderer.pipeline_cache_data().unwrap();
 
-        let offset = current_offset.get();
-        let bytes = bytemuck::bytes_of(&instance_data);
+        // SAFETY: The data was just read from a cache of the same device.
+        let restored = unsafe { WgpuRenderer::create_pipeline_cache(&device, Some(&data)) };
+        assert!(restored.is_some());
+    }
 
-        let staging_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Standalone Instance Staging Buffer"),
-            contents: bytes,
-            usage: wgpu::BufferUsages::COPY_SRC,
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_lcd_antialiasing_needs_dual_source_blending() {
+        let (device, queue) = create_device();
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let pixels = render_text_rgba(&device, &queue, format, "ao", [1.0; 4], |renderer| {
+            assert!(!renderer.set_lcd_antialiasing(&device, true));
+            assert!(!renderer.lcd_antialiasing());
         });
+        assert!(pixels.iter().all(|&[r, g, b, _]| r == g && g == b));
 
-        controller.encoder()?.copy_buffer_to_buffer(
-            &staging_buffer,
-            0,
-            &instance_buffer,
-            offset,
-            bytes.len() as u64,
-        );
+        // Enabled on the generic renderer alone, text falls back to grayscale.
+        let fallback = render_text_rgba(&device, &queue, format, "ao", [1.0; 4], |renderer| {
+            renderer.gpu_renderer.set_lcd_antialiasing(true);
+        });
+        assert_eq!(fallback, pixels);
+    }
 
-        let format = controller.format()?;
-        let mut rpass = controller.create_pass()?;
-
-        let pipeline = self.get_standalone_pipeline(device, format);
-        rpass.set_pipeline(&pipeline);
-        rpass.set_bind_group(0, &resources.bind_group, &[]);
-        rpass.set_vertex_buffer(
-            0,
-            instance_buffer.slice(offset..offset + bytes.len() as u64),
-        );
-        rpass.draw(0..4, 0..1);
+    #[test]
+    #[ignore = "needs a wgpu adapter"]
+    fn test_lcd_antialiasing_colors_glyph_edges() {
+        let Some((device, queue)) =
+            create_device_with_features(wgpu::Features::DUAL_SOURCE_BLENDING)
+        else {
+            // No adapter with dual-source blending available.
+            return;
+        };
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let render = |lcd| {
+            render_text_rgba(&device, &queue, format, "ao", [1.0; 4], |renderer| {
+                assert_eq!(renderer.set_lcd_antialiasing(&device, lcd), lcd);
+            })
+        };
+        let grayscale = render(false);
+        let lcd = render(true);
+        assert!(grayscale.iter().all(|&[r, g, b, _]| r == g && g == b));
+        assert!(lcd.iter().any(|&[r, _, b, _]| r != b));
 
-        current_offset.set(offset + bytes.len() as u64);
-        Ok(())
+        // The glyphs cover the same columns, not three times as many.
+        let columns = |pixels: &[[u8; 4]]| {
+            let drawn: Vec<usize> = (0..64)
+                .filter(|&x| {
+                    pixels
+                        .iter()
+                        .skip(x)
+                        .step_by(64)
+                        .any(|&[r, g, b, _]| r | g | b > 0)
+                })
+                .collect();
+            (drawn[0], drawn[drawn.len() - 1])
+        };
+        let (gray_min, gray_max) = columns(&grayscale);
+        let (lcd_min, lcd_max) = columns(&lcd);
+        assert!(gray_min.abs_diff(lcd_min) <= 1 && gray_max.abs_diff(lcd_max) <= 1);
     }
 }
@@ -0,0 +1,230 @@
+//! Buffers written through the queue, whose space is reused only once every
+//! command reading it has finished on the GPU.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{Arc, Weak};
+
+/// Leases kept for reuse by [`FrameLease::next`].
+const SPARE_LEASES: usize = 8;
+
+/// Marks the space one render reads as in use until the GPU is done with it.
+///
+/// The renderer holds a lease while it records a render, and each command
+/// buffer drawing from it holds a clone until its work is done. Space leased by
+/// a render that was recorded but never submitted is freed once its encoder is
+/// dropped.
+#[derive(Clone, Debug, Default)]
+pub(super) struct FrameLease(Arc<()>);
+
+impl FrameLease {
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces `current` with a lease for the next render, keeping it in
+    /// `spare` for a later one.
+    ///
+    /// A lease is taken from `spare` once neither commands nor regions refer to
+    /// it anymore, which makes it indistinguishable from a new one, so steady
+    /// renders do not allocate one each. Regions forget the leases of `spare`
+    /// through [`UploadRing::release_finished`].
+    pub(super) fn next(current: &mut Self, spare: &mut Vec<Self>) {
+        let next = match spare.iter().position(Self::is_unused) {
+            Some(index) => spare.swap_remove(index),
+            None => Self::new(),
+        };
+        let previous = std::mem::replace(current, next);
+        if spare.len() < SPARE_LEASES {
+            spare.push(previous);
+        }
+    }
+
+    fn is_unused(&self) -> bool {
+        self.is_done() && Arc::weak_count(&self.0) == 0
+    }
+
+    /// Returns whether a lease kept as spare is held by no command anymore.
+    fn is_done(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+
+    /// Keeps the lease until the commands recorded into `encoder` are done.
+    pub(super) fn hold_by_encoder(&self, encoder: &wgpu::CommandEncoder) {
+        let lease = self.clone();
+        encoder.on_submitted_work_done(move || drop(lease));
+    }
+
+    /// Keeps the lease until the commands recorded into `pass` are done.
+    pub(super) fn hold_by_pass(&self, pass: &wgpu::RenderPass<'_>) {
+        let lease = self.clone();
+        pass.on_submitted_work_done(move || drop(lease));
+    }
+
+    /// Returns a token that is alive as long as the lease is held.
+    pub(super) fn downgrade(&self) -> Weak<()> {
+        Arc::downgrade(&self.0)
+    }
+
+    fn is_same(&self, reader: &Weak<()>) -> bool {
+        std::ptr::eq(Arc::as_ptr(&self.0), reader.as_ptr())
+    }
+}
+
+/// Space of an [`UploadRing`] and the renders reading it.
+#[derive(Debug)]
+struct Region {
+    range: Range<u64>,
+    readers: Vec<Weak<()>>,
+}
+
+impl Region {
+    fn is_free(&self) -> bool {
+        self.readers.iter().all(|reader| reader.strong_count() == 0)
+    }
+
+    fn add_reader(&mut self, frame: &FrameLease) {
+        if !self.readers.iter().any(|reader| frame.is_same(reader)) {
+            self.readers.push(Arc::downgrade(&frame.0));
+        }
+    }
+}
+
+/// A buffer handing out space in allocation order, wrapping around to its
+/// start once the oldest space is free again.
+///
+/// When no free space fits, the buffer is replaced by a larger one. Commands
+/// recorded earlier keep the old buffer alive and read it unchanged.
+#[derive(Debug)]
+pub(super) struct UploadRing {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    /// Alignment of the start of each region, in bytes.
+    alignment: u64,
+    buffer: wgpu::Buffer,
+    /// Regions of `buffer` in use, oldest first.
+    regions: VecDeque<Region>,
+    /// The region allocated last, which may already have been freed.
+    last: Option<Range<u64>>,
+}
+
+impl UploadRing {
+    pub(super) fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        alignment: u64,
+        capacity: u64,
+    ) -> Self {
+        Self {
+            label,
+            usage,
+            alignment,
+            buffer: Self::create_buffer(device, label, usage, capacity),
+            regions: VecDeque::new(),
+            last: None,
+        }
+    }
+
+    fn create_buffer(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        size: u64,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Returns the current buffer.
+    pub(super) fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Returns the start of `size` bytes of free space, read by `frame` from now
+    /// on, and whether the buffer was replaced by a larger one to fit them.
+    pub(super) fn allocate(
+        &mut self,
+        device: &wgpu::Device,
+        size: u64,
+        frame: &FrameLease,
+    ) -> (u64, bool) {
+        while self.regions.front().is_some_and(Region::is_free) {
+            self.regions.pop_front();
+        }
+
+        let (start, grown) = match self.free_start(size) {
+            Some(start) => (start, false),
+            None => {
+                let capacity = (self.buffer.size() * 2).max(size * 2);
+                self.buffer = Self::create_buffer(device, self.label, self.usage, capacity);
+                self.regions.clear();
+                (0, true)
+            }
+        };
+        let mut region = Region {
+            range: start..start + size,
+            readers: Vec::new(),
+        };
+        region.add_reader(frame);
+        self.regions.push_back(region);
+        self.last = Some(start..start + size);
+        (start, grown)
+    }
+
+    /// Forgets the renders whose commands are done, including those of the
+    /// leases in `spare`, so the leases can be reused.
+    pub(super) fn release_finished(&mut self, spare: &[FrameLease]) {
+        for region in &mut self.regions {
+            region.readers.retain(|reader| {
+                reader.strong_count() > 0
+                    && !spare
+                        .iter()
+                        .any(|lease| lease.is_same(reader) && lease.is_done())
+            });
+        }
+    }
+
+    /// Returns where `size` bytes fit without overlapping any region in use.
+    fn free_start(&self, size: u64) -> Option<u64> {
+        let capacity = self.buffer.size();
+        let (Some(oldest), Some(newest)) = (self.regions.front(), self.regions.back()) else {
+            return (size <= capacity).then_some(0);
+        };
+        let after_newest = newest.range.end.next_multiple_of(self.alignment);
+        if newest.range.start < oldest.range.start {
+            // Already wrapped around: the free space lies between the two.
+            (after_newest + size <= oldest.range.start).then_some(after_newest)
+        } else if after_newest + size <= capacity {
+            Some(after_newest)
+        } else {
+            (size <= oldest.range.start).then_some(0)
+        }
+    }
+
+    /// Makes `frame` a reader of the region allocated last, and returns it, so a
+    /// render can draw what the previous one uploaded. Returns `None` before
+    /// anything was allocated.
+    pub(super) fn lease_last(&mut self, frame: &FrameLease) -> Option<Range<u64>> {
+        let last = self.last.clone()?;
+        match self.regions.back_mut() {
+            Some(region) if region.range == last => region.add_reader(frame),
+            // Regions are freed oldest first, so a freed last region means
+            // all of them are free, and nothing was written over it since.
+            _ => {
+                self.regions.clear();
+                let mut region = Region {
+                    range: last.clone(),
+                    readers: Vec::new(),
+                };
+                region.add_reader(frame);
+                self.regions.push_back(region);
+            }
+        }
+        Some(last)
+    }
+}
@@ -0,0 +1,8 @@
+// Default fragment hook, appended to the shaders of atlas and standalone glyphs
+// unless replaced by `CustomShader::FragmentHook`. It receives the premultiplied
+// color of the fragment, in the encoding of the target, before coverage is
+// applied, and returns the color to draw instead.
+
+fn fragment_hook(in: VertexOutput, color: vec4<f32>) -> vec4<f32> {
+    return color;
+}
@@ -0,0 +1,34 @@
+// Vertex stages for multiview passes, appended to the shaders of atlas and
+// standalone glyphs after the shadow stages. Each instance is drawn to the views
+// whose bit is set in its view mask and collapsed to a point in the others.
+
+// Returns `clip_position`, or a point outside the target unless the view mask
+// holds the view.
+fn select_view(clip_position: vec4<f32>, view_mask: u32, view_index: i32) -> vec4<f32> {
+    if (((view_mask >> u32(view_index)) & 1u) == 0u) {
+        return vec4<f32>(2.0, 2.0, 0.0, 1.0);
+    }
+    return clip_position;
+}
+
+@vertex
+fn vs_multiview(
+    model: VertexInput,
+    instance: InstanceInput,
+    @builtin(view_index) view_index: i32,
+) -> VertexOutput {
+    var out = glyph_vertex(model, instance);
+    out.clip_position = select_view(out.clip_position, instance.view_mask, view_index);
+    return out;
+}
+
+@vertex
+fn vs_shadow_multiview(
+    model: VertexInput,
+    instance: InstanceInput,
+    @builtin(view_index) view_index: i32,
+) -> ShadowOutput {
+    var out = shadow_vertex(model, instance);
+    out.clip_position = select_view(out.clip_position, instance.view_mask, view_index);
+    return out;
+}
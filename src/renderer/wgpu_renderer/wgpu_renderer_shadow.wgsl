@@ -0,0 +1,58 @@
+// Shadow stages, appended to the shaders of atlas and standalone glyphs, which
+// provide `glyph_coverage`, `layout_to_clip` and `clip_coverage`. The quad of each glyph grows by
+// the blur radius and moves by the shadow offset; its coverage is blurred with
+// 5x5 taps spread over the radius.
+
+struct ShadowOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) layer: u32,
+    // Minimum and maximum texture coordinates of the glyph, so no taps sample
+    // its neighbors in the atlas.
+    @location(3) @interpolate(flat) bounds: vec4<f32>,
+    // Distance between taps in texture coordinates.
+    @location(4) @interpolate(flat) tap: vec2<f32>,
+}
+
+fn shadow_vertex(model: VertexInput, instance: InstanceInput) -> ShadowOutput {
+    let idx = model.vertex_index;
+    let corner = vec2<f32>(f32(idx & 1u), f32(idx >> 1u));
+    let outward = corner * 2.0 - 1.0;
+
+    // Texture coordinates per layout unit.
+    let scale = instance.uv_rect.zw / max(instance.screen_rect.zw, vec2<f32>(1e-6));
+    let blur = globals.shadow_blur;
+    let layout_pos = instance.screen_rect.xy + corner * instance.screen_rect.zw
+        + outward * blur + globals.shadow_offset;
+
+    var out: ShadowOutput;
+    out.clip_position = layout_to_clip(layout_pos, instance.screen_rect.xy, instance.depth);
+    out.tex_coords = instance.uv_rect.xy + corner * instance.uv_rect.zw + outward * blur * scale;
+    out.color = convert_color(globals.shadow_color) * instance.color.a;
+    out.layer = instance.layer;
+    out.bounds = vec4<f32>(instance.uv_rect.xy, instance.uv_rect.xy + instance.uv_rect.zw);
+    out.tap = blur * scale / 2.0;
+    return out;
+}
+
+@vertex
+fn vs_shadow(model: VertexInput, instance: InstanceInput) -> ShadowOutput {
+    return shadow_vertex(model, instance);
+}
+
+@fragment
+fn fs_shadow(in: ShadowOutput) -> @location(0) vec4<f32> {
+    // Binomial weights, summing to 16 per axis.
+    var weights = array<f32, 5>(1.0, 4.0, 6.0, 4.0, 1.0);
+    var coverage = 0.0;
+    for (var i = 0; i < 5; i++) {
+        for (var j = 0; j < 5; j++) {
+            let uv = in.tex_coords + vec2<f32>(f32(i - 2), f32(j - 2)) * in.tap;
+            let inside = all(uv >= in.bounds.xy) && all(uv <= in.bounds.zw);
+            let sample = select(0.0, glyph_coverage(uv, in.layer), inside);
+            coverage += sample * weights[i] * weights[j];
+        }
+    }
+    return in.color * (coverage / 256.0) * clip_coverage(in.clip_position.xy);
+}
@@ -1,5 +1,26 @@
 struct Globals {
+    // Rows of the 3x2 transform from layout coordinates to the target.
+    transform_x: vec4<f32>,
+    transform_y: vec4<f32>,
     screen_size: vec2<f32>,
+    // 0: none, 1: sRGB to linear, 2: linear to sRGB
+    color_transform: u32,
+    // 0: none, 1: coverage corrected to approximate blending in linear space
+    coverage_correction: u32,
+    // Premultiplied color, offset in layout coordinates and blur radius of the
+    // shadow drawn behind text.
+    shadow_color: vec4<f32>,
+    shadow_offset: vec2<f32>,
+    shadow_blur: f32,
+    // 0: none, 1: quad origins rounded to whole pixels of the target
+    pixel_snap: u32,
+    // Factor linear colors are scaled by, for the white level of HDR targets.
+    output_scale: f32,
+    // Rows of the transform from pixels of the target into the coordinates of
+    // the clip rectangle, and its minimum and maximum.
+    clip_transform_x: vec4<f32>,
+    clip_transform_y: vec4<f32>,
+    clip_rect: vec4<f32>,
 };
 @group(0) @binding(0) var<uniform> globals: Globals;
 @group(0) @binding(1) var font_sampler: sampler;
@@ -14,35 +35,156 @@ struct InstanceInput {
     @location(1) uv_rect: vec4<f32>, // Usually 0,0,1,1 for standalone
     @location(2) color: vec4<f32>,
     @location(3) layer: u32, // Unused for standalone
+    @location(4) depth: f32,
+    @location(5) secondary_color: vec4<f32>,
+    @location(6) gradient: vec4<f32>,
+    // Bit `i` is set when the instance is drawn to view `i` of a multiview pass.
+    @location(7) view_mask: u32,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) color: vec4<f32>,
+    @location(2) secondary_color: vec4<f32>,
+    // Start and end point of the gradient, in layout coordinates.
+    @location(3) @interpolate(flat) gradient: vec4<f32>,
+    @location(4) layout_pos: vec2<f32>,
 }
 
-@vertex
-fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
+
+// Converts a premultiplied color into the encoding of the render target,
+// at the brightness of its output.
+fn convert_color(color: vec4<f32>) -> vec4<f32> {
+    let encoded = encode_color(color);
+    return vec4<f32>(encoded.rgb * globals.output_scale, encoded.a);
+}
+
+fn encode_color(color: vec4<f32>) -> vec4<f32> {
+    if (globals.color_transform == 0u || color.a <= 0.0) {
+        return color;
+    }
+    let straight = clamp(color.rgb / color.a, vec3<f32>(0.0), vec3<f32>(1.0));
+    var rgb: vec3<f32>;
+    if (globals.color_transform == 1u) {
+        rgb = srgb_to_linear(straight);
+    } else {
+        rgb = linear_to_srgb(straight);
+    }
+    return vec4<f32>(rgb * color.a, color.a);
+}
+
+// Returns the luminance of a premultiplied color, as encoded for the target.
+fn luma(color: vec4<f32>) -> f32 {
+    if (color.a <= 0.0) {
+        return 0.0;
+    }
+    let straight = clamp(color.rgb / color.a, vec3<f32>(0.0), vec3<f32>(1.0));
+    return dot(straight, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Corrects glyph coverage blended in sRGB-encoded space to approximate blending
+// in linear space. Exact for light text on black and dark text on white, between
+// those it assumes the text contrasts with its background.
+fn correct_coverage(coverage: f32, luma: f32) -> f32 {
+    if (globals.coverage_correction == 0u) {
+        return coverage;
+    }
+    let light = linear_to_srgb(vec3<f32>(coverage)).x;
+    let dark = 1.0 - linear_to_srgb(vec3<f32>(1.0 - coverage)).x;
+    return mix(dark, light, luma);
+}
+
+// Returns 1.0 for a fragment at `position`, in pixels of the target, inside
+// the clip rectangle of the globals, and 0.0 outside.
+fn clip_coverage(position: vec2<f32>) -> f32 {
+    let pos = vec3<f32>(position, 1.0);
+    let clip_pos = vec2<f32>(
+        dot(globals.clip_transform_x.xyz, pos),
+        dot(globals.clip_transform_y.xyz, pos),
+    );
+    let inside = all(clip_pos >= globals.clip_rect.xy) && all(clip_pos <= globals.clip_rect.zw);
+    return select(0.0, 1.0, inside);
+}
+
+// Transforms a point in layout coordinates into pixels of the target.
+fn layout_to_screen(layout_pos: vec2<f32>) -> vec2<f32> {
+    let pos = vec3<f32>(layout_pos, 1.0);
+    return vec2<f32>(
+        dot(globals.transform_x.xyz, pos),
+        dot(globals.transform_y.xyz, pos),
+    );
+}
+
+// Transforms a point of the quad at `origin`, both in layout coordinates, into
+// clip space. With pixel snapping, the whole quad moves so its origin falls on
+// a whole pixel.
+fn layout_to_clip(layout_pos: vec2<f32>, origin: vec2<f32>, depth: f32) -> vec4<f32> {
+    var screen_pos = layout_to_screen(layout_pos);
+    if (globals.pixel_snap != 0u) {
+        let screen_origin = layout_to_screen(origin);
+        screen_pos += floor(screen_origin + 0.5) - screen_origin;
+    }
+
+    let clip_x = (screen_pos.x / globals.screen_size.x) * 2.0 - 1.0;
+    let clip_y = 1.0 - (screen_pos.y / globals.screen_size.y) * 2.0;
+    return vec4<f32>(clip_x, clip_y, depth, 1.0);
+}
+
+// Returns the coverage of the glyph at normalized `tex_coords`. `layer` is unused.
+fn glyph_coverage(tex_coords: vec2<f32>, layer: u32) -> f32 {
+    return textureSampleLevel(font_texture, font_sampler, tex_coords, 0.0).r;
+}
+
+// Returns the color of the glyph at the fragment, mixed toward its secondary
+// color along the gradient.
+fn fill_color(in: VertexOutput) -> vec4<f32> {
+    let direction = in.gradient.zw - in.gradient.xy;
+    let length_sq = dot(direction, direction);
+    if (length_sq <= 0.0) {
+        return in.color;
+    }
+    let t = clamp(dot(in.layout_pos - in.gradient.xy, direction) / length_sq, 0.0, 1.0);
+    return mix(in.color, in.secondary_color, t);
+}
+
+fn glyph_vertex(model: VertexInput, instance: InstanceInput) -> VertexOutput {
     let idx = model.vertex_index;
     let x = f32(idx & 1u);
     let y = f32(idx >> 1u);
 
-    let screen_pos = instance.screen_rect.xy + vec2<f32>(x, y) * instance.screen_rect.zw;
+    let layout_pos = instance.screen_rect.xy + vec2<f32>(x, y) * instance.screen_rect.zw;
     let uv_pos = instance.uv_rect.xy + vec2<f32>(x, y) * instance.uv_rect.zw;
 
-    let clip_x = (screen_pos.x / globals.screen_size.x) * 2.0 - 1.0;
-    let clip_y = 1.0 - (screen_pos.y / globals.screen_size.y) * 2.0;
-
     var out: VertexOutput;
-    out.clip_position = vec4<f32>(clip_x, clip_y, 0.0, 1.0);
+    out.clip_position = layout_to_clip(layout_pos, instance.screen_rect.xy, instance.depth);
     out.tex_coords = uv_pos;
-    out.color = instance.color;
+    out.color = convert_color(instance.color);
+    out.secondary_color = convert_color(instance.secondary_color);
+    out.gradient = instance.gradient;
+    out.layout_pos = layout_pos;
     return out;
 }
 
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    return glyph_vertex(model, instance);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let alpha = textureSample(font_texture, font_sampler, in.tex_coords).r;
-    return in.color * alpha;
+    let alpha = glyph_coverage(in.tex_coords, 0u);
+    let color = fragment_hook(in, fill_color(in));
+    return color * correct_coverage(alpha, luma(color)) * clip_coverage(in.clip_position.xy);
 }
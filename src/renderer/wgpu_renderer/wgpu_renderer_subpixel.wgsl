@@ -0,0 +1,42 @@
+// Fragment stage for LCD subpixel masks, appended to the main shader along with
+// `enable dual_source_blending;`. Each pixel of a mask is three texels wide,
+// holding the coverage of its red, green and blue subpixel.
+
+struct SubpixelOutput {
+    // Premultiplied color, scaled per channel by subpixel coverage.
+    @location(0) @blend_src(0) color: vec4<f32>,
+    // How much of the target each channel covers.
+    @location(0) @blend_src(1) coverage: vec4<f32>,
+}
+
+@fragment
+fn fs_subpixel(in: VertexOutput) -> SubpixelOutput {
+    let size = vec2<f32>(textureDimensions(font_texture));
+    // The pixel center falls onto the center of its green subpixel.
+    let uv = in.tex_coords / size;
+    let step = vec2<f32>(1.0 / size.x, 0.0);
+    let layer = i32(in.layer);
+    let r = textureSample(font_texture, font_sampler, uv - step, layer).r;
+    let g = textureSample(font_texture, font_sampler, uv, layer).r;
+    let b = textureSample(font_texture, font_sampler, uv + step, layer).r;
+    // Sampled before selecting, as implicit derivatives need uniform control flow.
+    let solid = in.layer == SOLID_LAYER;
+    let color = fragment_hook(in, fill_color(in));
+    let color_luma = luma(color);
+    let coverage_rgb = select(
+        vec3<f32>(
+            correct_coverage(r, color_luma),
+            correct_coverage(g, color_luma),
+            correct_coverage(b, color_luma),
+        ),
+        vec3<f32>(1.0),
+        solid,
+    );
+    let coverage = coverage_rgb * clip_coverage(in.clip_position.xy);
+    let average = (coverage.r + coverage.g + coverage.b) / 3.0;
+
+    var out: SubpixelOutput;
+    out.color = vec4<f32>(color.rgb * coverage, color.a * average);
+    out.coverage = vec4<f32>(color.a * coverage, color.a * average);
+    return out;
+}
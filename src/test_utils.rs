@@ -0,0 +1,223 @@
+//! Helpers shared by the unit tests.
+
+use crate::{FontStorage, text::TextElement};
+
+/// DejaVu Sans Mono, bundled so tests do not depend on the fonts of the
+/// system. It covers Latin text and the full block `█`.
+pub(crate) const TEST_FONT: &[u8] = include_bytes!("../tests/fonts/DejaVuSansMono.ttf");
+
+/// Tuffy, bundled for tests that need a second face. It lacks glyphs of
+/// [`TEST_FONT`] such as the full block `█`.
+pub(crate) const SECOND_TEST_FONT: &[u8] = include_bytes!("../tests/fonts/Tuffy.ttf");
+
+/// The path of [`TEST_FONT`], for tests that load fonts from files.
+pub(crate) const TEST_FONT_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fonts/DejaVuSansMono.ttf"
+);
+
+/// Returns a storage holding [`TEST_FONT`] and the ID of its face.
+pub(crate) fn test_font_storage() -> (FontStorage, fontdb::ID) {
+    let mut font_storage = FontStorage::new();
+    let font_id = load_test_font(&mut font_storage);
+    (font_storage, font_id)
+}
+
+/// Loads [`TEST_FONT`] into `font_storage`, as a new face on every call, and
+/// returns its ID.
+pub(crate) fn load_test_font(font_storage: &mut FontStorage) -> fontdb::ID {
+    font_storage.load_font_binary(TEST_FONT).unwrap()[0]
+}
+
+/// Returns a run of `content` in face `font_id`, with no query and no line
+/// height or paragraph spacing of its own.
+pub(crate) fn text_element<T>(
+    font_id: fontdb::ID,
+    font_size: f32,
+    content: impl Into<String>,
+    user_data: T,
+) -> TextElement<T> {
+    TextElement {
+        font_id,
+        font_query: None,
+        font_size,
+        line_height: None,
+        paragraph_spacing: None,
+        content: content.into(),
+        user_data,
+    }
+}
+
+/// Returns a face ID for tests that do not need a real font.
+///
+/// The ID is taken from a fresh [`fontdb::Database`], so the same `n` always
+/// gives the same ID and different values of `n` give different IDs.
+pub(crate) fn make_id(n: u64) -> fontdb::ID {
+    let mut font_db = fontdb::Database::new();
+    let mut id = fontdb::ID::dummy();
+    for _ in 0..=n {
+        id = font_db.push_face_info(fontdb::FaceInfo {
+            id: fontdb::ID::dummy(),
+            source: fontdb::Source::Binary(std::sync::Arc::new(Vec::<u8>::new())),
+            index: 0,
+            families: Vec::new(),
+            post_script_name: String::new(),
+            style: fontdb::Style::Normal,
+            weight: fontdb::Weight::NORMAL,
+            stretch: fontdb::Stretch::Normal,
+            monospaced: false,
+        });
+    }
+    id
+}
+
+/// Returns `data`, a single TrueType face, with a weight axis from 100 to 900
+/// that moves every glyph 100 units down at its heaviest.
+pub(crate) fn with_weight_axis(data: &[u8]) -> Option<Vec<u8>> {
+    const DELTA: i8 = -100;
+
+    let u16_at = |at: usize| Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?));
+    let u32_at = |at: usize| Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?));
+    let num_tables = usize::from(u16_at(4)?);
+    let mut tables: Vec<([u8; 4], &[u8])> = (0..num_tables)
+        .map(|i| {
+            let record = 12 + 16 * i;
+            let offset = u32_at(record + 8)? as usize;
+            let length = u32_at(record + 12)? as usize;
+            Some((
+                data.get(record..record + 4)?.try_into().ok()?,
+                data.get(offset..offset + length)?,
+            ))
+        })
+        .collect::<Option<_>>()?;
+    let table = |tag: &[u8; 4]| tables.iter().find(|(t, _)| t == tag).map(|&(_, t)| t);
+    let (head, maxp, loca, glyf) = (
+        table(b"head")?,
+        table(b"maxp")?,
+        table(b"loca")?,
+        table(b"glyf")?,
+    );
+    let glyph_count = usize::from(u16::from_be_bytes([maxp[4], maxp[5]]));
+    let long_loca = head[51] == 1;
+    let glyph_range = |glyph: usize| -> Option<std::ops::Range<usize>> {
+        let at = |i: usize| {
+            Some(if long_loca {
+                u32::from_be_bytes(loca.get(4 * i..4 * i + 4)?.try_into().ok()?) as usize
+            } else {
+                2 * usize::from(u16::from_be_bytes(
+                    loca.get(2 * i..2 * i + 2)?.try_into().ok()?,
+                ))
+            })
+        };
+        Some(at(glyph)?..at(glyph + 1)?)
+    };
+
+    // Variation data moving all points of each glyph, including the phantom points, down.
+    let mut gvar = Vec::new();
+    let mut offsets = vec![0u32];
+    let mut glyph_data = Vec::new();
+    for glyph in 0..glyph_count {
+        let outline = glyf.get(glyph_range(glyph)?)?;
+        let points = if outline.is_empty() {
+            0
+        } else if i16::from_be_bytes([outline[0], outline[1]]) >= 0 {
+            let contours = usize::from(u16::from_be_bytes([outline[0], outline[1]]));
+            match contours {
+                0 => 0,
+                _ => {
+                    let last = 10 + 2 * (contours - 1);
+                    usize::from(u16::from_be_bytes([outline[last], outline[last + 1]])) + 1
+                }
+            }
+        } else {
+            // Components move as points of composite glyphs.
+            let mut components = 0;
+            let mut at = 10;
+            loop {
+                let flags = u16::from_be_bytes([outline[at], outline[at + 1]]);
+                components += 1;
+                at += 4 + if flags & 0x1 != 0 { 4 } else { 2 };
+                at += match flags {
+                    f if f & 0x8 != 0 => 2,
+                    f if f & 0x40 != 0 => 4,
+                    f if f & 0x80 != 0 => 8,
+                    _ => 0,
+                };
+                if flags & 0x20 == 0 {
+                    break components;
+                }
+            }
+        };
+        let packed = |value: i8| {
+            let mut deltas = Vec::new();
+            let mut left = points + 4;
+            while left > 0 {
+                let run = left.min(64);
+                if value == 0 {
+                    deltas.push(0x80 | (run - 1) as u8);
+                } else {
+                    deltas.push((run - 1) as u8);
+                    deltas.extend(std::iter::repeat_n(value as u8, run));
+                }
+                left -= run;
+            }
+            deltas
+        };
+        // All points, then the x and the y deltas.
+        let mut serialized = vec![0];
+        serialized.extend(packed(0));
+        serialized.extend(packed(DELTA));
+
+        // One tuple with an embedded peak at the heaviest weight and its own points.
+        glyph_data.extend_from_slice(&1u16.to_be_bytes());
+        glyph_data.extend_from_slice(&10u16.to_be_bytes());
+        glyph_data.extend_from_slice(&(serialized.len() as u16).to_be_bytes());
+        glyph_data.extend_from_slice(&(0x8000u16 | 0x2000).to_be_bytes());
+        glyph_data.extend_from_slice(&0x4000u16.to_be_bytes());
+        glyph_data.extend(serialized);
+        glyph_data.resize(glyph_data.len().next_multiple_of(2), 0);
+        offsets.push(glyph_data.len() as u32);
+    }
+    let data_offset = 20 + 4 * offsets.len() as u32;
+    for value in [1u16, 0, 1, 0] {
+        gvar.extend_from_slice(&value.to_be_bytes());
+    }
+    gvar.extend_from_slice(&data_offset.to_be_bytes());
+    gvar.extend_from_slice(&(glyph_count as u16).to_be_bytes());
+    gvar.extend_from_slice(&1u16.to_be_bytes());
+    gvar.extend_from_slice(&data_offset.to_be_bytes());
+    for offset in offsets {
+        gvar.extend_from_slice(&offset.to_be_bytes());
+    }
+    gvar.extend(glyph_data);
+
+    let mut fvar = Vec::new();
+    for value in [1u16, 0, 16, 2, 1, 20, 0, 8] {
+        fvar.extend_from_slice(&value.to_be_bytes());
+    }
+    fvar.extend_from_slice(b"wght");
+    for value in [100i32, 400, 900] {
+        fvar.extend_from_slice(&(value << 16).to_be_bytes());
+    }
+    fvar.extend_from_slice(&[0, 0, 1, 0]);
+
+    tables.push((*b"fvar", &fvar));
+    tables.push((*b"gvar", &gvar));
+    tables.sort_by_key(|&(tag, _)| tag);
+    let mut font = data[..4].to_vec();
+    font.extend_from_slice(&(tables.len() as u16).to_be_bytes());
+    font.extend_from_slice(&[0; 6]);
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, table) in &tables {
+        font.extend_from_slice(tag);
+        font.extend_from_slice(&[0; 4]);
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        offset += table.len().next_multiple_of(4);
+    }
+    for (_, table) in &tables {
+        font.extend_from_slice(table);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+    Some(font)
+}
@@ -1,10 +1,34 @@
+/// Conversion of ANSI-colored terminal output into text data.
+pub mod ansi;
+/// A single string styled by attribute spans over byte ranges.
+pub mod attributed;
 /// Defines the input data structures for text layout.
 pub mod data;
+mod emoji;
+#[cfg(feature = "serde")]
+mod fontdb_serde;
+/// Conversion of a small HTML subset into text data.
+#[cfg(feature = "html")]
+pub mod html;
 /// The core text layout engine and configuration.
 pub mod layout;
+/// Keeping laid out text inside the safe area of a display.
+pub mod safe_area;
+/// Built-in run style understood by the layout and renderers.
+pub mod style;
+/// Named placeholders substituted into text data at layout time.
+pub mod template;
 
-pub use data::{TextData, TextElement};
+pub use ansi::{AnsiPalette, AnsiStyle, parse_ansi};
+pub use attributed::{AttributedText, SpanAttributes};
+pub use data::{FontFamily, FontQuery, TextData, TextDataBuilder, TextElement, TextWriter};
 pub use layout::{
-    GlyphPosition, HorizontalAlign, TextLayout, TextLayoutConfig, TextLayoutLine, VerticalAlign,
-    WrapStyle,
+    FontSizeSnapping, GlyphPosition, HorizontalAlign, LineHeight, ParagraphInfo, TextLayout,
+    TextLayoutConfig, TextLayoutLine, VerticalAlign, WordBox, WrapStyle,
 };
+pub use safe_area::{SafeArea, SafeAreaInsets};
+pub use style::{TextDecoration, TextStyle};
+pub use template::TemplateArgs;
+
+#[cfg(feature = "html")]
+pub use html::{HtmlStyle, HtmlStylesheet, parse_html};
@@ -0,0 +1,400 @@
+//! Conversion of ANSI-colored terminal output into [`TextData`].
+//!
+//! SGR sequences (`ESC [ … m`) set the color and emphasis of the following text;
+//! every other escape sequence is stripped. Both the 16-color palette and the
+//! 256-color and 24-bit extensions are supported, in both the `;` and the `:`
+//! (ITU T.416) parameter forms.
+
+use super::data::{TextData, TextElement};
+
+const ESC: char = '\u{1B}';
+const BEL: char = '\u{07}';
+
+/// Colors used to resolve palette indices and default colors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnsiPalette {
+    /// The 16 standard and bright colors, as `[r, g, b, a]`.
+    pub colors: [[f32; 4]; 16],
+    /// Text color when no foreground is set.
+    pub foreground: [f32; 4],
+    /// Background color used when inverting text without a background set.
+    pub background: [f32; 4],
+}
+
+impl Default for AnsiPalette {
+    /// The xterm palette with light gray text on black.
+    fn default() -> Self {
+        const COLORS: [[u8; 3]; 16] = [
+            [0, 0, 0],
+            [205, 0, 0],
+            [0, 205, 0],
+            [205, 205, 0],
+            [0, 0, 238],
+            [205, 0, 205],
+            [0, 205, 205],
+            [229, 229, 229],
+            [127, 127, 127],
+            [255, 0, 0],
+            [0, 255, 0],
+            [255, 255, 0],
+            [92, 92, 255],
+            [255, 0, 255],
+            [0, 255, 255],
+            [255, 255, 255],
+        ];
+        Self {
+            colors: COLORS.map(rgb),
+            foreground: rgb(COLORS[7]),
+            background: rgb(COLORS[0]),
+        }
+    }
+}
+
+impl AnsiPalette {
+    /// Resolves an index of the 256-color palette.
+    pub fn indexed(&self, index: u8) -> [f32; 4] {
+        match index {
+            0..16 => self.colors[index as usize],
+            16..232 => {
+                let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+                let i = index - 16;
+                rgb([level(i / 36), level(i / 6 % 6), level(i % 6)])
+            }
+            232.. => {
+                let gray = 8 + (index - 232) * 10;
+                rgb([gray; 3])
+            }
+        }
+    }
+}
+
+/// Resolved style of a run of terminal text, used as its user data.
+///
+/// Converts into its foreground color, so layouts can be passed to renderers
+/// that take `[r, g, b, a]` user data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnsiStyle {
+    /// Text color, with inversion already applied.
+    pub foreground: [f32; 4],
+    /// Cell background color, or `None` for the terminal background.
+    pub background: Option<[f32; 4]>,
+    /// SGR 1.
+    pub bold: bool,
+    /// SGR 2.
+    pub faint: bool,
+    /// SGR 3.
+    pub italic: bool,
+    /// SGR 4.
+    pub underline: bool,
+}
+
+impl From<AnsiStyle> for [f32; 4] {
+    fn from(style: AnsiStyle) -> Self {
+        style.foreground
+    }
+}
+
+/// Raw SGR state before palette resolution.
+#[derive(Clone, Copy, Default, PartialEq)]
+struct SgrState {
+    foreground: Option<[f32; 4]>,
+    background: Option<[f32; 4]>,
+    bold: bool,
+    faint: bool,
+    italic: bool,
+    underline: bool,
+    inverse: bool,
+}
+
+impl SgrState {
+    /// Applies the parameters of an SGR sequence.
+    ///
+    /// Parameters are separated by `;`, and sub-parameters by `:`, as in `4:0` or
+    /// `38:2::r:g:b`.
+    fn apply(&mut self, params: &str, palette: &AnsiPalette) {
+        let mut params = params.split(';');
+
+        // `ESC [ m` is the same as `ESC [ 0 m`.
+        while let Some(param) = params.next() {
+            let mut sub_params = param.split(':').map(parse_param);
+            let has_sub_params = param.contains(':');
+            let param = sub_params.next().unwrap_or(0);
+            match param {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                2 => self.faint = true,
+                3 => self.italic = true,
+                // `4:0` turns underlines off; `4:1` to `4:5` pick a style.
+                4 => self.underline = sub_params.next() != Some(0),
+                7 => self.inverse = true,
+                22 => {
+                    self.bold = false;
+                    self.faint = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                30..=37 => self.foreground = Some(palette.colors[param as usize - 30]),
+                38 if has_sub_params => {
+                    self.foreground = extended_color_sub_params(sub_params, palette);
+                }
+                38 => {
+                    self.foreground = extended_color(&mut params.by_ref().map(parse_param), palette)
+                }
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(palette.colors[param as usize - 40]),
+                48 if has_sub_params => {
+                    self.background = extended_color_sub_params(sub_params, palette);
+                }
+                48 => {
+                    self.background = extended_color(&mut params.by_ref().map(parse_param), palette)
+                }
+                49 => self.background = None,
+                90..=97 => self.foreground = Some(palette.colors[param as usize - 90 + 8]),
+                100..=107 => self.background = Some(palette.colors[param as usize - 100 + 8]),
+                _ => {}
+            }
+        }
+    }
+
+    fn resolve(&self, palette: &AnsiPalette) -> AnsiStyle {
+        let foreground = self.foreground.unwrap_or(palette.foreground);
+        let (foreground, background) = if self.inverse {
+            (
+                self.background.unwrap_or(palette.background),
+                Some(foreground),
+            )
+        } else {
+            (foreground, self.background)
+        };
+        AnsiStyle {
+            foreground,
+            background,
+            bold: self.bold,
+            faint: self.faint,
+            italic: self.italic,
+            underline: self.underline,
+        }
+    }
+}
+
+/// Parses an SGR parameter.
+///
+/// An empty parameter is 0. Values too large for `u16`, or that are not numbers,
+/// become `u16::MAX`, which no attribute uses, so they are ignored rather than
+/// resetting the attributes like 0.
+fn parse_param(param: &str) -> u16 {
+    if param.is_empty() {
+        0
+    } else {
+        param.parse().unwrap_or(u16::MAX)
+    }
+}
+
+/// Parses the arguments of SGR 38/48 in the `;` form: `5;n` or `2;r;g;b`.
+fn extended_color(
+    params: &mut impl Iterator<Item = u16>,
+    palette: &AnsiPalette,
+) -> Option<[f32; 4]> {
+    match params.next()? {
+        5 => Some(palette.indexed(channel(params.next()))),
+        2 => Some(rgb([
+            channel(params.next()),
+            channel(params.next()),
+            channel(params.next()),
+        ])),
+        _ => None,
+    }
+}
+
+/// Parses the sub-parameters of SGR 38/48 in the `:` form: `5:n`, or `2:id:r:g:b`
+/// with an optional (usually empty) color space ID, which is ignored.
+fn extended_color_sub_params(
+    sub_params: impl Iterator<Item = u16>,
+    palette: &AnsiPalette,
+) -> Option<[f32; 4]> {
+    let sub_params: Vec<u16> = sub_params.collect();
+    match *sub_params.as_slice() {
+        [5, index, ..] => Some(palette.indexed(channel(Some(index)))),
+        [2, _, r, g, b, ..] | [2, r, g, b] => Some(rgb([r, g, b].map(|v| channel(Some(v))))),
+        _ => None,
+    }
+}
+
+/// Clamps a color channel or palette index to `0..=255`; missing values are 0.
+fn channel(value: Option<u16>) -> u8 {
+    value.map_or(0, |v| v.min(255) as u8)
+}
+
+fn rgb([r, g, b]: [u8; 3]) -> [f32; 4] {
+    [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0]
+}
+
+/// Converts ANSI-colored `text` into text runs with [`AnsiStyle`] user data.
+///
+/// `font_for` picks the font of each run, e.g. a bold face for [`AnsiStyle::bold`].
+/// Carriage returns are dropped so `\r\n` line endings become single breaks.
+pub fn parse_ansi(
+    text: &str,
+    font_size: f32,
+    palette: &AnsiPalette,
+    mut font_for: impl FnMut(&AnsiStyle) -> fontdb::ID,
+) -> TextData<AnsiStyle> {
+    let mut data = TextData::new();
+    let mut state = SgrState::default();
+    let mut run = String::new();
+    let mut run_state = state;
+
+    let mut flush = |run: &mut String, run_state: &SgrState| {
+        if !run.is_empty() {
+            let style = run_state.resolve(palette);
+            data.append(TextElement {
+                font_id: font_for(&style),
+                font_query: None,
+                line_height: None,
+                paragraph_spacing: None,
+                font_size,
+                content: std::mem::take(run),
+                user_data: style,
+            });
+        }
+    };
+
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            ESC => match chars.peek().map(|&(_, next)| next) {
+                // Control Sequence Introducer: parameters end at a byte in `@`..=`~`.
+                Some('[') => {
+                    chars.next();
+                    let start = index + 2;
+                    let mut end = None;
+                    for (i, c) in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            end = Some((i, c));
+                            break;
+                        }
+                    }
+                    if let Some((end, 'm')) = end {
+                        state.apply(&text[start..end], palette);
+                    }
+                }
+                // Operating System Command: ends at BEL or `ESC \`.
+                Some(']') => {
+                    chars.next();
+                    while let Some((_, c)) = chars.next() {
+                        if c == BEL {
+                            break;
+                        }
+                        if c == ESC && chars.peek().map(|&(_, next)| next) == Some('\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                // Two-character escape sequences.
+                Some(_) => {
+                    chars.next();
+                }
+                None => {}
+            },
+            '\r' => {}
+            _ => {
+                if state != run_state {
+                    flush(&mut run, &run_state);
+                    run_state = state;
+                }
+                run.push(ch);
+            }
+        }
+    }
+    flush(&mut run, &run_state);
+
+    data
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::make_id;
+
+    fn parse(text: &str) -> Vec<(String, AnsiStyle)> {
+        parse_ansi(text, 14.0, &AnsiPalette::default(), |style| {
+            make_id(style.bold as u64)
+        })
+        .into_texts()
+        .into_iter()
+        .map(|text| (text.content, text.user_data))
+        .collect()
+    }
+
+    #[test]
+    fn test_basic_colors() {
+        let palette = AnsiPalette::default();
+        let runs = parse("plain \x1b[31mred\x1b[0m \x1b[1;94mbold blue\x1b[m");
+
+        let contents: Vec<_> = runs.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(contents, ["plain ", "red", " ", "bold blue"]);
+        assert_eq!(runs[0].1.foreground, palette.foreground);
+        assert_eq!(runs[1].1.foreground, palette.colors[1]);
+        assert_eq!(runs[2].1, runs[0].1);
+        assert_eq!(runs[3].1.foreground, palette.colors[12]);
+        assert!(runs[3].1.bold);
+    }
+
+    #[test]
+    fn test_extended_colors() {
+        let runs = parse("\x1b[38;5;196ma\x1b[38;2;10;20;30;48;5;232mb\x1b[38:5:15mc");
+        assert_eq!(runs[0].1.foreground, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(runs[1].1.foreground, rgb([10, 20, 30]));
+        assert_eq!(runs[1].1.background, Some(rgb([8, 8, 8])));
+        assert_eq!(runs[2].1.foreground, [1.0; 4]);
+    }
+
+    #[test]
+    fn test_colon_sub_params() {
+        let runs =
+            parse("\x1b[38:2::255:128:0ma\x1b[38:2:1:2:3;48:5:196mb\x1b[4:3mc\x1b[4:0;38:2:9md");
+        assert_eq!(runs[0].1.foreground, rgb([255, 128, 0]));
+        assert_eq!(runs[1].1.foreground, rgb([1, 2, 3]));
+        assert_eq!(runs[1].1.background, Some([1.0, 0.0, 0.0, 1.0]));
+        assert!(runs[2].1.underline);
+        // An incomplete color resets only that color.
+        assert!(!runs[3].1.underline);
+        assert_eq!(runs[3].1.foreground, AnsiPalette::default().foreground);
+        assert_eq!(runs[3].1.background, runs[1].1.background);
+    }
+
+    #[test]
+    fn test_overflowing_params_are_ignored() {
+        let runs = parse("\x1b[1;31ma\x1b[99999mb\x1b[38;2;70000;0;0mc");
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].0, "ab");
+        assert!(runs[0].1.bold);
+        assert_eq!(runs[1].1.foreground, [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_attributes_and_inverse() {
+        let palette = AnsiPalette::default();
+        let runs = parse("\x1b[3;4ma\x1b[23mb\x1b[7mc\x1b[27;24md");
+        assert!(runs[0].1.italic && runs[0].1.underline);
+        assert!(!runs[1].1.italic && runs[1].1.underline);
+        assert_eq!(runs[2].1.foreground, palette.background);
+        assert_eq!(runs[2].1.background, Some(palette.foreground));
+        assert_eq!(runs[3].1, parse("d")[0].1);
+
+        let data = parse_ansi("\x1b[1mbold", 14.0, &palette, |style| {
+            make_id(style.bold as u64)
+        });
+        assert_eq!(data.texts[0].font_id, make_id(1));
+    }
+
+    #[test]
+    fn test_strips_other_sequences() {
+        let runs = parse("\x1b]0;title\x07a\x1b[2Kb\x1b]8;;http://x\x1b\\c\r\nd\x1b");
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].0, "abc\nd");
+    }
+}
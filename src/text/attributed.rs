@@ -0,0 +1,256 @@
+//! A single string styled by attribute spans over byte ranges.
+//!
+//! Syntax highlighting and search overlays naturally produce ranges into one
+//! buffer rather than a sequence of separate runs. [`AttributedText`] keeps the
+//! text in one `String` and resolves its spans into runs when laid out.
+
+use std::{collections::BTreeSet, ops::Range};
+
+use super::{
+    data::{TextData, TextElement},
+    layout::{TextLayout, TextLayoutConfig},
+};
+use crate::font_storage::FontStorage;
+
+/// Attributes applied over a byte range; `None` fields leave the value underneath.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpanAttributes<T> {
+    /// Font of the range.
+    pub font_id: Option<fontdb::ID>,
+    /// Font size of the range in pixels.
+    pub font_size: Option<f32>,
+    /// User data (e.g. color) of the range.
+    pub user_data: Option<T>,
+}
+
+impl<T> Default for SpanAttributes<T> {
+    fn default() -> Self {
+        Self {
+            font_id: None,
+            font_size: None,
+            user_data: None,
+        }
+    }
+}
+
+impl<T> SpanAttributes<T> {
+    /// Attributes that only set the user data.
+    pub fn user_data(user_data: T) -> Self {
+        Self {
+            user_data: Some(user_data),
+            ..Self::default()
+        }
+    }
+}
+
+/// Text in one string with a base style and overlapping attribute spans.
+///
+/// Spans are applied in order, so a later span (e.g. a search highlight) overrides
+/// the fields it sets on an earlier one (e.g. syntax coloring). Byte ranges index
+/// into [`Self::text`] and must lie on `char` boundaries. Layout character and byte
+/// indices refer to [`Self::text`] directly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttributedText<T> {
+    /// The text content.
+    pub text: String,
+    /// Font of text not covered by a span setting one.
+    pub font_id: fontdb::ID,
+    /// Font size of text not covered by a span setting one.
+    pub font_size: f32,
+    /// User data of text not covered by a span setting one.
+    pub user_data: T,
+    /// Attribute spans in application order.
+    pub spans: Vec<(Range<usize>, SpanAttributes<T>)>,
+}
+
+impl<T: Clone> AttributedText<T> {
+    /// Creates unstyled text with a base style.
+    pub fn new(text: impl Into<String>, font_id: fontdb::ID, font_size: f32, user_data: T) -> Self {
+        Self {
+            text: text.into(),
+            font_id,
+            font_size,
+            user_data,
+            spans: Vec::new(),
+        }
+    }
+
+    /// Adds a span applied over any previously added ones.
+    pub fn push_span(&mut self, range: Range<usize>, attributes: SpanAttributes<T>) {
+        self.spans.push((range, attributes));
+    }
+
+    /// Removes all spans, e.g. before re-highlighting.
+    pub fn clear_spans(&mut self) {
+        self.spans.clear();
+    }
+
+    /// Resolves the spans into one run per stretch of text styled by the same spans.
+    ///
+    /// # Panics
+    /// When a span range is out of bounds or does not lie on `char` boundaries.
+    pub fn to_text_data(&self) -> TextData<T> {
+        // Span boundaries, where spans start or stop applying.
+        let mut events: Vec<(usize, usize)> = Vec::with_capacity(self.spans.len() * 2);
+        for (index, (range, _)) in self.spans.iter().enumerate() {
+            assert!(
+                range.start <= range.end
+                    && self.text.is_char_boundary(range.start)
+                    && self.text.is_char_boundary(range.end),
+                "span range {range:?} does not lie on char boundaries of the text"
+            );
+            if !range.is_empty() {
+                events.push((range.start, index));
+                events.push((range.end, index));
+            }
+        }
+        events.sort_unstable();
+
+        let mut data: TextData<T> = TextData::new();
+        let mut previous = None;
+        let mut active = BTreeSet::new();
+        let mut start = 0;
+        let mut events = events.into_iter().peekable();
+        while start < self.text.len() {
+            while let Some(&(position, index)) = events.peek()
+                && position == start
+            {
+                if !active.remove(&index) {
+                    active.insert(index);
+                }
+                events.next();
+            }
+            let end = events
+                .peek()
+                .map_or(self.text.len(), |&(position, _)| position);
+
+            // Segments styled by the same spans form one run.
+            let winners = self.winners(&active);
+            match data.texts_mut().last_mut() {
+                Some(last) if previous == Some(winners) => {
+                    last.content.push_str(&self.text[start..end]);
+                }
+                _ => data.append(self.element(winners, &self.text[start..end])),
+            }
+            previous = Some(winners);
+            start = end;
+        }
+        data
+    }
+
+    /// Lays out the text, see [`TextData::layout`].
+    pub fn layout(&self, config: &TextLayoutConfig, font_storage: &FontStorage) -> TextLayout<T> {
+        self.to_text_data().layout(config, font_storage)
+    }
+
+    /// Indices of the spans providing the font, size and user data among `active`.
+    ///
+    /// The latest span setting a field wins; `None` keeps the base value.
+    fn winners(&self, active: &BTreeSet<usize>) -> [Option<usize>; 3] {
+        let latest = |field: fn(&SpanAttributes<T>) -> bool| {
+            active
+                .iter()
+                .rev()
+                .copied()
+                .find(|&index| field(&self.spans[index].1))
+        };
+        [
+            latest(|a| a.font_id.is_some()),
+            latest(|a| a.font_size.is_some()),
+            latest(|a| a.user_data.is_some()),
+        ]
+    }
+
+    /// Builds the run for `content` from the winning spans.
+    fn element(&self, winners: [Option<usize>; 3], content: &str) -> TextElement<T> {
+        let [font_id, font_size, user_data] = winners.map(|w| w.map(|i| &self.spans[i].1));
+        TextElement {
+            font_id: font_id.and_then(|a| a.font_id).unwrap_or(self.font_id),
+            font_query: None,
+            line_height: None,
+            paragraph_spacing: None,
+            font_size: font_size
+                .and_then(|a| a.font_size)
+                .unwrap_or(self.font_size),
+            content: content.to_string(),
+            user_data: user_data
+                .and_then(|a| a.user_data.clone())
+                .unwrap_or_else(|| self.user_data.clone()),
+        }
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::make_id;
+
+    fn runs(text: &AttributedText<u8>) -> Vec<(String, fontdb::ID, u8)> {
+        text.to_text_data()
+            .into_texts()
+            .into_iter()
+            .map(|text| (text.content, text.font_id, text.user_data))
+            .collect()
+    }
+
+    #[test]
+    fn test_overlapping_spans() {
+        let mut text = AttributedText::new("let x = \"héllo\";", make_id(0), 14.0, 0u8);
+        // Syntax colors.
+        text.push_span(0..3, SpanAttributes::user_data(1));
+        text.push_span(8..16, SpanAttributes::user_data(2));
+        // Search highlight over the end of the keyword and the start of the string.
+        text.push_span(
+            2..10,
+            SpanAttributes {
+                font_id: Some(make_id(1)),
+                user_data: Some(9),
+                ..Default::default()
+            },
+        );
+        // An inner span that only changes the font, keeping the highlight color.
+        text.push_span(
+            4..5,
+            SpanAttributes {
+                font_id: Some(make_id(2)),
+                ..Default::default()
+            },
+        );
+        text.push_span(6..6, SpanAttributes::user_data(7));
+
+        assert_eq!(
+            runs(&text),
+            [
+                ("le".to_string(), make_id(0), 1),
+                ("t ".to_string(), make_id(1), 9),
+                ("x".to_string(), make_id(2), 9),
+                (" = \"h".to_string(), make_id(1), 9),
+                ("éllo\"".to_string(), make_id(0), 2),
+                (";".to_string(), make_id(0), 0),
+            ]
+        );
+        let content: String = text
+            .to_text_data()
+            .texts
+            .iter()
+            .map(|t| t.content.as_str())
+            .collect();
+        assert_eq!(content, text.text);
+    }
+
+    #[test]
+    fn test_without_spans() {
+        let text = AttributedText::new("plain", make_id(0), 14.0, 3u8);
+        assert_eq!(runs(&text), [("plain".to_string(), make_id(0), 3)]);
+        assert!(runs(&AttributedText::new("", make_id(0), 14.0, 3u8)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "char boundaries")]
+    fn test_span_inside_char_panics() {
+        let mut text = AttributedText::new("é", make_id(0), 14.0, 0u8);
+        text.push_span(0..1, SpanAttributes::user_data(1));
+        text.to_text_data();
+    }
+}
@@ -1,12 +1,29 @@
+use std::sync::Arc;
+
+use crate::{font_storage::FontStorage, text::LineHeight};
+
 /// Collection of text runs that will be laid out together.
 ///
 /// The layout code walks over the stored [`TextElement`] values in order and
 /// builds line buffers from them. Keeping the runs grouped here lets the
 /// caller reuse the same builder for repeated layout work.
+///
+/// The runs are shared between clones, so a document can be cloned cheaply to
+/// lay it out at several widths or on other threads. Modifying a clone copies
+/// the runs first.
+///
+/// With the `serde` feature, text data can be saved and loaded. Fonts are stored
+/// by [`TextElement::font_query`] only, since face IDs differ between runs; call
+/// [`Self::with_font_queries`] before serializing data that names faces by ID.
+/// Serializing a run that names its face by ID only fails rather than losing
+/// the font.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TextData<T: Clone> {
-    /// The list of text elements to be processed.
-    pub texts: Vec<TextElement<T>>,
+    /// The list of text elements to be processed, shared between clones.
+    ///
+    /// Use [`Self::texts_mut`] to modify it.
+    pub texts: Arc<Vec<TextElement<T>>>,
 }
 
 /// Single run of text that references a font and size.
@@ -14,17 +31,210 @@ pub struct TextData<T: Clone> {
 /// A run is processed sequentially during layout so we can merge glyphs that
 /// belong to the same font while still respecting wrapping boundaries.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
 pub struct TextElement<T> {
     /// The ID of the font to be used for this text run.
+    ///
+    /// When [`Self::font_query`] is set, this is only the fallback used if the
+    /// query matches no face; `fontdb::ID::dummy()` skips the run instead.
+    ///
+    /// Not serialized; deserialized elements hold `fontdb::ID::dummy()`, so a
+    /// run with a face other than the dummy needs a query to be serialized.
+    #[cfg_attr(feature = "serde", serde(skip, default = "fontdb::ID::dummy"))]
     pub font_id: fontdb::ID,
+    /// Describes the font instead of naming a face, resolved at layout time.
+    ///
+    /// This lets text be built before the fonts it uses are loaded.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub font_query: Option<FontQuery>,
     /// The size of the font in pixels.
     pub font_size: f32,
+    /// Line height of the lines holding this run, overriding the layout's.
+    ///
+    /// A line containing several runs is as tall as all of them need, so e.g. a
+    /// heading span with more leading enlarges only its own lines.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub line_height: Option<LineHeight>,
+    /// Space after the paragraph holding this run, overriding
+    /// [`crate::text::TextLayoutConfig::paragraph_spacing`]. The largest override in a
+    /// paragraph wins.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub paragraph_spacing: Option<f32>,
     /// The actual text content string.
     pub content: String,
     /// Custom user data associated with this text run (e.g., color, style).
     pub user_data: T,
 }
 
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for TextElement<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{Error, SerializeStruct};
+
+        if self.font_query.is_none() && self.font_id != fontdb::ID::dummy() {
+            return Err(S::Error::custom(
+                "the text run names its face by ID only, which is not serialized; \
+                 call `TextData::with_font_queries` first",
+            ));
+        }
+
+        let mut state = serializer.serialize_struct("TextElement", 6)?;
+        match &self.font_query {
+            Some(font_query) => state.serialize_field("font_query", font_query)?,
+            None => state.skip_field("font_query")?,
+        }
+        state.serialize_field("font_size", &self.font_size)?;
+        match &self.line_height {
+            Some(line_height) => state.serialize_field("line_height", line_height)?,
+            None => state.skip_field("line_height")?,
+        }
+        match &self.paragraph_spacing {
+            Some(spacing) => state.serialize_field("paragraph_spacing", spacing)?,
+            None => state.skip_field("paragraph_spacing")?,
+        }
+        state.serialize_field("content", &self.content)?;
+        state.serialize_field("user_data", &self.user_data)?;
+        state.end()
+    }
+}
+
+/// Owned counterpart of [`fontdb::Query`] that can be stored in a [`TextElement`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FontQuery {
+    /// Families tried in order; the first one with any face is used.
+    pub families: Vec<FontFamily>,
+    /// Preferred weight.
+    #[cfg_attr(feature = "serde", serde(with = "super::fontdb_serde::weight"))]
+    pub weight: fontdb::Weight,
+    /// Preferred stretch.
+    #[cfg_attr(feature = "serde", serde(with = "super::fontdb_serde::stretch"))]
+    pub stretch: fontdb::Stretch,
+    /// Preferred style.
+    #[cfg_attr(feature = "serde", serde(with = "super::fontdb_serde::style"))]
+    pub style: fontdb::Style,
+}
+
+impl Default for FontQuery {
+    /// The sans-serif family with regular properties.
+    fn default() -> Self {
+        Self::new([FontFamily::SansSerif])
+    }
+}
+
+impl FontQuery {
+    /// Creates a query for `families` with regular weight, stretch and style.
+    pub fn new(families: impl IntoIterator<Item = FontFamily>) -> Self {
+        Self {
+            families: families.into_iter().collect(),
+            weight: fontdb::Weight::NORMAL,
+            stretch: fontdb::Stretch::Normal,
+            style: fontdb::Style::Normal,
+        }
+    }
+
+    /// Creates a query for a single named family.
+    pub fn family(name: impl Into<String>) -> Self {
+        Self::new([FontFamily::Name(name.into())])
+    }
+
+    /// Returns the query with `weight`.
+    pub fn weight(self, weight: fontdb::Weight) -> Self {
+        Self { weight, ..self }
+    }
+
+    /// Returns the query with `style`.
+    pub fn style(self, style: fontdb::Style) -> Self {
+        Self { style, ..self }
+    }
+
+    /// Returns the query with `stretch`.
+    pub fn stretch(self, stretch: fontdb::Stretch) -> Self {
+        Self { stretch, ..self }
+    }
+
+    /// Returns a query matching the family, weight, stretch and style of face `id`.
+    ///
+    /// Face overrides are taken into account. Returns `None` if the face is unknown
+    /// or has no family name.
+    pub fn for_face(id: fontdb::ID, font_storage: &FontStorage) -> Option<Self> {
+        let face = font_storage.face(id)?;
+        let face_override = font_storage.face_override(id);
+        let family = match face_override.and_then(|o| o.families.as_ref()) {
+            Some(families) => families.first()?.clone(),
+            None => face.families.first()?.0.clone(),
+        };
+        Some(Self {
+            families: vec![FontFamily::Name(family)],
+            weight: face_override.and_then(|o| o.weight).unwrap_or(face.weight),
+            stretch: face_override
+                .and_then(|o| o.stretch)
+                .unwrap_or(face.stretch),
+            style: face_override.and_then(|o| o.style).unwrap_or(face.style),
+        })
+    }
+
+    /// Resolves the query against the fonts currently in `font_storage`.
+    pub fn resolve(&self, font_storage: &FontStorage) -> Option<fontdb::ID> {
+        let families: Vec<fontdb::Family<'_>> =
+            self.families.iter().map(FontFamily::as_family).collect();
+        font_storage.query_id(&fontdb::Query {
+            families: &families,
+            weight: self.weight,
+            stretch: self.stretch,
+            style: self.style,
+        })
+    }
+}
+
+/// Owned counterpart of [`fontdb::Family`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FontFamily {
+    /// A family name, e.g. `"Noto Sans"`.
+    Name(String),
+    /// The generic serif family.
+    Serif,
+    /// The generic sans-serif family.
+    SansSerif,
+    /// The generic cursive family.
+    Cursive,
+    /// The generic fantasy family.
+    Fantasy,
+    /// The generic monospace family.
+    Monospace,
+}
+
+impl FontFamily {
+    fn as_family(&self) -> fontdb::Family<'_> {
+        match self {
+            Self::Name(name) => fontdb::Family::Name(name),
+            Self::Serif => fontdb::Family::Serif,
+            Self::SansSerif => fontdb::Family::SansSerif,
+            Self::Cursive => fontdb::Family::Cursive,
+            Self::Fantasy => fontdb::Family::Fantasy,
+            Self::Monospace => fontdb::Family::Monospace,
+        }
+    }
+}
+
+impl<T: Clone> From<Vec<TextElement<T>>> for TextData<T> {
+    fn from(texts: Vec<TextElement<T>>) -> Self {
+        Self {
+            texts: Arc::new(texts),
+        }
+    }
+}
+
 impl<T: Clone> Default for TextData<T> {
     fn default() -> Self {
         Self::new()
@@ -34,7 +244,9 @@ impl<T: Clone> Default for TextData<T> {
 impl<T: Clone> TextData<T> {
     /// Creates an empty container that can receive text runs.
     pub fn new() -> Self {
-        Self { texts: vec![] }
+        Self {
+            texts: Arc::default(),
+        }
     }
 
     /// Adds a new text run to the layout queue.
@@ -42,11 +254,493 @@ impl<T: Clone> TextData<T> {
     /// Runs are processed in the order they were appended so callers can feed
     /// multiple fonts or styles without copying strings together.
     pub fn append(&mut self, text: TextElement<T>) {
-        self.texts.push(text);
+        self.texts_mut().push(text);
     }
 
     /// Removes all queued text runs so the builder can be reused.
     pub fn clear(&mut self) {
-        self.texts.clear();
+        match Arc::get_mut(&mut self.texts) {
+            Some(texts) => texts.clear(),
+            None => self.texts = Arc::default(),
+        }
+    }
+
+    /// Returns the runs for modification, copying them first if a clone shares them.
+    pub fn texts_mut(&mut self) -> &mut Vec<TextElement<T>> {
+        Arc::make_mut(&mut self.texts)
+    }
+
+    /// Returns the runs, copying them only if a clone shares them.
+    pub fn into_texts(self) -> Vec<TextElement<T>> {
+        Arc::unwrap_or_clone(self.texts)
+    }
+
+    /// Returns a copy of the data in which every run without a font query gets one
+    /// describing its face, so the data can be stored and reloaded in another session.
+    ///
+    /// Runs whose face is unknown to `font_storage` are left unchanged.
+    pub fn with_font_queries(&self, font_storage: &FontStorage) -> TextData<T> {
+        let texts = self
+            .texts
+            .iter()
+            .map(|text| TextElement {
+                font_query: text
+                    .font_query
+                    .clone()
+                    .or_else(|| FontQuery::for_face(text.font_id, font_storage)),
+                ..text.clone()
+            })
+            .collect::<Vec<_>>();
+        TextData::from(texts)
+    }
+
+    /// Returns a [`std::fmt::Write`] sink that appends text in the given style.
+    ///
+    /// ```rust
+    /// # use std::fmt::Write;
+    /// # use suzuri::{fontdb, text::TextData};
+    /// # fn hud(text: &mut TextData<[f32; 4]>, font: fontdb::ID, fps: f32) -> std::fmt::Result {
+    /// text.clear();
+    /// write!(text.styled(font, 16.0, [1.0; 4]), "FPS: {fps:.0}")
+    /// # }
+    /// ```
+    pub fn styled(
+        &mut self,
+        font_id: fontdb::ID,
+        font_size: f32,
+        user_data: T,
+    ) -> TextWriter<'_, T> {
+        TextWriter {
+            data: self,
+            font_id,
+            font_size,
+            user_data,
+            current: None,
+        }
+    }
+
+    /// Starts a [`TextDataBuilder`] with the style used for the first span.
+    pub fn builder(font_id: fontdb::ID, font_size: f32, user_data: T) -> TextDataBuilder<T> {
+        TextDataBuilder::new(font_id, font_size, user_data)
+    }
+}
+
+/// Appends formatted text to a [`TextData`] with a current style.
+///
+/// Created by [`TextData::styled`]. Consecutive writes extend the same element, so
+/// a `write!` with several arguments produces one run without building an
+/// intermediate `String`. Changing the style starts a new element on the next write.
+pub struct TextWriter<'a, T: Clone> {
+    data: &'a mut TextData<T>,
+    font_id: fontdb::ID,
+    font_size: f32,
+    user_data: T,
+    /// Index of the element the writes currently go to.
+    current: Option<usize>,
+}
+
+impl<T: Clone> TextWriter<'_, T> {
+    /// Sets the font of the following writes.
+    pub fn font(&mut self, font_id: fontdb::ID) -> &mut Self {
+        self.font_id = font_id;
+        self.current = None;
+        self
+    }
+
+    /// Sets the font size in pixels of the following writes.
+    pub fn size(&mut self, font_size: f32) -> &mut Self {
+        self.font_size = font_size;
+        self.current = None;
+        self
+    }
+
+    /// Replaces the user data (e.g. color) of the following writes.
+    pub fn user_data(&mut self, user_data: T) -> &mut Self {
+        self.user_data = user_data;
+        self.current = None;
+        self
+    }
+}
+
+impl<T: Clone> std::fmt::Write for TextWriter<'_, T> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+        let index = match self.current {
+            Some(index) => index,
+            None => {
+                self.data.append(TextElement {
+                    font_id: self.font_id,
+                    font_query: None,
+                    line_height: None,
+                    paragraph_spacing: None,
+                    font_size: self.font_size,
+                    content: String::new(),
+                    user_data: self.user_data.clone(),
+                });
+                let index = self.data.texts.len() - 1;
+                self.current = Some(index);
+                index
+            }
+        };
+        self.data.texts_mut()[index].content.push_str(s);
+        Ok(())
+    }
+}
+
+/// Fluent builder that assembles styled spans into a [`TextData`].
+///
+/// The builder keeps a current style (font, size and user data). Each
+/// [`Self::push_str`] call appends a [`TextElement`] with that style, so rich
+/// text is written as a sequence of style changes and strings. Color,
+/// decoration and any other per-span attributes live in the user data.
+///
+/// ```rust
+/// # use suzuri::{fontdb, text::TextData};
+/// # fn build(regular: fontdb::ID, bold: fontdb::ID) -> TextData<[f32; 4]> {
+/// TextData::builder(regular, 16.0, [1.0, 1.0, 1.0, 1.0])
+///     .push_str("Hello, ")
+///     .font(bold)
+///     .user_data([1.0, 0.0, 0.0, 1.0])
+///     .push_str("Suzuri")
+///     .font(regular)
+///     .size(12.0)
+///     .push_str("!")
+///     .build()
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct TextDataBuilder<T: Clone> {
+    data: TextData<T>,
+    font_id: fontdb::ID,
+    font_query: Option<FontQuery>,
+    font_size: f32,
+    line_height: Option<LineHeight>,
+    paragraph_spacing: Option<f32>,
+    user_data: T,
+}
+
+impl<T: Clone> TextDataBuilder<T> {
+    /// Creates a builder with the style used for the first span.
+    pub fn new(font_id: fontdb::ID, font_size: f32, user_data: T) -> Self {
+        Self {
+            data: TextData::new(),
+            font_id,
+            font_query: None,
+            font_size,
+            line_height: None,
+            paragraph_spacing: None,
+            user_data,
+        }
+    }
+
+    /// Sets the font of the following spans.
+    pub fn font(mut self, font_id: fontdb::ID) -> Self {
+        self.font_id = font_id;
+        self.font_query = None;
+        self
+    }
+
+    /// Selects the font of the following spans by description, resolved at layout time.
+    ///
+    /// The font set with [`Self::font`] remains the fallback if nothing matches.
+    pub fn font_query(mut self, query: FontQuery) -> Self {
+        self.font_query = Some(query);
+        self
+    }
+
+    /// Sets the font size in pixels of the following spans.
+    pub fn size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    /// Overrides the line height for the following spans; `None` uses the layout's.
+    pub fn line_height(mut self, line_height: Option<LineHeight>) -> Self {
+        self.line_height = line_height;
+        self
+    }
+
+    /// Overrides the space after the paragraphs of the following spans; `None` uses the layout's.
+    pub fn paragraph_spacing(mut self, paragraph_spacing: Option<f32>) -> Self {
+        self.paragraph_spacing = paragraph_spacing;
+        self
+    }
+
+    /// Replaces the user data (e.g. color) of the following spans.
+    pub fn user_data(mut self, user_data: T) -> Self {
+        self.user_data = user_data;
+        self
+    }
+
+    /// Modifies the user data of the following spans in place, e.g. to toggle
+    /// a decoration flag while keeping the color.
+    pub fn with_user_data(mut self, f: impl FnOnce(&mut T)) -> Self {
+        f(&mut self.user_data);
+        self
+    }
+
+    /// Appends `text` with the current style. Empty strings are skipped.
+    pub fn push_str(mut self, text: &str) -> Self {
+        if !text.is_empty() {
+            self.data.append(TextElement {
+                font_id: self.font_id,
+                font_query: self.font_query.clone(),
+                font_size: self.font_size,
+                line_height: self.line_height,
+                paragraph_spacing: self.paragraph_spacing,
+                content: text.to_string(),
+                user_data: self.user_data.clone(),
+            });
+        }
+        self
+    }
+
+    /// Returns the assembled text data.
+    pub fn build(self) -> TextData<T> {
+        self.data
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{make_id, test_font_storage};
+    use crate::text::{TextLayout, TextLayoutConfig};
+
+    fn glyph_ids(layout: &TextLayout<()>) -> Vec<crate::glyph_id::GlyphId> {
+        layout
+            .lines
+            .iter()
+            .flat_map(|line| line.glyphs.iter().map(|glyph| glyph.glyph_id))
+            .collect()
+    }
+
+    /// Registers a face without font data; enough for face matching.
+    fn push_face(
+        font_storage: &mut FontStorage,
+        family: &str,
+        weight: u16,
+        style: fontdb::Style,
+    ) -> fontdb::ID {
+        let before: Vec<_> = font_storage.faces().map(|face| face.id).collect();
+        font_storage.push_face_info(fontdb::FaceInfo {
+            id: fontdb::ID::dummy(),
+            source: fontdb::Source::Binary(std::sync::Arc::new(Vec::<u8>::new())),
+            index: 0,
+            families: vec![(family.to_string(), fontdb::Language::English_UnitedStates)],
+            post_script_name: format!("{family}-{weight}"),
+            style,
+            weight: fontdb::Weight(weight),
+            stretch: fontdb::Stretch::Condensed,
+            monospaced: false,
+        });
+        font_storage
+            .faces()
+            .map(|face| face.id)
+            .find(|id| !before.contains(id))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_with_font_queries() {
+        let mut font_storage = FontStorage::new();
+        let regular = push_face(&mut font_storage, "Stored", 400, fontdb::Style::Normal);
+        let bold_italic = push_face(&mut font_storage, "Stored", 700, fontdb::Style::Italic);
+
+        let data = TextData::builder(regular, 16.0, ())
+            .push_str("a")
+            .font(bold_italic)
+            .push_str("b")
+            .font_query(FontQuery::family("Kept"))
+            .push_str("c")
+            .font(make_id(99))
+            .push_str("d")
+            .build()
+            .with_font_queries(&font_storage);
+
+        let queries: Vec<_> = data.texts.iter().map(|t| t.font_query.clone()).collect();
+        let stored = FontQuery::family("Stored").stretch(fontdb::Stretch::Condensed);
+        assert_eq!(
+            queries,
+            [
+                Some(stored.clone()),
+                Some(
+                    stored
+                        .weight(fontdb::Weight::BOLD)
+                        .style(fontdb::Style::Italic)
+                ),
+                Some(FontQuery::family("Kept")),
+                None,
+            ]
+        );
+        // The queries find the faces they were made from.
+        assert_eq!(
+            data.texts[1]
+                .font_query
+                .as_ref()
+                .unwrap()
+                .resolve(&font_storage),
+            Some(bold_italic)
+        );
+        assert_eq!(data.texts[0].font_id, regular);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut font_storage = FontStorage::new();
+        let bold = push_face(&mut font_storage, "Stored", 700, fontdb::Style::Oblique);
+
+        let data = TextData::builder(bold, 18.0, [1.0, 0.5, 0.0, 1.0])
+            .line_height(Some(LineHeight::Px(30.0)))
+            .push_str("Saved\n")
+            .font_query(FontQuery::new([FontFamily::Monospace]))
+            .line_height(None)
+            .paragraph_spacing(Some(4.0))
+            .push_str("text")
+            .build()
+            .with_font_queries(&font_storage);
+
+        let json = serde_json::to_string(&data).unwrap();
+        assert!(!json.contains("font_id"));
+        assert!(json.contains("\"weight\":700") && json.contains("\"style\":\"oblique\""));
+
+        let loaded: TextData<[f32; 4]> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.texts.len(), 2);
+        for (loaded, saved) in loaded.texts.iter().zip(data.texts.iter()) {
+            assert_eq!(loaded.font_id, fontdb::ID::dummy());
+            assert_eq!(
+                TextElement {
+                    font_id: saved.font_id,
+                    ..loaded.clone()
+                },
+                *saved
+            );
+        }
+        assert_eq!(
+            loaded.texts[0]
+                .font_query
+                .as_ref()
+                .unwrap()
+                .resolve(&font_storage),
+            Some(bold)
+        );
+
+        // Without a query, the face would be lost.
+        let by_id = TextData::builder(bold, 18.0, 0u8).push_str("Lost").build();
+        assert!(serde_json::to_string(&by_id).is_err());
+        assert!(serde_json::to_string(&by_id.with_font_queries(&font_storage)).is_ok());
+
+        assert!(
+            serde_json::from_str::<FontQuery>(
+                r#"{"families":["Serif"],"weight":400,"stretch":10,"style":"normal"}"#
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_clones_share_runs() {
+        let data = TextData::builder(make_id(1), 12.0, 0u8)
+            .push_str("Shared ")
+            .push_str("document")
+            .build();
+
+        let preview = data.clone();
+        assert!(Arc::ptr_eq(&preview.texts, &data.texts));
+        let worker = {
+            let data = data.clone();
+            std::thread::spawn(move || data.texts.len())
+        };
+        assert_eq!(worker.join().unwrap(), 2);
+
+        // Modifying a clone leaves the original alone.
+        let mut edited = data.clone();
+        edited.append(TextElement {
+            content: "!".to_string(),
+            ..data.texts[0].clone()
+        });
+        edited.texts_mut()[0].content.clear();
+        assert_eq!(data.texts.len(), 2);
+        assert_eq!(data.texts[0].content, "Shared ");
+        assert_eq!(edited.texts.len(), 3);
+
+        let mut cleared = data.clone();
+        cleared.clear();
+        assert!(cleared.texts.is_empty());
+        assert_eq!(data.into_texts().len(), 2);
+    }
+
+    #[test]
+    fn test_styled_writer() {
+        use std::fmt::Write;
+
+        let mut data = TextData::builder(make_id(1), 12.0, 0u8)
+            .push_str("Label ")
+            .build();
+        let fps = 59.94f32;
+        {
+            let mut writer = data.styled(make_id(2), 16.0, 1u8);
+            write!(writer, "FPS: {fps:.1} ({} ms)", 16).unwrap();
+            write!(writer, "").unwrap();
+            writer.user_data(2);
+            write!(writer, "!").unwrap();
+            writer.user_data(2);
+            writer.write_str("").unwrap();
+        }
+
+        let runs: Vec<_> = data
+            .texts
+            .iter()
+            .map(|text| (text.content.as_str(), text.font_id, text.user_data))
+            .collect();
+        assert_eq!(
+            runs,
+            [
+                ("Label ", make_id(1), 0),
+                ("FPS: 59.9 (16 ms)", make_id(2), 1),
+                ("!", make_id(2), 2),
+            ]
+        );
+        assert_eq!(data.texts[1].font_size, 16.0);
+    }
+
+    #[test]
+    fn test_font_query_resolved_at_layout() {
+        let (font_storage, font_id) = test_font_storage();
+        let family = font_storage.face(font_id).unwrap().families[0].0.clone();
+
+        // The queried data names no face, so it could be built before loading fonts.
+        let queried = TextData::builder(fontdb::ID::dummy(), 16.0, ())
+            .font_query(FontQuery::family(&family))
+            .push_str("Query")
+            .build();
+        let missing = TextData::builder(font_id, 16.0, ())
+            .font_query(FontQuery::family("No Such Family"))
+            .push_str("Query")
+            .build();
+        let direct = TextData::builder(font_id, 16.0, ())
+            .push_str("Query")
+            .build();
+
+        let config = TextLayoutConfig::default();
+        let expected = glyph_ids(&direct.layout(&config, &font_storage));
+        let resolved = glyph_ids(&queried.layout(&config, &font_storage));
+        assert_eq!(resolved.len(), expected.len());
+        assert_eq!(
+            font_storage.face(resolved[0].font_id()).unwrap().families[0].0,
+            family
+        );
+
+        // An unmatched query falls back to `font_id`.
+        assert_eq!(glyph_ids(&missing.layout(&config, &font_storage)), expected);
+        let unresolved = TextData::builder(fontdb::ID::dummy(), 16.0, ())
+            .font_query(FontQuery::family("No Such Family"))
+            .push_str("Query")
+            .build();
+        assert!(glyph_ids(&unresolved.layout(&config, &font_storage)).is_empty());
     }
 }
@@ -0,0 +1,69 @@
+//! Serde representations of the `fontdb` face properties, which do not implement serde.
+//!
+//! Weights are stored as numbers (`400`, `700`), stretches as their OS/2 class
+//! (`1` to `9`) and styles as `"normal"`, `"italic"` or `"oblique"`.
+
+use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+pub mod weight {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(weight: &fontdb::Weight, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u16(weight.0)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<fontdb::Weight, D::Error> {
+        u16::deserialize(d).map(fontdb::Weight)
+    }
+}
+
+pub mod stretch {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(stretch: &fontdb::Stretch, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u16(stretch.to_number())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<fontdb::Stretch, D::Error> {
+        use fontdb::Stretch::*;
+
+        match u16::deserialize(d)? {
+            1 => Ok(UltraCondensed),
+            2 => Ok(ExtraCondensed),
+            3 => Ok(Condensed),
+            4 => Ok(SemiCondensed),
+            5 => Ok(Normal),
+            6 => Ok(SemiExpanded),
+            7 => Ok(Expanded),
+            8 => Ok(ExtraExpanded),
+            9 => Ok(UltraExpanded),
+            n => Err(D::Error::custom(format!(
+                "invalid stretch {n}, expected 1 to 9"
+            ))),
+        }
+    }
+}
+
+pub mod style {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(style: &fontdb::Style, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(match style {
+            fontdb::Style::Normal => "normal",
+            fontdb::Style::Italic => "italic",
+            fontdb::Style::Oblique => "oblique",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<fontdb::Style, D::Error> {
+        match String::deserialize(d)?.as_str() {
+            "normal" => Ok(fontdb::Style::Normal),
+            "italic" => Ok(fontdb::Style::Italic),
+            "oblique" => Ok(fontdb::Style::Oblique),
+            other => Err(D::Error::unknown_variant(
+                other,
+                &["normal", "italic", "oblique"],
+            )),
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Conversion of a small, safe HTML subset into [`TextData`].
+//!
+//! Supported markup is `<b>`/`<strong>`, `<i>`/`<em>`, `<u>`, `<span style="color: …">`
+//! and `<br>`, plus character references such as `&amp;` and `&#x263A;`. Other tags
+//! are dropped while their text is kept, and comments are skipped. Whitespace is
+//! collapsed like in HTML, so line breaks only come from `<br>`.
+
+use super::data::{TextData, TextElement};
+
+/// Style of a run of HTML text, resolved from the enclosing tags.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HtmlStyle {
+    /// Inside `<b>` or `<strong>`.
+    pub bold: bool,
+    /// Inside `<i>` or `<em>`.
+    pub italic: bool,
+    /// Inside `<u>`.
+    pub underline: bool,
+    /// Color of the innermost `<span style="color: …">`, as straight `[r, g, b, a]`.
+    pub color: Option<[f32; 4]>,
+}
+
+/// Maps [`HtmlStyle`]s to fonts and user data.
+///
+/// Implemented by the application, which knows which faces to use for bold and
+/// italic text and how colors and decorations are stored in its user data.
+pub trait HtmlStylesheet {
+    /// User data stored in the produced [`TextElement`]s.
+    type UserData: Clone;
+
+    /// Returns the font for text with `style`.
+    fn font(&self, style: &HtmlStyle) -> fontdb::ID;
+
+    /// Returns the font size in pixels for text with `style`.
+    fn font_size(&self, style: &HtmlStyle) -> f32;
+
+    /// Returns the user data for text with `style`.
+    fn user_data(&self, style: &HtmlStyle) -> Self::UserData;
+}
+
+/// Parses `html` into text runs styled through `stylesheet`.
+///
+/// Parsing never fails: malformed markup is treated as text and unmatched
+/// closing tags are ignored. Adjacent text with the same style becomes one run.
+pub fn parse_html<S: HtmlStylesheet>(html: &str, stylesheet: &S) -> TextData<S::UserData> {
+    let mut parser = HtmlParser {
+        stylesheet,
+        data: TextData::new(),
+        stack: Vec::new(),
+        style: HtmlStyle::default(),
+        run: String::new(),
+        run_style: HtmlStyle::default(),
+        pending_space: None,
+    };
+
+    let mut rest = html;
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+        } else if let Some(tag_end) = rest.starts_with('<').then(|| rest.find('>')).flatten()
+            && is_tag(&rest[1..tag_end])
+        {
+            parser.tag(&rest[1..tag_end]);
+            rest = &rest[tag_end + 1..];
+        } else if rest.starts_with('&')
+            && let Some((ch, len)) = parse_char_reference(rest)
+        {
+            parser.push_char(ch);
+            rest = &rest[len..];
+        } else {
+            let mut chars = rest.chars();
+            if let Some(ch) = chars.next() {
+                parser.push_char(ch);
+            }
+            rest = chars.as_str();
+        }
+    }
+
+    parser.flush();
+    parser.data
+}
+
+struct HtmlParser<'a, S: HtmlStylesheet> {
+    stylesheet: &'a S,
+    data: TextData<S::UserData>,
+    /// Open tags with the style that was active before each of them.
+    stack: Vec<(String, HtmlStyle)>,
+    style: HtmlStyle,
+    run: String,
+    run_style: HtmlStyle,
+    /// Style of collapsed whitespace waiting to be emitted before the next character.
+    pending_space: Option<HtmlStyle>,
+}
+
+impl<S: HtmlStylesheet> HtmlParser<'_, S> {
+    fn tag(&mut self, tag: &str) {
+        let tag = tag.trim();
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim().to_ascii_lowercase();
+            if let Some(index) = self.stack.iter().rposition(|(open, _)| *open == name) {
+                self.style = self.stack[index].1;
+                self.stack.truncate(index);
+            }
+            return;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, attributes) = tag
+            .split_once(|c: char| c.is_ascii_whitespace())
+            .unwrap_or((tag, ""));
+        let name = name.to_ascii_lowercase();
+
+        if name == "br" {
+            self.pending_space = None;
+            self.push_raw('\n');
+            return;
+        }
+        if self_closing {
+            return;
+        }
+
+        let previous = self.style;
+        match name.as_str() {
+            "b" | "strong" => self.style.bold = true,
+            "i" | "em" => self.style.italic = true,
+            "u" => self.style.underline = true,
+            "span" => {
+                if let Some(color) = attribute(attributes, "style").and_then(style_color) {
+                    self.style.color = Some(color);
+                }
+            }
+            _ => {}
+        }
+        self.stack.push((name, previous));
+    }
+
+    fn push_char(&mut self, ch: char) {
+        if ch.is_ascii_whitespace() {
+            self.pending_space.get_or_insert(self.style);
+            return;
+        }
+
+        let at_line_start = self.run.ends_with('\n')
+            || (self.run.is_empty()
+                && self
+                    .data
+                    .texts
+                    .last()
+                    .is_none_or(|text| text.content.ends_with('\n')));
+        if let Some(space_style) = self.pending_space.take()
+            && !at_line_start
+        {
+            let style = std::mem::replace(&mut self.style, space_style);
+            self.push_raw(' ');
+            self.style = style;
+        }
+        self.push_raw(ch);
+    }
+
+    fn push_raw(&mut self, ch: char) {
+        if self.style != self.run_style {
+            self.flush();
+            self.run_style = self.style;
+        }
+        self.run.push(ch);
+    }
+
+    fn flush(&mut self) {
+        if self.run.is_empty() {
+            return;
+        }
+        self.data.append(TextElement {
+            font_id: self.stylesheet.font(&self.run_style),
+            font_query: None,
+            line_height: None,
+            paragraph_spacing: None,
+            font_size: self.stylesheet.font_size(&self.run_style),
+            content: std::mem::take(&mut self.run),
+            user_data: self.stylesheet.user_data(&self.run_style),
+        });
+    }
+}
+
+/// Returns `true` if the text between `<` and `>` looks like a tag rather than a stray `<`.
+fn is_tag(inner: &str) -> bool {
+    let name = inner.strip_prefix('/').unwrap_or(inner);
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+/// Returns the value of attribute `name`, quoted or not.
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes.trim_start();
+    while !rest.is_empty() {
+        let key_end = rest
+            .find(|c: char| c == '=' || c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = rest[key_end..].trim_start();
+
+        let mut value = "";
+        if let Some(after_eq) = rest.strip_prefix('=') {
+            let after_eq = after_eq.trim_start();
+            let (parsed, remaining) = match after_eq.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let inner = &after_eq[1..];
+                    let end = inner.find(quote).unwrap_or(inner.len());
+                    (&inner[..end], inner.get(end + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let end = after_eq
+                        .find(|c: char| c.is_ascii_whitespace())
+                        .unwrap_or(after_eq.len());
+                    (&after_eq[..end], &after_eq[end..])
+                }
+            };
+            value = parsed;
+            rest = remaining.trim_start();
+        }
+
+        if key.eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Extracts the `color` declaration of an inline `style` attribute.
+fn style_color(style: &str) -> Option<[f32; 4]> {
+    style.split(';').find_map(|declaration| {
+        let (property, value) = declaration.split_once(':')?;
+        if property.trim().eq_ignore_ascii_case("color") {
+            parse_color(value.trim())
+        } else {
+            None
+        }
+    })
+}
+
+/// Parses `#rgb`, `#rrggbb`, `#rrggbbaa`, `rgb(…)`/`rgba(…)` and a few named colors.
+fn parse_color(value: &str) -> Option<[f32; 4]> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let digit = |i: usize| u8::from_str_radix(hex.get(i..i + 1)?, 16).ok();
+        let byte = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        let channels: [u8; 4] = match hex.len() {
+            3 => [digit(0)? * 17, digit(1)? * 17, digit(2)? * 17, 255],
+            6 => [byte(0)?, byte(2)?, byte(4)?, 255],
+            8 => [byte(0)?, byte(2)?, byte(4)?, byte(6)?],
+            _ => return None,
+        };
+        return Some(channels.map(|c| c as f32 / 255.0));
+    }
+
+    let lower = value.to_ascii_lowercase();
+    if let Some(arguments) = lower
+        .strip_prefix("rgba(")
+        .or_else(|| lower.strip_prefix("rgb("))
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let mut parts = arguments.split(',').map(str::trim);
+        let mut channel = || parts.next()?.parse::<f32>().ok();
+        let [r, g, b] = [channel()?, channel()?, channel()?];
+        let a = channel().unwrap_or(1.0);
+        return Some([r / 255.0, g / 255.0, b / 255.0, a].map(|c| c.clamp(0.0, 1.0)));
+    }
+
+    let named = match lower.as_str() {
+        "black" => [0, 0, 0],
+        "white" => [255, 255, 255],
+        "gray" | "grey" => [128, 128, 128],
+        "red" => [255, 0, 0],
+        "green" => [0, 128, 0],
+        "blue" => [0, 0, 255],
+        "yellow" => [255, 255, 0],
+        "cyan" | "aqua" => [0, 255, 255],
+        "magenta" | "fuchsia" => [255, 0, 255],
+        "orange" => [255, 165, 0],
+        "purple" => [128, 0, 128],
+        _ => return None,
+    };
+    let [r, g, b] = named.map(|c| c as f32 / 255.0);
+    Some([r, g, b, 1.0])
+}
+
+/// Decodes a character reference at the start of `text`, returning it and its byte length.
+fn parse_char_reference(text: &str) -> Option<(char, usize)> {
+    let end = text.find(';')?;
+    let name = &text[1..end];
+    let ch = if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        char::from_u32(code)?
+    } else {
+        match name {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            "nbsp" => '\u{A0}',
+            _ => return None,
+        }
+    };
+    Some((ch, end + 1))
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::make_id;
+
+    struct Sheet;
+
+    impl HtmlStylesheet for Sheet {
+        type UserData = HtmlStyle;
+
+        fn font(&self, style: &HtmlStyle) -> fontdb::ID {
+            make_id(style.bold as u64 * 2 + style.italic as u64)
+        }
+
+        fn font_size(&self, _style: &HtmlStyle) -> f32 {
+            16.0
+        }
+
+        fn user_data(&self, style: &HtmlStyle) -> HtmlStyle {
+            *style
+        }
+    }
+
+    fn runs(html: &str) -> Vec<(String, HtmlStyle)> {
+        parse_html(html, &Sheet)
+            .into_texts()
+            .into_iter()
+            .map(|text| (text.content, text.user_data))
+            .collect()
+    }
+
+    #[test]
+    fn test_nested_styles() {
+        let runs = runs("Hello <b>bold <i>both</i></b> <u>under</u>");
+        let bold = HtmlStyle {
+            bold: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            runs,
+            vec![
+                ("Hello ".to_string(), HtmlStyle::default()),
+                ("bold ".to_string(), bold),
+                (
+                    "both".to_string(),
+                    HtmlStyle {
+                        italic: true,
+                        ..bold
+                    }
+                ),
+                (" ".to_string(), HtmlStyle::default()),
+                (
+                    "under".to_string(),
+                    HtmlStyle {
+                        underline: true,
+                        ..Default::default()
+                    }
+                ),
+            ]
+        );
+
+        let data = parse_html("<B>x</B><EM>y</EM>", &Sheet);
+        assert_eq!(data.texts[0].font_id, make_id(2));
+        assert_eq!(data.texts[1].font_id, make_id(1));
+    }
+
+    #[test]
+    fn test_span_colors() {
+        let runs = runs(
+            r##"<span style="font-weight: 700; color: #f00">a<span style='color:rgb(0, 0, 255)'>b</span></span><span style=color:white>c</span>"##,
+        );
+        assert_eq!(runs[0].1.color, Some([1.0, 0.0, 0.0, 1.0]));
+        assert_eq!(runs[1].1.color, Some([0.0, 0.0, 1.0, 1.0]));
+        assert_eq!(runs[2].1.color, Some([1.0; 4]));
+    }
+
+    #[test]
+    fn test_whitespace_and_breaks() {
+        let runs = runs("  one\n   two<br>three <br/> four ");
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].0, "one two\nthree\nfour");
+    }
+
+    #[test]
+    fn test_entities_and_malformed_markup() {
+        let runs = runs("a &lt; b &amp;&amp; c &#x263A;&#65; &bogus; 1 < 2 </i><script>x</script>");
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].0, "a < b && c ☺A &bogus; 1 < 2 x");
+
+        assert!(parse_html("<!-- comment -->", &Sheet).texts.is_empty());
+    }
+}
@@ -1,11 +1,20 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, ops::Range};
 
-use crate::{glyph_id::GlyphId, text::TextData};
+use crate::{
+    glyph_id::GlyphId,
+    renderer::hex_box,
+    text::{TextData, TextElement, emoji},
+};
 
 /// Default tab size in spaces.
 /// TODO: Move this into TextLayoutConfig when bumping the major version.
 const TAB_SIZE_IN_SPACES: f32 = 4.0;
 
+/// Marks drawn for invisible characters when [`TextLayoutConfig::visualize_whitespace`] is set.
+const SPACE_MARK: char = '\u{00B7}';
+const TAB_MARK: char = '\u{2192}';
+const LINEBREAK_MARK: char = '\u{00B6}';
+
 /// Configuration knobs used by the text layout pipeline.
 ///
 /// All parameters are honored during a single `TextData::layout` call so the
@@ -20,8 +29,14 @@ pub struct TextLayoutConfig {
     pub horizontal_align: HorizontalAlign,
     /// Vertical alignment of the text within the layout box.
     pub vertical_align: VerticalAlign,
+    /// Line height of each line. Overrides `line_height_scale` when set.
+    pub line_height: Option<LineHeight>,
     /// Scaling factor for the line height.
     pub line_height_scale: f32,
+    /// Extra space in pixels between paragraphs, i.e. after each hard line break.
+    ///
+    /// Elements can override it with [`TextElement::paragraph_spacing`].
+    pub paragraph_spacing: f32,
     /// Strategy for wrapping text.
     pub wrap_style: WrapStyle,
     /// Whether to force a hard break when text exceeds width, even in the middle of a word (if word wrapping fails).
@@ -30,6 +45,31 @@ pub struct TextLayoutConfig {
     pub word_separators: HashSet<char, fxhash::FxBuildHasher>,
     /// Characters that trigger a hard line break.
     pub linebreak_char: HashSet<char, fxhash::FxBuildHasher>,
+    /// Whether characters that no font covers are drawn as a box showing their
+    /// code point in hex instead of the font's `.notdef` glyph.
+    pub hex_box_missing_glyphs: bool,
+    /// Whether spaces, tabs, and line breaks are drawn as `·`, `→`, and `¶`.
+    ///
+    /// The marks keep the advance of the character they stand for, so toggling
+    /// this option does not move the surrounding text.
+    pub visualize_whitespace: bool,
+    /// Number of horizontal subpixel positions glyphs are rasterized at.
+    ///
+    /// With `0`, glyphs are drawn at their exact, fractional positions, so moving
+    /// text by fractions of a pixel resamples the same bitmaps and shimmers. With
+    /// `n > 0`, each glyph picks the closest of `n` variants rasterized at offsets
+    /// of `0`, `1/n`, … pixels and is drawn at whole pixels; see
+    /// [`GlyphPosition::raster_origin`]. `4` is a good choice for animated text.
+    /// Each variant takes its own cache entry.
+    pub subpixel_positions: u8,
+    /// Rounding of the font sizes glyphs are rasterized at.
+    ///
+    /// Every distinct size takes its own cache entries, so smoothly animated font
+    /// sizes fill the caches with glyphs drawn once. With snapping, glyphs are
+    /// rasterized at the snapped size while the layout keeps using the exact size.
+    /// Glyphs may then appear slightly larger or smaller, but they reuse the
+    /// cached bitmaps.
+    pub font_size_snapping: FontSizeSnapping,
 }
 
 impl Default for TextLayoutConfig {
@@ -39,12 +79,82 @@ impl Default for TextLayoutConfig {
             max_height: None,
             horizontal_align: HorizontalAlign::Left,
             vertical_align: VerticalAlign::Top,
+            line_height: None,
             line_height_scale: 1.0,
+            paragraph_spacing: 0.0,
             wrap_style: WrapStyle::NoWrap,
             wrap_hard_break: true,
             // TODO: implement tab handling.
             word_separators: [' ', '\t', '\n', '\r'].iter().cloned().collect(),
             linebreak_char: ['\n', '\r'].iter().cloned().collect(),
+            hex_box_missing_glyphs: false,
+            visualize_whitespace: false,
+            subpixel_positions: 0,
+            font_size_snapping: FontSizeSnapping::Exact,
+        }
+    }
+}
+
+/// Height of a line box, resolved per line from the glyphs placed on it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineHeight {
+    /// Multiple of the font's natural line height (ascent, descent and line gap).
+    Scale(f32),
+    /// Absolute height in pixels.
+    Px(f32),
+    /// Multiple of the largest font size on the line, like CSS unitless `line-height`.
+    Relative(f32),
+}
+
+impl LineHeight {
+    /// Returns the line height and the distance from the top of the line to its baseline.
+    ///
+    /// `Px` and `Relative` split the difference to the font's content height evenly
+    /// above and below the glyphs (half-leading), so text stays vertically centered.
+    fn resolve(self, ascent: f32, descent: f32, line_gap: f32, font_size: f32) -> (f32, f32) {
+        let line_height = match self {
+            LineHeight::Scale(scale) => return ((ascent - descent + line_gap) * scale, ascent),
+            LineHeight::Px(px) => px,
+            LineHeight::Relative(factor) => font_size * factor,
+        };
+        let half_leading = (line_height - (ascent - descent)) / 2.0;
+        (line_height, half_leading + ascent)
+    }
+}
+
+/// Rounding of font sizes for rasterization, see [`TextLayoutConfig::font_size_snapping`].
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FontSizeSnapping {
+    /// Glyphs are rasterized at their exact size.
+    #[default]
+    Exact,
+    /// Sizes are rounded to the nearest multiple of this many pixels, e.g. `0.5`.
+    Step(f32),
+    /// Sizes are rounded to the nearest power of this ratio, e.g. `1.05` for steps
+    /// of 5%, so large sizes snap as coarsely as small ones relative to their size.
+    Geometric(f32),
+}
+
+impl FontSizeSnapping {
+    /// Returns `font_size` rounded by the policy.
+    ///
+    /// Steps that are not positive, ratios not above `1.0`, and sizes not above
+    /// zero leave the size unchanged, as does a result of zero.
+    pub fn snap(self, font_size: f32) -> f32 {
+        let snapped = match self {
+            Self::Exact => return font_size,
+            Self::Step(step) if step > 0.0 => (font_size / step).round() * step,
+            Self::Geometric(ratio) if ratio > 1.0 => {
+                ratio.powf((font_size.ln() / ratio.ln()).round())
+            }
+            Self::Step(_) | Self::Geometric(_) => return font_size,
+        };
+        if snapped > 0.0 && snapped.is_finite() {
+            snapped
+        } else {
+            font_size
         }
     }
 }
@@ -96,6 +206,17 @@ pub struct TextLayout<T> {
     pub total_width: f32,
     /// The lines of text in the layout.
     pub lines: Vec<TextLayoutLine<T>>,
+    /// The paragraphs of the layout, in source order.
+    pub paragraphs: Vec<ParagraphInfo>,
+    /// Bounding boxes of the words of the layout, in visual order.
+    pub words: Vec<WordBox>,
+    /// Screen-space rectangle outside of which the layout is not drawn.
+    ///
+    /// `None` draws the whole layout. Renderers skip glyphs entirely outside
+    /// the rectangle and clip the rest (the wgpu renderer via scissor), so a
+    /// scrolled pane can [`translate`](Self::translate) its layout without
+    /// painting outside its viewport. Translating does not move the clip.
+    pub clip_rect: Option<euclid::Box2D<f32, euclid::UnknownUnit>>,
 }
 
 impl<T> TextLayout<T> {
@@ -108,6 +229,68 @@ impl<T> TextLayout<T> {
     pub fn len_glyphs(&self) -> usize {
         self.lines.iter().map(|line| line.glyphs.len()).sum()
     }
+
+    /// Returns the paragraphs of the layout, in source order.
+    pub fn paragraphs(&self) -> &[ParagraphInfo] {
+        &self.paragraphs
+    }
+
+    /// Returns the bounding boxes of the words of the layout, in visual order.
+    pub fn word_boxes(&self) -> &[WordBox] {
+        &self.words
+    }
+
+    /// Sets the screen-space clip rectangle of the layout; see [`TextLayout::clip_rect`].
+    pub fn with_clip_rect(mut self, clip_rect: euclid::Box2D<f32, euclid::UnknownUnit>) -> Self {
+        self.clip_rect = Some(clip_rect);
+        self
+    }
+
+    /// Moves every glyph, line and word box of the layout by `(dx, dy)`.
+    ///
+    /// Layouts are produced with their box at the origin; this places one on screen.
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        for line in &mut self.lines {
+            line.top += dy;
+            line.bottom += dy;
+            for glyph in &mut line.glyphs {
+                glyph.x += dx;
+                glyph.y += dy;
+                glyph.choose_subpixel_variant(self.config.subpixel_positions);
+            }
+        }
+        for word in &mut self.words {
+            word.rect = word.rect.translate(euclid::Vector2D::new(dx, dy));
+        }
+    }
+}
+
+/// Bounding box of a word, or of the part of a word placed on one line.
+///
+/// Words are runs of characters between word separators and line breaks. A
+/// word that is broken over several lines produces one box per line; all of
+/// them share the byte range of the whole word.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WordBox {
+    /// Horizontal extent of the word's glyphs, vertical extent of its line.
+    pub rect: euclid::Box2D<f32, euclid::UnknownUnit>,
+    /// Byte range of the word in the concatenated content of all elements of the `TextData`.
+    pub bytes: Range<usize>,
+    /// Index into [`TextLayout::lines`] of the line holding the box.
+    pub line: usize,
+}
+
+/// A run of text between hard line breaks.
+///
+/// Wrapping may split a paragraph over several lines; a hard line break
+/// always starts a new paragraph.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ParagraphInfo {
+    /// Indices into [`TextLayout::lines`] covered by this paragraph.
+    pub lines: Range<usize>,
+    /// Source characters of this paragraph, in the same units as
+    /// [`GlyphPosition::char_index`]. The terminating line break is not included.
+    pub chars: Range<usize>,
 }
 
 /// A single row of positioned glyphs in the final layout.
@@ -137,9 +320,39 @@ pub struct GlyphPosition<T> {
     pub x: f32,
     /// The absolute Y coordinate of the glyph.
     pub y: f32,
+    /// Index of the source character, counted in `char`s across all elements of the `TextData`.
+    ///
+    /// Whitespace marks and hex boxes point at the character they stand for.
+    pub char_index: usize,
     /// Custom user data associated with this glyph.
     pub user_data: T,
 }
+impl<T> GlyphPosition<T> {
+    /// Returns the top left corner to draw the glyph's bitmap at.
+    ///
+    /// This is [`Self::x`] and [`Self::y`], unless the glyph is a subpixel variant
+    /// (see [`TextLayoutConfig::subpixel_positions`]): its bitmap already contains
+    /// the fractional part of the position, so it is drawn at whole pixels.
+    pub fn raster_origin(&self) -> [f32; 2] {
+        match self.glyph_id.subpixel_offset() {
+            Some(offset) => [(self.x - offset).round(), self.y.round()],
+            None => [self.x, self.y],
+        }
+    }
+
+    /// Switches to the closest of `subpixel_positions` rasterized variants of the
+    /// glyph; keeps the glyph as it is for `0`.
+    pub(crate) fn choose_subpixel_variant(&mut self, subpixel_positions: u8) {
+        if subpixel_positions == 0 {
+            return;
+        }
+        let n = f32::from(subpixel_positions);
+        // The step past the last variant is the next whole pixel.
+        let step = ((self.x - self.x.floor()) * n).round() % n;
+        self.glyph_id = self.glyph_id.with_subpixel_offset(step / n);
+    }
+}
+
 // place holder for eq and hash
 // todo: consider another way
 impl<T: Eq> Eq for GlyphPosition<T> {}
@@ -148,14 +361,25 @@ impl<T: std::hash::Hash> std::hash::Hash for GlyphPosition<T> {
         self.glyph_id.hash(state);
         self.x.to_bits().hash(state);
         self.y.to_bits().hash(state);
+        self.char_index.hash(state);
         self.user_data.hash(state);
     }
 }
 
+/// Source range of a word, collected while the text is processed.
+struct SourceWord {
+    chars: Range<usize>,
+    bytes: Range<usize>,
+}
+
 /// Intermediate storage used while collecting glyphs for a single line.
 struct LineRecord<T> {
     buffer: Option<layout_utl::LayoutBuffer<T>>,
     metrics: Option<fontdue::LineMetrics>,
+    /// Font size paired with `metrics`, used for lines without glyphs.
+    font_size: f32,
+    /// Line height override paired with `metrics`.
+    line_height: Option<LineHeight>,
 }
 
 impl<T: Clone> TextData<T> {
@@ -167,7 +391,7 @@ impl<T: Clone> TextData<T> {
     pub fn measure(
         &self,
         config: &TextLayoutConfig,
-        font_storage: &mut crate::font_storage::FontStorage,
+        font_storage: &crate::font_storage::FontStorage,
     ) -> [f32; 2] {
         let layout = self.layout(config, font_storage);
         [layout.total_width, layout.total_height]
@@ -188,7 +412,7 @@ impl<T: Clone> TextData<T> {
     pub fn layout(
         &self,
         config: &TextLayoutConfig,
-        font_storage: &mut crate::font_storage::FontStorage,
+        font_storage: &crate::font_storage::FontStorage,
     ) -> TextLayout<T> {
         LayoutEngine::new(config, font_storage).layout(&self.texts)
     }
@@ -196,19 +420,35 @@ impl<T: Clone> TextData<T> {
 
 struct LayoutEngine<'a, T> {
     config: &'a TextLayoutConfig,
-    font_storage: &'a mut crate::font_storage::FontStorage,
+    font_storage: &'a crate::font_storage::FontStorage,
 
     // State
     lines: Vec<LineRecord<T>>,
     line_buf: Option<layout_utl::LayoutBuffer<T>>,
     word_buf: Option<Vec<layout_utl::GlyphFragment<T>>>,
     last_line_metrics: Option<fontdue::LineMetrics>,
+    last_font_size: f32,
+    last_line_height: Option<LineHeight>,
+    /// Number of characters in the runs processed so far.
+    char_offset: usize,
+    paragraphs: Vec<ParagraphInfo>,
+    /// Line and character index at which the current paragraph starts.
+    paragraph_start: (usize, usize),
+    /// Largest spacing override of the elements in the current paragraph.
+    paragraph_spacing: Option<f32>,
+    /// Space after each closed paragraph.
+    paragraph_gaps: Vec<f32>,
+    /// Number of bytes in the runs processed so far.
+    byte_offset: usize,
+    words: Vec<SourceWord>,
+    /// Character and byte index at which the current word starts.
+    word_start: Option<(usize, usize)>,
 }
 
 impl<'a, T: Clone> LayoutEngine<'a, T> {
     fn new(
         config: &'a TextLayoutConfig,
-        font_storage: &'a mut crate::font_storage::FontStorage,
+        font_storage: &'a crate::font_storage::FontStorage,
     ) -> Self {
         Self {
             config,
@@ -220,12 +460,24 @@ impl<'a, T: Clone> LayoutEngine<'a, T> {
             word_buf: None,
             // Metrics of the last processed line, used for handling empty lines/newlines.
             last_line_metrics: None,
+            last_font_size: 0.0,
+            last_line_height: None,
+            char_offset: 0,
+            paragraphs: Vec::new(),
+            paragraph_start: (0, 0),
+            paragraph_spacing: None,
+            paragraph_gaps: Vec::new(),
+            byte_offset: 0,
+            words: Vec::new(),
+            word_start: None,
         }
     }
 
-    fn layout(mut self, texts: &[crate::text::TextElement<T>]) -> TextLayout<T> {
+    fn layout(mut self, texts: &[TextElement<T>]) -> TextLayout<T> {
         for text in texts {
             self.process_text_run(text);
+            self.char_offset += text.content.chars().count();
+            self.byte_offset += text.content.len();
         }
 
         // Flush remaining word buffer
@@ -234,18 +486,29 @@ impl<'a, T: Clone> LayoutEngine<'a, T> {
         }
 
         // Ensure the last line is finalized, even if empty (to preserve vertical spacing).
-        self.finalize_line(self.last_line_metrics);
+        self.finalize_line(
+            self.last_line_metrics,
+            self.last_font_size,
+            self.last_line_height,
+        );
+        self.end_paragraph(self.char_offset);
+        self.end_word(self.char_offset, self.byte_offset);
 
         self.build_result()
     }
 
-    fn process_text_run(&mut self, text: &crate::text::TextElement<T>) {
+    fn process_text_run(&mut self, text: &TextElement<T>) {
         use std::sync::Arc;
 
-        let Some(font) = self.font_storage.font(text.font_id) else {
+        let font_id = text
+            .font_query
+            .as_ref()
+            .and_then(|query| query.resolve(self.font_storage))
+            .unwrap_or(text.font_id);
+        let Some(font) = self.font_storage.font(font_id) else {
             return;
         };
-        let Some(line_metric) = font.horizontal_line_metrics(text.font_size) else {
+        let Some(line_metric) = self.font_storage.line_metrics(font_id, text.font_size) else {
             return;
         };
         if text.content.is_empty() {
@@ -253,29 +516,101 @@ impl<'a, T: Clone> LayoutEngine<'a, T> {
         }
 
         self.last_line_metrics = Some(line_metric);
-
-        let create_fragment = |ch: char| {
-            let glyph_idx = font.lookup_glyph_index(ch);
-            let metrics = font.metrics_indexed(glyph_idx, text.font_size);
-            layout_utl::GlyphFragment {
-                ch,
-                glyph_idx,
-                metrics,
-                line_metrics: line_metric,
-                font_id: text.font_id,
-                font_size: text.font_size,
-                font: Arc::clone(&font),
-                user_data: text.user_data.clone(),
-            }
+        self.last_font_size = text.font_size;
+        self.last_line_height = text.line_height;
+        self.apply_paragraph_spacing(text);
+
+        let primary = layout_utl::ResolvedFont {
+            font_id,
+            font: Arc::clone(&font),
+            line_metrics: line_metric,
         };
 
-        for ch in text.content.chars() {
+        let hex_box_missing_glyphs = self.config.hex_box_missing_glyphs;
+        let raster_size = self.config.font_size_snapping.snap(text.font_size);
+        let create_fragment =
+            |ch: char,
+             resolved: &layout_utl::ResolvedFont,
+             char_index: usize,
+             font_storage: &crate::font_storage::FontStorage| {
+                // Glyphs are rasterized at the snapped size but advance by the exact one.
+                let (glyph_idx, metrics, glyph_id) = if hex_box_missing_glyphs
+                    && !resolved.font.has_glyph(ch)
+                {
+                    let mut metrics = hex_box::metrics(ch, raster_size);
+                    metrics.advance_width = hex_box::metrics(ch, text.font_size).advance_width;
+                    (
+                        0,
+                        metrics,
+                        GlyphId::hex_box(resolved.font_id, ch, raster_size),
+                    )
+                } else {
+                    let glyph_idx = resolved.font.lookup_glyph_index(ch);
+                    let metrics = |font_size| {
+                        font_storage
+                            .glyph_metrics(resolved.font_id, glyph_idx, font_size)
+                            .unwrap_or_else(|| resolved.font.metrics_indexed(glyph_idx, font_size))
+                    };
+                    let mut raster_metrics = metrics(raster_size);
+                    if raster_size != text.font_size {
+                        let exact = metrics(text.font_size);
+                        raster_metrics.advance_width = exact.advance_width;
+                        raster_metrics.advance_height = exact.advance_height;
+                    }
+                    (
+                        glyph_idx,
+                        raster_metrics,
+                        GlyphId::new(resolved.font_id, glyph_idx, raster_size),
+                    )
+                };
+                layout_utl::GlyphFragment {
+                    ch,
+                    char_index,
+                    glyph_idx,
+                    glyph_id,
+                    metrics,
+                    line_metrics: resolved.line_metrics,
+                    font_id: resolved.font_id,
+                    font_size: text.font_size,
+                    font: Arc::clone(&resolved.font),
+                    line_height: text.line_height,
+                    user_data: text.user_data.clone(),
+                }
+            };
+
+        // Draws `mark` in place of the invisible `ch`. The fragment keeps `ch` so
+        // wrapping rules still see whitespace, and uses the given advance.
+        let visualize_whitespace = self.config.visualize_whitespace;
+        let create_mark_fragment =
+            |ch: char,
+             mark: char,
+             advance_width: f32,
+             char_index: usize,
+             font_storage: &crate::font_storage::FontStorage| {
+                if !visualize_whitespace || !primary.font.has_glyph(mark) {
+                    return None;
+                }
+                let mut fragment = create_fragment(mark, &primary, char_index, font_storage);
+                fragment.ch = ch;
+                fragment.metrics.advance_width = advance_width;
+                Some(fragment)
+            };
+
+        let char_offset = self.char_offset;
+        let byte_offset = self.byte_offset;
+        let mut chars = text.content.char_indices().enumerate().peekable();
+        while let Some((index, (byte, ch))) = chars.next() {
+            let next = chars.peek().map(|&(_, (_, next))| next);
+            let char_index = char_offset + index;
+            let byte_index = byte_offset + byte;
+
             match layout_utl::classify_char(
                 ch,
                 &self.config.word_separators,
                 &self.config.linebreak_char,
             ) {
                 layout_utl::CharBehavior::LineBreak => {
+                    self.end_word(char_index, byte_index);
                     // Newline characters always terminate the current line.
                     // If there is a pending word, append it to the current line first.
                     if let Some(word) = self.word_buf.take() {
@@ -284,21 +619,52 @@ impl<'a, T: Clone> LayoutEngine<'a, T> {
 
                     // We explicitly do not append the newline glyph to the layout.
                     // Instead, we just finalize the line with the current metrics.
-                    self.finalize_line(Some(line_metric));
+                    // The visualization mark may overhang the wrap width like a caret would.
+                    if let Some(fragment) =
+                        create_mark_fragment(ch, LINEBREAK_MARK, 0.0, char_index, self.font_storage)
+                    {
+                        self.line_buf
+                            .get_or_insert_with(|| {
+                                layout_utl::LayoutBuffer::new_empty(
+                                    &line_metric,
+                                    text.font_size,
+                                    text.line_height,
+                                )
+                            })
+                            .push_fragment(&fragment, self.font_storage);
+                    }
+                    self.finalize_line(Some(line_metric), text.font_size, text.line_height);
+                    self.end_paragraph(char_index);
+                    // The rest of the run belongs to the next paragraph.
+                    self.apply_paragraph_spacing(text);
                 }
                 layout_utl::CharBehavior::WordBreak { render_glyph } => {
+                    self.end_word(char_index, byte_index);
                     // A separator (e.g., space) marks the end of a word.
                     if let Some(word) = self.word_buf.take() {
                         self.append_fragments_with_rules(&word, true);
                     }
 
                     if render_glyph {
-                        let fragment = create_fragment(ch);
+                        let mut fragment =
+                            create_fragment(ch, &primary, char_index, self.font_storage);
+                        if ch.is_whitespace()
+                            && let Some(mark) = create_mark_fragment(
+                                ch,
+                                SPACE_MARK,
+                                fragment.metrics.advance_width,
+                                char_index,
+                                self.font_storage,
+                            )
+                        {
+                            fragment = mark;
+                        }
                         // Append the separator itself (not part of the `word_buf`).
                         self.append_fragments_with_rules(std::slice::from_ref(&fragment), false);
                     }
                 }
                 layout_utl::CharBehavior::Tab => {
+                    self.end_word(char_index, byte_index);
                     // Tab character works as a word separator and also adds spacing.
                     if let Some(word) = self.word_buf.take() {
                         self.append_fragments_with_rules(&word, true);
@@ -306,23 +672,43 @@ impl<'a, T: Clone> LayoutEngine<'a, T> {
 
                     // Ensure we have a line buffer to apply tab spacing to.
                     if self.line_buf.is_none() {
-                        self.line_buf = Some(layout_utl::LayoutBuffer::new_empty(&line_metric));
+                        self.line_buf = Some(layout_utl::LayoutBuffer::new_empty(
+                            &line_metric,
+                            text.font_size,
+                            text.line_height,
+                        ));
                     }
 
                     if let Some(line) = self.line_buf.as_mut() {
                         // Calculate tab width based on space width.
                         let space_glyph_idx = font.lookup_glyph_index(' ');
-                        let space_metrics = font.metrics_indexed(space_glyph_idx, text.font_size);
+                        let space_metrics = self
+                            .font_storage
+                            .glyph_metrics(font_id, space_glyph_idx, text.font_size)
+                            .unwrap_or_else(|| {
+                                font.metrics_indexed(space_glyph_idx, text.font_size)
+                            });
                         let tab_width = space_metrics.advance_width * TAB_SIZE_IN_SPACES;
 
                         // Move next_origin_x to the next tab stop.
                         let current_x = line.next_origin_x;
                         let next_stop = (current_x / tab_width).floor() * tab_width + tab_width;
+                        if let Some(fragment) = create_mark_fragment(
+                            ch,
+                            TAB_MARK,
+                            next_stop - current_x,
+                            char_index,
+                            self.font_storage,
+                        ) {
+                            line.push_fragment(&fragment, self.font_storage);
+                        }
                         line.next_origin_x = next_stop;
                     }
                 }
                 layout_utl::CharBehavior::Regular => {
-                    let fragment = create_fragment(ch);
+                    self.word_start.get_or_insert((char_index, byte_index));
+                    let resolved = self.resolve_font(ch, next, &primary, text.font_size);
+                    let fragment = create_fragment(ch, &resolved, char_index, self.font_storage);
                     if matches!(self.config.wrap_style, WrapStyle::CharWrap) {
                         // In CharWrap mode, we treat every character as an independent unit,
                         // bypassing the word buffer.
@@ -342,6 +728,45 @@ impl<'a, T: Clone> LayoutEngine<'a, T> {
         }
     }
 
+    /// Picks the face used to render `ch`.
+    ///
+    /// Clusters with emoji presentation go through the storage's emoji fallback
+    /// chain first. Characters missing from the run's font are taken from its
+    /// fallback chain; everything else uses the run's own font.
+    fn resolve_font(
+        &mut self,
+        ch: char,
+        next: Option<char>,
+        primary: &layout_utl::ResolvedFont,
+        font_size: f32,
+    ) -> layout_utl::ResolvedFont {
+        if emoji::has_emoji_presentation(ch, next)
+            && let Some((font_id, font)) = self.font_storage.emoji_font_for(ch)
+            && let Some(line_metrics) = self.font_storage.line_metrics(font_id, font_size)
+        {
+            return layout_utl::ResolvedFont {
+                font_id,
+                font,
+                line_metrics,
+            };
+        }
+
+        if !ch.is_control()
+            && !ch.is_whitespace()
+            && !primary.font.has_glyph(ch)
+            && let Some((font_id, font)) = self.font_storage.fallback_font_for(primary.font_id, ch)
+            && let Some(line_metrics) = self.font_storage.line_metrics(font_id, font_size)
+        {
+            return layout_utl::ResolvedFont {
+                font_id,
+                font,
+                line_metrics,
+            };
+        }
+
+        primary.clone()
+    }
+
     fn append_fragments_with_rules(
         &mut self,
         fragments: &[layout_utl::GlyphFragment<T>],
@@ -470,11 +895,49 @@ impl<'a, T: Clone> LayoutEngine<'a, T> {
         }
     }
 
-    fn finalize_line(&mut self, metrics: Option<fontdue::LineMetrics>) {
+    fn finalize_line(
+        &mut self,
+        metrics: Option<fontdue::LineMetrics>,
+        font_size: f32,
+        line_height: Option<LineHeight>,
+    ) {
         if self.line_buf.is_some() || metrics.is_some() {
             self.lines.push(LineRecord {
                 buffer: self.line_buf.take(),
                 metrics,
+                font_size,
+                line_height,
+            });
+        }
+    }
+
+    /// Lets the paragraph spacing override of `text` apply to the current paragraph.
+    fn apply_paragraph_spacing(&mut self, text: &TextElement<T>) {
+        if let Some(spacing) = text.paragraph_spacing {
+            self.paragraph_spacing =
+                Some(self.paragraph_spacing.map_or(spacing, |s| s.max(spacing)));
+        }
+    }
+
+    /// Closes the current paragraph at `char_end` and starts the next one after it.
+    fn end_paragraph(&mut self, char_end: usize) {
+        let (line_start, char_start) = self.paragraph_start;
+        self.paragraphs.push(ParagraphInfo {
+            lines: line_start..self.lines.len(),
+            chars: char_start..char_end,
+        });
+        self.paragraph_start = (self.lines.len(), char_end + 1);
+        let spacing = self.paragraph_spacing.take();
+        self.paragraph_gaps
+            .push(spacing.unwrap_or(self.config.paragraph_spacing));
+    }
+
+    /// Closes the current word, if any, at the given character and byte index.
+    fn end_word(&mut self, char_end: usize, byte_end: usize) {
+        if let Some((char_start, byte_start)) = self.word_start.take() {
+            self.words.push(SourceWord {
+                chars: char_start..char_end,
+                bytes: byte_start..byte_end,
             });
         }
     }
@@ -484,11 +947,13 @@ impl<'a, T: Clone> LayoutEngine<'a, T> {
             self.lines.push(LineRecord {
                 buffer: self.line_buf.take(),
                 metrics: None,
+                font_size: 0.0,
+                line_height: None,
             });
         }
     }
 
-    fn build_result(self) -> TextLayout<T> {
+    fn build_result(mut self) -> TextLayout<T> {
         /// Final measurements for a single laid-out line before alignment.
         struct LineData<T> {
             width: f32,
@@ -500,35 +965,60 @@ impl<'a, T: Clone> LayoutEngine<'a, T> {
         let mut layout_lines: Vec<LineData<T>> = Vec::new();
         let mut cursor_y = 0.0;
         let mut max_line_width: f32 = 0.0;
-        let line_height_scale = self.config.line_height_scale;
+        let line_height = self
+            .config
+            .line_height
+            .unwrap_or(LineHeight::Scale(self.config.line_height_scale));
+
+        // Space after each line that ends a paragraph; none after the last line.
+        let mut gaps_after_line = vec![0.0; self.lines.len()];
+        for (paragraph, &gap) in self.paragraphs.iter().zip(&self.paragraph_gaps) {
+            if let Some(last_line) = paragraph.lines.end.checked_sub(1)
+                && paragraph.lines.start <= last_line
+            {
+                gaps_after_line[last_line] = gap;
+            }
+        }
+        let line_count = self.lines.len();
 
         // Convert the abstract "lines" (buffers) into physical "LineData" (coordinates).
-        for record in self.lines {
-            let (width, ascent, descent, line_gap, glyphs) = if let Some(buffer) = record.buffer {
-                let (ascent, descent, line_gap) = buffer.line_metrics();
-                let width_value = buffer.width();
-                let glyphs = buffer.glyphs;
-                (width_value, ascent, descent, line_gap, glyphs)
-            } else if let Some(metrics) = record.metrics {
-                // Empty line but with valid metrics (e.g., from newline char).
-                (
-                    0.0,
-                    metrics.ascent,
-                    metrics.descent,
-                    metrics.line_gap,
-                    Vec::new(),
-                )
-            } else {
-                // Fallback for completely empty state (should happen rarely).
-                (0.0, 0.0, 0.0, 0.0, Vec::new())
-            };
+        for (index, record) in std::mem::take(&mut self.lines).into_iter().enumerate() {
+            let (width, (resolved_line_height, baseline_offset), glyphs) =
+                if let Some(buffer) = record.buffer {
+                    let (ascent, descent, line_gap) = buffer.line_metrics();
+                    // Spans with their own line height widen the line box around the baseline.
+                    let default = buffer.has_default_line_height.then(|| {
+                        line_height.resolve(ascent, descent, line_gap, buffer.max_font_size)
+                    });
+                    let extents = match (default, buffer.line_height_extents) {
+                        (Some((height, baseline)), Some([above, below])) => {
+                            let above = above.max(baseline);
+                            (above + below.max(height - baseline), above)
+                        }
+                        (Some(default), None) => default,
+                        (None, Some([above, below])) => (above + below, above),
+                        (None, None) => (0.0, 0.0),
+                    };
+                    (buffer.width(), extents, buffer.glyphs)
+                } else if let Some(metrics) = record.metrics {
+                    // Empty line but with valid metrics (e.g., from newline char).
+                    let extents = record.line_height.unwrap_or(line_height).resolve(
+                        metrics.ascent,
+                        metrics.descent,
+                        metrics.line_gap,
+                        record.font_size,
+                    );
+                    (0.0, extents, Vec::new())
+                } else {
+                    // Fallback for completely empty state (should happen rarely).
+                    (0.0, line_height.resolve(0.0, 0.0, 0.0, 0.0), Vec::new())
+                };
 
             max_line_width = max_line_width.max(width);
-            let raw_line_height = ascent - descent + line_gap;
-            let scaled_line_height = (raw_line_height * line_height_scale).max(0.0);
+            let scaled_line_height = resolved_line_height.max(0.0);
 
             // Baseline is relative to the *top* of the line box.
-            let baseline = cursor_y + ascent;
+            let baseline = cursor_y + baseline_offset;
 
             let mut glyph_positions = Vec::with_capacity(glyphs.len());
             for mut glyph in glyphs {
@@ -544,6 +1034,10 @@ impl<'a, T: Clone> LayoutEngine<'a, T> {
                 y: cursor_y - scaled_line_height,
                 glyphs: glyph_positions,
             });
+
+            if index + 1 < line_count {
+                cursor_y += gaps_after_line[index];
+            }
         }
 
         let total_height = cursor_y;
@@ -588,13 +1082,73 @@ impl<'a, T: Clone> LayoutEngine<'a, T> {
             });
         }
 
+        let words = self.collect_word_boxes(&lines_out);
+
+        for line in &mut lines_out {
+            for glyph in &mut line.glyphs {
+                glyph.choose_subpixel_variant(self.config.subpixel_positions);
+            }
+        }
+
         TextLayout {
             config: self.config.clone(),
             total_height,
             total_width,
             lines: lines_out,
+            paragraphs: self.paragraphs,
+            words,
+            clip_rect: None,
         }
     }
+
+    /// Groups the glyphs of each line by source word and measures their extents.
+    fn collect_word_boxes(&mut self, lines: &[TextLayoutLine<T>]) -> Vec<WordBox> {
+        let mut boxes: Vec<WordBox> = Vec::new();
+
+        for (line_index, line) in lines.iter().enumerate() {
+            // Index of the word the last box on this line belongs to.
+            let mut current_word: Option<usize> = None;
+
+            for glyph in &line.glyphs {
+                let word_index = self
+                    .words
+                    .partition_point(|word| word.chars.end <= glyph.char_index);
+                let Some(word) = self.words.get(word_index) else {
+                    current_word = None;
+                    continue;
+                };
+                if !word.chars.contains(&glyph.char_index) {
+                    current_word = None;
+                    continue;
+                }
+
+                let width = crate::renderer::glyph_metrics(&glyph.glyph_id, self.font_storage)
+                    .map(|metrics| metrics.width as f32)
+                    .unwrap_or(0.0);
+                let (min_x, max_x) = (glyph.x, glyph.x + width);
+
+                match boxes.last_mut() {
+                    Some(word_box) if current_word == Some(word_index) => {
+                        word_box.rect.min.x = word_box.rect.min.x.min(min_x);
+                        word_box.rect.max.x = word_box.rect.max.x.max(max_x);
+                    }
+                    _ => {
+                        boxes.push(WordBox {
+                            rect: euclid::Box2D::new(
+                                euclid::Point2D::new(min_x, line.top),
+                                euclid::Point2D::new(max_x, line.bottom),
+                            ),
+                            bytes: word.bytes.clone(),
+                            line: line_index,
+                        });
+                        current_word = Some(word_index);
+                    }
+                }
+            }
+        }
+
+        boxes
+    }
 }
 
 mod layout_utl {
@@ -638,13 +1192,21 @@ mod layout_utl {
             };
         }
 
-        if ch.is_control() {
+        if ch.is_control() || emoji::is_presentation_modifier(ch) {
             return CharBehavior::Ignore;
         }
 
         CharBehavior::Regular
     }
 
+    /// Face chosen for a character, together with its line metrics at the run's size.
+    #[derive(Clone)]
+    pub struct ResolvedFont {
+        pub font_id: fontdb::ID,
+        pub font: Arc<fontdue::Font>,
+        pub line_metrics: fontdue::LineMetrics,
+    }
+
     #[derive(Clone)]
     /// Precomputed glyph data used to build layout buffers.
     ///
@@ -652,12 +1214,15 @@ mod layout_utl {
     /// fetching the same font from storage.
     pub struct GlyphFragment<T> {
         pub ch: char,
+        pub char_index: usize,
         pub glyph_idx: u16,
+        pub glyph_id: GlyphId,
         pub metrics: fontdue::Metrics,
         pub line_metrics: fontdue::LineMetrics,
         pub font_id: fontdb::ID,
         pub font_size: f32,
         pub font: Arc<fontdue::Font>,
+        pub line_height: Option<LineHeight>,
         pub user_data: T,
     }
 
@@ -672,6 +1237,11 @@ mod layout_utl {
         pub max_accent: f32,
         pub max_descent: f32,
         pub max_line_gap: f32,
+        pub max_font_size: f32,
+        /// Whether any glyph uses the layout's line height rather than an override.
+        pub has_default_line_height: bool,
+        /// Largest extents above and below the baseline of glyphs with a line height override.
+        pub line_height_extents: Option<[f32; 2]>,
 
         pub first_glyph: Option<u16>,
         pub first_font_id: Option<fontdb::ID>,
@@ -687,12 +1257,19 @@ mod layout_utl {
 
     impl<T: Clone> LayoutBuffer<T> {
         /// Creates an empty buffer with valid line metrics but no glyphs.
-        pub fn new_empty(line_metrics: &fontdue::LineMetrics) -> Self {
-            Self {
+        pub fn new_empty(
+            line_metrics: &fontdue::LineMetrics,
+            font_size: f32,
+            line_height: Option<LineHeight>,
+        ) -> Self {
+            let mut buffer = Self {
                 instance_length: 0.0,
                 max_accent: line_metrics.ascent,
                 max_descent: line_metrics.descent,
                 max_line_gap: line_metrics.line_gap,
+                max_font_size: font_size,
+                has_default_line_height: false,
+                line_height_extents: None,
                 first_glyph: None,
                 first_font_id: None,
                 first_font_size: None,
@@ -702,7 +1279,9 @@ mod layout_utl {
                 last_metrics: None,
                 next_origin_x: 0.0,
                 glyphs: vec![],
-            }
+            };
+            buffer.record_line_height(line_height, line_metrics, font_size);
+            buffer
         }
 
         /// Creates a buffer containing a single glyph fragment.
@@ -710,6 +1289,8 @@ mod layout_utl {
         /// The glyph is stored relative to the baseline so it can be shifted
         /// after all fragments for the line are known.
         pub fn new(
+            char_index: usize,
+            glyph_id: GlyphId,
             glyph_idx: u16,
             metrics: &fontdue::Metrics,
             line_metrics: &fontdue::LineMetrics,
@@ -722,6 +1303,9 @@ mod layout_utl {
                 max_accent: line_metrics.ascent,
                 max_descent: line_metrics.descent,
                 max_line_gap: line_metrics.line_gap,
+                max_font_size: font_size,
+                has_default_line_height: false,
+                line_height_extents: None,
                 first_glyph: Some(glyph_idx),
                 first_font_id: Some(font_id),
                 first_font_size: Some(font_size),
@@ -734,7 +1318,8 @@ mod layout_utl {
             };
 
             buffer.glyphs.push(GlyphPosition {
-                glyph_id: GlyphId::new(font_id, glyph_idx, font_size),
+                glyph_id,
+                char_index,
                 x: metrics.xmin as f32,
                 y: -(metrics.ymin as f32 + metrics.height as f32),
                 user_data,
@@ -750,6 +1335,8 @@ mod layout_utl {
         /// layout accurate while avoiding redundant lookups.
         pub fn push(
             &mut self,
+            char_index: usize,
+            glyph_id: GlyphId,
             glyph_idx: u16,
             metrics: &fontdue::Metrics,
             line_metrics: &fontdue::LineMetrics,
@@ -757,7 +1344,7 @@ mod layout_utl {
             font_id: fontdb::ID,
             font_size: f32,
             user_data: T,
-            _font_storage: &mut FontStorage,
+            _font_storage: &FontStorage,
         ) {
             let kerning = if let (Some(last_id), Some(last_size), Some(last_glyph)) =
                 (self.last_font_id, self.last_font_size, self.last_glyph)
@@ -777,6 +1364,7 @@ mod layout_utl {
             self.max_accent = self.max_accent.max(line_metrics.ascent);
             self.max_descent = self.max_descent.max(line_metrics.descent);
             self.max_line_gap = self.max_line_gap.max(line_metrics.line_gap);
+            self.max_font_size = self.max_font_size.max(font_size);
 
             if self.first_glyph.is_none() {
                 self.first_glyph = Some(glyph_idx);
@@ -790,7 +1378,8 @@ mod layout_utl {
             self.last_metrics = Some(*metrics);
             self.next_origin_x = new_next_origin_x;
             self.glyphs.push(GlyphPosition {
-                glyph_id: GlyphId::new(font_id, glyph_idx, font_size),
+                glyph_id,
+                char_index,
                 x: current_origin_x + metrics.xmin as f32,
                 y: -(metrics.ymin as f32 + metrics.height as f32),
                 user_data,
@@ -802,7 +1391,7 @@ mod layout_utl {
         /// When the buffers originate from the same font and size we apply
         /// kerning between the boundary glyphs; otherwise the buffers are joined
         /// using the recorded advance of the current buffer.
-        pub fn concat(&mut self, other: LayoutBuffer<T>, font_storage: &mut FontStorage) {
+        pub fn concat(&mut self, other: LayoutBuffer<T>, font_storage: &FontStorage) {
             let kerning = if let (
                 Some(last_id),
                 Some(last_size),
@@ -838,6 +1427,11 @@ mod layout_utl {
             self.max_accent = self.max_accent.max(other.max_accent);
             self.max_descent = self.max_descent.max(other.max_descent);
             self.max_line_gap = self.max_line_gap.max(other.max_line_gap);
+            self.max_font_size = self.max_font_size.max(other.max_font_size);
+            self.has_default_line_height |= other.has_default_line_height;
+            if let Some(extents) = other.line_height_extents {
+                self.union_line_height_extents(extents);
+            }
 
             if self.first_glyph.is_none() {
                 self.first_glyph = other.first_glyph;
@@ -876,7 +1470,7 @@ mod layout_utl {
         pub fn projected_concat_length(
             &self,
             other: &LayoutBuffer<T>,
-            font_storage: &mut FontStorage,
+            font_storage: &FontStorage,
         ) -> f32 {
             let kerning = if let (
                 Some(last_id),
@@ -909,6 +1503,34 @@ mod layout_utl {
             x_offset + other.instance_length
         }
 
+        /// Records the line height a glyph with the given metrics asks for.
+        pub fn record_line_height(
+            &mut self,
+            line_height: Option<LineHeight>,
+            line_metrics: &fontdue::LineMetrics,
+            font_size: f32,
+        ) {
+            match line_height {
+                Some(line_height) => {
+                    let (height, baseline) = line_height.resolve(
+                        line_metrics.ascent,
+                        line_metrics.descent,
+                        line_metrics.line_gap,
+                        font_size,
+                    );
+                    self.union_line_height_extents([baseline, height - baseline]);
+                }
+                None => self.has_default_line_height = true,
+            }
+        }
+
+        fn union_line_height_extents(&mut self, [above, below]: [f32; 2]) {
+            self.line_height_extents = Some(match self.line_height_extents {
+                Some([a, b]) => [a.max(above), b.max(below)],
+                None => [above, below],
+            });
+        }
+
         /// Returns line metrics derived from the buffered glyph fragments.
         pub fn line_metrics(&self) -> (f32, f32, f32) {
             (self.max_accent, self.max_descent, self.max_line_gap)
@@ -920,10 +1542,12 @@ mod layout_utl {
         /// glyphs to measure or position.
         pub fn from_fragments(
             fragments: &[GlyphFragment<T>],
-            font_storage: &mut FontStorage,
+            font_storage: &FontStorage,
         ) -> Option<LayoutBuffer<T>> {
             let first = fragments.first()?;
             let mut buffer = LayoutBuffer::new(
+                first.char_index,
+                first.glyph_id,
                 first.glyph_idx,
                 &first.metrics,
                 &first.line_metrics,
@@ -931,21 +1555,237 @@ mod layout_utl {
                 first.font_size,
                 first.user_data.clone(),
             );
+            buffer.record_line_height(first.line_height, &first.line_metrics, first.font_size);
 
             for fragment in fragments.iter().skip(1) {
-                buffer.push(
-                    fragment.glyph_idx,
-                    &fragment.metrics,
-                    &fragment.line_metrics,
-                    fragment.font.as_ref(),
-                    fragment.font_id,
-                    fragment.font_size,
-                    fragment.user_data.clone(),
-                    font_storage,
-                );
+                buffer.push_fragment(fragment, font_storage);
             }
 
             Some(buffer)
         }
+
+        /// Appends a precomputed glyph fragment, see [`Self::push`].
+        pub fn push_fragment(&mut self, fragment: &GlyphFragment<T>, font_storage: &FontStorage) {
+            self.push(
+                fragment.char_index,
+                fragment.glyph_id,
+                fragment.glyph_idx,
+                &fragment.metrics,
+                &fragment.line_metrics,
+                fragment.font.as_ref(),
+                fragment.font_id,
+                fragment.font_size,
+                fragment.user_data.clone(),
+                font_storage,
+            );
+            self.record_line_height(
+                fragment.line_height,
+                &fragment.line_metrics,
+                fragment.font_size,
+            );
+        }
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{SECOND_TEST_FONT, test_font_storage};
+
+    fn heights(layout: &TextLayout<()>) -> Vec<f32> {
+        layout.lines.iter().map(|line| line.line_height).collect()
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn test_span_line_height_override() {
+        let (font_storage, font_id) = test_font_storage();
+        let config = TextLayoutConfig::default();
+        let plain = TextData::builder(font_id, 16.0, ())
+            .push_str("Title\nbody a")
+            .build()
+            .layout(&config, &font_storage);
+        let default_height = plain.lines[0].line_height;
+
+        let layout = TextData::builder(font_id, 16.0, ())
+            .line_height(Some(LineHeight::Px(60.0)))
+            .push_str("Title\n")
+            .line_height(None)
+            .push_str("body ")
+            .line_height(Some(LineHeight::Px(2.0)))
+            .push_str("a")
+            .build()
+            .layout(&config, &font_storage);
+
+        // The heading line takes its own height; a smaller override cannot shrink a line.
+        let line_heights = heights(&layout);
+        assert!(close(line_heights[0], 60.0) && close(line_heights[1], default_height));
+        assert!(close(layout.lines[1].top, 60.0));
+        assert!(close(layout.total_height, 60.0 + default_height));
+
+        // Half-leading keeps the heading's glyphs centered in the taller line.
+        let shift = layout.lines[0].glyphs[0].y - plain.lines[0].glyphs[0].y;
+        assert!(shift > 0.0 && shift < 60.0 - default_height);
+        assert!(close(
+            layout.lines[1].glyphs[0].y - layout.lines[1].top,
+            plain.lines[1].glyphs[0].y - plain.lines[1].top
+        ));
+
+        let small = TextData::builder(font_id, 16.0, ())
+            .line_height(Some(LineHeight::Px(5.0)))
+            .push_str("tight")
+            .build()
+            .layout(&config, &font_storage);
+        assert!(close(heights(&small)[0], 5.0));
+    }
+
+    #[test]
+    fn test_font_size_snapping() {
+        assert_eq!(FontSizeSnapping::Step(0.5).snap(16.2), 16.0);
+        assert_eq!(FontSizeSnapping::Step(0.5).snap(16.3), 16.5);
+        assert_eq!(FontSizeSnapping::Step(0.0).snap(16.3), 16.3);
+        assert_eq!(FontSizeSnapping::Step(0.5).snap(0.1), 0.1);
+        let geometric = FontSizeSnapping::Geometric(2.0);
+        assert_eq!(geometric.snap(15.0), 16.0);
+        assert_eq!(geometric.snap(40.0), 32.0);
+
+        let (font_storage, font_id) = test_font_storage();
+        let config = TextLayoutConfig {
+            font_size_snapping: FontSizeSnapping::Step(0.5),
+            ..Default::default()
+        };
+        let layout = |font_size, config: &TextLayoutConfig| {
+            TextData::builder(font_id, font_size, ())
+                .push_str("Hamburgefonts")
+                .build()
+                .layout(config, &font_storage)
+        };
+        let (a, b) = (layout(16.1, &config), layout(16.2, &config));
+        let exact = layout(16.2, &TextLayoutConfig::default());
+        let pen = |glyph: &GlyphPosition<()>| {
+            let id = glyph.glyph_id;
+            let metrics = font_storage
+                .glyph_metrics(font_id, id.glyph_index(), id.font_size())
+                .unwrap();
+            glyph.x - metrics.xmin as f32
+        };
+        for ((a, b), exact) in a.lines[0]
+            .glyphs
+            .iter()
+            .zip(&b.lines[0].glyphs)
+            .zip(&exact.lines[0].glyphs)
+        {
+            // Both sizes reuse the glyphs of 16px but advance by their own size.
+            assert_eq!(a.glyph_id, b.glyph_id);
+            assert_eq!(b.glyph_id.font_size(), 16.0);
+            assert!((pen(b) - pen(exact)).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_subpixel_positions() {
+        let (font_storage, font_id) = test_font_storage();
+        let config = TextLayoutConfig {
+            subpixel_positions: 4,
+            ..Default::default()
+        };
+        let mut layout = TextData::builder(font_id, 15.3, ())
+            .push_str("Hamburgefonts")
+            .build()
+            .layout(&config, &font_storage);
+
+        let check = |layout: &TextLayout<()>| {
+            for glyph in &layout.lines[0].glyphs {
+                let offset = glyph.glyph_id.subpixel_offset().unwrap();
+                let [x, _] = glyph.raster_origin();
+                assert_eq!(x, x.round());
+                assert!((x + offset - glyph.x).abs() <= 0.125 + 1e-4);
+            }
+        };
+        check(&layout);
+        let before = layout.lines[0].glyphs[0].glyph_id;
+        layout.translate(0.25, 0.0);
+        check(&layout);
+        assert_ne!(layout.lines[0].glyphs[0].glyph_id, before);
+
+        // A shifted variant keeps the coverage of the glyph, one column wider.
+        let glyph_id = GlyphId::new(
+            font_id,
+            font_storage.font(font_id).unwrap().lookup_glyph_index('H'),
+            15.3,
+        );
+        let (metrics, bitmap) = crate::renderer::rasterize_glyph(&glyph_id, &font_storage).unwrap();
+        let (shifted_metrics, shifted) =
+            crate::renderer::rasterize_glyph(&glyph_id.with_subpixel_offset(0.5), &font_storage)
+                .unwrap();
+        assert_eq!(shifted_metrics.width, metrics.width + 1);
+        let sum = |pixels: &[u8]| pixels.iter().map(|&p| u32::from(p)).sum::<u32>();
+        assert!(sum(&shifted).abs_diff(sum(&bitmap)) <= shifted.len() as u32);
+    }
+
+    #[test]
+    fn test_missing_glyphs_use_fallback_chain() {
+        let (mut font_storage, fallback) = test_font_storage();
+        let primary = font_storage.load_font_binary(SECOND_TEST_FONT).unwrap()[0];
+        let ch = '█';
+        assert!(!font_storage.font(primary).unwrap().has_glyph(ch));
+
+        font_storage.set_fallback_chain(primary, vec![fallback]);
+        let layout = TextData::builder(primary, 16.0, ())
+            .push_str(&format!("a{ch}"))
+            .build()
+            .layout(&TextLayoutConfig::default(), &font_storage);
+        let glyphs = &layout.lines[0].glyphs;
+        assert_eq!(glyphs[0].glyph_id.font_id(), primary);
+        assert_eq!(glyphs[1].glyph_id.font_id(), fallback);
+        assert!(glyphs[1].glyph_id.hex_box_char().is_none());
+
+        // Without a chain the character falls back to a hex box in the primary face.
+        font_storage.set_fallback_chain(primary, Vec::new());
+        font_storage.set_default_fallback(Vec::new());
+        let layout = TextData::builder(primary, 16.0, ())
+            .push_str(&ch.to_string())
+            .build()
+            .layout(&TextLayoutConfig::default(), &font_storage);
+        assert_eq!(layout.lines[0].glyphs[0].glyph_id.font_id(), primary);
+    }
+
+    #[test]
+    fn test_paragraph_spacing() {
+        let (font_storage, font_id) = test_font_storage();
+        let text = "one\ntwo\nthree";
+        let plain = TextData::builder(font_id, 16.0, ())
+            .push_str(text)
+            .build()
+            .layout(&TextLayoutConfig::default(), &font_storage);
+
+        let config = TextLayoutConfig {
+            paragraph_spacing: 10.0,
+            ..Default::default()
+        };
+        let spaced = TextData::builder(font_id, 16.0, ())
+            .push_str(text)
+            .build()
+            .layout(&config, &font_storage);
+        assert_eq!(heights(&spaced), heights(&plain));
+        assert_eq!(spaced.lines[1].top, plain.lines[1].top + 10.0);
+        assert_eq!(spaced.total_height, plain.total_height + 20.0);
+
+        // The override applies to the paragraph holding the span, even mid-paragraph.
+        let overridden = TextData::builder(font_id, 16.0, ())
+            .push_str("one\nt")
+            .paragraph_spacing(Some(30.0))
+            .push_str("w")
+            .paragraph_spacing(None)
+            .push_str("o\nthree")
+            .build()
+            .layout(&config, &font_storage);
+        assert_eq!(overridden.lines[1].top, spaced.lines[1].top);
+        assert_eq!(overridden.lines[2].top, spaced.lines[2].top + 20.0);
+        assert_eq!(overridden.total_height, spaced.total_height + 20.0);
     }
 }
@@ -0,0 +1,250 @@
+//! Keeping text inside the safe area of a display.
+//!
+//! Consoles and TVs may crop the edges of the picture, and platform certification
+//! requirements ask that important text stays within a title-safe rectangle. A
+//! [`SafeArea`] describes that rectangle, narrows layout configs to it, moves laid
+//! out text inside it, and reports glyphs that still fall outside.
+
+use euclid::{Box2D, Point2D, UnknownUnit};
+
+use super::layout::{TextLayout, TextLayoutConfig};
+use crate::font_storage::FontStorage;
+
+/// Distances in pixels from each edge of the screen to the safe area.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SafeAreaInsets {
+    /// Inset from the left edge.
+    pub left: f32,
+    /// Inset from the top edge.
+    pub top: f32,
+    /// Inset from the right edge.
+    pub right: f32,
+    /// Inset from the bottom edge.
+    pub bottom: f32,
+}
+
+impl SafeAreaInsets {
+    /// The same inset on every edge.
+    pub fn uniform(inset: f32) -> Self {
+        Self {
+            left: inset,
+            top: inset,
+            right: inset,
+            bottom: inset,
+        }
+    }
+}
+
+/// The part of a screen in which text must be drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SafeArea {
+    /// Screen size in pixels, `[width, height]`.
+    pub screen_size: [f32; 2],
+    /// Insets from the screen edges.
+    pub insets: SafeAreaInsets,
+}
+
+impl SafeArea {
+    /// Creates a safe area from a screen size and insets.
+    pub fn new(screen_size: [f32; 2], insets: SafeAreaInsets) -> Self {
+        Self {
+            screen_size,
+            insets,
+        }
+    }
+
+    /// The conventional title-safe area: the central 90% of each screen dimension.
+    pub fn title_safe(screen_size: [f32; 2]) -> Self {
+        let [width, height] = screen_size;
+        Self::new(
+            screen_size,
+            SafeAreaInsets {
+                left: width * 0.05,
+                top: height * 0.05,
+                right: width * 0.05,
+                bottom: height * 0.05,
+            },
+        )
+    }
+
+    /// Returns the safe rectangle in screen coordinates.
+    pub fn rect(&self) -> Box2D<f32, UnknownUnit> {
+        let [width, height] = self.screen_size;
+        Box2D::new(
+            Point2D::new(self.insets.left, self.insets.top),
+            Point2D::new(width - self.insets.right, height - self.insets.bottom),
+        )
+    }
+
+    /// Returns `config` with its maximum size reduced so that a layout box placed at
+    /// `origin` ends inside the safe area.
+    pub fn constrain(&self, config: &TextLayoutConfig, origin: [f32; 2]) -> TextLayoutConfig {
+        let rect = self.rect();
+        let available_width = (rect.max.x - origin[0].max(rect.min.x)).max(0.0);
+        let available_height = (rect.max.y - origin[1].max(rect.min.y)).max(0.0);
+        TextLayoutConfig {
+            max_width: Some(
+                config
+                    .max_width
+                    .map_or(available_width, |w| w.min(available_width)),
+            ),
+            max_height: Some(
+                config
+                    .max_height
+                    .map_or(available_height, |h| h.min(available_height)),
+            ),
+            ..config.clone()
+        }
+    }
+
+    /// Moves `layout` to `origin` and then by the smallest offset that brings its glyphs
+    /// inside the safe area.
+    ///
+    /// Text larger than the safe area is aligned to its top-left corner. Returns the
+    /// total offset applied to the layout.
+    pub fn place<T>(
+        &self,
+        layout: &mut TextLayout<T>,
+        origin: [f32; 2],
+        font_storage: &FontStorage,
+    ) -> [f32; 2] {
+        layout.translate(origin[0], origin[1]);
+        let Some(bounds) = glyph_bounds(layout, font_storage) else {
+            return origin;
+        };
+
+        let rect = self.rect();
+        let clamp = |min: f32, max: f32, safe_min: f32, safe_max: f32| {
+            if min < safe_min {
+                safe_min - min
+            } else if max > safe_max {
+                (safe_max - max).max(safe_min - min)
+            } else {
+                0.0
+            }
+        };
+        let dx = clamp(bounds.min.x, bounds.max.x, rect.min.x, rect.max.x);
+        let dy = clamp(bounds.min.y, bounds.max.y, rect.min.y, rect.max.y);
+        layout.translate(dx, dy);
+        [origin[0] + dx, origin[1] + dy]
+    }
+
+    /// Returns `true` if every glyph of `layout` lies inside the safe area.
+    pub fn contains<T>(&self, layout: &TextLayout<T>, font_storage: &FontStorage) -> bool {
+        self.violations(layout, font_storage).is_empty()
+    }
+
+    /// Returns the `(line, glyph)` indices of the glyphs that extend outside the safe area.
+    pub fn violations<T>(
+        &self,
+        layout: &TextLayout<T>,
+        font_storage: &FontStorage,
+    ) -> Vec<(usize, usize)> {
+        let rect = self.rect();
+        let mut violations = Vec::new();
+        for (line_index, line) in layout.lines.iter().enumerate() {
+            for (glyph_index, glyph) in line.glyphs.iter().enumerate() {
+                let Some(metrics) = crate::renderer::glyph_metrics(&glyph.glyph_id, font_storage)
+                else {
+                    continue;
+                };
+                if metrics.width == 0 || metrics.height == 0 {
+                    continue;
+                }
+                let inside = glyph.x >= rect.min.x
+                    && glyph.y >= rect.min.y
+                    && glyph.x + metrics.width as f32 <= rect.max.x
+                    && glyph.y + metrics.height as f32 <= rect.max.y;
+                if !inside {
+                    violations.push((line_index, glyph_index));
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// Bounding box of the visible glyph bitmaps of a layout.
+fn glyph_bounds<T>(
+    layout: &TextLayout<T>,
+    font_storage: &FontStorage,
+) -> Option<Box2D<f32, UnknownUnit>> {
+    let mut bounds: Option<Box2D<f32, UnknownUnit>> = None;
+    for glyph in layout.lines.iter().flat_map(|line| &line.glyphs) {
+        let Some(metrics) = crate::renderer::glyph_metrics(&glyph.glyph_id, font_storage) else {
+            continue;
+        };
+        if metrics.width == 0 || metrics.height == 0 {
+            continue;
+        }
+        let rect = Box2D::new(
+            Point2D::new(glyph.x, glyph.y),
+            Point2D::new(
+                glyph.x + metrics.width as f32,
+                glyph.y + metrics.height as f32,
+            ),
+        );
+        bounds = Some(bounds.map_or(rect, |bounds| bounds.union(&rect)));
+    }
+    bounds
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_font_storage;
+    use crate::text::TextData;
+
+    #[test]
+    fn test_constrain() {
+        let area = SafeArea::title_safe([1000.0, 500.0]);
+        assert_eq!(
+            area.rect(),
+            Box2D::new(Point2D::new(50.0, 25.0), Point2D::new(950.0, 475.0))
+        );
+
+        let config = area.constrain(&TextLayoutConfig::default(), [0.0, 100.0]);
+        assert_eq!(config.max_width, Some(900.0));
+        assert_eq!(config.max_height, Some(375.0));
+
+        let narrow = TextLayoutConfig {
+            max_width: Some(300.0),
+            ..Default::default()
+        };
+        assert_eq!(area.constrain(&narrow, [800.0, 0.0]).max_width, Some(150.0));
+        assert_eq!(area.constrain(&narrow, [100.0, 0.0]).max_width, Some(300.0));
+    }
+
+    #[test]
+    fn test_place_and_violations() {
+        let (font_storage, font_id) = test_font_storage();
+        let area = SafeArea::new([400.0, 200.0], SafeAreaInsets::uniform(20.0));
+        let layout = TextData::builder(font_id, 16.0, ())
+            .push_str("Score: 12345")
+            .build()
+            .layout(&TextLayoutConfig::default(), &font_storage);
+
+        // Drawn at the origin, the text overlaps the inset.
+        assert!(!area.contains(&layout, &font_storage));
+        assert!(!area.violations(&layout, &font_storage).is_empty());
+
+        // Requested past the bottom-right corner, the text is pulled back inside.
+        let mut placed = layout.clone();
+        let offset = area.place(&mut placed, [390.0, 190.0], &font_storage);
+        assert!(area.contains(&placed, &font_storage));
+        let bounds = glyph_bounds(&placed, &font_storage).unwrap();
+        assert!((bounds.max.x - 380.0).abs() < 1e-3 && (bounds.max.y - 180.0).abs() < 1e-3);
+        assert_eq!(
+            placed.lines[0].glyphs[0].x,
+            layout.lines[0].glyphs[0].x + offset[0]
+        );
+
+        // Text that already fits stays where it was put.
+        let mut inside = layout.clone();
+        assert_eq!(
+            area.place(&mut inside, [100.0, 100.0], &font_storage),
+            [100.0, 100.0]
+        );
+    }
+}
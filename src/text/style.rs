@@ -0,0 +1,242 @@
+//! Built-in run style for applications that do not need their own user data.
+//!
+//! [`TextStyle`] covers the attributes most text needs: color, weight, slant,
+//! decoration and background. Using it as the user data of a [`TextData`] lets the crate resolve
+//! those attributes itself: [`TextData::layout_styled`] picks the bold and italic
+//! faces, the renderers read the color, and
+//! [`crate::renderer::text_decorations`] produces underline and strikethrough
+//! rectangles. [`crate::renderer::run_backgrounds`] does the same for
+//! [`TextStyle::background`].
+
+use super::{
+    data::{TextData, TextElement},
+    layout::{TextLayout, TextLayoutConfig},
+};
+use crate::font_storage::FontStorage;
+
+/// Color, weight, slant, decoration and background of a text run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TextStyle {
+    /// Text color, `[r, g, b, a]`.
+    pub color: [f32; 4],
+    /// Weight of the face used for the run.
+    #[cfg_attr(feature = "serde", serde(with = "super::fontdb_serde::weight"))]
+    pub weight: fontdb::Weight,
+    /// Whether an italic (or oblique) face is used for the run.
+    pub italic: bool,
+    /// Lines drawn with the run.
+    pub decoration: TextDecoration,
+    /// Color of a rectangle filled behind the run, `[r, g, b, a]`.
+    pub background: Option<[f32; 4]>,
+}
+
+impl Default for TextStyle {
+    /// Opaque white text in a regular, upright face without decoration or background.
+    fn default() -> Self {
+        Self {
+            color: [1.0, 1.0, 1.0, 1.0],
+            weight: fontdb::Weight::NORMAL,
+            italic: false,
+            decoration: TextDecoration::default(),
+            background: None,
+        }
+    }
+}
+
+impl TextStyle {
+    /// Creates a regular, undecorated style with the given color.
+    pub fn new(color: [f32; 4]) -> Self {
+        Self {
+            color,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the style with a bold weight.
+    pub fn bold(self) -> Self {
+        Self {
+            weight: fontdb::Weight::BOLD,
+            ..self
+        }
+    }
+
+    /// Returns the style with an italic slant.
+    pub fn italic(self) -> Self {
+        Self {
+            italic: true,
+            ..self
+        }
+    }
+
+    /// Returns the style with an underline.
+    pub fn underline(mut self) -> Self {
+        self.decoration.underline = true;
+        self
+    }
+
+    /// Returns the style with a strikethrough.
+    pub fn strikethrough(mut self) -> Self {
+        self.decoration.strikethrough = true;
+        self
+    }
+
+    /// Returns the style with a background filled in `color`.
+    pub fn background(self, color: [f32; 4]) -> Self {
+        Self {
+            background: Some(color),
+            ..self
+        }
+    }
+
+    fn font_style(&self) -> fontdb::Style {
+        if self.italic {
+            fontdb::Style::Italic
+        } else {
+            fontdb::Style::Normal
+        }
+    }
+}
+
+impl From<TextStyle> for [f32; 4] {
+    fn from(style: TextStyle) -> Self {
+        style.color
+    }
+}
+
+/// Lines drawn along a text run, in the run's color.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TextDecoration {
+    /// A line just below the baseline.
+    pub underline: bool,
+    /// A line through the middle of lowercase letters.
+    pub strikethrough: bool,
+}
+
+impl TextDecoration {
+    /// Returns `true` if no line is drawn.
+    pub fn is_none(&self) -> bool {
+        !self.underline && !self.strikethrough
+    }
+}
+
+impl TextData<TextStyle> {
+    /// Lays out the text after resolving each run's weight and slant to a face.
+    ///
+    /// The `font_id` of a run names the family; the face used is the one of that
+    /// family that best matches [`TextStyle::weight`] and [`TextStyle::italic`].
+    /// When the family has no faces to choose from, the run keeps its `font_id`.
+    /// A run's [`TextElement::font_query`] takes the style's weight and slant instead.
+    pub fn layout_styled(
+        &self,
+        config: &TextLayoutConfig,
+        font_storage: &FontStorage,
+    ) -> TextLayout<TextStyle> {
+        self.resolve_faces(font_storage)
+            .layout(config, font_storage)
+    }
+
+    /// Returns a copy of the data with each run's `font_id` replaced by its styled face.
+    ///
+    /// Runs with a font query are not resolved here; their query is updated instead.
+    pub fn resolve_faces(&self, font_storage: &FontStorage) -> TextData<TextStyle> {
+        let texts = self
+            .texts
+            .iter()
+            .map(|text| TextElement {
+                font_id: font_storage
+                    .styled_face(
+                        text.font_id,
+                        text.user_data.weight,
+                        text.user_data.font_style(),
+                    )
+                    .unwrap_or(text.font_id),
+                font_query: text.font_query.clone().map(|query| {
+                    query
+                        .weight(text.user_data.weight)
+                        .style(text.user_data.font_style())
+                }),
+                font_size: text.font_size,
+                line_height: text.line_height,
+                paragraph_spacing: text.paragraph_spacing,
+                content: text.content.clone(),
+                user_data: text.user_data,
+            })
+            .collect::<Vec<_>>();
+        TextData::from(texts)
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Registers a face without font data; enough for face matching.
+    fn push_face(
+        font_storage: &mut FontStorage,
+        family: &str,
+        weight: u16,
+        style: fontdb::Style,
+    ) -> fontdb::ID {
+        let before: Vec<_> = font_storage.faces().map(|face| face.id).collect();
+        font_storage.push_face_info(fontdb::FaceInfo {
+            id: fontdb::ID::dummy(),
+            source: fontdb::Source::Binary(Arc::new(Vec::<u8>::new())),
+            index: 0,
+            families: vec![(family.to_string(), fontdb::Language::English_UnitedStates)],
+            post_script_name: format!("{family}-{weight}"),
+            style,
+            weight: fontdb::Weight(weight),
+            stretch: fontdb::Stretch::Normal,
+            monospaced: false,
+        });
+        font_storage
+            .faces()
+            .map(|face| face.id)
+            .find(|id| !before.contains(id))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_faces() {
+        let mut font_storage = FontStorage::new();
+        let regular = push_face(&mut font_storage, "Styled", 400, fontdb::Style::Normal);
+        let bold = push_face(&mut font_storage, "Styled", 700, fontdb::Style::Normal);
+        let italic = push_face(&mut font_storage, "Styled", 400, fontdb::Style::Italic);
+        let other = push_face(&mut font_storage, "Other", 700, fontdb::Style::Normal);
+
+        let data = TextData::builder(regular, 16.0, TextStyle::default())
+            .push_str("a")
+            .user_data(TextStyle::default().bold())
+            .push_str("b")
+            .user_data(TextStyle::default().italic())
+            .push_str("c")
+            .font(bold)
+            .user_data(TextStyle::default())
+            .push_str("d")
+            .build();
+
+        let resolved = data.resolve_faces(&font_storage);
+        let ids: Vec<_> = resolved.texts.iter().map(|text| text.font_id).collect();
+        assert_eq!(ids, [regular, bold, italic, regular]);
+        assert!(!ids.contains(&other));
+        assert_eq!(resolved.texts[1].content, "b");
+    }
+
+    #[test]
+    fn test_color_conversion() {
+        let style = TextStyle::new([0.1, 0.2, 0.3, 0.4]).bold().underline();
+        assert_eq!(<[f32; 4]>::from(style), [0.1, 0.2, 0.3, 0.4]);
+        assert_eq!(style.weight, fontdb::Weight::BOLD);
+        assert!(style.decoration.underline && !style.decoration.strikethrough);
+        assert!(TextStyle::default().decoration.is_none());
+        assert_eq!(TextStyle::default().background, None);
+        assert_eq!(style.background([0.5; 4]).background, Some([0.5; 4]));
+    }
+}
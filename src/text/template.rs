@@ -0,0 +1,193 @@
+//! Named placeholders substituted into text data at layout time.
+//!
+//! A template is ordinary [`TextData`] whose content contains `{name}`
+//! placeholders. Translators can restyle and reorder the runs of a message while
+//! the program only supplies the values; each value takes the style of the run
+//! holding its placeholder. `{{` and `}}` stand for literal braces.
+
+use std::collections::HashMap;
+
+use super::{
+    data::{TextData, TextElement},
+    layout::{TextLayout, TextLayoutConfig},
+};
+use crate::font_storage::FontStorage;
+
+/// Values for the placeholders of a template.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TemplateArgs {
+    values: HashMap<String, String>,
+}
+
+impl TemplateArgs {
+    /// Creates an empty set of values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the arguments with `name` set to `value`.
+    pub fn with(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    /// Sets the value of placeholder `name`.
+    pub fn set(&mut self, name: impl Into<String>, value: impl ToString) {
+        self.values.insert(name.into(), value.to_string());
+    }
+
+    /// Returns the value of placeholder `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+}
+
+/// A piece of template content.
+enum Piece<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str),
+}
+
+/// Splits `content` into literal text and placeholders.
+///
+/// A `{` without a closing `}` is kept as literal text.
+fn pieces(content: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = content;
+    while let Some(index) = rest.find(['{', '}']) {
+        pieces.push(Piece::Literal(&rest[..index]));
+        let brace = &rest[index..index + 1];
+        let after = &rest[index + 1..];
+        if after.starts_with(brace) {
+            pieces.push(Piece::Literal(brace));
+            rest = &after[1..];
+        } else if brace == "{"
+            && let Some(end) = after.find(['{', '}'])
+            && after[end..].starts_with('}')
+        {
+            pieces.push(Piece::Placeholder(&after[..end]));
+            rest = &after[end + 1..];
+        } else {
+            pieces.push(Piece::Literal(brace));
+            rest = after;
+        }
+    }
+    pieces.push(Piece::Literal(rest));
+    pieces
+}
+
+impl<T: Clone> TextData<T> {
+    /// Returns the names of the placeholders in the data, in order of appearance.
+    pub fn placeholders(&self) -> Vec<&str> {
+        self.texts
+            .iter()
+            .flat_map(|text| pieces(&text.content))
+            .filter_map(|piece| match piece {
+                Piece::Placeholder(name) => Some(name),
+                Piece::Literal(_) => None,
+            })
+            .collect()
+    }
+
+    /// Returns a copy of the data with its placeholders replaced by `args`.
+    ///
+    /// Each value keeps the style of the run its placeholder is in, so the runs
+    /// are preserved one to one. Placeholders without a value are left as written
+    /// so that missing arguments stay visible.
+    pub fn substitute(&self, args: &TemplateArgs) -> TextData<T> {
+        let texts = self
+            .texts
+            .iter()
+            .map(|text| {
+                let mut content = String::with_capacity(text.content.len());
+                for piece in pieces(&text.content) {
+                    match piece {
+                        Piece::Literal(literal) => content.push_str(literal),
+                        Piece::Placeholder(name) => match args.get(name) {
+                            Some(value) => content.push_str(value),
+                            None => {
+                                content.push('{');
+                                content.push_str(name);
+                                content.push('}');
+                            }
+                        },
+                    }
+                }
+                TextElement {
+                    content,
+                    ..text.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+        TextData::from(texts)
+    }
+
+    /// Substitutes `args` and lays out the result, see [`Self::substitute`].
+    ///
+    /// Character and byte indices of the layout refer to the substituted text.
+    pub fn layout_template(
+        &self,
+        args: &TemplateArgs,
+        config: &TextLayoutConfig,
+        font_storage: &FontStorage,
+    ) -> TextLayout<T> {
+        self.substitute(args).layout(config, font_storage)
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::make_id;
+
+    #[test]
+    fn test_substitute_keeps_runs() {
+        // A translation that moves the name into an emphasized run of its own.
+        let template = TextData::builder(make_id(0), 16.0, 0u8)
+            .push_str("{count} new messages from ")
+            .user_data(1)
+            .push_str("{name}")
+            .user_data(0)
+            .push_str(" {{inbox}} {missing} {unclosed")
+            .build();
+        assert_eq!(template.placeholders(), ["count", "name", "missing"]);
+
+        let args = TemplateArgs::new().with("count", 3).with("name", "Ana");
+        let text = template.substitute(&args);
+        let runs: Vec<_> = text
+            .texts
+            .iter()
+            .map(|text| (text.content.as_str(), text.user_data))
+            .collect();
+        assert_eq!(
+            runs,
+            [
+                ("3 new messages from ", 0),
+                ("Ana", 1),
+                (" {inbox} {missing} {unclosed", 0),
+            ]
+        );
+        // The template itself is untouched and can be reused.
+        assert_eq!(template.texts[1].content, "{name}");
+    }
+
+    #[test]
+    fn test_pieces_edge_cases() {
+        let text = |content: &str| {
+            TextData::builder(make_id(0), 16.0, ())
+                .push_str(content)
+                .build()
+                .substitute(&TemplateArgs::new().with("a", "x"))
+                .texts[0]
+                .content
+                .clone()
+        };
+        assert_eq!(text("{a}{a}"), "xx");
+        assert_eq!(text("{{a}}"), "{a}");
+        assert_eq!(text("}{a}{"), "}x{");
+        assert_eq!(text("{{{a}}}"), "{x}");
+        assert_eq!(text("{b{a}"), "{bx");
+        assert_eq!(text("é{a}é"), "éxé");
+    }
+}
@@ -0,0 +1,341 @@
+//! Fonts delivered over the network, like web fonts.
+//!
+//! Suzuri does not make HTTP requests itself. Fetching over HTTPS needs a TLS
+//! stack, and which one, with which certificate roots and proxy settings, is
+//! the application's choice, so the `web-fonts` feature adds no network
+//! dependencies. The application passes a fetch callback built on whatever it
+//! already uses (e.g. `ureq` natively, or bytes handed over from the browser's
+//! `fetch` on wasm).
+//! [`WebFontLoader`](crate::web_fonts::WebFontLoader) caches the fetched bytes in
+//! memory and, optionally, in a directory so that later runs start without
+//! touching the network. On wasm, where there is no file system or threads,
+//! fonts are loaded with
+//! [`WebFontLoader::load`](crate::web_fonts::WebFontLoader::load) from bytes
+//! passed to [`WebFontLoader::insert`](crate::web_fonts::WebFontLoader::insert).
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use parking_lot::Mutex;
+
+use crate::{Error, FontStorage, error::FontOrigin};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{FontSystem, font_system::FontLoad};
+
+/// Fetches the bytes behind a URL.
+type Fetch = dyn Fn(&str) -> Result<Vec<u8>, std::io::Error> + Send + Sync;
+
+/// Fetches fonts by URL, caches their bytes and registers them in a [`FontStorage`].
+///
+/// Share a loader behind an `Arc` to load several fonts in the background with
+/// [`FontSystem::load_web_font`].
+pub struct WebFontLoader {
+    fetch: Box<Fetch>,
+    cache_dir: Option<PathBuf>,
+    memory: Mutex<HashMap<String, Arc<Vec<u8>>, fxhash::FxBuildHasher>>,
+}
+
+impl WebFontLoader {
+    /// Creates a loader that fetches with `fetch`, which receives the URL.
+    pub fn new(
+        fetch: impl Fn(&str) -> Result<Vec<u8>, std::io::Error> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            fetch: Box::new(fetch),
+            cache_dir: None,
+            memory: Mutex::new(HashMap::with_hasher(fxhash::FxBuildHasher::default())),
+        }
+    }
+
+    /// Creates a loader that never fetches; fonts must be supplied with [`Self::insert`].
+    ///
+    /// Useful on wasm, where fetching is asynchronous and done by the application.
+    pub fn offline() -> Self {
+        Self::new(|url| {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{url} is not cached"),
+            ))
+        })
+    }
+
+    /// Returns the loader with fetched fonts also stored in, and read from, `dir`.
+    ///
+    /// The directory is created when the first font is stored. Each file holds
+    /// the URL it was fetched from, which is checked when it is read, and is
+    /// written under a temporary name first, so an interrupted write leaves no
+    /// truncated font behind.
+    pub fn with_cache_dir(mut self, dir: PathBuf) -> Self {
+        self.cache_dir = Some(dir);
+        self
+    }
+
+    /// Stores bytes obtained elsewhere for `url`, replacing any cached ones.
+    pub fn insert(&self, url: &str, bytes: Vec<u8>) {
+        let bytes = Arc::new(bytes);
+        self.store_on_disk(url, &bytes);
+        self.memory.lock().insert(url.to_string(), bytes);
+    }
+
+    /// Returns the cached bytes for `url` without fetching.
+    pub fn cached(&self, url: &str) -> Option<Arc<Vec<u8>>> {
+        if let Some(bytes) = self.memory.lock().get(url) {
+            return Some(Arc::clone(bytes));
+        }
+        let bytes = Arc::new(self.read_from_disk(url)?);
+        self.memory
+            .lock()
+            .insert(url.to_string(), Arc::clone(&bytes));
+        Some(bytes)
+    }
+
+    /// Returns the bytes for `url`, fetching them if they are not cached.
+    pub fn bytes(&self, url: &str) -> Result<Arc<Vec<u8>>, std::io::Error> {
+        match self.cached(url) {
+            Some(bytes) => Ok(bytes),
+            None => self.fetch_and_cache(url),
+        }
+    }
+
+    /// Drops the cached bytes for `url`, from memory and from the cache directory.
+    pub fn remove(&self, url: &str) {
+        self.memory.lock().remove(url);
+        if let Some(path) = self.cache_path(url)
+            && let Err(e) = std::fs::remove_file(&path)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            log::warn!("Failed to remove cached web font {}: {}", path.display(), e);
+        }
+    }
+
+    fn fetch_and_cache(&self, url: &str) -> Result<Arc<Vec<u8>>, std::io::Error> {
+        let bytes = Arc::new((self.fetch)(url)?);
+        self.store_on_disk(url, &bytes);
+        self.memory
+            .lock()
+            .insert(url.to_string(), Arc::clone(&bytes));
+        Ok(bytes)
+    }
+
+    /// Fetches `url` if needed and registers its faces, returning their IDs.
+    ///
+    /// Each call registers the faces again; keep the IDs to reuse a loaded font.
+    /// Cached bytes that are not a font, e.g. a file damaged on disk, are
+    /// dropped and fetched again.
+    pub fn load(
+        &self,
+        url: &str,
+        font_storage: &mut FontStorage,
+    ) -> Result<Vec<fontdb::ID>, Error> {
+        let mut font_db = fontdb::Database::new();
+        self.load_into(url, &mut font_db)?;
+        Ok(font_storage.register_faces(font_db.faces().cloned()))
+    }
+
+    /// Parses the font behind `url` into `font_db`.
+    ///
+    /// Cached bytes that fail to parse are dropped and fetched again once; when
+    /// that fetch fails, the parse error is returned.
+    fn load_into(&self, url: &str, font_db: &mut fontdb::Database) -> Result<(), Error> {
+        let origin = || FontOrigin::Url(url.to_string());
+        let parse = |font_db: &mut fontdb::Database, bytes| {
+            crate::font_storage::load_source(font_db, fontdb::Source::Binary(bytes))
+                .map(drop)
+                .map_err(|e| e.with_origin(origin()))
+        };
+
+        let Some(bytes) = self.cached(url) else {
+            let bytes = self
+                .fetch_and_cache(url)
+                .map_err(|e| Error::from(e).with_origin(origin()))?;
+            return parse(font_db, bytes).inspect_err(|_| self.remove(url));
+        };
+        parse(font_db, bytes).or_else(|error| {
+            log::warn!("Dropping the cached web font {url}: {error}");
+            self.remove(url);
+            let bytes = self.fetch_and_cache(url).map_err(|_| error)?;
+            parse(font_db, bytes).inspect_err(|_| self.remove(url))
+        })
+    }
+
+    fn cache_path(&self, url: &str) -> Option<PathBuf> {
+        let dir = self.cache_dir.as_ref()?;
+        Some(dir.join(format!("{:016x}.font", fxhash::hash64(url))))
+    }
+
+    /// Reads the bytes stored for `url` in the cache directory.
+    ///
+    /// File names are hashes of the URLs, so a file is only used if the URL it
+    /// starts with is `url`.
+    fn read_from_disk(&self, url: &str) -> Option<Vec<u8>> {
+        let mut data = std::fs::read(self.cache_path(url)?).ok()?;
+        let url_len = u64::from_le_bytes(data.get(..8)?.try_into().ok()?);
+        let header_len = usize::try_from(url_len).ok()?.checked_add(8)?;
+        if data.get(8..header_len)? != url.as_bytes() {
+            return None;
+        }
+        data.drain(..header_len);
+        Some(data)
+    }
+
+    fn store_on_disk(&self, url: &str, bytes: &[u8]) {
+        let Some(path) = self.cache_path(url) else {
+            return;
+        };
+        if let Err(e) = write_cache_file(&path, url, bytes) {
+            log::warn!("Failed to cache web font at {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Writes `bytes` fetched from `url` to `path` through a temporary file, so
+/// readers never see a partly written file.
+fn write_cache_file(path: &Path, url: &str, bytes: &[u8]) -> Result<(), std::io::Error> {
+    static NEXT_TEMP_ID: AtomicU64 = AtomicU64::new(0);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temp_id = NEXT_TEMP_ID.fetch_add(1, Ordering::Relaxed);
+    let temp_path = path.with_extension(format!("{}-{temp_id}.tmp", std::process::id()));
+    let result = std::fs::File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(&(url.len() as u64).to_le_bytes())?;
+            file.write_all(url.as_bytes())?;
+            file.write_all(bytes)?;
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// web fonts
+#[cfg(not(target_arch = "wasm32"))]
+impl FontSystem {
+    /// Fetches `url` with `loader` on a background thread and registers its faces.
+    ///
+    /// See [`FontSystem::load_fonts_dir_async`] for how the faces become available.
+    pub fn load_web_font(&self, loader: &Arc<WebFontLoader>, url: &str) -> FontLoad {
+        let loader = Arc::clone(loader);
+        let url = url.to_string();
+        self.spawn_font_load(move |font_db| loader.load_into(&url, font_db))
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::test_utils::TEST_FONT;
+
+    #[test]
+    fn test_fetches_once_and_caches_on_disk() {
+        let cache_dir =
+            std::env::temp_dir().join(format!("suzuri-web-fonts-{}", std::process::id()));
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let loader = {
+            let fetches = Arc::clone(&fetches);
+            Arc::new(
+                WebFontLoader::new(move |url| {
+                    assert_eq!(url, "https://example.com/font.ttf");
+                    fetches.fetch_add(1, Ordering::SeqCst);
+                    Ok(TEST_FONT.to_vec())
+                })
+                .with_cache_dir(cache_dir.clone()),
+            )
+        };
+
+        let font_system = FontSystem::new();
+        let ids = font_system
+            .load_web_font(&loader, "https://example.com/font.ttf")
+            .wait()
+            .unwrap();
+        assert!(!ids.is_empty() && font_system.face(ids[0]).is_some());
+        let mut font_storage = FontStorage::new();
+        assert_eq!(
+            loader
+                .load("https://example.com/font.ttf", &mut font_storage)
+                .unwrap()
+                .len(),
+            ids.len()
+        );
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        // A new loader finds the bytes on disk.
+        let offline = WebFontLoader::offline().with_cache_dir(cache_dir.clone());
+        assert!(offline.cached("https://example.com/font.ttf").is_some());
+        assert!(offline.cached("https://example.com/other.ttf").is_none());
+        std::fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_damaged_cache_files_are_fetched_again() {
+        let url = "https://example.com/font.ttf";
+        let cache_dir =
+            std::env::temp_dir().join(format!("suzuri-web-fonts-damaged-{}", std::process::id()));
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let loader = || {
+            let fetches = Arc::clone(&fetches);
+            WebFontLoader::new(move |_| {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                Ok(TEST_FONT.to_vec())
+            })
+            .with_cache_dir(cache_dir.clone())
+        };
+        let mut font_storage = FontStorage::new();
+        loader().load(url, &mut font_storage).unwrap();
+        let cache_path = loader().cache_path(url).unwrap();
+        let files = || std::fs::read_dir(&cache_dir).unwrap().count();
+        assert_eq!(files(), 1);
+
+        // A truncated file is dropped and fetched again, which repairs it.
+        let data = std::fs::read(&cache_path).unwrap();
+        std::fs::write(&cache_path, &data[..data.len() / 2]).unwrap();
+        loader().load(url, &mut font_storage).unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+        assert_eq!(std::fs::read(&cache_path).unwrap(), data);
+
+        // A file stored for another URL with the same name is not used.
+        write_cache_file(&cache_path, "https://example.com/other.ttf", b"other").unwrap();
+        assert!(loader().cached(url).is_none());
+        loader().load(url, &mut font_storage).unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 3);
+        assert_eq!(files(), 1);
+        std::fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_and_missing_fonts() {
+        let loader = WebFontLoader::offline();
+        let mut font_storage = FontStorage::new();
+        let error = loader.load("missing.ttf", &mut font_storage).unwrap_err();
+        assert!(matches!(
+            &error,
+            Error::Io { source, .. } if source.kind() == std::io::ErrorKind::NotFound
+        ));
+        assert_eq!(
+            error.origin(),
+            Some(&FontOrigin::Url("missing.ttf".to_string()))
+        );
+        assert_eq!(error.path(), None);
+
+        loader.insert("garbage.ttf", b"not a font".to_vec());
+        let error = loader.load("garbage.ttf", &mut font_storage).unwrap_err();
+        assert!(matches!(error, Error::UnsupportedFormat { .. }));
+        assert!(font_storage.is_empty());
+        // Bytes that are not a font are not kept.
+        assert!(loader.cached("garbage.ttf").is_none());
+    }
+}